@@ -0,0 +1,81 @@
+use std::hint::black_box;
+
+use criterion::{criterion_group, criterion_main, Criterion};
+
+use track_rename::{formatting, genre};
+
+/// Raw artist and title pairs that exercise most of the formatting rules.
+const NAMES: &[(&str, &str)] = &[
+    (
+        "Seige",
+        "Holla Remix (featuring Busta Rhymes, Little Brother, Kurupt, Crooked I, and Willie B)",
+    ),
+    ("Janet Jackson", "If (Kaytranada Edition (Live Set Version)"),
+    ("Various Artists", "Moodymann - Shades Of Jae [Extended Mix]"),
+    ("DJ Tool Ft. MC Someone", "Big Room Anthem - Acapella-Intro (128 8a)"),
+    ("Azn Danza", "Azn Danza - Myles Club Edit.mp3"),
+    ("THE ARTIST", "A VERY LOUD TITLE (CLEAN INTRO-OUTRO)"),
+    ("www.example.com", "Song {Dirty-Intro}!!!"),
+];
+
+/// Already formatted names that should pass through unchanged.
+const FORMATTED_NAMES: &[(&str, &str)] = &[
+    ("Janet Jackson", "If (Kaytranada Edition) (Live Set Version)"),
+    ("Fanu feat. Ane Brun", "Taivaita ja Tarinoita"),
+    ("Moodymann", "Shades Of Jae (Extended Mix)"),
+];
+
+const GENRES: &[&str] = &[
+    "house",
+    "Tech House",
+    "Original Samples / Hip-Hop",
+    "r&b / soul",
+    "90's Rap",
+    "Deep House, Electronic",
+];
+
+fn bench_artist_and_title(c: &mut Criterion) {
+    c.bench_function("format artist and title", |b| {
+        b.iter(|| {
+            for (artist, title) in NAMES {
+                black_box(formatting::format_tags_for_artist_and_title(
+                    black_box(artist),
+                    black_box(title),
+                ));
+            }
+        });
+    });
+    c.bench_function("format unchanged artist and title", |b| {
+        b.iter(|| {
+            for (artist, title) in FORMATTED_NAMES {
+                black_box(formatting::format_tags_for_artist_and_title(
+                    black_box(artist),
+                    black_box(title),
+                ));
+            }
+        });
+    });
+}
+
+fn bench_filename(c: &mut Criterion) {
+    c.bench_function("format filename", |b| {
+        b.iter(|| {
+            for (artist, title) in NAMES {
+                black_box(formatting::format_filename(black_box(artist), black_box(title)));
+            }
+        });
+    });
+}
+
+fn bench_genre(c: &mut Criterion) {
+    c.bench_function("format genre", |b| {
+        b.iter(|| {
+            for genre in GENRES {
+                black_box(genre::format_genre(black_box(genre)));
+            }
+        });
+    });
+}
+
+criterion_group!(benches, bench_artist_and_title, bench_filename, bench_genre);
+criterion_main!(benches);
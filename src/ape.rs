@@ -0,0 +1,112 @@
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::ops::Range;
+use std::path::Path;
+
+/// `APEv2` header and footer size in bytes.
+const APE_FOOTER_SIZE: u64 = 32;
+/// `ID3v1` tag size in bytes, which can follow the `APEv2` tag.
+const ID3V1_SIZE: u64 = 128;
+const APE_PREAMBLE: &[u8; 8] = b"APETAGEX";
+/// Footer flag for a tag that also has a header before the items.
+const FLAG_HAS_HEADER: u32 = 1 << 31;
+
+/// Find the byte range of an `APEv2` tag at the end of the file.
+///
+/// The tag is either the last data in the file or right before an `ID3v1` tag.
+pub fn find_tag(file: &mut File) -> io::Result<Option<Range<u64>>> {
+    let len = file.metadata()?.len();
+    let mut end = len;
+    if len >= ID3V1_SIZE && read_at::<3>(file, len - ID3V1_SIZE)? == *b"TAG" {
+        end -= ID3V1_SIZE;
+    }
+    if end < APE_FOOTER_SIZE {
+        return Ok(None);
+    }
+    let footer = read_at::<32>(file, end - APE_FOOTER_SIZE)?;
+    if footer[..8] != APE_PREAMBLE[..] {
+        return Ok(None);
+    }
+    // Tag size includes the items and footer but not the header
+    let size = u64::from(u32::from_le_bytes([footer[12], footer[13], footer[14], footer[15]]));
+    let flags = u32::from_le_bytes([footer[20], footer[21], footer[22], footer[23]]);
+    let size = if flags & FLAG_HAS_HEADER == 0 {
+        size
+    } else {
+        size + APE_FOOTER_SIZE
+    };
+    if size < APE_FOOTER_SIZE || size > end {
+        return Ok(None);
+    }
+    Ok(Some(end - size..end))
+}
+
+/// Check if the file at the given path ends with an `APEv2` tag.
+pub fn has_tag(path: &Path) -> io::Result<bool> {
+    Ok(find_tag(&mut File::open(path)?)?.is_some())
+}
+
+/// Remove an `APEv2` tag from the end of the file, keeping a following `ID3v1` tag.
+///
+/// Returns true if a tag was found and removed.
+pub fn remove_tag(path: &Path) -> io::Result<bool> {
+    let mut file = OpenOptions::new().read(true).write(true).open(path)?;
+    let Some(range) = find_tag(&mut file)? else {
+        return Ok(false);
+    };
+    let mut trailing = Vec::new();
+    file.seek(SeekFrom::Start(range.end))?;
+    file.read_to_end(&mut trailing)?;
+    file.set_len(range.start)?;
+    file.seek(SeekFrom::Start(range.start))?;
+    file.write_all(&trailing)?;
+    Ok(true)
+}
+
+fn read_at<const N: usize>(file: &mut File, offset: u64) -> io::Result<[u8; N]> {
+    let mut buffer = [0; N];
+    file.seek(SeekFrom::Start(offset))?;
+    file.read_exact(&mut buffer)?;
+    Ok(buffer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ape_tag(items: &[u8]) -> Vec<u8> {
+        let size = u32::try_from(items.len() + 32).unwrap();
+        let block = |flags: u32| {
+            let mut block = APE_PREAMBLE.to_vec();
+            block.extend_from_slice(&2000_u32.to_le_bytes());
+            block.extend_from_slice(&size.to_le_bytes());
+            block.extend_from_slice(&1_u32.to_le_bytes());
+            block.extend_from_slice(&flags.to_le_bytes());
+            block.extend_from_slice(&[0; 8]);
+            block
+        };
+        [block(FLAG_HAS_HEADER | 1 << 29), items.to_vec(), block(FLAG_HAS_HEADER)].concat()
+    }
+
+    #[test]
+    fn test_remove_tag() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let path = dir.path().join("track.mp3");
+        let mut id3v1 = vec![0_u8; 128];
+        id3v1[..3].copy_from_slice(b"TAG");
+
+        std::fs::write(
+            &path,
+            [b"audio data".as_slice(), &ape_tag(b"item data"), &id3v1].concat(),
+        )
+        .expect("Failed to write test file");
+        assert!(has_tag(&path).expect("Failed to read"));
+        assert!(remove_tag(&path).expect("Failed to remove tag"));
+        assert_eq!(
+            std::fs::read(&path).expect("Failed to read"),
+            [b"audio data".as_slice(), &id3v1].concat()
+        );
+        assert!(!has_tag(&path).expect("Failed to read"));
+        assert!(!remove_tag(&path).expect("Failed to check tag"));
+    }
+}
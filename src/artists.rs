@@ -0,0 +1,313 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::sync::LazyLock;
+
+use regex::Regex;
+
+use crate::utils;
+
+/// Match delimiters between individual artist names like `feat.`, `vs.`, and commas.
+///
+/// `&`, `and`, and `x` are not delimiters since they are part of many artist names like `Above & Beyond`.
+static RE_ARTIST_DELIMITER: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)\s+(?:feat\.|ft\.|vs\.?)\s+|\s*,\s*").unwrap());
+
+/// Match a title that is only a mix or version name like `Extended Mix` or `Radio Edit`
+static RE_MIX_NAME: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)^[\w\s'.&]*\b(?:mix|remix|edit|version|dub|rework|bootleg|vip|instrumental|acapella)\)?$").unwrap()
+});
+
+/// Artist names seen in the library.
+///
+/// Used for choosing where to split a "Various Artists" title that contains more than one ` - `.
+#[derive(Debug, Clone, Default)]
+pub struct KnownArtists {
+    keys: HashSet<String>,
+}
+
+/// One possible way to split a title into artist and title.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SplitCandidate {
+    pub artist: String,
+    pub title: String,
+    /// Higher is more likely to be correct
+    pub score: i32,
+}
+
+/// Different spellings of the same artist name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArtistCluster {
+    /// Most used spelling
+    pub canonical: String,
+    /// All spellings with their track counts, most used first
+    pub spellings: Vec<(String, usize)>,
+}
+
+impl ArtistCluster {
+    /// Total number of tracks using any of the spellings.
+    #[must_use]
+    pub fn track_count(&self) -> usize {
+        self.spellings.iter().map(|(_, count)| count).sum()
+    }
+
+    /// Spellings other than the canonical one.
+    pub fn variants(&self) -> impl Iterator<Item = &str> {
+        self.spellings
+            .iter()
+            .map(|(spelling, _)| spelling.as_str())
+            .filter(|spelling| *spelling != self.canonical)
+    }
+}
+
+impl fmt::Display for ArtistCluster {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let spellings = self
+            .spellings
+            .iter()
+            .map(|(spelling, count)| format!("{spelling} ({count})"))
+            .collect::<Vec<_>>()
+            .join(", ");
+        write!(f, "{}: {spellings}", self.canonical)
+    }
+}
+
+impl KnownArtists {
+    /// Add the individual artist names from an artist tag.
+    pub fn insert(&mut self, artist: &str) {
+        if artist.trim().eq_ignore_ascii_case("Various Artists") {
+            return;
+        }
+        for name in split_artist_names(artist) {
+            let key = normalized_key(name);
+            if !key.is_empty() {
+                self.keys.insert(key);
+            }
+        }
+    }
+
+    /// Return true if the artist name has been seen.
+    #[must_use]
+    pub fn contains(&self, name: &str) -> bool {
+        self.keys.contains(&normalized_key(name))
+    }
+
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+}
+
+impl SplitCandidate {
+    fn new(artist: &str, title: &str, known: &KnownArtists) -> Self {
+        // Every known artist name counts for the split and every unknown one against it
+        let mut score: i32 = split_artist_names(artist)
+            .iter()
+            .map(|name| if known.contains(name) { 2 } else { -1 })
+            .sum();
+        if RE_MIX_NAME.is_match(title) {
+            score -= 3;
+        }
+        Self {
+            artist: artist.to_string(),
+            title: title.to_string(),
+            score,
+        }
+    }
+}
+
+/// Every way to split a "Various Artists" title at ` - ` into artist and title, best first.
+///
+/// Splits are scored by how many of the artist names are already known,
+/// and penalized when the remaining title is only a mix name.
+/// Candidates with the same score keep their order so the first ` - ` wins a tie.
+#[must_use]
+pub fn split_candidates(title: &str, known: &KnownArtists) -> Vec<SplitCandidate> {
+    let mut candidates: Vec<SplitCandidate> = title
+        .match_indices(" - ")
+        .map(|(index, separator)| (title[..index].trim(), title[index + separator.len()..].trim()))
+        .filter(|(artist, title)| !artist.is_empty() && !title.is_empty())
+        .map(|(artist, title)| SplitCandidate::new(artist, title, known))
+        .collect();
+    candidates.sort_by_key(|candidate| std::cmp::Reverse(candidate.score));
+    candidates
+}
+
+/// Return true if the best candidates are equally likely.
+#[must_use]
+pub fn is_ambiguous(candidates: &[SplitCandidate]) -> bool {
+    matches!(candidates, [first, second, ..] if first.score == second.score)
+}
+
+/// Split an artist tag into the individual artist names.
+#[must_use]
+pub fn split_artist_names(artist: &str) -> Vec<&str> {
+    RE_ARTIST_DELIMITER
+        .split(artist)
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .collect()
+}
+
+/// Comparison key that ignores case, whitespace, and punctuation,
+/// so `Dj Snake`, `DJ Snake`, and `DJSnake` are considered the same artist.
+#[must_use]
+pub fn normalized_key(name: &str) -> String {
+    utils::normalize_str(name)
+        .chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// Group artist names from the given artist tags by their normalized key.
+///
+/// Only groups with more than one spelling are returned, sorted by track count.
+#[must_use]
+pub fn cluster_artists<'a>(artists: impl IntoIterator<Item = &'a str>) -> Vec<ArtistCluster> {
+    let mut groups: HashMap<String, HashMap<&str, usize>> = HashMap::new();
+    for artist in artists {
+        for name in split_artist_names(artist) {
+            let key = normalized_key(name);
+            if !key.is_empty() {
+                *groups.entry(key).or_default().entry(name).or_insert(0) += 1;
+            }
+        }
+    }
+
+    let mut clusters: Vec<ArtistCluster> = groups
+        .into_values()
+        .filter(|spellings| spellings.len() > 1)
+        .map(|spellings| {
+            let mut spellings: Vec<(String, usize)> = spellings
+                .into_iter()
+                .map(|(spelling, count)| (spelling.to_string(), count))
+                .collect();
+            // Prefer the most used spelling, then the one with more separating whitespace
+            spellings.sort_unstable_by(|a, b| {
+                b.1.cmp(&a.1)
+                    .then_with(|| b.0.chars().count().cmp(&a.0.chars().count()))
+                    .then_with(|| a.0.cmp(&b.0))
+            });
+            ArtistCluster {
+                canonical: spellings[0].0.clone(),
+                spellings,
+            }
+        })
+        .collect();
+
+    clusters.sort_unstable_by(|a, b| {
+        b.track_count()
+            .cmp(&a.track_count())
+            .then_with(|| a.canonical.cmp(&b.canonical))
+    });
+    clusters
+}
+
+/// Replace an individual artist name in an artist tag, keeping the delimiters and other names.
+#[must_use]
+pub fn replace_artist_name(artist: &str, name: &str, replacement: &str) -> String {
+    let mut result = String::with_capacity(artist.len());
+    let mut start = 0;
+    for delimiter in RE_ARTIST_DELIMITER.find_iter(artist) {
+        push_name(&mut result, &artist[start..delimiter.start()], name, replacement);
+        result.push_str(delimiter.as_str());
+        start = delimiter.end();
+    }
+    push_name(&mut result, &artist[start..], name, replacement);
+    result
+}
+
+fn push_name(result: &mut String, segment: &str, name: &str, replacement: &str) {
+    if segment.trim() == name {
+        let leading = &segment[..segment.len() - segment.trim_start().len()];
+        let trailing = &segment[segment.trim_end().len()..];
+        result.push_str(leading);
+        result.push_str(replacement);
+        result.push_str(trailing);
+    } else {
+        result.push_str(segment);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_artist_names() {
+        assert_eq!(
+            split_artist_names("DJ Snake feat. Lil Jon, Someone vs. Other ft. Last"),
+            vec!["DJ Snake", "Lil Jon", "Someone", "Other", "Last"]
+        );
+        assert_eq!(split_artist_names("Xander"), vec!["Xander"]);
+        assert_eq!(
+            split_artist_names("Above & Beyond feat. Zoë Johnston"),
+            vec!["Above & Beyond", "Zoë Johnston"]
+        );
+        assert_eq!(
+            split_artist_names("Chase and Status x Someone"),
+            vec!["Chase and Status x Someone"]
+        );
+    }
+
+    #[test]
+    fn test_cluster_artists() {
+        let artists = [
+            "DJ Snake",
+            "DJ Snake feat. Lil Jon",
+            "Dj Snake",
+            "DJSnake, Someone",
+            "Someone",
+            "Other",
+        ];
+        let clusters = cluster_artists(artists);
+        assert_eq!(clusters.len(), 1);
+        assert_eq!(clusters[0].canonical, "DJ Snake");
+        assert_eq!(clusters[0].track_count(), 4);
+        assert_eq!(clusters[0].variants().collect::<Vec<_>>(), vec!["Dj Snake", "DJSnake"]);
+    }
+
+    #[test]
+    fn test_split_candidates() {
+        let mut known = KnownArtists::default();
+        known.insert("Various Artists");
+        assert!(known.is_empty());
+        known.insert("Jay-Z feat. Alicia Keys");
+        known.insert("Crosby, Stills - Nash");
+        assert!(known.contains("jay z"));
+
+        let title = "Crosby, Stills - Nash - Song - Extended Mix";
+        let candidates = split_candidates(title, &known);
+        assert_eq!(candidates.len(), 3);
+        assert_eq!(candidates[0].artist, "Crosby, Stills - Nash");
+        assert_eq!(candidates[0].title, "Song - Extended Mix");
+        assert!(!is_ambiguous(&candidates));
+
+        let candidates = split_candidates("Artist - Song - Other", &KnownArtists::default());
+        assert_eq!(candidates[0].artist, "Artist");
+        assert!(is_ambiguous(&candidates));
+
+        let candidates = split_candidates("Artist - Song - Radio Edit", &KnownArtists::default());
+        assert_eq!(candidates[0].artist, "Artist");
+        assert!(!is_ambiguous(&candidates));
+        assert!(split_candidates("Song", &known).is_empty());
+    }
+
+    #[test]
+    fn test_replace_artist_name() {
+        assert_eq!(
+            replace_artist_name("DJSnake, Someone feat. DJSnakes", "DJSnake", "DJ Snake"),
+            "DJ Snake, Someone feat. DJSnakes"
+        );
+        assert_eq!(
+            replace_artist_name("Above & Beyond", "Above", "Below"),
+            "Above & Beyond"
+        );
+        assert_eq!(replace_artist_name("Dj Snake", "Dj Snake", "DJ Snake"), "DJ Snake");
+    }
+}
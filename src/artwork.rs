@@ -0,0 +1,222 @@
+use std::fmt;
+use std::io::{Read, Write};
+use std::process::{Command, Stdio};
+
+use clap::ValueEnum;
+use id3::frame::Picture;
+use id3::{Tag, TagLike};
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Result, TrackRenameError};
+
+/// JPEG quality used when not given.
+pub const DEFAULT_QUALITY: u8 = 85;
+
+const PNG_SIGNATURE: &[u8; 8] = b"\x89PNG\r\n\x1a\n";
+
+/// Image format for re-encoded artwork.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum ArtFormat {
+    #[default]
+    Jpeg,
+    Png,
+}
+
+/// Limits for embedded artwork.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ArtworkOptions {
+    /// Maximum width and height in pixels
+    pub max_size: u32,
+    pub format: ArtFormat,
+    /// JPEG quality from 1 to 100
+    pub quality: u8,
+}
+
+/// Total artwork size before and after re-encoding.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ArtworkResult {
+    pub resized: usize,
+    pub original_bytes: usize,
+    pub new_bytes: usize,
+}
+
+impl ArtFormat {
+    #[must_use]
+    pub const fn mime_type(self) -> &'static str {
+        match self {
+            Self::Jpeg => "image/jpeg",
+            Self::Png => "image/png",
+        }
+    }
+
+    const fn codec(self) -> &'static str {
+        match self {
+            Self::Jpeg => "mjpeg",
+            Self::Png => "png",
+        }
+    }
+}
+
+impl fmt::Display for ArtFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Jpeg => write!(f, "jpeg"),
+            Self::Png => write!(f, "png"),
+        }
+    }
+}
+
+impl fmt::Display for ArtworkOptions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}px {} {}", self.max_size, self.format, self.quality)
+    }
+}
+
+impl ArtworkResult {
+    /// Bytes saved by re-encoding.
+    #[must_use]
+    pub const fn saved_bytes(&self) -> usize {
+        self.original_bytes.saturating_sub(self.new_bytes)
+    }
+}
+
+/// Read the width and height from PNG or JPEG image data.
+#[must_use]
+pub fn image_dimensions(data: &[u8]) -> Option<(u32, u32)> {
+    if data.starts_with(PNG_SIGNATURE) {
+        // IHDR chunk is always first and starts with the width and height
+        let width = u32::from_be_bytes(data.get(16..20)?.try_into().ok()?);
+        let height = u32::from_be_bytes(data.get(20..24)?.try_into().ok()?);
+        return Some((width, height));
+    }
+    if data.starts_with(&[0xFF, 0xD8]) {
+        return jpeg_dimensions(data);
+    }
+    None
+}
+
+/// Check if the tag has pictures larger than the maximum size.
+#[must_use]
+pub fn has_oversized_pictures(tag: &Tag, max_size: u32) -> bool {
+    tag.pictures().any(|picture| is_oversized(picture, max_size))
+}
+
+/// Re-encode pictures larger than the maximum size with ffmpeg.
+///
+/// Other pictures are kept as they are,
+/// and a re-encoded picture is only used if it is smaller than the original.
+pub fn resize_pictures(tag: &mut Tag, options: ArtworkOptions) -> Result<ArtworkResult> {
+    let mut result = ArtworkResult::default();
+    if !has_oversized_pictures(tag, options.max_size) {
+        return Ok(result);
+    }
+    let mut pictures: Vec<Picture> = tag.pictures().cloned().collect();
+    for picture in &mut pictures {
+        if !is_oversized(picture, options.max_size) {
+            continue;
+        }
+        let data = resize_image(&picture.data, options)?;
+        if data.len() < picture.data.len() {
+            result.resized += 1;
+            result.original_bytes += picture.data.len();
+            result.new_bytes += data.len();
+            picture.data = data;
+            picture.mime_type = options.format.mime_type().to_string();
+        }
+    }
+    tag.remove_all_pictures();
+    for picture in pictures {
+        tag.add_frame(picture);
+    }
+    Ok(result)
+}
+
+/// Scale the image to fit the maximum size and encode it in the given format using ffmpeg.
+pub fn resize_image(data: &[u8], options: ArtworkOptions) -> Result<Vec<u8>> {
+    let scale = format!(
+        "scale='min(iw,{0})':'min(ih,{0})':force_original_aspect_ratio=decrease",
+        options.max_size
+    );
+    // Map quality 1-100 to the ffmpeg JPEG scale where 2 is the best and 31 the worst
+    let qscale = (31 - u32::from(options.quality.clamp(1, 100)) * 29 / 100).to_string();
+    let mut child = Command::new("ffmpeg")
+        .args(["-v", "error", "-i", "pipe:0", "-vf", scale.as_str(), "-frames:v", "1"])
+        .args(["-c:v", options.format.codec(), "-q:v", qscale.as_str()])
+        .args(["-f", "image2pipe", "pipe:1"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| TrackRenameError::io("Failed to run ffmpeg", e))?;
+
+    let mut stdin = child.stdin.take().expect("Failed to open ffmpeg stdin");
+    let mut stdout = child.stdout.take().expect("Failed to open ffmpeg stdout");
+    // Write and read concurrently so a full output pipe can not block the input
+    let output = std::thread::scope(|scope| {
+        let writer = scope.spawn(move || stdin.write_all(data));
+        let mut output = Vec::new();
+        let read = stdout.read_to_end(&mut output);
+        // ffmpeg can close the input early after reading enough data
+        let _ = writer.join();
+        read.map(|_| output)
+    })
+    .map_err(|e| TrackRenameError::io("Failed to read ffmpeg output", e))?;
+
+    let result = child
+        .wait_with_output()
+        .map_err(|e| TrackRenameError::io("Failed to run ffmpeg", e))?;
+    if !result.status.success() || output.is_empty() {
+        return Err(TrackRenameError::Convert(
+            String::from_utf8_lossy(&result.stderr).trim().to_string(),
+        ));
+    }
+    Ok(output)
+}
+
+fn is_oversized(picture: &Picture, max_size: u32) -> bool {
+    image_dimensions(&picture.data).is_some_and(|(width, height)| width.max(height) > max_size)
+}
+
+/// Find the frame size from the first start of frame marker.
+fn jpeg_dimensions(data: &[u8]) -> Option<(u32, u32)> {
+    let mut index = 2;
+    while index + 9 < data.len() {
+        if data[index] != 0xFF {
+            return None;
+        }
+        let marker = data[index + 1];
+        let length = usize::from(u16::from_be_bytes([data[index + 2], data[index + 3]]));
+        // SOF markers except DHT (C4), JPG (C8), and DAC (CC)
+        if (0xC0..=0xCF).contains(&marker) && !matches!(marker, 0xC4 | 0xC8 | 0xCC) {
+            let height = u16::from_be_bytes([data[index + 5], data[index + 6]]);
+            let width = u16::from_be_bytes([data[index + 7], data[index + 8]]);
+            return Some((u32::from(width), u32::from(height)));
+        }
+        index += 2 + length;
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_image_dimensions() {
+        let mut png = PNG_SIGNATURE.to_vec();
+        png.extend_from_slice(&[0, 0, 0, 13]);
+        png.extend_from_slice(b"IHDR");
+        png.extend_from_slice(&3000_u32.to_be_bytes());
+        png.extend_from_slice(&2000_u32.to_be_bytes());
+        assert_eq!(image_dimensions(&png), Some((3000, 2000)));
+
+        let jpeg = [
+            0xFF, 0xD8, // SOI
+            0xFF, 0xE0, 0x00, 0x04, 0x00, 0x00, // APP0
+            0xFF, 0xC0, 0x00, 0x11, 0x08, 0x03, 0xE8, 0x05, 0xDC, 0x03, // SOF0 1500x1000
+        ];
+        assert_eq!(image_dimensions(&jpeg), Some((1500, 1000)));
+        assert_eq!(image_dimensions(b"not an image"), None);
+    }
+}
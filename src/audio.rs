@@ -0,0 +1,552 @@
+use std::fmt;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+use std::process::Command;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Result, TrackRenameError};
+use crate::mpeg::{find_frame_sync, id3_tag_size, FrameHeader, ID3_HEADER_SIZE};
+use crate::{riff, utils};
+
+/// How many bytes to search for the first MP3 frame after the tag.
+const MP3_SYNC_SEARCH_BYTES: usize = 64 * 1024;
+
+/// Audio stream properties for a track.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct AudioProperties {
+    pub sample_rate: u32,
+    /// Bits per sample, not defined for lossy formats like MP3
+    pub bit_depth: Option<u16>,
+    pub channels: u16,
+}
+
+/// Target sample rate and bit depth for resampling, given as `SAMPLE_RATE:BIT_DEPTH`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ResampleTarget {
+    pub sample_rate: u32,
+    pub bit_depth: u16,
+}
+
+/// Parsed AIFF `COMM` chunk.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AiffInfo {
+    pub channels: u16,
+    pub sample_frames: u32,
+    pub bit_depth: u16,
+    pub sample_rate: f64,
+    /// Compression type and name for AIFC files
+    pub compression: Option<(String, String)>,
+}
+
+/// Parsed WAV `fmt ` chunk and audio data size.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WavInfo {
+    pub channels: u16,
+    pub sample_rate: u32,
+    pub bit_depth: u16,
+    /// Bytes per sample frame for all channels
+    pub block_align: u16,
+    pub data_size: u32,
+}
+
+impl AudioProperties {
+    /// Read audio properties from an AIFF file header.
+    pub fn from_aiff(path: &Path) -> Result<Self> {
+        let info = AiffInfo::from_path(path)?;
+        Ok(Self {
+            sample_rate: info.sample_rate.round() as u32,
+            bit_depth: Some(info.bit_depth),
+            channels: info.channels,
+        })
+    }
+
+    /// Read audio properties from the WAV `fmt ` chunk.
+    pub fn from_wav(path: &Path) -> Result<Self> {
+        let info = WavInfo::from_path(path)?;
+        Ok(Self {
+            sample_rate: info.sample_rate,
+            bit_depth: Some(info.bit_depth),
+            channels: info.channels,
+        })
+    }
+
+    /// Read audio properties from the first MP3 frame header.
+    pub fn from_mp3(path: &Path) -> Result<Self> {
+        let read_error = |e| TrackRenameError::io(format!("Failed to read file: {}", path.display()), e);
+        let mut file = File::open(path).map_err(read_error)?;
+        let mut header = [0u8; ID3_HEADER_SIZE];
+        file.read_exact(&mut header).map_err(read_error)?;
+        let audio_start = id3_tag_size(&header).map_or(0, |size| ID3_HEADER_SIZE + size);
+        file.seek(SeekFrom::Start(audio_start as u64)).map_err(read_error)?;
+
+        let mut data = Vec::with_capacity(MP3_SYNC_SEARCH_BYTES);
+        file.take(MP3_SYNC_SEARCH_BYTES as u64)
+            .read_to_end(&mut data)
+            .map_err(read_error)?;
+        let frame = find_frame_sync(&data, 0)
+            .and_then(|offset| FrameHeader::parse(&data[offset..]))
+            .ok_or_else(|| TrackRenameError::InvalidAudio(format!("No MPEG frame header found: {}", path.display())))?;
+
+        Ok(Self {
+            sample_rate: frame.sample_rate,
+            bit_depth: None,
+            channels: if frame.mono { 1 } else { 2 },
+        })
+    }
+
+    /// Read audio properties using ffprobe.
+    pub fn from_ffprobe(path: &Path) -> Result<Self> {
+        let output = Command::new("ffprobe")
+            .args([
+                "-v",
+                "error",
+                "-select_streams",
+                "a:0",
+                "-show_entries",
+                "stream=sample_rate,bits_per_raw_sample,bits_per_sample,channels",
+                "-of",
+                "default=noprint_wrappers=1",
+                utils::path_to_string(path).as_str(),
+            ])
+            .output()
+            .map_err(|e| TrackRenameError::io("Failed to run ffprobe", e))?;
+
+        if !output.status.success() {
+            return Err(TrackRenameError::Probe(
+                String::from_utf8_lossy(&output.stderr).trim().to_string(),
+            ));
+        }
+
+        let mut properties = Self::default();
+        for line in String::from_utf8_lossy(&output.stdout).lines() {
+            let Some((key, value)) = line.split_once('=') else {
+                continue;
+            };
+            let value = value.trim();
+            match key {
+                "sample_rate" => properties.sample_rate = value.parse().unwrap_or_default(),
+                "channels" => properties.channels = value.parse().unwrap_or_default(),
+                "bits_per_raw_sample" | "bits_per_sample" => {
+                    if let Ok(bits) = value.parse::<u16>() {
+                        if bits > 0 {
+                            properties.bit_depth = Some(bits);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        if properties.sample_rate == 0 {
+            return Err(TrackRenameError::Probe(format!(
+                "No sample rate returned for: {}",
+                path.display()
+            )));
+        }
+        Ok(properties)
+    }
+
+    /// PCM bit depth that keeps the full resolution of the audio, 16, 24, or 32 bits.
+    ///
+    /// Lossy formats without a bit depth are decoded to 16 bits.
+    #[must_use]
+    pub const fn pcm_bit_depth(&self) -> u16 {
+        match self.bit_depth {
+            Some(bits) => pcm_bit_depth(bits),
+            None => 16,
+        }
+    }
+
+    /// Returns true if sample rate or bit depth is higher than the target.
+    #[must_use]
+    pub fn exceeds(&self, target: &ResampleTarget) -> bool {
+        self.sample_rate > target.sample_rate || self.bit_depth.is_some_and(|bits| bits > target.bit_depth)
+    }
+
+    /// Target limited to these properties so the sample rate or bit depth is never increased.
+    #[must_use]
+    pub fn limit_target(&self, target: &ResampleTarget) -> ResampleTarget {
+        ResampleTarget {
+            sample_rate: self.sample_rate.min(target.sample_rate),
+            bit_depth: self
+                .bit_depth
+                .map_or(target.bit_depth, |bits| pcm_bit_depth(bits).min(target.bit_depth)),
+        }
+    }
+}
+
+impl AiffInfo {
+    /// Read the `COMM` chunk from an AIFF file.
+    pub fn from_path(path: &Path) -> Result<Self> {
+        let read_error = |e| TrackRenameError::io(format!("Failed to read file: {}", path.display()), e);
+        let mut file = File::open(path).map_err(read_error)?;
+        let mut header = [0u8; 12];
+        file.read_exact(&mut header).map_err(read_error)?;
+        if &header[0..4] != b"FORM" || !matches!(&header[8..12], b"AIFF" | b"AIFC") {
+            return Err(TrackRenameError::InvalidAudio(format!(
+                "Not an AIFF file: {}",
+                path.display()
+            )));
+        }
+
+        loop {
+            let mut chunk_header = [0u8; 8];
+            file.read_exact(&mut chunk_header)
+                .map_err(|e| TrackRenameError::io("AIFF file does not contain a COMM chunk", e))?;
+            let size = u32::from_be_bytes([chunk_header[4], chunk_header[5], chunk_header[6], chunk_header[7]]);
+            if &chunk_header[0..4] == b"COMM" {
+                // The size comes from the file, so only read what is actually there
+                let mut data = Vec::new();
+                file.take(u64::from(size)).read_to_end(&mut data).map_err(read_error)?;
+                if data.len() < size as usize {
+                    return Err(read_error(std::io::ErrorKind::UnexpectedEof.into()));
+                }
+                return Self::parse_comm(&data);
+            }
+            // Chunks are padded to an even length
+            let skip = i64::from(size) + i64::from(size % 2);
+            file.seek(SeekFrom::Current(skip)).map_err(read_error)?;
+        }
+    }
+
+    /// AIFC files have a compression type even when the audio is uncompressed.
+    #[must_use]
+    pub const fn is_aifc(&self) -> bool {
+        self.compression.is_some()
+    }
+
+    /// Check if the audio is not big-endian integer PCM.
+    #[must_use]
+    pub fn is_compressed(&self) -> bool {
+        self.compression
+            .as_ref()
+            .is_some_and(|(compression_type, _)| !matches!(compression_type.as_str(), "NONE" | "twos"))
+    }
+
+    /// Duration in seconds.
+    #[must_use]
+    pub fn duration(&self) -> f64 {
+        if self.sample_rate > 0.0 {
+            f64::from(self.sample_frames) / self.sample_rate
+        } else {
+            0.0
+        }
+    }
+
+    /// Parse `COMM` chunk data.
+    ///
+    /// | Offset | Length | Type                  | Description
+    /// | ------ | ------ | --------------------- | -----------
+    /// |   `00` |   `02` | `int16_t`             | Number of channels
+    /// |   `02` |   `04` | `uint32_t`            | Number of sample frames
+    /// |   `06` |   `02` | `int16_t`             | Bits per sample
+    /// |   `08` |   `0a` | 80-bit extended float | Sample rate
+    /// |   `12` |   `04` | `ID`                  | AIFC compression type
+    /// |   `16` |        | Pascal string         | AIFC compression name
+    ///
+    pub fn parse_comm(data: &[u8]) -> Result<Self> {
+        if data.len() < 18 {
+            return Err(TrackRenameError::InvalidAudio("COMM chunk is too short".to_string()));
+        }
+        let channels = u16::from_be_bytes([data[0], data[1]]);
+        let sample_frames = u32::from_be_bytes([data[2], data[3], data[4], data[5]]);
+        let bit_depth = u16::from_be_bytes([data[6], data[7]]);
+        let sample_rate = parse_extended_float(&data[8..18]);
+        let compression = data.get(18..22).map(|compression_type| {
+            let name = data
+                .get(22)
+                .and_then(|&len| data.get(23..23 + usize::from(len)))
+                .map(|name| String::from_utf8_lossy(name).trim().to_string())
+                .unwrap_or_default();
+            (String::from_utf8_lossy(compression_type).to_string(), name)
+        });
+        Ok(Self {
+            channels,
+            sample_frames,
+            bit_depth,
+            sample_rate,
+            compression,
+        })
+    }
+}
+
+impl WavInfo {
+    /// Read the `fmt ` and `data` chunks from a WAV file.
+    pub fn from_path(path: &Path) -> Result<Self> {
+        let read_error = |e| TrackRenameError::io(format!("Failed to read file: {}", path.display()), e);
+        let mut file = File::open(path).map_err(read_error)?;
+        let chunks = riff::read_chunks(&mut file).map_err(read_error)?;
+        let fmt = chunks.iter().find(|chunk| &chunk.id == b"fmt ").ok_or_else(|| {
+            TrackRenameError::InvalidAudio(format!("WAV file does not contain a fmt chunk: {}", path.display()))
+        })?;
+        let mut info = Self::parse_fmt(&riff::read_chunk_data(&mut file, fmt).map_err(read_error)?)?;
+        info.data_size = chunks
+            .iter()
+            .find(|chunk| &chunk.id == b"data")
+            .map_or(0, |chunk| chunk.size);
+        Ok(info)
+    }
+
+    /// Duration in seconds.
+    #[must_use]
+    pub fn duration(&self) -> f64 {
+        if self.sample_rate > 0 && self.block_align > 0 {
+            f64::from(self.data_size / u32::from(self.block_align)) / f64::from(self.sample_rate)
+        } else {
+            0.0
+        }
+    }
+
+    /// Parse `fmt ` chunk data.
+    ///
+    /// | Offset | Length | Type       | Description
+    /// | ------ | ------ | ---------- | -----------
+    /// |   `00` |   `02` | `uint16_t` | Format tag
+    /// |   `02` |   `02` | `uint16_t` | Number of channels
+    /// |   `04` |   `04` | `uint32_t` | Sample rate
+    /// |   `08` |   `04` | `uint32_t` | Bytes per second
+    /// |   `0c` |   `02` | `uint16_t` | Block align
+    /// |   `0e` |   `02` | `uint16_t` | Bits per sample
+    ///
+    pub fn parse_fmt(data: &[u8]) -> Result<Self> {
+        if data.len() < 16 {
+            return Err(TrackRenameError::InvalidAudio("fmt chunk is too short".to_string()));
+        }
+        Ok(Self {
+            channels: u16::from_le_bytes([data[2], data[3]]),
+            sample_rate: u32::from_le_bytes([data[4], data[5], data[6], data[7]]),
+            block_align: u16::from_le_bytes([data[12], data[13]]),
+            bit_depth: u16::from_le_bytes([data[14], data[15]]),
+            data_size: 0,
+        })
+    }
+}
+
+impl FromStr for ResampleTarget {
+    type Err = TrackRenameError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (sample_rate, bit_depth) = s.split_once(':').ok_or_else(|| {
+            TrackRenameError::InvalidArgument(format!(
+                "Resample target should be in format SAMPLE_RATE:BIT_DEPTH: {s}"
+            ))
+        })?;
+        let sample_rate: u32 = sample_rate
+            .trim()
+            .parse()
+            .map_err(|e| TrackRenameError::InvalidArgument(format!("Invalid sample rate: {e}")))?;
+        let bit_depth: u16 = bit_depth
+            .trim()
+            .parse()
+            .map_err(|e| TrackRenameError::InvalidArgument(format!("Invalid bit depth: {e}")))?;
+        if !matches!(bit_depth, 16 | 24 | 32) {
+            return Err(TrackRenameError::InvalidArgument(format!(
+                "Unsupported bit depth: {bit_depth}"
+            )));
+        }
+        Ok(Self { sample_rate, bit_depth })
+    }
+}
+
+impl ResampleTarget {
+    /// ffmpeg PCM codec name for big-endian AIFF samples.
+    #[must_use]
+    pub const fn aiff_codec(&self) -> &'static str {
+        aiff_codec(self.bit_depth)
+    }
+}
+
+/// Smallest PCM bit depth of 16, 24, or 32 bits that fits the given bit depth.
+#[must_use]
+pub const fn pcm_bit_depth(bits: u16) -> u16 {
+    match bits {
+        0..=16 => 16,
+        17..=24 => 24,
+        _ => 32,
+    }
+}
+
+/// ffmpeg PCM codec name for big-endian AIFF samples with the given bit depth.
+#[must_use]
+pub const fn aiff_codec(bit_depth: u16) -> &'static str {
+    match pcm_bit_depth(bit_depth) {
+        16 => "pcm_s16be",
+        24 => "pcm_s24be",
+        _ => "pcm_s32be",
+    }
+}
+
+/// ffmpeg PCM codec name for little-endian WAV samples with the given bit depth.
+#[must_use]
+pub const fn wav_codec(bit_depth: u16) -> &'static str {
+    match pcm_bit_depth(bit_depth) {
+        16 => "pcm_s16le",
+        24 => "pcm_s24le",
+        _ => "pcm_s32le",
+    }
+}
+
+impl fmt::Display for AudioProperties {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sample_rate = f64::from(self.sample_rate) / 1000.0;
+        match self.bit_depth {
+            Some(bits) => write!(f, "{sample_rate} kHz / {bits} bit"),
+            None => write!(f, "{sample_rate} kHz"),
+        }
+    }
+}
+
+impl fmt::Display for ResampleTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.sample_rate, self.bit_depth)
+    }
+}
+
+/// Convert an 80-bit IEEE 754 extended precision float to `f64`.
+fn parse_extended_float(bytes: &[u8]) -> f64 {
+    let exponent = (i32::from(bytes[0] & 0x7F) << 8) | i32::from(bytes[1]);
+    let mut mantissa_bytes = [0u8; 8];
+    mantissa_bytes.copy_from_slice(&bytes[2..10]);
+    let mantissa = u64::from_be_bytes(mantissa_bytes);
+    if exponent == 0 && mantissa == 0 {
+        return 0.0;
+    }
+    let value = mantissa as f64 * 2f64.powi(exponent - 16383 - 63);
+    if bytes[0] & 0x80 == 0 {
+        value
+    } else {
+        -value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_parse_extended_float() {
+        // 44100 Hz
+        let bytes = [0x40, 0x0E, 0xAC, 0x44, 0, 0, 0, 0, 0, 0];
+        assert!((parse_extended_float(&bytes) - 44100.0).abs() < f64::EPSILON);
+        // 48000 Hz
+        let bytes = [0x40, 0x0E, 0xBB, 0x80, 0, 0, 0, 0, 0, 0];
+        assert!((parse_extended_float(&bytes) - 48000.0).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn test_resample_target_from_str() {
+        let target = ResampleTarget::from_str("44100:16").expect("Valid target");
+        assert_eq!(target.sample_rate, 44100);
+        assert_eq!(target.bit_depth, 16);
+        assert_eq!(target.aiff_codec(), "pcm_s16be");
+        assert_eq!(aiff_codec(24), "pcm_s24be");
+        assert_eq!(wav_codec(20), "pcm_s24le");
+        assert_eq!(wav_codec(8), "pcm_s16le");
+        assert!(ResampleTarget::from_str("44100").is_err());
+        assert!(ResampleTarget::from_str("44100:12").is_err());
+        assert!(ResampleTarget::from_str("abc:16").is_err());
+    }
+
+    #[test]
+    fn test_exceeds_target() {
+        let target = ResampleTarget::from_str("44100:16").unwrap();
+        let properties = AudioProperties {
+            sample_rate: 48000,
+            bit_depth: Some(16),
+            channels: 2,
+        };
+        assert!(properties.exceeds(&target));
+        let properties = AudioProperties {
+            sample_rate: 44100,
+            bit_depth: Some(24),
+            channels: 2,
+        };
+        assert!(properties.exceeds(&target));
+        let properties = AudioProperties {
+            sample_rate: 44100,
+            bit_depth: None,
+            channels: 2,
+        };
+        assert!(!properties.exceeds(&target));
+    }
+
+    #[test]
+    fn test_limit_target() {
+        let target = ResampleTarget::from_str("44100:24").unwrap();
+        let properties = AudioProperties {
+            sample_rate: 48000,
+            bit_depth: Some(16),
+            channels: 2,
+        };
+        assert_eq!(
+            properties.limit_target(&target),
+            ResampleTarget::from_str("44100:16").unwrap()
+        );
+        let target = ResampleTarget::from_str("48000:16").unwrap();
+        let properties = AudioProperties {
+            sample_rate: 44100,
+            bit_depth: Some(24),
+            channels: 2,
+        };
+        assert_eq!(
+            properties.limit_target(&target),
+            ResampleTarget::from_str("44100:16").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_mp3_test_file() {
+        let path: PathBuf = ["tests", "files", "basic_tags", "Basic Tags - Song - 16-44.mp3"]
+            .iter()
+            .collect();
+        let properties = AudioProperties::from_mp3(&path).expect("Failed to read MP3 header");
+        assert_eq!(properties.sample_rate, 44100);
+        assert_eq!(properties.bit_depth, None);
+    }
+
+    #[test]
+    fn test_aiff_test_file() {
+        let path: PathBuf = ["tests", "files", "basic_tags", "Basic Tags - Song - 16-44.aif"]
+            .iter()
+            .collect();
+        let properties = AudioProperties::from_aiff(&path).expect("Failed to read AIFF header");
+        assert_eq!(properties.sample_rate, 44100);
+        assert_eq!(properties.bit_depth, Some(16));
+        assert!(!AiffInfo::from_path(&path)
+            .expect("Failed to read AIFF header")
+            .is_aifc());
+    }
+
+    #[test]
+    fn test_parse_aifc_comm() {
+        let mut comm = vec![0, 2, 0, 0, 0x10, 0, 0, 16, 0x40, 0x0E, 0xAC, 0x44, 0, 0, 0, 0, 0, 0];
+        comm.extend_from_slice(b"ima4");
+        comm.push(7);
+        comm.extend_from_slice(b"IMA 4:1");
+        let info = AiffInfo::parse_comm(&comm).expect("Failed to parse COMM");
+        assert!((info.sample_rate - 44100.0).abs() < f64::EPSILON);
+        assert!(info.is_aifc());
+        assert!(info.is_compressed());
+        assert_eq!(info.compression, Some(("ima4".to_string(), "IMA 4:1".to_string())));
+
+        comm.truncate(18);
+        comm.extend_from_slice(b"NONE");
+        let info = AiffInfo::parse_comm(&comm).expect("Failed to parse COMM");
+        assert!(info.is_aifc());
+        assert!(!info.is_compressed());
+    }
+
+    #[test]
+    fn test_aiff_truncated_comm() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let path = dir.path().join("truncated.aif");
+        let mut data = b"FORM\0\0\0\x20AIFFCOMM\xFF\xFF\xFF\xF0".to_vec();
+        data.extend_from_slice(&[0; 18]);
+        std::fs::write(&path, data).expect("Failed to write file");
+        assert!(AiffInfo::from_path(&path).is_err());
+    }
+}
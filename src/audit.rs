@@ -0,0 +1,517 @@
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use colored::Colorize;
+use id3::TagLike;
+
+use crate::config;
+use crate::AuditArgs;
+
+use track_rename::artists::{self, ArtistCluster};
+use track_rename::genre::{self, GENRE_MAPPINGS};
+use track_rename::lookup::{self, DurationCheck};
+use track_rename::prompt;
+use track_rename::serato::crates;
+use track_rename::serato::database::{SeratoDatabase, DATABASE_FILE_NAME};
+use track_rename::spectrum::Spectrum;
+use track_rename::state::{RenameEntry, RenameHistory, State};
+use track_rename::tempo::TempoSources;
+use track_rename::track::Track;
+use track_rename::utils;
+
+/// Run library audit subcommand.
+pub fn run(args: &AuditArgs) -> Result<()> {
+    if !(args.serato
+        || args.tempo
+        || args.lookup
+        || args.artists
+        || args.genres
+        || args.spectrum
+        || args.history
+        || args.lengths)
+    {
+        anyhow::bail!(
+            "No audit specified, use for example --serato, --tempo, --lookup, --artists, --genres, --spectrum, --history, or --lengths"
+        );
+    }
+    if args.apply && !(args.artists || args.genres) {
+        anyhow::bail!("--apply requires --artists or --genres");
+    }
+    let root = utils::resolve_input_path(&args.path)?;
+    if args.serato {
+        let serato_dir = match &args.serato_dir {
+            Some(dir) => dir.clone(),
+            None => crates::default_serato_dir().context("Failed to get Serato library directory")?,
+        };
+        audit_serato(&root, &serato_dir, args.verbose)?;
+    }
+    if args.tempo {
+        audit_tempo(&root, args.bpm_tolerance, args.verbose);
+    }
+    if args.lookup {
+        audit_lookup(&root, args.duration_tolerance, args.verbose);
+    }
+    if args.artists {
+        audit_artists(&root, args.min_tracks, args.apply, args.verbose);
+    }
+    if args.genres {
+        audit_genres(&root, args.apply, args.verbose);
+    }
+    if args.spectrum {
+        audit_spectrum(&root, args.min_cutoff, args.verbose);
+    }
+    if args.history {
+        audit_history(&root);
+    }
+    if args.lengths {
+        audit_lengths(&root, args.verbose)?;
+    }
+    Ok(())
+}
+
+/// Compare library files with the Serato database.
+fn audit_serato(root: &Path, serato_dir: &Path, verbose: bool) -> Result<()> {
+    let database_path = serato_dir.join(DATABASE_FILE_NAME);
+    let database = SeratoDatabase::read(&database_path)?;
+    if verbose {
+        println!(
+            "Serato database: {} ({} tracks)",
+            database_path.display().to_string().cyan(),
+            database.tracks().count()
+        );
+    }
+
+    let tracks = utils::collect_tracks(root);
+
+    // Compare with NFC normalized strings since Serato might use a different Unicode form
+    let serato_paths: Vec<PathBuf> = database.track_paths(&database_path);
+    let serato_set: HashSet<String> = serato_paths.iter().map(|path| normalized(path)).collect();
+    let library_set: HashSet<String> = tracks.iter().map(|track| normalized(&track.path)).collect();
+
+    let not_in_serato: Vec<&Track> = tracks
+        .iter()
+        .filter(|track| !serato_set.contains(&normalized(&track.path)))
+        .collect();
+    let known_count = tracks.len() - not_in_serato.len();
+
+    // Only report stale entries that point inside the audited directory
+    let missing_files: Vec<&PathBuf> = serato_paths
+        .iter()
+        .filter(|path| path.starts_with(root) && !library_set.contains(&normalized(path)) && !path.exists())
+        .collect();
+
+    println!("{}", format!("Serato audit: {}", root.display()).bold());
+    println!("Library files:       {}", tracks.len());
+    println!("Known to Serato:     {}", known_count.to_string().green());
+    println!("Missing from Serato: {}", colorize_count(not_in_serato.len()));
+    println!("Missing files:       {}", colorize_count(missing_files.len()));
+
+    if !not_in_serato.is_empty() {
+        println!(
+            "{}",
+            format!("\nNot in Serato ({}):", not_in_serato.len()).yellow().bold()
+        );
+        for track in not_in_serato {
+            println!("  {track}");
+        }
+    }
+    if !missing_files.is_empty() {
+        println!("{}", format!("\nMissing files ({}):", missing_files.len()).red().bold());
+        for path in missing_files {
+            println!("  {}", path.display());
+        }
+    }
+    Ok(())
+}
+
+/// Compare BPM values from the tag, Serato beatgrid, and title.
+fn audit_tempo(root: &Path, tolerance: f32, verbose: bool) {
+    let tracks = utils::collect_tracks(root);
+    let mut checked = 0;
+    let mut mismatches = Vec::new();
+    for track in &tracks {
+        let Some(file_tags) = utils::read_tags(track, verbose) else {
+            continue;
+        };
+        let sources = TempoSources::from_tags(&file_tags);
+        let suggestions = sources.check(tolerance);
+        checked += 1;
+        if suggestions.is_empty() {
+            if verbose {
+                println!("{track}: {}", sources.to_string().green());
+            }
+        } else {
+            mismatches.push((track, sources, suggestions));
+        }
+    }
+
+    println!("{}", format!("Tempo audit: {}", root.display()).bold());
+    println!("Checked tracks:  {checked}");
+    println!("BPM mismatches:  {}", colorize_count(mismatches.len()));
+    for (track, sources, suggestions) in mismatches {
+        println!("  {track}: {}", sources.to_string().yellow());
+        for suggestion in suggestions {
+            println!("    {suggestion}");
+        }
+    }
+}
+
+/// Compare track durations and titles with the canonical recordings from `MusicBrainz`.
+fn audit_lookup(root: &Path, tolerance: f64, verbose: bool) {
+    let tracks = utils::collect_tracks(root);
+    let mut checked = 0;
+    let mut not_found = Vec::new();
+    let mut mismatches = Vec::new();
+    let mut title_mismatches = Vec::new();
+    let mut requested = false;
+    for track in &tracks {
+        let Some(file_tags) = utils::read_tags(track, verbose) else {
+            continue;
+        };
+        let (Some(artist), Some(title)) = (file_tags.artist(), file_tags.title()) else {
+            continue;
+        };
+        let duration = match track.duration() {
+            Ok(duration) => duration,
+            Err(error) => {
+                eprintln!("{}", format!("Failed to read duration for {track}: {error}").red());
+                continue;
+            }
+        };
+        // MusicBrainz allows one request per second
+        if requested {
+            thread::sleep(Duration::from_secs(1));
+        }
+        requested = true;
+        let recordings = match lookup::search_musicbrainz(artist, title) {
+            Ok(recordings) => recordings,
+            Err(error) => {
+                eprintln!("{}", format!("Lookup failed for {track}: {error}").red());
+                continue;
+            }
+        };
+        checked += 1;
+        match lookup::compare_duration(duration, &recordings, tolerance) {
+            DurationCheck::Match(recording) => {
+                if let Some(closest) = lookup::title_mismatch(title, duration, &recordings, tolerance) {
+                    title_mismatches.push((track, duration, closest.clone()));
+                } else if verbose {
+                    println!("{track}: {}", recording.to_string().green());
+                }
+            }
+            DurationCheck::Mismatch(recording) => mismatches.push((track, duration, recording)),
+            DurationCheck::NotFound => not_found.push(track),
+        }
+    }
+
+    println!("{}", format!("Lookup audit: {}", root.display()).bold());
+    println!("Checked tracks:      {checked}");
+    println!("Not found:           {}", colorize_count(not_found.len()));
+    println!("Duration mismatches: {}", colorize_count(mismatches.len()));
+    for (track, duration, recording) in mismatches {
+        println!("  {track} ({})", lookup::format_duration(duration).yellow());
+        println!("    closest: {recording}");
+    }
+    println!("Title mismatches:    {}", colorize_count(title_mismatches.len()));
+    for (track, duration, recording) in title_mismatches {
+        println!("  {track} ({})", lookup::format_duration(duration));
+        println!("    same length: {}", recording.to_string().yellow());
+    }
+    if verbose && !not_found.is_empty() {
+        println!("{}", format!("\nNot found ({}):", not_found.len()).yellow().bold());
+        for track in not_found {
+            println!("  {track}");
+        }
+    }
+}
+
+/// Report different spellings of the same artist name and optionally unify them.
+fn audit_artists(root: &Path, min_tracks: usize, apply: bool, verbose: bool) {
+    let tracks: Vec<(Track, String)> = utils::collect_tracks(root)
+        .into_iter()
+        .filter_map(|track| {
+            let artist = utils::read_text_tags(&track, verbose)?.artist()?.to_string();
+            Some((track, artist))
+        })
+        .collect();
+
+    let clusters: Vec<ArtistCluster> = artists::cluster_artists(tracks.iter().map(|(_, artist)| artist.as_str()))
+        .into_iter()
+        .filter(|cluster| cluster.track_count() >= min_tracks)
+        .collect();
+
+    println!("{}", format!("Artist audit: {}", root.display()).bold());
+    println!("Tracks with artist: {}", tracks.len());
+    println!("Spelling groups:    {}", colorize_count(clusters.len()));
+    for cluster in &clusters {
+        println!("  {}", cluster.to_string().yellow());
+    }
+    if !apply {
+        return;
+    }
+
+    for cluster in &clusters {
+        let variants: Vec<&str> = cluster.variants().collect();
+        let matching: Vec<&Track> = tracks
+            .iter()
+            .filter(|(_, artist)| {
+                artists::split_artist_names(artist)
+                    .iter()
+                    .any(|name| variants.contains(name))
+            })
+            .map(|(track, _)| track)
+            .collect();
+        println!(
+            "\nUse {} for {} track(s)?",
+            cluster.canonical.green().bold(),
+            matching.len()
+        );
+        if !prompt::confirm() {
+            continue;
+        }
+        let mut updated = 0;
+        for track in matching {
+            if unify_artist(track, &variants, &cluster.canonical, verbose) {
+                updated += 1;
+            }
+        }
+        println!("{}", format!("Updated {updated} file(s)").green());
+    }
+}
+
+/// Report tracks whose genre tag disagrees with the genre mapped to their folder and optionally fix them.
+fn audit_genres(root: &Path, apply: bool, verbose: bool) {
+    let mut checked = 0;
+    let mut folders: BTreeMap<(String, &str), Vec<(Track, String)>> = BTreeMap::new();
+    for track in utils::collect_tracks(root) {
+        if !GENRE_MAPPINGS.contains_key(track.directory.as_str()) {
+            continue;
+        }
+        let Some(genre) = utils::read_text_tags(&track, verbose).and_then(|tag| tag.genre().map(ToString::to_string))
+        else {
+            continue;
+        };
+        checked += 1;
+        if let Some(folder_genre) = genre::folder_genre_conflict(&track.directory, &genre) {
+            folders
+                .entry((track.directory.clone(), folder_genre))
+                .or_default()
+                .push((track, genre));
+        }
+    }
+
+    println!("{}", format!("Genre audit: {}", root.display()).bold());
+    println!("Checked tracks:     {checked}");
+    println!(
+        "Mismatched genres:  {}",
+        colorize_count(folders.values().map(Vec::len).sum())
+    );
+    for ((folder, folder_genre), tracks) in &folders {
+        println!("  {} ({})", folder.cyan(), folder_genre.green());
+        for (track, genre) in tracks {
+            println!("    {}: {}", track.filename(), genre.yellow());
+        }
+    }
+    if !apply {
+        return;
+    }
+
+    for ((folder, folder_genre), tracks) in &folders {
+        println!(
+            "\nUse {} for {} track(s) in {folder}?",
+            folder_genre.green().bold(),
+            tracks.len()
+        );
+        if !prompt::confirm() {
+            continue;
+        }
+        let updated = tracks
+            .iter()
+            .filter(|(track, _)| set_genre(track, folder_genre, verbose))
+            .count();
+        println!("{}", format!("Updated {updated} file(s)").green());
+    }
+}
+
+/// Report lossless files with a frequency cutoff typical of a low bitrate MP3 encode.
+fn audit_spectrum(root: &Path, min_cutoff: u32, verbose: bool) {
+    let tracks = utils::collect_tracks(root);
+    let mut checked = 0;
+    let mut transcoded = Vec::new();
+    for track in &tracks {
+        let spectrum = match Spectrum::from_path(&track.path, &track.format) {
+            Ok(Some(spectrum)) => spectrum,
+            Ok(None) => continue,
+            Err(error) => {
+                eprintln!("{}", format!("Failed to analyse {track}: {error}").red());
+                continue;
+            }
+        };
+        checked += 1;
+        let Some(cutoff) = spectrum.cutoff() else {
+            continue;
+        };
+        if spectrum.is_transcoded(min_cutoff) {
+            transcoded.push((track, cutoff));
+        } else if verbose {
+            println!("{track}: {}", format_frequency(cutoff).green());
+        }
+    }
+
+    println!("{}", format!("Spectrum audit: {}", root.display()).bold());
+    println!("Checked tracks:       {checked}");
+    println!("Suspected transcodes: {}", colorize_count(transcoded.len()));
+    for (track, cutoff) in transcoded {
+        println!("  {track} ({})", format_frequency(cutoff).yellow());
+    }
+}
+
+/// Report tracks with tag values longer than the limits from the user config.
+fn audit_lengths(root: &Path, verbose: bool) -> Result<()> {
+    let limits = config::user_tag_limits();
+    if limits.is_empty() {
+        anyhow::bail!("No tag length limits configured, add a [max_length] table to the user config");
+    }
+    let mut checked = 0;
+    let mut too_long = Vec::new();
+    for track in utils::collect_tracks(root) {
+        let Some(file_tags) = utils::read_text_tags(&track, verbose) else {
+            continue;
+        };
+        checked += 1;
+        let violations = limits.check(
+            file_tags.artist().unwrap_or_default(),
+            file_tags.title().unwrap_or_default(),
+            file_tags.album().unwrap_or_default(),
+            file_tags.genre().unwrap_or_default(),
+        );
+        if !violations.is_empty() {
+            too_long.push((track, violations));
+        }
+    }
+
+    println!("{}", format!("Length audit: {}", root.display()).bold());
+    println!("Limits:          {}", limits.to_string().cyan());
+    println!("Checked tracks:  {checked}");
+    println!("Too long:        {}", colorize_count(too_long.len()));
+    for (track, violations) in too_long {
+        println!("  {track}");
+        for violation in violations {
+            println!("    {}", violation.to_string().yellow());
+        }
+    }
+    Ok(())
+}
+
+/// Show who processed the files in the state and who renamed files under the directory.
+fn audit_history(root: &Path) {
+    let state = State::load();
+    let processed = state.user_counts(root);
+    let history = RenameHistory::load();
+    let renames: Vec<&RenameEntry> = history
+        .entries()
+        .iter()
+        .filter(|entry| entry.old.starts_with(root) || entry.new.starts_with(root))
+        .collect();
+
+    println!("{}", format!("History audit: {}", root.display()).bold());
+    println!("Processed files: {}", processed.values().sum::<usize>());
+    for (user, count) in &processed {
+        println!("  {}: {count}", display_user(user).cyan());
+    }
+    println!("Renames:         {}", renames.len());
+    for entry in renames {
+        let (old, new) = if entry.old.parent() == entry.new.parent() {
+            (file_name(&entry.old), file_name(&entry.new))
+        } else {
+            (entry.old.display().to_string(), entry.new.display().to_string())
+        };
+        println!(
+            "  {} {} {old} -> {new}",
+            utils::format_timestamp(entry.timestamp).dimmed(),
+            display_user(&entry.user).cyan()
+        );
+    }
+}
+
+pub fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map_or_else(|| path.display().to_string(), |name| name.to_string_lossy().to_string())
+}
+
+/// Entries from before users were recorded have an empty user.
+pub const fn display_user(user: &str) -> &str {
+    if user.is_empty() {
+        "unknown"
+    } else {
+        user
+    }
+}
+
+fn format_frequency(frequency: f64) -> String {
+    format!("{:.1} kHz", frequency / 1000.0)
+}
+
+/// Replace artist name variants with the canonical spelling and write the tags.
+///
+/// Returns true if the tags were written.
+fn unify_artist(track: &Track, variants: &[&str], canonical: &str, verbose: bool) -> bool {
+    let Some(mut file_tags) = utils::read_tags(track, verbose) else {
+        return false;
+    };
+    let Some(artist) = file_tags.artist() else {
+        return false;
+    };
+    let unified = variants.iter().fold(artist.to_string(), |artist, variant| {
+        artists::replace_artist_name(&artist, variant, canonical)
+    });
+    if unified == artist {
+        return false;
+    }
+    if verbose {
+        println!("  {track}: {artist} -> {unified}");
+    }
+    file_tags.set_artist(unified);
+    match file_tags.write_to_path(&track.path, file_tags.version()) {
+        Ok(()) => true,
+        Err(error) => {
+            utils::print_error(&format!("Failed to write tags for {track}: {error}"));
+            false
+        }
+    }
+}
+
+/// Write the genre tag.
+///
+/// Returns true if the tags were written.
+fn set_genre(track: &Track, genre: &str, verbose: bool) -> bool {
+    let Some(mut file_tags) = utils::read_tags(track, verbose) else {
+        return false;
+    };
+    if verbose {
+        println!("  {track}: {} -> {genre}", file_tags.genre().unwrap_or_default());
+    }
+    file_tags.set_genre(genre);
+    match file_tags.write_to_path(&track.path, file_tags.version()) {
+        Ok(()) => true,
+        Err(error) => {
+            utils::print_error(&format!("Failed to write tags for {track}: {error}"));
+            false
+        }
+    }
+}
+
+fn normalized(path: &Path) -> String {
+    utils::normalize_str(&path.to_string_lossy())
+}
+
+fn colorize_count(count: usize) -> colored::ColoredString {
+    if count == 0 {
+        count.to_string().green()
+    } else {
+        count.to_string().yellow()
+    }
+}
@@ -0,0 +1,184 @@
+use std::borrow::Cow;
+use std::cmp::Reverse;
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use colored::Colorize;
+use id3::TagLike;
+use rayon::prelude::*;
+use regex::Regex;
+
+use crate::BenchArgs;
+
+use track_rename::{formatting, genre, utils};
+
+/// Raw tag strings for one track.
+#[derive(Debug, Clone, Default)]
+struct TagStrings {
+    artist: String,
+    title: String,
+    album: String,
+    genre: String,
+}
+
+/// Accumulated time for one formatting stage.
+#[derive(Debug, Default)]
+struct StageTimes {
+    artist_and_title: Duration,
+    album: Duration,
+    genre: Duration,
+    filename: Duration,
+}
+
+/// Time spent in one regex over all input strings.
+struct RegexTiming {
+    pattern: &'static str,
+    duration: Duration,
+    matches: usize,
+}
+
+/// Run formatting benchmark subcommand.
+///
+/// Tags are read before timing, so the measurements do not include any file IO.
+pub fn run(args: &BenchArgs) -> Result<()> {
+    let root = utils::resolve_input_path(&args.path)?;
+    let tracks = utils::collect_tracks(&root);
+    let inputs: Vec<TagStrings> = tracks
+        .par_iter()
+        .filter_map(|track| utils::read_tags(track, false))
+        .map(|tag| TagStrings {
+            artist: utils::normalize_str(tag.artist().unwrap_or_default()),
+            title: utils::normalize_str(tag.title().unwrap_or_default()),
+            album: utils::normalize_str(tag.album().unwrap_or_default()),
+            genre: utils::normalize_str(tag.genre_parsed().unwrap_or_default().as_ref()),
+        })
+        .collect();
+    if inputs.is_empty() {
+        println!("{}", "No tags to benchmark".yellow());
+        return Ok(());
+    }
+
+    let iterations = args.iterations.max(1);
+    println!(
+        "{}",
+        format!("Formatting {} tracks, {iterations} iterations", inputs.len()).bold()
+    );
+
+    // Compile the lazily initialized regexes before timing
+    for input in &inputs {
+        format_track(input, &mut StageTimes::default());
+    }
+
+    let mut stages = StageTimes::default();
+    let start = Instant::now();
+    for _ in 0..iterations {
+        for input in &inputs {
+            format_track(input, &mut stages);
+        }
+    }
+    let total = start.elapsed();
+    let count = inputs.len() * iterations;
+
+    println!("Total:      {:.3}s", total.as_secs_f64());
+    println!("Per track:  {:.1}µs", total.as_secs_f64() * 1e6 / count as f64);
+    println!("Throughput: {:.0} tracks/s", count as f64 / total.as_secs_f64());
+    println!("{}", "\nStages:".bold());
+    for (name, duration) in [
+        ("artist and title", stages.artist_and_title),
+        ("album", stages.album),
+        ("genre", stages.genre),
+        ("filename", stages.filename),
+    ] {
+        println!(
+            "  {name:<17} {:>8.3}s {:>5.1}%",
+            duration.as_secs_f64(),
+            100.0 * duration.as_secs_f64() / total.as_secs_f64()
+        );
+    }
+
+    let timings = regex_timings(&inputs, iterations);
+    let regex_total: Duration = timings.iter().map(|timing| timing.duration).sum();
+    println!(
+        "{}",
+        format!(
+            "\nMost expensive regexes ({} total, {:.3}s when run separately):",
+            timings.len(),
+            regex_total.as_secs_f64()
+        )
+        .bold()
+    );
+    for timing in timings.iter().take(args.top) {
+        println!(
+            "  {:>8.3}ms {:>7} matches  {}",
+            timing.duration.as_secs_f64() * 1000.0,
+            timing.matches,
+            timing.pattern.cyan()
+        );
+    }
+    if args.verbose {
+        let unused: Vec<&RegexTiming> = timings.iter().filter(|timing| timing.matches == 0).collect();
+        println!("{}", format!("\nRegexes without matches ({}):", unused.len()).bold());
+        for timing in unused {
+            println!("  {}", timing.pattern);
+        }
+    }
+    Ok(())
+}
+
+/// Run the full formatting pipeline for one track.
+fn format_track(input: &TagStrings, stages: &mut StageTimes) {
+    let start = Instant::now();
+    let (artist, title) = formatting::format_tags_for_artist_and_title(&input.artist, &input.title);
+    let album_start = Instant::now();
+    black_box(formatting::format_album(&input.album));
+    let genre_start = Instant::now();
+    black_box(genre::format_genre(&input.genre));
+    let filename_start = Instant::now();
+    black_box(formatting::format_filename(&artist, &title));
+    let end = Instant::now();
+
+    stages.artist_and_title += album_start - start;
+    stages.album += genre_start - album_start;
+    stages.genre += filename_start - genre_start;
+    stages.filename += end - filename_start;
+}
+
+/// Time each regex separately over the raw input strings, sorted from slowest to fastest.
+fn regex_timings(inputs: &[TagStrings], iterations: usize) -> Vec<RegexTiming> {
+    let name_texts: Vec<&str> = inputs
+        .iter()
+        .flat_map(|input| [input.artist.as_str(), input.title.as_str(), input.album.as_str()])
+        .collect();
+    let genre_texts: Vec<&str> = inputs.iter().map(|input| input.genre.as_str()).collect();
+
+    let mut timings: Vec<RegexTiming> = formatting::regexes()
+        .into_iter()
+        .map(|regex| time_regex(regex, &name_texts, iterations))
+        .chain(
+            genre::regexes()
+                .into_iter()
+                .map(|regex| time_regex(regex, &genre_texts, iterations)),
+        )
+        .collect();
+    timings.sort_unstable_by_key(|timing| Reverse(timing.duration));
+    timings
+}
+
+fn time_regex(regex: &'static Regex, texts: &[&str], iterations: usize) -> RegexTiming {
+    let mut matches = 0;
+    let start = Instant::now();
+    for _ in 0..iterations {
+        for text in texts {
+            // Replacing returns the borrowed input when there is no match
+            if matches!(regex.replace_all(text, ""), Cow::Owned(_)) {
+                matches += 1;
+            }
+        }
+    }
+    RegexTiming {
+        pattern: regex.as_str(),
+        duration: start.elapsed(),
+        matches: matches / iterations,
+    }
+}
@@ -0,0 +1,141 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::utils;
+
+/// Numbers in this range at the end of a title are removed as BPM values.
+///
+/// Numbers outside the range are kept since they are more likely part of the title,
+/// like a year or an edit number.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct BpmRange {
+    pub min: u16,
+    pub max: u16,
+}
+
+/// BPM range for tracks in a directory or with a genre.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct BpmRangeRule {
+    /// Directory name or consecutive path components, for example `DNB` or `DJ MUSIC/DNB`
+    pub path: Option<PathBuf>,
+    /// Genre, also matching genres that start with it, so `House` matches `House Deep`
+    pub genre: Option<String>,
+    pub min: u16,
+    pub max: u16,
+}
+
+/// Default range and the rules for directories and genres.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Serialize)]
+pub struct BpmRanges {
+    pub default: BpmRange,
+    pub rules: Vec<BpmRangeRule>,
+}
+
+impl BpmRange {
+    #[must_use]
+    pub const fn new(min: u16, max: u16) -> Self {
+        Self { min, max }
+    }
+
+    /// Return true if the value is inside the range, including the limits.
+    #[must_use]
+    pub fn contains(self, bpm: f64) -> bool {
+        (f64::from(self.min)..=f64::from(self.max)).contains(&bpm)
+    }
+}
+
+impl Default for BpmRange {
+    fn default() -> Self {
+        Self::new(50, 180)
+    }
+}
+
+impl fmt::Display for BpmRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-{}", self.min, self.max)
+    }
+}
+
+impl BpmRangeRule {
+    #[must_use]
+    pub const fn range(&self) -> BpmRange {
+        BpmRange::new(self.min, self.max)
+    }
+
+    fn matches_path(&self, path: &Path) -> bool {
+        self.path.as_ref().is_some_and(|rule_path| {
+            path.parent()
+                .is_some_and(|directory| utils::contains_subpath(directory, rule_path))
+        })
+    }
+
+    fn matches_genre(&self, genre: &str) -> bool {
+        self.genre.as_ref().is_some_and(|rule_genre| {
+            let rule_genre = rule_genre.trim();
+            !rule_genre.is_empty()
+                && genre.get(..rule_genre.len()).is_some_and(|start| {
+                    start.eq_ignore_ascii_case(rule_genre)
+                        && matches!(genre[rule_genre.len()..].chars().next(), None | Some(' ' | ','))
+                })
+        })
+    }
+}
+
+impl BpmRanges {
+    #[must_use]
+    pub const fn new(default: BpmRange, rules: Vec<BpmRangeRule>) -> Self {
+        Self { default, rules }
+    }
+
+    /// BPM range for a track.
+    ///
+    /// The most specific matching directory rule is used first, then the first matching genre rule,
+    /// and otherwise the default range.
+    #[must_use]
+    pub fn range_for_track(&self, path: &Path, genre: &str) -> BpmRange {
+        self.rules
+            .iter()
+            .filter(|rule| rule.matches_path(path))
+            .max_by_key(|rule| rule.path.as_ref().map_or(0, |rule_path| rule_path.components().count()))
+            .or_else(|| self.rules.iter().find(|rule| rule.matches_genre(genre)))
+            .map_or(self.default, BpmRangeRule::range)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rule_matching() {
+        let rule = |path: Option<&str>, genre: Option<&str>, min, max| BpmRangeRule {
+            path: path.map(PathBuf::from),
+            genre: genre.map(ToString::to_string),
+            min,
+            max,
+        };
+        let dnb = rule(Some("DNB"), None, 160, 180);
+        let hip_hop = rule(None, Some("Hip-Hop"), 60, 115);
+        assert!(dnb.matches_path(Path::new("/music/DNB/Artist - Song.mp3")));
+        assert!(!dnb.matches_path(Path::new("/music/DNB.mp3")));
+        assert!(hip_hop.matches_genre("Hip-Hop 90s"));
+        assert!(hip_hop.matches_genre("hip-hop"));
+        assert!(!hip_hop.matches_genre("Hip-Hopera"));
+        assert!(!dnb.matches_genre("Drum & Bass"));
+
+        assert!(BpmRange::default().contains(175.0));
+        assert!(!BpmRange::new(60, 115).contains(175.0));
+        assert_eq!(BpmRange::new(60, 115).to_string(), "60-115");
+
+        let ranges = BpmRanges::new(BpmRange::default(), vec![dnb, hip_hop]);
+        let range = |path, genre| ranges.range_for_track(Path::new(path), genre);
+        assert_eq!(
+            range("/music/DNB/Artist - Song.mp3", "Hip-Hop"),
+            BpmRange::new(160, 180)
+        );
+        assert_eq!(range("/music/Artist - Song.mp3", "Hip-Hop"), BpmRange::new(60, 115));
+        assert_eq!(range("/music/Artist - Song.mp3", "House"), BpmRange::default());
+    }
+}
@@ -0,0 +1,49 @@
+use id3::frame::ExtendedText;
+use id3::{Tag, TagLike};
+
+use crate::formatting::FilenameSeparator;
+
+/// Description of the `TXXX` frame that stores the canonical name.
+pub const FRAME_DESCRIPTION: &str = "CANONICAL_NAME";
+
+/// Read the canonical name from the tag.
+#[must_use]
+pub fn read(tag: &Tag) -> Option<String> {
+    tag.extended_texts()
+        .find(|text| text.description == FRAME_DESCRIPTION)
+        .map(|text| text.value.clone())
+}
+
+/// Store the canonical name in the tag, replacing an existing one.
+pub fn set(tag: &mut Tag, name: &str) {
+    tag.remove_extended_text(Some(FRAME_DESCRIPTION), None);
+    tag.add_frame(ExtendedText {
+        description: FRAME_DESCRIPTION.to_string(),
+        value: name.to_string(),
+    });
+}
+
+/// Canonical `Artist - Title` name for the formatted artist and title.
+///
+/// Always uses a hyphen separator regardless of the filename separator,
+/// so other tools can rely on a single format.
+#[must_use]
+pub fn from_artist_and_title(artist: &str, title: &str) -> String {
+    FilenameSeparator::Hyphen.join(artist, title)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_set_and_read_canonical_name() {
+        let mut tag = Tag::new();
+        assert_eq!(read(&tag), None);
+        set(&mut tag, "Artist - Old");
+        set(&mut tag, &from_artist_and_title("Artist", "Song (Extended Mix)"));
+        assert_eq!(read(&tag), Some("Artist - Song (Extended Mix)".to_string()));
+        assert_eq!(tag.extended_texts().count(), 1);
+        assert_eq!(from_artist_and_title("", "Song"), "Song");
+    }
+}
@@ -1,76 +1,665 @@
-use std::path::PathBuf;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::OnceLock;
 use std::{fmt, fs};
 
-use anyhow::{anyhow, Context};
+use anyhow::{anyhow, bail, Context};
+use clap::ValueEnum;
 use colored::Colorize;
 use serde::{Deserialize, Serialize};
 
 use crate::RenamerArgs;
 
+use track_rename::artwork::{self, ArtFormat, ArtworkOptions};
+use track_rename::audio::ResampleTarget;
+use track_rename::bpm_range::{BpmRange, BpmRangeRule, BpmRanges};
+use track_rename::file_format::FormatRule;
+use track_rename::formatting::{
+    AlbumArtist, FilenameSeparator, FormatOptions, RuleCategory, RuleSet, Subtitle, YearInTitle,
+};
+use track_rename::i18n::Language;
+use track_rename::prompt::ChangeSeverity;
+use track_rename::serato::colors::ColorRule;
+use track_rename::serato::crates;
+use track_rename::serato::templates::CueTemplate;
+use track_rename::state_store::StateLocation;
+use track_rename::tag_limits::TagLimits;
+use track_rename::throttle::Rate;
 use track_rename::utils;
 
 const CONFIG_FILE_DIR: &str = ".config";
 const CONFIG_FILE_NAME: &str = "track-rename.toml";
 
+/// Seconds to wait before retrying failed files when not given.
+const DEFAULT_RETRY_DELAY: f64 = 2.0;
+
+/// User config file given with `--config`, used instead of the default locations.
+static CONFIG_PATH_OVERRIDE: OnceLock<PathBuf> = OnceLock::new();
+
 /// Renamer settings combined from CLI options and user config file.
 #[derive(Default, Debug, Serialize, Deserialize)]
 pub struct Config {
+    /// Which artist wins when the filename and artist tag disagree
+    pub artist_conflict: ArtistConflict,
+    pub artwork: Option<ArtworkOptions>,
+    /// Ask once for changes shared by at least this many tracks
+    pub batch_threshold: Option<usize>,
+    /// Numbers at the end of titles in this range are removed as BPM values
+    pub bpm_range: BpmRange,
+    /// BPM ranges for directories and genres
+    pub bpm_rules: Vec<BpmRangeRule>,
+    /// Store the formatted name in a `TXXX:CANONICAL_NAME` frame
+    pub canonical_name: bool,
+    pub check_aifc: bool,
+    pub check_vbr: bool,
     pub convert_failed: bool,
+    /// Mirror directory for formatted copies, the source files are not modified
+    pub copy_to: Option<PathBuf>,
     pub debug: bool,
+    pub descriptor_statistics: bool,
     pub excluded_tracks: Vec<String>,
+    pub feat_index: Option<PathBuf>,
+    /// Separator between the artist and title in filenames
+    pub filename_separator: FilenameSeparator,
+    /// File with the paths to process one per line, `-` for stdin
+    pub files_from: Option<PathBuf>,
+    pub fix_aifc: bool,
+    pub fix_vbr: bool,
     pub force: bool,
+    pub format_rules: Vec<FormatRule>,
+    pub force_rules_version: bool,
     pub genre_statistics: bool,
+    pub io_threads: Option<usize>,
+    pub link_duplicates: bool,
     pub log_failures: bool,
+    /// Abort before modifying anything if more tracks than this would change
+    pub max_changes: Option<usize>,
+    pub minor_changes: Option<MinorChanges>,
+    pub minor_threshold: ChangeSeverity,
+    /// Playlist or crate file for the tracks added or modified in this run
+    pub new_playlist: Option<PathBuf>,
+    /// Do not warn about DJ music folders without a genre mapping
+    pub no_genre_warnings: bool,
     pub no_state: bool,
+    /// Processing order, or the gathered directory or playlist order when not given
+    pub order: Option<TrackOrder>,
+    pub placeholders: Placeholders,
+    /// Show this many of the most common pending changes before processing
+    pub preview: Option<usize>,
     pub print_only: bool,
+    pub preset: Option<String>,
+    pub quick: bool,
+    pub read_only: bool,
+    /// Append original and formatted artist and title to this corpus file
+    pub record_corpus: Option<PathBuf>,
     pub rename_files: bool,
-    pub sort_files: bool,
+    /// Append each applied rename to this JSON lines file
+    pub rename_journal: Option<PathBuf>,
+    pub repair_failed: bool,
+    pub replay: Option<PathBuf>,
+    pub resample: Option<ResampleTarget>,
+    /// Enabled formatting rule categories
+    pub rules: RuleSet,
+    /// Number of end of run retries for files that failed with a transient error
+    pub retries: usize,
+    /// Seconds to wait before each retry
+    pub retry_delay: f64,
+    /// Where to show tracks after accepting a change at the prompt
+    pub reveal: Option<Reveal>,
+    pub safe_writes: bool,
+    pub serato_dir: Option<PathBuf>,
+    /// Per-folder and per-genre statistics output file
+    pub stats_report: Option<PathBuf>,
+    pub strip_ape: bool,
+    /// Frame IDs removed when writing tags
+    pub strip_frames: Vec<String>,
+    /// Maximum number of characters for each tag field
+    pub tag_limits: TagLimits,
     pub tags_only: bool,
     pub test_mode: bool,
+    /// Maximum number of renames per second
+    pub throttle_renames: Option<Rate>,
+    pub profile: bool,
+    pub tolerant: bool,
+    /// Shorten tag values over the limits instead of only warning
+    pub truncate_tags: bool,
+    pub update_crates: bool,
+    pub update_playlist: bool,
+    pub upgrade_tags: bool,
+    pub user_config_file: Option<PathBuf>,
     pub verbose: bool,
+    /// Check this many random state entries from this run against the files after saving the state
+    pub verify_state: Option<usize>,
+    pub verify_writes: bool,
     pub write_all_tags: bool,
+    /// Write a track ID to files that do not have one
+    pub write_id: bool,
+    /// Keep a year at the end of the title or move it to the year tag
+    pub year_in_title: YearInTitle,
+    /// Keep mix descriptors in the title or move them to or from the subtitle tag
+    pub subtitle: Subtitle,
+    /// Format the album artist or also fill in missing album artists
+    pub album_artist: AlbumArtist,
     pub overwrite_existing: bool,
 }
 
+/// How tracks are shown after accepting a change at the prompt.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Reveal {
+    /// Open the containing folder in the file manager
+    Folder,
+    /// Add the track to a playlist or Serato crate for reviewing later
+    Playlist(PathBuf),
+}
+
+/// What to do with changes that are at most the minor change threshold.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum MinorChanges {
+    /// Leave the file unchanged without asking
+    Skip,
+    /// Apply the change without asking
+    Approve,
+}
+
+/// Order in which tracks are processed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum TrackOrder {
+    /// Most recently modified files first
+    Newest,
+    /// Least recently modified files first
+    Oldest,
+    /// Filename, ignoring the parent directory
+    Name,
+    /// Full path, processing one directory at a time
+    Path,
+    /// Random order
+    Random,
+}
+
+/// What to do with online-only cloud storage files that have not been downloaded.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Placeholders {
+    /// Leave placeholder files unprocessed
+    #[default]
+    Skip,
+    /// Process placeholder files, which downloads them
+    Hydrate,
+}
+
+/// Which artist to use when the artist in the filename differs from the artist tag.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum ArtistConflict {
+    /// Keep the artist tag and only report the conflict
+    #[default]
+    Tag,
+    /// Use the artist from the filename
+    Filename,
+    /// Ask which one to use
+    Ask,
+}
+
 /// User config options from a config file.
 #[derive(Debug, Default, Deserialize)]
 struct UserConfig {
     /// Filenames to ignore
     pub exclude: Vec<String>,
     #[serde(default)]
+    /// Default range for removing BPM values from titles
+    pub bpm_range: Option<BpmRange>,
+    #[serde(default)]
+    /// BPM ranges for directories and genres
+    pub bpm_rules: Vec<BpmRangeRule>,
+    #[serde(default)]
+    /// Which artist wins when the filename and artist tag disagree
+    pub artist_conflict: Option<ArtistConflict>,
+    #[serde(default)]
+    /// Ask once for changes shared by at least this many tracks
+    pub batch_threshold: Option<usize>,
+    #[serde(default)]
+    /// Store the formatted name in a `TXXX:CANONICAL_NAME` frame
+    pub canonical_name: bool,
+    #[serde(default)]
+    /// Report AIFC and compressed AIFF files
+    pub check_aifc: bool,
+    #[serde(default)]
+    /// Check MP3 VBR header matches the audio data
+    pub check_vbr: bool,
+    #[serde(default)]
+    /// Actions for Serato track colours
+    pub color_rules: Vec<ColorRule>,
+    #[serde(default)]
     /// Convert files that could not be read to AIFF
     pub convert_failed: bool,
     #[serde(default)]
+    /// Required file formats for directories
+    pub format_rules: Vec<FormatRule>,
+    #[serde(default)]
+    /// Serato cue point templates
+    pub cue_templates: Vec<CueTemplate>,
+    #[serde(default)]
+    /// Answer yes when pressing Enter in confirmation prompts
+    pub default_yes: bool,
+    #[serde(default)]
+    /// Collect statistics of parenthesized title descriptors
+    pub descriptor_statistics: bool,
+    #[serde(default)]
     pub genre_statistics: bool,
     #[serde(default)]
+    /// Number of threads for concurrent file reads
+    pub io_threads: Option<usize>,
+    #[serde(default)]
+    /// Language for prompts, warnings, and summaries, for example "fi"
+    pub language: Option<String>,
+    #[serde(default)]
+    /// Abort before modifying anything if more tracks than this would change
+    pub max_changes: Option<usize>,
+    #[serde(default)]
     pub log_failures: bool,
     #[serde(default)]
+    /// Skip or auto-approve changes below the minor threshold
+    pub minor_changes: Option<MinorChanges>,
+    #[serde(default)]
+    /// Largest change counted as minor
+    pub minor_threshold: Option<ChangeSeverity>,
+    #[serde(default)]
+    /// Do not warn about DJ music folders without a genre mapping
+    pub no_genre_warnings: bool,
+    #[serde(default)]
     pub no_state: bool,
+    #[serde(default)]
+    /// Processing order
+    pub order: Option<TrackOrder>,
+    #[serde(default)]
+    /// Skip or download online-only cloud storage files
+    pub placeholders: Option<Placeholders>,
+    #[serde(default)]
+    /// Try to repair MP3 files that could not be read
+    pub repair_failed: bool,
+    #[serde(default)]
+    /// Resample target for AIFF files in format `SAMPLE_RATE:BIT_DEPTH`
+    pub resample: Option<String>,
+    #[serde(default)]
+    /// Maximum number of renames, for example "5/s" or "60/min"
+    pub throttle_renames: Option<String>,
+    #[serde(default)]
+    /// Number of retries for files that failed with a transient error
+    pub retries: Option<usize>,
+    #[serde(default)]
+    /// Only apply these formatting rule categories
+    pub rules: Option<Vec<RuleCategory>>,
+    #[serde(default)]
+    /// Skip these formatting rule categories
+    pub skip_rules: Vec<RuleCategory>,
+    #[serde(default)]
+    /// Seconds to wait before each retry
+    pub retry_delay: Option<f64>,
+    #[serde(default)]
+    /// Re-encode embedded artwork larger than this many pixels
+    pub max_art_size: Option<u32>,
+    #[serde(default)]
+    /// Image format for re-encoded artwork
+    pub art_format: Option<ArtFormat>,
+    #[serde(default)]
+    /// JPEG quality for re-encoded artwork
+    pub art_quality: Option<u8>,
+    #[serde(default)]
+    /// Write tags to a temporary copy and atomically replace the original file
+    pub safe_writes: bool,
+    #[serde(default)]
+    /// Frame IDs removed when writing tags, for example "PRIV"
+    pub strip_frames: Vec<String>,
+    #[serde(default)]
+    /// Skip malformed ID3 frames instead of failing the file
+    pub tolerant: bool,
+    #[serde(default)]
+    /// Maximum number of characters for each tag field
+    pub max_length: TagLimits,
+    #[serde(default)]
+    /// Shorten tag values over the limits instead of only warning
+    pub truncate_tags: bool,
+    #[serde(default)]
+    /// Read tags back after writing and check they match
+    pub verify_writes: bool,
+    #[serde(default)]
+    /// Write a track ID to files that do not have one
+    pub write_id: bool,
+    #[serde(default)]
+    /// Keep a year at the end of the title or move it to the year tag
+    pub year_in_title: Option<YearInTitle>,
+    #[serde(default)]
+    /// Keep mix descriptors in the title or move them to or from the subtitle tag
+    pub subtitle: Option<Subtitle>,
+    #[serde(default)]
+    /// Format the album artist or also fill in missing album artists
+    pub album_artist: Option<AlbumArtist>,
+    #[serde(default)]
+    /// Serato library directory
+    pub serato_dir: Option<String>,
+    #[serde(default)]
+    /// Shared directory or `WebDAV` URL for the state and rename history
+    pub state_location: Option<String>,
+    #[serde(default)]
+    /// Separator between the artist and title in filenames
+    pub filename_separator: Option<FilenameSeparator>,
+    #[serde(default)]
+    /// Named option sets selected with `--preset`
+    pub presets: HashMap<String, Preset>,
+}
+
+/// Named set of command line options from the user config file.
+///
+/// Options use the same names as the command line flags
+/// and are combined with the flags given on the command line.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct Preset {
+    all_tags: bool,
+    art_format: Option<ArtFormat>,
+    art_quality: Option<u8>,
+    artist_conflict: Option<ArtistConflict>,
+    batch_threshold: Option<usize>,
+    canonical_name: bool,
+    check_aifc: bool,
+    check_vbr: bool,
+    convert: bool,
+    descriptors: bool,
+    fix_aifc: bool,
+    fix_vbr: bool,
+    force: bool,
+    genre: bool,
+    io_threads: Option<usize>,
+    link_duplicates: bool,
+    log: bool,
+    max_art_size: Option<u32>,
+    max_changes: Option<usize>,
+    minor_changes: Option<MinorChanges>,
+    minor_threshold: Option<ChangeSeverity>,
+    no_genre_warnings: bool,
+    no_state: bool,
+    order: Option<TrackOrder>,
+    overwrite: bool,
+    placeholders: Option<Placeholders>,
+    print: bool,
+    quick: bool,
+    read_only: bool,
+    rename: bool,
+    repair: bool,
+    resample: Option<String>,
+    retries: Option<usize>,
+    retry_delay: Option<f64>,
+    rules: Option<Vec<RuleCategory>>,
+    safe_writes: bool,
+    skip_rules: Vec<RuleCategory>,
+    sort: bool,
+    strip_ape: bool,
+    strip_chapters: bool,
+    throttle_renames: Option<String>,
+    tags_only: bool,
+    tolerant: bool,
+    truncate_tags: bool,
+    update_crates: bool,
+    update_playlist: bool,
+    upgrade_tags: bool,
+    verbose: bool,
+    verify_writes: bool,
+    write_id: bool,
+    year_in_title: Option<YearInTitle>,
+    subtitle: Option<Subtitle>,
+    album_artist: Option<AlbumArtist>,
 }
 
 impl Config {
     /// Create config from given command line args and user config file.
-    pub fn from_args(args: &RenamerArgs) -> Self {
-        let user_config = UserConfig::get_user_config();
-        Self {
-            convert_failed: args.convert || user_config.convert_failed,
+    ///
+    /// Options from the selected preset are combined with the command line args.
+    pub fn from_args(args: &RenamerArgs) -> anyhow::Result<Self> {
+        let mut user_config = UserConfig::get_user_config();
+        let preset = match &args.preset {
+            Some(name) => user_config.presets.remove(name).with_context(|| {
+                let mut names: Vec<&str> = user_config.presets.keys().map(String::as_str).collect();
+                names.sort_unstable();
+                if names.is_empty() {
+                    format!("Preset not found in user config: {name}")
+                } else {
+                    format!(
+                        "Preset not found in user config: {name}\nAvailable presets: {}",
+                        names.join(", ")
+                    )
+                }
+            })?,
+            None => Preset::default(),
+        };
+        let fix_vbr = args.fix_vbr || preset.fix_vbr;
+        let fix_aifc = args.fix_aifc || preset.fix_aifc;
+        // Read-only mode turns off everything that would write files without an explicit path
+        let read_only = args.read_only || preset.read_only;
+        let rules = Self::rule_set(args, &preset, &user_config);
+        if args.record_corpus.is_some() && !rules.is_all() {
+            bail!("Recording a corpus requires all formatting rules, remove `--rules` and `--skip-rules`");
+        }
+        let artwork = args
+            .max_art_size
+            .or(preset.max_art_size)
+            .or(user_config.max_art_size)
+            .map(|max_size| ArtworkOptions {
+                max_size,
+                format: args
+                    .art_format
+                    .or(preset.art_format)
+                    .or(user_config.art_format)
+                    .unwrap_or_default(),
+                quality: args
+                    .art_quality
+                    .or(preset.art_quality)
+                    .or(user_config.art_quality)
+                    .unwrap_or(artwork::DEFAULT_QUALITY)
+                    .clamp(1, 100),
+            });
+        let resample = match args.resample {
+            Some(target) => Some(target),
+            None => preset
+                .resample
+                .as_deref()
+                .or(user_config.resample.as_deref())
+                .map(ResampleTarget::from_str)
+                .transpose()
+                .context("Invalid resample in config")?,
+        };
+        let throttle_renames = match args.throttle_renames {
+            Some(rate) => Some(rate),
+            None => preset
+                .throttle_renames
+                .as_deref()
+                .or(user_config.throttle_renames.as_deref())
+                .map(Rate::from_str)
+                .transpose()
+                .context("Invalid throttle_renames in config")?,
+        };
+        Ok(Self {
+            artist_conflict: args
+                .artist_conflict
+                .or(preset.artist_conflict)
+                .or(user_config.artist_conflict)
+                .unwrap_or_default(),
+            artwork,
+            batch_threshold: args
+                .batch_threshold
+                .or(preset.batch_threshold)
+                .or(user_config.batch_threshold)
+                .filter(|&count| count > 1),
+            bpm_range: user_config.bpm_range.unwrap_or_default(),
+            bpm_rules: user_config.bpm_rules,
+            filename_separator: args
+                .filename_separator
+                .or(user_config.filename_separator)
+                .unwrap_or_default(),
+            canonical_name: args.canonical_name || preset.canonical_name || user_config.canonical_name,
+            check_aifc: args.check_aifc || fix_aifc || preset.check_aifc || user_config.check_aifc,
+            check_vbr: args.check_vbr || fix_vbr || preset.check_vbr || user_config.check_vbr,
+            convert_failed: !read_only && (args.convert || preset.convert || user_config.convert_failed),
+            copy_to: args.copy_to.clone(),
             debug: args.debug,
+            descriptor_statistics: args.descriptors || preset.descriptors || user_config.descriptor_statistics,
             excluded_tracks: user_config.exclude,
-            force: args.force,
-            genre_statistics: args.genre || user_config.genre_statistics,
-            log_failures: args.log || user_config.log_failures,
-            no_state: args.no_state || user_config.no_state,
-            print_only: args.print,
-            rename_files: args.rename,
-            sort_files: args.sort,
-            tags_only: args.tags_only,
+            feat_index: args.feat_index.clone(),
+            files_from: args.files_from.clone(),
+            fix_aifc,
+            fix_vbr,
+            force: args.force || preset.force,
+            format_rules: user_config.format_rules,
+            force_rules_version: args.force_rules_version,
+            genre_statistics: args.genre || preset.genre || user_config.genre_statistics,
+            io_threads: args
+                .io_threads
+                .or(preset.io_threads)
+                .or(user_config.io_threads)
+                .filter(|&threads| threads > 0),
+            // Linking would replace source files
+            link_duplicates: args.copy_to.is_none() && (args.link_duplicates || preset.link_duplicates),
+            minor_changes: args
+                .minor_changes
+                .or(preset.minor_changes)
+                .or(user_config.minor_changes),
+            minor_threshold: args
+                .minor_threshold
+                .or(preset.minor_threshold)
+                .or(user_config.minor_threshold)
+                .unwrap_or_default(),
+            log_failures: !read_only && (args.log || preset.log || user_config.log_failures),
+            max_changes: args.max_changes.or(preset.max_changes).or(user_config.max_changes),
+            new_playlist: args.new_playlist.clone(),
+            no_genre_warnings: args.no_genre_warnings || preset.no_genre_warnings || user_config.no_genre_warnings,
+            no_state: args.no_state || preset.no_state || user_config.no_state,
+            preview: args.preview.filter(|&count| count > 0),
+            print_only: read_only || args.print || preset.print,
+            preset: args.preset.clone(),
+            quick: args.quick || preset.quick,
+            read_only,
+            record_corpus: args.record_corpus.clone(),
+            rename_files: args.rename || preset.rename,
+            rename_journal: args.rename_journal.clone(),
+            repair_failed: !read_only && (args.repair || preset.repair || user_config.repair_failed),
+            replay: args.replay.clone(),
+            resample,
+            rules,
+            retries: args
+                .retries
+                .or(preset.retries)
+                .or(user_config.retries)
+                .unwrap_or_default(),
+            retry_delay: args
+                .retry_delay
+                .or(preset.retry_delay)
+                .or(user_config.retry_delay)
+                .unwrap_or(DEFAULT_RETRY_DELAY)
+                .max(0.0),
+            reveal: args
+                .reveal
+                .clone()
+                .map(|playlist| playlist.map_or(Reveal::Folder, Reveal::Playlist)),
+            safe_writes: args.safe_writes || preset.safe_writes || user_config.safe_writes,
+            serato_dir: serato_dir(user_config.serato_dir.as_deref()),
+            stats_report: args.stats_report.clone(),
+            strip_ape: args.strip_ape || preset.strip_ape,
+            strip_frames: strip_frames(&user_config.strip_frames, args.strip_chapters || preset.strip_chapters),
+            tag_limits: user_config.max_length,
+            tags_only: args.tags_only || preset.tags_only,
             test_mode: false,
-            verbose: args.verbose,
-            write_all_tags: args.all_tags,
-            overwrite_existing: args.overwrite,
+            throttle_renames,
+            profile: args.profile,
+            tolerant: args.tolerant || preset.tolerant || user_config.tolerant,
+            truncate_tags: args.truncate_tags || preset.truncate_tags || user_config.truncate_tags,
+            update_crates: args.update_crates || preset.update_crates,
+            update_playlist: args.update_playlist || preset.update_playlist,
+            upgrade_tags: args.upgrade_tags || preset.upgrade_tags,
+            user_config_file: user_config_path(),
+            verbose: args.verbose || preset.verbose,
+            verify_state: args.verify_state.filter(|&count| count > 0),
+            verify_writes: args.verify_writes || preset.verify_writes || user_config.verify_writes,
+            write_all_tags: args.all_tags || preset.all_tags,
+            write_id: args.write_id || preset.write_id || user_config.write_id,
+            year_in_title: args
+                .year_in_title
+                .or(preset.year_in_title)
+                .or(user_config.year_in_title)
+                .unwrap_or_default(),
+            subtitle: args
+                .subtitle
+                .or(preset.subtitle)
+                .or(user_config.subtitle)
+                .unwrap_or_default(),
+            album_artist: args
+                .album_artist
+                .or(preset.album_artist)
+                .or(user_config.album_artist)
+                .unwrap_or_default(),
+            overwrite_existing: args.overwrite || preset.overwrite,
+            order: args
+                .order
+                .or_else(|| (args.sort || preset.sort).then_some(TrackOrder::Name))
+                .or(preset.order)
+                .or(user_config.order),
+            placeholders: args
+                .placeholders
+                .or(preset.placeholders)
+                .or(user_config.placeholders)
+                .unwrap_or_default(),
+        })
+    }
+
+    /// Enabled rule categories from the given or configured categories without the skipped ones.
+    fn rule_set(args: &RenamerArgs, preset: &Preset, user_config: &UserConfig) -> RuleSet {
+        let rules = args
+            .rules
+            .as_ref()
+            .or(preset.rules.as_ref())
+            .or(user_config.rules.as_ref())
+            .map_or(RuleSet::ALL, |categories| categories.iter().copied().collect());
+        args.skip_rules
+            .iter()
+            .chain(&preset.skip_rules)
+            .chain(&user_config.skip_rules)
+            .fold(rules, |rules, &category| rules.without(category))
+    }
+
+    /// Options that change the formatted tags and filenames.
+    pub fn format_options(&self) -> FormatOptions {
+        FormatOptions {
+            rules: self.rules,
+            filename_separator: self.filename_separator,
+            year_in_title: self.year_in_title,
+            subtitle: self.subtitle,
+            album_artist: self.album_artist,
+            bpm_ranges: BpmRanges::new(self.bpm_range, self.bpm_rules.clone()),
         }
     }
 
+    /// Tracks are processed one directory at a time so directory headers and counts can be shown.
+    pub const fn directory_order(&self) -> bool {
+        matches!(self.order, None | Some(TrackOrder::Path))
+    }
+
+    /// Chapter frames are removed when writing tags.
+    pub fn strips_chapters(&self) -> bool {
+        utils::CHAPTER_FRAME_IDS
+            .iter()
+            .all(|id| self.strip_frames.iter().any(|frame_id| frame_id == id))
+    }
+
+    /// Only the formatting tag frames need to be read up front.
+    ///
+    /// Full tags are read only when writing.
+    /// Removing frames and resizing artwork need the full tag to find the chapter and picture frames.
+    pub const fn text_tags_only(&self) -> bool {
+        self.tags_only && !self.write_all_tags && !self.debug && self.strip_frames.is_empty() && self.artwork.is_none()
+    }
+
     #[cfg(test)]
     /// Used in test cases.
     pub fn new_for_tests() -> Self {
@@ -83,6 +672,140 @@ impl Config {
     }
 }
 
+/// Use the given user config file instead of the default locations.
+///
+/// Fails if the file does not exist or can not be parsed,
+/// since silently falling back to defaults would be surprising for an explicitly given file.
+pub fn set_config_path(path: &Path) -> anyhow::Result<()> {
+    let path = dunce::canonicalize(utils::expand_home_dir(&path.to_string_lossy()))
+        .with_context(|| format!("Config file not found: {}", path.display()))?;
+    let contents =
+        fs::read_to_string(&path).with_context(|| format!("Failed to read config file: {}", path.display()))?;
+    toml::from_str::<UserConfig>(&contents)
+        .with_context(|| format!("Failed to parse config file: {}", path.display()))?;
+    CONFIG_PATH_OVERRIDE
+        .set(path)
+        .map_err(|_| anyhow!("Config file path is already set"))
+}
+
+/// Get the path of the user config file that is used, if there is one.
+pub fn user_config_path() -> Option<PathBuf> {
+    UserConfig::user_config_file_path().ok()
+}
+
+/// Check that the user config file can be read and parsed.
+///
+/// Returns the path of the parsed file, or `None` if there is no user config file.
+pub fn check_user_config() -> anyhow::Result<Option<PathBuf>> {
+    let Some(path) = user_config_path() else {
+        return Ok(None);
+    };
+    let contents =
+        fs::read_to_string(&path).with_context(|| format!("Failed to read config file: {}", path.display()))?;
+    toml::from_str::<UserConfig>(&contents)
+        .with_context(|| format!("Failed to parse config file: {}", path.display()))?;
+    Ok(Some(path))
+}
+
+/// Get the preferred location for a new user config file.
+pub fn default_user_config_path() -> Option<PathBuf> {
+    UserConfig::config_file_candidates().into_iter().next()
+}
+
+/// Get the default answer for confirmation prompts from the user config file.
+pub fn confirm_default_yes() -> bool {
+    UserConfig::get_user_config().default_yes
+}
+
+/// Get the language for user-facing messages.
+///
+/// The user config setting takes precedence over the locale environment variables,
+/// and English is used if neither is set to a supported language.
+pub fn language() -> Language {
+    UserConfig::get_user_config()
+        .language
+        .and_then(|code| Language::from_code(&code))
+        .or_else(Language::from_env)
+        .unwrap_or_default()
+}
+
+/// Get the shared state location from the command line or the user config file.
+///
+/// Returns `None` when the default user data directory should be used.
+pub fn state_location(configured: Option<&StateLocation>) -> anyhow::Result<Option<StateLocation>> {
+    if let Some(location) = configured {
+        return Ok(Some(location.clone()));
+    }
+    UserConfig::get_user_config()
+        .state_location
+        .map(|location| StateLocation::from_str(&location))
+        .transpose()
+        .context("Invalid state_location in user config")
+}
+
+/// Get the formatting options from the user config file,
+/// with the separator between the artist and title from the command line if given.
+///
+/// Used by subcommands that format tracks without the full renamer config.
+pub fn format_options(filename_separator: Option<FilenameSeparator>) -> FormatOptions {
+    let user_config = UserConfig::get_user_config();
+    let rules = user_config
+        .rules
+        .as_ref()
+        .map_or(RuleSet::ALL, |categories| categories.iter().copied().collect());
+    let rules = user_config
+        .skip_rules
+        .iter()
+        .fold(rules, |rules, &category| rules.without(category));
+    FormatOptions {
+        rules,
+        filename_separator: filename_separator
+            .or(user_config.filename_separator)
+            .unwrap_or_default(),
+        year_in_title: user_config.year_in_title.unwrap_or_default(),
+        subtitle: user_config.subtitle.unwrap_or_default(),
+        album_artist: user_config.album_artist.unwrap_or_default(),
+        bpm_ranges: BpmRanges::new(user_config.bpm_range.unwrap_or_default(), user_config.bpm_rules),
+    }
+}
+
+/// Get the tag length limits from the user config file.
+pub fn user_tag_limits() -> TagLimits {
+    UserConfig::get_user_config().max_length
+}
+
+/// Get the Serato library directory from the user config file or the default location.
+pub fn user_serato_dir() -> Option<PathBuf> {
+    serato_dir(UserConfig::get_user_config().serato_dir.as_deref())
+}
+
+fn serato_dir(configured: Option<&str>) -> Option<PathBuf> {
+    configured
+        .map(utils::expand_home_dir)
+        .or_else(crates::default_serato_dir)
+}
+
+/// Frame IDs to remove when writing tags, with the chapter frames added if requested.
+fn strip_frames(configured: &[String], strip_chapters: bool) -> Vec<String> {
+    let mut frames: Vec<String> = configured.iter().map(|id| id.trim().to_uppercase()).collect();
+    if strip_chapters {
+        frames.extend(utils::CHAPTER_FRAME_IDS.iter().map(ToString::to_string));
+    }
+    frames.sort_unstable();
+    frames.dedup();
+    frames
+}
+
+/// Get Serato track colour rules from the user config file.
+pub fn serato_color_rules() -> Vec<ColorRule> {
+    UserConfig::get_user_config().color_rules
+}
+
+/// Get Serato cue point templates from the user config file.
+pub fn serato_cue_templates() -> Vec<CueTemplate> {
+    UserConfig::get_user_config().cue_templates
+}
+
 impl UserConfig {
     /// Try to read user config from file if it exists.
     /// Otherwise, fall back to default config.
@@ -99,14 +822,41 @@ impl UserConfig {
     }
 
     /// Get user config file if it exists.
+    ///
+    /// A path given with `--config` is always used.
+    /// Otherwise, the first existing file from `$XDG_CONFIG_HOME`, `~/.config`,
+    /// and the platform config directory is used.
     fn user_config_file_path() -> anyhow::Result<PathBuf> {
-        let home_dir = dirs::home_dir().context("Failed to get home directory path")?;
-        let config_path = home_dir.join(CONFIG_FILE_DIR).join(CONFIG_FILE_NAME);
-        if config_path.exists() {
-            Ok(config_path)
-        } else {
-            Err(anyhow!("Config file not found: {}", config_path.display()))
+        if let Some(path) = CONFIG_PATH_OVERRIDE.get() {
+            return Ok(path.clone());
+        }
+        let candidates = Self::config_file_candidates();
+        candidates.iter().find(|path| path.is_file()).cloned().ok_or_else(|| {
+            anyhow!(
+                "Config file not found in: {}",
+                candidates
+                    .iter()
+                    .map(|path| path.display().to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )
+        })
+    }
+
+    /// Possible user config file locations in order of precedence.
+    fn config_file_candidates() -> Vec<PathBuf> {
+        let xdg_config_home = std::env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .filter(|dir| dir.is_absolute());
+        let home_config = dirs::home_dir().map(|home| home.join(CONFIG_FILE_DIR));
+        let mut candidates: Vec<PathBuf> = Vec::new();
+        for dir in [xdg_config_home, home_config, dirs::config_dir()].into_iter().flatten() {
+            let path = dir.join(CONFIG_FILE_NAME);
+            if !candidates.contains(&path) {
+                candidates.push(path);
+            }
         }
+        candidates
     }
 }
 
@@ -115,18 +865,192 @@ impl fmt::Display for Config {
         // Serialize the struct to a serde_json::Value in place of reflection
         // to automatically handle each member variable.
         writeln!(f, "{}", "Config:".bold())?;
+        match &self.user_config_file {
+            Some(path) => writeln!(f, "  user_config_file: {}", path.display().to_string().cyan())?,
+            None => writeln!(f, "  user_config_file: None")?,
+        }
         writeln!(f, "  force: {}", utils::colorize_bool(self.force))?;
+        writeln!(
+            f,
+            "  force_rules_version: {}",
+            utils::colorize_bool(self.force_rules_version)
+        )?;
         writeln!(f, "  rename_files: {}", utils::colorize_bool(self.rename_files))?;
-        writeln!(f, "  sort_files: {}", utils::colorize_bool(self.sort_files))?;
+        match self.order {
+            Some(order) => writeln!(f, "  order: {}", format!("{order:?}").to_lowercase().cyan())?,
+            None => writeln!(f, "  order: None")?,
+        }
+        match self.preview {
+            Some(count) => writeln!(f, "  preview: {}", count.to_string().cyan())?,
+            None => writeln!(f, "  preview: None")?,
+        }
         writeln!(f, "  print_only: {}", utils::colorize_bool(self.print_only))?;
+        writeln!(f, "  read_only: {}", utils::colorize_bool(self.read_only))?;
+        match &self.copy_to {
+            Some(dir) => writeln!(f, "  copy_to: {}", dir.display().to_string().cyan())?,
+            None => writeln!(f, "  copy_to: None")?,
+        }
+        writeln!(f, "  quick: {}", utils::colorize_bool(self.quick))?;
         writeln!(f, "  tags_only: {}", utils::colorize_bool(self.tags_only))?;
+        writeln!(f, "  upgrade_tags: {}", utils::colorize_bool(self.upgrade_tags))?;
+        writeln!(f, "  strip_ape: {}", utils::colorize_bool(self.strip_ape))?;
+        writeln!(f, "  strip_frames: [{}]", self.strip_frames.join(", ").cyan())?;
         writeln!(f, "  verbose: {}", utils::colorize_bool(self.verbose))?;
         writeln!(f, "  debug: {}", utils::colorize_bool(self.debug))?;
+        match &self.preset {
+            Some(name) => writeln!(f, "  preset: {}", name.cyan())?,
+            None => writeln!(f, "  preset: None")?,
+        }
+        writeln!(f, "  profile: {}", utils::colorize_bool(self.profile))?;
         writeln!(f, "  test_mode: {}", utils::colorize_bool(self.test_mode))?;
         writeln!(f, "  log_failures: {}", utils::colorize_bool(self.log_failures))?;
         writeln!(f, "  convert_failed: {}", utils::colorize_bool(self.convert_failed))?;
+        writeln!(f, "  repair_failed: {}", utils::colorize_bool(self.repair_failed))?;
+        writeln!(f, "  tolerant: {}", utils::colorize_bool(self.tolerant))?;
+        writeln!(f, "  max_length: {}", self.tag_limits.to_string().cyan())?;
+        writeln!(f, "  truncate_tags: {}", utils::colorize_bool(self.truncate_tags))?;
+        writeln!(f, "  rules: {}", self.rules.to_string().cyan())?;
+        writeln!(
+            f,
+            "  filename_separator: {}",
+            format!("{:?}", self.filename_separator.as_str()).cyan()
+        )?;
+        writeln!(f, "  bpm_range: {}", self.bpm_range.to_string().cyan())?;
+        for rule in &self.bpm_rules {
+            let target = match (&rule.path, &rule.genre) {
+                (Some(path), _) => path.display().to_string(),
+                (None, Some(genre)) => genre.clone(),
+                (None, None) => continue,
+            };
+            writeln!(f, "  bpm_rule: {target} -> {}", rule.range().to_string().cyan())?;
+        }
+        writeln!(f, "  retries: {} ({}s delay)", self.retries, self.retry_delay)?;
+        match &self.reveal {
+            Some(Reveal::Folder) => writeln!(f, "  reveal: {}", "folder".cyan())?,
+            Some(Reveal::Playlist(path)) => writeln!(f, "  reveal: {}", path.display().to_string().cyan())?,
+            None => writeln!(f, "  reveal: None")?,
+        }
+        writeln!(
+            f,
+            "  placeholders: {}",
+            format!("{:?}", self.placeholders).to_lowercase().cyan()
+        )?;
+        writeln!(
+            f,
+            "  artist_conflict: {}",
+            format!("{:?}", self.artist_conflict).to_lowercase().cyan()
+        )?;
+        writeln!(f, "  safe_writes: {}", utils::colorize_bool(self.safe_writes))?;
+        match self.verify_state {
+            Some(count) => writeln!(f, "  verify_state: {}", count.to_string().cyan())?,
+            None => writeln!(f, "  verify_state: None")?,
+        }
+        writeln!(f, "  verify_writes: {}", utils::colorize_bool(self.verify_writes))?;
+        writeln!(f, "  write_id: {}", utils::colorize_bool(self.write_id))?;
+        writeln!(f, "  canonical_name: {}", utils::colorize_bool(self.canonical_name))?;
+        writeln!(
+            f,
+            "  year_in_title: {}",
+            format!("{:?}", self.year_in_title).to_lowercase().cyan()
+        )?;
+        writeln!(
+            f,
+            "  subtitle: {}",
+            format!("{:?}", self.subtitle).to_lowercase().cyan()
+        )?;
+        writeln!(
+            f,
+            "  album_artist: {}",
+            format!("{:?}", self.album_artist).to_lowercase().cyan()
+        )?;
+        writeln!(f, "  check_vbr: {}", utils::colorize_bool(self.check_vbr))?;
+        writeln!(f, "  fix_vbr: {}", utils::colorize_bool(self.fix_vbr))?;
+        writeln!(f, "  check_aifc: {}", utils::colorize_bool(self.check_aifc))?;
+        writeln!(f, "  fix_aifc: {}", utils::colorize_bool(self.fix_aifc))?;
+        match self.resample {
+            Some(target) => writeln!(f, "  resample: {}", target.to_string().cyan())?,
+            None => writeln!(f, "  resample: None")?,
+        }
+        match self.throttle_renames {
+            Some(rate) => writeln!(f, "  throttle_renames: {}", rate.to_string().cyan())?,
+            None => writeln!(f, "  throttle_renames: None")?,
+        }
+        match &self.rename_journal {
+            Some(path) => writeln!(f, "  rename_journal: {}", path.display().to_string().cyan())?,
+            None => writeln!(f, "  rename_journal: None")?,
+        }
+        match self.artwork {
+            Some(options) => writeln!(f, "  artwork: {}", options.to_string().cyan())?,
+            None => writeln!(f, "  artwork: None")?,
+        }
+        match self.batch_threshold {
+            Some(count) => writeln!(f, "  batch_threshold: {}", count.to_string().cyan())?,
+            None => writeln!(f, "  batch_threshold: None")?,
+        }
+        match self.io_threads {
+            Some(threads) => writeln!(f, "  io_threads: {}", threads.to_string().cyan())?,
+            None => writeln!(f, "  io_threads: None")?,
+        }
+        match self.max_changes {
+            Some(count) => writeln!(f, "  max_changes: {}", count.to_string().cyan())?,
+            None => writeln!(f, "  max_changes: None")?,
+        }
+        writeln!(f, "  update_crates: {}", utils::colorize_bool(self.update_crates))?;
+        match &self.serato_dir {
+            Some(dir) => writeln!(f, "  serato_dir: {}", dir.display().to_string().cyan())?,
+            None => writeln!(f, "  serato_dir: None")?,
+        }
+        writeln!(f, "  update_playlist: {}", utils::colorize_bool(self.update_playlist))?;
         writeln!(f, "  write_all_tags: {}", utils::colorize_bool(self.write_all_tags))?;
         writeln!(f, "  genre_statistics: {}", utils::colorize_bool(self.genre_statistics))?;
+        writeln!(
+            f,
+            "  no_genre_warnings: {}",
+            utils::colorize_bool(self.no_genre_warnings)
+        )?;
+        writeln!(
+            f,
+            "  descriptor_statistics: {}",
+            utils::colorize_bool(self.descriptor_statistics)
+        )?;
+        match &self.record_corpus {
+            Some(path) => writeln!(f, "  record_corpus: {}", path.display().to_string().cyan())?,
+            None => writeln!(f, "  record_corpus: None")?,
+        }
+        match &self.feat_index {
+            Some(path) => writeln!(f, "  feat_index: {}", path.display().to_string().cyan())?,
+            None => writeln!(f, "  feat_index: None")?,
+        }
+        match &self.files_from {
+            Some(path) => writeln!(f, "  files_from: {}", path.display().to_string().cyan())?,
+            None => writeln!(f, "  files_from: None")?,
+        }
+        match &self.stats_report {
+            Some(path) => writeln!(f, "  stats_report: {}", path.display().to_string().cyan())?,
+            None => writeln!(f, "  stats_report: None")?,
+        }
+        match &self.new_playlist {
+            Some(path) => writeln!(f, "  new_playlist: {}", path.display().to_string().cyan())?,
+            None => writeln!(f, "  new_playlist: None")?,
+        }
+        writeln!(f, "  link_duplicates: {}", utils::colorize_bool(self.link_duplicates))?;
+        match self.minor_changes {
+            Some(action) => writeln!(
+                f,
+                "  minor_changes: {} ({:?})",
+                format!("{action:?}").to_lowercase().cyan(),
+                self.minor_threshold
+            )?,
+            None => writeln!(f, "  minor_changes: None")?,
+        }
+        for rule in &self.format_rules {
+            writeln!(
+                f,
+                "  format_rule: {} -> {}",
+                rule.path.display(),
+                rule.format.to_string().cyan()
+            )?;
+        }
         if self.excluded_tracks.is_empty() {
             writeln!(f, "  excluded_tracks: []")?;
         } else {
@@ -145,9 +1069,12 @@ impl fmt::Display for Config {
 impl fmt::Display for UserConfig {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         writeln!(f, "{}", "UserConfig:".bold())?;
+        writeln!(f, "  check_aifc: {}", utils::colorize_bool(self.check_aifc))?;
+        writeln!(f, "  check_vbr: {}", utils::colorize_bool(self.check_vbr))?;
         writeln!(f, "  convert_failed: {}", utils::colorize_bool(self.convert_failed))?;
         writeln!(f, "  genre_statistics: {}", utils::colorize_bool(self.convert_failed))?;
         writeln!(f, "  log_failures: {}", utils::colorize_bool(self.convert_failed))?;
+        writeln!(f, "  repair_failed: {}", utils::colorize_bool(self.repair_failed))?;
         if self.exclude.is_empty() {
             writeln!(f, "  exclude: []")
         } else {
@@ -161,3 +1088,65 @@ impl fmt::Display for UserConfig {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_text_tags_only() {
+        let config = Config {
+            tags_only: true,
+            ..Config::default()
+        };
+        assert!(config.text_tags_only());
+
+        let config = Config {
+            tags_only: true,
+            strip_frames: strip_frames(&[], true),
+            ..Config::default()
+        };
+        assert!(config.strips_chapters());
+        assert!(!config.text_tags_only());
+
+        let config = Config {
+            tags_only: true,
+            artwork: Some(ArtworkOptions {
+                max_size: 1000,
+                format: ArtFormat::default(),
+                quality: artwork::DEFAULT_QUALITY,
+            }),
+            ..Config::default()
+        };
+        assert!(!config.text_tags_only());
+    }
+
+    #[test]
+    fn test_parse_presets() {
+        let config: UserConfig = toml::from_str(
+            r#"
+            exclude = []
+
+            [presets.quick]
+            tags_only = true
+            force = true
+
+            [presets.deep]
+            check_vbr = true
+            resample = "44100:16"
+            placeholders = "hydrate"
+            "#,
+        )
+        .expect("Failed to parse user config");
+        assert_eq!(config.presets.len(), 2);
+        let quick = &config.presets["quick"];
+        assert!(quick.tags_only && quick.force && !quick.rename);
+        let deep = &config.presets["deep"];
+        assert!(deep.check_vbr);
+        assert_eq!(deep.resample.as_deref(), Some("44100:16"));
+        assert_eq!(deep.placeholders, Some(Placeholders::Hydrate));
+
+        let typo = toml::from_str::<UserConfig>("exclude = []\n[presets.quick]\ntag_only = true\n");
+        assert!(typo.is_err());
+    }
+}
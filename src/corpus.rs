@@ -0,0 +1,124 @@
+use std::collections::BTreeSet;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Result, TrackRenameError};
+use crate::formatting;
+
+/// Original and formatted artist and title from a real run.
+///
+/// File paths are not recorded, so a corpus can be shared without exposing the library layout.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct CorpusEntry {
+    pub artist: String,
+    pub title: String,
+    pub formatted_artist: String,
+    pub formatted_title: String,
+}
+
+impl CorpusEntry {
+    /// Format the original artist and title with the current rules.
+    #[must_use]
+    pub fn new(artist: &str, title: &str) -> Self {
+        let (formatted_artist, formatted_title) = formatting::format_tags_for_artist_and_title(artist, title);
+        Self {
+            artist: artist.to_string(),
+            title: title.to_string(),
+            formatted_artist,
+            formatted_title,
+        }
+    }
+
+    /// Format the original artist and title again,
+    /// returning the current result if it differs from the recorded output.
+    #[must_use]
+    pub fn replay(&self) -> Option<Self> {
+        let current = Self::new(&self.artist, &self.title);
+        (current != *self).then_some(current)
+    }
+}
+
+/// Parse corpus entries from JSON lines.
+pub fn parse(contents: &str) -> Result<Vec<CorpusEntry>> {
+    contents
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(index, line)| {
+            serde_json::from_str(line).map_err(|error| {
+                TrackRenameError::InvalidArgument(format!("Invalid corpus entry on line {}: {error}", index + 1))
+            })
+        })
+        .collect()
+}
+
+/// Read corpus entries from a file.
+pub fn read(path: &Path) -> Result<Vec<CorpusEntry>> {
+    let contents = fs::read_to_string(path)
+        .map_err(|e| TrackRenameError::io(format!("Failed to read corpus: {}", path.display()), e))?;
+    parse(&contents)
+}
+
+/// Append entries that are not in the corpus file yet.
+///
+/// Returns the number of new entries.
+pub fn append<'a>(path: &Path, entries: impl IntoIterator<Item = &'a CorpusEntry>) -> Result<usize> {
+    let existing: BTreeSet<CorpusEntry> = match fs::read_to_string(path) {
+        Ok(contents) => parse(&contents)?.into_iter().collect(),
+        Err(error) if error.kind() == io::ErrorKind::NotFound => BTreeSet::new(),
+        Err(error) => {
+            return Err(TrackRenameError::io(
+                format!("Failed to read corpus: {}", path.display()),
+                error,
+            ));
+        }
+    };
+    let mut lines = String::new();
+    let mut count = 0;
+    for entry in entries.into_iter().filter(|entry| !existing.contains(entry)) {
+        let line = serde_json::to_string(entry)
+            .map_err(|error| TrackRenameError::InvalidArgument(format!("Failed to serialize corpus entry: {error}")))?;
+        lines.push_str(&line);
+        lines.push('\n');
+        count += 1;
+    }
+    if count > 0 {
+        let write_error = |e| TrackRenameError::io(format!("Failed to write corpus: {}", path.display()), e);
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(write_error)?;
+        file.write_all(lines.as_bytes()).map_err(write_error)?;
+    }
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_corpus_round_trip() {
+        let entry = CorpusEntry::new("Artist feat Other", "Song (Original Mix)");
+        assert_eq!(entry.formatted_artist, "Artist feat. Other");
+        assert_eq!(entry.formatted_title, "Song");
+        assert_eq!(entry.replay(), None);
+
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let path = dir.path().join("corpus.jsonl");
+        assert_eq!(append(&path, [&entry]).expect("Failed to append"), 1);
+        assert_eq!(append(&path, [&entry]).expect("Failed to append"), 0);
+        assert_eq!(read(&path).expect("Failed to read corpus"), vec![entry.clone()]);
+
+        let outdated = CorpusEntry {
+            formatted_title: "Song (Original Mix)".to_string(),
+            ..entry.clone()
+        };
+        assert_eq!(outdated.replay(), Some(entry));
+        assert!(parse("{}").is_err());
+    }
+}
@@ -0,0 +1,130 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use colored::Colorize;
+
+use crate::{CratesArgs, CratesCommand};
+
+use track_rename::formatting::FormatOptions;
+use track_rename::serato::crates::{self, SeratoCrate};
+use track_rename::utils;
+
+/// Run Serato crate subcommand.
+pub fn run(args: &CratesArgs, options: &FormatOptions) -> Result<()> {
+    let serato_dir = match &args.serato_dir {
+        Some(dir) => dir.clone(),
+        None => crates::default_serato_dir().context("Failed to get Serato library directory")?,
+    };
+    match &args.action {
+        CratesCommand::List { crates, missing } => list_crates(&serato_dir, crates, *missing),
+        CratesCommand::Genres { path, parent, print } => {
+            let root = utils::resolve_input_path(path)?;
+            organize_by_genre(&serato_dir, &root, parent, *print, options)
+        }
+    }
+}
+
+/// Print tracks in the given crates, or all crates if none are given.
+fn list_crates(serato_dir: &Path, names: &[String], missing_only: bool) -> Result<()> {
+    let crate_paths: Vec<PathBuf> = if names.is_empty() {
+        crates::crate_files(serato_dir)?
+    } else {
+        names
+            .iter()
+            .map(|name| {
+                let path = PathBuf::from(name);
+                if path.is_file() {
+                    path
+                } else {
+                    crates::crate_file_path(serato_dir, name)
+                }
+            })
+            .collect()
+    };
+
+    for crate_path in crate_paths {
+        let serato_crate = match SeratoCrate::read(&crate_path) {
+            Ok(serato_crate) => serato_crate,
+            Err(error) => {
+                utils::print_error(&format!("{error:#}"));
+                continue;
+            }
+        };
+        let tracks = serato_crate.track_paths(&crate_path);
+        let missing: Vec<&PathBuf> = tracks.iter().filter(|path| !path.exists()).collect();
+        if missing_only && missing.is_empty() {
+            continue;
+        }
+        println!(
+            "{} ({} tracks, {} missing)",
+            crates::crate_name(&crate_path).bold().magenta(),
+            tracks.len(),
+            missing.len()
+        );
+        for path in &tracks {
+            if !path.exists() {
+                println!("  {}", path.display().to_string().red());
+            } else if !missing_only {
+                println!("  {}", path.display());
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Add tracks to crates by their formatted genre tag.
+fn organize_by_genre(
+    serato_dir: &Path,
+    root: &Path,
+    parent: &str,
+    print_only: bool,
+    options: &FormatOptions,
+) -> Result<()> {
+    let mut tracks = utils::collect_tracks(root);
+
+    let mut genres: BTreeMap<String, Vec<PathBuf>> = BTreeMap::new();
+    for track in &mut tracks {
+        let Some(file_tags) = utils::read_tags(track, false) else {
+            continue;
+        };
+        track.format_tags(&file_tags, options);
+        if !track.tags.formatted_genre.is_empty() {
+            genres
+                .entry(track.tags.formatted_genre.clone())
+                .or_default()
+                .push(track.path.clone());
+        }
+    }
+
+    if genres.is_empty() {
+        println!("{}", "No tracks with genre tags found".yellow());
+        return Ok(());
+    }
+    if print_only {
+        println!("{}", "Running in print-only mode".yellow().bold());
+    }
+
+    for (genre, paths) in genres {
+        let crate_path = crates::crate_file_path(serato_dir, &format!("{parent}/{}", genre.replace('/', "-")));
+        let mut serato_crate = if crate_path.is_file() {
+            SeratoCrate::read(&crate_path)?
+        } else {
+            SeratoCrate::new()
+        };
+        let added = paths
+            .iter()
+            .filter(|path| serato_crate.add_track(path, &crate_path))
+            .count();
+        println!(
+            "{}: {} added, {} total",
+            crates::crate_name(&crate_path).cyan(),
+            added,
+            serato_crate.tracks.len()
+        );
+        if added > 0 && !print_only {
+            serato_crate.write(&crate_path)?;
+        }
+    }
+    Ok(())
+}
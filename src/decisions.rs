@@ -0,0 +1,261 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use track_rename::prompt::{Answer, ChangePattern};
+use track_rename::tags::TrackTags;
+
+const DECISION_LOG_FILE_NAME: &str = "track-rename-decisions.log";
+
+/// Change that was confirmed interactively.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Action {
+    Tags,
+    Rename,
+    Link,
+    Upgrade,
+}
+
+/// One interactive answer for a track.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Decision {
+    pub path: PathBuf,
+    pub action: Action,
+    pub answer: Answer,
+    /// Tag values entered by the user before answering
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub edited: Option<EditedTags>,
+}
+
+/// Formatted tag values after editing.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EditedTags {
+    pub artist: String,
+    pub title: String,
+    pub album: String,
+    pub genre: String,
+}
+
+/// Log of interactive answers for the current run,
+/// and optionally answers from a previous run to replay.
+///
+/// Decisions are appended to the log file as JSON lines as soon as they are made,
+/// so the log is usable even if the run is interrupted.
+#[derive(Debug, Default)]
+pub struct DecisionLog {
+    file: Option<File>,
+    /// The log file is the one being replayed, so this run is not logged
+    replaying_log: bool,
+    count: usize,
+    replay: HashMap<(PathBuf, Action), Decision>,
+    /// Changes accepted at the prompt in this run
+    accepted: usize,
+    /// Changes declined in this run, including replayed answers
+    declined: usize,
+    /// Changes accepted once for all tracks with the same pattern
+    approved_patterns: HashMap<Action, HashSet<ChangePattern>>,
+    /// All remaining changes were accepted at the prompt
+    accept_all: bool,
+}
+
+impl DecisionLog {
+    /// Load decisions to replay from a previous decision log.
+    ///
+    /// Decisions are not logged when replaying the log file itself,
+    /// so an interrupted run can not mix its answers with the ones being replayed.
+    pub fn replay(path: &Path) -> Result<Self> {
+        let contents =
+            fs::read_to_string(path).with_context(|| format!("Failed to read decision log: {}", path.display()))?;
+        let replay = Self::parse(&contents)?
+            .into_iter()
+            .map(|decision| ((decision.path.clone(), decision.action), decision))
+            .collect();
+        let replaying_log = Self::path().ok() == dunce::canonicalize(path).ok();
+        Ok(Self {
+            replaying_log,
+            replay,
+            ..Self::default()
+        })
+    }
+
+    /// Parse decisions from JSON lines.
+    pub fn parse(contents: &str) -> Result<Vec<Decision>> {
+        contents
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .enumerate()
+            .map(|(index, line)| {
+                serde_json::from_str(line).with_context(|| format!("Invalid decision on line {}", index + 1))
+            })
+            .collect()
+    }
+
+    /// Get the replayed decision for a track and action if there is one.
+    #[must_use]
+    pub fn replayed(&self, path: &Path, action: Action) -> Option<&Decision> {
+        self.replay.get(&(path.to_path_buf(), action))
+    }
+
+    /// Accept all changes with this pattern without asking again.
+    pub fn approve_pattern(&mut self, action: Action, pattern: ChangePattern) {
+        self.approved_patterns.entry(action).or_default().insert(pattern);
+    }
+
+    /// Return true if changes with this pattern were accepted for all similar tracks.
+    #[must_use]
+    pub fn pattern_approved(&self, action: Action, pattern: &ChangePattern) -> bool {
+        self.approved_patterns
+            .get(&action)
+            .is_some_and(|patterns| patterns.contains(pattern))
+    }
+
+    /// Accept all remaining changes without asking again.
+    pub const fn accept_all(&mut self) {
+        self.accept_all = true;
+    }
+
+    /// Return true if all remaining changes were accepted at the prompt.
+    #[must_use]
+    pub const fn all_accepted(&self) -> bool {
+        self.accept_all
+    }
+
+    /// Return true if this run is not logged because the log file is being replayed.
+    #[must_use]
+    pub const fn replaying_log(&self) -> bool {
+        self.replaying_log
+    }
+
+    /// Number of replayable decisions.
+    #[must_use]
+    pub fn replay_count(&self) -> usize {
+        self.replay.len()
+    }
+
+    /// Number of decisions logged in this run.
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.count
+    }
+
+    /// Number of changes accepted at the prompt in this run.
+    #[must_use]
+    pub const fn accepted(&self) -> usize {
+        self.accepted
+    }
+
+    /// Count a change accepted at the prompt or with accept all, as opposed to a replayed or batch-approved answer.
+    pub const fn record_accepted(&mut self) {
+        self.accepted += 1;
+    }
+
+    /// Number of changes declined in this run.
+    #[must_use]
+    pub const fn declined(&self) -> usize {
+        self.declined
+    }
+
+    /// Count a change that was answered with no or quit.
+    pub const fn record_declined(&mut self) {
+        self.declined += 1;
+    }
+
+    /// Append decision to the log file.
+    pub fn add(&mut self, decision: &Decision) -> Result<()> {
+        if self.replaying_log {
+            return Ok(());
+        }
+        if self.file.is_none() {
+            let file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(DECISION_LOG_FILE_NAME)
+                .context("Failed to open decision log")?;
+            self.file = Some(file);
+        }
+        if let Some(file) = self.file.as_mut() {
+            writeln!(file, "{}", serde_json::to_string(decision)?)?;
+        }
+        self.count += 1;
+        Ok(())
+    }
+
+    /// Path to the decision log file.
+    pub fn path() -> Result<PathBuf> {
+        Ok(dunce::canonicalize(DECISION_LOG_FILE_NAME)?)
+    }
+}
+
+impl EditedTags {
+    /// Get current formatted values.
+    #[must_use]
+    pub fn from_tags(tags: &TrackTags) -> Self {
+        Self {
+            artist: tags.formatted_artist.clone(),
+            title: tags.formatted_title.clone(),
+            album: tags.formatted_album.clone(),
+            genre: tags.formatted_genre.clone(),
+        }
+    }
+
+    /// Set formatted values.
+    pub fn apply(&self, tags: &mut TrackTags) {
+        tags.formatted_artist.clone_from(&self.artist);
+        tags.formatted_title.clone_from(&self.title);
+        tags.formatted_album.clone_from(&self.album);
+        tags.formatted_genre.clone_from(&self.genre);
+        tags.formatted_name = format!("{} - {}", self.artist, self.title);
+    }
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Tags => write!(f, "tags"),
+            Self::Rename => write!(f, "rename"),
+            Self::Link => write!(f, "link"),
+            Self::Upgrade => write!(f, "upgrade"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_decisions() {
+        let decision = Decision {
+            path: PathBuf::from("/music/Artist - Song.mp3"),
+            action: Action::Tags,
+            answer: Answer::Yes,
+            edited: Some(EditedTags {
+                artist: "Artist".to_string(),
+                title: "Song (Extended Mix)".to_string(),
+                album: String::new(),
+                genre: "House".to_string(),
+            }),
+        };
+        let line = serde_json::to_string(&decision).expect("Failed to serialize decision");
+        assert!(line.contains(r#""action":"tags""#));
+        assert!(line.contains(r#""answer":"yes""#));
+
+        let contents = format!(
+            "{line}\n\n{}\n",
+            r#"{"path":"/music/Other.mp3","action":"rename","answer":"quit"}"#
+        );
+        let decisions = DecisionLog::parse(&contents).expect("Failed to parse decisions");
+        assert_eq!(decisions.len(), 2);
+        assert_eq!(decisions[0], decision);
+        assert_eq!(decisions[1].answer, Answer::Quit);
+        assert_eq!(decisions[1].edited, None);
+
+        assert!(DecisionLog::parse("not json").is_err());
+    }
+}
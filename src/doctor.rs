@@ -0,0 +1,260 @@
+use std::fs;
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Result};
+use colored::Colorize;
+
+use crate::config;
+use crate::DoctorArgs;
+
+use track_rename::serato::database::DATABASE_FILE_NAME;
+use track_rename::state;
+use track_rename::state_store::StateLocation;
+use track_rename::utils;
+
+/// Known Serato DJ application install locations.
+#[cfg(target_os = "macos")]
+const SERATO_APP_PATHS: &[&str] = &["/Applications/Serato DJ Pro.app", "/Applications/Serato DJ Lite.app"];
+#[cfg(target_os = "windows")]
+const SERATO_APP_PATHS: &[&str] = &[
+    r"C:\Program Files\Serato\Serato DJ Pro",
+    r"C:\Program Files\Serato\Serato DJ Lite",
+];
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+const SERATO_APP_PATHS: &[&str] = &[];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Status {
+    Ok,
+    Warning,
+    Error,
+}
+
+/// Result of one environment check with guidance for fixing it.
+#[derive(Debug)]
+struct Check {
+    name: &'static str,
+    status: Status,
+    detail: String,
+    guidance: Option<String>,
+}
+
+impl Check {
+    fn ok(name: &'static str, detail: impl Into<String>) -> Self {
+        Self {
+            name,
+            status: Status::Ok,
+            detail: detail.into(),
+            guidance: None,
+        }
+    }
+
+    fn warning(name: &'static str, detail: impl Into<String>, guidance: impl Into<String>) -> Self {
+        Self {
+            name,
+            status: Status::Warning,
+            detail: detail.into(),
+            guidance: Some(guidance.into()),
+        }
+    }
+
+    fn error(name: &'static str, detail: impl Into<String>, guidance: impl Into<String>) -> Self {
+        Self {
+            name,
+            status: Status::Error,
+            detail: detail.into(),
+            guidance: Some(guidance.into()),
+        }
+    }
+
+    fn print(&self) {
+        let status = match self.status {
+            Status::Ok => "OK".green().bold(),
+            Status::Warning => "WARN".yellow().bold(),
+            Status::Error => "FAIL".red().bold(),
+        };
+        println!("{status:<4}  {}: {}", self.name.bold(), self.detail);
+        if let Some(guidance) = &self.guidance {
+            println!("      {}", guidance.cyan());
+        }
+    }
+}
+
+/// Check the environment and print guidance for any problems found.
+///
+/// Fails if a check finds a problem that prevents normal use.
+pub fn run(args: &DoctorArgs) -> Result<()> {
+    let root = utils::resolve_input_path(&args.path)?;
+    let root_dir = if root.is_dir() {
+        root
+    } else {
+        root.parent().map(Path::to_path_buf).unwrap_or(root)
+    };
+    let serato_dir = args.serato_dir.clone().or_else(config::user_serato_dir);
+
+    let checks = [
+        check_ffmpeg("ffmpeg"),
+        check_ffmpeg("ffprobe"),
+        check_state_location(state::location()),
+        check_user_config(),
+        check_terminal(),
+        check_case_sensitivity(&root_dir),
+        check_serato(serato_dir.as_deref()),
+    ];
+    for check in &checks {
+        check.print();
+    }
+
+    let errors = checks.iter().filter(|check| check.status == Status::Error).count();
+    let warnings = checks.iter().filter(|check| check.status == Status::Warning).count();
+    println!();
+    if errors > 0 {
+        bail!("{errors} check(s) failed and {warnings} had warnings");
+    }
+    if warnings > 0 {
+        println!("{}", format!("No errors, {warnings} warning(s)").yellow());
+    } else {
+        println!("{}", "All checks passed".green());
+    }
+    Ok(())
+}
+
+fn check_ffmpeg(program: &'static str) -> Check {
+    utils::ffmpeg_version(program).map_or_else(
+        || {
+            Check::warning(
+                program,
+                "not found in PATH",
+                "Install FFmpeg to convert and resample files, for example `brew install ffmpeg` or `apt install ffmpeg`",
+            )
+        },
+        |version| Check::ok(program, format!("version {version}")),
+    )
+}
+
+fn check_state_location(location: &StateLocation) -> Check {
+    const NAME: &str = "State location";
+    match location {
+        StateLocation::Directory(dir) => match check_writable(dir) {
+            Ok(()) => Check::ok(NAME, dir.display().to_string()),
+            Err(error) => Check::error(
+                NAME,
+                format!("{} is not writable: {error}", dir.display()),
+                "Fix the directory permissions, or use `--no-state` to run without saving state",
+            ),
+        },
+        StateLocation::Remote(url) => match location.check() {
+            Ok(()) => Check::ok(NAME, url.clone()),
+            Err(error) => Check::error(
+                NAME,
+                format!("{url} is not reachable: {error:#}"),
+                "Check the URL and the credentials in ~/.netrc, or use `--no-state` to run without saving state",
+            ),
+        },
+    }
+}
+
+fn check_user_config() -> Check {
+    const NAME: &str = "User config";
+    match config::check_user_config() {
+        Ok(Some(path)) => Check::ok(NAME, path.display().to_string()),
+        Ok(None) => missing_user_config(NAME),
+        Err(error) => Check::error(
+            NAME,
+            format!("{error:#}"),
+            "Fix the config file or compare it with track-rename.toml in the repository",
+        ),
+    }
+}
+
+fn missing_user_config(name: &'static str) -> Check {
+    let Some(path) = config::default_user_config_path() else {
+        return Check::warning(
+            name,
+            "not found, using defaults",
+            "Use `--config PATH` since the config directory could not be determined",
+        );
+    };
+    match path.parent() {
+        Some(dir) if dir.is_dir() && check_writable(dir).is_err() => Check::warning(
+            name,
+            format!("not found, and {} is not writable", dir.display()),
+            "Fix the config directory permissions or use `--config PATH`",
+        ),
+        _ => Check::warning(
+            name,
+            "not found, using defaults",
+            format!("Copy track-rename.toml from the repository to {}", path.display()),
+        ),
+    }
+}
+
+fn check_terminal() -> Check {
+    const NAME: &str = "Terminal colours";
+    if std::env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty()) {
+        Check::warning(NAME, "disabled by NO_COLOR", "Unset NO_COLOR to see coloured diffs")
+    } else if !std::io::stdout().is_terminal() {
+        Check::warning(
+            NAME,
+            "output is not a terminal",
+            "Colours are disabled when output is redirected, set CLICOLOR_FORCE=1 to force them",
+        )
+    } else if std::env::var("TERM").is_ok_and(|term| term == "dumb") {
+        Check::warning(NAME, "TERM is set to dumb", "Use a terminal that supports ANSI colours")
+    } else {
+        Check::ok(NAME, "supported")
+    }
+}
+
+fn check_case_sensitivity(root: &Path) -> Check {
+    const NAME: &str = "Filesystem";
+    match utils::is_case_sensitive(root) {
+        Ok(true) => Check::ok(NAME, format!("case-sensitive: {}", root.display())),
+        Ok(false) => Check::ok(NAME, format!("case-insensitive: {}", root.display())),
+        Err(error) => Check::error(
+            NAME,
+            format!("{error}"),
+            "The library directory must be writable for renaming files",
+        ),
+    }
+}
+
+fn check_serato(serato_dir: Option<&Path>) -> Check {
+    const NAME: &str = "Serato";
+    let app = SERATO_APP_PATHS.iter().map(PathBuf::from).find(|path| path.exists());
+    let Some(serato_dir) = serato_dir else {
+        return Check::warning(
+            NAME,
+            "library directory not found",
+            "Set `serato_dir` in the user config or use `--serato-dir`",
+        );
+    };
+    if serato_dir.join(DATABASE_FILE_NAME).is_file() {
+        let detail = app.map_or_else(
+            || format!("library {}", serato_dir.display()),
+            |app| format!("library {}, app {}", serato_dir.display(), app.display()),
+        );
+        Check::ok(NAME, detail)
+    } else if let Some(app) = app {
+        Check::warning(
+            NAME,
+            format!("{} found but no library in {}", app.display(), serato_dir.display()),
+            "Start Serato once to create the library, or set `serato_dir` in the user config",
+        )
+    } else {
+        Check::warning(
+            NAME,
+            format!("not installed, no library in {}", serato_dir.display()),
+            "Serato features like `--update-crates` and `relocate` are not available",
+        )
+    }
+}
+
+/// Check a directory can be created and written to.
+fn check_writable(dir: &Path) -> std::io::Result<()> {
+    fs::create_dir_all(dir)?;
+    let probe = dir.join(format!(".track-rename-write-probe-{}", std::process::id()));
+    fs::write(&probe, b"")?;
+    fs::remove_file(&probe)
+}
@@ -0,0 +1,159 @@
+use id3::frame::{Content, Frame};
+use id3::{Tag, TagLike};
+
+/// Frames that tools identify by description, so only one frame per description is expected.
+const DESCRIBED_FRAMES: [&str; 2] = ["TXXX", "GEOB"];
+
+/// `TXXX` and `GEOB` frames that share a description with a later frame.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DuplicateFrames {
+    /// `ID:description` for each redundant frame
+    pub frames: Vec<String>,
+    /// Redundant frames with a different value than the frame that is kept
+    pub conflicting: usize,
+}
+
+impl DuplicateFrames {
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+}
+
+/// Find duplicate `TXXX` and `GEOB` frames from the frames as they are stored in the file.
+///
+/// The last frame for each description is the one kept,
+/// same as when the id3 crate reads frames with the same description and encoding.
+#[must_use]
+pub fn find(frames: &[Frame]) -> DuplicateFrames {
+    let mut duplicates = DuplicateFrames::default();
+    for (index, frame) in frames.iter().enumerate() {
+        let Some(description) = description(frame) else {
+            continue;
+        };
+        let Some(kept) = frames[index + 1..]
+            .iter()
+            .rev()
+            .find(|other| is_same_frame(other, frame.id(), description))
+        else {
+            continue;
+        };
+        if kept.content() != frame.content() {
+            duplicates.conflicting += 1;
+        }
+        duplicates.frames.push(format!("{}:{description}", frame.id()));
+    }
+    duplicates
+}
+
+/// Remove all but the last `TXXX` and `GEOB` frame for each description.
+///
+/// Frames with the same description but a different text encoding are still separate after reading the tag.
+/// Returns the number of removed frames.
+pub fn merge(tag: &mut Tag) -> usize {
+    let frames: Vec<Frame> = tag.frames().cloned().collect();
+    let mut removed = 0;
+    for (index, frame) in frames.iter().enumerate() {
+        let Some(description) = description(frame) else {
+            continue;
+        };
+        // Handle each description once at its last frame
+        if frames[index + 1..]
+            .iter()
+            .any(|other| is_same_frame(other, frame.id(), description))
+        {
+            continue;
+        }
+        let count = frames
+            .iter()
+            .filter(|other| is_same_frame(other, frame.id(), description))
+            .count();
+        if count < 2 {
+            continue;
+        }
+        if frame.id() == "TXXX" {
+            tag.remove_extended_text(Some(description), None);
+        } else {
+            tag.remove_encapsulated_object(Some(description), None, None, None);
+        }
+        tag.add_frame(frame.clone());
+        removed += count - 1;
+    }
+    removed
+}
+
+/// Description of a `TXXX` or `GEOB` frame.
+fn description(frame: &Frame) -> Option<&str> {
+    if !DESCRIBED_FRAMES.contains(&frame.id()) {
+        return None;
+    }
+    match frame.content() {
+        Content::ExtendedText(text) => Some(&text.description),
+        Content::EncapsulatedObject(object) => Some(&object.description),
+        _ => None,
+    }
+}
+
+fn is_same_frame(frame: &Frame, id: &str, description: &str) -> bool {
+    frame.id() == id && self::description(frame) == Some(description)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use id3::frame::{EncapsulatedObject, ExtendedText};
+
+    fn text(description: &str, value: &str) -> Frame {
+        ExtendedText {
+            description: description.to_string(),
+            value: value.to_string(),
+        }
+        .into()
+    }
+
+    fn object(description: &str, data: &[u8]) -> Frame {
+        EncapsulatedObject {
+            mime_type: "application/octet-stream".to_string(),
+            filename: String::new(),
+            description: description.to_string(),
+            data: data.to_vec(),
+        }
+        .into()
+    }
+
+    #[test]
+    fn test_find_duplicates() {
+        let frames = vec![
+            text("SERATO_PLAYCOUNT", "1"),
+            object("Serato Markers_", b"old"),
+            text("SERATO_PLAYCOUNT", "1"),
+            text("CANONICAL_NAME", "Artist - Song"),
+            object("Serato Markers_", b"new"),
+            object("Serato Overview", b"data"),
+        ];
+        let duplicates = find(&frames);
+        assert_eq!(duplicates.frames, vec!["TXXX:SERATO_PLAYCOUNT", "GEOB:Serato Markers_"]);
+        assert_eq!(duplicates.conflicting, 1);
+        assert!(find(&frames[3..]).is_empty());
+    }
+
+    #[test]
+    fn test_merge_different_encodings() {
+        let mut tag = Tag::new();
+        tag.add_frame(text("SERATO_PLAYCOUNT", "1").set_encoding(Some(id3::Encoding::Latin1)));
+        tag.add_frame(text("SERATO_PLAYCOUNT", "2").set_encoding(Some(id3::Encoding::UTF16)));
+        tag.add_frame(text("CANONICAL_NAME", "Artist - Song"));
+        assert_eq!(tag.extended_texts().count(), 3);
+
+        assert_eq!(merge(&mut tag), 1);
+        let values: Vec<&str> = tag
+            .extended_texts()
+            .filter(|text| text.description == "SERATO_PLAYCOUNT")
+            .map(|text| text.value.as_str())
+            .collect();
+        assert_eq!(values, vec!["2"]);
+        assert_eq!(tag.extended_texts().count(), 2);
+        assert_eq!(merge(&mut tag), 0);
+    }
+}
@@ -0,0 +1,110 @@
+use std::io;
+use std::path::{Path, PathBuf};
+
+use thiserror::Error;
+
+/// Result type used in the library modules.
+pub type Result<T, E = TrackRenameError> = std::result::Result<T, E>;
+/// Errors returned by the library modules.
+/// Errors returned by the track handling, file utility, and Serato modules.
+///
+/// The binaries convert these to `anyhow` errors and add context where needed.
+#[derive(Debug, Error)]
+pub enum TrackRenameError {
+    /// Reading the ID3 tag of a file failed.
+    #[error("Failed to read tags: {}", path.display())]
+    TagRead {
+        path: PathBuf,
+        #[source]
+        source: id3::Error,
+    },
+    /// Writing the ID3 tag of a file failed.
+    #[error("Failed to write tags: {}", path.display())]
+    TagWrite {
+        path: PathBuf,
+        #[source]
+        source: id3::Error,
+    },
+    /// Renaming or replacing a file failed.
+    #[error("Failed to rename {} to {}", from.display(), to.display())]
+    Rename {
+        from: PathBuf,
+        to: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+    /// Audio conversion with ffmpeg failed.
+    #[error("FFmpeg error: {0}")]
+    Convert(String),
+    /// Reading audio properties with ffprobe failed.
+    #[error("FFprobe error: {0}")]
+    Probe(String),
+    /// File extension or audio format is not supported.
+    #[error("Unsupported file format: {0}")]
+    UnsupportedFormat(String),
+    /// File does not exist.
+    #[error("File does not exist: {}", .0.display())]
+    NotFound(PathBuf),
+    /// Path is missing a required component or is not valid Unicode.
+    #[error("{reason}: {}", path.display())]
+    InvalidPath { path: PathBuf, reason: &'static str },
+    /// Audio data could not be parsed.
+    #[error("{0}")]
+    InvalidAudio(String),
+    /// Serato tag, crate, or database data could not be parsed.
+    #[error("{0}")]
+    InvalidSerato(String),
+    /// Command line value could not be parsed.
+    #[error("{0}")]
+    InvalidArgument(String),
+    /// Request to a remote state location or online database failed.
+    #[error("{0}")]
+    Request(String),
+    /// JSON data could not be parsed or serialized.
+    #[error("{message}")]
+    Json {
+        message: String,
+        #[source]
+        source: serde_json::Error,
+    },
+    /// File was changed by another process while saving.
+    #[error("{0}")]
+    Conflict(String),
+    /// Moving a file to the trash failed.
+    #[error("Failed to move file to trash")]
+    Trash(#[from] trash::Error),
+    /// Other file system operation failed.
+    #[error("{message}")]
+    Io {
+        message: String,
+        #[source]
+        source: io::Error,
+    },
+}
+
+impl TrackRenameError {
+    /// IO error with a description of the failed operation.
+    pub fn io(message: impl Into<String>, source: io::Error) -> Self {
+        Self::Io {
+            message: message.into(),
+            source,
+        }
+    }
+
+    /// JSON error with a description of the failed operation.
+    pub fn json(message: impl Into<String>, source: serde_json::Error) -> Self {
+        Self::Json {
+            message: message.into(),
+            source,
+        }
+    }
+
+    /// Path error for a missing or invalid path component.
+    #[must_use]
+    pub fn invalid_path(path: &Path, reason: &'static str) -> Self {
+        Self::InvalidPath {
+            path: path.to_path_buf(),
+            reason,
+        }
+    }
+}
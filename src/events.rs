@@ -0,0 +1,157 @@
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::Path;
+use std::sync::{Mutex, OnceLock};
+
+use serde::Serialize;
+
+use crate::error::{Result, TrackRenameError};
+use crate::prompt::FieldChange;
+
+/// Destination for progress events, if enabled.
+static EVENT_SINK: OnceLock<Mutex<Box<dyn Write + Send>>> = OnceLock::new();
+
+/// Structured progress event for frontends.
+///
+/// Events are written as JSON lines separately from the human-readable output.
+#[derive(Debug, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event<'a> {
+    RunStarted {
+        total: usize,
+    },
+    TrackStarted {
+        index: usize,
+        total: usize,
+        path: &'a Path,
+        /// Stable identifier that stays the same across runs, unlike the index
+        id: &'a str,
+    },
+    DiffComputed {
+        path: &'a Path,
+        action: &'a str,
+        changes: &'a [FieldChange],
+    },
+    ActionApplied {
+        path: &'a Path,
+        action: &'a str,
+    },
+    ActionSkipped {
+        path: &'a Path,
+        action: &'a str,
+    },
+    RunFinished {
+        tags_fixed: usize,
+        renamed: usize,
+        failed: usize,
+        /// Total bytes removed from files during the run
+        bytes_saved: u64,
+        /// Total bytes added to files during the run
+        bytes_added: u64,
+    },
+}
+
+/// Enable progress events.
+///
+/// The target is either `stderr` or a path to a file, named pipe, or Unix socket.
+pub fn init(target: &str) -> Result<()> {
+    let sink: Box<dyn Write + Send> = if target == "stderr" {
+        Box::new(std::io::stderr())
+    } else {
+        open_path(Path::new(target))?
+    };
+    EVENT_SINK
+        .set(Mutex::new(sink))
+        .map_err(|_| TrackRenameError::InvalidArgument("Progress events are already enabled".to_string()))
+}
+
+/// Write event if progress events are enabled.
+///
+/// Write errors are ignored so a disconnected frontend does not stop processing.
+pub fn emit(event: &Event) {
+    let Some(sink) = EVENT_SINK.get() else {
+        return;
+    };
+    let Ok(json) = serde_json::to_string(event) else {
+        return;
+    };
+    if let Ok(mut writer) = sink.lock() {
+        let _ = writeln!(writer, "{json}").and_then(|()| writer.flush());
+    }
+}
+
+#[cfg(unix)]
+fn open_path(path: &Path) -> Result<Box<dyn Write + Send>> {
+    use std::os::unix::fs::FileTypeExt;
+    use std::os::unix::net::UnixStream;
+
+    if path.metadata().is_ok_and(|metadata| metadata.file_type().is_socket()) {
+        let stream = UnixStream::connect(path).map_err(|error| {
+            TrackRenameError::io(format!("Failed to connect to event socket: {}", path.display()), error)
+        })?;
+        return Ok(Box::new(stream));
+    }
+    open_file(path)
+}
+
+#[cfg(not(unix))]
+fn open_path(path: &Path) -> Result<Box<dyn Write + Send>> {
+    open_file(path)
+}
+
+fn open_file(path: &Path) -> Result<Box<dyn Write + Send>> {
+    let file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|error| TrackRenameError::io(format!("Failed to open event output: {}", path.display()), error))?;
+    Ok(Box::new(file))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_event_json() {
+        let path = Path::new("/music/Artist - Song.mp3");
+        let started = serde_json::to_string(&Event::TrackStarted {
+            index: 1,
+            total: 10,
+            path,
+            id: "Artist - Song.mp3",
+        })
+        .expect("Failed to serialize event");
+        assert_eq!(
+            started,
+            r#"{"event":"track_started","index":1,"total":10,"path":"/music/Artist - Song.mp3","id":"Artist - Song.mp3"}"#
+        );
+
+        let changes = [FieldChange {
+            field: "genre".to_string(),
+            current: "house".to_string(),
+            proposed: "House".to_string(),
+        }];
+        let diff = serde_json::to_string(&Event::DiffComputed {
+            path,
+            action: "tags",
+            changes: &changes,
+        })
+        .expect("Failed to serialize event");
+        assert!(diff.starts_with(r#"{"event":"diff_computed","path":"/music/Artist - Song.mp3","action":"tags""#));
+        assert!(diff.contains(r#""proposed":"House""#));
+
+        let finished = serde_json::to_string(&Event::RunFinished {
+            tags_fixed: 1,
+            renamed: 0,
+            failed: 0,
+            bytes_saved: 2184,
+            bytes_added: 0,
+        })
+        .expect("Failed to serialize event");
+        assert_eq!(
+            finished,
+            r#"{"event":"run_finished","tags_fixed":1,"renamed":0,"failed":0,"bytes_saved":2184,"bytes_added":0}"#
+        );
+    }
+}
@@ -0,0 +1,386 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use colored::Colorize;
+use id3::{Tag, TagLike};
+use unicode_normalization::UnicodeNormalization;
+
+use crate::ExportArgs;
+
+use track_rename::file_format::FileFormat;
+use track_rename::formatting::FormatOptions;
+use track_rename::track::Track;
+use track_rename::utils;
+
+/// Characters that are not allowed in FAT32 and exFAT filenames.
+const FAT_RESERVED_CHARACTERS: [char; 9] = ['<', '>', ':', '"', '/', '\\', '|', '?', '*'];
+
+/// Non-ASCII letters that do not decompose to an ASCII base character.
+const ASCII_REPLACEMENTS: [(char, &str); 14] = [
+    ('ß', "ss"),
+    ('æ', "ae"),
+    ('Æ', "AE"),
+    ('ø', "o"),
+    ('Ø', "O"),
+    ('œ', "oe"),
+    ('Œ', "OE"),
+    ('đ', "d"),
+    ('Đ', "D"),
+    ('ł', "l"),
+    ('Ł', "L"),
+    ('þ', "th"),
+    ('’', "'"),
+    ('‘', "'"),
+];
+
+/// Output file format for exported tracks.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum ExportFormat {
+    /// Keep the original file format
+    #[default]
+    Original,
+    /// Convert MP3 files to AIFF
+    Aiff,
+    /// Convert AIFF files to MP3
+    Mp3,
+}
+
+/// Copy formatted tracks to another location without modifying the source files.
+#[derive(Debug)]
+pub struct Exporter {
+    inputs: Vec<PathBuf>,
+    dest: PathBuf,
+    format: ExportFormat,
+    /// Formatting options for the exported tags and filenames
+    format_options: FormatOptions,
+    ascii: bool,
+    flatten: bool,
+    max_name: usize,
+    overwrite: bool,
+    print_only: bool,
+    verbose: bool,
+    stats: ExportStatistics,
+    /// Lowercase destination paths already used in this run
+    used_paths: HashSet<String>,
+}
+
+/// Store export statistics.
+#[derive(Debug, Default)]
+struct ExportStatistics {
+    to_export: usize,
+    exported: usize,
+    converted: usize,
+    existing: usize,
+    failed: usize,
+}
+
+impl ExportFormat {
+    /// Resolve the output format for the given source format.
+    #[must_use]
+    pub fn target(self, source: &FileFormat) -> FileFormat {
+        match self {
+            Self::Original => source.clone(),
+            Self::Aiff => FileFormat::Aif,
+            Self::Mp3 => FileFormat::Mp3,
+        }
+    }
+}
+
+impl Exporter {
+    /// Create Exporter from command line arguments.
+    pub fn new(args: &ExportArgs, format_options: FormatOptions) -> Result<Self> {
+        let inputs = if args.paths.is_empty() {
+            vec![utils::resolve_input_path(&None)?]
+        } else {
+            args.paths
+                .iter()
+                .map(|path| utils::resolve_input_path(&Some(path.clone())))
+                .collect::<Result<Vec<_>, _>>()?
+        };
+
+        let dest = if args.dest.exists() {
+            dunce::canonicalize(&args.dest)?
+        } else {
+            std::path::absolute(&args.dest).context("Failed to resolve destination path")?
+        };
+        if let Some(input) = inputs.iter().find(|input| dest.starts_with(input)) {
+            anyhow::bail!("Destination can't be inside the input directory: {}", input.display());
+        }
+
+        Ok(Self {
+            inputs,
+            dest,
+            format: args.format,
+            format_options,
+            ascii: args.ascii,
+            flatten: args.flatten,
+            max_name: usize::from(args.max_name),
+            overwrite: args.overwrite,
+            print_only: args.print,
+            verbose: args.verbose,
+            stats: ExportStatistics::default(),
+            used_paths: HashSet::new(),
+        })
+    }
+
+    /// Gather and export all tracks from the input paths.
+    pub fn run(&mut self) -> Result<()> {
+        if self.format != ExportFormat::Original && !self.print_only && !utils::ffmpeg_available() {
+            anyhow::bail!(
+                "Export format {:?} specified but ffmpeg command was not found!",
+                self.format
+            )
+        }
+
+        let mut tracks: Vec<(Track, PathBuf)> = Vec::new();
+        for input in &self.inputs {
+            // Mirror paths relative to the input parent so the selected folder itself is included
+            let base = input.parent().unwrap_or(input).to_path_buf();
+            tracks.extend(
+                utils::collect_tracks(input)
+                    .into_iter()
+                    .map(|track| (track, base.clone())),
+            );
+        }
+
+        if tracks.is_empty() {
+            anyhow::bail!("no supported audio files found");
+        }
+
+        let total = tracks.len();
+        println!(
+            "{}",
+            format!("Exporting {total} tracks to: {}", self.dest.display()).bold()
+        );
+        if self.print_only {
+            println!("{}", "Running in print-only mode".yellow().bold());
+        }
+
+        let max_index_width = total.to_string().chars().count();
+        for (index, (mut track, base)) in tracks.into_iter().enumerate() {
+            track.number = index + 1;
+            if let Err(error) = self.export_track(&mut track, &base, total, max_index_width) {
+                track.show(total, max_index_width);
+                utils::print_error(&error.to_string());
+                self.stats.failed += 1;
+            }
+        }
+
+        println!("{}", "\nFinished".green());
+        println!("{}", self.stats);
+        Ok(())
+    }
+
+    /// Copy or convert a single track to the destination and write formatted tags to the copy.
+    fn export_track(&mut self, track: &mut Track, base: &Path, total: usize, max_index_width: usize) -> Result<()> {
+        let mut file_tags = utils::read_tags(track, self.verbose).unwrap_or_default();
+        track.format_tags(&file_tags, &self.format_options);
+
+        let target_format = self.format.target(&track.format);
+        let name = track.formatted_filename(&self.format_options);
+        let name = if name.is_empty() { track.name.clone() } else { name };
+
+        let directory = if self.flatten {
+            self.dest.clone()
+        } else {
+            let relative = track.root.strip_prefix(base).unwrap_or_else(|_| Path::new(""));
+            relative
+                .components()
+                .filter_map(|component| match component {
+                    Component::Normal(name) => Some(fat_safe_name(&name.to_string_lossy(), self.ascii, self.max_name)),
+                    _ => None,
+                })
+                .fold(self.dest.clone(), |path, name| path.join(name))
+        };
+
+        let output_path = self.unique_path(&directory, &name, &target_format.to_string());
+        let convert = target_format != track.format;
+
+        if output_path.exists() && !self.overwrite {
+            self.stats.existing += 1;
+            if self.verbose {
+                track.show(total, max_index_width);
+                println!("{}", format!("Already exists: {}", output_path.display()).yellow());
+            }
+            return Ok(());
+        }
+
+        track.show(total, max_index_width);
+        let relative_output = output_path.strip_prefix(&self.dest).unwrap_or(&output_path);
+        if convert {
+            println!(
+                "{} {}",
+                format!("Convert to {}:", target_format.to_string().to_uppercase())
+                    .cyan()
+                    .bold(),
+                relative_output.display()
+            );
+        } else {
+            println!("{} {}", "Copy:".cyan().bold(), relative_output.display());
+        }
+        self.stats.to_export += 1;
+        if self.print_only {
+            return Ok(());
+        }
+
+        fs::create_dir_all(&directory)
+            .with_context(|| format!("Failed to create directory: {}", directory.display()))?;
+
+        if convert {
+            track.convert_to(&output_path, &target_format, self.overwrite)?;
+            self.stats.converted += 1;
+        } else {
+            fs::copy(&track.path, &output_path)
+                .with_context(|| format!("Failed to copy file to: {}", output_path.display()))?;
+        }
+
+        Self::write_tags(track, &mut file_tags, &output_path)?;
+        self.stats.exported += 1;
+        Ok(())
+    }
+
+    /// Build a FAT-safe destination path that has not been used yet in this run.
+    /// FAT file systems are case-insensitive so paths are compared in lowercase.
+    fn unique_path(&mut self, directory: &Path, name: &str, extension: &str) -> PathBuf {
+        let mut number = 1;
+        loop {
+            let candidate = if number == 1 {
+                name.to_string()
+            } else {
+                format!("{name} ({number})")
+            };
+            let filename = fat_safe_filename(&candidate, extension, self.ascii, self.max_name);
+            let path = directory.join(filename);
+            if self.used_paths.insert(utils::path_to_string(&path).to_lowercase()) {
+                return path;
+            }
+            number += 1;
+        }
+    }
+
+    /// Write formatted tags to the exported file.
+    fn write_tags(track: &Track, file_tags: &mut Tag, path: &Path) -> Result<()> {
+        file_tags.set_artist(track.tags.formatted_artist.clone());
+        file_tags.set_title(track.tags.formatted_title.clone());
+        file_tags.set_album(track.tags.formatted_album.clone());
+        file_tags.set_genre(track.tags.formatted_genre.clone());
+        file_tags
+            .write_to_path(path, id3::Version::Id3v24)
+            .with_context(|| format!("Failed to write tags for: {}", path.display()))
+    }
+}
+
+/// Build a FAT-safe filename with the extension that fits within the maximum length.
+#[must_use]
+pub fn fat_safe_filename(name: &str, extension: &str, ascii: bool, max_length: usize) -> String {
+    let max_stem_length = max_length.saturating_sub(extension.chars().count() + 1).max(1);
+    let stem = fat_safe_name(name, ascii, max_stem_length);
+    format!("{stem}.{extension}")
+}
+
+/// Replace characters that are not allowed on FAT file systems
+/// and truncate to the maximum length in characters.
+#[must_use]
+pub fn fat_safe_name(name: &str, ascii: bool, max_length: usize) -> String {
+    let name = if ascii { to_ascii(name) } else { name.nfc().collect() };
+    let mut safe: String = name
+        .chars()
+        .map(|c| {
+            if c.is_control() || FAT_RESERVED_CHARACTERS.contains(&c) {
+                '_'
+            } else {
+                c
+            }
+        })
+        .collect();
+
+    if safe.chars().count() > max_length {
+        safe = safe.chars().take(max_length).collect();
+    }
+
+    // Trailing dots and spaces are not allowed
+    let trimmed = safe.trim().trim_end_matches('.').trim_end();
+    if trimmed.is_empty() {
+        "_".to_string()
+    } else {
+        trimmed.to_string()
+    }
+}
+
+/// Convert text to ASCII by removing diacritics and replacing other characters.
+fn to_ascii(text: &str) -> String {
+    let mut ascii = String::with_capacity(text.len());
+    for c in text.nfd() {
+        if c.is_ascii() {
+            ascii.push(c);
+        } else if let Some((_, replacement)) = ASCII_REPLACEMENTS.iter().find(|(from, _)| *from == c) {
+            ascii.push_str(replacement);
+        } else if unicode_normalization::char::is_combining_mark(c) {
+            // Diacritic separated from its base character by NFD
+        } else {
+            ascii.push('_');
+        }
+    }
+    ascii
+}
+
+impl fmt::Display for ExportStatistics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}", "Exported:".bold())?;
+        writeln!(f, "Files:      {} / {}", self.exported, self.to_export)?;
+        if self.converted > 0 {
+            writeln!(f, "Converted:  {}", self.converted)?;
+        }
+        if self.existing > 0 {
+            writeln!(f, "Existing:   {}", self.existing)?;
+        }
+        if self.failed > 0 {
+            writeln!(f, "Failed:     {}", self.failed)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fat_safe_name() {
+        assert_eq!(
+            fat_safe_name("AC/DC - Back In Black", false, 255),
+            "AC_DC - Back In Black"
+        );
+        assert_eq!(fat_safe_name("What? \"Yes\" <No>", false, 255), "What_ _Yes_ _No_");
+        assert_eq!(fat_safe_name("Title...", false, 255), "Title");
+        assert_eq!(fat_safe_name("...", false, 255), "_");
+    }
+
+    #[test]
+    fn test_ascii_name() {
+        assert_eq!(
+            fat_safe_name("Räntä & Benjamin Mùll - Sippa På En Tequila", true, 255),
+            "Ranta & Benjamin Mull - Sippa Pa En Tequila"
+        );
+        assert_eq!(fat_safe_name("Røyksopp - Straße", true, 255), "Royksopp - Strasse");
+        assert_eq!(fat_safe_name("坂本龍一 - Title", true, 255), "____ - Title");
+    }
+
+    #[test]
+    fn test_fat_safe_filename_max_length() {
+        let filename = fat_safe_filename("Artist - A Very Long Title (Extended Mix)", "aif", false, 20);
+        assert_eq!(filename, "Artist - A Very.aif");
+        assert!(filename.chars().count() <= 20);
+    }
+
+    #[test]
+    fn test_export_format_target() {
+        assert_eq!(ExportFormat::Original.target(&FileFormat::Mp3), FileFormat::Mp3);
+        assert_eq!(ExportFormat::Aiff.target(&FileFormat::Mp3), FileFormat::Aif);
+        assert_eq!(ExportFormat::Mp3.target(&FileFormat::Aif), FileFormat::Mp3);
+    }
+}
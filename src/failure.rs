@@ -0,0 +1,149 @@
+use std::fmt;
+use std::io;
+
+use id3::ErrorKind;
+use serde::{Deserialize, Serialize};
+
+/// Reason a file could not be processed, used for triaging the failed files log.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FailureCategory {
+    /// No permission to read or write the file
+    Permission,
+    /// File was removed or moved during processing
+    NotFound,
+    /// File ends before the tag or audio data
+    Truncated,
+    /// Tag header or frame data could not be parsed
+    CorruptTag,
+    /// Text frame uses an invalid or unsupported text encoding
+    Encoding,
+    /// Tag uses a feature the ID3 library does not support
+    Unsupported,
+    /// Online-only cloud storage file that could not be downloaded
+    Placeholder,
+    /// Other file system error
+    Io,
+    /// Processing panicked
+    Panic,
+    Other,
+}
+
+/// Failed file with the failure category and error message.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FailedFile {
+    pub path: String,
+    pub category: FailureCategory,
+    pub message: String,
+    /// Stable track identifier, see [`crate::track::Track::report_id`]
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub id: String,
+}
+
+impl FailureCategory {
+    /// Classify an error from reading or writing a tag.
+    #[must_use]
+    pub fn from_id3_error(error: &id3::Error) -> Self {
+        match &error.kind {
+            ErrorKind::Io(io_error) => Self::from_io_error(io_error),
+            ErrorKind::StringDecoding(_) => Self::Encoding,
+            ErrorKind::Parsing if error.description.contains("encoding") => Self::Encoding,
+            ErrorKind::Parsing | ErrorKind::InvalidInput | ErrorKind::NoTag => Self::CorruptTag,
+            ErrorKind::UnsupportedFeature => Self::Unsupported,
+        }
+    }
+
+    /// Failures that can go away when trying again later,
+    /// for example a file locked by another program or a placeholder that is still downloading.
+    #[must_use]
+    pub const fn is_transient(self) -> bool {
+        matches!(self, Self::Permission | Self::Truncated | Self::Placeholder | Self::Io)
+    }
+
+    /// Classify a file system error.
+    #[must_use]
+    pub fn from_io_error(error: &io::Error) -> Self {
+        match error.kind() {
+            io::ErrorKind::PermissionDenied | io::ErrorKind::ReadOnlyFilesystem => Self::Permission,
+            io::ErrorKind::NotFound => Self::NotFound,
+            io::ErrorKind::UnexpectedEof => Self::Truncated,
+            _ => Self::Io,
+        }
+    }
+}
+
+impl FailedFile {
+    /// Create a log entry with the message on a single line.
+    #[must_use]
+    pub fn new(path: impl Into<String>, category: FailureCategory, message: &str) -> Self {
+        Self {
+            path: path.into(),
+            category,
+            message: message.split_whitespace().collect::<Vec<_>>().join(" "),
+            id: String::new(),
+        }
+    }
+
+    /// Create a log entry for a failed tag read.
+    #[must_use]
+    pub fn from_id3_error(path: impl Into<String>, error: &id3::Error) -> Self {
+        Self::new(path, FailureCategory::from_id3_error(error), &error.to_string())
+    }
+}
+
+impl fmt::Display for FailureCategory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Permission => "permission",
+            Self::NotFound => "not_found",
+            Self::Truncated => "truncated",
+            Self::CorruptTag => "corrupt_tag",
+            Self::Encoding => "encoding",
+            Self::Unsupported => "unsupported",
+            Self::Placeholder => "placeholder",
+            Self::Io => "io",
+            Self::Panic => "panic",
+            Self::Other => "other",
+        };
+        write!(f, "{name}")
+    }
+}
+
+/// Tab separated path, category, and message.
+impl fmt::Display for FailedFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}\t{}\t{}", self.path, self.category, self.message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_failure_category() {
+        let permission = id3::Error::from(io::Error::from(io::ErrorKind::PermissionDenied));
+        assert_eq!(
+            FailureCategory::from_id3_error(&permission),
+            FailureCategory::Permission
+        );
+        let truncated = id3::Error::from(io::Error::from(io::ErrorKind::UnexpectedEof));
+        assert_eq!(FailureCategory::from_id3_error(&truncated), FailureCategory::Truncated);
+        let encoding = id3::Error::new(ErrorKind::Parsing, "unknown encoding");
+        assert_eq!(FailureCategory::from_id3_error(&encoding), FailureCategory::Encoding);
+        let corrupt = id3::Error::new(ErrorKind::Parsing, "frame size exceeds tag size");
+        assert_eq!(FailureCategory::from_id3_error(&corrupt), FailureCategory::CorruptTag);
+        assert!(FailureCategory::Truncated.is_transient());
+        assert!(!FailureCategory::CorruptTag.is_transient());
+    }
+
+    #[test]
+    fn test_failed_file_line() {
+        let failed = FailedFile::new("/music/a.mp3", FailureCategory::Panic, "index out of\nbounds");
+        assert_eq!(failed.to_string(), "/music/a.mp3\tpanic\tindex out of bounds");
+        assert_eq!(
+            serde_json::to_string(&failed).expect("Failed to serialize"),
+            r#"{"path":"/music/a.mp3","category":"panic","message":"index out of bounds"}"#
+        );
+    }
+}
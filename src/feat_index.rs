@@ -0,0 +1,80 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+
+use track_rename::formatting;
+use track_rename::utils::csv_field;
+
+/// Index of main artists and the artists featured on their tracks.
+///
+/// Useful for building "more by this artist" crates.
+#[derive(Debug, Default)]
+pub struct FeatIndex {
+    artists: BTreeMap<String, BTreeMap<String, usize>>,
+}
+
+impl FeatIndex {
+    /// Add the featured artists from a formatted artist string.
+    pub fn add(&mut self, formatted_artist: &str) {
+        let (main, featured) = formatting::split_featured_artists(formatted_artist);
+        if main.is_empty() || featured.is_empty() {
+            return;
+        }
+        let entry = self.artists.entry(main.to_string()).or_default();
+        for artist in featured {
+            *entry.entry(artist.to_string()).or_insert(0) += 1;
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.artists.len()
+    }
+
+    /// Write the index to a file as CSV if the file extension is `csv`, otherwise as JSON.
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let is_csv = path
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("csv"));
+        let contents = if is_csv {
+            self.to_csv()
+        } else {
+            serde_json::to_string_pretty(&self.artists).context("Failed to serialize featured artist index")?
+        };
+        fs::write(path, contents)
+            .with_context(|| format!("Failed to write featured artist index: {}", path.display()))?;
+        println!("Wrote featured artist index to: {}", path.display());
+        Ok(())
+    }
+
+    fn to_csv(&self) -> String {
+        let mut csv = String::from("artist,featured_artist,count\n");
+        for (artist, featured) in &self.artists {
+            for (featured_artist, count) in featured {
+                let _ = writeln!(csv, "{},{},{count}", csv_field(artist), csv_field(featured_artist));
+            }
+        }
+        csv
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_feat_index() {
+        let mut index = FeatIndex::default();
+        index.add("Artist feat. Guest & Other, Inc");
+        index.add("Artist feat. Guest");
+        index.add("Artist");
+        index.add("Someone Else feat. Guest");
+        assert_eq!(index.len(), 2);
+        assert_eq!(
+            index.to_csv(),
+            "artist,featured_artist,count\nArtist,Guest,2\nArtist,\"Other, Inc\",1\nSomeone Else,Guest,1\n"
+        );
+    }
+}
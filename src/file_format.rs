@@ -1,25 +1,61 @@
 use std::fmt::Display;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 
-use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Result, TrackRenameError};
+use crate::utils;
 
 /// Supported audio file formats.
 // TODO: add support for "flac" and "m4a"
-#[derive(Debug, Default, Clone, PartialEq, Ord, PartialOrd, Eq)]
+#[derive(Debug, Default, Clone, PartialEq, Ord, PartialOrd, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum FileFormat {
     #[default]
     Mp3,
+    #[serde(alias = "aiff")]
     Aif,
+    #[serde(alias = "wave")]
+    Wav,
+}
+
+/// Required file format for all tracks under a directory.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FormatRule {
+    /// Directory name or consecutive path components, for example `HOUSE` or `DJ MUSIC/HOUSE`
+    pub path: PathBuf,
+    pub format: FileFormat,
+}
+
+impl FormatRule {
+    /// Check if the file is under the rule directory.
+    #[must_use]
+    pub fn matches(&self, path: &Path) -> bool {
+        path.parent()
+            .is_some_and(|directory| utils::contains_subpath(directory, &self.path))
+    }
+}
+
+/// Return the format required by the most specific matching rule for the file.
+#[must_use]
+pub fn required_format<'a>(rules: &'a [FormatRule], path: &Path) -> Option<&'a FileFormat> {
+    rules
+        .iter()
+        .filter(|rule| rule.matches(path))
+        .max_by_key(|rule| rule.path.components().count())
+        .map(|rule| &rule.format)
 }
 
 impl FromStr for FileFormat {
-    type Err = anyhow::Error;
+    type Err = TrackRenameError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s.to_lowercase().as_str() {
             "mp3" => Ok(Self::Mp3),
             "aif" | "aiff" => Ok(Self::Aif),
-            _ => Err(anyhow!("Unsupported file format: {}", s)),
+            "wav" | "wave" => Ok(Self::Wav),
+            _ => Err(TrackRenameError::UnsupportedFormat(s.to_string())),
         }
     }
 }
@@ -32,6 +68,7 @@ impl Display for FileFormat {
             match self {
                 Self::Mp3 => "mp3",
                 Self::Aif => "aif",
+                Self::Wav => "wav",
             }
         )
     }
@@ -52,18 +89,41 @@ mod tests {
         assert_eq!(FileFormat::from_str("Aiff").unwrap(), FileFormat::Aif);
         assert_eq!(FileFormat::from_str("AIF").unwrap(), FileFormat::Aif);
         assert_eq!(FileFormat::from_str("AIFF").unwrap(), FileFormat::Aif);
+        assert_eq!(FileFormat::from_str("wav").unwrap(), FileFormat::Wav);
+        assert_eq!(FileFormat::from_str("WAVE").unwrap(), FileFormat::Wav);
     }
 
     #[test]
     fn test_from_str_invalid_format() {
-        assert!(FileFormat::from_str("wav").is_err());
+        assert!(FileFormat::from_str("flac").is_err());
         assert!(FileFormat::from_str("m4a").is_err());
         assert!(FileFormat::from_str("zip").is_err());
     }
 
+    #[test]
+    fn test_required_format() {
+        let rules = [
+            FormatRule {
+                path: PathBuf::from("HOUSE"),
+                format: FileFormat::Aif,
+            },
+            FormatRule {
+                path: PathBuf::from("HOUSE/Promo"),
+                format: FileFormat::Mp3,
+            },
+        ];
+        let format = |path: &str| required_format(&rules, Path::new(path)).cloned();
+        assert_eq!(format("/music/HOUSE/Artist - Song.mp3"), Some(FileFormat::Aif));
+        assert_eq!(format("/music/HOUSE/Classics/Artist - Song.mp3"), Some(FileFormat::Aif));
+        assert_eq!(format("/music/HOUSE/Promo/Artist - Song.aif"), Some(FileFormat::Mp3));
+        assert_eq!(format("/music/TECHNO/Artist - Song.mp3"), None);
+        assert_eq!(format("/music/HOUSE.mp3"), None);
+    }
+
     #[test]
     fn test_display() {
         assert_eq!(format!("{}", FileFormat::Mp3), "mp3");
         assert_eq!(format!("{}", FileFormat::Aif), "aif");
+        assert_eq!(format!("{}", FileFormat::Wav), "wav");
     }
 }
@@ -0,0 +1,223 @@
+use std::f64::consts::PI;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use clap::ValueEnum;
+use id3::{Tag, TagLike, Version};
+
+use crate::error::{Result, TrackRenameError};
+use crate::file_format::FileFormat;
+use crate::serato;
+
+/// Sample rate of generated audio.
+const SAMPLE_RATE: u32 = 44100;
+/// Length of generated audio in seconds.
+const DURATION_SECONDS: u32 = 1;
+/// Frequency of the generated test tone.
+const TONE_FREQUENCY: f64 = 440.0;
+/// BPM written to the extended and Serato tags.
+const FIXTURE_BPM: f32 = 124.0;
+
+/// Tag configuration for a generated test file.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum FixtureTags {
+    /// No ID3 tag
+    None,
+    /// Artist and title
+    #[default]
+    Basic,
+    /// Artist, title, album, genre, BPM, key, year, and comment
+    Extended,
+    /// Extended tags with Serato autotags, beatgrid, and cue point GEOB frames
+    Serato,
+}
+
+impl FixtureTags {
+    /// Artist name used for the generated file, matching the committed test files.
+    #[must_use]
+    pub const fn artist(self) -> &'static str {
+        match self {
+            Self::None => "No Tags",
+            Self::Basic => "Basic Tags",
+            Self::Extended => "Extended Tags",
+            Self::Serato => "Serato Tags",
+        }
+    }
+
+    fn tag(self) -> Option<Tag> {
+        if self == Self::None {
+            return None;
+        }
+        let mut tag = Tag::new();
+        tag.set_artist(self.artist());
+        tag.set_title("Song");
+        if matches!(self, Self::Extended | Self::Serato) {
+            tag.set_album("Test Album");
+            tag.set_genre("House");
+            tag.set_year(2024);
+            tag.set_text("TBPM", FIXTURE_BPM.to_string());
+            tag.set_text("TKEY", "8A");
+            tag.add_frame(id3::frame::Comment {
+                lang: "eng".to_string(),
+                description: String::new(),
+                text: "Generated test file".to_string(),
+            });
+        }
+        if self == Self::Serato {
+            serato::add_example_data(&mut tag, FIXTURE_BPM);
+        }
+        Some(tag)
+    }
+}
+
+impl fmt::Display for FixtureTags {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = self.to_possible_value().map(|value| value.get_name().to_string());
+        write!(f, "{}", name.unwrap_or_default())
+    }
+}
+
+/// Write a short 16-bit 44.1 kHz test file with the given tags to the directory.
+///
+/// Returns the path of the generated file, named like the committed test files,
+/// for example `Basic Tags - Song - 16-44.aif`.
+pub fn generate(dir: &Path, format: &FileFormat, tags: FixtureTags) -> Result<PathBuf> {
+    let audio = match format {
+        FileFormat::Aif => aiff_bytes(),
+        FileFormat::Wav => wav_bytes(),
+        FileFormat::Mp3 => mp3_bytes(),
+    };
+    fs::create_dir_all(dir)
+        .map_err(|e| TrackRenameError::io(format!("Failed to create directory: {}", dir.display()), e))?;
+    let path = dir.join(format!("{} - Song - 16-44.{format}", tags.artist()));
+    fs::write(&path, audio)
+        .map_err(|e| TrackRenameError::io(format!("Failed to write file: {}", path.display()), e))?;
+    if let Some(tag) = tags.tag() {
+        tag.write_to_path(&path, Version::Id3v24)
+            .map_err(|source| TrackRenameError::TagWrite {
+                path: path.clone(),
+                source,
+            })?;
+    }
+    Ok(path)
+}
+
+/// Stereo 16-bit sine tone samples, interleaved.
+fn pcm_samples() -> Vec<i16> {
+    let frames = SAMPLE_RATE * DURATION_SECONDS;
+    (0..frames)
+        .flat_map(|index| {
+            let value = (2.0 * PI * TONE_FREQUENCY * f64::from(index) / f64::from(SAMPLE_RATE)).sin();
+            let sample = (value * f64::from(i16::MAX) * 0.25).round() as i16;
+            [sample, sample]
+        })
+        .collect()
+}
+
+fn aiff_bytes() -> Vec<u8> {
+    let samples = pcm_samples();
+    let mut comm = b"COMM".to_vec();
+    comm.extend_from_slice(&18_u32.to_be_bytes());
+    comm.extend_from_slice(&2_u16.to_be_bytes());
+    comm.extend_from_slice(&(SAMPLE_RATE * DURATION_SECONDS).to_be_bytes());
+    comm.extend_from_slice(&16_u16.to_be_bytes());
+    comm.extend_from_slice(&extended_float(SAMPLE_RATE));
+
+    let mut ssnd = b"SSND".to_vec();
+    ssnd.extend_from_slice(&(8 + samples.len() as u32 * 2).to_be_bytes());
+    ssnd.extend_from_slice(&[0; 8]);
+    ssnd.extend(samples.iter().flat_map(|sample| sample.to_be_bytes()));
+
+    let mut file = b"FORM".to_vec();
+    file.extend_from_slice(&(4 + comm.len() as u32 + ssnd.len() as u32).to_be_bytes());
+    file.extend_from_slice(b"AIFF");
+    file.extend(comm);
+    file.extend(ssnd);
+    file
+}
+
+fn wav_bytes() -> Vec<u8> {
+    let samples = pcm_samples();
+    let mut fmt = b"fmt ".to_vec();
+    fmt.extend_from_slice(&16_u32.to_le_bytes());
+    fmt.extend_from_slice(&1_u16.to_le_bytes());
+    fmt.extend_from_slice(&2_u16.to_le_bytes());
+    fmt.extend_from_slice(&SAMPLE_RATE.to_le_bytes());
+    fmt.extend_from_slice(&(SAMPLE_RATE * 4).to_le_bytes());
+    fmt.extend_from_slice(&4_u16.to_le_bytes());
+    fmt.extend_from_slice(&16_u16.to_le_bytes());
+
+    let mut data = b"data".to_vec();
+    data.extend_from_slice(&(samples.len() as u32 * 2).to_le_bytes());
+    data.extend(samples.iter().flat_map(|sample| sample.to_le_bytes()));
+
+    let mut file = b"RIFF".to_vec();
+    file.extend_from_slice(&(4 + fmt.len() as u32 + data.len() as u32).to_le_bytes());
+    file.extend_from_slice(b"WAVE");
+    file.extend(fmt);
+    file.extend(data);
+    file
+}
+
+/// Silent MPEG-1 Layer III frames at 128 kbps.
+///
+/// Zeroed side information decodes as silence, so no encoder is needed.
+fn mp3_bytes() -> Vec<u8> {
+    const FRAME_HEADER: [u8; 4] = [0xFF, 0xFB, 0x90, 0x44];
+    const FRAME_SIZE: usize = 417;
+    const SAMPLES_PER_FRAME: u32 = 1152;
+    let frames = (SAMPLE_RATE * DURATION_SECONDS).div_ceil(SAMPLES_PER_FRAME);
+    let mut frame = vec![0; FRAME_SIZE];
+    frame[..4].copy_from_slice(&FRAME_HEADER);
+    frame.repeat(frames as usize)
+}
+
+/// Encode an integer sample rate as an 80-bit IEEE 754 extended precision float.
+fn extended_float(value: u32) -> [u8; 10] {
+    let mut bytes = [0; 10];
+    if value == 0 {
+        return bytes;
+    }
+    let value = u64::from(value);
+    let shift = value.leading_zeros();
+    let exponent = 16383 + 63 - shift as u16;
+    bytes[..2].copy_from_slice(&exponent.to_be_bytes());
+    bytes[2..].copy_from_slice(&(value << shift).to_be_bytes());
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::serato::SeratoData;
+    use crate::track::Track;
+
+    #[test]
+    fn test_extended_float() {
+        assert_eq!(extended_float(44100), [0x40, 0x0E, 0xAC, 0x44, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(extended_float(48000), [0x40, 0x0E, 0xBB, 0x80, 0, 0, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_generate_fixtures() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        for format in [FileFormat::Aif, FileFormat::Wav, FileFormat::Mp3] {
+            let path = generate(dir.path(), &format, FixtureTags::Serato).expect("Failed to generate fixture");
+            let track = Track::try_from_path(&path).expect("Failed to create track");
+            let tag = Tag::read_from_path(&path).expect("Failed to read tags");
+            assert_eq!(tag.artist(), Some("Serato Tags"));
+            assert_eq!(tag.title(), Some("Song"));
+            let serato_data = SeratoData::parse(&tag).expect("Missing Serato data");
+            assert!(serato_data.beatgrid.is_some());
+            let properties = track.audio_properties().expect("Failed to read audio properties");
+            assert_eq!(properties.sample_rate, SAMPLE_RATE);
+            let duration = track.duration().expect("Failed to read duration");
+            assert!(
+                (duration - f64::from(DURATION_SECONDS)).abs() < 0.1,
+                "{format}: {duration}"
+            );
+        }
+    }
+}
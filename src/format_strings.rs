@@ -0,0 +1,123 @@
+use colored::Colorize;
+
+use crate::FormatArgs;
+
+use track_rename::formatting::{self, FilenameSeparator, RuleCategory, RuleSet};
+use track_rename::{genre, utils};
+
+/// Formatted artist, title, album, and genre strings.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Formatted {
+    artist: String,
+    title: String,
+    album: String,
+    genre: String,
+}
+
+/// Run the formatter on the given strings without touching any files.
+pub fn run(args: &FormatArgs, separator: FilenameSeparator) {
+    let input = Formatted {
+        artist: utils::normalize_str(args.artist.trim()),
+        title: utils::normalize_str(args.title.trim()),
+        album: utils::normalize_str(args.album.trim()),
+        genre: utils::normalize_str(args.genre.trim()),
+    };
+    let rules = args
+        .rules
+        .as_ref()
+        .map_or(RuleSet::ALL, |categories| categories.iter().copied().collect());
+    let rules = args
+        .skip_rules
+        .iter()
+        .fold(rules, |rules, &category| rules.without(category));
+    if !rules.is_all() {
+        println!("{}", format!("Rules: {rules}").yellow());
+    }
+
+    let output = format(&input, rules);
+    if !formatting::unconverged_inputs().is_empty() {
+        println!(
+            "{}",
+            format!(
+                "Formatting did not converge after {} passes, the result might change when formatted again",
+                formatting::MAX_FORMAT_PASSES
+            )
+            .yellow()
+        );
+    }
+    print_field("Artist", &input.artist, &output.artist);
+    print_field("Title", &input.title, &output.title);
+    if !input.album.is_empty() || !output.album.is_empty() {
+        print_field("Album", &input.album, &output.album);
+    }
+    if !input.genre.is_empty() || !output.genre.is_empty() {
+        print_field("Genre", &input.genre, &output.genre);
+    }
+    let (file_artist, file_title) = formatting::format_filename(&output.artist, &output.title);
+    println!(
+        "{:<9} {}",
+        "Filename:".bold(),
+        separator.join(&file_artist, &file_title).cyan()
+    );
+
+    if args.trace {
+        print_trace(&input, &output, rules);
+    }
+}
+
+fn format(input: &Formatted, rules: RuleSet) -> Formatted {
+    let (artist, title) = formatting::format_tags_with_rules(&input.artist, &input.title, rules);
+    let album = if rules.contains(RuleCategory::Album) {
+        formatting::format_album(&input.album)
+    } else {
+        input.album.clone()
+    };
+    let genre = if rules.contains(RuleCategory::Genre) {
+        genre::format_genre(&input.genre)
+    } else {
+        input.genre.clone()
+    };
+    Formatted {
+        artist,
+        title,
+        album,
+        genre,
+    }
+}
+
+/// Show the change from each rule category by formatting again without it.
+fn print_trace(input: &Formatted, output: &Formatted, rules: RuleSet) {
+    println!("\n{}", "Rule trace:".bold());
+    let mut changed = false;
+    for category in rules.categories() {
+        let without = format(input, rules.without(category));
+        if without == *output {
+            continue;
+        }
+        changed = true;
+        println!("  {}", category.to_string().cyan().bold());
+        for (name, old, new) in [
+            ("artist", &without.artist, &output.artist),
+            ("title", &without.title, &output.title),
+            ("album", &without.album, &output.album),
+            ("genre", &without.genre, &output.genre),
+        ] {
+            if old != new {
+                print!("    {name:<7}");
+                utils::print_diff(old, new);
+            }
+        }
+    }
+    if !changed {
+        println!("  {}", "No rule changes the input".green());
+    }
+}
+
+fn print_field(name: &str, input: &str, output: &str) {
+    print!("{:<9} ", format!("{name}:").bold());
+    if input == output {
+        println!("{}", output.green());
+    } else {
+        utils::print_diff(input, output);
+    }
+}
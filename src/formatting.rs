@@ -1,33 +1,77 @@
+use std::borrow::Cow;
 use std::cmp::Ordering;
-use std::sync::LazyLock;
-
-use regex::{Captures, Regex};
-
-static COMMON_SUBSTITUTES: [(&str, &str); 23] = [
-    ("\0", "/"),
-    ("`", "'"),
-    ("´", "'"),
-    (")(", ") ("),
-    ("()", " "),
-    (") - (", ""),
-    (" - (", " ("),
-    ("(- ", "("),
-    ("( - ", "("),
-    (" -)", " )"),
-    (" - ) ", ")"),
-    (" )", ")"),
-    ("( ", "("),
-    ("...", " "),
-    ("..", " "),
-    (" feat. - ", " feat. "),
-    (" feat.-", " feat. "),
-    ("o¨", "ö"),
-    ("e¨", "ë"),
-    (" ,", ","),
-    ("\u{FFFD}", " "),
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+use std::ops::RangeInclusive;
+use std::sync::atomic::{AtomicUsize, Ordering as AtomicOrdering};
+use std::sync::{LazyLock, Mutex};
+
+use clap::ValueEnum;
+use regex::{Captures, Regex, RegexSet};
+use serde::{Deserialize, Serialize};
+
+use crate::bpm_range::{BpmRange, BpmRanges};
+
+/// Version of the formatting and genre rules.
+///
+/// Stored in the state for each processed file, and files processed with a different rules version
+/// are formatted again even if they have not changed.
+/// Bump this when rule changes should be applied to already formatted files,
+/// instead of reprocessing the whole library after every release.
+pub const RULES_VERSION: u32 = 3;
+
+/// Category of formatting rules that can be turned on or off for a run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum RuleCategory {
+    /// Featured artist formatting, like moving `feat.` from the title to the artist
+    Feat,
+    /// Removing BPM and key from the end of the title
+    Bpm,
+    /// Bracket and parenthesis formatting
+    Parentheses,
+    /// Capitalization of all caps titles and abbreviations
+    Casing,
+    /// Spelling fixes and standard names for mixes and versions
+    Spelling,
+    /// Whitespace, punctuation, and special character cleanup
+    Cleanup,
+    /// Album formatting
+    Album,
+    /// Genre formatting and directory genre mappings
+    Genre,
+}
+
+/// Set of enabled formatting rule categories.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(from = "Vec<RuleCategory>", into = "Vec<RuleCategory>")]
+pub struct RuleSet(u16);
+
+static COMMON_SUBSTITUTES: [(RuleCategory, &str, &str); 23] = [
+    (RuleCategory::Cleanup, "\0", "/"),
+    (RuleCategory::Cleanup, "`", "'"),
+    (RuleCategory::Cleanup, "´", "'"),
+    (RuleCategory::Parentheses, ")(", ") ("),
+    (RuleCategory::Parentheses, "()", " "),
+    (RuleCategory::Parentheses, ") - (", ""),
+    (RuleCategory::Parentheses, " - (", " ("),
+    (RuleCategory::Parentheses, "(- ", "("),
+    (RuleCategory::Parentheses, "( - ", "("),
+    (RuleCategory::Parentheses, " -)", " )"),
+    (RuleCategory::Parentheses, " - ) ", ")"),
+    (RuleCategory::Parentheses, " )", ")"),
+    (RuleCategory::Parentheses, "( ", "("),
+    (RuleCategory::Cleanup, "...", " "),
+    (RuleCategory::Cleanup, "..", " "),
+    (RuleCategory::Feat, " feat. - ", " feat. "),
+    (RuleCategory::Feat, " feat.-", " feat. "),
+    (RuleCategory::Cleanup, "o¨", "ö"),
+    (RuleCategory::Cleanup, "e¨", "ë"),
+    (RuleCategory::Cleanup, " ,", ","),
+    (RuleCategory::Cleanup, "\u{FFFD}", " "),
     // Replace en and em dashes with regular dash
-    ("–", "-"),
-    ("—", "-"),
+    (RuleCategory::Cleanup, "–", "-"),
+    (RuleCategory::Cleanup, "—", "-"),
 ];
 static TITLE_SUBSTITUTES: [(&str, &str); 18] = [
     ("(Original Mix/", "("),
@@ -49,119 +93,131 @@ static TITLE_SUBSTITUTES: [(&str, &str); 18] = [
     ("(Clean-", "(Clean "),
     ("(Dirty-", "(Dirty "),
 ];
-static REGEX_SUBSTITUTES: LazyLock<[(Regex, &'static str); 12]> = LazyLock::new(|| {
-    [
+static REGEX_SUBSTITUTES: LazyLock<SubstitutionTable> = LazyLock::new(|| {
+    SubstitutionTable::with_categories(&[
         // Replace various opening bracket types with "("
-        (Regex::new(r"[\[{]+").unwrap(), "("),
+        (RuleCategory::Parentheses, r"[\[{]+", "("),
         // Replace various closing bracket types with ")"
-        (Regex::new(r"[]}]+").unwrap(), ")"),
+        (RuleCategory::Parentheses, r"[]}]+", ")"),
         // Collapse multiple exclamation marks into one
-        (Regex::new(r"!{2,}").unwrap(), "!"),
+        (RuleCategory::Cleanup, r"!{2,}", "!"),
         // Collapse multiple periods into a single period
-        (Regex::new(r"\.{2,}").unwrap(), "."),
+        (RuleCategory::Cleanup, r"\.{2,}", "."),
         // Remove empty parentheses
-        (Regex::new(r"\(\s*?\)").unwrap(), ""),
+        (RuleCategory::Parentheses, r"\(\s*?\)", ""),
         // Ensure a space before an opening parenthesis
-        (Regex::new(r"(\S)\(").unwrap(), "$1 ("),
+        (RuleCategory::Parentheses, r"(\S)\(", "$1 ("),
         // Ensure a space after a closing parenthesis
-        (Regex::new(r"\)([A-Za-z0-9])").unwrap(), ") $1"),
+        (RuleCategory::Parentheses, r"\)([A-Za-z0-9])", ") $1"),
         // Collapse multiple consecutive opening parentheses into one
-        (Regex::new(r"\(\s*\){2,}").unwrap(), "("),
+        (RuleCategory::Parentheses, r"\(\s*\){2,}", "("),
         // Collapse multiple consecutive closing parentheses into one
-        (Regex::new(r"\)\s*\){2,}").unwrap(), ")"),
+        (RuleCategory::Parentheses, r"\)\s*\){2,}", ")"),
         // Transforms underscore-wrapped text into single-quoted text
-        (Regex::new(r"\s_(.*?)_\s").unwrap(), " '$1' "),
+        (RuleCategory::Cleanup, r"\s_(.*?)_\s", " '$1' "),
         // Remove asterisks after a word boundary
-        (Regex::new(r"\s\*+\b").unwrap(), ""),
+        (RuleCategory::Cleanup, r"\s\*+\b", ""),
         // Collapses multiple spaces into a single space
-        (Regex::new(r"\s+").unwrap(), " "),
-    ]
+        (RuleCategory::Cleanup, r"\s+", " "),
+    ])
 });
-static REGEX_NAME_SUBSTITUTES: LazyLock<[(Regex, &'static str); 43]> = LazyLock::new(|| {
-    [
+static REGEX_NAME_SUBSTITUTES: LazyLock<SubstitutionTable> = LazyLock::new(|| {
+    SubstitutionTable::with_categories(&[
         // Matches "12 Inch" or "12Inch" with optional space, case-insensitive
-        (Regex::new(r"(?i)\b12\s?inch\b").unwrap(), "12''"),
+        (RuleCategory::Spelling, r"(?i)\b12\s?inch\b", "12''"),
         // Matches "12in" or "12 in" with optional space, case-insensitive
-        (Regex::new(r"(?i)\b12\s?in\b").unwrap(), "12''"),
+        (RuleCategory::Spelling, r"(?i)\b12\s?in\b", "12''"),
         // Matches "7 Inch" or "7Inch" with optional space, case-insensitive
-        (Regex::new(r"(?i)\b7\s?inch\b").unwrap(), "7''"),
+        (RuleCategory::Spelling, r"(?i)\b7\s?inch\b", "7''"),
         // Matches "7in" or "7 in" with optional space, case-insensitive
-        (Regex::new(r"(?i)\b7\s?in\b").unwrap(), "7''"),
+        (RuleCategory::Spelling, r"(?i)\b7\s?in\b", "7''"),
         // Standardize various forms of "featuring" to "feat."
-        (Regex::new(r"(?i)\b(?:feat\.?|ft\.?|featuring)\b").unwrap(), "feat."),
-        (Regex::new(r"(?i)\(\s*(?:feat\.?|ft\.?|featuring)\b").unwrap(), "(feat."),
+        (RuleCategory::Feat, r"(?i)\b(?:feat\.?|ft\.?|featuring)\b", "feat."),
+        (RuleCategory::Feat, r"(?i)\(\s*(?:feat\.?|ft\.?|featuring)\b", "(feat."),
+        // Remove the extra period left from "ft." without relying on the punctuation cleanup
+        (RuleCategory::Feat, r"\bfeat\.\.", "feat."),
         // Standardize "w/" to "feat."
-        (Regex::new(r"(?i)\sW/").unwrap(), " feat. "),
+        (RuleCategory::Feat, r"(?i)\sW/", " feat. "),
         // Standardize Remix
-        (Regex::new(r"(?i)\(Rmx\)").unwrap(), "(Remix)"),
-        (Regex::new(r"(?i)\bRmx\b").unwrap(), "Remix"),
+        (RuleCategory::Spelling, r"(?i)\(Rmx\)", "(Remix)"),
+        (RuleCategory::Spelling, r"(?i)\bRmx\b", "Remix"),
         // Remove trademark symbols
-        (Regex::new(r"[®™]").unwrap(), ""),
+        (RuleCategory::Cleanup, r"[®™]", ""),
         // Correct name for "Missy Elliott"
         (
-            Regex::new(r"(?i)\bMissy Elliot\b|\bMissy Elliot$").unwrap(),
+            RuleCategory::Spelling,
+            r"(?i)\bMissy Elliot\b|\bMissy Elliot$",
             "Missy Elliott",
         ),
         // Correct name for "Gang Starr"
-        (Regex::new(r"(?i)\bGangstarr\b|\bGangstarr$").unwrap(), "Gang Starr"),
+        (RuleCategory::Spelling, r"(?i)\bGangstarr\b|\bGangstarr$", "Gang Starr"),
         // Fix capitalization for SZA
-        (Regex::new(r"(?i)\bSza\b").unwrap(), "SZA"),
+        (RuleCategory::Casing, r"(?i)\bSza\b", "SZA"),
         // Fix spelling for "You're"
-        (Regex::new(r"(?i)\bYoure\b").unwrap(), "You're"),
+        (RuleCategory::Spelling, r"(?i)\bYoure\b", "You're"),
         // Fix spelling for "I'm"
-        (Regex::new(r"(?i)\bIm\b").unwrap(), "I'm"),
+        (RuleCategory::Spelling, r"(?i)\bIm\b", "I'm"),
         // Fix spelling for "You've"
-        (Regex::new(r"(?i)\bYouve\b").unwrap(), "You've"),
+        (RuleCategory::Spelling, r"(?i)\bYouve\b", "You've"),
         // Fix spelling for "Can't"
-        (Regex::new(r"(?i)\bCant\b").unwrap(), "Can't"),
+        (RuleCategory::Spelling, r"(?i)\bCant\b", "Can't"),
         // Fix spelling for "Won't"
-        (Regex::new(r"(?i)\bWont\b").unwrap(), "Won't"),
+        (RuleCategory::Spelling, r"(?i)\bWont\b", "Won't"),
         // Fix spelling for "Don't"
-        (Regex::new(r"(?i)\bDont\b").unwrap(), "Don't"),
+        (RuleCategory::Spelling, r"(?i)\bDont\b", "Don't"),
         // Fix capitalization for "DJ"
-        (Regex::new(r"(?i)\bDj\b").unwrap(), "DJ"),
+        (RuleCategory::Casing, r"(?i)\bDj\b", "DJ"),
         // Ensure one whitespace after "feat."
-        (Regex::new(r"\bfeat\.([A-Za-z0-9])").unwrap(), "feat. $1"),
-        (Regex::new(r"(?i)\b(dirty!)\b").unwrap(), "(Dirty)"),
+        (RuleCategory::Feat, r"\bfeat\.([A-Za-z0-9])", "feat. $1"),
+        (RuleCategory::Spelling, r"(?i)\b(dirty!)\b", "(Dirty)"),
         // Removes "Original Mix" with case-insensitivity
-        (Regex::new(r"(?i)\(Original Mix\)").unwrap(), ""),
+        (RuleCategory::Spelling, r"(?i)\(Original Mix\)", ""),
         // Removes "DJCity" with case-insensitivity
-        (Regex::new(r"(?i)\bdjcity\b").unwrap(), ""),
-        (Regex::new(r"(?i)\bintro - clean\b").unwrap(), "Clean Intro"),
-        (Regex::new(r"(?i)\bintro - dirty\b").unwrap(), "Dirty Intro"),
-        (Regex::new(r"(?i)\(clean - intro\)").unwrap(), "(Clean Intro)"),
-        (Regex::new(r"(?i)\(dirty - intro\)").unwrap(), "(Dirty Intro)"),
-        (Regex::new(r"(?i)\bIntro[:\s/+\-&]*outro\b").unwrap(), "Intro"),
-        (Regex::new(r"(?i)\bAca In\b").unwrap(), "Acapella Intro"),
+        (RuleCategory::Spelling, r"(?i)\bdjcity\b", ""),
+        (RuleCategory::Spelling, r"(?i)\bintro - clean\b", "Clean Intro"),
+        (RuleCategory::Spelling, r"(?i)\bintro - dirty\b", "Dirty Intro"),
+        (RuleCategory::Spelling, r"(?i)\(clean - intro\)", "(Clean Intro)"),
+        (RuleCategory::Spelling, r"(?i)\(dirty - intro\)", "(Dirty Intro)"),
+        (RuleCategory::Spelling, r"(?i)\bIntro[:\s/+\-&]*outro\b", "Intro"),
+        (RuleCategory::Spelling, r"(?i)\bAca In\b", "Acapella Intro"),
         (
-            Regex::new(r"(?i)\bAca intro[:\s/+\-&]*aca outro\b").unwrap(),
+            RuleCategory::Spelling,
+            r"(?i)\bAca intro[:\s/+\-&]*aca outro\b",
             "Acapella In-Out",
         ),
         (
-            Regex::new(r"(?i)\bAcapella Intro[:\s/+\-&]*aca out\b").unwrap(),
+            RuleCategory::Spelling,
+            r"(?i)\bAcapella Intro[:\s/+\-&]*aca out\b",
             "Acapella In-Out",
         ),
-        (Regex::new(r"(?i)\bAca Out\b").unwrap(), "Acapella Out"),
-        (Regex::new(r"(?i)\bAcap-In\b").unwrap(), "Acapella Intro"),
-        (Regex::new(r"(?i)\bAcap - diy\b").unwrap(), "Acapella DIY"),
-        (Regex::new(r"(?i)\bAcap in[:\s/+\-&]*out\b").unwrap(), "Acapella In-Out"),
-        (Regex::new(r"(?i)\bAcap\b").unwrap(), "Acapella"),
+        (RuleCategory::Spelling, r"(?i)\bAca Out\b", "Acapella Out"),
+        (RuleCategory::Spelling, r"(?i)\bAcap-In\b", "Acapella Intro"),
+        (RuleCategory::Spelling, r"(?i)\bAcap - diy\b", "Acapella DIY"),
         (
-            Regex::new(r"(?i)\bAcapella[\s/+\-]*In[:\s/+\-&]*Out\b").unwrap(),
+            RuleCategory::Spelling,
+            r"(?i)\bAcap in[:\s/+\-&]*out\b",
             "Acapella In-Out",
         ),
-        (Regex::new(r"(?i)\bAcapella[\s/+\-]*In\b").unwrap(), "Acapella Intro"),
+        (RuleCategory::Spelling, r"(?i)\bAcap\b", "Acapella"),
         (
-            Regex::new(r"(?i)\bAcapella Intro[:\s/+\-&]*Out\b").unwrap(),
+            RuleCategory::Spelling,
+            r"(?i)\bAcapella[\s/+\-]*In[:\s/+\-&]*Out\b",
             "Acapella In-Out",
         ),
+        (RuleCategory::Spelling, r"(?i)\bAcapella[\s/+\-]*In\b", "Acapella Intro"),
         (
-            Regex::new(r"(?i)\bAcapella-Intro[:\s/+\-&]*Out\b").unwrap(),
+            RuleCategory::Spelling,
+            r"(?i)\bAcapella Intro[:\s/+\-&]*Out\b",
             "Acapella In-Out",
         ),
-        (Regex::new(r"(?i)\bAcapella-Intro\b").unwrap(), "Acapella Intro"),
-        (Regex::new(r"(?i)\bAcapella-out\b").unwrap(), "Acapella Out"),
-    ]
+        (
+            RuleCategory::Spelling,
+            r"(?i)\bAcapella-Intro[:\s/+\-&]*Out\b",
+            "Acapella In-Out",
+        ),
+        (RuleCategory::Spelling, r"(?i)\bAcapella-Intro\b", "Acapella Intro"),
+        (RuleCategory::Spelling, r"(?i)\bAcapella-out\b", "Acapella Out"),
+    ])
 });
 static REGEX_FILENAME_SUBSTITUTES: LazyLock<[(Regex, &str); 2]> = LazyLock::new(|| {
     [
@@ -175,7 +231,10 @@ static REGEX_FILENAME_SUBSTITUTES: LazyLock<[(Regex, &str); 2]> = LazyLock::new(
 static RE_FEAT: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\bfeat\. .*?( -|\(|\)|$)").unwrap());
 
 // Matches text after a closing parenthesis until the next opening parenthesis
-static RE_TEXT_AFTER_PARENTHESES: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\)\s(.*?)\s\(").unwrap());
+static RE_TEXT_AFTER_PARENTHESES: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\)\s([^()]+?)\s\(").unwrap());
+
+// Matches the BPM number in text removed by the BPM regexes
+static RE_BPM_NUMBER: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\d{1,3}(\.\d)?").unwrap());
 
 // Matches BPM information inside parentheses at the end of a string,
 // allowing for decimal BPMs or BPM with a trailing "a"
@@ -193,12 +252,30 @@ static RE_BPM_WITH_TEXT_PARENTHESES: LazyLock<Regex> =
     LazyLock::new(|| Regex::new(r"\s\(\d{2,3}\s?[a-zA-Z]{2,3}\)$").unwrap());
 static RE_BPM_WITH_EXTRA_TEXT: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\b\d{2,3}\s?[a-zA-Z]{2,3}$").unwrap());
 
+// Matches a 19xx or 20xx year inside parentheses at the end of a string
+static RE_YEAR_IN_PARENTHESES: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\s\(((?:19|20)\d{2})\)$").unwrap());
+
+// Matches the text inside the last parentheses at the end of a string
+static RE_TRAILING_PARENTHESES: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\s*\(([^()]*)\)\s*$").unwrap());
+
+// Matches a transition edit inside parentheses, like "(128-100 Transition)" or "(Dirty 128 - 100 Transition Edit)",
+// where the two numbers are the start and end BPM
+static RE_TRANSITION: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"(?i)\(([^()]*?\b\d{2,3}(?:\s?bpm)?\s?(?:-|–|to|>)\s?\d{2,3}(?:\s?bpm)?\s(?:[^()]*?\s)?transition\b[^()]*)\)",
+    )
+    .unwrap()
+});
+
 // Matches any text within parentheses that contains a dash, separating it into two groups
 static RE_DASH_IN_PARENTHESES: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\((.*?) - (.*?)\)").unwrap());
 
 // Matches variations on "and" in feat artist names
 static RE_FEAT_AND: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?i),?\s+and\s+").unwrap());
 
+// Matches the text inside parentheses or square brackets
+static RE_DESCRIPTOR: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"[(\[]([^()\[\]]*)[)\]]").unwrap());
+
 // Collapse multiple spaces into a single space
 static RE_MULTIPLE_SPACES: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\s{2,}").unwrap());
 
@@ -206,100 +283,584 @@ static RE_WWW: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?i)^www\.").unwr
 
 static RE_CHARS_AND_DOTS: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?i)^([a-z]\.)+([a-z])?$").unwrap());
 
-/// Return formatted artist and title string.
+/// Raw or formatted artist and title pair.
+type ArtistAndTitle = (String, String);
+
+/// Raw artist and title with the rule categories and BPM range they are formatted with.
+type FormatCacheKey = (ArtistAndTitle, RuleSet, BpmRange);
+
+/// Formatted artist and title keyed by the raw input and formatting options.
+static FORMAT_CACHE: LazyLock<Mutex<HashMap<FormatCacheKey, ArtistAndTitle>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+static FORMAT_CACHE_HITS: AtomicUsize = AtomicUsize::new(0);
+static FORMAT_CACHE_MISSES: AtomicUsize = AtomicUsize::new(0);
+
+/// Maximum number of times the formatting rules are applied to reach a stable output.
+pub const MAX_FORMAT_PASSES: usize = 5;
+
+/// Private use character range for protected transition edit placeholders, one character per transition.
+const TRANSITION_PLACEHOLDERS: RangeInclusive<char> = '\u{E000}'..='\u{F8FF}';
+
+/// Raw artist and title inputs where the output still changed on the last formatting pass.
+static UNCONVERGED_INPUTS: LazyLock<Mutex<BTreeSet<ArtistAndTitle>>> = LazyLock::new(|| Mutex::new(BTreeSet::new()));
+
+/// Options that change the formatted tags and filenames.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Serialize)]
+pub struct FormatOptions {
+    /// Enabled formatting rule categories
+    pub rules: RuleSet,
+    /// Separator between the artist and title in filenames
+    pub filename_separator: FilenameSeparator,
+    pub year_in_title: YearInTitle,
+    pub subtitle: Subtitle,
+    pub album_artist: AlbumArtist,
+    /// Numbers at the end of titles in these ranges are removed as BPM values
+    pub bpm_ranges: BpmRanges,
+}
+
+impl FormatOptions {
+    /// Hash of the options stored in the state, so files are formatted again when the options change.
+    ///
+    /// The default options give zero, the same as state entries saved before the hash was stored.
+    /// Uses FNV-1a over the JSON serialization so the value stays the same across builds.
+    #[must_use]
+    pub fn state_hash(&self) -> u64 {
+        if *self == Self::default() {
+            return 0;
+        }
+        serde_json::to_vec(self)
+            .unwrap_or_default()
+            .iter()
+            .fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+                (hash ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3)
+            })
+    }
+}
+
+/// Hit statistics for the formatting cache.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    pub hits: usize,
+    pub misses: usize,
+    pub entries: usize,
+}
+
+/// Ordered list of regex substitutions.
+///
+/// All patterns are first matched at once with a regex set,
+/// so only the substitutions that can match are executed.
+/// The result is the same as applying each substitution in order.
+pub struct SubstitutionTable {
+    set: RegexSet,
+    substitutes: Vec<(Regex, &'static str)>,
+    /// Rule category for each substitution, or `None` if it is always applied
+    categories: Vec<Option<RuleCategory>>,
+}
+
+impl SubstitutionTable {
+    /// Compile table from pattern and replacement pairs.
+    ///
+    /// # Panics
+    /// Panics if a pattern is not a valid regex.
+    #[must_use]
+    pub fn new(substitutes: &[(&str, &'static str)]) -> Self {
+        let set = RegexSet::new(substitutes.iter().map(|(pattern, _)| pattern)).expect("Invalid regex pattern");
+        let substitutes: Vec<(Regex, &'static str)> = substitutes
+            .iter()
+            .map(|(pattern, replacement)| (Regex::new(pattern).expect("Invalid regex pattern"), *replacement))
+            .collect();
+        let categories = vec![None; substitutes.len()];
+        Self {
+            set,
+            substitutes,
+            categories,
+        }
+    }
+
+    /// Compile table from substitutions that only apply when their rule category is enabled.
+    ///
+    /// # Panics
+    /// Panics if a pattern is not a valid regex.
+    #[must_use]
+    pub fn with_categories(substitutes: &[(RuleCategory, &str, &'static str)]) -> Self {
+        let pairs: Vec<(&str, &'static str)> = substitutes
+            .iter()
+            .map(|(_, pattern, replacement)| (*pattern, *replacement))
+            .collect();
+        Self {
+            categories: substitutes.iter().map(|(category, _, _)| Some(*category)).collect(),
+            ..Self::new(&pairs)
+        }
+    }
+
+    /// Apply all matching substitutions in order.
+    pub fn apply(&self, text: &mut String) {
+        self.apply_rules(text, RuleSet::ALL);
+    }
+
+    /// Apply matching substitutions in order, skipping the ones from disabled rule categories.
+    pub fn apply_rules(&self, text: &mut String, rules: RuleSet) {
+        let enabled = |index: usize| self.categories[index].is_none_or(|category| rules.contains(category));
+        let mut matches = self.set.matches(text);
+        let mut start = 0;
+        while let Some(index) = matches.iter().find(|&index| index >= start && enabled(index)) {
+            let (regex, replacement) = &self.substitutes[index];
+            if let Cow::Owned(replaced) = regex.replace_all(text, *replacement) {
+                *text = replaced;
+                // A replacement can create or remove matches for the following patterns
+                matches = self.set.matches(text);
+            }
+            start = index + 1;
+        }
+    }
+
+    /// Iterate over the compiled regexes in order.
+    pub fn regexes(&self) -> impl Iterator<Item = &Regex> {
+        self.substitutes.iter().map(|(regex, _)| regex)
+    }
+}
+
+/// All regexes used for formatting artist, title, album, and filename.
+///
+/// Used for profiling the formatting pipeline.
+pub fn regexes() -> Vec<&'static Regex> {
+    REGEX_NAME_SUBSTITUTES
+        .regexes()
+        .chain(REGEX_SUBSTITUTES.regexes())
+        .chain(REGEX_FILENAME_SUBSTITUTES.iter().map(|(regex, _)| regex))
+        .chain([
+            &*RE_FEAT,
+            &*RE_TEXT_AFTER_PARENTHESES,
+            &*RE_BPM_NUMBER,
+            &*RE_BPM_IN_PARENTHESES,
+            &*RE_BPM_WITH_KEY,
+            &*RE_BPM_WITH_TEXT,
+            &*RE_BPM_WITH_TEXT_PARENTHESES,
+            &*RE_BPM_WITH_EXTRA_TEXT,
+            &*RE_YEAR_IN_PARENTHESES,
+            &*RE_TRAILING_PARENTHESES,
+            &*RE_TRANSITION,
+            &*RE_DASH_IN_PARENTHESES,
+            &*RE_FEAT_AND,
+            &*RE_DESCRIPTOR,
+            &*RE_MULTIPLE_SPACES,
+            &*RE_WWW,
+            &*RE_CHARS_AND_DOTS,
+        ])
+        .collect()
+}
+
+/// Return formatted artist and title string using all rule categories and the default BPM range.
+#[must_use]
 pub fn format_tags_for_artist_and_title(artist: &str, title: &str) -> (String, String) {
+    format_tags_with_rules(artist, title, RuleSet::ALL)
+}
+
+/// Return formatted artist and title string using only the given rule categories.
+#[must_use]
+pub fn format_tags_with_rules(artist: &str, title: &str, rules: RuleSet) -> (String, String) {
+    format_tags_with_options(artist, title, rules, BpmRange::default())
+}
+
+/// Return formatted artist and title string using the given rule categories,
+/// only removing BPM values inside the given range.
+///
+/// Formatting is repeated until the output no longer changes, up to [`MAX_FORMAT_PASSES`] times.
+/// Inputs that still change on the last pass are recorded, see [`unconverged_inputs`].
+#[must_use]
+pub fn format_tags_with_options(artist: &str, title: &str, rules: RuleSet, bpm: BpmRange) -> (String, String) {
+    let mut formatted = format_pass(artist, title, rules, bpm);
+    for _ in 1..MAX_FORMAT_PASSES {
+        let next = format_pass(&formatted.0, &formatted.1, rules, bpm);
+        if next == formatted {
+            return formatted;
+        }
+        formatted = next;
+    }
+    if let Ok(mut unconverged) = UNCONVERGED_INPUTS.lock() {
+        unconverged.insert((artist.to_string(), title.to_string()));
+    }
+    formatted
+}
+
+/// Inputs where formatting did not converge within [`MAX_FORMAT_PASSES`] passes during this run.
+#[must_use]
+pub fn unconverged_inputs() -> Vec<(String, String)> {
+    UNCONVERGED_INPUTS
+        .lock()
+        .map(|unconverged| unconverged.iter().cloned().collect())
+        .unwrap_or_default()
+}
+
+/// Run all formatting rules once.
+fn format_pass(artist: &str, title: &str, rules: RuleSet, bpm: BpmRange) -> (String, String) {
     let mut formatted_artist = artist.to_string();
     let mut formatted_title = title.to_string();
+    let cleanup = rules.contains(RuleCategory::Cleanup);
+    let parentheses = rules.contains(RuleCategory::Parentheses);
+    let feat = rules.contains(RuleCategory::Feat);
+    let transitions = protect_transitions(&mut formatted_title);
+
+    if cleanup {
+        // Remove an extra file extension from the end
+        let extensions = [".mp3", ".flac", ".aif", ".aiff", ".m4a"];
+        for ext in &extensions {
+            remove_suffix_ignore_case(&mut formatted_artist, ext);
+            remove_suffix_ignore_case(&mut formatted_title, ext);
+        }
+    }
 
-    // Remove an extra file extension from the end
-    let extensions = [".mp3", ".flac", ".aif", ".aiff", ".m4a"];
-    for ext in &extensions {
-        if formatted_artist.to_lowercase().ends_with(ext) {
-            formatted_artist = formatted_artist[0..formatted_artist.len() - ext.len()].to_string();
+    apply_common_substitutes(&mut formatted_artist, &mut formatted_title, rules);
+
+    if rules.contains(RuleCategory::Spelling) {
+        for (pattern, replacement) in &TITLE_SUBSTITUTES {
+            replace_in_place(&mut formatted_title, pattern, replacement);
+        }
+    }
+
+    REGEX_NAME_SUBSTITUTES.apply_rules(&mut formatted_artist, rules);
+    REGEX_NAME_SUBSTITUTES.apply_rules(&mut formatted_title, rules);
+
+    REGEX_SUBSTITUTES.apply_rules(&mut formatted_artist, rules);
+    REGEX_SUBSTITUTES.apply_rules(&mut formatted_title, rules);
+
+    if cleanup {
+        replace_in_place(&mut formatted_artist, " / ", ", ");
+        if formatted_artist.eq_ignore_ascii_case("Various Artists") {
+            let (artist, title) = match formatted_title.splitn(2, " - ").collect::<Vec<&str>>().as_slice() {
+                [artist, title] => (*artist, *title),
+                [no_split] => ("", *no_split),
+                _ => ("", ""),
+            };
+            formatted_artist = artist.to_string();
+            formatted_title = title.to_string();
+        } else {
+            trim_start_matches_in_place(&mut formatted_artist, "Various Artists - ");
         }
-        if formatted_title.to_lowercase().ends_with(ext) {
-            formatted_title = formatted_title[0..formatted_title.len() - ext.len()].to_string();
+
+        // Remove duplicate artist name from title
+        let artist_with_dash = format!("{formatted_artist} - ");
+        if formatted_title.starts_with(&artist_with_dash) {
+            formatted_title.drain(..artist_with_dash.len());
         }
+
+        // Artist name should not start with a dot since this will make it a hidden file
+        trim_start_matches_in_place(&mut formatted_artist, ".");
     }
 
-    for (pattern, replacement) in &COMMON_SUBSTITUTES {
-        formatted_artist = formatted_artist.replace(pattern, replacement);
-        formatted_title = formatted_title.replace(pattern, replacement);
+    if parentheses {
+        use_parenthesis_for_mix(&mut formatted_title);
+    }
+    if feat {
+        move_feat_from_title_to_artist(&mut formatted_artist, &mut formatted_title);
+    }
+    if parentheses {
+        replace_dash_in_parentheses(&mut formatted_title);
+        fix_nested_parentheses(&mut formatted_title);
+        wrap_text_after_parentheses(&mut formatted_title);
+    }
+    if rules.contains(RuleCategory::Bpm) {
+        remove_bpm_in_parentheses_from_end(&mut formatted_title, bpm);
+    }
+    if parentheses {
+        remove_unmatched_closing_parenthesis(&mut formatted_artist);
     }
 
-    for (pattern, replacement) in &TITLE_SUBSTITUTES {
-        formatted_title = formatted_title.replace(pattern, replacement);
+    if feat {
+        extract_feat_from_parentheses(&mut formatted_artist);
+    }
+    if parentheses {
+        balance_parenthesis(&mut formatted_title);
     }
 
-    for (regex, replacement) in REGEX_NAME_SUBSTITUTES.iter() {
-        formatted_artist = regex.replace_all(&formatted_artist, *replacement).to_string();
-        formatted_title = regex.replace_all(&formatted_title, *replacement).to_string();
+    REGEX_SUBSTITUTES.apply_rules(&mut formatted_artist, rules);
+    REGEX_SUBSTITUTES.apply_rules(&mut formatted_title, rules);
+
+    apply_common_substitutes(&mut formatted_artist, &mut formatted_title, rules);
+
+    if rules.contains(RuleCategory::Casing) {
+        if formatted_title == formatted_title.to_uppercase()
+            && formatted_title.chars().count() > 10
+            && !RE_CHARS_AND_DOTS.is_match(&formatted_title)
+        {
+            formatted_title = titlecase::titlecase(&formatted_title);
+            if formatted_artist == formatted_artist.to_uppercase() && formatted_artist.chars().count() > 8 {
+                formatted_artist = titlecase::titlecase(&formatted_artist);
+            }
+        } else if RE_CHARS_AND_DOTS.is_match(&formatted_title) {
+            formatted_title = formatted_title.to_uppercase();
+        }
     }
 
-    for (regex, replacement) in REGEX_SUBSTITUTES.iter() {
-        formatted_artist = regex.replace_all(&formatted_artist, *replacement).to_string();
-        formatted_title = regex.replace_all(&formatted_title, *replacement).to_string();
+    restore_transitions(&mut formatted_artist, &mut formatted_title, &transitions);
+    trim_in_place(&mut formatted_artist);
+    trim_in_place(&mut formatted_title);
+    (formatted_artist, formatted_title)
+}
+
+/// Replace the text of transition edits like `(128-100 Transition)` with placeholders,
+/// so the BPM and dash rules do not split or remove them.
+///
+/// Each placeholder is a single private use character that no formatting rule changes.
+/// Private use characters already in the title are removed so they can not be mistaken for placeholders.
+/// Returns the original texts for [`restore_transitions`].
+fn protect_transitions(title: &mut String) -> Vec<String> {
+    title.retain(|c| !TRANSITION_PLACEHOLDERS.contains(&c));
+    let mut transitions = Vec::new();
+    let result = RE_TRANSITION.replace_all(title, |caps: &Captures| {
+        let Some(placeholder) = transition_placeholder(transitions.len()) else {
+            return caps[0].to_string();
+        };
+        transitions.push(caps[1].to_string());
+        format!("({placeholder})")
+    });
+    if let Cow::Owned(result) = result {
+        *title = result;
     }
+    transitions
+}
 
-    formatted_artist = formatted_artist.replace(" / ", ", ");
-    if formatted_artist.eq_ignore_ascii_case("Various Artists") {
-        let (artist, title) = match formatted_title.splitn(2, " - ").collect::<Vec<&str>>().as_slice() {
-            [artist, title] => (*artist, *title),
-            [no_split] => ("", *no_split),
-            _ => ("", ""),
+/// Put the original transition edit texts back in place of the placeholders.
+///
+/// A rule like the Various Artists split can move a placeholder to the artist, so both are restored.
+/// Placeholders can not be left in the output even if a rule duplicated one.
+fn restore_transitions(artist: &mut String, title: &mut String, transitions: &[String]) {
+    for (index, transition) in transitions.iter().enumerate() {
+        let Some(placeholder) = transition_placeholder(index) else {
+            break;
         };
-        formatted_artist = artist.to_string();
-        formatted_title = title.to_string();
-    } else {
-        formatted_artist = formatted_artist.trim_start_matches("Various Artists - ").to_string();
+        debug_assert!(
+            artist.contains(placeholder) || title.contains(placeholder),
+            "Transition placeholder was removed: {transition}"
+        );
+        let placeholder = placeholder.to_string();
+        replace_in_place(artist, &placeholder, transition);
+        replace_in_place(title, &placeholder, transition);
+    }
+    artist.retain(|c| !TRANSITION_PLACEHOLDERS.contains(&c));
+    title.retain(|c| !TRANSITION_PLACEHOLDERS.contains(&c));
+}
+
+/// Placeholder character for the transition with the given index.
+fn transition_placeholder(index: usize) -> Option<char> {
+    u32::try_from(index)
+        .ok()
+        .and_then(|index| char::from_u32(u32::from(*TRANSITION_PLACEHOLDERS.start()) + index))
+        .filter(|placeholder| TRANSITION_PLACEHOLDERS.contains(placeholder))
+}
+
+fn apply_common_substitutes(artist: &mut String, title: &mut String, rules: RuleSet) {
+    for (category, pattern, replacement) in &COMMON_SUBSTITUTES {
+        if rules.contains(*category) {
+            replace_in_place(artist, pattern, replacement);
+            replace_in_place(title, pattern, replacement);
+        }
+    }
+}
+
+/// Return formatted artist and title string, reusing earlier results for identical input.
+///
+/// Many tracks share the same artist string, so this skips recomputing them within a run.
+#[must_use]
+pub fn format_tags_cached(artist: &str, title: &str, options: &FormatOptions) -> (String, String) {
+    format_tags_in_bpm_range_cached(artist, title, options.rules, options.bpm_ranges.default)
+}
+
+/// Return formatted artist and title string for the given rules and BPM range,
+/// reusing earlier results for identical input.
+#[must_use]
+pub fn format_tags_in_bpm_range_cached(artist: &str, title: &str, rules: RuleSet, bpm: BpmRange) -> (String, String) {
+    let key = ((artist.to_string(), title.to_string()), rules, bpm);
+    if let Some(formatted) = FORMAT_CACHE.lock().ok().and_then(|cache| cache.get(&key).cloned()) {
+        FORMAT_CACHE_HITS.fetch_add(1, AtomicOrdering::Relaxed);
+        return formatted;
     }
+    FORMAT_CACHE_MISSES.fetch_add(1, AtomicOrdering::Relaxed);
+    let formatted = format_tags_with_options(artist, title, rules, bpm);
+    if let Ok(mut cache) = FORMAT_CACHE.lock() {
+        cache.insert(key, formatted.clone());
+    }
+    formatted
+}
 
-    // Remove duplicate artist name from title
-    let artist_with_dash = format!("{formatted_artist} - ");
-    if formatted_title.starts_with(&artist_with_dash) {
-        formatted_title = formatted_title.replacen(&artist_with_dash, "", 1);
+/// Get hit statistics for the formatting cache.
+pub fn cache_stats() -> CacheStats {
+    CacheStats {
+        hits: FORMAT_CACHE_HITS.load(AtomicOrdering::Relaxed),
+        misses: FORMAT_CACHE_MISSES.load(AtomicOrdering::Relaxed),
+        entries: FORMAT_CACHE.lock().map_or(0, |cache| cache.len()),
     }
+}
 
-    // Artist name should not start with a dot since this will make it a hidden file
-    formatted_artist = formatted_artist.trim_start_matches('.').to_string();
+/// Album artist for compilations.
+pub const VARIOUS_ARTISTS: &str = "Various Artists";
+
+/// Separator between descriptors in the subtitle tag, like `Extended Mix / Clean`.
+pub const SUBTITLE_SEPARATOR: &str = " / ";
+
+/// What to do with a year in parentheses at the end of a title, like `Song (1998)`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum YearInTitle {
+    /// Keep the year in the title
+    #[default]
+    Keep,
+    /// Move the year to the year tag if the tag is empty or has the same year
+    Tag,
+}
 
-    use_parenthesis_for_mix(&mut formatted_title);
-    move_feat_from_title_to_artist(&mut formatted_artist, &mut formatted_title);
-    replace_dash_in_parentheses(&mut formatted_title);
-    fix_nested_parentheses(&mut formatted_title);
-    wrap_text_after_parentheses(&mut formatted_title);
-    remove_bpm_in_parentheses_from_end(&mut formatted_title);
-    remove_unmatched_closing_parenthesis(&mut formatted_artist);
+/// Where to keep mix and version descriptors like `Extended Mix` in `Song (Extended Mix)`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Subtitle {
+    /// Keep the title and the subtitle tag as they are
+    #[default]
+    Keep,
+    /// Move descriptors from the end of the title to the subtitle tag (TIT3)
+    Tag,
+    /// Move the subtitle tag back to the end of the title
+    Title,
+}
 
-    // TODO: Fix above so this is not needed
-    formatted_title = formatted_title.replace("((", "(").replace("))", ")");
+/// How the album artist tag (TPE2) is handled.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum AlbumArtist {
+    /// Format an existing album artist with the artist rules
+    #[default]
+    Format,
+    /// Also fill a missing album artist with the main artist, or "Various Artists" for compilations
+    Fill,
+    /// Also fill a missing album artist with "Various Artists"
+    Compilation,
+}
 
-    extract_feat_from_parentheses(&mut formatted_artist);
-    balance_parenthesis(&mut formatted_title);
+/// Separator between the artist and title in formatted filenames.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum FilenameSeparator {
+    /// `Artist - Title`
+    #[default]
+    Hyphen,
+    /// `Artist – Title`
+    EnDash,
+    /// `Artist — Title`
+    EmDash,
+    /// `Artist_Title`
+    Underscore,
+}
 
-    for (regex, replacement) in REGEX_SUBSTITUTES.iter() {
-        formatted_artist = regex.replace_all(&formatted_artist, *replacement).to_string();
-        formatted_title = regex.replace_all(&formatted_title, *replacement).to_string();
+impl FilenameSeparator {
+    /// Dash separators in the order they are tried when parsing existing filenames.
+    const PARSE_ORDER: [Self; 3] = [Self::Hyphen, Self::EnDash, Self::EmDash];
+
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Hyphen => " - ",
+            Self::EnDash => " – ",
+            Self::EmDash => " — ",
+            Self::Underscore => "_",
+        }
     }
 
-    for (pattern, replacement) in &COMMON_SUBSTITUTES {
-        formatted_artist = formatted_artist.replace(pattern, replacement);
-        formatted_title = formatted_title.replace(pattern, replacement);
+    /// Join artist and title, leaving out the separator if either one is empty.
+    #[must_use]
+    pub fn join(self, artist: &str, title: &str) -> String {
+        match (artist.is_empty(), title.is_empty()) {
+            (true, _) => title.to_string(),
+            (false, true) => artist.to_string(),
+            (false, false) => format!("{artist}{}{title}", self.as_str()),
+        }
     }
 
-    if formatted_title == formatted_title.to_uppercase()
-        && formatted_title.chars().count() > 10
-        && !RE_CHARS_AND_DOTS.is_match(&formatted_title)
-    {
-        formatted_title = titlecase::titlecase(&formatted_title);
-        if formatted_artist == formatted_artist.to_uppercase() && formatted_artist.chars().count() > 8 {
-            formatted_artist = titlecase::titlecase(&formatted_artist);
+    /// Split a filename into artist and title at the first known separator.
+    ///
+    /// A dash separator is tried first, then the others in [`Self::PARSE_ORDER`],
+    /// so files named with any of the dashes can be parsed.
+    /// An underscore is only used when it is the configured separator and none of the dashes are found,
+    /// since names like `Track_01` are common without an artist.
+    #[must_use]
+    pub fn split(self, name: &str) -> Option<(&str, &str)> {
+        let underscore = (self == Self::Underscore).then_some(self);
+        std::iter::once(self)
+            .filter(|separator| *separator != Self::Underscore)
+            .chain(Self::PARSE_ORDER)
+            .chain(underscore)
+            .find_map(|separator| name.split_once(separator.as_str()))
+    }
+}
+
+/// Format an album artist with the same rules as the artist.
+///
+/// "Various Artists" is kept as is,
+/// since as an artist it is removed and the real artist is taken from the title.
+#[must_use]
+pub fn format_album_artist(album_artist: &str, options: &FormatOptions) -> String {
+    if album_artist.trim().eq_ignore_ascii_case(VARIOUS_ARTISTS) {
+        return VARIOUS_ARTISTS.to_string();
+    }
+    format_tags_cached(album_artist, "", options).0
+}
+
+/// Split descriptors in parentheses from the end of a title for the subtitle tag.
+///
+/// Featured artists and years stay in the title, and descriptors before them are not moved.
+/// Returns the title without the descriptors and the descriptors joined with [`SUBTITLE_SEPARATOR`],
+/// or `None` if the title does not end with a descriptor or would be empty without it.
+#[must_use]
+pub fn split_subtitle_from_title(title: &str) -> Option<(&str, String)> {
+    let mut rest = title.trim_end();
+    let mut descriptors = Vec::new();
+    while let Some(caps) = RE_TRAILING_PARENTHESES.captures(rest) {
+        let descriptor = caps.get(1)?.as_str().trim();
+        if descriptor.is_empty()
+            || descriptor.to_lowercase().starts_with("feat.")
+            || RE_YEAR_IN_PARENTHESES.is_match(&format!(" ({descriptor})"))
+        {
+            break;
         }
-    } else if RE_CHARS_AND_DOTS.is_match(&formatted_title) {
-        formatted_title = formatted_title.to_uppercase();
+        descriptors.push(descriptor);
+        rest = rest[..caps.get(0)?.start()].trim_end();
+    }
+    if descriptors.is_empty() || rest.is_empty() {
+        return None;
     }
+    descriptors.reverse();
+    Some((rest, descriptors.join(SUBTITLE_SEPARATOR)))
+}
 
-    (formatted_artist.trim().to_string(), formatted_title.trim().to_string())
+/// Add the descriptors from a subtitle tag to the end of the title in parentheses.
+///
+/// Descriptors that are already in the title are not added again.
+#[must_use]
+pub fn join_subtitle_to_title(title: &str, subtitle: &str) -> String {
+    subtitle
+        .split(SUBTITLE_SEPARATOR)
+        .map(str::trim)
+        .filter(|descriptor| !descriptor.is_empty())
+        .fold(title.to_string(), |mut title, descriptor| {
+            let group = format!("({descriptor})");
+            if !title.to_lowercase().contains(&group.to_lowercase()) {
+                if !title.is_empty() {
+                    title.push(' ');
+                }
+                title.push_str(&group);
+            }
+            title
+        })
+}
+
+/// Split a 19xx or 20xx year in parentheses from the end of a title.
+///
+/// Returns the title without the year, or `None` if the title does not end with a year
+/// or would be empty without it.
+#[must_use]
+pub fn split_year_from_title(title: &str) -> Option<(&str, i32)> {
+    let caps = RE_YEAR_IN_PARENTHESES.captures(title)?;
+    let year = caps[1].parse().ok()?;
+    let rest = title[..caps.get(0)?.start()].trim_end();
+    (!rest.is_empty()).then_some((rest, year))
 }
 
 /// Apply filename formatting.
@@ -309,22 +870,66 @@ pub fn format_filename(artist: &str, title: &str) -> (String, String) {
     let mut formatted_title = title.replace('"', "''");
 
     for (regex, replacement) in REGEX_FILENAME_SUBSTITUTES.iter() {
-        formatted_artist = regex.replace_all(&formatted_artist, *replacement).to_string();
-        formatted_title = regex.replace_all(&formatted_title, *replacement).to_string();
+        regex_replace_in_place(&mut formatted_artist, regex, replacement);
+        regex_replace_in_place(&mut formatted_title, regex, replacement);
     }
 
-    (formatted_artist.trim().to_string(), formatted_title.trim().to_string())
+    trim_in_place(&mut formatted_artist);
+    trim_in_place(&mut formatted_title);
+    (formatted_artist, formatted_title)
 }
 
+#[must_use]
 pub fn format_album(album: &str) -> String {
     let mut formatted_album = album.trim().to_string();
-    formatted_album = RE_WWW.replace(&formatted_album, "").to_string();
+    regex_replace_in_place(&mut formatted_album, &RE_WWW, "");
     fix_whitespace(&mut formatted_album);
     formatted_album
 }
 
 pub fn fix_whitespace(text: &mut String) {
-    *text = RE_MULTIPLE_SPACES.replace_all(text, " ").to_string().trim().to_string();
+    regex_replace_in_place(text, &RE_MULTIPLE_SPACES, " ");
+    trim_in_place(text);
+}
+
+/// Replace all occurrences of a literal pattern.
+///
+/// Only allocates a new string when the pattern is found.
+pub fn replace_in_place(text: &mut String, pattern: &str, replacement: &str) {
+    if text.contains(pattern) {
+        *text = text.replace(pattern, replacement);
+    }
+}
+
+/// Replace all regex matches.
+///
+/// Only allocates a new string when the regex matches.
+pub fn regex_replace_in_place(text: &mut String, regex: &Regex, replacement: &str) {
+    if let Cow::Owned(replaced) = regex.replace_all(text, replacement) {
+        *text = replaced;
+    }
+}
+
+/// Remove leading and trailing whitespace without allocating.
+pub fn trim_in_place(text: &mut String) {
+    text.truncate(text.trim_end().len());
+    let start = text.len() - text.trim_start().len();
+    text.drain(..start);
+}
+
+/// Remove all repeated prefixes matching the pattern without allocating.
+fn trim_start_matches_in_place(text: &mut String, pattern: &str) {
+    let start = text.len() - text.trim_start_matches(pattern).len();
+    text.drain(..start);
+}
+
+/// Remove suffix if the text ends with it, ignoring ASCII case.
+fn remove_suffix_ignore_case(text: &mut String, suffix: &str) {
+    if let Some(start) = text.len().checked_sub(suffix.len()) {
+        if text.is_char_boundary(start) && text[start..].eq_ignore_ascii_case(suffix) {
+            text.truncate(start);
+        }
+    }
 }
 
 /// Check parenthesis counts match and insert missing.
@@ -339,18 +944,19 @@ fn balance_parenthesis(title: &mut String) {
 }
 
 fn remove_unmatched_closing_parenthesis(input: &mut String) {
-    *input = input.trim().to_string();
+    trim_in_place(input);
     if input.ends_with(')') && !input.contains('(') {
         input.pop();
     }
 }
 
 fn move_feat_from_title_to_artist(artist: &mut String, title: &mut String) {
-    if let Some(feat_match) = RE_FEAT.find(&title.clone()) {
-        let feat = feat_match.as_str().trim_end_matches(['(', ')', '-']);
+    if let Some(feat_match) = RE_FEAT.find(title) {
+        let feat = feat_match.as_str().trim_end_matches(['(', ')', '-']).to_string();
 
         // Remove the feat from the title
-        *title = title.replace(feat, "").trim().to_string();
+        replace_in_place(title, &feat, "");
+        trim_in_place(title);
 
         // Format feat artists string: remove "feat. ", and change all "and" variations to "&"
         let feat = RE_FEAT_AND
@@ -368,9 +974,8 @@ fn move_feat_from_title_to_artist(artist: &mut String, title: &mut String) {
         for feat_artist in &feat_artists {
             for delimiter in [", ", " & ", " and ", " + "] {
                 // Remove the individual featuring artist from the artist string if present
-                *artist = artist
-                    .replace(&format!("{delimiter}{feat_artist}"), "")
-                    .replace(&format!("{feat_artist}{delimiter}"), "");
+                replace_in_place(artist, &format!("{delimiter}{feat_artist}"), "");
+                replace_in_place(artist, &format!("{feat_artist}{delimiter}"), "");
             }
         }
 
@@ -381,6 +986,35 @@ fn move_feat_from_title_to_artist(artist: &mut String, title: &mut String) {
     }
 }
 
+/// Parenthesized or bracketed descriptors in a title, like `Extended Mix` in `Song (Extended Mix)`.
+#[must_use]
+pub fn title_descriptors(title: &str) -> Vec<&str> {
+    RE_DESCRIPTOR
+        .captures_iter(title)
+        .filter_map(|captures| captures.get(1))
+        .map(|descriptor| descriptor.as_str().trim())
+        .filter(|descriptor| !descriptor.is_empty())
+        .collect()
+}
+
+/// Split a formatted artist like `Artist feat. Guest & Other` into the main artist and featured artists.
+///
+/// The main artist is kept as is since names like `Simon & Garfunkel` are a single act.
+#[must_use]
+pub fn split_featured_artists(artist: &str) -> (&str, Vec<&str>) {
+    artist.split_once(" feat. ").map_or_else(
+        || (artist.trim(), Vec::new()),
+        |(main, feat)| {
+            let featured = feat
+                .split(" & ")
+                .map(str::trim)
+                .filter(|name| !name.is_empty())
+                .collect();
+            (main.trim(), featured)
+        },
+    )
+}
+
 fn add_missing_closing_parentheses(text: &mut String) {
     let mut open_count: usize = 0;
     let mut result = String::new();
@@ -496,11 +1130,10 @@ fn fix_nested_parentheses(text: &mut String) {
         result.push(')');
     }
 
-    *text = result
-        .replace(" )", ")")
-        .replace("( ", "(")
-        .replace(" ()", "")
-        .replace("() ", "");
+    for (pattern, replacement) in [(" )", ")"), ("( ", "("), (" ()", ""), ("() ", "")] {
+        replace_in_place(&mut result, pattern, replacement);
+    }
+    *text = result;
 }
 
 fn extract_feat_from_parentheses(artist: &mut String) {
@@ -513,15 +1146,17 @@ fn extract_feat_from_parentheses(artist: &mut String) {
     }
 }
 
-fn remove_bpm_in_parentheses_from_end(text: &mut String) {
+/// Remove a BPM value from the end of the title.
+///
+/// Only the first matching pattern is used, and the text is kept if its number is outside the BPM range.
+fn remove_bpm_in_parentheses_from_end(text: &mut String, bpm: BpmRange) {
     // Skip some valid titles
     let suffixes = [" (4u)", "33rpm)", "45rpm)", " mix)", " dub)", " eq)", " rip)"];
     let text_lower = text.to_lowercase();
-    if suffixes.iter().any(|suffix| text_lower.ends_with(suffix)) {
+    if suffixes.iter().any(|suffix| text_lower.ends_with(suffix)) || RE_YEAR_IN_PARENTHESES.is_match(text) {
         return;
     }
 
-    let mut result = (*text).to_string();
     let regexes = [
         &RE_BPM_IN_PARENTHESES,
         &RE_BPM_WITH_TEXT,
@@ -530,13 +1165,25 @@ fn remove_bpm_in_parentheses_from_end(text: &mut String) {
         &RE_BPM_WITH_EXTRA_TEXT,
     ];
     for re in regexes {
-        if re.is_match(&result) {
-            result = re.replace_all(&result, "").to_string();
-            break;
+        if !re.is_match(text) {
+            continue;
+        }
+        let result = re.replace_all(text, |caps: &Captures| {
+            let in_range = RE_BPM_NUMBER
+                .find(&caps[0])
+                .and_then(|number| number.as_str().parse::<f64>().ok())
+                .is_some_and(|value| bpm.contains(value));
+            if in_range {
+                String::new()
+            } else {
+                caps[0].to_string()
+            }
+        });
+        if let Cow::Owned(result) = result {
+            *text = result;
         }
+        break;
     }
-
-    *text = result;
 }
 
 fn wrap_text_after_parentheses(text: &mut String) {
@@ -567,15 +1214,268 @@ fn wrap_text_after_parentheses(text: &mut String) {
 }
 
 fn replace_dash_in_parentheses(text: &mut String) {
-    *text = RE_DASH_IN_PARENTHESES
-        .replace_all(text, |caps: &Captures| format!("({}) ({})", &caps[1], &caps[2]))
-        .to_string();
+    if let Cow::Owned(result) =
+        RE_DASH_IN_PARENTHESES.replace_all(text, |caps: &Captures| format!("({}) ({})", &caps[1], &caps[2]))
+    {
+        *text = result;
+    }
+}
+
+impl RuleSet {
+    /// All rule categories.
+    pub const ALL: Self = Self((1 << (RuleCategory::Genre as u16 + 1)) - 1);
+    /// No rule categories.
+    pub const NONE: Self = Self(0);
+
+    #[must_use]
+    pub const fn contains(self, category: RuleCategory) -> bool {
+        self.0 & Self::bit(category) != 0
+    }
+
+    /// Return the set with the category added.
+    #[must_use]
+    pub const fn with(self, category: RuleCategory) -> Self {
+        Self(self.0 | Self::bit(category))
+    }
+
+    /// Return the set with the category removed.
+    #[must_use]
+    pub const fn without(self, category: RuleCategory) -> Self {
+        Self(self.0 & !Self::bit(category))
+    }
+
+    /// True if all rule categories are enabled, which is the normal formatting.
+    #[must_use]
+    pub const fn is_all(self) -> bool {
+        self.0 == Self::ALL.0
+    }
+
+    /// Iterate over the enabled categories.
+    pub fn categories(self) -> impl Iterator<Item = RuleCategory> {
+        RuleCategory::value_variants()
+            .iter()
+            .copied()
+            .filter(move |&category| self.contains(category))
+    }
+
+    const fn bit(category: RuleCategory) -> u16 {
+        1 << category as u16
+    }
+}
+
+impl Default for RuleSet {
+    fn default() -> Self {
+        Self::ALL
+    }
+}
+
+impl FromIterator<RuleCategory> for RuleSet {
+    fn from_iter<I: IntoIterator<Item = RuleCategory>>(iter: I) -> Self {
+        iter.into_iter().fold(Self::NONE, Self::with)
+    }
+}
+
+impl From<Vec<RuleCategory>> for RuleSet {
+    fn from(categories: Vec<RuleCategory>) -> Self {
+        categories.into_iter().collect()
+    }
+}
+
+impl From<RuleSet> for Vec<RuleCategory> {
+    fn from(rules: RuleSet) -> Self {
+        rules.categories().collect()
+    }
+}
+
+impl fmt::Display for RuleCategory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = self.to_possible_value().map(|value| value.get_name().to_string());
+        write!(f, "{}", name.unwrap_or_default())
+    }
+}
+
+impl fmt::Display for RuleSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_all() {
+            write!(f, "all")
+        } else {
+            write!(
+                f,
+                "{}",
+                self.categories()
+                    .map(|category| category.to_string())
+                    .collect::<Vec<_>>()
+                    .join(",")
+            )
+        }
+    }
+}
+
+impl CacheStats {
+    /// Fraction of lookups that were found in the cache.
+    #[must_use]
+    pub fn hit_rate(&self) -> f64 {
+        let total = self.hits + self.misses;
+        if total == 0 {
+            0.0
+        } else {
+            self.hits as f64 / total as f64
+        }
+    }
+}
+
+impl fmt::Display for CacheStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Format cache: {} hits, {} misses, {} entries ({:.1}% hit rate)",
+            self.hits,
+            self.misses,
+            self.entries,
+            100.0 * self.hit_rate()
+        )
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_filename_separator() {
+        assert_eq!(FilenameSeparator::Hyphen.join("Artist", "Song"), "Artist - Song");
+        assert_eq!(FilenameSeparator::EnDash.join("Artist", "Song"), "Artist – Song");
+        assert_eq!(FilenameSeparator::Underscore.join("Artist", "Song"), "Artist_Song");
+        assert_eq!(FilenameSeparator::EnDash.join("", "Song"), "Song");
+
+        assert_eq!(
+            FilenameSeparator::Hyphen.split("Artist – Song"),
+            Some(("Artist", "Song"))
+        );
+        assert_eq!(
+            FilenameSeparator::EnDash.split("Artist - Song – Remix"),
+            Some(("Artist - Song", "Remix"))
+        );
+        assert_eq!(
+            FilenameSeparator::Underscore.split("Artist_Name - Song"),
+            Some(("Artist_Name", "Song"))
+        );
+        assert_eq!(
+            FilenameSeparator::Underscore.split("Artist_Song"),
+            Some(("Artist", "Song"))
+        );
+        assert_eq!(FilenameSeparator::Hyphen.split("Song"), None);
+        assert_eq!(FilenameSeparator::Hyphen.split("Track_01"), None);
+        assert_eq!(FilenameSeparator::EnDash.split("DJ_Mix_2020"), None);
+    }
+
+    #[test]
+    fn test_format_cache() {
+        let artist = "Cache Test Artist Ft. Someone";
+        let title = "Cache Test Song [Extended Mix]";
+        let before = cache_stats();
+        let options = FormatOptions::default();
+        let first = format_tags_cached(artist, title, &options);
+        let second = format_tags_cached(artist, title, &options);
+        let after = cache_stats();
+        assert_eq!(first, format_tags_for_artist_and_title(artist, title));
+        assert_eq!(first, second);
+        assert!(after.hits > before.hits);
+        assert!(after.misses > before.misses);
+        assert!(after.entries > 0);
+
+        let feat_only = FormatOptions {
+            rules: RuleSet::NONE.with(RuleCategory::Feat),
+            ..FormatOptions::default()
+        };
+        assert_eq!(format_tags_cached(artist, title, &feat_only).1, title);
+    }
+
+    #[test]
+    fn test_format_options_state_hash() {
+        assert_eq!(FormatOptions::default().state_hash(), 0);
+        let en_dash = FormatOptions {
+            filename_separator: FilenameSeparator::EnDash,
+            ..FormatOptions::default()
+        };
+        let year_tag = FormatOptions {
+            year_in_title: YearInTitle::Tag,
+            ..FormatOptions::default()
+        };
+        assert_ne!(en_dash.state_hash(), 0);
+        assert_ne!(en_dash.state_hash(), year_tag.state_hash());
+        // Stored in the state so it must not change between builds
+        assert_eq!(en_dash.state_hash(), 5_518_206_336_133_641_711);
+    }
+
+    #[test]
+    fn test_format_with_rules() {
+        let (artist, title) = ("ARTIST ft. Someone", "SONG TITLE [Extended Mix] (128 BPM)");
+        let feat_only = RuleSet::NONE.with(RuleCategory::Feat);
+        assert_eq!(
+            format_tags_with_rules(artist, title, feat_only),
+            (
+                "ARTIST feat. Someone".to_string(),
+                "SONG TITLE [Extended Mix] (128 BPM)".to_string()
+            )
+        );
+        assert_eq!(
+            format_tags_with_rules(artist, title, RuleSet::NONE),
+            (artist.to_string(), title.to_string())
+        );
+        assert_eq!(
+            format_tags_with_rules(artist, title, RuleSet::ALL),
+            format_tags_for_artist_and_title(artist, title)
+        );
+
+        let rules: RuleSet = [RuleCategory::Feat, RuleCategory::Bpm].into_iter().collect();
+        assert_eq!(rules.to_string(), "feat,bpm");
+        assert!(!rules.without(RuleCategory::Bpm).contains(RuleCategory::Bpm));
+        assert_eq!(RuleSet::ALL.to_string(), "all");
+    }
+
+    #[test]
+    fn test_title_descriptors() {
+        assert_eq!(
+            title_descriptors("Song (Clean Extended Mix) [Acapella In-Out] ( )"),
+            vec!["Clean Extended Mix", "Acapella In-Out"]
+        );
+        assert!(title_descriptors("Song").is_empty());
+    }
+
+    #[test]
+    fn test_split_featured_artists() {
+        assert_eq!(
+            split_featured_artists("Simon & Garfunkel feat. Guest & Other"),
+            ("Simon & Garfunkel", vec!["Guest", "Other"])
+        );
+        assert_eq!(split_featured_artists("Artist"), ("Artist", Vec::new()));
+    }
+
+    #[test]
+    fn test_substitution_table_matches_sequential_replace() {
+        let texts = [
+            "",
+            "Artist [Extended Mix]!!!",
+            "Song {feat Someone} (Original Mix)",
+            "Artist Ft. Other & Third",
+            "Track (Clean Intro-Outro)",
+            "Title (Acapella-Intro) (12\" Mix)",
+            "ALL CAPS (Dirty-Intro) [[Remix]]",
+        ];
+        for table in [&*REGEX_NAME_SUBSTITUTES, &*REGEX_SUBSTITUTES] {
+            for text in texts {
+                let mut expected = text.to_string();
+                for (regex, replacement) in &table.substitutes {
+                    expected = regex.replace_all(&expected, *replacement).to_string();
+                }
+                let mut result = text.to_string();
+                table.apply(&mut result);
+                assert_eq!(result, expected, "input: {text}");
+            }
+        }
+    }
+
     #[test]
     fn test_use_parenthesis_for_mix() {
         let mut title = "Azn Danza - Myles Club Edit".to_string();
@@ -623,9 +1523,79 @@ mod tests {
 
         for (input, expected) in test_cases {
             let mut input_string = input.to_string();
-            remove_bpm_in_parentheses_from_end(&mut input_string);
+            remove_bpm_in_parentheses_from_end(&mut input_string, BpmRange::default());
             assert_eq!(input_string, expected);
         }
+
+        let mut title = "Song (Edit) (175)".to_string();
+        remove_bpm_in_parentheses_from_end(&mut title, BpmRange::new(60, 115));
+        assert_eq!(title, "Song (Edit) (175)");
+        remove_bpm_in_parentheses_from_end(&mut title, BpmRange::new(160, 180));
+        assert_eq!(title, "Song (Edit)");
+
+        let mut title = "Song (Edit) (20)".to_string();
+        remove_bpm_in_parentheses_from_end(&mut title, BpmRange::default());
+        assert_eq!(title, "Song (Edit) (20)");
+    }
+
+    #[test]
+    fn test_year_in_parentheses() {
+        let mut title = "Song (1998)".to_string();
+        remove_bpm_in_parentheses_from_end(&mut title, BpmRange::new(50, 2100));
+        assert_eq!(title, "Song (1998)");
+
+        let test_cases = [
+            ("Song (1998)", "Song (1998)"),
+            ("Song [2004]", "Song (2004)"),
+            ("Song - 1998", "Song (1998)"),
+            ("Song (1998) (128)", "Song (1998)"),
+            ("Song (1998) - Remastered", "Song (1998) (Remastered)"),
+            ("Song (Club Mix 1998)", "Song (Club Mix 1998)"),
+        ];
+        for (input, expected) in test_cases {
+            let (_, formatted_title) = format_tags_for_artist_and_title("Artist", input);
+            assert_eq!(formatted_title, expected);
+        }
+
+        assert_eq!(split_year_from_title("Song (1998)"), Some(("Song", 1998)));
+        assert_eq!(split_year_from_title("Song (Edit) (2021)"), Some(("Song (Edit)", 2021)));
+        assert_eq!(split_year_from_title("Song (1850)"), None);
+        assert_eq!(split_year_from_title("Song (150)"), None);
+        assert_eq!(split_year_from_title("(1998)"), None);
+    }
+
+    #[test]
+    fn test_format_album_artist() {
+        let options = FormatOptions::default();
+        assert_eq!(format_album_artist("various artists", &options), VARIOUS_ARTISTS);
+        assert_eq!(format_album_artist("Artist ft. Guest", &options), "Artist feat. Guest");
+        assert_eq!(format_album_artist("  Artist  ", &options), "Artist");
+        assert_eq!(format_album_artist("", &options), "");
+    }
+
+    #[test]
+    fn test_subtitle() {
+        assert_eq!(
+            split_subtitle_from_title("Song (Extended Mix)"),
+            Some(("Song", "Extended Mix".to_string()))
+        );
+        assert_eq!(
+            split_subtitle_from_title("Song (feat. Guest) (Extended Mix) (Clean)"),
+            Some(("Song (feat. Guest)", "Extended Mix / Clean".to_string()))
+        );
+        assert_eq!(split_subtitle_from_title("Song (Extended Mix) (1998)"), None);
+        assert_eq!(split_subtitle_from_title("(Extended Mix)"), None);
+        assert_eq!(split_subtitle_from_title("Song"), None);
+
+        assert_eq!(
+            join_subtitle_to_title("Song (feat. Guest)", "Extended Mix / Clean"),
+            "Song (feat. Guest) (Extended Mix) (Clean)"
+        );
+        assert_eq!(
+            join_subtitle_to_title("Song (Extended Mix)", "Extended Mix"),
+            "Song (Extended Mix)"
+        );
+        assert_eq!(join_subtitle_to_title("Song", ""), "Song");
     }
 
     #[test]
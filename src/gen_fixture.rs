@@ -0,0 +1,19 @@
+use std::path::PathBuf;
+
+use anyhow::Result;
+
+use crate::GenFixtureArgs;
+
+use track_rename::fixture;
+
+/// Generate test files for each combination of the given formats and tag configurations.
+pub fn run(args: &GenFixtureArgs) -> Result<()> {
+    let dir = args.dir.clone().unwrap_or_else(|| PathBuf::from("."));
+    for format in &args.format {
+        for tags in &args.tags {
+            let path = fixture::generate(&dir, format, *tags)?;
+            println!("{}", path.display());
+        }
+    }
+    Ok(())
+}
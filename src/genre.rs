@@ -3,7 +3,7 @@ use std::sync::LazyLock;
 
 use regex::Regex;
 
-use crate::formatting;
+use crate::formatting::{self, SubstitutionTable};
 
 // Map folder names to default genre for that folder.
 // If the genre tag is empty, can apply default genre tag.
@@ -339,55 +339,67 @@ static REGEX_SUBSTITUTES: LazyLock<[(Regex, &'static str); 5]> = LazyLock::new(|
 });
 
 /// Map various genres to the correct version
-static REGEX_MAPPINGS: LazyLock<[(Regex, &'static str); 42]> = LazyLock::new(|| {
-    [
-        (Regex::new(r"(?i)\br\s*[&'n]*\s*b\b").unwrap(), "R&B"),
-        (Regex::new(r"(?i)\bother\b").unwrap(), ""),
-        (Regex::new(r"(?i)\bAccapella\b").unwrap(), "Acapella"),
-        (Regex::new(r"(?i)\bHip Hop\b").unwrap(), "Hip-Hop"),
-        (Regex::new(r"(?i)\bHip / Hop\b").unwrap(), "Hip-Hop"),
-        (Regex::new(r"(?i)\bHip-Hop 90's\b").unwrap(), "Hip-Hop 90s"),
-        (Regex::new(r"(?i)\bHip-Hop 80's\b").unwrap(), "Hip-Hop 80s"),
-        (Regex::new(r"(?i)\bHip-Hop 90$").unwrap(), "Hip-Hop 90s"),
-        (Regex::new(r"(?i)\bHip-Hop 80$").unwrap(), "Hip-Hop 80s"),
-        (Regex::new(r"(?i)\b90's Hip-Hop\b").unwrap(), "Hip-Hop 90s"),
-        (Regex::new(r"(?i)\b80's Hip-Hop\b").unwrap(), "Hip-Hop 80s"),
-        (Regex::new(r"(?i)\bHip-Hop / Rap\b").unwrap(), "Hip-Hop"),
-        (Regex::new(r"(?i)\bRap & Hip-Hop\b").unwrap(), "Hip-Hop"),
-        (Regex::new(r"(?i)^Rap$").unwrap(), "Hip-Hop"),
-        (Regex::new(r"(?i)\bNu Disco / Disco\b").unwrap(), "Disco Nu"),
-        (Regex::new(r"(?i)\bSoul / Funk / Disco\b").unwrap(), "Funk"),
-        (Regex::new(r"(?i)\bFunk / Soul\b").unwrap(), "Soul"),
-        (Regex::new(r"(?i)\bSoul / Funk\b").unwrap(), "Soul"),
-        (Regex::new(r"(?i)\bAfro beats\b").unwrap(), "Afrobeats"),
-        (Regex::new(r"(?i)\bblend\b").unwrap(), "Mashup"),
-        (Regex::new(r"(?i)\bDrum 'n' Bass\b").unwrap(), "Drum & Bass"),
-        (Regex::new(r"(?i)\bD'n'B\b").unwrap(), "Drum & Bass"),
-        (Regex::new(r"(?i)\bD&B\b").unwrap(), "Drum & Bass"),
-        (Regex::new(r"(?i)\bDisco, Funk\b").unwrap(), "Disco"),
-        (Regex::new(r"(?i)\bDisco Funk\b").unwrap(), "Disco"),
-        (Regex::new(r"(?i)\bFunk / Boogie\b").unwrap(), "Funk Boogie"),
-        (Regex::new(r"(?i)\bHouse / Funk\b").unwrap(), "House"),
-        (Regex::new(r"(?i)\bHousemusic\b").unwrap(), "House"),
-        (Regex::new(r"(?i)^House, Deep House\b").unwrap(), "House Deep"),
-        (Regex::new(r"(?i)^West Coast$").unwrap(), "Hip-Hop West Coast"),
-        (Regex::new(r"(?i)^West Coast, Hip-Hop$").unwrap(), "Hip-Hop West Coast"),
-        (Regex::new(r"(?i)^Dance, Electro Pop$").unwrap(), "Dance"),
-        (Regex::new(r"(?i)^90s X Golden Era$").unwrap(), "Hip-Hop 90s"),
-        (Regex::new(r"(?i)\bB-more\b").unwrap(), "Baltimore Club"),
-        (Regex::new(r"(?i)\bBmore\b").unwrap(), "Baltimore Club"),
-        (Regex::new(r"(?i)\bBreaks, Funk\b").unwrap(), "Funk Breaks"),
-        (Regex::new(r"(?i)\bClassic House\b").unwrap(), "House Old School"),
-        (Regex::new(r"(?i)\bHouse Classic\b").unwrap(), "House Old School"),
-        (Regex::new(r"(?i)^Italo$").unwrap(), "Disco Italo"),
-        (Regex::new(r"(?i)\b70's\b").unwrap(), "70s"),
-        (Regex::new(r"(?i)\b80's\b").unwrap(), "80s"),
-        (Regex::new(r"(?i)\b90's\b").unwrap(), "90s"),
-    ]
+static REGEX_MAPPINGS: LazyLock<SubstitutionTable> = LazyLock::new(|| {
+    SubstitutionTable::new(&[
+        (r"(?i)\br\s*[&'n]*\s*b\b", "R&B"),
+        (r"(?i)\bother\b", ""),
+        (r"(?i)\bAccapella\b", "Acapella"),
+        (r"(?i)\bHip Hop\b", "Hip-Hop"),
+        (r"(?i)\bHip / Hop\b", "Hip-Hop"),
+        (r"(?i)\bHip-Hop 90's\b", "Hip-Hop 90s"),
+        (r"(?i)\bHip-Hop 80's\b", "Hip-Hop 80s"),
+        (r"(?i)\bHip-Hop 90$", "Hip-Hop 90s"),
+        (r"(?i)\bHip-Hop 80$", "Hip-Hop 80s"),
+        (r"(?i)\b90's Hip-Hop\b", "Hip-Hop 90s"),
+        (r"(?i)\b80's Hip-Hop\b", "Hip-Hop 80s"),
+        (r"(?i)\bHip-Hop / Rap\b", "Hip-Hop"),
+        (r"(?i)\bRap & Hip-Hop\b", "Hip-Hop"),
+        (r"(?i)^Rap$", "Hip-Hop"),
+        (r"(?i)\bNu Disco / Disco\b", "Disco Nu"),
+        (r"(?i)\bSoul / Funk / Disco\b", "Funk"),
+        (r"(?i)\bFunk / Soul\b", "Soul"),
+        (r"(?i)\bSoul / Funk\b", "Soul"),
+        (r"(?i)\bAfro beats\b", "Afrobeats"),
+        (r"(?i)\bblend\b", "Mashup"),
+        (r"(?i)\bDrum 'n' Bass\b", "Drum & Bass"),
+        (r"(?i)\bD'n'B\b", "Drum & Bass"),
+        (r"(?i)\bD&B\b", "Drum & Bass"),
+        (r"(?i)\bDisco, Funk\b", "Disco"),
+        (r"(?i)\bDisco Funk\b", "Disco"),
+        (r"(?i)\bFunk / Boogie\b", "Funk Boogie"),
+        (r"(?i)\bHouse / Funk\b", "House"),
+        (r"(?i)\bHousemusic\b", "House"),
+        (r"(?i)^House, Deep House\b", "House Deep"),
+        (r"(?i)^West Coast$", "Hip-Hop West Coast"),
+        (r"(?i)^West Coast, Hip-Hop$", "Hip-Hop West Coast"),
+        (r"(?i)^Dance, Electro Pop$", "Dance"),
+        (r"(?i)^90s X Golden Era$", "Hip-Hop 90s"),
+        (r"(?i)\bB-more\b", "Baltimore Club"),
+        (r"(?i)\bBmore\b", "Baltimore Club"),
+        (r"(?i)\bBreaks, Funk\b", "Funk Breaks"),
+        (r"(?i)\bClassic House\b", "House Old School"),
+        (r"(?i)\bHouse Classic\b", "House Old School"),
+        (r"(?i)^Italo$", "Disco Italo"),
+        (r"(?i)\b70's\b", "70s"),
+        (r"(?i)\b80's\b", "80s"),
+        (r"(?i)\b90's\b", "90s"),
+    ])
 });
 
 static RE_HOUSE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^[^,]* House$").unwrap());
 
+/// All regexes used for formatting genres.
+///
+/// Used for profiling the formatting pipeline.
+pub fn regexes() -> Vec<&'static Regex> {
+    REGEX_SUBSTITUTES
+        .iter()
+        .map(|(regex, _)| regex)
+        .chain(REGEX_MAPPINGS.regexes())
+        .chain([&*RE_HOUSE])
+        .collect()
+}
+
 /// Format genre string.
 pub fn format_genre(genre: &str) -> String {
     let mut formatted_genre = genre.trim().to_string();
@@ -396,23 +408,44 @@ pub fn format_genre(genre: &str) -> String {
     }
 
     for (pattern, replacement) in &COMMON_SUBSTITUTES {
-        formatted_genre = formatted_genre.replace(pattern, replacement);
+        formatting::replace_in_place(&mut formatted_genre, pattern, replacement);
     }
 
     for (regex, replacement) in REGEX_SUBSTITUTES.iter() {
-        formatted_genre = regex.replace_all(&formatted_genre, *replacement).to_string();
+        formatting::regex_replace_in_place(&mut formatted_genre, regex, replacement);
     }
 
-    for (regex, replacement) in REGEX_MAPPINGS.iter() {
-        formatted_genre = regex.replace_all(&formatted_genre, *replacement).to_string();
-    }
+    REGEX_MAPPINGS.apply(&mut formatted_genre);
 
-    formatted_genre = formatted_genre.replace("Original Samples / ", "").replace(" / ", ", ");
+    formatting::replace_in_place(&mut formatted_genre, "Original Samples / ", "");
+    formatting::replace_in_place(&mut formatted_genre, " / ", ", ");
 
     reorder_house_genres(&mut formatted_genre);
     formatting::fix_whitespace(&mut formatted_genre);
+    formatting::replace_in_place(&mut formatted_genre, " / ", ", ");
+    formatted_genre
+}
 
-    formatted_genre.replace(" / ", ", ")
+/// Mapped genre for a folder if the genre tag has nothing in common with it.
+///
+/// Genres agree when they share a word, so "House Deep" fits a "House" folder but "Techno" does not fit "Funk".
+/// Empty genres and folders without a mapping are never a conflict.
+#[must_use]
+pub fn folder_genre_conflict(directory: &str, genre: &str) -> Option<&'static str> {
+    let folder_genre = *GENRE_MAPPINGS.get(directory)?;
+    let genre = format_genre(genre);
+    if genre.is_empty() {
+        return None;
+    }
+    let words = |text: &str| -> Vec<String> {
+        text.split(|c: char| c.is_whitespace() || c == ',')
+            .filter(|word| !word.is_empty())
+            .map(str::to_lowercase)
+            .collect()
+    };
+    let folder_words = words(folder_genre);
+    let shared = words(&genre).iter().any(|word| folder_words.contains(word));
+    (!shared).then_some(folder_genre)
 }
 
 /// Reorder house genres to start with "House".
@@ -431,6 +464,15 @@ fn reorder_house_genres(genre: &mut String) {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_folder_genre_conflict() {
+        assert_eq!(folder_genre_conflict("FUNK CLASSICS", "Techno"), Some("Funk"));
+        assert_eq!(folder_genre_conflict("FUNK CLASSICS", "Funk Boogie"), None);
+        assert_eq!(folder_genre_conflict("FUNK CLASSICS", "funk"), None);
+        assert_eq!(folder_genre_conflict("FUNK CLASSICS", ""), None);
+        assert_eq!(folder_genre_conflict("Not A Mapped Folder", "Techno"), None);
+    }
+
     #[test]
     fn test_rnb() {
         assert_eq!(format_genre(" Rnb   "), "R&B");
@@ -0,0 +1,107 @@
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use colored::Colorize;
+
+use crate::audit;
+use crate::HistoryArgs;
+
+use track_rename::state::{RenameEntry, RenameHistory, TagEntry, TagHistory};
+use track_rename::track_id;
+use track_rename::utils;
+
+/// One recorded change to a file.
+enum Change<'a> {
+    Tags(&'a TagEntry),
+    Rename(&'a RenameEntry),
+}
+
+impl Change<'_> {
+    const fn timestamp(&self) -> u64 {
+        match self {
+            Self::Tags(entry) => entry.timestamp,
+            Self::Rename(entry) => entry.timestamp,
+        }
+    }
+
+    fn user(&self) -> &str {
+        match self {
+            Self::Tags(entry) => &entry.user,
+            Self::Rename(entry) => &entry.user,
+        }
+    }
+}
+
+/// Print the tag values and paths a file has had, oldest first.
+pub fn run(args: &HistoryArgs) -> Result<()> {
+    let path = dunce::canonicalize(&args.file)
+        .or_else(|_| std::path::absolute(&args.file))
+        .with_context(|| format!("Failed to resolve path: {}", args.file.display()))?;
+
+    let renames = RenameHistory::load();
+    // An earlier path shows the history of the file it was renamed to
+    let path = renames.resolved().remove(&path).unwrap_or(path);
+    let mut paths = renames.previous_paths(&path);
+    paths.push(path.clone());
+    let tags = TagHistory::load();
+    // Entries with the same track ID belong to this file even if it was renamed outside track-rename
+    let id = track_id::read_from_path(&path).unwrap_or_default();
+    let same_file =
+        |entry_path: &PathBuf, entry_id: &str| paths.contains(entry_path) || (!id.is_empty() && entry_id == id);
+
+    let mut changes: Vec<Change> = tags
+        .entries()
+        .iter()
+        .filter(|entry| same_file(&entry.path, &entry.id))
+        .map(Change::Tags)
+        .chain(
+            renames
+                .entries()
+                .iter()
+                .filter(|entry| same_file(&entry.new, &entry.id))
+                .map(Change::Rename),
+        )
+        .collect();
+    // Stable sort keeps tags written before the rename in the same second first
+    changes.sort_by_key(Change::timestamp);
+
+    println!("{}", format!("History: {}", path.display()).bold());
+    if !id.is_empty() {
+        println!("Track ID: {}", id.cyan());
+    }
+    if changes.is_empty() {
+        println!("{}", "No recorded changes".yellow());
+        return Ok(());
+    }
+    for change in &changes {
+        println!(
+            "{} {}",
+            utils::format_timestamp(change.timestamp()).dimmed(),
+            audit::display_user(change.user()).cyan()
+        );
+        match change {
+            Change::Tags(entry) => {
+                for (name, old, new) in [
+                    ("Artist", &entry.old.artist, &entry.new.artist),
+                    ("Title", &entry.old.title, &entry.new.title),
+                    ("Album", &entry.old.album, &entry.new.album),
+                    ("Genre", &entry.old.genre, &entry.new.genre),
+                ] {
+                    if old != new {
+                        print!("  {:<8}", format!("{name}:"));
+                        utils::print_diff(old, new);
+                    }
+                }
+            }
+            Change::Rename(entry) => {
+                print!("  {:<8}", "Path:");
+                if entry.old.parent() == entry.new.parent() {
+                    utils::print_diff(&audit::file_name(&entry.old), &audit::file_name(&entry.new));
+                } else {
+                    utils::print_diff(&entry.old.display().to_string(), &entry.new.display().to_string());
+                }
+            }
+        }
+    }
+    Ok(())
+}
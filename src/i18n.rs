@@ -0,0 +1,354 @@
+use std::fmt;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+/// Language used for prompts, warnings, and summaries.
+static LANGUAGE: AtomicU8 = AtomicU8::new(Language::English as u8);
+
+/// Environment variables checked for the message language, in order of precedence.
+const LANGUAGE_ENV_VARS: [&str; 3] = ["LC_ALL", "LC_MESSAGES", "LANG"];
+
+/// Supported languages for user-facing messages.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Language {
+    #[default]
+    English,
+    Finnish,
+}
+
+/// User-facing messages with a translation for each supported language.
+///
+/// Messages containing `{}` are filled in order with [`Message::format`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Message {
+    Proceed,
+    AnswerHint,
+    NotInteractive,
+    HelpYes,
+    HelpNo,
+    HelpEdit,
+    HelpAll,
+    HelpQuit,
+    ApplyingAll,
+    ApplySimilar,
+    ApprovedSimilar,
+    Stopping,
+    NoTracks,
+    ProcessingTrack,
+    ProcessingTracks,
+    PrintOnlyMode,
+    ReadOnlyMode,
+    PartialRules,
+    SkippingUnchanged,
+    QuickCheck,
+    RetryingFailed,
+    MissingGenreMapping,
+    EmptyGenreMapping,
+    TagTooLong,
+    TagShortened,
+    OverwritingFile,
+    Finished,
+    AllGood,
+    Updated,
+    Directories,
+    DirectorySummary,
+    CaseOnly,
+    SummaryTags,
+    SummaryRenamed,
+    SummaryConverted,
+    SummaryRepaired,
+    SummaryVbrHeader,
+    SummaryAifc,
+    SummaryResampled,
+    SummaryWrongFormat,
+    SummaryDeleted,
+    SummaryDuplicate,
+    SummaryLinked,
+    SummaryUpgraded,
+    SummaryId3v1Removed,
+    SummaryApeRemoved,
+    SummaryChaptersRemoved,
+    SummaryIdsAdded,
+    SummaryRelinked,
+    SummaryArtistConflicts,
+    SummaryArtwork,
+    SummaryMinorSkipped,
+    SummaryMinorApproved,
+    SummaryFramesSkipped,
+    SummaryDuplicateFrames,
+    SummaryPlaceholders,
+    SummaryRetried,
+    SummaryFailed,
+    SummaryBadWrites,
+    SummaryRenameFailed,
+    SummaryDeclined,
+    SummaryUnchanged,
+    AllDeclined,
+    SummarySpaceSaved,
+    SummarySpaceAdded,
+}
+
+impl Language {
+    /// Parse a language name or locale like `fi`, `fi_FI.UTF-8`, or `en-US`.
+    #[must_use]
+    pub fn from_code(code: &str) -> Option<Self> {
+        let code = code.trim().to_lowercase();
+        match code.split(['_', '-', '.', '@']).next().unwrap_or_default() {
+            "en" | "english" | "c" | "posix" => Some(Self::English),
+            "fi" | "finnish" | "suomi" => Some(Self::Finnish),
+            _ => None,
+        }
+    }
+
+    /// Get language from the locale environment variables.
+    ///
+    /// Uses the first variable that is set like the C library does,
+    /// so `LC_ALL` overrides `LC_MESSAGES` which overrides `LANG`.
+    #[must_use]
+    pub fn from_env() -> Option<Self> {
+        LANGUAGE_ENV_VARS
+            .iter()
+            .filter_map(|name| std::env::var(name).ok())
+            .find(|value| !value.trim().is_empty())
+            .and_then(|value| Self::from_code(&value))
+    }
+
+    const fn from_u8(value: u8) -> Self {
+        match value {
+            1 => Self::Finnish,
+            _ => Self::English,
+        }
+    }
+}
+
+impl Message {
+    /// Message text in the current language.
+    #[must_use]
+    pub fn text(self) -> &'static str {
+        self.text_in(language())
+    }
+
+    /// Message text in the given language.
+    #[must_use]
+    pub const fn text_in(self, language: Language) -> &'static str {
+        match language {
+            Language::English => self.english(),
+            Language::Finnish => self.finnish(),
+        }
+    }
+
+    /// Message in the current language with each `{}` replaced by the next argument.
+    #[must_use]
+    pub fn format(self, args: &[&dyn fmt::Display]) -> String {
+        fill(self.text(), args)
+    }
+
+    const fn english(self) -> &'static str {
+        match self {
+            Self::Proceed => "Proceed",
+            Self::AnswerHint => "Answer {}",
+            Self::NotInteractive => {
+                "Can't ask for confirmation because stdin is not a terminal, \
+                use --force, --assume-yes, --assume-no, or --prompt-protocol json"
+            }
+            Self::HelpYes => "y to apply",
+            Self::HelpNo => "n to skip",
+            Self::HelpEdit => "e to edit",
+            Self::HelpAll => "a to apply all remaining",
+            Self::HelpQuit => "q to quit",
+            Self::ApplyingAll => "Applying all remaining changes",
+            Self::ApplySimilar => "Apply this change to all {} similar tracks:",
+            Self::ApprovedSimilar => "Approved with similar tracks",
+            Self::Stopping => "Stopping, remaining tracks will not be processed",
+            Self::NoTracks => "No tracks to process",
+            Self::ProcessingTrack => "Processing 1 track...",
+            Self::ProcessingTracks => "Processing {} tracks...",
+            Self::PrintOnlyMode => "Running in print-only mode",
+            Self::ReadOnlyMode => "Running in read-only mode, no files will be written",
+            Self::PartialRules => "Only applying formatting rules: {}",
+            Self::SkippingUnchanged => "Skipping {} unchanged tracks",
+            Self::QuickCheck => "Quick check: {} of {} filenames need formatting",
+            Self::RetryingFailed => "Retrying {} failed files ({} / {})...",
+            Self::MissingGenreMapping => "WARNING: DJ music folder missing genre mapping: {}",
+            Self::EmptyGenreMapping => "WARNING: Empty genre mapping for: {}",
+            Self::TagTooLong => "WARNING: {} is {} characters, over the limit of {}: {}",
+            Self::TagShortened => "Shortened {} from {} to at most {} characters: {}",
+            Self::OverwritingFile => "Overwriting existing file: {}",
+            Self::Finished => "Finished",
+            Self::AllGood => "All Good",
+            Self::Updated => "Updated:",
+            Self::Directories => "Directories:",
+            Self::DirectorySummary => "{} processed, {} tags fixed, {} renamed, {} errors",
+            Self::CaseOnly => "case only",
+            Self::SummaryTags => "Fix tags:",
+            Self::SummaryRenamed => "Renamed:",
+            Self::SummaryConverted => "Converted:",
+            Self::SummaryRepaired => "Repaired:",
+            Self::SummaryVbrHeader => "VBR header:",
+            Self::SummaryAifc => "AIFC converted:",
+            Self::SummaryResampled => "Resampled:",
+            Self::SummaryWrongFormat => "Wrong format:",
+            Self::SummaryDeleted => "Deleted:",
+            Self::SummaryDuplicate => "Duplicate:",
+            Self::SummaryLinked => "Linked:",
+            Self::SummaryUpgraded => "Upgraded tags:",
+            Self::SummaryId3v1Removed => "ID3v1 removed:",
+            Self::SummaryApeRemoved => "APEv2 removed:",
+            Self::SummaryChaptersRemoved => "Chapters removed:",
+            Self::SummaryIdsAdded => "Track IDs added:",
+            Self::SummaryRelinked => "Relinked:",
+            Self::SummaryArtistConflicts => "Artist from filename:",
+            Self::SummaryArtwork => "Artwork resized:",
+            Self::SummaryMinorSkipped => "Minor skipped:",
+            Self::SummaryMinorApproved => "Minor approved:",
+            Self::SummaryFramesSkipped => "Frames skipped:",
+            Self::SummaryDuplicateFrames => "Duplicate frames:",
+            Self::SummaryPlaceholders => "Placeholders skipped:",
+            Self::SummaryRetried => "Recovered on retry:",
+            Self::SummaryFailed => "Failed:",
+            Self::SummaryBadWrites => "Bad writes:",
+            Self::SummaryRenameFailed => "Failed renames:",
+            Self::SummaryDeclined => "Declined:",
+            Self::SummaryUnchanged => "Unchanged:",
+            Self::AllDeclined => "All changes were declined, nothing was modified",
+            Self::SummarySpaceSaved => "Space saved:",
+            Self::SummarySpaceAdded => "Space added:",
+        }
+    }
+
+    const fn finnish(self) -> &'static str {
+        match self {
+            Self::Proceed => "Jatketaanko",
+            Self::AnswerHint => "Vastaa {}",
+            Self::NotInteractive => {
+                "Vahvistusta ei voi kysyä, koska syöte ei ole pääte, \
+                käytä --force, --assume-yes, --assume-no tai --prompt-protocol json"
+            }
+            Self::HelpYes => "y hyväksyy",
+            Self::HelpNo => "n ohittaa",
+            Self::HelpEdit => "e muokkaa",
+            Self::HelpAll => "a hyväksyy kaikki loput",
+            Self::HelpQuit => "q lopettaa",
+            Self::ApplyingAll => "Hyväksytään kaikki jäljellä olevat muutokset",
+            Self::ApplySimilar => "Tee tämä muutos kaikille {} samanlaiselle kappaleelle:",
+            Self::ApprovedSimilar => "Hyväksytty samanlaisten kappaleiden kanssa",
+            Self::Stopping => "Lopetetaan, jäljellä olevia kappaleita ei käsitellä",
+            Self::NoTracks => "Ei käsiteltäviä kappaleita",
+            Self::ProcessingTrack => "Käsitellään 1 kappale...",
+            Self::ProcessingTracks => "Käsitellään {} kappaletta...",
+            Self::PrintOnlyMode => "Vain tulostus, tiedostoja ei muuteta",
+            Self::ReadOnlyMode => "Vain luku, mitään tiedostoja ei kirjoiteta",
+            Self::PartialRules => "Käytetään vain muotoilusääntöjä: {}",
+            Self::SkippingUnchanged => "Ohitetaan {} muuttumatonta kappaletta",
+            Self::QuickCheck => "Pikatarkistus: {} / {} tiedostonimeä vaatii muotoilua",
+            Self::RetryingFailed => "Yritetään uudelleen {} epäonnistunutta tiedostoa ({} / {})...",
+            Self::MissingGenreMapping => "VAROITUS: DJ-musiikkikansiolta puuttuu genremääritys: {}",
+            Self::EmptyGenreMapping => "VAROITUS: Tyhjä genremääritys kansiolle: {}",
+            Self::TagTooLong => "VAROITUS: {} on {} merkkiä, yli rajan {}: {}",
+            Self::TagShortened => "Lyhennettiin {} {} merkistä enintään {} merkkiin: {}",
+            Self::OverwritingFile => "Korvataan olemassa oleva tiedosto: {}",
+            Self::Finished => "Valmis",
+            Self::AllGood => "Kaikki kunnossa",
+            Self::Updated => "Päivitetty:",
+            Self::Directories => "Kansiot:",
+            Self::DirectorySummary => "{} käsitelty, {} tagia korjattu, {} nimetty uudelleen, {} virhettä",
+            Self::CaseOnly => "vain kirjainkoko",
+            Self::SummaryTags => "Tagit:",
+            Self::SummaryRenamed => "Nimetty:",
+            Self::SummaryConverted => "Muunnettu:",
+            Self::SummaryRepaired => "Korjattu:",
+            Self::SummaryVbrHeader => "VBR-otsake:",
+            Self::SummaryAifc => "AIFC muunnettu:",
+            Self::SummaryResampled => "Näytteistetty:",
+            Self::SummaryWrongFormat => "Väärä tiedostomuoto:",
+            Self::SummaryDeleted => "Poistettu:",
+            Self::SummaryDuplicate => "Kaksoiskappale:",
+            Self::SummaryLinked => "Linkitetty:",
+            Self::SummaryUpgraded => "Tagiversio päivitetty:",
+            Self::SummaryId3v1Removed => "ID3v1 poistettu:",
+            Self::SummaryApeRemoved => "APEv2 poistettu:",
+            Self::SummaryChaptersRemoved => "Luvut poistettu:",
+            Self::SummaryIdsAdded => "Tunnisteita lisätty:",
+            Self::SummaryRelinked => "Linkitetty uudelleen:",
+            Self::SummaryArtistConflicts => "Artisti tiedostonimestä:",
+            Self::SummaryArtwork => "Kansikuvia pienennetty:",
+            Self::SummaryMinorSkipped => "Pieni, ohitettu:",
+            Self::SummaryMinorApproved => "Pieni, hyväksytty:",
+            Self::SummaryFramesSkipped => "Kehyksiä ohitettu:",
+            Self::SummaryDuplicateFrames => "Kaksoiskehykset:",
+            Self::SummaryPlaceholders => "Pilvitiedostoja ohitettu:",
+            Self::SummaryRetried => "Onnistui uudella yrityksellä:",
+            Self::SummaryFailed => "Epäonnistui:",
+            Self::SummaryBadWrites => "Virheellinen:",
+            Self::SummaryRenameFailed => "Nimeäminen epäonnistui:",
+            Self::SummaryDeclined => "Hylätty:",
+            Self::SummaryUnchanged => "Ennallaan:",
+            Self::AllDeclined => "Kaikki muutokset hylättiin, mitään ei muutettu",
+            Self::SummarySpaceSaved => "Tilaa säästetty:",
+            Self::SummarySpaceAdded => "Tilaa lisätty:",
+        }
+    }
+}
+
+impl fmt::Display for Message {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.text())
+    }
+}
+
+/// Set the language used for user-facing messages.
+pub fn set_language(language: Language) {
+    LANGUAGE.store(language as u8, Ordering::Relaxed);
+}
+
+/// Get the language used for user-facing messages.
+#[must_use]
+pub fn language() -> Language {
+    Language::from_u8(LANGUAGE.load(Ordering::Relaxed))
+}
+
+/// Replace each `{}` in the template with the next argument.
+fn fill(template: &str, args: &[&dyn fmt::Display]) -> String {
+    let mut result = String::with_capacity(template.len());
+    let mut args = args.iter();
+    let mut parts = template.split("{}");
+    if let Some(first) = parts.next() {
+        result.push_str(first);
+    }
+    for part in parts {
+        if let Some(arg) = args.next() {
+            result.push_str(&arg.to_string());
+        }
+        result.push_str(part);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_language_from_code() {
+        assert_eq!(Language::from_code("fi"), Some(Language::Finnish));
+        assert_eq!(Language::from_code("fi_FI.UTF-8"), Some(Language::Finnish));
+        assert_eq!(Language::from_code("en-US"), Some(Language::English));
+        assert_eq!(Language::from_code("C.UTF-8"), Some(Language::English));
+        assert_eq!(Language::from_code("Finnish"), Some(Language::Finnish));
+        assert_eq!(Language::from_code("de_DE"), None);
+        assert_eq!(Language::from_code(""), None);
+    }
+
+    #[test]
+    fn test_fill_message() {
+        assert_eq!(
+            fill(Message::ProcessingTracks.text_in(Language::English), &[&12]),
+            "Processing 12 tracks..."
+        );
+        assert_eq!(
+            fill(Message::ProcessingTracks.text_in(Language::Finnish), &[&12]),
+            "Käsitellään 12 kappaletta..."
+        );
+        assert_eq!(fill("{} and {}", &[&"a"]), "a and ");
+        assert_eq!(fill("no args", &[&1]), "no args");
+    }
+}
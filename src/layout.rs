@@ -0,0 +1,122 @@
+use std::io::IsTerminal;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crossterm::terminal;
+
+/// Output width given with `--width`, or zero to use the terminal width.
+static WIDTH_OVERRIDE: AtomicUsize = AtomicUsize::new(0);
+
+/// Smallest width used for shortening, so very narrow values still show some text.
+const MIN_WIDTH: usize = 20;
+
+/// Number of unchanged characters kept next to a change when shortening.
+const CONTEXT: usize = 8;
+
+const ELLIPSIS: char = '…';
+
+/// Use a fixed output width instead of the terminal width, for example when piping output.
+pub fn set_width(width: Option<usize>) {
+    WIDTH_OVERRIDE.store(width.unwrap_or_default(), Ordering::Relaxed);
+}
+
+/// Output width in columns.
+///
+/// Returns `None` when output is not a terminal and no width was given,
+/// in which case lines should not be shortened.
+#[must_use]
+pub fn width() -> Option<usize> {
+    match WIDTH_OVERRIDE.load(Ordering::Relaxed) {
+        0 if std::io::stdout().is_terminal() => terminal::size().ok().map(|(columns, _)| usize::from(columns)),
+        0 => None,
+        width => Some(width),
+    }
+}
+
+/// Shorten text to the given width, ending with an ellipsis if anything was removed.
+#[must_use]
+pub fn truncate(text: &str, width: usize) -> String {
+    let width = width.max(MIN_WIDTH);
+    if text.chars().count() <= width {
+        return text.to_string();
+    }
+    let mut truncated: String = text.chars().take(width - 1).collect();
+    truncated.push(ELLIPSIS);
+    truncated
+}
+
+/// Shorten an old and new value to fit the given width while keeping the changed part visible.
+///
+/// Unchanged text at the start and end is replaced with an ellipsis first,
+/// keeping a few characters of context around the change.
+/// If the change itself is too long, the end is cut off.
+#[must_use]
+pub fn shorten_pair(old: &str, new: &str, width: usize) -> (String, String) {
+    let width = width.max(MIN_WIDTH);
+    let old_chars: Vec<char> = old.chars().collect();
+    let new_chars: Vec<char> = new.chars().collect();
+    let longest = old_chars.len().max(new_chars.len());
+    if longest <= width {
+        return (old.to_string(), new.to_string());
+    }
+
+    let prefix = old_chars.iter().zip(&new_chars).take_while(|(a, b)| a == b).count();
+    let suffix = old_chars[prefix..]
+        .iter()
+        .rev()
+        .zip(new_chars[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    // Removing characters and adding an ellipsis saves one character less than was removed
+    let mut excess = longest - width;
+    let mut cut = |common: usize| {
+        if excess == 0 || common <= CONTEXT + 1 {
+            return 0;
+        }
+        let removed = (common - CONTEXT).min(excess + 1);
+        excess -= removed - 1;
+        removed
+    };
+    let suffix_cut = cut(suffix);
+    let prefix_cut = cut(prefix);
+
+    let shorten = |chars: &[char]| {
+        let mut text = String::new();
+        if prefix_cut > 0 {
+            text.push(ELLIPSIS);
+        }
+        text.extend(&chars[prefix_cut..chars.len() - suffix_cut]);
+        if suffix_cut > 0 {
+            text.push(ELLIPSIS);
+        }
+        truncate(&text, width)
+    };
+    (shorten(&old_chars), shorten(&new_chars))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shorten_pair() {
+        let old = "Some Very Long Artist Name - A Very Long Song Title (Extended Mix)";
+        let new = "Some Very Long Artist Name - A Very Long Song Title (Extended Version)";
+        assert_eq!(shorten_pair(old, new, 100), (old.to_string(), new.to_string()));
+
+        let (short_old, short_new) = shorten_pair(old, new, 40);
+        assert!(short_new.chars().count() <= 40);
+        assert!(short_old.starts_with(ELLIPSIS) && short_new.starts_with(ELLIPSIS));
+        assert!(short_old.ends_with("(Extended Mix)"));
+        assert!(short_new.ends_with("(Extended Version)"));
+
+        let old = "Artist - Song (Remix) and a long tail that is exactly the same";
+        let new = "Artist - Song (Edit) and a long tail that is exactly the same";
+        let (short_old, short_new) = shorten_pair(old, new, 30);
+        assert_eq!(short_old, "Artist - Song (Remix) and a l…");
+        assert_eq!(short_new, "Artist - Song (Edit) and a l…");
+
+        assert_eq!(truncate("Short", 80), "Short");
+        assert_eq!(truncate(&"x".repeat(30), 20).chars().count(), 20);
+    }
+}
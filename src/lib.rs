@@ -1,8 +1,39 @@
+pub mod ape;
+pub mod artists;
+pub mod artwork;
+pub mod audio;
+pub mod bpm_range;
+pub mod canonical_name;
+pub mod corpus;
+pub mod duplicate_frames;
+pub mod error;
+pub mod events;
+pub mod failure;
 pub mod file_format;
+#[cfg(any(test, feature = "fixture"))]
+pub mod fixture;
 pub mod formatting;
 pub mod genre;
+pub mod i18n;
+pub mod layout;
+pub mod lookup;
+pub mod mpeg;
+pub mod output;
+pub mod playlist;
+pub mod plugin_api;
+pub mod prompt;
+pub mod rename_journal;
+pub mod repair;
+pub mod riff;
 pub mod serato;
+pub mod spectrum;
 pub mod state;
+pub mod state_store;
+pub mod tag_limits;
+pub mod tag_reader;
 pub mod tags;
+pub mod tempo;
+pub mod throttle;
 pub mod track;
+pub mod track_id;
 pub mod utils;
@@ -0,0 +1,277 @@
+use std::fmt;
+use std::fmt::Display;
+use std::process::Command;
+use std::sync::LazyLock;
+
+use regex::Regex;
+use serde_json::Value;
+
+use crate::artists;
+use crate::error::{Result, TrackRenameError};
+
+const MUSICBRAINZ_RECORDING_URL: &str = "https://musicbrainz.org/ws/2/recording";
+
+/// `MusicBrainz` requires a descriptive user agent with contact information.
+const USER_AGENT: &str = concat!(
+    "track-rename/",
+    env!("CARGO_PKG_VERSION"),
+    " ( https://github.com/Esgrove/track-rename )"
+);
+
+/// Minimum search score for a recording to be considered the same track
+const MIN_SCORE: u64 = 90;
+
+/// Match a featured artist part in a title, since `MusicBrainz` has featured artists only in the artist credit
+static RE_TITLE_FEAT: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)\s*[(\[](?:feat|ft)\.?\s[^)\]]*[)\]]|\s+(?:feat|ft)\.\s[^(\[]*").unwrap());
+
+/// Recording metadata from an online music database.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Recording {
+    pub artist: String,
+    pub title: String,
+    /// Duration in seconds
+    pub duration: f64,
+}
+
+/// Result of comparing the local duration with the canonical recordings.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DurationCheck {
+    /// A recording with a matching duration was found
+    Match(Recording),
+    /// No recording matches the local duration, contains the closest one
+    Mismatch(Recording),
+    /// No matching recordings found
+    NotFound,
+}
+
+/// Search `MusicBrainz` recordings by artist and title using curl.
+///
+/// Returns only confident matches that have a duration.
+/// `MusicBrainz` allows one request per second, so callers should throttle consecutive lookups.
+pub fn search_musicbrainz(artist: &str, title: &str) -> Result<Vec<Recording>> {
+    let output = Command::new("curl")
+        .args([
+            "--silent",
+            "--show-error",
+            "--fail",
+            "--max-time",
+            "15",
+            "--user-agent",
+            USER_AGENT,
+            "--get",
+            MUSICBRAINZ_RECORDING_URL,
+            "--data-urlencode",
+            &format!("query={}", build_query(artist, title)),
+            "--data-urlencode",
+            "fmt=json",
+            "--data-urlencode",
+            "limit=10",
+        ])
+        .output()
+        .map_err(|error| TrackRenameError::io("Failed to run curl", error))?;
+
+    if !output.status.success() {
+        return Err(TrackRenameError::Request(format!(
+            "MusicBrainz request failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    parse_musicbrainz_response(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// Parse recordings from a `MusicBrainz` recording search JSON response.
+pub fn parse_musicbrainz_response(json: &str) -> Result<Vec<Recording>> {
+    let value: Value = serde_json::from_str(json)
+        .map_err(|error| TrackRenameError::json("Failed to parse MusicBrainz response", error))?;
+    let recordings = value
+        .get("recordings")
+        .and_then(Value::as_array)
+        .ok_or_else(|| TrackRenameError::Request("MusicBrainz response is missing recordings".to_string()))?;
+
+    Ok(recordings
+        .iter()
+        .filter(|recording| recording.get("score").and_then(Value::as_u64).unwrap_or_default() >= MIN_SCORE)
+        .filter_map(|recording| {
+            let length = recording.get("length").and_then(Value::as_u64)?;
+            let title = recording.get("title").and_then(Value::as_str)?.to_string();
+            let artist = recording
+                .get("artist-credit")
+                .and_then(Value::as_array)
+                .map(|credits| {
+                    credits
+                        .iter()
+                        .flat_map(|credit| {
+                            [
+                                credit.get("name").and_then(Value::as_str).unwrap_or_default(),
+                                credit.get("joinphrase").and_then(Value::as_str).unwrap_or_default(),
+                            ]
+                        })
+                        .collect::<String>()
+                })
+                .unwrap_or_default();
+            Some(Recording {
+                artist,
+                title,
+                duration: length as f64 / 1000.0,
+            })
+        })
+        .collect())
+}
+
+/// Compare local duration in seconds with the found recordings.
+#[must_use]
+pub fn compare_duration(duration: f64, recordings: &[Recording], tolerance: f64) -> DurationCheck {
+    let closest = recordings
+        .iter()
+        .min_by(|a, b| (a.duration - duration).abs().total_cmp(&(b.duration - duration).abs()));
+    match closest {
+        Some(recording) if (recording.duration - duration).abs() <= tolerance => {
+            DurationCheck::Match(recording.clone())
+        }
+        Some(recording) => DurationCheck::Mismatch(recording.clone()),
+        None => DurationCheck::NotFound,
+    }
+}
+
+/// Find a recording with a matching duration but a different title.
+///
+/// Returns the closest recording within the tolerance if none of the recordings within the tolerance have the same title,
+/// for example when the local file is tagged as an extended mix but has the length of the radio edit.
+/// Titles are compared ignoring case, punctuation, and featured artists.
+#[must_use]
+pub fn title_mismatch<'a>(
+    title: &str,
+    duration: f64,
+    recordings: &'a [Recording],
+    tolerance: f64,
+) -> Option<&'a Recording> {
+    let key = title_key(title);
+    let mut matching: Vec<&Recording> = recordings
+        .iter()
+        .filter(|recording| (recording.duration - duration).abs() <= tolerance)
+        .collect();
+    if matching.iter().any(|recording| title_key(&recording.title) == key) {
+        return None;
+    }
+    matching.sort_by(|a, b| (a.duration - duration).abs().total_cmp(&(b.duration - duration).abs()));
+    matching.first().copied()
+}
+
+/// Format duration in seconds as `M:SS`.
+#[must_use]
+pub fn format_duration(seconds: f64) -> String {
+    let total = seconds.round() as u64;
+    format!("{}:{:02}", total / 60, total % 60)
+}
+
+/// Comparison key for a title without featured artists.
+fn title_key(title: &str) -> String {
+    artists::normalized_key(&RE_TITLE_FEAT.replace_all(title, ""))
+}
+
+/// Build a Lucene search query with quoted phrases.
+fn build_query(artist: &str, title: &str) -> String {
+    let escape = |text: &str| text.replace('\\', "\\\\").replace('"', "\\\"");
+    format!("recording:\"{}\" AND artist:\"{}\"", escape(title), escape(artist))
+}
+
+impl Display for Recording {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} - {} ({})",
+            self.artist,
+            self.title,
+            format_duration(self.duration)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RESPONSE: &str = r#"{
+        "created": "2025-01-01T00:00:00.000Z",
+        "count": 3,
+        "offset": 0,
+        "recordings": [
+            {
+                "id": "1",
+                "score": 100,
+                "title": "Song (Extended Mix)",
+                "length": 392000,
+                "artist-credit": [
+                    {"name": "Artist", "joinphrase": " & "},
+                    {"name": "Other", "joinphrase": ""}
+                ]
+            },
+            {
+                "id": "2",
+                "score": 95,
+                "title": "Song (Radio Edit)",
+                "length": 185500,
+                "artist-credit": [{"name": "Artist"}]
+            },
+            {
+                "id": "3",
+                "score": 40,
+                "title": "Different Song",
+                "length": 200000,
+                "artist-credit": [{"name": "Someone"}]
+            }
+        ]
+    }"#;
+
+    #[test]
+    fn test_parse_musicbrainz_response() {
+        let recordings = parse_musicbrainz_response(RESPONSE).expect("Failed to parse response");
+        assert_eq!(recordings.len(), 2);
+        assert_eq!(recordings[0].artist, "Artist & Other");
+        assert_eq!(recordings[0].title, "Song (Extended Mix)");
+        assert!((recordings[1].duration - 185.5).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn test_compare_duration() {
+        let recordings = parse_musicbrainz_response(RESPONSE).expect("Failed to parse response");
+        assert!(matches!(
+            compare_duration(390.0, &recordings, 5.0),
+            DurationCheck::Match(_)
+        ));
+        match compare_duration(120.0, &recordings, 5.0) {
+            DurationCheck::Mismatch(recording) => assert_eq!(recording.title, "Song (Radio Edit)"),
+            other => panic!("Expected mismatch, got {other:?}"),
+        }
+        assert_eq!(compare_duration(120.0, &[], 5.0), DurationCheck::NotFound);
+    }
+
+    #[test]
+    fn test_title_mismatch() {
+        let recordings = parse_musicbrainz_response(RESPONSE).expect("Failed to parse response");
+        assert_eq!(title_mismatch("Song (Extended Mix)", 390.0, &recordings, 5.0), None);
+        assert_eq!(
+            title_mismatch("song (extended mix) [feat. Other]", 390.0, &recordings, 5.0),
+            None
+        );
+        assert_eq!(
+            title_mismatch("Song feat. Other (Extended Mix)", 390.0, &recordings, 5.0),
+            None
+        );
+        assert_eq!(
+            title_mismatch("Song (Extended Mix)", 186.0, &recordings, 5.0).map(|recording| recording.title.as_str()),
+            Some("Song (Radio Edit)")
+        );
+        assert_eq!(title_mismatch("Song (Extended Mix)", 120.0, &recordings, 5.0), None);
+    }
+
+    #[test]
+    fn test_build_query() {
+        assert_eq!(
+            build_query("Artist", "Song \"Quoted\""),
+            r#"recording:"Song \"Quoted\"" AND artist:"Artist""#
+        );
+        assert_eq!(format_duration(392.4), "6:32");
+    }
+}
@@ -1,66 +1,824 @@
+mod audit;
+mod bench;
 mod config;
+mod crates;
+mod decisions;
+mod doctor;
+mod export;
+mod feat_index;
+mod format_strings;
+#[cfg(feature = "fixture")]
+mod gen_fixture;
+mod history;
+mod manual;
+mod plugin;
+mod prefetch;
+mod print_name;
+mod relocate;
+mod replay_corpus;
+mod serato_edit;
 mod statistics;
 mod track_renamer;
 
+use std::path::PathBuf;
+
 use anyhow::Result;
-use clap::Parser;
+use clap::{Parser, Subcommand};
+
+use track_rename::artwork::ArtFormat;
+use track_rename::audio::ResampleTarget;
+use track_rename::events;
+#[cfg(feature = "fixture")]
+use track_rename::file_format::FileFormat;
+#[cfg(feature = "fixture")]
+use track_rename::fixture::FixtureTags;
+use track_rename::formatting::{AlbumArtist, FilenameSeparator, RuleCategory, Subtitle, YearInTitle};
+use track_rename::i18n;
+use track_rename::layout;
+use track_rename::prompt::{self, ChangeSeverity, NonInteractive, PromptProtocol};
+use track_rename::spectrum;
+use track_rename::state;
+use track_rename::state_store::StateLocation;
+use track_rename::throttle::Rate;
 
+use crate::config::{ArtistConflict, MinorChanges, Placeholders, TrackOrder};
+use crate::export::{ExportFormat, Exporter};
+use crate::print_name::NameFormat;
 use crate::track_renamer::TrackRenamer;
 
 #[derive(Parser)]
-#[command(author, about, version)]
+#[command(
+    author,
+    about,
+    version,
+    args_conflicts_with_subcommands = true,
+    after_long_help = manual::examples_help()
+)]
 pub struct RenamerArgs {
-    /// Optional input directory or audio file to format
+    #[command(subcommand)]
+    command: Option<RenamerCommand>,
+
+    /// Optional input directory, audio file, or playlist (m3u, m3u8, crate) to format
     path: Option<String>,
 
+    /// How confirmation prompts are shown and answered
+    #[arg(long, global = true, value_enum, default_value_t = PromptProtocol::Text, value_name = "PROTOCOL")]
+    prompt_protocol: PromptProtocol,
+
+    /// Answer yes to prompts when stdin is not a terminal
+    #[arg(long, global = true, conflicts_with = "assume_no")]
+    assume_yes: bool,
+
+    /// Answer no to prompts when stdin is not a terminal
+    #[arg(long, global = true)]
+    assume_no: bool,
+
+    /// Use the given user config file instead of the default location
+    #[arg(long, global = true, value_name = "PATH")]
+    config: Option<PathBuf>,
+
+    /// Store the state and rename history in a shared directory or `WebDAV` URL
+    #[arg(long, global = true, value_name = "DIR|URL")]
+    state_location: Option<StateLocation>,
+
+    /// Output width in columns for shortening diffs, defaults to the terminal width
+    #[arg(long, global = true, value_name = "COLUMNS")]
+    width: Option<usize>,
+
+    /// Separator between the artist and title in formatted filenames
+    #[arg(long, global = true, value_name = "SEPARATOR")]
+    filename_separator: Option<FilenameSeparator>,
+
+    /// Write JSON progress events to `stderr` or a file, named pipe, or Unix socket
+    #[arg(long, global = true, value_name = "TARGET")]
+    events: Option<String>,
+
     /// Resave tags for all files with ID3v2.4
     #[arg(short, long)]
     all_tags: bool,
 
-    /// Convert failed files to AIFF using ffmpeg
+    /// Which artist to use when the filename and artist tag disagree [default: tag]
+    #[arg(long, value_enum, value_name = "POLICY")]
+    artist_conflict: Option<ArtistConflict>,
+
+    /// Ask once for a tag or rename change shared by at least this many tracks
+    #[arg(long, value_name = "COUNT")]
+    batch_threshold: Option<usize>,
+
+    /// Store the formatted "Artist - Title" name in a `TXXX:CANONICAL_NAME` tag frame
+    #[arg(long)]
+    canonical_name: bool,
+
+    /// Convert failed files to AIFF, and files in the wrong format for their directory, using ffmpeg
     #[arg(short, long)]
     convert: bool,
 
+    /// Write formatted copies to a mirror directory tree and never modify the source files
+    #[arg(long, value_name = "DIR", conflicts_with = "read_only")]
+    copy_to: Option<PathBuf>,
+
+    /// Check MP3 VBR header matches the actual audio frame count
+    #[arg(long)]
+    check_vbr: bool,
+
+    /// Report AIFC and compressed AIFF files that some CDJs can't play
+    #[arg(long)]
+    check_aifc: bool,
+
     /// Collect and save genre statistics
     #[arg(short, long)]
     genre: bool,
 
+    /// Collect and save statistics of title descriptors like "Extended Mix"
+    #[arg(long)]
+    descriptors: bool,
+
     /// Enable debug prints
     #[arg(short, long)]
     debug: bool,
 
+    /// Write an index of featured artists for each main artist to a JSON or CSV file
+    #[arg(long, value_name = "FILE")]
+    feat_index: Option<PathBuf>,
+
+    /// Process the files listed one per line in a file, or read from stdin with -
+    #[arg(long, value_name = "FILE")]
+    files_from: Option<PathBuf>,
+
     /// Do not ask for confirmation
     #[arg(short, long)]
     force: bool,
 
-    /// Log files that can't be read
+    /// Reprocess files already formatted with the current formatting rules
+    #[arg(long)]
+    force_rules_version: bool,
+
+    /// Read file metadata and tags concurrently with N threads, useful for network shares
+    #[arg(long, value_name = "N")]
+    io_threads: Option<usize>,
+
+    /// Replace identical duplicate files with reflinks or hard links
+    #[arg(long)]
+    link_duplicates: bool,
+
+    /// Write the tracks added or modified in this run to an M3U8 playlist or Serato crate file
+    #[arg(long, value_name = "FILE")]
+    new_playlist: Option<PathBuf>,
+
+    /// Skip or auto-approve changes that only fix capitalization or whitespace
+    #[arg(long, value_enum, value_name = "ACTION")]
+    minor_changes: Option<MinorChanges>,
+
+    /// Largest change handled by `--minor-changes`
+    #[arg(long, value_enum, value_name = "LEVEL")]
+    minor_threshold: Option<ChangeSeverity>,
+
+    /// Log files that can't be read or renamed with the failure category and error message
     #[arg(short, long)]
     log: bool,
 
+    /// Rebuild mismatching MP3 VBR headers using ffmpeg
+    #[arg(long)]
+    fix_vbr: bool,
+
+    /// Convert AIFC and compressed AIFF files to standard PCM AIFF using ffmpeg
+    #[arg(long)]
+    fix_aifc: bool,
+
+    /// Write manual pages to the given directory, or ~/.local/share/man/man1 by default
+    #[arg(long, value_name = "DIR")]
+    #[allow(clippy::option_option)]
+    generate_man: Option<Option<PathBuf>>,
+
+    /// Don't warn about DJ music folders without a genre mapping
+    #[arg(long)]
+    no_genre_warnings: bool,
+
     /// Don't skip unchanged files since last run
     #[arg(short, long)]
     no_state: bool,
 
+    /// Processing order, for example newest files first
+    #[arg(long, value_enum, value_name = "ORDER", conflicts_with = "sort")]
+    order: Option<TrackOrder>,
+
     /// Overwrite existing files when renaming
     #[arg(short, long)]
     overwrite: bool,
 
+    /// Only apply these formatting rule categories
+    #[arg(long, value_enum, value_name = "CATEGORIES", value_delimiter = ',')]
+    rules: Option<Vec<RuleCategory>>,
+
+    /// Skip these formatting rule categories
+    #[arg(long, value_enum, value_name = "CATEGORIES", value_delimiter = ',')]
+    skip_rules: Vec<RuleCategory>,
+
+    /// Skip or download online-only cloud storage files [default: skip]
+    #[arg(long, value_enum, value_name = "ACTION")]
+    placeholders: Option<Placeholders>,
+
+    /// Show the N most common pending changes and ask to continue before processing [default: 20]
+    #[arg(long, value_name = "N", num_args = 0..=1, default_missing_value = "20")]
+    preview: Option<usize>,
+
     /// Only print changes without modifying files
     #[arg(short, long)]
     print: bool,
 
+    /// Use a named set of options from the user config file
+    #[arg(long, value_name = "NAME")]
+    preset: Option<String>,
+
+    /// Only print changes and never write any files, including state and logs
+    #[arg(long)]
+    read_only: bool,
+
+    /// Append original and formatted artist and title from this run to a corpus file
+    #[arg(long, value_name = "FILE")]
+    record_corpus: Option<PathBuf>,
+
+    /// Check that the current formatting rules still produce the outputs in a corpus file
+    #[arg(long, value_name = "FILE")]
+    replay_corpus: Option<PathBuf>,
+
+    /// Only read tags for files whose filename would change when formatted
+    #[arg(long)]
+    quick: bool,
+
+    /// Print timing and formatting cache statistics
+    #[arg(long)]
+    profile: bool,
+
     /// Rename all audio files
     #[arg(short, long)]
     rename: bool,
 
-    /// Sort audio files by name
+    /// Append each applied rename to a JSON lines journal file
+    #[arg(long, value_name = "FILE")]
+    rename_journal: Option<PathBuf>,
+
+    /// Limit renames to this rate so file sync tools are not overwhelmed, for example 5/s or 60/min
+    #[arg(long, value_name = "N/s")]
+    throttle_renames: Option<Rate>,
+
+    /// Reuse answers from a decision log instead of asking again
+    #[arg(long, value_name = "FILE")]
+    replay: Option<PathBuf>,
+
+    /// Resample AIFF files above the given target, for example 44100:16
+    #[arg(long, value_name = "RATE:BITS")]
+    resample: Option<ResampleTarget>,
+
+    /// Re-encode embedded artwork larger than this many pixels when writing tags
+    #[arg(long, value_name = "PIXELS")]
+    max_art_size: Option<u32>,
+
+    /// Image format for re-encoded artwork
+    #[arg(long, value_enum, value_name = "FORMAT", requires = "max_art_size")]
+    art_format: Option<ArtFormat>,
+
+    /// JPEG quality for re-encoded artwork from 1 to 100 [default: 85]
+    #[arg(long, value_name = "QUALITY", requires = "max_art_size", value_parser = clap::value_parser!(u8).range(1..=100))]
+    art_quality: Option<u8>,
+
+    /// Abort before modifying anything if more than N tracks would change
+    #[arg(long, value_name = "N")]
+    max_changes: Option<usize>,
+
+    /// Try to repair MP3 files with broken tags
+    #[arg(long)]
+    repair: bool,
+
+    /// Skip malformed ID3 frames and keep the rest of the tag instead of failing the file
+    #[arg(long)]
+    tolerant: bool,
+
+    /// Retry files that failed with a transient error this many times at the end of the run
+    #[arg(long, value_name = "COUNT")]
+    retries: Option<usize>,
+
+    /// Seconds to wait before each retry [default: 2]
+    #[arg(long, value_name = "SECONDS", requires = "retries")]
+    retry_delay: Option<f64>,
+
+    /// Open the folder of each track after accepting a change, or add it to the given review playlist
+    #[arg(long, value_name = "PLAYLIST")]
+    #[allow(clippy::option_option)]
+    reveal: Option<Option<PathBuf>>,
+
+    /// Remove APE tags from MP3 files when writing tags
+    #[arg(long)]
+    strip_ape: bool,
+
+    /// Remove chapter frames (CHAP and CTOC) when writing tags
+    #[arg(long)]
+    strip_chapters: bool,
+
+    /// Write tags to a temporary copy and atomically replace the original file
+    #[arg(long)]
+    safe_writes: bool,
+
+    /// Sort audio files by name, same as `--order name`
     #[arg(short, long)]
     sort: bool,
 
+    /// Write processing counts per top-level folder and per genre to a JSON or CSV file
+    #[arg(long, value_name = "FILE")]
+    stats_report: Option<PathBuf>,
+
     /// Only fix tags without renaming files
     #[arg(short, long)]
     tags_only: bool,
 
+    /// Shorten tag values over the `max_length` limits from the user config instead of only warning
+    #[arg(long)]
+    truncate_tags: bool,
+
+    /// Update renamed file paths in Serato crates
+    #[arg(long)]
+    update_crates: bool,
+
+    /// Write renamed file paths back to the input playlist
+    #[arg(long)]
+    update_playlist: bool,
+
+    /// Only rewrite outdated ID3 tag versions as ID3v2.4 without formatting or renaming
+    #[arg(long)]
+    upgrade_tags: bool,
+
+    /// After saving the state, check N random entries from this run against the files [default: 100]
+    #[arg(long, value_name = "N", num_args = 0..=1, default_missing_value = "100")]
+    verify_state: Option<usize>,
+
+    /// Read tags back after writing and check they match
+    #[arg(long)]
+    verify_writes: bool,
+
+    /// Verbose output
+    #[arg(short, long)]
+    verbose: bool,
+
+    /// Write a track ID to files that do not have one, for following files renamed outside track-rename
+    #[arg(long)]
+    write_id: bool,
+
+    /// Keep a year in parentheses at the end of the title or move it to the year tag [default: keep]
+    #[arg(long, value_enum, value_name = "MODE")]
+    year_in_title: Option<YearInTitle>,
+
+    /// Format the album artist tag, or also fill in missing album artists [default: format]
+    #[arg(long, value_enum, value_name = "MODE")]
+    album_artist: Option<AlbumArtist>,
+
+    /// Keep mix descriptors like "Extended Mix" in the title, or move them to or from the subtitle tag [default: keep]
+    #[arg(long, value_enum, value_name = "MODE")]
+    subtitle: Option<Subtitle>,
+}
+
+#[derive(Subcommand)]
+enum RenamerCommand {
+    /// Copy formatted tracks to an external drive with FAT-safe filenames
+    Export(ExportArgs),
+    /// List Serato crates or organize tracks into crates
+    Crates(CratesArgs),
+    /// Compare library files with DJ software databases
+    Audit(AuditArgs),
+    /// Measure formatting performance over the current tags without modifying files
+    Bench(BenchArgs),
+    /// Fix Serato entries for renamed files using the rename history
+    Relocate(RelocateArgs),
+    /// Edit Serato data stored in audio file tags
+    Serato(SeratoArgs),
+    /// Check the environment and print guidance for any problems
+    Doctor(DoctorArgs),
+    /// Format artist and title strings without touching any files
+    Format(FormatArgs),
+    /// Show the earlier tag values and filenames of a track
+    History(HistoryArgs),
+    /// Print the formatted name of a file without modifying anything
+    PrintName(PrintNameArgs),
+    /// Format tag values sent as JSON lines on stdin, for music library plugins like beets
+    Plugin,
+    /// Generate small audio files with the given tags for testing
+    #[cfg(feature = "fixture")]
+    #[command(hide = true)]
+    GenFixture(GenFixtureArgs),
+}
+
+#[derive(Parser)]
+pub struct HistoryArgs {
+    /// Audio file, or an earlier path of a renamed file
+    file: PathBuf,
+}
+
+#[derive(Parser)]
+pub struct PrintNameArgs {
+    /// Audio file
+    file: PathBuf,
+
+    /// Output format
+    #[arg(long, value_enum, default_value_t)]
+    format: NameFormat,
+}
+
+#[derive(Parser)]
+pub struct FormatArgs {
+    /// Artist to format
+    #[arg(short, long, default_value = "")]
+    artist: String,
+
+    /// Title to format
+    #[arg(short, long, default_value = "")]
+    title: String,
+
+    /// Album to format
+    #[arg(long, default_value = "")]
+    album: String,
+
+    /// Genre to format
+    #[arg(short, long, default_value = "")]
+    genre: String,
+
+    /// Only apply these formatting rule categories
+    #[arg(long, value_enum, value_name = "CATEGORIES", value_delimiter = ',')]
+    rules: Option<Vec<RuleCategory>>,
+
+    /// Skip these formatting rule categories
+    #[arg(long, value_enum, value_name = "CATEGORIES", value_delimiter = ',')]
+    skip_rules: Vec<RuleCategory>,
+
+    /// Show what each rule category changes
+    #[arg(long)]
+    trace: bool,
+}
+
+#[cfg(feature = "fixture")]
+#[derive(Parser)]
+pub struct GenFixtureArgs {
+    /// Output directory, defaults to the current directory
+    dir: Option<PathBuf>,
+
+    /// File formats to generate
+    #[arg(short, long, value_name = "FORMATS", value_delimiter = ',', default_value = "aif")]
+    format: Vec<FileFormat>,
+
+    /// Tag configurations to generate
+    #[arg(
+        short,
+        long,
+        value_enum,
+        value_name = "TAGS",
+        value_delimiter = ',',
+        default_value = "basic"
+    )]
+    tags: Vec<FixtureTags>,
+}
+
+#[derive(Parser)]
+pub struct DoctorArgs {
+    /// Library directory to check, defaults to the current directory
+    path: Option<String>,
+
+    /// Serato library directory, defaults to `_Serato_` in the user music directory
+    #[arg(long, value_name = "DIR")]
+    serato_dir: Option<PathBuf>,
+}
+
+#[derive(Parser)]
+pub struct SeratoArgs {
+    #[command(subcommand)]
+    action: SeratoCommand,
+}
+
+#[derive(Subcommand)]
+enum SeratoCommand {
+    /// Remove Serato data from tags
+    Strip(StripArgs),
+    /// Apply user config colour rules to tracks by their Serato track colour
+    Colors(ColorsArgs),
+    /// Rewrite the Serato manual gain value
+    Gain(GainArgs),
+    /// Shift beatgrid, cue points, and loops by a constant offset
+    ShiftGrid(ShiftGridArgs),
+    /// Add cue points from user config templates to tracks without cues
+    CueTemplate(CueTemplateArgs),
+}
+
+#[derive(Parser)]
+pub struct CueTemplateArgs {
+    /// Optional input directory or audio file
+    path: Option<String>,
+
+    /// Do not save a backup copy of modified files
+    #[arg(long)]
+    no_backup: bool,
+
+    /// Do not ask for confirmation
+    #[arg(short, long)]
+    force: bool,
+
+    /// Only print changes without modifying files
+    #[arg(short, long)]
+    print: bool,
+
+    /// Verbose output
+    #[arg(short, long)]
+    verbose: bool,
+}
+
+#[derive(Parser)]
+pub struct ShiftGridArgs {
+    /// Input directory or audio file
+    path: Option<String>,
+
+    /// Offset in milliseconds, negative values move markers earlier
+    #[arg(long, value_name = "MS", allow_negative_numbers = true)]
+    ms: i32,
+
+    /// Do not save a backup copy of modified files
+    #[arg(long)]
+    no_backup: bool,
+
+    /// Do not ask for confirmation
+    #[arg(short, long)]
+    force: bool,
+
+    /// Only print changes without modifying files
+    #[arg(short, long)]
+    print: bool,
+
+    /// Verbose output
+    #[arg(short, long)]
+    verbose: bool,
+}
+
+#[derive(Parser)]
+pub struct GainArgs {
+    /// Optional input directory or audio file
+    path: Option<String>,
+
+    /// Copy the auto gain value to the manual gain
+    #[arg(long)]
+    copy_auto: bool,
+
+    /// Limit the manual gain to at most the given value, 0 dB if no value is given
+    #[arg(long, value_name = "DB", num_args = 0..=1, default_missing_value = "0", allow_negative_numbers = true)]
+    clamp: Option<f32>,
+
+    /// Do not save a backup copy of modified files
+    #[arg(long)]
+    no_backup: bool,
+
+    /// Do not ask for confirmation
+    #[arg(short, long)]
+    force: bool,
+
+    /// Only print changes without modifying files
+    #[arg(short, long)]
+    print: bool,
+
+    /// Verbose output
+    #[arg(short, long)]
+    verbose: bool,
+}
+
+#[derive(Parser)]
+pub struct ColorsArgs {
+    /// Optional input directory or audio file
+    path: Option<String>,
+
+    /// Serato library directory, defaults to `_Serato_` in the user music directory
+    #[arg(long, value_name = "DIR")]
+    serato_dir: Option<PathBuf>,
+
+    /// Do not save a backup copy of modified files
+    #[arg(long)]
+    no_backup: bool,
+
+    /// Do not ask for confirmation
+    #[arg(short, long)]
+    force: bool,
+
+    /// Only print changes without modifying files
+    #[arg(short, long)]
+    print: bool,
+
+    /// Verbose output
+    #[arg(short, long)]
+    verbose: bool,
+}
+
+#[derive(Parser)]
+pub struct StripArgs {
+    /// Optional input directory or audio file
+    path: Option<String>,
+
+    /// Remove all Serato data, for example before sharing files
+    #[arg(long)]
+    all: bool,
+
+    /// Remove cue points
+    #[arg(long)]
+    cues: bool,
+
+    /// Remove saved loops
+    #[arg(long)]
+    loops: bool,
+
+    /// Remove beatgrid
+    #[arg(long)]
+    beatgrid: bool,
+
+    /// Do not save a backup copy of modified files
+    #[arg(long)]
+    no_backup: bool,
+
+    /// Do not ask for confirmation
+    #[arg(short, long)]
+    force: bool,
+
+    /// Only print changes without modifying files
+    #[arg(short, long)]
+    print: bool,
+
+    /// Verbose output
+    #[arg(short, long)]
+    verbose: bool,
+}
+
+#[derive(Parser)]
+pub struct RelocateArgs {
+    /// Serato library directory, defaults to `_Serato_` in the user music directory
+    #[arg(long, value_name = "DIR")]
+    serato_dir: Option<PathBuf>,
+
+    /// Do not ask for confirmation
+    #[arg(short, long)]
+    force: bool,
+
+    /// Only print changes without modifying Serato files
+    #[arg(short, long)]
+    print: bool,
+
+    /// Verbose output
+    #[arg(short, long)]
+    verbose: bool,
+}
+
+#[derive(Parser)]
+pub struct AuditArgs {
+    /// Optional input directory or audio file
+    path: Option<String>,
+
+    /// Report files missing from Serato and Serato entries for files that no longer exist
+    #[arg(long)]
+    serato: bool,
+
+    /// Report tracks where the BPM tag, Serato beatgrid, and title BPM disagree
+    #[arg(long)]
+    tempo: bool,
+
+    /// Allowed BPM difference for the tempo check
+    #[arg(long, value_name = "BPM", default_value_t = 0.5)]
+    bpm_tolerance: f32,
+
+    /// Compare track durations with `MusicBrainz` recordings
+    #[arg(long)]
+    lookup: bool,
+
+    /// Allowed duration difference in seconds for the lookup check
+    #[arg(long, value_name = "SECONDS", default_value_t = 10.0)]
+    duration_tolerance: f64,
+
+    /// Report different spellings of the same artist name
+    #[arg(long)]
+    artists: bool,
+
+    /// Minimum number of tracks in a reported artist spelling group
+    #[arg(long, value_name = "COUNT", default_value_t = 2)]
+    min_tracks: usize,
+
+    /// Report tracks whose genre tag disagrees with the genre mapped to their folder
+    #[arg(long)]
+    genres: bool,
+
+    /// Write the most used artist spelling or the folder genre to the tags after confirmation
+    #[arg(long)]
+    apply: bool,
+
+    /// Report AIFF and WAV files whose spectrum ends early like an MP3 transcode
+    #[arg(long)]
+    spectrum: bool,
+
+    /// Frequency in Hz below which the spectrum cutoff is reported
+    #[arg(long, value_name = "HZ", default_value_t = spectrum::DEFAULT_MIN_CUTOFF)]
+    min_cutoff: u32,
+
+    /// Show which user and machine processed and renamed the files
+    #[arg(long)]
+    history: bool,
+
+    /// Report tags longer than the `max_length` limits in the user config
+    #[arg(long)]
+    lengths: bool,
+
+    /// Serato library directory, defaults to `_Serato_` in the user music directory
+    #[arg(long, value_name = "DIR")]
+    serato_dir: Option<PathBuf>,
+
+    /// Verbose output
+    #[arg(short, long)]
+    verbose: bool,
+}
+
+#[derive(Parser)]
+pub struct BenchArgs {
+    /// Optional input directory or audio file
+    path: Option<String>,
+
+    /// Number of times to format all tracks
+    #[arg(short, long, value_name = "COUNT", default_value_t = 5)]
+    iterations: usize,
+
+    /// Number of slowest regexes to show
+    #[arg(short, long, value_name = "COUNT", default_value_t = 10)]
+    top: usize,
+
+    /// Verbose output
+    #[arg(short, long)]
+    verbose: bool,
+}
+
+#[derive(Parser)]
+pub struct CratesArgs {
+    #[command(subcommand)]
+    action: CratesCommand,
+
+    /// Serato library directory, defaults to `_Serato_` in the user music directory
+    #[arg(long, global = true, value_name = "DIR")]
+    serato_dir: Option<PathBuf>,
+}
+
+#[derive(Subcommand)]
+enum CratesCommand {
+    /// List tracks in crates
+    List {
+        /// Crate names or files, lists all crates if not given
+        crates: Vec<String>,
+
+        /// Only show tracks that no longer exist
+        #[arg(short, long)]
+        missing: bool,
+    },
+    /// Add tracks to crates by genre
+    Genres {
+        /// Optional input directory or audio file
+        path: Option<String>,
+
+        /// Parent crate for the genre crates
+        #[arg(long, default_value = "Genres")]
+        parent: String,
+
+        /// Only print changes without modifying crates
+        #[arg(short, long)]
+        print: bool,
+    },
+}
+
+#[derive(Parser)]
+pub struct ExportArgs {
+    /// Input directories or audio files to export
+    paths: Vec<String>,
+
+    /// Destination directory
+    #[arg(long, value_name = "DIR")]
+    dest: PathBuf,
+
+    /// Output file format
+    #[arg(long, value_enum, default_value_t = ExportFormat::Original)]
+    format: ExportFormat,
+
+    /// Only use ASCII characters in file and directory names
+    #[arg(long)]
+    ascii: bool,
+
+    /// Copy all files directly to the destination instead of mirroring the directory structure
+    #[arg(long)]
+    flatten: bool,
+
+    /// Maximum filename length in characters including the file extension
+    #[arg(long, value_name = "LENGTH", default_value_t = 255, value_parser = clap::value_parser!(u16).range(16..=255))]
+    max_name: u16,
+
+    /// Overwrite existing files in the destination
+    #[arg(short, long)]
+    overwrite: bool,
+
+    /// Only print changes without copying files
+    #[arg(short, long)]
+    print: bool,
+
     /// Verbose output
     #[arg(short, long)]
     verbose: bool,
@@ -70,7 +828,70 @@ fn main() -> Result<()> {
     std::env::set_var("RUST_BACKTRACE", "1");
 
     let args = RenamerArgs::parse();
+    if let Some(path) = &args.config {
+        config::set_config_path(path)?;
+    }
+    i18n::set_language(config::language());
+    if let Some(location) = config::state_location(args.state_location.as_ref())? {
+        state::set_location(location);
+    }
+    prompt::set_confirm_default(config::confirm_default_yes());
+    prompt::set_protocol(args.prompt_protocol)?;
+    prompt::set_non_interactive(if args.assume_yes {
+        NonInteractive::Yes
+    } else if args.assume_no {
+        NonInteractive::No
+    } else {
+        NonInteractive::Fail
+    });
+    layout::set_width(args.width);
+    if let Some(target) = &args.events {
+        events::init(target)?;
+    }
+    run_command(&args)?;
+    if prompt::non_interactive_failed() {
+        anyhow::bail!("Stopped because stdin is not a terminal");
+    }
+    Ok(())
+}
+
+fn run_command(args: &RenamerArgs) -> Result<()> {
+    match &args.command {
+        Some(RenamerCommand::Export(export_args)) => {
+            return Exporter::new(export_args, config::format_options(args.filename_separator))?.run()
+        }
+        Some(RenamerCommand::Crates(crates_args)) => {
+            return crates::run(crates_args, &config::format_options(args.filename_separator))
+        }
+        Some(RenamerCommand::Audit(audit_args)) => return audit::run(audit_args),
+        Some(RenamerCommand::Bench(bench_args)) => return bench::run(bench_args),
+        Some(RenamerCommand::Relocate(relocate_args)) => return relocate::run(relocate_args),
+        Some(RenamerCommand::Serato(serato_args)) => return serato_edit::run(serato_args),
+        Some(RenamerCommand::Doctor(doctor_args)) => return doctor::run(doctor_args),
+        Some(RenamerCommand::Format(format_args)) => {
+            format_strings::run(
+                format_args,
+                config::format_options(args.filename_separator).filename_separator,
+            );
+            return Ok(());
+        }
+        Some(RenamerCommand::History(history_args)) => return history::run(history_args),
+        Some(RenamerCommand::PrintName(print_name_args)) => {
+            return print_name::run(print_name_args, &config::format_options(args.filename_separator))
+        }
+        Some(RenamerCommand::Plugin) => return plugin::run(&config::format_options(args.filename_separator)),
+        #[cfg(feature = "fixture")]
+        Some(RenamerCommand::GenFixture(fixture_args)) => return gen_fixture::run(fixture_args),
+        None => {}
+    }
+    if let Some(dir) = &args.generate_man {
+        return manual::generate_man_pages(dir.as_deref());
+    }
+    if let Some(path) = &args.replay_corpus {
+        return replay_corpus::run(path);
+    }
+
     let absolute_input_path = track_rename::utils::resolve_input_path(&args.path)?;
 
-    TrackRenamer::new(absolute_input_path, &args).run()
+    TrackRenamer::new(absolute_input_path, args)?.run()
 }
@@ -0,0 +1,121 @@
+use std::fmt::Write;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use clap::{Command, CommandFactory};
+use clap_mangen::Man;
+
+use crate::RenamerArgs;
+
+/// Example invocations shown in `--help` and the manual page.
+///
+/// Each example is parsed with the actual argument definitions in the tests,
+/// so renamed or removed options can not leave outdated examples behind.
+pub const EXAMPLES: &[(&str, &[&str])] = &[
+    ("Format tags and rename files in the current directory", &[]),
+    ("Only print changes for a directory", &["~/Music/House", "--print"]),
+    (
+        "Fix tags without renaming or asking for confirmation",
+        &["--tags-only", "--force"],
+    ),
+    (
+        "Rename tracks in a playlist and write the new paths back",
+        &["house.m3u8", "--rename", "--update-playlist"],
+    ),
+    (
+        "Write tags safely and check them afterwards",
+        &["--safe-writes", "--verify-writes"],
+    ),
+    (
+        "Copy tracks to a USB drive with FAT-safe names",
+        &["export", "~/Music/House", "--dest", "/Volumes/USB", "--ascii"],
+    ),
+    (
+        "Remove all Serato data from the tags",
+        &["serato", "strip", "~/Music/Promo", "--all"],
+    ),
+    ("Check the environment for problems", &["doctor", "~/Music"]),
+    ("Write the manual pages", &["--generate-man"]),
+];
+
+/// Examples section for the long help output.
+pub fn examples_help() -> String {
+    let mut help = String::from("Examples:");
+    for (description, args) in EXAMPLES {
+        let _ = write!(help, "\n  # {description}\n  {}\n", example_command(args));
+    }
+    help
+}
+
+/// Write manual pages for the main command and each subcommand.
+///
+/// Pages are written to `~/.local/share/man/man1` by default,
+/// which `man` searches when `~/.local/bin` is in `PATH`.
+pub fn generate_man_pages(dir: Option<&Path>) -> Result<()> {
+    let dir = match dir {
+        Some(dir) => dir.to_path_buf(),
+        None => default_man_dir()?,
+    };
+    fs::create_dir_all(&dir).with_context(|| format!("Failed to create directory: {}", dir.display()))?;
+
+    let name = env!("CARGO_BIN_NAME");
+    let command = RenamerArgs::command().name(name);
+    write_man_page(command.clone(), &dir.join(format!("{name}.1")))?;
+    for subcommand in command.get_subcommands() {
+        let page = subcommand.clone().bin_name(format!("{name} {}", subcommand.get_name()));
+        write_man_page(page, &dir.join(format!("{name}-{}.1", subcommand.get_name())))?;
+    }
+    Ok(())
+}
+
+fn write_man_page(command: Command, path: &Path) -> Result<()> {
+    let mut buffer = Vec::new();
+    Man::new(command)
+        .render(&mut buffer)
+        .context("Failed to render manual page")?;
+    fs::write(path, buffer).with_context(|| format!("Failed to write manual page: {}", path.display()))?;
+    println!("{}", path.display());
+    Ok(())
+}
+
+fn default_man_dir() -> Result<PathBuf> {
+    let home_dir = dirs::home_dir().context("Failed to get home directory path")?;
+    Ok(home_dir.join(".local").join("share").join("man").join("man1"))
+}
+
+fn example_command(args: &[&str]) -> String {
+    std::iter::once(env!("CARGO_BIN_NAME"))
+        .chain(args.iter().copied())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use clap::Parser;
+
+    #[test]
+    fn test_examples_parse() {
+        for (description, args) in EXAMPLES {
+            let command_line = std::iter::once("trackrename").chain(args.iter().copied());
+            assert!(
+                RenamerArgs::try_parse_from(command_line).is_ok(),
+                "Example does not parse: {description}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_render_man_page() {
+        let mut buffer = Vec::new();
+        Man::new(RenamerArgs::command())
+            .render(&mut buffer)
+            .expect("Failed to render manual page");
+        let page = String::from_utf8(buffer).expect("Manual page should be UTF-8");
+        assert!(page.contains(r"\-\-generate\-man"));
+        assert!(page.contains("Write the manual pages"));
+    }
+}
@@ -0,0 +1,546 @@
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use crate::error::{Result, TrackRenameError};
+
+/// `ID3v2` header size in bytes.
+pub(crate) const ID3_HEADER_SIZE: usize = 10;
+
+/// Relative difference between the VBR header frame count and actual frame count that is reported as a mismatch.
+const FRAME_COUNT_TOLERANCE: f64 = 0.005;
+
+/// `MPEG` audio version.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MpegVersion {
+    Mpeg1,
+    Mpeg2,
+    Mpeg25,
+}
+
+/// Parsed `MPEG` audio frame header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameHeader {
+    pub version: MpegVersion,
+    pub layer: u8,
+    pub bitrate: u32,
+    pub sample_rate: u32,
+    pub padding: bool,
+    pub mono: bool,
+}
+
+/// VBR info header type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VbrHeaderKind {
+    /// Xing header used for VBR files
+    Xing,
+    /// Xing header variant used by LAME for CBR files
+    Info,
+    /// Fraunhofer VBRI header
+    Vbri,
+}
+
+/// VBR info header stored in the first audio frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VbrHeader {
+    pub kind: VbrHeaderKind,
+    /// Number of audio frames reported by the header
+    pub frames: Option<u32>,
+    /// Encoder delay and padding from the LAME extension
+    pub encoder_trim: Option<EncoderTrim>,
+}
+
+/// Encoder delay and padding samples stored in the LAME extension of the Xing header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EncoderTrim {
+    /// Number of silent samples added to the start by the encoder
+    pub delay: u16,
+    /// Number of silent samples added to the end by the encoder
+    pub padding: u16,
+}
+
+/// Gapless playback information for an MP3 file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GaplessInfo {
+    pub delay: u16,
+    pub padding: u16,
+    /// Number of actual audio samples after removing encoder delay and padding
+    pub samples: u64,
+    pub sample_rate: u32,
+}
+
+/// Frame level information for an MP3 file.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Mp3Info {
+    pub vbr_header: Option<VbrHeader>,
+    /// Number of actual audio frames, not including the VBR header frame
+    pub frames: u32,
+    pub sample_rate: u32,
+    pub samples_per_frame: u32,
+    /// True if the audio frames have different bitrates
+    pub variable_bitrate: bool,
+}
+
+/// Problem found with the VBR header.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DurationMismatch {
+    /// The header frame count differs from the actual frame count
+    FrameCount { header: u32, actual: u32 },
+    /// VBR file without a header, so players have to estimate the duration from the first frame
+    MissingHeader,
+}
+
+const BITRATES_V1_L1: [u32; 16] = [0, 32, 64, 96, 128, 160, 192, 224, 256, 288, 320, 352, 384, 416, 448, 0];
+const BITRATES_V1_L2: [u32; 16] = [0, 32, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320, 384, 0];
+const BITRATES_V1_L3: [u32; 16] = [0, 32, 40, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320, 0];
+const BITRATES_V2_L1: [u32; 16] = [0, 32, 48, 56, 64, 80, 96, 112, 128, 144, 160, 176, 192, 224, 256, 0];
+const BITRATES_V2_L23: [u32; 16] = [0, 8, 16, 24, 32, 40, 48, 56, 64, 80, 96, 112, 128, 144, 160, 0];
+
+impl FrameHeader {
+    /// Parse a frame header from the first four bytes.
+    ///
+    /// Returns `None` if the bytes are not a valid frame header.
+    /// Free format bitrate is not supported.
+    #[must_use]
+    pub fn parse(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() < 4 || bytes[0] != 0xFF || bytes[1] & 0xE0 != 0xE0 {
+            return None;
+        }
+        let version = match (bytes[1] >> 3) & 0b11 {
+            0b00 => MpegVersion::Mpeg25,
+            0b10 => MpegVersion::Mpeg2,
+            0b11 => MpegVersion::Mpeg1,
+            _ => return None,
+        };
+        let layer = match (bytes[1] >> 1) & 0b11 {
+            0b01 => 3,
+            0b10 => 2,
+            0b11 => 1,
+            _ => return None,
+        };
+        let bitrate_index = usize::from(bytes[2] >> 4);
+        let bitrate = match (version, layer) {
+            (MpegVersion::Mpeg1, 1) => BITRATES_V1_L1[bitrate_index],
+            (MpegVersion::Mpeg1, 2) => BITRATES_V1_L2[bitrate_index],
+            (MpegVersion::Mpeg1, _) => BITRATES_V1_L3[bitrate_index],
+            (_, 1) => BITRATES_V2_L1[bitrate_index],
+            _ => BITRATES_V2_L23[bitrate_index],
+        };
+        if bitrate == 0 {
+            return None;
+        }
+        let base_sample_rate = match (bytes[2] >> 2) & 0b11 {
+            0b00 => 44100,
+            0b01 => 48000,
+            0b10 => 32000,
+            _ => return None,
+        };
+        let sample_rate = match version {
+            MpegVersion::Mpeg1 => base_sample_rate,
+            MpegVersion::Mpeg2 => base_sample_rate / 2,
+            MpegVersion::Mpeg25 => base_sample_rate / 4,
+        };
+        Some(Self {
+            version,
+            layer,
+            bitrate,
+            sample_rate,
+            padding: bytes[2] & 0b10 != 0,
+            mono: bytes[3] >> 6 == 0b11,
+        })
+    }
+
+    /// Number of audio samples per channel in one frame.
+    #[must_use]
+    pub const fn samples_per_frame(&self) -> u32 {
+        match (self.layer, self.version) {
+            (1, _) => 384,
+            (2, _) | (3, MpegVersion::Mpeg1) => 1152,
+            _ => 576,
+        }
+    }
+
+    /// Frame length in bytes including the header.
+    #[must_use]
+    pub const fn frame_length(&self) -> usize {
+        let padding = if self.padding { 1 } else { 0 };
+        if self.layer == 1 {
+            ((12 * self.bitrate * 1000 / self.sample_rate + padding) * 4) as usize
+        } else {
+            (self.samples_per_frame() / 8 * self.bitrate * 1000 / self.sample_rate + padding) as usize
+        }
+    }
+
+    /// Size of the Layer III side information that precedes a Xing header.
+    const fn side_info_size(&self) -> usize {
+        match (self.version, self.mono) {
+            (MpegVersion::Mpeg1, false) => 32,
+            (MpegVersion::Mpeg1, true) | (_, false) => 17,
+            (_, true) => 9,
+        }
+    }
+}
+
+impl VbrHeader {
+    /// Try to parse a Xing, Info, or VBRI header from the first audio frame.
+    #[must_use]
+    pub fn parse(frame: &[u8], header: &FrameHeader) -> Option<Self> {
+        let xing_offset = 4 + header.side_info_size();
+        if let Some(tag) = frame.get(xing_offset..xing_offset + 8) {
+            let kind = match &tag[0..4] {
+                b"Xing" => Some(VbrHeaderKind::Xing),
+                b"Info" => Some(VbrHeaderKind::Info),
+                _ => None,
+            };
+            if let Some(kind) = kind {
+                let flags = u32::from_be_bytes([tag[4], tag[5], tag[6], tag[7]]);
+                let mut offset = xing_offset + 8;
+                let frames = if flags & 0x1 == 0 {
+                    None
+                } else {
+                    offset += 4;
+                    frame
+                        .get(offset - 4..offset)
+                        .map(|bytes| u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+                };
+                // Skip optional byte count, seek table, and quality fields
+                for (flag, size) in [(0x2, 4), (0x4, 100), (0x8, 4)] {
+                    if flags & flag != 0 {
+                        offset += size;
+                    }
+                }
+                let encoder_trim = frame.get(offset..).and_then(EncoderTrim::parse);
+                return Some(Self {
+                    kind,
+                    frames,
+                    encoder_trim,
+                });
+            }
+        }
+
+        // VBRI header is always at a fixed offset of 32 bytes after the frame header
+        let vbri_offset = 4 + 32;
+        if let Some(vbri) = frame.get(vbri_offset..vbri_offset + 18) {
+            if &vbri[0..4] == b"VBRI" {
+                let frames = u32::from_be_bytes([vbri[14], vbri[15], vbri[16], vbri[17]]);
+                return Some(Self {
+                    kind: VbrHeaderKind::Vbri,
+                    frames: Some(frames),
+                    encoder_trim: None,
+                });
+            }
+        }
+        None
+    }
+}
+
+impl EncoderTrim {
+    /// Parse encoder delay and padding from the LAME extension.
+    ///
+    /// | Offset | Length | Description
+    /// | ------ | ------ | -----------
+    /// |   `00` |   `09` | Encoder version string, for example `LAME3.100`
+    /// |   `09` |   `0c` | Tag revision, lowpass, replay gain, encoding flags, and bitrate
+    /// |   `15` |   `03` | Encoder delay (12 bits) and padding (12 bits)
+    ///
+    fn parse(data: &[u8]) -> Option<Self> {
+        let encoder = data.get(0..4)?;
+        if encoder != b"LAME" && encoder != b"Lavf" && encoder != b"Lavc" {
+            return None;
+        }
+        let bytes = data.get(21..24)?;
+        let delay = (u16::from(bytes[0]) << 4) | (u16::from(bytes[1]) >> 4);
+        let padding = (u16::from(bytes[1] & 0x0F) << 8) | u16::from(bytes[2]);
+        Some(Self { delay, padding })
+    }
+}
+
+impl GaplessInfo {
+    /// Duration in seconds of the actual audio content.
+    #[must_use]
+    pub fn duration(&self) -> f64 {
+        if self.sample_rate == 0 {
+            return 0.0;
+        }
+        self.samples as f64 / f64::from(self.sample_rate)
+    }
+}
+
+impl Mp3Info {
+    /// Scan all audio frames in the given file data.
+    #[must_use]
+    pub fn from_bytes(data: &[u8]) -> Option<Self> {
+        let start = id3_tag_end(data);
+        let first_frame = find_frame_sync(data, start)?;
+        let first_header = FrameHeader::parse(&data[first_frame..])?;
+
+        let mut info = Self {
+            sample_rate: first_header.sample_rate,
+            samples_per_frame: first_header.samples_per_frame(),
+            ..Default::default()
+        };
+
+        let mut offset = first_frame;
+        let frame_end = (offset + first_header.frame_length()).min(data.len());
+        if let Some(vbr_header) = VbrHeader::parse(&data[offset..frame_end], &first_header) {
+            info.vbr_header = Some(vbr_header);
+            offset += first_header.frame_length();
+        }
+
+        let mut bitrate: Option<u32> = None;
+        while let Some(header) = data.get(offset..).and_then(FrameHeader::parse) {
+            let length = header.frame_length();
+            if length == 0 || offset + length > data.len() {
+                break;
+            }
+            if bitrate.is_some_and(|bitrate| bitrate != header.bitrate) {
+                info.variable_bitrate = true;
+            }
+            bitrate = Some(header.bitrate);
+            info.frames += 1;
+            offset += length;
+        }
+
+        Some(info)
+    }
+
+    /// Read and scan an MP3 file.
+    pub fn from_path(path: &Path) -> Result<Option<Self>> {
+        let data =
+            fs::read(path).map_err(|e| TrackRenameError::io(format!("Failed to read file: {}", path.display()), e))?;
+        Ok(Self::from_bytes(&data))
+    }
+
+    /// Duration in seconds calculated from the actual frame count.
+    #[must_use]
+    pub fn duration(&self) -> f64 {
+        Self::frames_to_seconds(self.frames, self.samples_per_frame, self.sample_rate)
+    }
+
+    /// Duration in seconds reported by the VBR header.
+    #[must_use]
+    pub fn header_duration(&self) -> Option<f64> {
+        self.vbr_header
+            .and_then(|header| header.frames)
+            .map(|frames| Self::frames_to_seconds(frames, self.samples_per_frame, self.sample_rate))
+    }
+
+    /// Gapless playback information if the file has a LAME header with encoder delay and padding.
+    #[must_use]
+    pub fn gapless(&self) -> Option<GaplessInfo> {
+        let trim = self.vbr_header?.encoder_trim?;
+        let total = u64::from(self.frames) * u64::from(self.samples_per_frame);
+        Some(GaplessInfo {
+            delay: trim.delay,
+            padding: trim.padding,
+            samples: total.saturating_sub(u64::from(trim.delay) + u64::from(trim.padding)),
+            sample_rate: self.sample_rate,
+        })
+    }
+
+    /// Check that the VBR header matches the actual audio data.
+    #[must_use]
+    pub fn duration_mismatch(&self) -> Option<DurationMismatch> {
+        match self.vbr_header.and_then(|header| header.frames) {
+            Some(header) => {
+                let difference = f64::from(header.abs_diff(self.frames));
+                if difference > 1.0 && difference / f64::from(self.frames.max(1)) > FRAME_COUNT_TOLERANCE {
+                    Some(DurationMismatch::FrameCount {
+                        header,
+                        actual: self.frames,
+                    })
+                } else {
+                    None
+                }
+            }
+            None if self.variable_bitrate => Some(DurationMismatch::MissingHeader),
+            None => None,
+        }
+    }
+
+    fn frames_to_seconds(frames: u32, samples_per_frame: u32, sample_rate: u32) -> f64 {
+        if sample_rate == 0 {
+            return 0.0;
+        }
+        f64::from(frames) * f64::from(samples_per_frame) / f64::from(sample_rate)
+    }
+}
+
+impl fmt::Display for GaplessInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "delay {} samples, padding {} samples, {} samples ({:.3}s)",
+            self.delay,
+            self.padding,
+            self.samples,
+            self.duration()
+        )
+    }
+}
+
+impl fmt::Display for DurationMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::FrameCount { header, actual } => {
+                write!(
+                    f,
+                    "VBR header frame count {header} does not match actual frame count {actual}"
+                )
+            }
+            Self::MissingHeader => write!(f, "Variable bitrate file is missing a VBR header"),
+        }
+    }
+}
+
+/// Return the offset of the first valid MPEG audio frame header starting from the given offset.
+#[must_use]
+pub fn find_frame_sync(data: &[u8], start: usize) -> Option<usize> {
+    if start >= data.len() {
+        return None;
+    }
+    data[start..]
+        .windows(4)
+        .position(|window| FrameHeader::parse(window).is_some())
+        .map(|position| position + start)
+}
+
+/// Return the offset where the audio data starts after an `ID3v2` tag at the start of the data.
+fn id3_tag_end(data: &[u8]) -> usize {
+    id3_tag_size(data).map_or(0, |size| ID3_HEADER_SIZE + size)
+}
+
+/// Parse the `ID3v2` header at the start of the data and return the tag size excluding the header.
+pub(crate) fn id3_tag_size(data: &[u8]) -> Option<usize> {
+    if data.len() < ID3_HEADER_SIZE || &data[0..3] != b"ID3" {
+        return None;
+    }
+    let major_version = data[3];
+    let revision = data[4];
+    if !(2..=4).contains(&major_version) || revision == 0xFF {
+        return None;
+    }
+    // Tag size is stored as a 28-bit synchsafe integer
+    let size_bytes = &data[6..10];
+    if size_bytes.iter().any(|byte| byte & 0x80 != 0) {
+        return None;
+    }
+    let size = size_bytes
+        .iter()
+        .fold(0usize, |acc, &byte| (acc << 7) | usize::from(byte));
+
+    // Footer flag in ID3v2.4 adds another 10 bytes after the tag
+    let footer = if major_version == 4 && data[5] & 0x10 != 0 {
+        ID3_HEADER_SIZE
+    } else {
+        0
+    };
+    Some(size + footer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// MPEG-1 Layer III, 128 kbps, 44.1 kHz, stereo
+    const HEADER_128: [u8; 4] = [0xFF, 0xFB, 0x90, 0x00];
+    /// MPEG-1 Layer III, 192 kbps, 44.1 kHz, stereo
+    const HEADER_192: [u8; 4] = [0xFF, 0xFB, 0xB0, 0x00];
+
+    fn frame(header: [u8; 4]) -> Vec<u8> {
+        let length = FrameHeader::parse(&header).unwrap().frame_length();
+        let mut frame = header.to_vec();
+        frame.resize(length, 0);
+        frame
+    }
+
+    fn xing_frame(frames: u32) -> Vec<u8> {
+        let mut frame = frame(HEADER_128);
+        frame[36..40].copy_from_slice(b"Xing");
+        frame[40..44].copy_from_slice(&1u32.to_be_bytes());
+        frame[44..48].copy_from_slice(&frames.to_be_bytes());
+        frame
+    }
+
+    #[test]
+    fn test_frame_header() {
+        let header = FrameHeader::parse(&HEADER_128).expect("Valid header");
+        assert_eq!(header.version, MpegVersion::Mpeg1);
+        assert_eq!(header.layer, 3);
+        assert_eq!(header.bitrate, 128);
+        assert_eq!(header.sample_rate, 44100);
+        assert_eq!(header.frame_length(), 417);
+        assert_eq!(header.samples_per_frame(), 1152);
+        assert!(FrameHeader::parse(&[0xFF, 0xFB, 0xF0, 0x00]).is_none());
+        assert!(FrameHeader::parse(&[0xFF, 0xFB, 0x0C, 0x00]).is_none());
+    }
+
+    #[test]
+    fn test_matching_vbr_header() {
+        let mut data = xing_frame(10);
+        for _ in 0..10 {
+            data.extend(frame(HEADER_128));
+        }
+        let info = Mp3Info::from_bytes(&data).expect("Should parse");
+        assert_eq!(info.vbr_header.unwrap().kind, VbrHeaderKind::Xing);
+        assert_eq!(info.frames, 10);
+        assert_eq!(info.duration_mismatch(), None);
+    }
+
+    #[test]
+    fn test_frame_count_mismatch() {
+        let mut data = xing_frame(500);
+        for _ in 0..10 {
+            data.extend(frame(HEADER_128));
+        }
+        let info = Mp3Info::from_bytes(&data).expect("Should parse");
+        assert_eq!(
+            info.duration_mismatch(),
+            Some(DurationMismatch::FrameCount {
+                header: 500,
+                actual: 10
+            })
+        );
+        assert!(info.header_duration().unwrap() > info.duration());
+    }
+
+    #[test]
+    fn test_gapless_info() {
+        let mut data = xing_frame(10);
+        // LAME extension directly after the frame count since no other Xing fields are present
+        data[48..57].copy_from_slice(b"LAME3.100");
+        // Delay 576 = 0x240 and padding 1234 = 0x4D2
+        data[69..72].copy_from_slice(&[0x24, 0x04, 0xD2]);
+        for _ in 0..10 {
+            data.extend(frame(HEADER_128));
+        }
+        let info = Mp3Info::from_bytes(&data).expect("Should parse");
+        let gapless = info.gapless().expect("Should have gapless info");
+        assert_eq!(gapless.delay, 576);
+        assert_eq!(gapless.padding, 1234);
+        assert_eq!(gapless.samples, 10 * 1152 - 576 - 1234);
+    }
+
+    #[test]
+    fn test_missing_vbr_header() {
+        let mut data = Vec::new();
+        for header in [HEADER_128, HEADER_192, HEADER_128] {
+            data.extend(frame(header));
+        }
+        let info = Mp3Info::from_bytes(&data).expect("Should parse");
+        assert!(info.vbr_header.is_none());
+        assert!(info.variable_bitrate);
+        assert_eq!(info.duration_mismatch(), Some(DurationMismatch::MissingHeader));
+    }
+
+    #[test]
+    fn test_constant_bitrate_without_header() {
+        let mut data = Vec::new();
+        for _ in 0..5 {
+            data.extend(frame(HEADER_128));
+        }
+        let info = Mp3Info::from_bytes(&data).expect("Should parse");
+        assert_eq!(info.frames, 5);
+        assert_eq!(info.duration_mismatch(), None);
+    }
+}
@@ -0,0 +1,165 @@
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use colored::Colorize;
+
+use crate::utils;
+
+/// Track output grouped by directory and written in track order.
+///
+/// Tracks can complete in any order, for example when processed in parallel.
+/// Output for each track is buffered until all tracks before it have completed,
+/// so the directory headers and track output stay in the same order as the track list.
+#[derive(Debug)]
+pub struct OrderedOutput<W: Write> {
+    writer: W,
+    /// Number of the next track to write
+    next: usize,
+    pending: BTreeMap<usize, TrackOutput>,
+    current_dir: Option<PathBuf>,
+    directory_headers: bool,
+}
+
+/// Buffered output for a single track.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TrackOutput {
+    pub directory: PathBuf,
+    pub text: String,
+}
+
+impl TrackOutput {
+    #[must_use]
+    pub fn new(directory: &Path, text: impl Into<String>) -> Self {
+        Self {
+            directory: directory.to_path_buf(),
+            text: text.into(),
+        }
+    }
+}
+
+impl OrderedOutput<io::Stdout> {
+    /// Write to stdout starting from track number 1.
+    #[must_use]
+    pub fn stdout(directory_headers: bool) -> Self {
+        Self::new(io::stdout(), 1, directory_headers)
+    }
+}
+
+impl Default for OrderedOutput<io::Stdout> {
+    fn default() -> Self {
+        Self::stdout(true)
+    }
+}
+
+impl<W: Write> OrderedOutput<W> {
+    /// Create output that writes tracks starting from the given track number.
+    ///
+    /// A directory header is written before the first track of each directory if enabled.
+    pub const fn new(writer: W, first: usize, directory_headers: bool) -> Self {
+        Self {
+            writer,
+            next: first,
+            pending: BTreeMap::new(),
+            current_dir: None,
+            directory_headers,
+        }
+    }
+
+    /// Do not write a header for tracks at the start that are in the given directory.
+    #[must_use]
+    pub fn starting_in(mut self, directory: &Path) -> Self {
+        self.current_dir = Some(directory.to_path_buf());
+        self
+    }
+
+    /// Add the output for a completed track and write all output that is now in order.
+    pub fn complete(&mut self, number: usize, output: TrackOutput) -> io::Result<()> {
+        if number < self.next {
+            return self.write_track(&output);
+        }
+        self.pending.insert(number, output);
+        while let Some(output) = self.pending.remove(&self.next) {
+            self.write_track(&output)?;
+            self.next += 1;
+        }
+        self.writer.flush()
+    }
+
+    /// Number of completed tracks waiting for earlier tracks.
+    #[must_use]
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Write all remaining output in track order, skipping tracks that never completed.
+    pub fn finish(&mut self) -> io::Result<()> {
+        for (number, output) in std::mem::take(&mut self.pending) {
+            self.write_track(&output)?;
+            self.next = number + 1;
+        }
+        self.writer.flush()
+    }
+
+    fn write_track(&mut self, output: &TrackOutput) -> io::Result<()> {
+        if self.current_dir.as_deref() != Some(output.directory.as_path()) {
+            self.current_dir = Some(output.directory.clone());
+            let path = utils::path_to_string_relative(&output.directory);
+            if self.directory_headers && !path.is_empty() {
+                writeln!(self.writer, "\n{}", path.magenta())?;
+            }
+        }
+        write!(self.writer, "{}", output.text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_out_of_order_completion() {
+        let first = Path::new("/music/first");
+        let second = Path::new("/music/second");
+        let mut output = OrderedOutput::new(Vec::new(), 1, true);
+
+        output
+            .complete(2, TrackOutput::new(first, "two\n"))
+            .expect("Failed to write");
+        output
+            .complete(3, TrackOutput::new(second, "three\n"))
+            .expect("Failed to write");
+        assert!(output.writer.is_empty());
+        assert_eq!(output.pending(), 2);
+
+        output
+            .complete(1, TrackOutput::new(first, "one\n"))
+            .expect("Failed to write");
+        assert_eq!(output.pending(), 0);
+
+        let text = String::from_utf8(output.writer).expect("Output should be UTF-8");
+        let positions: Vec<usize> = ["first", "one", "two", "second", "three"]
+            .iter()
+            .map(|part| text.find(part).expect("Missing output"))
+            .collect();
+        assert!(positions.is_sorted());
+        assert_eq!(text.matches("first").count(), 1);
+    }
+
+    #[test]
+    fn test_finish_skips_missing() {
+        let dir = Path::new("/music");
+        let mut output = OrderedOutput::new(Vec::new(), 1, false);
+        output
+            .complete(3, TrackOutput::new(dir, "three\n"))
+            .expect("Failed to write");
+        output
+            .complete(2, TrackOutput::new(dir, "two\n"))
+            .expect("Failed to write");
+        output.finish().expect("Failed to write");
+        assert_eq!(
+            String::from_utf8(output.writer).expect("Output should be UTF-8"),
+            "two\nthree\n"
+        );
+    }
+}
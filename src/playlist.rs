@@ -0,0 +1,353 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use crate::error::{Result, TrackRenameError};
+use crate::serato::crates::SeratoCrate;
+use crate::utils;
+
+/// Supported playlist file formats.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlaylistFormat {
+    M3u,
+    M3u8,
+    Crate,
+}
+
+/// Playlist file with the tracks it references.
+#[derive(Debug, Clone)]
+pub struct Playlist {
+    /// Path to the playlist file
+    pub path: PathBuf,
+    pub format: PlaylistFormat,
+    /// Absolute track paths in playlist order
+    pub tracks: Vec<PathBuf>,
+    /// Original file content for M3U playlists, with the track index for each path line
+    lines: Vec<(String, Option<usize>)>,
+    /// M3U file was not valid UTF-8 and was read as Latin-1
+    latin1: bool,
+    /// Parsed crate data for Serato crates
+    serato_crate: Option<SeratoCrate>,
+}
+
+impl Playlist {
+    /// Create an empty playlist with the format from the file extension.
+    pub fn new(path: &Path) -> Result<Self> {
+        let format = PlaylistFormat::from_path(path)?;
+        let (lines, serato_crate) = match format {
+            PlaylistFormat::M3u | PlaylistFormat::M3u8 => (vec![("#EXTM3U".to_string(), None)], None),
+            PlaylistFormat::Crate => (Vec::new(), Some(SeratoCrate::new())),
+        };
+        Ok(Self {
+            path: path.to_path_buf(),
+            format,
+            tracks: Vec::new(),
+            lines,
+            latin1: false,
+            serato_crate,
+        })
+    }
+
+    /// Read playlist from file.
+    pub fn read(path: &Path) -> Result<Self> {
+        let format = PlaylistFormat::from_path(path)?;
+        match format {
+            PlaylistFormat::M3u | PlaylistFormat::M3u8 => Self::read_m3u(path, format),
+            PlaylistFormat::Crate => {
+                let serato_crate = SeratoCrate::read(path)?;
+                Ok(Self {
+                    path: path.to_path_buf(),
+                    format,
+                    tracks: serato_crate.track_paths(path),
+                    lines: Vec::new(),
+                    latin1: false,
+                    serato_crate: Some(serato_crate),
+                })
+            }
+        }
+    }
+
+    /// Parse M3U playlist.
+    /// Comment and directive lines starting with `#` are kept as is when the playlist is saved.
+    fn read_m3u(path: &Path, format: PlaylistFormat) -> Result<Self> {
+        let data = fs::read(path)
+            .map_err(|error| TrackRenameError::io(format!("Failed to read playlist: {}", path.display()), error))?;
+        // Plain M3U files are often Latin-1 encoded
+        let (content, latin1) = match String::from_utf8(data) {
+            Ok(content) => (content, false),
+            Err(error) => (error.into_bytes().iter().map(|&b| b as char).collect(), true),
+        };
+        let content = content.trim_start_matches('\u{FEFF}');
+        let playlist_dir = path.parent().unwrap_or_else(|| Path::new(""));
+
+        let mut tracks = Vec::new();
+        let mut lines = Vec::new();
+        for line in content.lines() {
+            let entry = line.trim();
+            if entry.is_empty() || entry.starts_with('#') {
+                lines.push((line.to_string(), None));
+                continue;
+            }
+            let entry = entry.strip_prefix("file://").unwrap_or(entry);
+            let track_path = PathBuf::from(entry);
+            let track_path = if track_path.is_absolute() {
+                track_path
+            } else {
+                playlist_dir.join(track_path)
+            };
+            lines.push((line.to_string(), Some(tracks.len())));
+            tracks.push(dunce::simplified(&track_path).to_path_buf());
+        }
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            format,
+            tracks,
+            lines,
+            latin1,
+            serato_crate: None,
+        })
+    }
+
+    /// Replace renamed track paths.
+    /// Returns the number of updated entries.
+    pub fn update_paths(&mut self, renamed: &HashMap<PathBuf, PathBuf>) -> usize {
+        if let Some(serato_crate) = self.serato_crate.as_mut() {
+            let updated = serato_crate.rename_tracks(renamed, &self.path);
+            self.tracks = serato_crate.track_paths(&self.path);
+            return updated;
+        }
+
+        // Compare with NFC normalized strings since the playlist might use a different Unicode form
+        let renamed: HashMap<String, &PathBuf> = renamed
+            .iter()
+            .map(|(old, new)| (utils::normalize_str(&old.to_string_lossy()), new))
+            .collect();
+
+        // Line for each track index
+        let mut track_lines: HashMap<usize, &mut String> = self
+            .lines
+            .iter_mut()
+            .filter_map(|(line, index)| index.map(|index| (index, line)))
+            .collect();
+
+        let mut updated = 0;
+        for (index, track) in self.tracks.iter_mut().enumerate() {
+            let Some(new_path) = renamed.get(&utils::normalize_str(&track.to_string_lossy())) else {
+                continue;
+            };
+            if let Some(line) = track_lines.get_mut(&index) {
+                **line = replace_filename(line, new_path);
+            }
+            track.clone_from(new_path);
+            updated += 1;
+        }
+        updated
+    }
+
+    /// Add a track to the end of the playlist.
+    ///
+    /// Crates only include tracks on the same volume as the crate file.
+    pub fn add_track(&mut self, track_path: &Path) {
+        if let Some(serato_crate) = self.serato_crate.as_mut() {
+            if serato_crate.add_track(track_path, &self.path) {
+                self.tracks = serato_crate.track_paths(&self.path);
+            }
+            return;
+        }
+        self.lines
+            .push((track_path.to_string_lossy().to_string(), Some(self.tracks.len())));
+        self.tracks.push(track_path.to_path_buf());
+    }
+
+    /// Write playlist back to file.
+    pub fn save(&self) -> Result<()> {
+        match self.format {
+            PlaylistFormat::M3u | PlaylistFormat::M3u8 => {
+                let mut content = self
+                    .lines
+                    .iter()
+                    .map(|(line, _)| line.as_str())
+                    .collect::<Vec<_>>()
+                    .join("\n");
+                content.push('\n');
+                // Keep the original encoding unless a renamed path can not be represented in Latin-1
+                let data = if self.latin1 {
+                    encode_latin1(&content).unwrap_or_else(|| content.into_bytes())
+                } else {
+                    content.into_bytes()
+                };
+                fs::write(&self.path, data).map_err(|error| {
+                    TrackRenameError::io(format!("Failed to write playlist: {}", self.path.display()), error)
+                })
+            }
+            PlaylistFormat::Crate => self
+                .serato_crate
+                .as_ref()
+                .ok_or_else(|| TrackRenameError::InvalidSerato("Missing crate data".to_string()))?
+                .write(&self.path),
+        }
+    }
+}
+
+/// Check if the path has a supported playlist file extension.
+#[must_use]
+pub fn is_playlist(path: &Path) -> bool {
+    path.is_file()
+        && path
+            .extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(|extension| PlaylistFormat::from_str(extension).is_ok())
+}
+
+/// Encode text as Latin-1 bytes, or return `None` if it has characters outside Latin-1.
+fn encode_latin1(text: &str) -> Option<Vec<u8>> {
+    text.chars().map(|c| u8::try_from(c).ok()).collect()
+}
+
+/// Replace the filename in a playlist path line while keeping the directory part as written.
+fn replace_filename(line: &str, new_path: &Path) -> String {
+    let new_name = new_path
+        .file_name()
+        .map(|name| name.to_string_lossy())
+        .unwrap_or_default();
+    line.rfind(['/', '\\']).map_or_else(
+        || {
+            let indent = &line[..line.len() - line.trim_start().len()];
+            format!("{indent}{new_name}")
+        },
+        |index| format!("{}{new_name}", &line[..=index]),
+    )
+}
+
+impl PlaylistFormat {
+    /// Get the playlist format from the file extension.
+    fn from_path(path: &Path) -> Result<Self> {
+        path.extension()
+            .and_then(|e| e.to_str())
+            .ok_or_else(|| TrackRenameError::invalid_path(path, "Failed to get playlist file extension"))
+            .and_then(Self::from_str)
+    }
+}
+
+impl FromStr for PlaylistFormat {
+    type Err = TrackRenameError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "m3u" => Ok(Self::M3u),
+            "m3u8" => Ok(Self::M3u8),
+            "crate" => Ok(Self::Crate),
+            _ => Err(TrackRenameError::UnsupportedFormat(format!("playlist {s}"))),
+        }
+    }
+}
+
+impl fmt::Display for PlaylistFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::M3u => "m3u",
+                Self::M3u8 => "m3u8",
+                Self::Crate => "crate",
+            }
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_replace_filename() {
+        assert_eq!(
+            replace_filename("../Music/old name.mp3", Path::new("/music/New Name.mp3")),
+            "../Music/New Name.mp3"
+        );
+        assert_eq!(
+            replace_filename("C:\\Music\\old.mp3", Path::new("new.aif")),
+            "C:\\Music\\new.aif"
+        );
+        assert_eq!(replace_filename("old.mp3", Path::new("/music/new.mp3")), "new.mp3");
+    }
+
+    #[test]
+    fn test_new_m3u() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let path = dir.path().join("new.m3u8");
+        let mut playlist = Playlist::new(&path).expect("Failed to create playlist");
+        playlist.add_track(Path::new("/music/Artist - Title.mp3"));
+        playlist.save().expect("Failed to save playlist");
+
+        assert_eq!(
+            fs::read_to_string(&path).expect("Failed to read playlist"),
+            "#EXTM3U\n/music/Artist - Title.mp3\n"
+        );
+        let playlist = Playlist::read(&path).expect("Failed to read playlist");
+        assert_eq!(playlist.tracks, vec![PathBuf::from("/music/Artist - Title.mp3")]);
+        assert!(Playlist::new(&dir.path().join("new.txt")).is_err());
+    }
+
+    #[test]
+    fn test_read_and_update_m3u() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let path = dir.path().join("test.m3u8");
+        fs::write(
+            &path,
+            "#EXTM3U\n#EXTINF:123,Artist - Title\nartist - title.mp3\n/abs/other.aif\n",
+        )
+        .expect("Failed to write playlist");
+
+        let mut playlist = Playlist::read(&path).expect("Failed to read playlist");
+        assert_eq!(playlist.format, PlaylistFormat::M3u8);
+        assert_eq!(
+            playlist.tracks,
+            vec![dir.path().join("artist - title.mp3"), PathBuf::from("/abs/other.aif")]
+        );
+
+        let renamed = HashMap::from([(
+            dir.path().join("artist - title.mp3"),
+            dir.path().join("Artist - Title.mp3"),
+        )]);
+        assert_eq!(playlist.update_paths(&renamed), 1);
+        playlist.save().expect("Failed to save playlist");
+
+        let content = fs::read_to_string(&path).expect("Failed to read playlist");
+        assert_eq!(
+            content,
+            "#EXTM3U\n#EXTINF:123,Artist - Title\nArtist - Title.mp3\n/abs/other.aif\n"
+        );
+    }
+
+    #[test]
+    fn test_keep_latin1_encoding() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let path = dir.path().join("latin1.m3u");
+        fs::write(&path, b"#EXTINF:1,Caf\xe9\ncaf\xe9 - old.mp3\n").expect("Failed to write playlist");
+
+        let mut playlist = Playlist::read(&path).expect("Failed to read playlist");
+        assert_eq!(playlist.tracks, vec![dir.path().join("café - old.mp3")]);
+
+        let renamed = HashMap::from([(dir.path().join("café - old.mp3"), dir.path().join("Café - New.mp3"))]);
+        assert_eq!(playlist.update_paths(&renamed), 1);
+        playlist.save().expect("Failed to save playlist");
+        assert_eq!(
+            fs::read(&path).expect("Failed to read playlist"),
+            b"#EXTINF:1,Caf\xe9\nCaf\xe9 - New.mp3\n"
+        );
+
+        // Characters outside Latin-1 can only be written as UTF-8
+        let renamed = HashMap::from([(dir.path().join("Café - New.mp3"), dir.path().join("Café - Ω.mp3"))]);
+        assert_eq!(playlist.update_paths(&renamed), 1);
+        playlist.save().expect("Failed to save playlist");
+        assert_eq!(
+            fs::read_to_string(&path).expect("Playlist should be UTF-8"),
+            "#EXTINF:1,Café\nCafé - Ω.mp3\n"
+        );
+    }
+}
@@ -0,0 +1,23 @@
+use std::io::{self, BufRead, Write};
+
+use anyhow::Result;
+
+use track_rename::formatting::FormatOptions;
+use track_rename::plugin_api;
+
+/// Answer formatting requests from stdin with one JSON response line for each request line.
+///
+/// Nothing else is written to stdout so the output can be read directly by the calling plugin.
+pub fn run(options: &FormatOptions) -> Result<()> {
+    let mut stdout = io::stdout().lock();
+    for line in io::stdin().lock().lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        writeln!(stdout, "{}", plugin_api::handle_json(&line, options))?;
+        // Flush each response so the plugin can send requests one at a time
+        stdout.flush()?;
+    }
+    Ok(())
+}
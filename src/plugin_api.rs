@@ -0,0 +1,229 @@
+//! Stable interface for other music library tools, like a beets plugin.
+//!
+//! A plugin sends the current tag values of one item as a JSON object
+//! and gets back the values formatted with the DJ-specific rules and the formatted file name.
+//! Files are never read or modified, so the calling tool stays in charge of writing tags and moving files.
+//!
+//! The JSON field names and meanings only change together with [`API_VERSION`].
+//! New optional fields can be added without a version change,
+//! so callers should ignore unknown fields in responses.
+
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Result, TrackRenameError};
+use crate::formatting::FormatOptions;
+use crate::tags::TrackTags;
+use crate::track::Track;
+use crate::utils;
+
+/// Version of the request and response format.
+pub const API_VERSION: u32 = 1;
+
+/// Tag values of one item to format.
+///
+/// All fields are optional in JSON.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ItemRequest {
+    /// Interface version the caller was written for, defaults to the current version
+    pub version: Option<u32>,
+    pub artist: String,
+    pub title: String,
+    pub album: String,
+    pub genre: String,
+    pub year: Option<i32>,
+    /// Mix and version descriptors from the subtitle tag
+    pub subtitle: String,
+    pub album_artist: String,
+    /// Item is part of a compilation
+    pub compilation: bool,
+    /// Path of the audio file, used for the file extension and the folder-specific rules
+    pub path: Option<PathBuf>,
+}
+
+/// Formatted tag values for one item.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct ItemResponse {
+    pub version: u32,
+    pub artist: String,
+    pub title: String,
+    pub album: String,
+    pub genre: String,
+    pub year: Option<i32>,
+    pub subtitle: String,
+    pub album_artist: String,
+    /// Formatted file name with the extension from the request path, or without an extension
+    pub filename: String,
+    /// True if any tag value differs from the request
+    pub changed: bool,
+}
+
+/// Error returned for a request that can't be handled.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct ErrorResponse {
+    pub version: u32,
+    pub error: String,
+}
+
+impl ErrorResponse {
+    #[must_use]
+    pub fn new(message: impl Into<String>) -> Self {
+        Self {
+            version: API_VERSION,
+            error: message.into(),
+        }
+    }
+}
+
+/// Format one item using the given formatting options.
+///
+/// The values are formatted the same way as the tags of a track in the renamer.
+/// Returns an error if the request is for a newer interface version.
+pub fn format_item(request: &ItemRequest, options: &FormatOptions) -> Result<ItemResponse> {
+    if let Some(version) = request.version.filter(|&version| version > API_VERSION) {
+        return Err(TrackRenameError::InvalidArgument(format!(
+            "Unsupported API version {version}, latest supported version is {API_VERSION}"
+        )));
+    }
+    let artist = utils::normalize_str(&request.artist);
+    let title = utils::normalize_str(&request.title);
+    let album_artist = utils::normalize_str(&request.album_artist);
+    let subtitle = utils::normalize_str(&request.subtitle);
+    let tags = TrackTags {
+        formatted_album_artist: album_artist.clone(),
+        current_album_artist: album_artist,
+        compilation: request.compilation,
+        formatted_subtitle: subtitle.clone(),
+        current_subtitle: subtitle,
+        current_year: request.year,
+        formatted_year: request.year,
+        ..TrackTags::new(
+            format!("{artist} - {title}"),
+            artist,
+            title,
+            utils::normalize_str(&request.album),
+            utils::normalize_str(&request.genre),
+        )
+    };
+    let mut track = Track::without_file(request.path.as_deref().unwrap_or_else(|| Path::new("")));
+    track.format_tag_values(tags, options);
+
+    let mut filename = track.formatted_filename(options);
+    if let Some(extension) = request.path.as_deref().and_then(Path::extension) {
+        filename = format!("{filename}.{}", extension.to_string_lossy());
+    }
+    let tags = track.tags;
+    let changed = tags.formatted_artist != request.artist
+        || tags.formatted_title != request.title
+        || tags.formatted_album != request.album
+        || tags.formatted_genre != request.genre
+        || tags.formatted_year != request.year
+        || tags.formatted_subtitle != request.subtitle
+        || tags.formatted_album_artist != request.album_artist;
+    Ok(ItemResponse {
+        version: API_VERSION,
+        artist: tags.formatted_artist,
+        title: tags.formatted_title,
+        album: tags.formatted_album,
+        genre: tags.formatted_genre,
+        year: tags.formatted_year,
+        subtitle: tags.formatted_subtitle,
+        album_artist: tags.formatted_album_artist,
+        filename,
+        changed,
+    })
+}
+
+/// Format one item from a JSON request and return the JSON response.
+///
+/// Invalid requests get an [`ErrorResponse`] so a caller reading line by line always gets one line back.
+#[must_use]
+pub fn handle_json(request: &str, options: &FormatOptions) -> String {
+    let response = serde_json::from_str::<ItemRequest>(request)
+        .map_err(|error| ErrorResponse::new(format!("Invalid request: {error}")))
+        .and_then(|request| format_item(&request, options).map_err(|error| ErrorResponse::new(error.to_string())));
+    match response {
+        Ok(response) => serde_json::to_string(&response),
+        Err(error) => serde_json::to_string(&error),
+    }
+    .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::formatting::{AlbumArtist, Subtitle};
+
+    #[test]
+    fn test_format_item() {
+        let request = ItemRequest {
+            artist: "Artist ft. Someone".to_string(),
+            title: "Song (Original Mix)".to_string(),
+            genre: "House".to_string(),
+            path: Some(PathBuf::from("/music/Artist - song.mp3")),
+            ..ItemRequest::default()
+        };
+        let response = format_item(&request, &FormatOptions::default()).expect("Failed to format item");
+        assert_eq!(response.version, API_VERSION);
+        assert_eq!(response.artist, "Artist feat. Someone");
+        assert_eq!(response.title, "Song");
+        assert_eq!(response.genre, "House");
+        assert_eq!(response.filename, "Artist feat. Someone - Song.mp3");
+        assert!(response.changed);
+
+        let unchanged = ItemRequest {
+            artist: response.artist,
+            title: response.title,
+            genre: response.genre,
+            ..ItemRequest::default()
+        };
+        let response = format_item(&unchanged, &FormatOptions::default()).expect("Failed to format item");
+        assert!(!response.changed);
+        assert_eq!(response.filename, "Artist feat. Someone - Song");
+    }
+
+    #[test]
+    fn test_format_item_with_options() {
+        let options = FormatOptions {
+            subtitle: Subtitle::Tag,
+            album_artist: AlbumArtist::Fill,
+            ..FormatOptions::default()
+        };
+        let request = ItemRequest {
+            artist: "Artist ft. Someone".to_string(),
+            title: "Song (Extended Mix) (128-100 Transition)".to_string(),
+            ..ItemRequest::default()
+        };
+        let response = format_item(&request, &options).expect("Failed to format item");
+        assert_eq!(response.title, "Song");
+        assert_eq!(response.subtitle, "Extended Mix / 128-100 Transition");
+        assert_eq!(response.album_artist, "Artist");
+        assert_eq!(
+            response.filename,
+            "Artist feat. Someone - Song (Extended Mix) (128-100 Transition)"
+        );
+    }
+
+    #[test]
+    fn test_handle_json() {
+        let response = handle_json(
+            r#"{"artist":"Artist","title":"Song","extra":1}"#,
+            &FormatOptions::default(),
+        );
+        let response: ItemResponse = serde_json::from_str(&response).expect("Failed to parse response");
+        assert_eq!(response.filename, "Artist - Song");
+
+        let error: ErrorResponse = serde_json::from_str(&handle_json(r#"{"version":99}"#, &FormatOptions::default()))
+            .expect("Failed to parse");
+        assert!(error.error.contains("99"));
+
+        let error: ErrorResponse =
+            serde_json::from_str(&handle_json("not json", &FormatOptions::default())).expect("Failed to parse");
+        assert!(error.error.starts_with("Invalid request"));
+    }
+}
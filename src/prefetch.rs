@@ -0,0 +1,64 @@
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::Arc;
+use std::thread;
+
+use id3::Tag;
+use rayon::prelude::*;
+use rayon::ThreadPool;
+
+use track_rename::utils;
+
+/// Reads tags ahead of processing using a separate IO thread pool.
+///
+/// Tracks are read concurrently in small batches and handed over in the original order,
+/// so processing and all file modifications stay serialized.
+/// The channel is bounded, which limits how many tags are kept in memory at a time.
+pub struct TagPrefetcher {
+    receiver: Receiver<(PathBuf, id3::Result<Tag>)>,
+}
+
+impl TagPrefetcher {
+    /// Start reading tags for the given files in the background.
+    pub fn start(paths: Vec<PathBuf>, pool: Arc<ThreadPool>, text_tags_only: bool) -> Self {
+        let batch_size = pool.current_num_threads() * 2;
+        let (sender, receiver) = mpsc::sync_channel(batch_size);
+        thread::spawn(move || {
+            for batch in paths.chunks(batch_size) {
+                let results: Vec<(PathBuf, id3::Result<Tag>)> = pool.install(|| {
+                    batch
+                        .par_iter()
+                        .map(|path| {
+                            let result = if text_tags_only {
+                                utils::read_text_tags_from_path(path)
+                            } else {
+                                Tag::read_from_path(path)
+                            };
+                            (path.clone(), result)
+                        })
+                        .collect()
+                });
+                for result in results {
+                    // Receiver is gone when processing stopped early
+                    if sender.send(result).is_err() {
+                        return;
+                    }
+                }
+            }
+        });
+        Self { receiver }
+    }
+
+    /// Get the read result for the given file.
+    ///
+    /// Results for files before it that were not requested are dropped.
+    /// Returns `None` if the file was not prefetched, in which case it should be read directly.
+    pub fn take(&self, path: &Path) -> Option<id3::Result<Tag>> {
+        while let Ok((prefetched_path, result)) = self.receiver.recv() {
+            if prefetched_path == path {
+                return Some(result);
+            }
+        }
+        None
+    }
+}
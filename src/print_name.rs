@@ -0,0 +1,57 @@
+use anyhow::{bail, Context, Result};
+use clap::ValueEnum;
+use serde::Serialize;
+
+use crate::PrintNameArgs;
+
+use track_rename::formatting::FormatOptions;
+use track_rename::track::Track;
+use track_rename::utils;
+
+/// Output format for `print-name`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum NameFormat {
+    /// Only the formatted filename
+    #[default]
+    Text,
+    /// All formatted name components as a JSON object
+    Json,
+}
+
+/// Formatted name components for one file.
+#[derive(Debug, Serialize)]
+struct FormattedName {
+    path: String,
+    artist: String,
+    title: String,
+    album: String,
+    genre: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    year: Option<i32>,
+    filename: String,
+}
+
+/// Print the formatted name of a single file without modifying anything.
+pub fn run(args: &PrintNameArgs, options: &FormatOptions) -> Result<()> {
+    let path = dunce::canonicalize(&args.file).with_context(|| format!("File not found: {}", args.file.display()))?;
+    let Some(mut track) = Track::try_from_path(&path) else {
+        bail!("Not a supported audio file: {}", path.display());
+    };
+    let file_tags = utils::read_tags(&track, false).with_context(|| format!("Failed to read tags: {track}"))?;
+    track.format_tags(&file_tags, options);
+
+    let name = FormattedName {
+        path: utils::path_to_string(&track.path),
+        filename: track.formatted_filename_with_extension(options),
+        artist: track.tags.formatted_artist,
+        title: track.tags.formatted_title,
+        album: track.tags.formatted_album,
+        genre: track.tags.formatted_genre,
+        year: track.tags.formatted_year,
+    };
+    match args.format {
+        NameFormat::Text => println!("{}", name.filename),
+        NameFormat::Json => println!("{}", serde_json::to_string_pretty(&name)?),
+    }
+    Ok(())
+}
@@ -0,0 +1,640 @@
+use std::fmt;
+use std::fs::File;
+use std::io::{IsTerminal, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+use std::sync::{Mutex, OnceLock, PoisonError};
+
+use clap::ValueEnum;
+use colored::Colorize;
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Result, TrackRenameError};
+use crate::i18n::Message;
+use crate::utils;
+
+/// Answer used for empty input in confirmation prompts.
+static CONFIRM_DEFAULT_YES: AtomicBool = AtomicBool::new(false);
+
+/// Use JSON lines instead of human-readable prompts.
+static JSON_PROMPTS: AtomicBool = AtomicBool::new(false);
+
+/// Original stdout for JSON prompt requests after the human-readable output was moved to stderr.
+static JSON_OUTPUT: OnceLock<Mutex<File>> = OnceLock::new();
+
+/// How prompts are answered when stdin is not a terminal.
+static NON_INTERACTIVE: AtomicU8 = AtomicU8::new(NonInteractive::Fail as u8);
+
+/// A prompt could not be answered because stdin is not a terminal.
+static NON_INTERACTIVE_FAILED: AtomicBool = AtomicBool::new(false);
+
+/// Answer to a confirmation prompt.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Answer {
+    Yes,
+    No,
+    /// Edit the change before applying it
+    Edit,
+    /// Accept this and all remaining changes
+    All,
+    /// Skip this change and stop processing
+    Quit,
+}
+
+/// How confirmation prompts are shown and answered.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum PromptProtocol {
+    /// Human-readable prompts answered with y/n
+    #[default]
+    Text,
+    /// JSON lines on stdout answered with JSON lines on stdin
+    Json,
+}
+
+/// Details of the change being confirmed, included in JSON prompts.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct PromptContext {
+    pub path: PathBuf,
+    pub action: String,
+    pub changes: Vec<FieldChange>,
+}
+
+/// Current and proposed value for one field.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct FieldChange {
+    pub field: String,
+    pub current: String,
+    pub proposed: String,
+}
+
+/// Changed words of each field without the unchanged text around them.
+///
+/// Tracks that get the same fix, like removing `DJcity` from the title, have the same pattern.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ChangePattern(Vec<(String, String, String)>);
+
+/// How much a change modifies the text, from least to most.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeSeverity {
+    /// Only whitespace differs
+    Whitespace,
+    /// Only capitalization and whitespace differ
+    #[default]
+    Case,
+    /// Any other change
+    #[value(skip)]
+    Content,
+}
+
+impl FieldChange {
+    /// Classify how much the proposed value differs from the current value.
+    #[must_use]
+    pub fn severity(&self) -> ChangeSeverity {
+        let current: String = self.current.split_whitespace().collect();
+        let proposed: String = self.proposed.split_whitespace().collect();
+        if current == proposed {
+            ChangeSeverity::Whitespace
+        } else if current.to_lowercase() == proposed.to_lowercase() {
+            ChangeSeverity::Case
+        } else {
+            ChangeSeverity::Content
+        }
+    }
+}
+
+impl ChangePattern {
+    /// Collect the changed words of all field changes.
+    #[must_use]
+    pub fn from_changes(changes: &[FieldChange]) -> Self {
+        Self(
+            changes
+                .iter()
+                .flat_map(|change| {
+                    utils::word_changes(&change.current, &change.proposed)
+                        .into_iter()
+                        .map(|(removed, added)| (change.field.clone(), removed, added))
+                })
+                .collect(),
+        )
+    }
+
+    /// Return true if there are no word changes, for example when only whitespace changes.
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl fmt::Display for ChangePattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, (field, removed, added)) in self.0.iter().enumerate() {
+            if index > 0 {
+                writeln!(f)?;
+            }
+            write!(
+                f,
+                "  {field}: {} -> {}",
+                format!("\"{removed}\"").red(),
+                format!("\"{added}\"").green()
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Return true if all changes are at most the given severity.
+#[must_use]
+pub fn is_minor_change(changes: &[FieldChange], threshold: ChangeSeverity) -> bool {
+    !changes.is_empty() && changes.iter().all(|change| change.severity() <= threshold)
+}
+
+/// Request written to stdout in the JSON prompt protocol.
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum PromptRequest<'a> {
+    Confirm {
+        options: Vec<Answer>,
+        default: Answer,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        context: Option<&'a PromptContext>,
+    },
+    Edit {
+        field: &'a str,
+        current: &'a str,
+    },
+    Select {
+        label: &'a str,
+        options: &'a [String],
+        default: usize,
+    },
+    Error {
+        message: String,
+    },
+}
+
+/// Response read from stdin in the JSON prompt protocol.
+#[derive(Debug, Default, Deserialize)]
+struct PromptResponse {
+    answer: Option<Answer>,
+    value: Option<String>,
+    index: Option<usize>,
+}
+
+impl Answer {
+    const fn key(self) -> &'static str {
+        match self {
+            Self::Yes => "y",
+            Self::No => "n",
+            Self::Edit => "e",
+            Self::All => "a",
+            Self::Quit => "q",
+        }
+    }
+
+    fn help(self) -> &'static str {
+        match self {
+            Self::Yes => Message::HelpYes,
+            Self::No => Message::HelpNo,
+            Self::Edit => Message::HelpEdit,
+            Self::All => Message::HelpAll,
+            Self::Quit => Message::HelpQuit,
+        }
+        .text()
+    }
+}
+
+/// How text prompts are answered when stdin is not a terminal, like when run from a script.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum NonInteractive {
+    /// Stop with an error instead of waiting for input that never comes
+    #[default]
+    Fail,
+    /// Answer yes to every prompt
+    Yes,
+    /// Answer no to every prompt
+    No,
+}
+
+/// Set the answer used when the user just presses Enter in a confirmation prompt.
+pub fn set_confirm_default(yes: bool) {
+    CONFIRM_DEFAULT_YES.store(yes, Ordering::Relaxed);
+}
+
+/// Set how confirmation prompts are shown and answered.
+///
+/// JSON prompts keep stdout for the requests only,
+/// so all other output is moved to stderr on platforms that support it.
+pub fn set_protocol(protocol: PromptProtocol) -> Result<()> {
+    let json = protocol == PromptProtocol::Json;
+    JSON_PROMPTS.store(json, Ordering::Relaxed);
+    #[cfg(unix)]
+    if json {
+        redirect_stdout_to_stderr()?;
+    }
+    Ok(())
+}
+
+/// Keep the original stdout for JSON requests and point the process stdout to stderr.
+#[cfg(unix)]
+fn redirect_stdout_to_stderr() -> Result<()> {
+    use std::os::fd::AsFd;
+
+    let mut stdout = std::io::stdout().lock();
+    stdout
+        .flush()
+        .map_err(|error| TrackRenameError::io("Failed to flush stdout", error))?;
+    let original = stdout
+        .as_fd()
+        .try_clone_to_owned()
+        .map_err(|error| TrackRenameError::io("Failed to duplicate stdout", error))?;
+    rustix::stdio::dup2_stdout(std::io::stderr())
+        .map_err(|error| TrackRenameError::io("Failed to redirect stdout to stderr", error.into()))?;
+    // Only the first call redirects, so the original stdout is always the one that was set
+    let _ = JSON_OUTPUT.set(Mutex::new(File::from(original)));
+    Ok(())
+}
+
+/// Set how text prompts are answered when stdin is not a terminal.
+pub fn set_non_interactive(policy: NonInteractive) {
+    NON_INTERACTIVE.store(policy as u8, Ordering::Relaxed);
+}
+
+/// True if a prompt was refused because stdin is not a terminal and no answer policy was given.
+#[must_use]
+pub fn non_interactive_failed() -> bool {
+    NON_INTERACTIVE_FAILED.load(Ordering::Relaxed)
+}
+
+/// Ask user to confirm action.
+///
+/// Only `y` and `yes` are accepted as a yes,
+/// and empty input uses the configured default answer which is a no unless changed.
+#[must_use]
+pub fn confirm() -> bool {
+    ask(&[], None) == Answer::Yes
+}
+
+/// Ask user to confirm action, also accepting the given extra answers.
+///
+/// Yes and no are always accepted.
+/// The context is only shown in JSON prompts since the human output prints the diff before asking.
+#[must_use]
+pub fn ask(extra: &[Answer], context: Option<&PromptContext>) -> Answer {
+    let default_yes = CONFIRM_DEFAULT_YES.load(Ordering::Relaxed);
+    if JSON_PROMPTS.load(Ordering::Relaxed) {
+        return ask_json(extra, default_yes, context);
+    }
+    let mut options = vec![if default_yes { "Y/n" } else { "y/N" }];
+    if let Some(answer) = non_interactive_answer() {
+        // Quitting stops processing after a failure, and is only possible where the caller handles it
+        let answer = if answer == Answer::Quit && !extra.contains(&Answer::Quit) {
+            Answer::No
+        } else {
+            answer
+        };
+        if answer != Answer::Quit {
+            println!("{} ({})? {}", Message::Proceed, options[0], answer.key());
+        }
+        return answer;
+    }
+    let mut help = vec![Answer::Yes.help(), Answer::No.help()];
+    for answer in extra {
+        options.push(answer.key());
+        help.push(answer.help());
+    }
+    loop {
+        print!("{} ({})? ", Message::Proceed, options.join("/"));
+        std::io::stdout().flush().expect("Failed to flush stdout");
+        // Closed input is always a no so nothing gets applied by accident
+        let Some(input) = read_input() else {
+            println!();
+            return Answer::No;
+        };
+        if let Some(answer) = parse_answer(&input, default_yes, extra) {
+            return answer;
+        }
+        println!("{}", Message::AnswerHint.format(&[&help.join(", ")]).yellow());
+    }
+}
+
+/// Parse answer to a confirmation prompt.
+///
+/// Returns `None` for unrecognized input or an answer that is not allowed.
+#[must_use]
+pub fn parse_answer(input: &str, default_yes: bool, extra: &[Answer]) -> Option<Answer> {
+    let answer = match input.trim().to_lowercase().as_str() {
+        "" => {
+            if default_yes {
+                Answer::Yes
+            } else {
+                Answer::No
+            }
+        }
+        "y" | "yes" => Answer::Yes,
+        "n" | "no" => Answer::No,
+        "e" | "edit" => Answer::Edit,
+        "a" | "all" => Answer::All,
+        "q" | "quit" => Answer::Quit,
+        _ => return None,
+    };
+    allowed(answer, extra).then_some(answer)
+}
+
+/// Ask user for a new value.
+///
+/// Empty input keeps the current value.
+#[must_use]
+pub fn edit_value(label: &str, current: &str) -> String {
+    let input = if JSON_PROMPTS.load(Ordering::Relaxed) {
+        // A closed output is handled like closed input and keeps the current value
+        write_request(&PromptRequest::Edit { field: label, current })
+            .ok()
+            .and_then(|()| read_input())
+            .and_then(|line| {
+                serde_json::from_str::<PromptResponse>(&line)
+                    .map_err(|error| {
+                        let _ = write_request(&PromptRequest::Error {
+                            message: format!("Invalid response, keeping current value: {error}"),
+                        });
+                    })
+                    .ok()
+                    .and_then(|response| response.value)
+            })
+    } else {
+        print!("{} [{current}]: ", label.bold());
+        std::io::stdout().flush().expect("Failed to flush stdout");
+        read_input()
+    };
+    match input.as_deref().map(str::trim) {
+        Some(value) if !value.is_empty() => utils::normalize_str(value),
+        _ => current.to_string(),
+    }
+}
+
+/// Ask user to choose one of the options.
+///
+/// Options are numbered from one in the text prompt and from zero in JSON prompts.
+/// Empty input and closed stdin choose the first option.
+#[must_use]
+pub fn select(label: &str, options: &[String]) -> usize {
+    if JSON_PROMPTS.load(Ordering::Relaxed) {
+        loop {
+            let request = PromptRequest::Select {
+                label,
+                options,
+                default: 0,
+            };
+            let Some(input) = write_request(&request).ok().and_then(|()| read_input()) else {
+                return 0;
+            };
+            if input.trim().is_empty() {
+                return 0;
+            }
+            let message = match serde_json::from_str::<PromptResponse>(input.trim()) {
+                Ok(PromptResponse { index: Some(index), .. }) if index < options.len() => return index,
+                Ok(_) => format!("Response must have an index below {}", options.len()),
+                Err(error) => format!("Invalid response: {error}"),
+            };
+            if write_request(&PromptRequest::Error { message }).is_err() {
+                return 0;
+            }
+        }
+    }
+    if non_interactive_answer().is_some() {
+        return 0;
+    }
+    println!("{}", label.bold());
+    for (number, option) in options.iter().enumerate() {
+        println!("  {}) {option}", number + 1);
+    }
+    loop {
+        print!("[1-{}] (1): ", options.len());
+        std::io::stdout().flush().expect("Failed to flush stdout");
+        let Some(input) = read_input() else {
+            println!();
+            return 0;
+        };
+        if let Some(index) = parse_selection(&input, options.len()) {
+            return index;
+        }
+        println!(
+            "{}",
+            Message::AnswerHint.format(&[&format!("1-{}", options.len())]).yellow()
+        );
+    }
+}
+
+/// Parse a one-based option number, returning the zero-based index.
+///
+/// Empty input chooses the first option.
+#[must_use]
+pub fn parse_selection(input: &str, count: usize) -> Option<usize> {
+    match input.trim() {
+        "" => Some(0),
+        number => number
+            .parse::<usize>()
+            .ok()
+            .filter(|number| (1..=count).contains(number))
+            .map(|number| number - 1),
+    }
+}
+
+/// Parse answer to a confirmation prompt in the JSON prompt protocol.
+///
+/// Returns an error message for invalid JSON or an answer that is not allowed.
+pub fn parse_json_answer(input: &str, extra: &[Answer]) -> Result<Answer, String> {
+    let response: PromptResponse =
+        serde_json::from_str(input.trim()).map_err(|error| format!("Invalid response: {error}"))?;
+    let answer = response
+        .answer
+        .ok_or_else(|| "Response is missing answer".to_string())?;
+    if allowed(answer, extra) {
+        Ok(answer)
+    } else {
+        Err(format!("Answer is not allowed here: {answer:?}"))
+    }
+}
+
+fn ask_json(extra: &[Answer], default_yes: bool, context: Option<&PromptContext>) -> Answer {
+    let mut options = vec![Answer::Yes, Answer::No];
+    options.extend_from_slice(extra);
+    let default = if default_yes { Answer::Yes } else { Answer::No };
+    loop {
+        let request = PromptRequest::Confirm {
+            options: options.clone(),
+            default,
+            context,
+        };
+        // A closed output is handled like closed input so nothing gets applied by accident
+        let Some(input) = write_request(&request).ok().and_then(|()| read_input()) else {
+            return Answer::No;
+        };
+        if input.trim().is_empty() {
+            return default;
+        }
+        match parse_json_answer(&input, extra) {
+            Ok(answer) => return answer,
+            Err(message) => {
+                if write_request(&PromptRequest::Error { message }).is_err() {
+                    return Answer::No;
+                }
+            }
+        }
+    }
+}
+
+/// Answer for a text prompt when stdin is not a terminal.
+///
+/// Returns `None` when the prompt can be shown.
+/// Without an answer policy the first prompt prints an error and the answer is to quit.
+fn non_interactive_answer() -> Option<Answer> {
+    if std::io::stdin().is_terminal() {
+        return None;
+    }
+    match NON_INTERACTIVE.load(Ordering::Relaxed) {
+        1 => Some(Answer::Yes),
+        2 => Some(Answer::No),
+        _ => {
+            if !NON_INTERACTIVE_FAILED.swap(true, Ordering::Relaxed) {
+                eprintln!("{}", Message::NotInteractive.text().red());
+            }
+            Some(Answer::Quit)
+        }
+    }
+}
+
+fn allowed(answer: Answer, extra: &[Answer]) -> bool {
+    matches!(answer, Answer::Yes | Answer::No) || extra.contains(&answer)
+}
+
+/// Write request as one JSON line to the original stdout.
+///
+/// Fails when the frontend has closed the output.
+fn write_request(request: &PromptRequest) -> std::io::Result<()> {
+    let json = serde_json::to_string(request)?;
+    // Output printed before the prompt should be visible before answering
+    let _ = std::io::stdout().flush();
+    if let Some(output) = JSON_OUTPUT.get() {
+        let mut output = output.lock().unwrap_or_else(PoisonError::into_inner);
+        writeln!(output, "{json}")?;
+        output.flush()
+    } else {
+        let mut stdout = std::io::stdout().lock();
+        writeln!(stdout, "{json}")?;
+        stdout.flush()
+    }
+}
+
+/// Read one line from stdin.
+/// Returns `None` when stdin is closed.
+fn read_input() -> Option<String> {
+    let mut input = String::new();
+    match std::io::stdin().read_line(&mut input) {
+        Ok(0) | Err(_) => None,
+        Ok(_) => Some(input),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_change_severity() {
+        let change = |current: &str, proposed: &str| FieldChange {
+            field: "title".to_string(),
+            current: current.to_string(),
+            proposed: proposed.to_string(),
+        };
+        assert_eq!(
+            change("Song  Name ", "Song Name").severity(),
+            ChangeSeverity::Whitespace
+        );
+        assert_eq!(change("song name", "Song Name").severity(), ChangeSeverity::Case);
+        assert_eq!(
+            change("Song Name", "Song Name (Remix)").severity(),
+            ChangeSeverity::Content
+        );
+
+        let changes = [change("song", "Song"), change("A  B", "A B")];
+        assert!(is_minor_change(&changes, ChangeSeverity::Case));
+        assert!(!is_minor_change(&changes, ChangeSeverity::Whitespace));
+        assert!(!is_minor_change(&[], ChangeSeverity::Case));
+    }
+
+    #[test]
+    fn test_change_pattern() {
+        let change = |current: &str, proposed: &str| FieldChange {
+            field: "name".to_string(),
+            current: current.to_string(),
+            proposed: proposed.to_string(),
+        };
+        let first = ChangePattern::from_changes(&[change("Artist - Song DJcity", "Artist - Song")]);
+        let second = ChangePattern::from_changes(&[change("Other - Another Title DJcity", "Other - Another Title")]);
+        assert_eq!(first, second);
+        assert!(!first.is_empty());
+        assert_ne!(
+            first,
+            ChangePattern::from_changes(&[change("Artist - Song DJcity", "Artist - Song (Clean)")])
+        );
+        assert!(ChangePattern::from_changes(&[change("Artist - Song", "Artist - Song")]).is_empty());
+    }
+
+    #[test]
+    fn test_parse_answer() {
+        let extra = [Answer::Edit, Answer::All, Answer::Quit];
+        assert_eq!(parse_answer("y\n", false, &[]), Some(Answer::Yes));
+        assert_eq!(parse_answer(" YES ", false, &[]), Some(Answer::Yes));
+        assert_eq!(parse_answer("no", true, &[]), Some(Answer::No));
+        assert_eq!(parse_answer("\n", false, &[]), Some(Answer::No));
+        assert_eq!(parse_answer("", true, &[]), Some(Answer::Yes));
+        assert_eq!(parse_answer("e", false, &extra), Some(Answer::Edit));
+        assert_eq!(parse_answer("A", false, &extra), Some(Answer::All));
+        assert_eq!(parse_answer("quit", false, &extra), Some(Answer::Quit));
+        assert_eq!(parse_answer("e", false, &[]), None);
+        assert_eq!(parse_answer("q", false, &[Answer::Edit]), None);
+        assert_eq!(parse_answer("yep", true, &extra), None);
+    }
+
+    #[test]
+    fn test_parse_selection() {
+        assert_eq!(parse_selection("\n", 3), Some(0));
+        assert_eq!(parse_selection(" 2 ", 3), Some(1));
+        assert_eq!(parse_selection("3", 3), Some(2));
+        assert_eq!(parse_selection("0", 3), None);
+        assert_eq!(parse_selection("4", 3), None);
+        assert_eq!(parse_selection("two", 3), None);
+    }
+
+    #[test]
+    fn test_json_protocol() {
+        let context = PromptContext {
+            path: PathBuf::from("/music/Artist - Song.mp3"),
+            action: "rename".to_string(),
+            changes: vec![FieldChange {
+                field: "filename".to_string(),
+                current: "Artist - Song.mp3".to_string(),
+                proposed: "Artist - Song (Original Mix).mp3".to_string(),
+            }],
+        };
+        let request = PromptRequest::Confirm {
+            options: vec![Answer::Yes, Answer::No, Answer::Quit],
+            default: Answer::No,
+            context: Some(&context),
+        };
+        let json = serde_json::to_string(&request).expect("Failed to serialize request");
+        assert!(json.starts_with(r#"{"type":"confirm","options":["yes","no","quit"],"default":"no""#));
+        assert!(json.contains(r#""proposed":"Artist - Song (Original Mix).mp3""#));
+
+        assert_eq!(parse_json_answer(r#"{"answer": "yes"}"#, &[]), Ok(Answer::Yes));
+        assert_eq!(
+            parse_json_answer(r#"{"answer":"quit"}"#, &[Answer::Quit]),
+            Ok(Answer::Quit)
+        );
+        assert!(parse_json_answer(r#"{"answer":"edit"}"#, &[Answer::Quit]).is_err());
+        assert!(parse_json_answer(r#"{"value":"x"}"#, &[]).is_err());
+        assert!(parse_json_answer("y", &[]).is_err());
+    }
+}
@@ -0,0 +1,108 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use colored::Colorize;
+
+use crate::RelocateArgs;
+
+use track_rename::prompt;
+use track_rename::serato::crates::{self, SeratoCrate};
+use track_rename::serato::database::{SeratoDatabase, DATABASE_FILE_NAME};
+use track_rename::state::RenameHistory;
+use track_rename::utils;
+
+/// Point Serato database and crate entries for renamed files to the new paths.
+pub fn run(args: &RelocateArgs) -> Result<()> {
+    let serato_dir = match &args.serato_dir {
+        Some(dir) => dir.clone(),
+        None => crates::default_serato_dir().context("Failed to get Serato library directory")?,
+    };
+
+    let history = RenameHistory::load();
+    if history.is_empty() {
+        println!("{}", "No rename history found".yellow());
+        return Ok(());
+    }
+
+    // Only relocate entries where the old file is gone and the new file exists
+    let renamed: HashMap<PathBuf, PathBuf> = history
+        .resolved()
+        .into_iter()
+        .filter(|(old, new)| !old.exists() && new.exists())
+        .collect();
+    if args.verbose {
+        println!(
+            "Rename history: {} entries, {} relocatable",
+            history.len(),
+            renamed.len()
+        );
+    }
+
+    let database_path = serato_dir.join(DATABASE_FILE_NAME);
+    let mut database = SeratoDatabase::read(&database_path)?;
+    let relocated = database.relocate_tracks(&renamed, &database_path);
+    if !relocated.is_empty() {
+        println!("{}", format!("Database ({}):", relocated.len()).cyan().bold());
+        for (old, new) in &relocated {
+            if args.verbose {
+                utils::print_stacked_diff(&utils::path_to_string(old), &utils::path_to_string(new));
+            } else {
+                println!("  {}", new.display());
+            }
+        }
+    }
+
+    let mut updated_crates = Vec::new();
+    for crate_path in crates::crate_files(&serato_dir).unwrap_or_default() {
+        let mut serato_crate = match SeratoCrate::read(&crate_path) {
+            Ok(serato_crate) => serato_crate,
+            Err(error) => {
+                utils::print_error(&format!("{error:#}"));
+                continue;
+            }
+        };
+        let updated = serato_crate.rename_tracks(&renamed, &crate_path);
+        if updated > 0 {
+            println!("Crate {}: {updated} entries", crates::crate_name(&crate_path).cyan());
+            updated_crates.push((crate_path, serato_crate));
+        }
+    }
+
+    if relocated.is_empty() && updated_crates.is_empty() {
+        println!("{}", "Nothing to relocate".green());
+        return Ok(());
+    }
+    if args.print {
+        return Ok(());
+    }
+
+    println!(
+        "{}",
+        "Serato should be closed before modifying its library files".yellow()
+    );
+    if !(args.force || prompt::confirm()) {
+        return Ok(());
+    }
+
+    if !relocated.is_empty() {
+        let backup_path = serato_dir.join(format!("{DATABASE_FILE_NAME}.backup"));
+        fs::copy(&database_path, &backup_path).context("Failed to back up Serato database")?;
+        database.write(&database_path)?;
+        println!("Backed up original database to: {}", backup_path.display());
+    }
+    for (crate_path, serato_crate) in &updated_crates {
+        serato_crate.write(crate_path)?;
+    }
+    println!(
+        "{}",
+        format!(
+            "Relocated {} database entries and {} crates",
+            relocated.len(),
+            updated_crates.len()
+        )
+        .green()
+    );
+    Ok(())
+}
@@ -0,0 +1,112 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Result, TrackRenameError};
+use crate::state;
+
+/// Append-only JSON lines log of renames in the order they were applied.
+///
+/// Each entry is written and flushed right after the rename,
+/// so the journal is complete even if the run is interrupted before the rename history is saved.
+#[derive(Debug)]
+pub struct RenameJournal {
+    path: PathBuf,
+    file: File,
+}
+
+/// One rename in the journal.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JournalEntry {
+    pub old: PathBuf,
+    pub new: PathBuf,
+    /// Seconds since unix epoch
+    pub timestamp: u64,
+    /// Rename was only printed and not applied
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub dry_run: bool,
+}
+
+impl RenameJournal {
+    /// Open the journal for appending, creating the file if needed.
+    pub fn open(path: &Path) -> Result<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|e| TrackRenameError::io(format!("Failed to open rename journal: {}", path.display()), e))?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            file,
+        })
+    }
+
+    /// Append a rename and flush it to disk.
+    pub fn record(&mut self, old: &Path, new: &Path, dry_run: bool) -> Result<()> {
+        let entry = JournalEntry {
+            old: old.to_path_buf(),
+            new: new.to_path_buf(),
+            timestamp: state::current_timestamp(),
+            dry_run,
+        };
+        let line = serde_json::to_string(&entry).map_err(|error| {
+            TrackRenameError::InvalidArgument(format!("Failed to serialize journal entry: {error}"))
+        })?;
+        let write_error =
+            |e| TrackRenameError::io(format!("Failed to write rename journal: {}", self.path.display()), e);
+        writeln!(self.file, "{line}").map_err(write_error)?;
+        self.file.sync_data().map_err(write_error)
+    }
+}
+
+/// Read all entries from a journal file.
+pub fn read(path: &Path) -> Result<Vec<JournalEntry>> {
+    let contents = fs::read_to_string(path)
+        .map_err(|e| TrackRenameError::io(format!("Failed to read rename journal: {}", path.display()), e))?;
+    contents
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(index, line)| {
+            serde_json::from_str(line).map_err(|error| {
+                TrackRenameError::InvalidArgument(format!(
+                    "Invalid rename journal entry on line {}: {error}",
+                    index + 1
+                ))
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_journal_round_trip() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let path = dir.path().join("renames.jsonl");
+        let mut journal = RenameJournal::open(&path).unwrap();
+        journal.record(Path::new("a.mp3"), Path::new("b.mp3"), false).unwrap();
+        journal.record(Path::new("c.mp3"), Path::new("d.mp3"), true).unwrap();
+        drop(journal);
+        let mut journal = RenameJournal::open(&path).unwrap();
+        journal.record(Path::new("b.mp3"), Path::new("e.mp3"), false).unwrap();
+
+        let entries = read(&path).unwrap();
+        let renames: Vec<(&str, &str, bool)> = entries
+            .iter()
+            .map(|entry| (entry.old.to_str().unwrap(), entry.new.to_str().unwrap(), entry.dry_run))
+            .collect();
+        assert_eq!(
+            renames,
+            [
+                ("a.mp3", "b.mp3", false),
+                ("c.mp3", "d.mp3", true),
+                ("b.mp3", "e.mp3", false)
+            ]
+        );
+    }
+}
@@ -0,0 +1,314 @@
+use std::fs;
+use std::io::Cursor;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use id3::{Error, ErrorKind, Tag};
+
+use crate::error::{Result, TrackRenameError};
+use crate::mpeg::{find_frame_sync, id3_tag_size, ID3_HEADER_SIZE};
+use crate::utils;
+
+/// Result of a successful repair attempt.
+///
+/// The cleaned file has been written next to the original file
+/// and needs to be either applied with [`RepairedFile::apply`] or removed with [`RepairedFile::discard`].
+#[derive(Debug)]
+pub struct RepairedFile {
+    /// Path to the original file
+    pub original: PathBuf,
+    /// Path to the cleaned temporary copy
+    pub path: PathBuf,
+    /// Tag data read from the cleaned copy
+    pub tag: Tag,
+    /// Number of bytes removed from the original file
+    pub removed_bytes: usize,
+    /// True if all existing tag data had to be dropped
+    pub tag_removed: bool,
+}
+
+/// Candidate layout for a cleaned file:
+/// an optional ID3 tag byte range followed by the audio data starting from the given offset.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Candidate {
+    tag: Option<Range<usize>>,
+    audio_start: usize,
+}
+
+impl Candidate {
+    /// True if this layout is identical to the original file.
+    fn is_original(&self) -> bool {
+        self.tag.as_ref().map_or(self.audio_start == 0, |tag| {
+            tag.start == 0 && tag.end == self.audio_start
+        })
+    }
+}
+
+impl RepairedFile {
+    /// Replace the original file with the repaired copy.
+    ///
+    /// The copy gets the permissions of the original file first.
+    pub fn apply(&self) -> Result<()> {
+        let permissions = fs::metadata(&self.original)
+            .map_err(|error| {
+                TrackRenameError::io(format!("Failed to read metadata: {}", self.original.display()), error)
+            })?
+            .permissions();
+        fs::set_permissions(&self.path, permissions).map_err(|error| {
+            TrackRenameError::io(format!("Failed to set permissions: {}", self.path.display()), error)
+        })?;
+        fs::rename(&self.path, &self.original).map_err(|source| TrackRenameError::Rename {
+            from: self.path.clone(),
+            to: self.original.clone(),
+            source,
+        })
+    }
+
+    /// Remove the repaired copy and leave the original file untouched.
+    pub fn discard(&self) -> Result<()> {
+        fs::remove_file(&self.path).map_err(|error| {
+            TrackRenameError::io(
+                format!("Failed to remove repaired file: {}", self.path.display()),
+                error,
+            )
+        })
+    }
+}
+
+/// Try to repair an MP3 file whose tags could not be read.
+///
+/// Scans for the first valid ID3 header or MPEG frame sync,
+/// skipping garbage bytes before the tag and broken duplicate tags.
+/// Each candidate layout is tested in memory,
+/// and the first one that parses is written to a temporary copy next to the original file.
+/// The tags are then read again from the copy to verify the result.
+///
+/// Returns `None` if no candidate produced readable tags.
+pub fn repair_mp3(path: &Path) -> Result<Option<RepairedFile>> {
+    let data = fs::read(path)
+        .map_err(|error| TrackRenameError::io(format!("Failed to read file: {}", path.display()), error))?;
+
+    let Some(candidate) = find_candidates(&data)
+        .into_iter()
+        .find(|candidate| read_candidate(&data, candidate).is_some())
+    else {
+        return Ok(None);
+    };
+
+    let cleaned = build_cleaned(&data, &candidate);
+    let temp_path = temp_repair_path(path)?;
+    fs::write(&temp_path, &cleaned).map_err(|error| {
+        TrackRenameError::io(format!("Failed to write repaired file: {}", temp_path.display()), error)
+    })?;
+
+    let tag = match Tag::read_from_path(&temp_path) {
+        Ok(tag) => tag,
+        Err(Error {
+            kind: ErrorKind::NoTag, ..
+        }) => Tag::new(),
+        Err(_) => {
+            fs::remove_file(&temp_path).map_err(|error| {
+                TrackRenameError::io(
+                    format!("Failed to remove repaired file: {}", temp_path.display()),
+                    error,
+                )
+            })?;
+            return Ok(None);
+        }
+    };
+
+    Ok(Some(RepairedFile {
+        original: path.to_path_buf(),
+        path: temp_path,
+        tag,
+        removed_bytes: data.len().saturating_sub(cleaned.len()),
+        tag_removed: candidate.tag.is_none(),
+    }))
+}
+
+/// Find all possible cleaned layouts in order of preference.
+fn find_candidates(data: &[u8]) -> Vec<Candidate> {
+    let mut candidates = Vec::new();
+    let mut audio_start = find_frame_sync(data, 0);
+
+    if let Some(first_tag) = find_id3_header(data) {
+        // Collect all consecutive tags, which happens when several tools have each prepended their own tag
+        let mut offset = first_tag;
+        while let Some(size) = id3_tag_size(&data[offset..]) {
+            let end = offset + ID3_HEADER_SIZE + size;
+            if end > data.len() {
+                break;
+            }
+            candidates.push(Candidate {
+                tag: Some(offset..end),
+                audio_start: 0,
+            });
+            offset = end;
+        }
+        audio_start = find_frame_sync(data, offset).or(audio_start);
+    }
+
+    let Some(audio_start) = audio_start else {
+        return Vec::new();
+    };
+    for candidate in &mut candidates {
+        candidate.audio_start = audio_start;
+    }
+
+    // Fallback: drop all tag data and keep only the audio
+    candidates.push(Candidate { tag: None, audio_start });
+
+    // The original layout is what already failed
+    candidates.retain(|candidate| !candidate.is_original());
+    candidates
+}
+
+/// Try to parse tags from the cleaned layout without writing anything to disk.
+fn read_candidate(data: &[u8], candidate: &Candidate) -> Option<Tag> {
+    candidate.tag.as_ref().map_or_else(
+        || Some(Tag::new()),
+        |tag| Tag::read_from2(Cursor::new(&data[tag.clone()])).ok(),
+    )
+}
+
+fn build_cleaned(data: &[u8], candidate: &Candidate) -> Vec<u8> {
+    let tag_bytes = candidate.tag.clone().map_or(&[][..], |tag| &data[tag]);
+    let mut cleaned = Vec::with_capacity(tag_bytes.len() + data.len() - candidate.audio_start);
+    cleaned.extend_from_slice(tag_bytes);
+    cleaned.extend_from_slice(&data[candidate.audio_start..]);
+    cleaned
+}
+
+/// Temporary file path in the same directory so the final rename stays on the same device.
+fn temp_repair_path(path: &Path) -> Result<PathBuf> {
+    let parent = path
+        .parent()
+        .ok_or_else(|| TrackRenameError::invalid_path(path, "Failed to get file root"))?;
+    let name = utils::get_filename_from_path(path)?;
+    Ok(parent.join(format!(".{name}.repair.tmp")))
+}
+
+/// Return the offset of the first valid `ID3v2` header.
+fn find_id3_header(data: &[u8]) -> Option<usize> {
+    data.windows(ID3_HEADER_SIZE)
+        .position(|window| id3_tag_size(window).is_some())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use id3::{TagLike, Version};
+
+    const FRAME_HEADER: [u8; 4] = [0xFF, 0xFB, 0x90, 0x64];
+
+    fn tag_bytes(title: &str) -> Vec<u8> {
+        let mut tag = Tag::new();
+        tag.set_title(title);
+        let mut bytes = Vec::new();
+        tag.write_to(&mut bytes, Version::Id3v24).unwrap();
+        bytes
+    }
+
+    fn audio_bytes() -> Vec<u8> {
+        let mut audio = FRAME_HEADER.to_vec();
+        audio.extend_from_slice(&[0u8; 64]);
+        audio
+    }
+
+    #[test]
+    fn test_id3_tag_size() {
+        let bytes = tag_bytes("Title");
+        assert_eq!(id3_tag_size(&bytes), Some(bytes.len() - ID3_HEADER_SIZE));
+        assert_eq!(id3_tag_size(b"ID3\x05\x00\x00\x00\x00\x00\x00"), None);
+        assert_eq!(id3_tag_size(b"ID3\x04\x00\x00\x80\x00\x00\x00"), None);
+        assert_eq!(id3_tag_size(b"XYZ"), None);
+    }
+
+    #[test]
+    fn test_garbage_before_tag() {
+        let mut data = b"garbage".to_vec();
+        let tag = tag_bytes("Title");
+        data.extend_from_slice(&tag);
+        data.extend_from_slice(&audio_bytes());
+
+        let candidates = find_candidates(&data);
+        let first = candidates.first().expect("Should find a candidate");
+        assert_eq!(first.tag, Some(7..7 + tag.len()));
+        assert_eq!(first.audio_start, 7 + tag.len());
+
+        let parsed = read_candidate(&data, first).expect("Tag should parse");
+        assert_eq!(parsed.title(), Some("Title"));
+
+        let cleaned = build_cleaned(&data, first);
+        assert_eq!(cleaned.len(), data.len() - 7);
+    }
+
+    #[test]
+    fn test_duplicate_tags() {
+        let mut broken = tag_bytes("Broken");
+        // Corrupt the first frame ID so the tag fails to parse
+        broken[ID3_HEADER_SIZE] = 0x01;
+        let valid = tag_bytes("Valid");
+
+        let mut data = broken.clone();
+        data.extend_from_slice(&valid);
+        data.extend_from_slice(&audio_bytes());
+
+        let candidates = find_candidates(&data);
+        let candidate = candidates
+            .iter()
+            .find(|candidate| read_candidate(&data, candidate).is_some_and(|tag| tag.title().is_some()))
+            .expect("Should find a readable candidate");
+
+        assert_eq!(candidate.tag, Some(broken.len()..broken.len() + valid.len()));
+    }
+
+    #[test]
+    fn test_no_tag_falls_back_to_audio() {
+        let mut data = b"junk".to_vec();
+        data.extend_from_slice(&audio_bytes());
+
+        let candidates = find_candidates(&data);
+        assert_eq!(
+            candidates,
+            vec![Candidate {
+                tag: None,
+                audio_start: 4
+            }]
+        );
+    }
+
+    #[test]
+    fn test_clean_file_has_no_candidates() {
+        let mut data = tag_bytes("Title");
+        data.extend_from_slice(&audio_bytes());
+        assert!(find_candidates(&data).iter().all(|candidate| candidate.tag.is_none()));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_apply_keeps_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let original = dir.path().join("track.mp3");
+        let path = dir.path().join(".track.mp3.repair.tmp");
+        fs::write(&original, b"original").expect("Failed to write file");
+        fs::write(&path, b"repaired").expect("Failed to write file");
+        fs::set_permissions(&original, fs::Permissions::from_mode(0o640)).expect("Failed to set permissions");
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o600)).expect("Failed to set permissions");
+
+        let repaired = RepairedFile {
+            original: original.clone(),
+            path,
+            tag: Tag::new(),
+            removed_bytes: 0,
+            tag_removed: false,
+        };
+        repaired.apply().expect("Failed to apply repair");
+        let metadata = fs::metadata(&original).expect("Failed to read metadata");
+        assert_eq!(metadata.permissions().mode() & 0o777, 0o640);
+        assert_eq!(fs::read(&original).expect("Failed to read file"), b"repaired");
+    }
+}
@@ -0,0 +1,34 @@
+use std::path::Path;
+
+use anyhow::{bail, Result};
+use colored::Colorize;
+
+use track_rename::corpus;
+use track_rename::utils;
+
+/// Format the original artist and title of each corpus entry again
+/// and report entries where the current rules give a different result.
+pub fn run(path: &Path) -> Result<()> {
+    let entries = corpus::read(path)?;
+    let mut mismatches = 0;
+    for entry in &entries {
+        let Some(current) = entry.replay() else {
+            continue;
+        };
+        mismatches += 1;
+        println!("{}", format!("{} - {}", entry.artist, entry.title).bold());
+        if current.formatted_artist != entry.formatted_artist {
+            print!("  artist: ");
+            utils::print_diff(&entry.formatted_artist, &current.formatted_artist);
+        }
+        if current.formatted_title != entry.formatted_title {
+            print!("  title:  ");
+            utils::print_diff(&entry.formatted_title, &current.formatted_title);
+        }
+    }
+    if mismatches > 0 {
+        bail!("{mismatches} of {} corpus entries changed", entries.len());
+    }
+    println!("{}", format!("All {} corpus entries match", entries.len()).green());
+    Ok(())
+}
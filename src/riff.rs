@@ -0,0 +1,388 @@
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use id3::{Tag, TagLike};
+
+use crate::error::{Result, TrackRenameError};
+use crate::utils;
+
+/// RIFF INFO entries for the tags used in formatting.
+const INFO_ARTIST: &[u8; 4] = b"IART";
+const INFO_TITLE: &[u8; 4] = b"INAM";
+const INFO_ALBUM: &[u8; 4] = b"IPRD";
+const INFO_GENRE: &[u8; 4] = b"IGNR";
+
+/// INFO list entry ID and value.
+pub type InfoEntry = ([u8; 4], String);
+
+/// Chunk in a RIFF file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Chunk {
+    pub id: [u8; 4],
+    /// Offset of the chunk header from the start of the file
+    pub offset: u64,
+    /// Data size without the header and padding
+    pub size: u32,
+}
+
+impl Chunk {
+    /// Offset of the chunk data.
+    #[must_use]
+    pub const fn data_offset(&self) -> u64 {
+        self.offset + 8
+    }
+
+    /// Offset right after the chunk including the padding byte.
+    #[must_use]
+    pub const fn end(&self) -> u64 {
+        self.data_offset() + self.size as u64 + (self.size % 2) as u64
+    }
+}
+
+/// Read the top-level chunk headers from a RIFF WAVE file.
+pub fn read_chunks(file: &mut File) -> io::Result<Vec<Chunk>> {
+    let len = file.metadata()?.len();
+    let mut header = [0_u8; 12];
+    file.seek(SeekFrom::Start(0))?;
+    file.read_exact(&mut header)?;
+    if &header[..4] != b"RIFF" || &header[8..12] != b"WAVE" {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "Not a RIFF WAVE file"));
+    }
+    let mut chunks = Vec::new();
+    let mut offset = 12;
+    while offset + 8 <= len {
+        let mut chunk_header = [0_u8; 8];
+        file.seek(SeekFrom::Start(offset))?;
+        file.read_exact(&mut chunk_header)?;
+        let chunk = Chunk {
+            id: [chunk_header[0], chunk_header[1], chunk_header[2], chunk_header[3]],
+            offset,
+            size: u32::from_le_bytes([chunk_header[4], chunk_header[5], chunk_header[6], chunk_header[7]]),
+        };
+        chunks.push(chunk);
+        offset = chunk.end();
+    }
+    Ok(chunks)
+}
+
+/// Read chunk data.
+///
+/// The size comes from the file, so the buffer only grows as data is actually read.
+pub fn read_chunk_data(file: &mut File, chunk: &Chunk) -> io::Result<Vec<u8>> {
+    let mut data = Vec::new();
+    file.seek(SeekFrom::Start(chunk.data_offset()))?;
+    file.take(u64::from(chunk.size)).read_to_end(&mut data)?;
+    if data.len() < chunk.size as usize {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    Ok(data)
+}
+
+/// Read the entries from the `LIST` chunk with the `INFO` type.
+///
+/// Returns `None` if the file does not have an INFO list.
+pub fn read_info(path: &Path) -> io::Result<Option<Vec<InfoEntry>>> {
+    let mut file = File::open(path)?;
+    let chunks = read_chunks(&mut file)?;
+    let Some(chunk) = find_info_chunk(&mut file, &chunks)? else {
+        return Ok(None);
+    };
+    Ok(Some(parse_info(&read_chunk_data(&mut file, &chunk)?)))
+}
+
+/// Create a tag from the artist, title, album, and genre in the RIFF INFO list.
+///
+/// Used for WAV files that do not have an ID3 chunk.
+#[must_use]
+pub fn info_tag(path: &Path) -> Option<Tag> {
+    let entries = read_info(path).ok()??;
+    let mut tag = Tag::new();
+    for (id, value) in entries {
+        match &id {
+            INFO_ARTIST => tag.set_artist(value),
+            INFO_TITLE => tag.set_title(value),
+            INFO_ALBUM => tag.set_album(value),
+            INFO_GENRE => tag.set_genre(value),
+            _ => {}
+        }
+    }
+    Some(tag)
+}
+
+/// Update the artist, title, album, and genre in an existing RIFF INFO list.
+///
+/// Other INFO entries are kept.
+/// Files without an INFO list are not modified since the tags are stored in the ID3 chunk.
+/// The list is updated in place if it fits in the old space or is the last chunk,
+/// and otherwise a copy of the file with the list moved to the end replaces the original,
+/// so the audio data is never truncated.
+/// Returns true if the INFO list was updated.
+pub fn write_info(path: &Path, tag: &Tag) -> Result<bool> {
+    let io_error = |error: io::Error| TrackRenameError::io("Failed to update RIFF INFO list", error);
+    let mut file = OpenOptions::new().read(true).write(true).open(path).map_err(io_error)?;
+    let Some((chunk, data)) = updated_info(&mut file, tag).map_err(io_error)? else {
+        return Ok(false);
+    };
+    let old_size = chunk.end() - chunk.offset;
+    let new_size = list_size(&data);
+    let is_last = chunk.end() >= file.metadata().map_err(io_error)?.len();
+    // A smaller list is followed by a JUNK chunk, which needs room for its header
+    if is_last || new_size == old_size || new_size + 8 <= old_size {
+        write_in_place(&mut file, &chunk, &data, is_last).map_err(io_error)?;
+    } else {
+        drop(file);
+        utils::replace_atomically(path, |temp_path| {
+            move_to_end(temp_path, &chunk, &data).map_err(io_error)
+        })?;
+    }
+    Ok(true)
+}
+
+/// Find the INFO list and encode it with the tag values.
+///
+/// Returns `None` if there is no INFO list or it is already up to date.
+fn updated_info(file: &mut File, tag: &Tag) -> io::Result<Option<(Chunk, Vec<u8>)>> {
+    let chunks = read_chunks(file)?;
+    let Some(chunk) = find_info_chunk(file, &chunks)? else {
+        return Ok(None);
+    };
+    let mut entries = parse_info(&read_chunk_data(file, &chunk)?);
+    for (id, value) in [
+        (INFO_ARTIST, tag.artist()),
+        (INFO_TITLE, tag.title()),
+        (INFO_ALBUM, tag.album()),
+        (INFO_GENRE, tag.genre_parsed().as_deref()),
+    ] {
+        let position = entries.iter().position(|(entry, _)| entry == id);
+        match (position, value.filter(|value| !value.is_empty())) {
+            (Some(index), Some(value)) => entries[index].1 = value.to_string(),
+            (Some(index), None) => {
+                entries.remove(index);
+            }
+            (None, Some(value)) => entries.push((*id, value.to_string())),
+            (None, None) => {}
+        }
+    }
+    let data = encode_info(&entries);
+    if data == read_chunk_data(file, &chunk)? {
+        return Ok(None);
+    }
+    Ok(Some((chunk, data)))
+}
+
+/// Size of a LIST chunk with the given data, including the header and padding.
+const fn list_size(data: &[u8]) -> u64 {
+    8 + data.len() as u64 + (data.len() % 2) as u64
+}
+
+/// Write the list over the old one, filling the rest of the old space with a JUNK chunk.
+///
+/// The last chunk in the file can also grow or shrink.
+fn write_in_place(file: &mut File, chunk: &Chunk, data: &[u8], is_last: bool) -> io::Result<()> {
+    file.seek(SeekFrom::Start(chunk.offset))?;
+    write_list(file, data)?;
+    if is_last {
+        let len = file.stream_position()?;
+        file.set_len(len)?;
+        update_riff_size(file, len)?;
+    } else {
+        let remaining = chunk.end() - chunk.offset - list_size(data);
+        if remaining > 0 {
+            file.write_all(b"JUNK")?;
+            file.write_all(&u32::try_from(remaining - 8).map_err(io::Error::other)?.to_le_bytes())?;
+            file.write_all(&vec![0; usize::try_from(remaining - 8).map_err(io::Error::other)?])?;
+        }
+    }
+    file.flush()
+}
+
+/// Move the chunks after the INFO list forward and write the new list at the end.
+fn move_to_end(path: &Path, chunk: &Chunk, data: &[u8]) -> io::Result<()> {
+    let mut file = OpenOptions::new().read(true).write(true).open(path)?;
+    let mut trailing = Vec::new();
+    file.seek(SeekFrom::Start(chunk.end()))?;
+    file.read_to_end(&mut trailing)?;
+    file.set_len(chunk.offset)?;
+    file.seek(SeekFrom::Start(chunk.offset))?;
+    file.write_all(&trailing)?;
+    write_list(&mut file, data)?;
+    let len = file.stream_position()?;
+    update_riff_size(&mut file, len)?;
+    file.flush()
+}
+
+fn write_list(file: &mut File, data: &[u8]) -> io::Result<()> {
+    file.write_all(b"LIST")?;
+    file.write_all(&u32::try_from(data.len()).map_err(io::Error::other)?.to_le_bytes())?;
+    file.write_all(data)?;
+    if data.len() % 2 == 1 {
+        file.write_all(&[0])?;
+    }
+    Ok(())
+}
+
+fn update_riff_size(file: &mut File, len: u64) -> io::Result<()> {
+    let riff_size = u32::try_from(len - 8).map_err(io::Error::other)?;
+    file.seek(SeekFrom::Start(4))?;
+    file.write_all(&riff_size.to_le_bytes())
+}
+
+fn find_info_chunk(file: &mut File, chunks: &[Chunk]) -> io::Result<Option<Chunk>> {
+    for chunk in chunks.iter().filter(|chunk| &chunk.id == b"LIST" && chunk.size >= 4) {
+        let mut list_type = [0_u8; 4];
+        file.seek(SeekFrom::Start(chunk.data_offset()))?;
+        file.read_exact(&mut list_type)?;
+        if &list_type == b"INFO" {
+            return Ok(Some(*chunk));
+        }
+    }
+    Ok(None)
+}
+
+/// Parse the INFO list data starting with the list type.
+fn parse_info(data: &[u8]) -> Vec<InfoEntry> {
+    let mut entries = Vec::new();
+    let mut offset = 4;
+    while offset + 8 <= data.len() {
+        let id = [data[offset], data[offset + 1], data[offset + 2], data[offset + 3]];
+        let size =
+            u32::from_le_bytes([data[offset + 4], data[offset + 5], data[offset + 6], data[offset + 7]]) as usize;
+        let start = offset + 8;
+        let Some(value) = data.get(start..start + size) else {
+            break;
+        };
+        // Values are null terminated and usually Latin-1 or UTF-8
+        let value = value.split(|byte| *byte == 0).next().unwrap_or_default();
+        let value =
+            String::from_utf8(value.to_vec()).unwrap_or_else(|_| value.iter().map(|&c| char::from(c)).collect());
+        entries.push((id, value));
+        offset = start + size + size % 2;
+    }
+    entries
+}
+
+/// Encode INFO list data with null terminated UTF-8 values.
+fn encode_info(entries: &[InfoEntry]) -> Vec<u8> {
+    let mut data = b"INFO".to_vec();
+    for (id, value) in entries {
+        let size = value.len() + 1;
+        data.extend_from_slice(id);
+        data.extend_from_slice(&u32::try_from(size).unwrap_or(u32::MAX).to_le_bytes());
+        data.extend_from_slice(value.as_bytes());
+        data.push(0);
+        if size % 2 == 1 {
+            data.push(0);
+        }
+    }
+    data
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wav_with_info(entries: &[InfoEntry]) -> Vec<u8> {
+        let mut fmt = b"fmt ".to_vec();
+        fmt.extend_from_slice(&16_u32.to_le_bytes());
+        fmt.extend_from_slice(&[1, 0, 2, 0]);
+        fmt.extend_from_slice(&44100_u32.to_le_bytes());
+        fmt.extend_from_slice(&(44100_u32 * 4).to_le_bytes());
+        fmt.extend_from_slice(&[4, 0, 16, 0]);
+        let mut data = b"data".to_vec();
+        data.extend_from_slice(&4_u32.to_le_bytes());
+        data.extend_from_slice(&[0; 4]);
+        let info = encode_info(entries);
+        let mut list = b"LIST".to_vec();
+        list.extend_from_slice(&u32::try_from(info.len()).unwrap().to_le_bytes());
+        list.extend_from_slice(&info);
+
+        let mut file = b"RIFF".to_vec();
+        file.extend_from_slice(
+            &u32::try_from(4 + fmt.len() + data.len() + list.len())
+                .unwrap()
+                .to_le_bytes(),
+        );
+        file.extend_from_slice(b"WAVE");
+        file.extend_from_slice(&fmt);
+        file.extend_from_slice(&list);
+        file.extend_from_slice(&data);
+        file
+    }
+
+    #[test]
+    fn test_info_tag() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let path = dir.path().join("track.wav");
+        std::fs::write(
+            &path,
+            wav_with_info(&[
+                (*INFO_ARTIST, "artist".to_string()),
+                (*b"ISFT", "Encoder".to_string()),
+                (*INFO_TITLE, "Song".to_string()),
+            ]),
+        )
+        .expect("Failed to write test file");
+
+        let tag = info_tag(&path).expect("Missing INFO tag");
+        assert_eq!(tag.artist(), Some("artist"));
+        assert_eq!(tag.title(), Some("Song"));
+
+        let mut tag = Tag::new();
+        tag.set_artist("Artist");
+        tag.set_title("Song");
+        tag.set_genre("House");
+        assert!(write_info(&path, &tag).expect("Failed to write INFO"));
+        assert!(!write_info(&path, &tag).expect("Failed to write INFO"));
+
+        let entries = read_info(&path).expect("Failed to read").expect("Missing INFO list");
+        assert_eq!(
+            entries,
+            vec![
+                (*INFO_ARTIST, "Artist".to_string()),
+                (*b"ISFT", "Encoder".to_string()),
+                (*INFO_TITLE, "Song".to_string()),
+                (*INFO_GENRE, "House".to_string()),
+            ]
+        );
+        let mut file = File::open(&path).expect("Failed to open");
+        let chunks = read_chunks(&mut file).expect("Failed to read chunks");
+        let ids: Vec<&[u8; 4]> = chunks.iter().map(|chunk| &chunk.id).collect();
+        assert_eq!(ids, vec![b"fmt ", b"data", b"LIST"]);
+        let riff_size = u64::from(u32::from_le_bytes(
+            std::fs::read(&path).unwrap()[4..8].try_into().unwrap(),
+        ));
+        assert_eq!(riff_size + 8, file.metadata().unwrap().len());
+    }
+
+    #[test]
+    fn test_write_smaller_info_in_place() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let path = dir.path().join("track.wav");
+        std::fs::write(
+            &path,
+            wav_with_info(&[
+                (*INFO_ARTIST, "A Very Long Artist Name".to_string()),
+                (*INFO_TITLE, "Song".to_string()),
+            ]),
+        )
+        .expect("Failed to write test file");
+        let original_len = std::fs::metadata(&path).unwrap().len();
+
+        let mut tag = Tag::new();
+        tag.set_artist("Artist");
+        tag.set_title("Song");
+        assert!(write_info(&path, &tag).expect("Failed to write INFO"));
+
+        let entries = read_info(&path).expect("Failed to read").expect("Missing INFO list");
+        assert_eq!(
+            entries,
+            vec![(*INFO_ARTIST, "Artist".to_string()), (*INFO_TITLE, "Song".to_string())]
+        );
+        let mut file = File::open(&path).expect("Failed to open");
+        let chunks = read_chunks(&mut file).expect("Failed to read chunks");
+        let ids: Vec<&[u8; 4]> = chunks.iter().map(|chunk| &chunk.id).collect();
+        assert_eq!(ids, vec![b"fmt ", b"LIST", b"JUNK", b"data"]);
+        assert_eq!(file.metadata().unwrap().len(), original_len);
+    }
+}
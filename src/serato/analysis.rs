@@ -1,8 +1,7 @@
 use std::fmt;
 use std::fmt::Display;
 
-use anyhow::anyhow;
-use anyhow::Result;
+use crate::error::{Result, TrackRenameError};
 
 #[derive(Debug, Clone, Default)]
 pub struct AnalysisVersion {
@@ -29,9 +28,17 @@ impl AnalysisVersion {
                 minor_version,
             })
         } else {
-            Err(anyhow!("Data is too short to contain version information"))
+            Err(TrackRenameError::InvalidSerato(
+                "Data is too short to contain version information".to_string(),
+            ))
         }
     }
+
+    /// Serialize to the tag data format.
+    #[must_use]
+    pub fn to_bytes(&self) -> Vec<u8> {
+        vec![self.major_version, self.minor_version]
+    }
 }
 
 impl Display for AnalysisVersion {
@@ -1,9 +1,9 @@
 use std::fmt;
 use std::fmt::Display;
 
-use anyhow::anyhow;
+use crate::error::{Result, TrackRenameError};
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, PartialEq)]
 pub struct AutoTags {
     /// Beats per minute
     pub bpm: f32,
@@ -24,58 +24,58 @@ impl AutoTags {
     /// |   `09` |   `07` | `2d 33 2e 32 35 37 00` |      `-3.257` | ASCII (zero-terminated) | Auto Gain
     /// |   `16` |   `06` | `30 2e 30 30 30 00`    |       `0.000` | ASCII (zero-terminated) | Gain dB
     ///
-    pub fn parse(data: &[u8]) -> anyhow::Result<Self> {
+    pub fn parse(data: &[u8]) -> Result<Self> {
         if data.len() < 16 {
-            return Err(anyhow!("Data is too short to contain all necessary information"));
+            return Err(TrackRenameError::InvalidSerato(
+                "Data is too short to contain all necessary information".to_string(),
+            ));
         }
 
-        // Parse BPM
-        let bpm_str: String = std::str::from_utf8(&data[2..9])
-            .map_err(|_| anyhow!("Failed to parse BPM string as UTF-8"))?
-            .trim_end_matches('\x00')
-            .trim()
-            .chars()
-            .filter(|c| c.is_numeric() || *c == '.')
-            .collect();
-
-        let bpm: f32 = bpm_str
-            .parse()
-            .map_err(|e| anyhow!("Failed to parse BPM as f32: {e} {bpm_str:?}"))?;
-
-        // Parse Auto Gain
-        let auto_gain_str: String = std::str::from_utf8(&data[9..16])
-            .map_err(|_| anyhow!("Failed to parse Auto Gain string as UTF-8"))?
-            .replace('\x00', "")
-            .trim()
-            .chars()
-            .filter(|c| c.is_numeric() || *c == '.' || *c == '-')
-            .collect();
-
-        let auto_gain: f32 = auto_gain_str
-            .trim_end_matches('.')
-            .parse()
-            .map_err(|e| anyhow!("Failed to parse Auto Gain as f32: {e} {auto_gain_str:?}"))?;
-
-        // Parse Gain dB (only if data is long enough)
-        let gain: f32 = if data.len() >= 22 {
-            let gain_str: String = std::str::from_utf8(&data[16..22])
-                .map_err(|_| anyhow!("Failed to parse Gain dB string as UTF-8"))?
-                .replace('\x00', "")
-                .trim()
-                .chars()
-                .filter(|c| c.is_numeric() || *c == '.' || *c == '-')
-                .collect();
-
-            gain_str
-                .trim_end_matches('.')
-                .parse()
-                .map_err(|e| anyhow!("Failed to parse Gain dB as f32: {e} {gain_str:?}"))?
-        } else {
-            0.0
+        let mut fields = data[2..].split(|&byte| byte == b'\x00');
+        let bpm = parse_field(fields.next(), "BPM")?;
+        let auto_gain = parse_field(fields.next(), "Auto Gain")?;
+        // Older files might be missing the manual gain
+        let gain = match fields.next() {
+            Some(field) if !field.is_empty() => parse_field(Some(field), "Gain dB")?,
+            _ => 0.0,
         };
 
         Ok(Self { bpm, auto_gain, gain })
     }
+
+    /// Serialize to the tag data format.
+    ///
+    /// Values are written as zero-terminated ASCII strings
+    /// with two decimals for the BPM and three decimals for the gain values like Serato does.
+    #[must_use]
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut data = vec![0x01, 0x01];
+        for value in [
+            format!("{:.2}", self.bpm),
+            format!("{:.3}", self.auto_gain),
+            format!("{:.3}", self.gain),
+        ] {
+            data.extend_from_slice(value.as_bytes());
+            data.push(0x00);
+        }
+        data
+    }
+}
+
+/// Parse a zero-terminated ASCII number.
+fn parse_field(field: Option<&[u8]>, name: &str) -> Result<f32> {
+    let field = field.ok_or_else(|| TrackRenameError::InvalidSerato(format!("Missing {name} value")))?;
+    let value: String = std::str::from_utf8(field)
+        .map_err(|_| TrackRenameError::InvalidSerato(format!("Failed to parse {name} string as UTF-8")))?
+        .trim()
+        .chars()
+        .filter(|c| c.is_numeric() || *c == '.' || *c == '-')
+        .collect();
+
+    value
+        .trim_end_matches('.')
+        .parse()
+        .map_err(|e| TrackRenameError::InvalidSerato(format!("Failed to parse {name} as f32: {e} {value:?}")))
 }
 
 impl Display for AutoTags {
@@ -87,3 +87,28 @@ impl Display for AutoTags {
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_autotags_round_trip() {
+        let data = b"\x01\x01120.00\x002.995\x000.000\x00";
+        let autotags = AutoTags::parse(data).expect("Failed to parse autotags");
+        assert!((autotags.bpm - 120.0).abs() < f32::EPSILON);
+        assert!((autotags.auto_gain - 2.995).abs() < f32::EPSILON);
+        assert!(autotags.gain.abs() < f32::EPSILON);
+        assert_eq!(autotags.to_bytes(), data);
+    }
+
+    #[test]
+    fn test_autotags_negative_gain() {
+        let data = b"\x01\x0195.50\x00-3.257\x00-1.500\x00";
+        let autotags = AutoTags::parse(data).expect("Failed to parse autotags");
+        assert!((autotags.bpm - 95.5).abs() < f32::EPSILON);
+        assert!((autotags.auto_gain + 3.257).abs() < f32::EPSILON);
+        assert!((autotags.gain + 1.5).abs() < f32::EPSILON);
+        assert_eq!(autotags.to_bytes(), data);
+    }
+}
@@ -1,13 +1,14 @@
 use std::fmt;
 use std::fmt::Display;
 
-use anyhow::anyhow;
-use anyhow::Result;
+use crate::error::{Result, TrackRenameError};
 
 #[derive(Debug, Clone, Default)]
 pub struct BeatGrid {
     pub num_markers: u32,
     pub markers: Vec<BeatGridMarker>,
+    /// Single byte after the markers, usually zero
+    footer: u8,
 }
 
 #[derive(Debug, Clone)]
@@ -17,6 +18,16 @@ pub enum BeatGridMarker {
 }
 
 impl BeatGrid {
+    /// New beatgrid with the given markers, where the last one should be a terminal marker.
+    #[must_use]
+    pub const fn new(markers: Vec<BeatGridMarker>) -> Self {
+        Self {
+            num_markers: markers.len() as u32,
+            markers,
+            footer: 0,
+        }
+    }
+
     /// Parse beatgrid tag.
     /// The tag data consists of a header followed by zero or more beatgrid markers and a single footer byte.
     ///
@@ -53,17 +64,24 @@ impl BeatGrid {
     ///
     pub fn parse(data: &[u8]) -> Result<Self> {
         if data.len() < 6 {
-            return Err(anyhow!("Data is too short to contain valid beatgrid information"));
+            return Err(TrackRenameError::InvalidSerato(
+                "Data is too short to contain valid beatgrid information".to_string(),
+            ));
         }
 
         let num_markers_bytes = [data[2], data[3], data[4], data[5]];
         let num_markers = u32::from_be_bytes(num_markers_bytes);
         if num_markers == 0 {
-            return Ok(Self::default());
+            return Ok(Self {
+                footer: data.get(6).copied().unwrap_or_default(),
+                ..Self::default()
+            });
         }
 
         if data.len() < 11 {
-            return Err(anyhow!("Data is too short to contain valid beatgrid information"));
+            return Err(TrackRenameError::InvalidSerato(
+                "Data is too short to contain valid beatgrid information".to_string(),
+            ));
         }
 
         let mut markers = Vec::new();
@@ -71,7 +89,9 @@ impl BeatGrid {
 
         for _ in 0..num_markers {
             if offset + 8 > data.len() {
-                return Err(anyhow!("Data is too short to contain all beatgrid markers"));
+                return Err(TrackRenameError::InvalidSerato(
+                    "Data is too short to contain all beatgrid markers".to_string(),
+                ));
             }
 
             let position_bytes = [data[offset], data[offset + 1], data[offset + 2], data[offset + 3]];
@@ -94,7 +114,89 @@ impl BeatGrid {
             offset += 8;
         }
 
-        Ok(Self { num_markers, markers })
+        Ok(Self {
+            num_markers,
+            markers,
+            footer: data.get(offset).copied().unwrap_or_default(),
+        })
+    }
+
+    /// Get the BPM from the terminal marker.
+    #[must_use]
+    pub fn bpm(&self) -> Option<f32> {
+        self.markers.iter().find_map(|marker| match marker {
+            BeatGridMarker::Terminal { bpm, .. } => Some(*bpm),
+            BeatGridMarker::NonTerminal { .. } => None,
+        })
+    }
+
+    /// Get the position in seconds for the given beat number counted from the first marker.
+    ///
+    /// Returns `None` if the beatgrid is empty.
+    #[must_use]
+    pub fn beat_position(&self, beat: u32) -> Option<f32> {
+        let mut remaining = beat;
+        for (index, marker) in self.markers.iter().enumerate() {
+            match marker {
+                BeatGridMarker::Terminal { position, bpm } => {
+                    return Some(position + remaining as f32 * 60.0 / bpm);
+                }
+                BeatGridMarker::NonTerminal {
+                    position,
+                    beats_till_next,
+                } => {
+                    if remaining < *beats_till_next {
+                        let next_position = match self.markers.get(index + 1)? {
+                            BeatGridMarker::Terminal { position, .. }
+                            | BeatGridMarker::NonTerminal { position, .. } => *position,
+                        };
+                        let beat_length = (next_position - position) / *beats_till_next as f32;
+                        return Some(position + remaining as f32 * beat_length);
+                    }
+                    remaining -= beats_till_next;
+                }
+            }
+        }
+        None
+    }
+
+    /// Move all markers by the given offset in milliseconds.
+    /// Returns the number of shifted markers.
+    pub fn shift(&mut self, offset_ms: i32) -> usize {
+        let offset = offset_ms as f32 / 1000.0;
+        for marker in &mut self.markers {
+            match marker {
+                BeatGridMarker::Terminal { position, .. } | BeatGridMarker::NonTerminal { position, .. } => {
+                    *position += offset;
+                }
+            }
+        }
+        self.markers.len()
+    }
+
+    /// Serialize to the tag data format.
+    #[must_use]
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut data = Vec::with_capacity(7 + self.markers.len() * 8);
+        data.extend_from_slice(&[0x01, 0x00]);
+        data.extend_from_slice(&(self.markers.len() as u32).to_be_bytes());
+        for marker in &self.markers {
+            match marker {
+                BeatGridMarker::Terminal { position, bpm } => {
+                    data.extend_from_slice(&position.to_be_bytes());
+                    data.extend_from_slice(&bpm.to_be_bytes());
+                }
+                BeatGridMarker::NonTerminal {
+                    position,
+                    beats_till_next,
+                } => {
+                    data.extend_from_slice(&position.to_be_bytes());
+                    data.extend_from_slice(&beats_till_next.to_be_bytes());
+                }
+            }
+        }
+        data.push(self.footer);
+        data
     }
 }
 
@@ -0,0 +1,115 @@
+use std::fmt;
+use std::fmt::Display;
+
+use serde::Deserialize;
+
+/// Actions to apply to tracks with the given Serato track colour.
+///
+/// Read from the user config:
+///
+/// ```toml
+/// [[color_rules]]
+/// color = "#FF0000"
+/// genre = "Peak Time"
+/// grouping = "Energy 5"
+/// crate = "Energy/High"
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+pub struct ColorRule {
+    /// Track colour as a hex RGB value, for example `#FF0000`
+    pub color: String,
+    /// Set genre tag
+    #[serde(default)]
+    pub genre: Option<String>,
+    /// Set grouping tag
+    #[serde(default)]
+    pub grouping: Option<String>,
+    /// Add track to crate, subcrates are separated with `/`
+    #[serde(default, rename = "crate")]
+    pub crate_name: Option<String>,
+}
+
+impl ColorRule {
+    /// Check if the rule applies to the given hex colour.
+    ///
+    /// Comparison is case-insensitive and the leading `#` is optional.
+    #[must_use]
+    pub fn matches(&self, color: &str) -> bool {
+        normalize_hex(&self.color) == normalize_hex(color)
+    }
+
+    /// Check if the rule has any actions.
+    #[must_use]
+    pub const fn has_actions(&self) -> bool {
+        self.genre.is_some() || self.grouping.is_some() || self.crate_name.is_some()
+    }
+}
+
+impl Display for ColorRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut actions = Vec::new();
+        if let Some(genre) = &self.genre {
+            actions.push(format!("genre = {genre}"));
+        }
+        if let Some(grouping) = &self.grouping {
+            actions.push(format!("grouping = {grouping}"));
+        }
+        if let Some(crate_name) = &self.crate_name {
+            actions.push(format!("crate = {crate_name}"));
+        }
+        write!(f, "#{}: {}", normalize_hex(&self.color), actions.join(", "))
+    }
+}
+
+/// Get the first rule matching the given hex colour.
+#[must_use]
+pub fn find_rule<'a>(rules: &'a [ColorRule], color: &str) -> Option<&'a ColorRule> {
+    rules.iter().find(|rule| rule.matches(color))
+}
+
+fn normalize_hex(color: &str) -> String {
+    color.trim().trim_start_matches('#').to_ascii_uppercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rule_matches() {
+        let rule = ColorRule {
+            color: "#ff0000".to_string(),
+            genre: Some("Peak Time".to_string()),
+            ..ColorRule::default()
+        };
+        assert!(rule.matches("#FF0000"));
+        assert!(rule.matches("ff0000"));
+        assert!(!rule.matches("#00FF00"));
+        assert!(rule.has_actions());
+    }
+
+    #[test]
+    fn test_parse_rules() {
+        #[derive(Deserialize)]
+        struct Rules {
+            color_rules: Vec<ColorRule>,
+        }
+        let config = r##"
+            [[color_rules]]
+            color = "#FF0000"
+            genre = "Peak Time"
+            crate = "Energy/High"
+
+            [[color_rules]]
+            color = "#0000FF"
+            grouping = "Warmup"
+        "##;
+        let rules: Rules = toml::from_str(config).expect("Failed to parse rules");
+        assert_eq!(rules.color_rules.len(), 2);
+        assert_eq!(rules.color_rules[0].crate_name.as_deref(), Some("Energy/High"));
+        assert_eq!(
+            find_rule(&rules.color_rules, "0000ff").and_then(|rule| rule.grouping.as_deref()),
+            Some("Warmup")
+        );
+    }
+}
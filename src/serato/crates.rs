@@ -0,0 +1,385 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use crate::error::{Result, TrackRenameError};
+use crate::utils;
+
+/// Directory name Serato uses for its library data.
+pub const SERATO_DIR_NAME: &str = "_Serato_";
+
+/// Directory under the Serato library directory that contains the crate files.
+const SUBCRATES_DIR_NAME: &str = "Subcrates";
+
+/// Separator between parent and child crate names in the crate filename.
+pub const SUBCRATE_SEPARATOR: &str = "%%";
+
+const CRATE_VERSION: &str = "1.0/Serato ScratchLive Crate";
+
+/// Columns shown for new crates.
+const DEFAULT_COLUMNS: [&str; 6] = ["song", "artist", "album", "length", "bpm", "key"];
+
+/// Serato crate containing a list of tracks.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SeratoCrate {
+    /// Version string, for example `1.0/Serato ScratchLive Crate`
+    pub version: String,
+    /// Track paths relative to the volume root
+    pub tracks: Vec<String>,
+    /// Sort and column records, kept as is
+    columns: Vec<([u8; 4], Vec<u8>)>,
+}
+
+impl SeratoCrate {
+    /// Read crate from file.
+    pub fn read(path: &Path) -> Result<Self> {
+        let data = fs::read(path)
+            .map_err(|e| TrackRenameError::io(format!("Failed to read crate file: {}", path.display()), e))?;
+        Self::parse(&data).map_err(|e| {
+            TrackRenameError::InvalidSerato(format!("Failed to parse crate file: {}: {e}", path.display()))
+        })
+    }
+
+    /// Parse crate file data.
+    ///
+    /// The file consists of records with a 4-byte ASCII tag and a big-endian `u32` length,
+    /// followed by the record data. Strings are stored as UTF-16 big-endian.
+    ///
+    /// | Tag    | Content
+    /// | ------ | -------
+    /// | `vrsn` | Version string
+    /// | `osrt` | Sort column, nested records
+    /// | `ovct` | Visible column, nested records
+    /// | `otrk` | Track, nested records with the path in `ptrk`
+    ///
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        let mut serato_crate = Self::default();
+        for (tag, value) in parse_records(data)? {
+            match &tag {
+                b"vrsn" => serato_crate.version = decode_utf16(value)?,
+                b"otrk" => {
+                    for (field, field_value) in parse_records(value)? {
+                        if &field == b"ptrk" {
+                            serato_crate.tracks.push(decode_utf16(field_value)?);
+                        }
+                    }
+                }
+                _ => serato_crate.columns.push((tag, value.to_vec())),
+            }
+        }
+        if serato_crate.version.is_empty() {
+            return Err(TrackRenameError::InvalidSerato("Missing crate version".to_string()));
+        }
+        Ok(serato_crate)
+    }
+
+    /// Create an empty crate with the default columns.
+    #[must_use]
+    pub fn new() -> Self {
+        let columns = DEFAULT_COLUMNS
+            .iter()
+            .map(|column| {
+                let mut value = encode_record(*b"tvcn", &encode_utf16(column));
+                value.extend(encode_record(*b"tvcw", &encode_utf16("0")));
+                (*b"ovct", value)
+            })
+            .collect();
+        Self {
+            version: CRATE_VERSION.to_string(),
+            tracks: Vec::new(),
+            columns,
+        }
+    }
+
+    /// Serialize crate to the binary file format.
+    #[must_use]
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut data = encode_record(*b"vrsn", &encode_utf16(&self.version));
+        for (tag, value) in &self.columns {
+            data.extend(encode_record(*tag, value));
+        }
+        for track in &self.tracks {
+            data.extend(encode_record(*b"otrk", &encode_record(*b"ptrk", &encode_utf16(track))));
+        }
+        data
+    }
+
+    /// Write crate to file.
+    pub fn write(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|e| {
+                TrackRenameError::io(format!("Failed to create crate directory: {}", parent.display()), e)
+            })?;
+        }
+        fs::write(path, self.to_bytes())
+            .map_err(|e| TrackRenameError::io(format!("Failed to write crate file: {}", path.display()), e))
+    }
+
+    /// Get absolute track paths for a crate located at the given path.
+    #[must_use]
+    pub fn track_paths(&self, crate_path: &Path) -> Vec<PathBuf> {
+        let root = volume_root(crate_path);
+        self.tracks.iter().map(|track| root.join(track)).collect()
+    }
+
+    /// Add track to the crate if it is not already included.
+    /// Returns false if the track is already in the crate or is not on the same volume.
+    pub fn add_track(&mut self, track_path: &Path, crate_path: &Path) -> bool {
+        let Some(relative) = crate_track_path(track_path, crate_path) else {
+            return false;
+        };
+        if self.tracks.contains(&relative) {
+            return false;
+        }
+        self.tracks.push(relative);
+        true
+    }
+
+    /// Replace renamed track paths.
+    /// Returns the number of updated entries.
+    pub fn rename_tracks(&mut self, renamed: &HashMap<PathBuf, PathBuf>, crate_path: &Path) -> usize {
+        let root = volume_root(crate_path);
+        // Compare with NFC normalized strings since Serato might use a different Unicode form
+        let renamed: HashMap<String, &PathBuf> = renamed
+            .iter()
+            .map(|(old, new)| (utils::normalize_str(&old.to_string_lossy()), new))
+            .collect();
+
+        let mut updated = 0;
+        for track in &mut self.tracks {
+            let path = utils::normalize_str(&root.join(&*track).to_string_lossy());
+            if let Some(relative) = renamed
+                .get(&path)
+                .and_then(|new_path| crate_track_path(new_path, crate_path))
+            {
+                *track = relative;
+                updated += 1;
+            }
+        }
+        updated
+    }
+}
+
+/// Get the default Serato library directory in the user music directory.
+#[must_use]
+pub fn default_serato_dir() -> Option<PathBuf> {
+    dirs::audio_dir()
+        .or_else(|| dirs::home_dir().map(|home| home.join("Music")))
+        .map(|music| music.join(SERATO_DIR_NAME))
+}
+
+/// List all crate files in the Serato library directory.
+pub fn crate_files(serato_dir: &Path) -> Result<Vec<PathBuf>> {
+    let subcrates = serato_dir.join(SUBCRATES_DIR_NAME);
+    let mut files: Vec<PathBuf> = fs::read_dir(&subcrates)
+        .map_err(|e| TrackRenameError::io(format!("Failed to read crate directory: {}", subcrates.display()), e))?
+        .filter_map(std::result::Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "crate"))
+        .collect();
+    files.sort_unstable();
+    Ok(files)
+}
+
+/// Get crate file path for the given crate name.
+/// Parent crates are separated with `/` in the name.
+#[must_use]
+pub fn crate_file_path(serato_dir: &Path, name: &str) -> PathBuf {
+    let filename = name
+        .split('/')
+        .map(|part| part.trim().replace(['\\', ':', '*', '?', '"', '<', '>', '|'], "_"))
+        .collect::<Vec<_>>()
+        .join(SUBCRATE_SEPARATOR);
+    serato_dir.join(SUBCRATES_DIR_NAME).join(format!("{filename}.crate"))
+}
+
+/// Get crate display name from the crate file path, with parent crates separated by `/`.
+#[must_use]
+pub fn crate_name(crate_path: &Path) -> String {
+    crate_path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().replace(SUBCRATE_SEPARATOR, "/"))
+        .unwrap_or_default()
+}
+
+/// Convert absolute track path to the form stored in the crate.
+pub(crate) fn crate_track_path(track_path: &Path, crate_path: &Path) -> Option<String> {
+    let root = volume_root(crate_path);
+    track_path
+        .strip_prefix(&root)
+        .ok()
+        .map(|relative| relative.to_string_lossy().replace('\\', "/"))
+}
+
+/// Get the volume root that crate track paths are relative to.
+///
+/// Crates in the home directory library use paths relative to the file system root,
+/// while crates on an external drive use paths relative to the drive root.
+#[must_use]
+pub fn volume_root(crate_path: &Path) -> PathBuf {
+    let components: Vec<Component> = crate_path.components().collect();
+    if let (Some(Component::RootDir), Some(Component::Normal(volumes)), Some(Component::Normal(drive))) =
+        (components.first(), components.get(1), components.get(2))
+    {
+        let serato_on_drive = crate_path
+            .ancestors()
+            .any(|ancestor| ancestor.file_name().is_some_and(|name| name == SERATO_DIR_NAME));
+        if *volumes == "Volumes" && serato_on_drive {
+            return Path::new("/").join(volumes).join(drive);
+        }
+    }
+    components
+        .iter()
+        .take_while(|component| matches!(component, Component::Prefix(_) | Component::RootDir))
+        .collect()
+}
+
+/// Split data into tag and value records.
+pub(crate) fn parse_records(data: &[u8]) -> Result<Vec<([u8; 4], &[u8])>> {
+    let mut records = Vec::new();
+    let mut offset = 0;
+    while offset < data.len() {
+        if offset + 8 > data.len() {
+            return Err(TrackRenameError::InvalidSerato(format!(
+                "Truncated record header at offset {offset}"
+            )));
+        }
+        let mut tag = [0u8; 4];
+        tag.copy_from_slice(&data[offset..offset + 4]);
+        let length =
+            u32::from_be_bytes([data[offset + 4], data[offset + 5], data[offset + 6], data[offset + 7]]) as usize;
+        let start = offset + 8;
+        let end = start + length;
+        if end > data.len() {
+            return Err(TrackRenameError::InvalidSerato(format!(
+                "Record {} length {length} exceeds data size",
+                String::from_utf8_lossy(&tag)
+            )));
+        }
+        records.push((tag, &data[start..end]));
+        offset = end;
+    }
+    Ok(records)
+}
+
+/// Encode a single tag and value record.
+pub(crate) fn encode_record(tag: [u8; 4], value: &[u8]) -> Vec<u8> {
+    let mut data = Vec::with_capacity(8 + value.len());
+    data.extend_from_slice(&tag);
+    data.extend_from_slice(&(value.len() as u32).to_be_bytes());
+    data.extend_from_slice(value);
+    data
+}
+
+/// Encode string as UTF-16 big-endian.
+pub(crate) fn encode_utf16(text: &str) -> Vec<u8> {
+    text.encode_utf16().flat_map(u16::to_be_bytes).collect()
+}
+
+/// Decode UTF-16 big-endian string.
+pub(crate) fn decode_utf16(data: &[u8]) -> Result<String> {
+    if !data.len().is_multiple_of(2) {
+        return Err(TrackRenameError::InvalidSerato(
+            "Odd number of bytes in UTF-16 string".to_string(),
+        ));
+    }
+    let units: Vec<u16> = data
+        .chunks_exact(2)
+        .map(|pair| u16::from_be_bytes([pair[0], pair[1]]))
+        .collect();
+    String::from_utf16(&units).map_err(|e| TrackRenameError::InvalidSerato(format!("Invalid UTF-16 string: {e}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use encode_record as record;
+    use encode_utf16 as utf16;
+
+    #[test]
+    fn test_parse_crate() {
+        let mut data = record(*b"vrsn", &utf16("1.0/Serato ScratchLive Crate"));
+        data.extend(record(*b"ovct", &record(*b"tvcn", &utf16("song"))));
+        data.extend(record(
+            *b"otrk",
+            &record(*b"ptrk", &utf16("Users/test/Music/Artist - Title.mp3")),
+        ));
+        data.extend(record(
+            *b"otrk",
+            &record(*b"ptrk", &utf16("Users/test/Music/Ääkköset - Title.aif")),
+        ));
+
+        let serato_crate = SeratoCrate::parse(&data).expect("Failed to parse crate");
+        assert_eq!(serato_crate.version, "1.0/Serato ScratchLive Crate");
+        assert_eq!(
+            serato_crate.tracks,
+            vec![
+                "Users/test/Music/Artist - Title.mp3",
+                "Users/test/Music/Ääkköset - Title.aif"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_truncated_crate() {
+        let mut data = record(*b"vrsn", &utf16("1.0/Serato ScratchLive Crate"));
+        data.extend_from_slice(b"otrk\x00\x00\x01\x00");
+        assert!(SeratoCrate::parse(&data).is_err());
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    #[test]
+    fn test_volume_root() {
+        assert_eq!(
+            volume_root(Path::new("/Users/test/Music/_Serato_/Subcrates/House.crate")),
+            PathBuf::from("/")
+        );
+        assert_eq!(
+            volume_root(Path::new("/Volumes/USB/_Serato_/Subcrates/House.crate")),
+            PathBuf::from("/Volumes/USB")
+        );
+    }
+
+    #[test]
+    fn test_crate_round_trip() {
+        let mut data = record(*b"vrsn", &utf16("1.0/Serato ScratchLive Crate"));
+        data.extend(record(*b"osrt", &record(*b"tvcn", &utf16("bpm"))));
+        data.extend(record(*b"ovct", &record(*b"tvcn", &utf16("song"))));
+        data.extend(record(*b"otrk", &record(*b"ptrk", &utf16("Music/Artist - Title.mp3"))));
+
+        let serato_crate = SeratoCrate::parse(&data).expect("Failed to parse crate");
+        assert_eq!(serato_crate.to_bytes(), data);
+
+        let new_crate = SeratoCrate::new();
+        assert_eq!(
+            SeratoCrate::parse(&new_crate.to_bytes()).expect("Failed to parse new crate"),
+            new_crate
+        );
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    #[test]
+    fn test_rename_and_add_tracks() {
+        let crate_path = Path::new("/Users/test/Music/_Serato_/Subcrates/House.crate");
+        let mut serato_crate = SeratoCrate::new();
+        assert!(serato_crate.add_track(Path::new("/Users/test/Music/old name.mp3"), crate_path));
+        assert!(!serato_crate.add_track(Path::new("/Users/test/Music/old name.mp3"), crate_path));
+        assert_eq!(serato_crate.tracks, vec!["Users/test/Music/old name.mp3"]);
+
+        let renamed = HashMap::from([(
+            PathBuf::from("/Users/test/Music/old name.mp3"),
+            PathBuf::from("/Users/test/Music/Artist - New Name.mp3"),
+        )]);
+        assert_eq!(serato_crate.rename_tracks(&renamed, crate_path), 1);
+        assert_eq!(serato_crate.tracks, vec!["Users/test/Music/Artist - New Name.mp3"]);
+    }
+
+    #[test]
+    fn test_crate_file_path() {
+        let serato_dir = Path::new("_Serato_");
+        let path = crate_file_path(serato_dir, "Genres/Drum & Bass");
+        assert_eq!(path, serato_dir.join("Subcrates").join("Genres%%Drum & Bass.crate"));
+        assert_eq!(crate_name(&path), "Genres/Drum & Bass");
+    }
+}
@@ -0,0 +1,277 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::error::{Result, TrackRenameError};
+use crate::serato::crates::{crate_track_path, decode_utf16, encode_record, encode_utf16, parse_records, volume_root};
+use crate::utils;
+
+/// Database filename in the Serato library directory.
+pub const DATABASE_FILE_NAME: &str = "database V2";
+
+/// Serato library database.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SeratoDatabase {
+    /// Version string, for example `2.0/Serato Scratch LIVE Database`
+    pub version: String,
+    /// All top-level records in the original order
+    records: Vec<Record>,
+}
+
+/// Top-level record in the Serato database.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Record {
+    Version,
+    Track(DatabaseTrack),
+    /// Other records and track entries that could not be parsed, kept as is
+    Raw([u8; 4], Vec<u8>),
+}
+
+/// One track entry in the Serato database.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DatabaseTrack {
+    /// File path relative to the volume root
+    pub path: String,
+    pub artist: String,
+    pub title: String,
+    pub genre: String,
+    /// True if Serato has marked the file as missing
+    pub missing: bool,
+    /// All track fields in the original order
+    fields: Vec<([u8; 4], Vec<u8>)>,
+}
+
+impl SeratoDatabase {
+    /// Read database from file.
+    pub fn read(path: &Path) -> Result<Self> {
+        let data = fs::read(path)
+            .map_err(|e| TrackRenameError::io(format!("Failed to read Serato database: {}", path.display()), e))?;
+        Self::parse(&data).map_err(|e| {
+            TrackRenameError::InvalidSerato(format!("Failed to parse Serato database: {}: {e}", path.display()))
+        })
+    }
+
+    /// Read database from the Serato library directory.
+    pub fn read_from_dir(serato_dir: &Path) -> Result<Self> {
+        Self::read(&serato_dir.join(DATABASE_FILE_NAME))
+    }
+
+    /// Parse database file data.
+    ///
+    /// Uses the same record format as crate files.
+    /// Each track is an `otrk` record with nested fields,
+    /// where the first character of the field tag tells the value type:
+    ///
+    /// | Prefix | Type
+    /// | ------ | ----
+    /// | `t`    | UTF-16 text
+    /// | `p`    | UTF-16 path
+    /// | `u`    | `u32`
+    /// | `s`    | `u16`
+    /// | `b`    | Boolean byte
+    /// | `o`    | Nested records
+    ///
+    ///
+    /// Track entries that can not be parsed are kept as is and left out of [`SeratoDatabase::tracks`].
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        let mut database = Self::default();
+        for (tag, value) in parse_records(data)? {
+            let record = match &tag {
+                b"vrsn" => {
+                    database.version = decode_utf16(value)?;
+                    Record::Version
+                }
+                b"otrk" => DatabaseTrack::parse(value).map_or_else(|_| Record::Raw(tag, value.to_vec()), Record::Track),
+                _ => Record::Raw(tag, value.to_vec()),
+            };
+            database.records.push(record);
+        }
+        if database.version.is_empty() {
+            return Err(TrackRenameError::InvalidSerato("Missing database version".to_string()));
+        }
+        Ok(database)
+    }
+
+    /// Serialize database to the binary file format.
+    #[must_use]
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.records
+            .iter()
+            .flat_map(|record| match record {
+                Record::Version => encode_record(*b"vrsn", &encode_utf16(&self.version)),
+                Record::Track(track) => encode_record(*b"otrk", &track.to_bytes()),
+                Record::Raw(tag, value) => encode_record(*tag, value),
+            })
+            .collect()
+    }
+
+    /// Track entries that could be parsed.
+    pub fn tracks(&self) -> impl Iterator<Item = &DatabaseTrack> {
+        self.records.iter().filter_map(|record| match record {
+            Record::Track(track) => Some(track),
+            _ => None,
+        })
+    }
+
+    fn tracks_mut(&mut self) -> impl Iterator<Item = &mut DatabaseTrack> {
+        self.records.iter_mut().filter_map(|record| match record {
+            Record::Track(track) => Some(track),
+            _ => None,
+        })
+    }
+
+    /// Write database to file.
+    pub fn write(&self, path: &Path) -> Result<()> {
+        fs::write(path, self.to_bytes())
+            .map_err(|e| TrackRenameError::io(format!("Failed to write Serato database: {}", path.display()), e))
+    }
+
+    /// Get absolute track paths for a database located at the given path.
+    #[must_use]
+    pub fn track_paths(&self, database_path: &Path) -> Vec<PathBuf> {
+        let root = volume_root(database_path);
+        self.tracks().map(|track| root.join(&track.path)).collect()
+    }
+
+    /// Point entries for renamed files to the new path.
+    /// Returns the old and new path for each relocated track.
+    pub fn relocate_tracks(
+        &mut self,
+        renamed: &HashMap<PathBuf, PathBuf>,
+        database_path: &Path,
+    ) -> Vec<(PathBuf, PathBuf)> {
+        let root = volume_root(database_path);
+        // Compare with NFC normalized strings since Serato might use a different Unicode form
+        let renamed: HashMap<String, &PathBuf> = renamed
+            .iter()
+            .map(|(old, new)| (utils::normalize_str(&old.to_string_lossy()), new))
+            .collect();
+
+        let mut relocated = Vec::new();
+        for track in self.tracks_mut() {
+            let old_path = root.join(&track.path);
+            let Some(new_path) = renamed.get(&utils::normalize_str(&old_path.to_string_lossy())) else {
+                continue;
+            };
+            if let Some(relative) = crate_track_path(new_path, database_path) {
+                track.set_path(relative);
+                relocated.push((old_path, (*new_path).clone()));
+            }
+        }
+        relocated
+    }
+}
+
+impl DatabaseTrack {
+    /// Parse nested track fields.
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        let mut track = Self::default();
+        for (tag, value) in parse_records(data)? {
+            match &tag {
+                b"pfil" => track.path = decode_utf16(value)?,
+                b"tart" => track.artist = decode_utf16(value)?,
+                b"tsng" => track.title = decode_utf16(value)?,
+                b"tgen" => track.genre = decode_utf16(value)?,
+                b"bmis" => track.missing = value.first().is_some_and(|&byte| byte != 0),
+                _ => {}
+            }
+            track.fields.push((tag, value.to_vec()));
+        }
+        if track.path.is_empty() {
+            return Err(TrackRenameError::InvalidSerato(
+                "Track entry is missing the file path".to_string(),
+            ));
+        }
+        Ok(track)
+    }
+
+    /// Change the file path and clear the missing flag.
+    pub fn set_path(&mut self, path: String) {
+        for (tag, value) in &mut self.fields {
+            match &*tag {
+                b"pfil" => *value = encode_utf16(&path),
+                b"bmis" => value.iter_mut().for_each(|byte| *byte = 0),
+                _ => {}
+            }
+        }
+        self.path = path;
+        self.missing = false;
+    }
+
+    /// Serialize track fields.
+    #[must_use]
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.fields
+            .iter()
+            .flat_map(|(tag, value)| encode_record(*tag, value))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use encode_record as record;
+    use encode_utf16 as utf16;
+
+    fn track_record(path: &str, missing: bool) -> Vec<u8> {
+        let mut fields = record(*b"ttyp", &utf16("mp3"));
+        fields.extend(record(*b"pfil", &utf16(path)));
+        fields.extend(record(*b"tsng", &utf16("Title")));
+        fields.extend(record(*b"tart", &utf16("Artist")));
+        fields.extend(record(*b"uadd", &1_700_000_000_u32.to_be_bytes()));
+        fields.extend(record(*b"bmis", &[u8::from(missing)]));
+        record(*b"otrk", &fields)
+    }
+
+    #[test]
+    fn test_parse_database() {
+        let mut data = record(*b"vrsn", &utf16("2.0/Serato Scratch LIVE Database"));
+        data.extend(track_record("Users/test/Music/Artist - Title.mp3", false));
+        data.extend(track_record("Users/test/Music/Missing.mp3", true));
+
+        let database = SeratoDatabase::parse(&data).expect("Failed to parse database");
+        assert_eq!(database.version, "2.0/Serato Scratch LIVE Database");
+        let tracks: Vec<&DatabaseTrack> = database.tracks().collect();
+        assert_eq!(tracks.len(), 2);
+        assert_eq!(tracks[0].path, "Users/test/Music/Artist - Title.mp3");
+        assert_eq!(tracks[0].artist, "Artist");
+        assert_eq!(tracks[0].title, "Title");
+        assert!(!tracks[0].missing);
+        assert!(tracks[1].missing);
+        assert_eq!(database.to_bytes(), data);
+    }
+
+    #[test]
+    fn test_track_without_path() {
+        let mut data = record(*b"vrsn", &utf16("2.0/Serato Scratch LIVE Database"));
+        data.extend(record(*b"otrk", &record(*b"tsng", &utf16("Title"))));
+        data.extend(track_record("Users/test/Music/Artist - Title.mp3", false));
+        data.extend(record(*b"xtra", b"data"));
+        let database = SeratoDatabase::parse(&data).expect("Failed to parse database");
+        assert_eq!(database.tracks().count(), 1);
+        assert_eq!(database.to_bytes(), data);
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    #[test]
+    fn test_relocate_tracks() {
+        let mut data = record(*b"vrsn", &utf16("2.0/Serato Scratch LIVE Database"));
+        data.extend(track_record("Users/test/Music/old name.mp3", true));
+        let mut database = SeratoDatabase::parse(&data).expect("Failed to parse database");
+
+        let database_path = Path::new("/Users/test/Music/_Serato_/database V2");
+        let renamed = HashMap::from([(
+            PathBuf::from("/Users/test/Music/old name.mp3"),
+            PathBuf::from("/Users/test/Music/Artist - Title.mp3"),
+        )]);
+        let relocated = database.relocate_tracks(&renamed, database_path);
+        assert_eq!(relocated.len(), 1);
+
+        let database = SeratoDatabase::parse(&database.to_bytes()).expect("Failed to parse relocated database");
+        let track = database.tracks().next().expect("Missing track");
+        assert_eq!(track.path, "Users/test/Music/Artist - Title.mp3");
+        assert!(!track.missing);
+    }
+}
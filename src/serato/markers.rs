@@ -2,12 +2,13 @@ use std::fmt::Display;
 use std::io::{Cursor, Read};
 use std::{fmt, io, str};
 
-use anyhow::{anyhow, Context, Result};
 use base64::{engine::general_purpose, Engine as _};
 use byteorder::{BigEndian, ReadBytesExt};
 use colored::{ColoredString, Colorize};
 
-#[derive(Debug, Clone)]
+use crate::error::{Result, TrackRenameError};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 /// Cue points, saved loops, track color, and BPM lock status
 pub enum Markers {
     BpmLock(BpmLock),
@@ -16,49 +17,49 @@ pub enum Markers {
     Loop(Loop),
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 /// Boolean for BPM lock status.
 /// True means lock is enabled.
 /// <https://support.serato.com/hc/en-us/articles/235214887-Lock-Beatgrids>
 pub struct BpmLock {
-    enabled: bool,
+    pub enabled: bool,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 /// RGB colour.
 /// Used for track, cues, and loops.
 pub struct Color {
-    r: u8,
-    b: u8,
-    g: u8,
+    pub r: u8,
+    pub b: u8,
+    pub g: u8,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 /// A cue point.
 pub struct Cue {
     /// Cue number
-    index: u8,
+    pub index: u8,
     /// Position in milliseconds
-    position: u32,
+    pub position: u32,
     /// RGB Color
-    color: Color,
+    pub color: Color,
     /// Name
-    name: String,
+    pub name: String,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 /// Saved loop.
 pub struct Loop {
     /// Loop number
-    index: u8,
+    pub index: u8,
     /// Start position in milliseconds
-    start_position: u32,
+    pub start_position: u32,
     /// End position in milliseconds
-    end_position: u32,
+    pub end_position: u32,
     /// RGB Color
-    color: Color,
-    locked: bool,
-    name: String,
+    pub color: Color,
+    pub locked: bool,
+    pub name: String,
 }
 
 impl Markers {
@@ -67,7 +68,7 @@ impl Markers {
         let b64data_end = data
             .iter()
             .position(|&x| x == b'\x00')
-            .ok_or_else(|| anyhow!("No null terminator found"))?;
+            .ok_or_else(|| TrackRenameError::InvalidSerato("No null terminator found".to_string()))?;
         let b64data = &data[b64data_start..b64data_end];
 
         // Remove linefeed characters
@@ -87,24 +88,29 @@ impl Markers {
 
         let payload = general_purpose::STANDARD
             .decode(&b64data_padded)
-            .context("Failed to decode base64 data")?;
+            .map_err(|e| TrackRenameError::InvalidSerato(format!("Failed to decode base64 data: {e}")))?;
 
         let mut cursor = Cursor::new(payload);
-        let version = (cursor.read_u8()?, cursor.read_u8()?);
+        let version = (
+            cursor.read_u8().map_err(invalid_data)?,
+            cursor.read_u8().map_err(invalid_data)?,
+        );
         if version != (0x01, 0x01) {
-            return Err(anyhow!("Invalid payload version: {:?}", version));
+            return Err(TrackRenameError::InvalidSerato(format!(
+                "Invalid payload version: {version:?}"
+            )));
         }
 
         let mut entries = Vec::new();
         while let Ok(entry_name_bytes) = read_bytes(&mut cursor) {
-            let entry_name = String::from_utf8(entry_name_bytes)?;
+            let entry_name = String::from_utf8(entry_name_bytes).map_err(invalid_data)?;
             let name = entry_name.trim();
             if name.is_empty() {
                 break;
             }
-            let entry_len = cursor.read_u32::<BigEndian>()?;
+            let entry_len = cursor.read_u32::<BigEndian>().map_err(invalid_data)?;
             let mut entry_data = vec![0; entry_len as usize];
-            cursor.read_exact(&mut entry_data)?;
+            cursor.read_exact(&mut entry_data).map_err(invalid_data)?;
             entries.push(Self::load(&entry_name, &entry_data)?);
         }
 
@@ -117,7 +123,60 @@ impl Markers {
             "COLOR" => Ok(Self::Color(Color::load(data)?)),
             "CUE" => Ok(Self::Cue(Cue::load(data)?)),
             "LOOP" => Ok(Self::Loop(Loop::load(data)?)),
-            _ => Err(anyhow!("Unknown entry type: {}", entry_name)),
+            _ => Err(TrackRenameError::InvalidSerato(format!(
+                "Unknown entry type: {entry_name}"
+            ))),
+        }
+    }
+
+    /// Serialize marker entries to the `Serato Markers2` tag data format.
+    ///
+    /// Serato drops the incomplete last base64 character of the null-terminated payload
+    /// instead of padding, splits the base64 data to lines of 72 characters,
+    /// and pads the tag data with null bytes to at least 470 bytes.
+    #[must_use]
+    pub fn dump(entries: &[Self]) -> Vec<u8> {
+        let mut payload = vec![0x01, 0x01];
+        for entry in entries {
+            let entry_data = entry.to_bytes();
+            payload.extend_from_slice(entry.entry_name().as_bytes());
+            payload.push(0x00);
+            payload.extend_from_slice(&(entry_data.len() as u32).to_be_bytes());
+            payload.extend(entry_data);
+        }
+        payload.push(0x00);
+
+        let mut encoded = general_purpose::STANDARD_NO_PAD.encode(&payload);
+        encoded.truncate(payload.len() * 8 / 6);
+        let mut data = vec![0x01, 0x01];
+        for (index, chunk) in encoded.as_bytes().chunks(72).enumerate() {
+            if index > 0 {
+                data.push(b'\n');
+            }
+            data.extend_from_slice(chunk);
+        }
+        data.push(0x00);
+        if data.len() < 470 {
+            data.resize(470, 0x00);
+        }
+        data
+    }
+
+    const fn entry_name(&self) -> &'static str {
+        match self {
+            Self::BpmLock(_) => "BPMLOCK",
+            Self::Color(_) => "COLOR",
+            Self::Cue(_) => "CUE",
+            Self::Loop(_) => "LOOP",
+        }
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        match self {
+            Self::BpmLock(bpm_lock) => vec![u8::from(bpm_lock.enabled)],
+            Self::Color(color) => vec![0x00, color.r, color.g, color.b],
+            Self::Cue(cue) => cue.to_bytes(),
+            Self::Loop(loop_var) => loop_var.to_bytes(),
         }
     }
 }
@@ -125,7 +184,9 @@ impl Markers {
 impl BpmLock {
     fn load(data: &[u8]) -> Result<Self> {
         if data.len() != 1 {
-            return Err(anyhow!("Invalid data length for BpmLock"));
+            return Err(TrackRenameError::InvalidSerato(
+                "Invalid data length for BpmLock".to_string(),
+            ));
         }
         Ok(Self { enabled: data[0] != 0 })
     }
@@ -151,6 +212,24 @@ impl Color {
         }
     }
 
+    /// Parse a hex RGB string like `#FF99FF`, the leading `#` is optional.
+    #[must_use]
+    pub fn from_hex(hex: &str) -> Option<Self> {
+        let hex = hex.trim().trim_start_matches('#');
+        if hex.len() != 6 {
+            return None;
+        }
+        let value = u32::from_str_radix(hex, 16).ok()?;
+        let [_, r, g, b] = value.to_be_bytes();
+        Some(Self::new([r, g, b]))
+    }
+
+    /// Format as a hex RGB string, for example `#FF99FF`.
+    #[must_use]
+    pub fn to_hex(&self) -> String {
+        format!("#{:02X}{:02X}{:02X}", self.r, self.g, self.b)
+    }
+
     #[inline]
     pub fn format(&self, text: &str) -> ColoredString {
         text.truecolor(self.r, self.g, self.b)
@@ -158,7 +237,9 @@ impl Color {
 
     fn load(data: &[u8]) -> Result<Self> {
         if data.len() != 4 {
-            return Err(anyhow!("Invalid data length for Color"));
+            return Err(TrackRenameError::InvalidSerato(
+                "Invalid data length for Color".to_string(),
+            ));
         }
         Ok(Self {
             r: data[1],
@@ -181,26 +262,26 @@ impl Cue {
     ///
     fn load(data: &[u8]) -> Result<Self> {
         if data.len() < 13 {
-            return Err(anyhow!("Invalid data length for CueEntry"));
+            return Err(TrackRenameError::InvalidSerato(
+                "Invalid data length for CueEntry".to_string(),
+            ));
         }
         let mut cursor = Cursor::new(data);
         // Skip first byte
         cursor.set_position(1);
-        let index = cursor.read_u8()?;
-        let position = cursor.read_u32::<BigEndian>()?;
+        let index = cursor.read_u8().map_err(invalid_data)?;
+        let position = cursor.read_u32::<BigEndian>().map_err(invalid_data)?;
         cursor.set_position(cursor.position() + 1);
         let mut color = [0; 3];
-        cursor.read_exact(&mut color)?;
+        cursor.read_exact(&mut color).map_err(invalid_data)?;
         let color = Color::new(color);
         cursor.set_position(cursor.position() + 2);
         let mut name_bytes = Vec::new();
-        cursor.read_to_end(&mut name_bytes)?;
-        let name = str::from_utf8(&name_bytes)?.trim_end_matches('\x00').trim();
-        let name = if name.is_empty() {
-            super::format_position_timestamp(position)
-        } else {
-            name.to_string()
-        };
+        cursor.read_to_end(&mut name_bytes).map_err(invalid_data)?;
+        let name = str::from_utf8(&name_bytes)
+            .map_err(invalid_data)?
+            .trim_end_matches('\x00')
+            .to_string();
         Ok(Self {
             index,
             position,
@@ -208,6 +289,15 @@ impl Cue {
             name,
         })
     }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut data = vec![0x00, self.index];
+        data.extend_from_slice(&self.position.to_be_bytes());
+        data.extend_from_slice(&[0x00, self.color.r, self.color.g, self.color.b, 0x00, 0x00]);
+        data.extend_from_slice(self.name.as_bytes());
+        data.push(0x00);
+        data
+    }
 }
 
 impl Loop {
@@ -225,23 +315,28 @@ impl Loop {
     ///
     fn load(data: &[u8]) -> Result<Self> {
         if data.len() < 15 {
-            return Err(anyhow!("Invalid data length for Loop"));
+            return Err(TrackRenameError::InvalidSerato(
+                "Invalid data length for Loop".to_string(),
+            ));
         }
         let mut cursor = Cursor::new(data);
         cursor.set_position(1);
-        let index = cursor.read_u8()?;
-        let start_position = cursor.read_u32::<BigEndian>()?;
-        let end_position = cursor.read_u32::<BigEndian>()?;
+        let index = cursor.read_u8().map_err(invalid_data)?;
+        let start_position = cursor.read_u32::<BigEndian>().map_err(invalid_data)?;
+        let end_position = cursor.read_u32::<BigEndian>().map_err(invalid_data)?;
         cursor.set_position(cursor.position() + 4);
         let mut color = [0; 4];
-        cursor.read_exact(&mut color)?;
+        cursor.read_exact(&mut color).map_err(invalid_data)?;
         let color = Color::new_argb(color);
         cursor.set_position(cursor.position() + 1);
-        let locked = cursor.read_u8()?;
+        let locked = cursor.read_u8().map_err(invalid_data)?;
         let locked = locked == 1;
         let mut name_bytes = Vec::new();
-        cursor.read_to_end(&mut name_bytes)?;
-        let name = str::from_utf8(&name_bytes)?.trim_end_matches('\x00').to_string();
+        cursor.read_to_end(&mut name_bytes).map_err(invalid_data)?;
+        let name = str::from_utf8(&name_bytes)
+            .map_err(invalid_data)?
+            .trim_end_matches('\x00')
+            .to_string();
         Ok(Self {
             index,
             start_position,
@@ -251,6 +346,18 @@ impl Loop {
             name,
         })
     }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut data = vec![0x00, self.index];
+        data.extend_from_slice(&self.start_position.to_be_bytes());
+        data.extend_from_slice(&self.end_position.to_be_bytes());
+        data.extend_from_slice(&[0xff, 0xff, 0xff, 0xff]);
+        data.extend_from_slice(&[0x00, self.color.r, self.color.g, self.color.b, 0x00]);
+        data.push(u8::from(self.locked));
+        data.extend_from_slice(self.name.as_bytes());
+        data.push(0x00);
+        data
+    }
 }
 
 impl Display for Markers {
@@ -285,7 +392,12 @@ impl Display for Cue {
         let seconds = self.position as f32 * 0.001;
         let position = format!("{seconds:>7.3}s");
         let title = format!("Cue {}", self.index + 1);
-        let text = self.color.format(&self.name);
+        let name = if self.name.trim().is_empty() {
+            super::format_position_timestamp(self.position)
+        } else {
+            self.name.trim().to_string()
+        };
+        let text = self.color.format(&name);
         write!(f, "{title}: {text:<12} {position}")
     }
 }
@@ -308,9 +420,15 @@ impl Display for Loop {
     }
 }
 
+/// Marker data ended too early or contained invalid text.
+fn invalid_data(error: impl Display) -> TrackRenameError {
+    TrackRenameError::InvalidSerato(format!("Invalid marker data: {error}"))
+}
+
 /// Read bytes until null byte
 fn read_bytes<R: Read>(reader: &mut R) -> io::Result<Vec<u8>> {
     let mut bytes = Vec::new();
+    #[allow(clippy::unbuffered_bytes)]
     for byte in reader.bytes() {
         let byte = byte?;
         if byte == b'\x00' {
@@ -320,3 +438,35 @@ fn read_bytes<R: Read>(reader: &mut R) -> io::Result<Vec<u8>> {
     }
     Ok(bytes)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dump_markers() {
+        let markers = vec![
+            Markers::Color(Color::new([0xff, 0x99, 0xff])),
+            Markers::Cue(Cue {
+                index: 0,
+                position: 1234,
+                color: Color::new([0xcc, 0x00, 0x00]),
+                name: String::new(),
+            }),
+            Markers::Loop(Loop {
+                index: 1,
+                start_position: 2000,
+                end_position: 4000,
+                color: Color::new([0x27, 0xaa, 0xe1]),
+                locked: true,
+                name: "Intro".to_string(),
+            }),
+            Markers::BpmLock(BpmLock { enabled: true }),
+        ];
+        let data = Markers::dump(&markers);
+        assert_eq!(data.len(), 470);
+        assert_eq!(&data[..2], &[0x01, 0x01]);
+        let parsed = Markers::parse(&data).expect("Failed to parse dumped markers");
+        assert_eq!(parsed, markers);
+    }
+}
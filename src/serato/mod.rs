@@ -1,21 +1,28 @@
 mod analysis;
 mod autotags;
 mod beatgrid;
+pub mod colors;
+pub mod crates;
+pub mod database;
 mod markers;
 mod overview;
+pub mod templates;
 
 use std::fmt::Display;
+use std::fmt::Write;
 use std::str::FromStr;
 
-use anyhow::{anyhow, Result};
 use colored::Colorize;
-use id3::Tag;
+use id3::frame::{Content, EncapsulatedObject};
+use id3::{Frame, Tag, TagLike};
 
+use crate::error::{Result, TrackRenameError};
 use crate::serato::analysis::AnalysisVersion;
-use crate::serato::autotags::AutoTags;
+pub use crate::serato::autotags::AutoTags;
 use crate::serato::beatgrid::BeatGrid;
-use crate::serato::markers::Markers;
+use crate::serato::markers::{BpmLock, Color, Cue, Markers};
 use crate::serato::overview::Overview;
+use crate::serato::templates::CueTemplate;
 use crate::utils;
 
 /// Contains all Serato custom tag data in the file.
@@ -28,6 +35,76 @@ pub struct SeratoData {
     pub overview: Option<Overview>,
 }
 
+/// Serato data to remove from a file.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StripOptions {
+    /// Remove all Serato frames
+    pub all: bool,
+    /// Remove cue points
+    pub cues: bool,
+    /// Remove saved loops
+    pub loops: bool,
+    /// Remove beatgrid
+    pub beatgrid: bool,
+}
+
+/// Counts of removed Serato data.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StripResult {
+    /// Number of removed Serato frames
+    pub frames: usize,
+    /// Number of removed cue points
+    pub cues: usize,
+    /// Number of removed saved loops
+    pub loops: usize,
+}
+
+/// Changes to the Serato manual gain value.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct GainOptions {
+    /// Copy the auto gain value to the manual gain
+    pub copy_auto_gain: bool,
+    /// Limit the manual gain to at most this value in dB
+    pub max_gain: Option<f32>,
+}
+
+/// Counts of shifted Serato markers.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ShiftResult {
+    /// Number of shifted beatgrid markers
+    pub beatgrid_markers: usize,
+    /// Number of shifted cue points
+    pub cues: usize,
+    /// Number of shifted saved loops
+    pub loops: usize,
+}
+
+/// Older Serato marker frame with fixed slots for cues and loops, still written for MP3 files.
+const LEGACY_MARKERS: &str = "Serato Markers_";
+
+/// Default Serato colours for cue points 1-8.
+const CUE_COLORS: [[u8; 3]; 8] = [
+    [0xcc, 0x00, 0x00],
+    [0xcc, 0x44, 0x00],
+    [0x00, 0x00, 0xcc],
+    [0xcc, 0xcc, 0x00],
+    [0x00, 0xcc, 0x00],
+    [0xcc, 0x88, 0x00],
+    [0xcc, 0x00, 0xcc],
+    [0x00, 0xcc, 0xcc],
+];
+
+/// Outcome of applying a cue template.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CueTemplateResult {
+    /// Number of added cue points
+    Added(usize),
+    /// Track already has cue points
+    HasCues,
+    /// Track has no beatgrid to place the cues with
+    NoBeatGrid,
+}
+
 /// Serato tag types.
 #[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd)]
 pub enum SeratoTag {
@@ -99,10 +176,33 @@ impl SeratoData {
             None
         }
     }
+
+    /// Track colour from the markers as a hex RGB string.
+    #[must_use]
+    pub fn track_color(&self) -> Option<String> {
+        self.markers.iter().find_map(|marker| match marker {
+            Markers::Color(color) => Some(color.to_hex()),
+            _ => None,
+        })
+    }
+}
+
+impl SeratoTag {
+    /// GEOB frame description used by Serato.
+    #[must_use]
+    pub const fn description(&self) -> &'static str {
+        match self {
+            Self::Analysis => "Serato Analysis",
+            Self::Autotags => "Serato Autotags",
+            Self::BeatGrid => "Serato BeatGrid",
+            Self::Markers => "Serato Markers2",
+            Self::Overview => "Serato Overview",
+        }
+    }
 }
 
 impl FromStr for SeratoTag {
-    type Err = anyhow::Error;
+    type Err = TrackRenameError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s {
@@ -111,7 +211,7 @@ impl FromStr for SeratoTag {
             "Serato BeatGrid" => Ok(Self::BeatGrid),
             "Serato Markers2" => Ok(Self::Markers),
             "Serato Overview" => Ok(Self::Overview),
-            _ => Err(anyhow!("Unknown tag description: {}", s)),
+            _ => Err(TrackRenameError::InvalidSerato(format!("Unknown tag description: {s}"))),
         }
     }
 }
@@ -178,6 +278,242 @@ impl Display for SeratoData {
     }
 }
 
+impl StripResult {
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.frames == 0 && self.cues == 0 && self.loops == 0
+    }
+}
+
+impl ShiftResult {
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.beatgrid_markers == 0 && self.cues == 0 && self.loops == 0
+    }
+}
+
+impl Display for ShiftResult {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{} beatgrid markers, {} cues, {} loops",
+            self.beatgrid_markers, self.cues, self.loops
+        )
+    }
+}
+
+impl Display for StripResult {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let mut parts = Vec::new();
+        if self.frames > 0 {
+            parts.push(format!("{} frames", self.frames));
+        }
+        if self.cues > 0 {
+            parts.push(format!("{} cues", self.cues));
+        }
+        if self.loops > 0 {
+            parts.push(format!("{} loops", self.loops));
+        }
+        if parts.is_empty() {
+            write!(f, "nothing removed")
+        } else {
+            write!(f, "removed {}", parts.join(", "))
+        }
+    }
+}
+
+/// Remove Serato data from the tag.
+///
+/// Removing all data drops every GEOB frame with a Serato description,
+/// including the legacy ones this program does not parse.
+/// Cues and loops are removed from the `Serato Markers2` data,
+/// keeping the track colour and BPM lock status.
+pub fn strip_serato_data(file_tags: &mut Tag, options: StripOptions) -> Result<StripResult> {
+    let mut result = StripResult::default();
+    if options.all {
+        let descriptions: Vec<String> = file_tags
+            .encapsulated_objects()
+            .filter(|object| object.description.starts_with("Serato "))
+            .map(|object| object.description.clone())
+            .collect();
+        let frame_count = file_tags.frames().count();
+        for description in &descriptions {
+            file_tags.remove_encapsulated_object(Some(description), None, None, None);
+        }
+        result.frames = frame_count - file_tags.frames().count();
+        return Ok(result);
+    }
+    if options.beatgrid && serato_object(file_tags, &SeratoTag::BeatGrid).is_some() {
+        file_tags.remove_encapsulated_object(Some(SeratoTag::BeatGrid.description()), None, None, None);
+        result.frames += 1;
+    }
+    if options.cues || options.loops {
+        if let Some(object) = serato_object(file_tags, &SeratoTag::Markers) {
+            let mut markers = Markers::parse(&object.data)?;
+            let count = markers.len();
+            if options.cues {
+                markers.retain(|marker| !matches!(marker, Markers::Cue(_)));
+                result.cues = count - markers.len();
+            }
+            let count = markers.len();
+            if options.loops {
+                markers.retain(|marker| !matches!(marker, Markers::Loop(_)));
+                result.loops = count - markers.len();
+            }
+            if result.cues > 0 || result.loops > 0 {
+                replace_serato_object(file_tags, &SeratoTag::Markers, Markers::dump(&markers));
+                // The legacy markers frame has its own copy of the cues and loops
+                if file_tags
+                    .encapsulated_objects()
+                    .any(|object| object.description == LEGACY_MARKERS)
+                {
+                    file_tags.remove_encapsulated_object(Some(LEGACY_MARKERS), None, None, None);
+                    result.frames += 1;
+                }
+            }
+        }
+    }
+    Ok(result)
+}
+
+/// Rewrite the manual gain value in the `Serato Autotags` data.
+///
+/// The auto gain is copied first if requested, and then clamped to the maximum gain.
+/// Returns the original and updated autotags if the data changed.
+pub fn rewrite_gain(file_tags: &mut Tag, options: GainOptions) -> Result<Option<(AutoTags, AutoTags)>> {
+    let Some(object) = serato_object(file_tags, &SeratoTag::Autotags) else {
+        return Ok(None);
+    };
+    let original = AutoTags::parse(&object.data)?;
+    let mut autotags = original.clone();
+    if options.copy_auto_gain {
+        autotags.gain = autotags.auto_gain;
+    }
+    if let Some(max_gain) = options.max_gain {
+        autotags.gain = autotags.gain.min(max_gain);
+    }
+
+    // Compare serialized data since values are stored with limited precision
+    let data = autotags.to_bytes();
+    if data == original.to_bytes() {
+        return Ok(None);
+    }
+    replace_serato_object(file_tags, &SeratoTag::Autotags, data);
+    Ok(Some((original, autotags)))
+}
+
+/// Shift all beatgrid markers, cue points, and saved loops by a constant offset.
+///
+/// Used after adding or removing audio at the start of a track,
+/// for example when prepending an intro in an audio editor.
+/// Cue and loop positions are limited to the start of the track.
+pub fn shift_markers(file_tags: &mut Tag, offset_ms: i32) -> Result<ShiftResult> {
+    let mut result = ShiftResult::default();
+    if let Some(object) = serato_object(file_tags, &SeratoTag::BeatGrid) {
+        let mut beatgrid = BeatGrid::parse(&object.data)?;
+        result.beatgrid_markers = beatgrid.shift(offset_ms);
+        if result.beatgrid_markers > 0 {
+            replace_serato_object(file_tags, &SeratoTag::BeatGrid, beatgrid.to_bytes());
+        }
+    }
+    if let Some(object) = serato_object(file_tags, &SeratoTag::Markers) {
+        let mut markers = Markers::parse(&object.data)?;
+        for marker in &mut markers {
+            match marker {
+                Markers::Cue(cue) => {
+                    cue.position = cue.position.saturating_add_signed(offset_ms);
+                    result.cues += 1;
+                }
+                Markers::Loop(loop_var) => {
+                    loop_var.start_position = loop_var.start_position.saturating_add_signed(offset_ms);
+                    loop_var.end_position = loop_var.end_position.saturating_add_signed(offset_ms);
+                    result.loops += 1;
+                }
+                Markers::BpmLock(_) | Markers::Color(_) => {}
+            }
+        }
+        if result.cues > 0 || result.loops > 0 {
+            replace_serato_object(file_tags, &SeratoTag::Markers, Markers::dump(&markers));
+        }
+    }
+    Ok(result)
+}
+
+/// Add cue points from the template to a track that does not have any cues yet.
+///
+/// Cue positions are calculated from the beatgrid,
+/// counting four beats per bar from the first beatgrid marker.
+pub fn apply_cue_template(file_tags: &mut Tag, template: &CueTemplate) -> Result<CueTemplateResult> {
+    let mut markers = match serato_object(file_tags, &SeratoTag::Markers) {
+        Some(object) => Markers::parse(&object.data)?,
+        None => vec![
+            Markers::Color(Color::new([0xff, 0xff, 0xff])),
+            Markers::BpmLock(BpmLock { enabled: false }),
+        ],
+    };
+    if markers.iter().any(|marker| matches!(marker, Markers::Cue(_))) {
+        return Ok(CueTemplateResult::HasCues);
+    }
+    let Some(beatgrid) = serato_object(file_tags, &SeratoTag::BeatGrid)
+        .map(|object| BeatGrid::parse(&object.data))
+        .transpose()?
+        .filter(|beatgrid| !beatgrid.markers.is_empty())
+    else {
+        return Ok(CueTemplateResult::NoBeatGrid);
+    };
+
+    let mut added = 0;
+    for (index, template_cue) in template.cues.iter().take(CUE_COLORS.len()).enumerate() {
+        let Some(seconds) = beatgrid.beat_position(template_cue.bars * 4) else {
+            continue;
+        };
+        let color = template_cue
+            .color
+            .as_deref()
+            .and_then(Color::from_hex)
+            .unwrap_or_else(|| Color::new(CUE_COLORS[index]));
+        markers.push(Markers::Cue(Cue {
+            index: index as u8,
+            position: (seconds.max(0.0) * 1000.0).round() as u32,
+            color,
+            name: template_cue.name.clone(),
+        }));
+        added += 1;
+    }
+    if added > 0 {
+        replace_serato_object(file_tags, &SeratoTag::Markers, Markers::dump(&markers));
+    }
+    Ok(CueTemplateResult::Added(added))
+}
+
+/// Add Serato autotags, a single marker beatgrid, and a cue point at the start.
+///
+/// Used for generating test files with Serato data.
+#[cfg(any(test, feature = "fixture"))]
+pub fn add_example_data(file_tags: &mut Tag, bpm: f32) {
+    use crate::serato::beatgrid::BeatGridMarker;
+
+    let autotags = AutoTags {
+        bpm,
+        auto_gain: -3.0,
+        gain: 0.0,
+    };
+    replace_serato_object(file_tags, &SeratoTag::Autotags, autotags.to_bytes());
+    let beatgrid = BeatGrid::new(vec![BeatGridMarker::Terminal { position: 0.0, bpm }]);
+    replace_serato_object(file_tags, &SeratoTag::BeatGrid, beatgrid.to_bytes());
+    let markers = [
+        Markers::Color(Color::new([0xff, 0xff, 0xff])),
+        Markers::BpmLock(BpmLock { enabled: false }),
+        Markers::Cue(Cue {
+            index: 0,
+            position: 0,
+            color: Color::new(CUE_COLORS[0]),
+            name: "Start".to_string(),
+        }),
+    ];
+    replace_serato_object(file_tags, &SeratoTag::Markers, Markers::dump(&markers));
+}
+
 /// Parse and print Serato tag data if any is present.
 pub fn print_serato_tags(file_tags: &Tag) {
     if let Some(serato_data) = SeratoData::parse(file_tags) {
@@ -185,6 +521,40 @@ pub fn print_serato_tags(file_tags: &Tag) {
     }
 }
 
+/// Get the GEOB frame for the given Serato tag type.
+fn serato_object<'a>(file_tags: &'a Tag, tag: &SeratoTag) -> Option<&'a EncapsulatedObject> {
+    file_tags
+        .encapsulated_objects()
+        .find(|object| object.description == tag.description())
+}
+
+/// Replace the data of a Serato GEOB frame,
+/// keeping the MIME type and text encoding of the existing frame.
+fn replace_serato_object(file_tags: &mut Tag, tag: &SeratoTag, data: Vec<u8>) {
+    let existing = file_tags.frames().find(|frame| {
+        frame
+            .content()
+            .encapsulated_object()
+            .is_some_and(|object| object.description == tag.description())
+    });
+    let encoding = existing.and_then(Frame::encoding);
+    let mime_type = existing
+        .and_then(|frame| frame.content().encapsulated_object())
+        .map_or_else(
+            || "application/octet-stream".to_string(),
+            |object| object.mime_type.clone(),
+        );
+
+    file_tags.remove_encapsulated_object(Some(tag.description()), None, None, None);
+    let object = EncapsulatedObject {
+        description: tag.description().to_string(),
+        mime_type,
+        filename: String::new(),
+        data,
+    };
+    file_tags.add_frame(Frame::with_content("GEOB", Content::EncapsulatedObject(object)).set_encoding(encoding));
+}
+
 /// Format duration in milliseconds as `MM:SS:T` to match Serato.
 fn format_position_timestamp(position_in_ms: u32) -> String {
     let minutes = position_in_ms / 60000;
@@ -213,11 +583,11 @@ fn hexdump(buffer: &[u8], ascii: bool) -> String {
         let line = &buffer[offset..end];
 
         // Format the offset
-        result.push_str(&format!("    {offset:08x}  "));
+        let _ = write!(result, "    {offset:08x}  ");
 
         // Format the hexadecimal values
         for byte in line {
-            result.push_str(&format!("{byte:02x} "));
+            let _ = write!(result, "{byte:02x} ");
         }
         // Add padding if the line is less than 16 bytes
         if line.len() < 16 {
@@ -242,3 +612,243 @@ fn hexdump(buffer: &[u8], ascii: bool) -> String {
     }
     result
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::serato::beatgrid::BeatGridMarker;
+    use crate::serato::markers::Loop;
+    use crate::serato::templates::TemplateCue;
+
+    const FIXTURES: [&str; 2] = [
+        "tests/files/extended_tags/Extended Tags - Song - 16-44.aif",
+        "tests/files/extended_tags/Extended Tags - Song - 16-44.mp3",
+    ];
+
+    fn add_object(tag: &mut Tag, description: &str, data: Vec<u8>) {
+        tag.add_frame(EncapsulatedObject {
+            description: description.to_string(),
+            mime_type: "application/octet-stream".to_string(),
+            filename: String::new(),
+            data,
+        });
+    }
+
+    fn serato_tag() -> Tag {
+        let markers = vec![
+            Markers::Color(Color::new([0xff, 0x99, 0xff])),
+            Markers::Cue(Cue {
+                index: 0,
+                position: 1000,
+                color: Color::new([0xcc, 0x00, 0x00]),
+                name: String::new(),
+            }),
+            Markers::Cue(Cue {
+                index: 1,
+                position: 5000,
+                color: Color::new([0x00, 0xcc, 0x00]),
+                name: "Drop".to_string(),
+            }),
+            Markers::Loop(Loop {
+                index: 0,
+                start_position: 2000,
+                end_position: 4000,
+                color: Color::new([0x27, 0xaa, 0xe1]),
+                locked: false,
+                name: String::new(),
+            }),
+            Markers::BpmLock(BpmLock { enabled: false }),
+        ];
+        let mut tag = Tag::new();
+        tag.set_title("Title");
+        add_object(&mut tag, "Serato Analysis", vec![0x02, 0x01]);
+        add_object(&mut tag, "Serato BeatGrid", vec![0x01, 0x00, 0, 0, 0, 0, 0]);
+        add_object(&mut tag, "Serato Markers_", vec![0x02, 0x05]);
+        add_object(&mut tag, "Serato Markers2", Markers::dump(&markers));
+        tag
+    }
+
+    #[test]
+    fn test_round_trip_fixtures() {
+        for path in FIXTURES {
+            let tag = Tag::read_from_path(path).expect("Failed to read test file tags");
+            let mut checked = 0;
+            for object in tag.encapsulated_objects() {
+                let Ok(serato_tag) = SeratoTag::from_str(&object.description) else {
+                    continue;
+                };
+                let data = match serato_tag {
+                    SeratoTag::Analysis => AnalysisVersion::parse(&object.data).map(|value| value.to_bytes()),
+                    SeratoTag::Autotags => AutoTags::parse(&object.data).map(|value| value.to_bytes()),
+                    SeratoTag::BeatGrid => BeatGrid::parse(&object.data).map(|value| value.to_bytes()),
+                    SeratoTag::Markers => Markers::parse(&object.data).map(|value| Markers::dump(&value)),
+                    SeratoTag::Overview => Overview::parse(&object.data).map(|value| value.to_bytes()),
+                }
+                .expect("Failed to parse Serato data");
+                assert_eq!(data, object.data, "{serato_tag} round trip failed for {path}");
+                checked += 1;
+            }
+            assert_eq!(checked, 5, "Missing Serato frames in {path}");
+        }
+    }
+
+    #[test]
+    fn test_rewrite_gain() {
+        let mut tag = serato_tag();
+        add_object(
+            &mut tag,
+            "Serato Autotags",
+            b"\x01\x01120.00\x002.995\x000.000\x00".to_vec(),
+        );
+        let options = GainOptions {
+            copy_auto_gain: true,
+            max_gain: Some(1.5),
+        };
+        let (original, updated) = rewrite_gain(&mut tag, options)
+            .expect("Failed to rewrite gain")
+            .expect("Gain should change");
+        assert!(original.gain.abs() < f32::EPSILON);
+        assert!((updated.gain - 1.5).abs() < f32::EPSILON);
+
+        let serato_data = SeratoData::parse(&tag).expect("Failed to parse Serato data");
+        assert_eq!(serato_data.autotags, Some(updated));
+        assert!(rewrite_gain(&mut tag, options)
+            .expect("Failed to rewrite gain")
+            .is_none());
+    }
+
+    #[test]
+    fn test_shift_markers() {
+        let mut tag = serato_tag();
+        let mut beatgrid = vec![0x01, 0x00, 0x00, 0x00, 0x00, 0x01];
+        beatgrid.extend_from_slice(&0.05_f32.to_be_bytes());
+        beatgrid.extend_from_slice(&128.0_f32.to_be_bytes());
+        beatgrid.push(0x00);
+        tag.remove_encapsulated_object(Some("Serato BeatGrid"), None, None, None);
+        add_object(&mut tag, "Serato BeatGrid", beatgrid);
+
+        let result = shift_markers(&mut tag, 15_000).expect("Failed to shift markers");
+        assert_eq!(
+            result,
+            ShiftResult {
+                beatgrid_markers: 1,
+                cues: 2,
+                loops: 1
+            }
+        );
+        let serato_data = SeratoData::parse(&tag).expect("Failed to parse Serato data");
+        let positions: Vec<u32> = serato_data
+            .markers
+            .iter()
+            .filter_map(|marker| match marker {
+                Markers::Cue(cue) => Some(cue.position),
+                Markers::Loop(loop_var) => Some(loop_var.start_position),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(positions, vec![16_000, 20_000, 17_000]);
+        match serato_data.beatgrid.expect("Missing beatgrid").markers[0] {
+            BeatGridMarker::Terminal { position, bpm } => {
+                assert!((position - 15.05).abs() < 0.001);
+                assert!((bpm - 128.0).abs() < f32::EPSILON);
+            }
+            BeatGridMarker::NonTerminal { .. } => panic!("Expected terminal beatgrid marker"),
+        }
+
+        shift_markers(&mut tag, -20_000).expect("Failed to shift markers");
+        let serato_data = SeratoData::parse(&tag).expect("Failed to parse Serato data");
+        assert!(serato_data.markers.iter().all(|marker| match marker {
+            Markers::Cue(cue) => cue.position <= 1000,
+            _ => true,
+        }));
+    }
+
+    #[test]
+    fn test_apply_cue_template() {
+        let mut tag = Tag::new();
+        let mut beatgrid = vec![0x01, 0x00, 0x00, 0x00, 0x00, 0x01];
+        beatgrid.extend_from_slice(&0.5_f32.to_be_bytes());
+        beatgrid.extend_from_slice(&120.0_f32.to_be_bytes());
+        beatgrid.push(0x00);
+        let template = CueTemplate {
+            cues: vec![
+                TemplateCue::default(),
+                TemplateCue {
+                    bars: 32,
+                    name: "Drop".to_string(),
+                    color: Some("#00FF00".to_string()),
+                },
+            ],
+            ..CueTemplate::default()
+        };
+
+        let result = apply_cue_template(&mut tag, &template).expect("Failed to apply template");
+        assert_eq!(result, CueTemplateResult::NoBeatGrid);
+
+        add_object(&mut tag, "Serato BeatGrid", beatgrid);
+        let result = apply_cue_template(&mut tag, &template).expect("Failed to apply template");
+        assert_eq!(result, CueTemplateResult::Added(2));
+
+        let serato_data = SeratoData::parse(&tag).expect("Failed to parse Serato data");
+        let cues: Vec<&Cue> = serato_data
+            .markers
+            .iter()
+            .filter_map(|marker| match marker {
+                Markers::Cue(cue) => Some(cue),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(cues.len(), 2);
+        assert_eq!(cues[0].position, 500);
+        assert_eq!(cues[0].color.to_hex(), "#CC0000");
+        // 32 bars at 120 BPM is 64 seconds
+        assert_eq!(cues[1].position, 64_500);
+        assert_eq!(cues[1].name, "Drop");
+        assert_eq!(cues[1].color.to_hex(), "#00FF00");
+
+        let result = apply_cue_template(&mut tag, &template).expect("Failed to apply template");
+        assert_eq!(result, CueTemplateResult::HasCues);
+    }
+
+    #[test]
+    fn test_strip_all() {
+        let mut tag = serato_tag();
+        let options = StripOptions {
+            all: true,
+            ..StripOptions::default()
+        };
+        let result = strip_serato_data(&mut tag, options).expect("Failed to strip Serato data");
+        assert_eq!(result.frames, 4);
+        assert_eq!(tag.encapsulated_objects().count(), 0);
+        assert_eq!(tag.title(), Some("Title"));
+    }
+
+    #[test]
+    fn test_strip_cues_and_beatgrid() {
+        let mut tag = serato_tag();
+        let options = StripOptions {
+            cues: true,
+            beatgrid: true,
+            ..StripOptions::default()
+        };
+        let result = strip_serato_data(&mut tag, options).expect("Failed to strip Serato data");
+        assert_eq!(result.frames, 2);
+        assert_eq!(result.cues, 2);
+        assert_eq!(result.loops, 0);
+        assert!(serato_object(&tag, &SeratoTag::BeatGrid).is_none());
+        assert!(!tag
+            .encapsulated_objects()
+            .any(|object| object.description == LEGACY_MARKERS));
+
+        let serato_data = SeratoData::parse(&tag).expect("Failed to parse Serato data");
+        assert_eq!(serato_data.markers.len(), 3);
+        assert!(!serato_data
+            .markers
+            .iter()
+            .any(|marker| matches!(marker, Markers::Cue(_))));
+
+        let result = strip_serato_data(&mut tag, options).expect("Failed to strip Serato data");
+        assert!(result.is_empty());
+    }
+}
@@ -1,10 +1,10 @@
 use std::fmt;
 use std::fmt::Display;
 
-use anyhow::anyhow;
-use anyhow::Result;
 use colored::Colorize;
-use crossterm::terminal;
+
+use crate::error::{Result, TrackRenameError};
+use crate::layout;
 
 #[derive(Debug, Clone, Default)]
 /// Contains the waveform overview data.
@@ -29,7 +29,9 @@ impl Overview {
     ///
     pub fn parse(data: &[u8]) -> Result<Self> {
         if data.len() < 2 {
-            return Err(anyhow!("Data too short to contain initial bytes"));
+            return Err(TrackRenameError::InvalidSerato(
+                "Data too short to contain initial bytes".to_string(),
+            ));
         }
 
         let mut frequency_info = Vec::new();
@@ -45,10 +47,22 @@ impl Overview {
         Ok(Self { blocks: frequency_info })
     }
 
+    /// Serialize to the tag data format.
+    #[must_use]
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut data = Vec::with_capacity(2 + self.blocks.len() * 16);
+        data.extend_from_slice(&[0x01, 0x05]);
+        for block in &self.blocks {
+            data.extend_from_slice(block);
+        }
+        data
+    }
+
     /// Convert waveform overview to a minimized text representation for terminal display.
-    fn draw_waveform(&self) -> Result<String> {
-        let (terminal_width, _) = terminal::size().map_err(|e| anyhow!("Failed to get terminal size: {}", e))?;
+    fn draw_waveform(&self) -> String {
         let width = self.blocks.len();
+        // Fit into the output width, or the classic 80 columns when the width is not known
+        let columns = layout::width().unwrap_or(80).clamp(1, width.max(1));
 
         let mut waveform = String::new();
 
@@ -68,36 +82,18 @@ impl Overview {
             }
         }
 
-        // Adjust width if needed to fit into available terminal width
-        let resampled_blocks = if terminal_width >= 240 {
-            averaged_blocks
-        } else if terminal_width >= 120 {
-            // Downsample by two, 240 -> 120
-            (0..width / 2)
-                .map(|i| {
-                    (0..height)
-                        .map(|y| {
-                            ((u16::from(averaged_blocks[2 * i][y]) + u16::from(averaged_blocks[2 * i + 1][y])) / 2)
-                                as u8
-                        })
-                        .collect()
-                })
-                .collect()
-        } else {
-            // Downsample by three, 240 -> 80
-            (0..width / 3)
-                .map(|i| {
-                    (0..height)
-                        .map(|y| {
-                            ((u16::from(averaged_blocks[3 * i][y])
-                                + u16::from(averaged_blocks[3 * i + 1][y])
-                                + u16::from(averaged_blocks[3 * i + 2][y]))
-                                / 3) as u8
-                        })
-                        .collect()
-                })
-                .collect()
-        };
+        // Downsample by averaging each group of consecutive columns into one column
+        let resampled_blocks: Vec<Vec<u8>> = (0..columns)
+            .map(|i| {
+                let group = &averaged_blocks[i * width / columns..(i + 1) * width / columns];
+                (0..height)
+                    .map(|y| {
+                        let sum: usize = group.iter().map(|column| usize::from(column[y])).sum();
+                        (sum / group.len().max(1)) as u8
+                    })
+                    .collect()
+            })
+            .collect();
 
         let max_value = resampled_blocks
             .iter()
@@ -119,7 +115,6 @@ impl Overview {
         // Iterate in reverse so first values of the vertical block go to the bottom of the waveform
         for y in (0..height).rev() {
             for block in &normalized_blocks {
-                #[allow(clippy::match_on_vec_items)]
                 let (symbol, color) = match block[y] {
                     value if value <= 0.05 => ('░', "blue"),
                     value if value <= 0.25 => ('░', "cyan"),
@@ -133,19 +128,12 @@ impl Overview {
             waveform.push('\n');
         }
 
-        Ok(waveform)
+        waveform
     }
 }
 
 impl Display for Overview {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match self.draw_waveform() {
-            Ok(view) => {
-                write!(f, "{view}")
-            }
-            Err(error) => {
-                write!(f, "Error: {error}")
-            }
-        }
+        write!(f, "{}", self.draw_waveform())
     }
 }
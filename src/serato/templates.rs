@@ -0,0 +1,98 @@
+use std::path::Path;
+
+use serde::Deserialize;
+
+/// Cue points to add to tracks that do not have any cues yet.
+///
+/// Read from the user config:
+///
+/// ```toml
+/// [[cue_templates]]
+/// genres = ["House", "Techno"]
+/// cues = [
+///     { bars = 0, name = "Start" },
+///     { bars = 32, name = "Drop" },
+/// ]
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+pub struct CueTemplate {
+    /// Only apply to tracks with one of these genres
+    #[serde(default)]
+    pub genres: Vec<String>,
+    /// Only apply to tracks inside a directory with one of these names
+    #[serde(default)]
+    pub folders: Vec<String>,
+    pub cues: Vec<TemplateCue>,
+}
+
+/// One cue point in a template.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+pub struct TemplateCue {
+    /// Position in bars from the first beat
+    pub bars: u32,
+    /// Optional cue name
+    #[serde(default)]
+    pub name: String,
+    /// Cue colour as a hex RGB value, defaults to the Serato colour for the cue number
+    #[serde(default)]
+    pub color: Option<String>,
+}
+
+impl CueTemplate {
+    /// Check if the template applies to a track with the given genre and path.
+    ///
+    /// A template without genres and folders applies to all tracks.
+    #[must_use]
+    pub fn matches(&self, genre: &str, path: &Path) -> bool {
+        if self.genres.is_empty() && self.folders.is_empty() {
+            return true;
+        }
+        let genre_match = self.genres.iter().any(|name| name.eq_ignore_ascii_case(genre.trim()));
+        let folder_match = path.parent().into_iter().flat_map(Path::components).any(|component| {
+            let component = component.as_os_str().to_string_lossy();
+            self.folders
+                .iter()
+                .any(|folder| folder.eq_ignore_ascii_case(&component))
+        });
+        genre_match || folder_match
+    }
+}
+
+/// Get the first template matching the given track genre and path.
+#[must_use]
+pub fn find_template<'a>(templates: &'a [CueTemplate], genre: &str, path: &Path) -> Option<&'a CueTemplate> {
+    templates.iter().find(|template| template.matches(genre, path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_template() {
+        #[derive(Deserialize)]
+        struct Templates {
+            cue_templates: Vec<CueTemplate>,
+        }
+        let config = r##"
+            [[cue_templates]]
+            genres = ["House"]
+            folders = ["Club"]
+            cues = [{ bars = 0 }, { bars = 32, name = "Drop", color = "#00CC00" }]
+
+            [[cue_templates]]
+            cues = [{ bars = 0, name = "Start" }]
+        "##;
+        let templates: Templates = toml::from_str(config).expect("Failed to parse templates");
+        let templates = templates.cue_templates;
+        assert_eq!(templates[0].cues[1].bars, 32);
+        assert_eq!(templates[0].cues[1].color.as_deref(), Some("#00CC00"));
+
+        let house = find_template(&templates, "house", Path::new("/music/Other/track.mp3"));
+        assert_eq!(house, Some(&templates[0]));
+        let club = find_template(&templates, "Techno", Path::new("/music/Club/track.mp3"));
+        assert_eq!(club, Some(&templates[0]));
+        let other = find_template(&templates, "Techno", Path::new("/music/Other/track.mp3"));
+        assert_eq!(other, Some(&templates[1]));
+    }
+}
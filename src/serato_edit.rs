@@ -0,0 +1,399 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use colored::Colorize;
+use id3::{Tag, TagLike};
+
+use crate::config;
+use crate::{ColorsArgs, CueTemplateArgs, GainArgs, SeratoArgs, SeratoCommand, ShiftGridArgs, StripArgs};
+
+use track_rename::prompt;
+use track_rename::serato::colors::{self, ColorRule};
+use track_rename::serato::crates::{self, SeratoCrate};
+use track_rename::serato::templates::{self, CueTemplate};
+use track_rename::serato::{self, CueTemplateResult, GainOptions, SeratoData, StripOptions};
+use track_rename::track::Track;
+use track_rename::utils;
+
+/// Run Serato tag editing subcommand.
+pub fn run(args: &SeratoArgs) -> Result<()> {
+    match &args.action {
+        SeratoCommand::Strip(strip_args) => {
+            let options = StripOptions {
+                all: strip_args.all,
+                cues: strip_args.cues,
+                loops: strip_args.loops,
+                beatgrid: strip_args.beatgrid,
+            };
+            if !(options.all || options.cues || options.loops || options.beatgrid) {
+                anyhow::bail!("Nothing to strip, use --all, --cues, --loops, or --beatgrid");
+            }
+            let root = utils::resolve_input_path(&strip_args.path)?;
+            strip(&root, options, strip_args);
+        }
+        SeratoCommand::Colors(colors_args) => {
+            let rules: Vec<ColorRule> = config::serato_color_rules()
+                .into_iter()
+                .filter(ColorRule::has_actions)
+                .collect();
+            if rules.is_empty() {
+                anyhow::bail!("No colour rules found, add `color_rules` to the user config");
+            }
+            let serato_dir = match &colors_args.serato_dir {
+                Some(dir) => dir.clone(),
+                None => crates::default_serato_dir().context("Failed to get Serato library directory")?,
+            };
+            let root = utils::resolve_input_path(&colors_args.path)?;
+            apply_color_rules(&root, &serato_dir, &rules, colors_args)?;
+        }
+        SeratoCommand::Gain(gain_args) => {
+            let options = GainOptions {
+                copy_auto_gain: gain_args.copy_auto,
+                max_gain: gain_args.clamp,
+            };
+            if !options.copy_auto_gain && options.max_gain.is_none() {
+                anyhow::bail!("No gain change given, use --copy-auto or --clamp");
+            }
+            let root = utils::resolve_input_path(&gain_args.path)?;
+            rewrite_gain(&root, options, gain_args);
+        }
+        SeratoCommand::ShiftGrid(shift_args) => {
+            if shift_args.ms == 0 {
+                anyhow::bail!("Offset must not be zero");
+            }
+            let root = utils::resolve_input_path(&shift_args.path)?;
+            shift_grid(&root, shift_args);
+        }
+        SeratoCommand::CueTemplate(template_args) => {
+            let cue_templates: Vec<CueTemplate> = config::serato_cue_templates()
+                .into_iter()
+                .filter(|template| !template.cues.is_empty())
+                .collect();
+            if cue_templates.is_empty() {
+                anyhow::bail!("No cue templates found, add `cue_templates` to the user config");
+            }
+            let root = utils::resolve_input_path(&template_args.path)?;
+            apply_cue_templates(&root, &cue_templates, template_args);
+        }
+    }
+    Ok(())
+}
+
+/// Remove Serato data from all tracks under the given path.
+fn strip(root: &Path, options: StripOptions, args: &StripArgs) {
+    let tracks = utils::collect_tracks(root);
+    if tracks.is_empty() {
+        println!("{}", "No audio files found".yellow());
+        return;
+    }
+
+    let mut changes = Vec::new();
+    for track in &tracks {
+        let Some(mut file_tags) = utils::read_tags(track, args.verbose) else {
+            continue;
+        };
+        match serato::strip_serato_data(&mut file_tags, options) {
+            Ok(result) if result.is_empty() => {
+                if args.verbose {
+                    println!("{}: {}", track, "no Serato data".green());
+                }
+            }
+            Ok(result) => {
+                println!("{track}: {}", result.to_string().yellow());
+                changes.push((track, file_tags));
+            }
+            Err(error) => utils::print_error(&format!("{track}: {error:#}")),
+        }
+    }
+
+    if changes.is_empty() {
+        println!("{}", "Nothing to strip".green());
+        return;
+    }
+    if args.print || !(args.force || prompt::confirm()) {
+        return;
+    }
+
+    let mut num_written = 0;
+    for (track, file_tags) in &changes {
+        match write_tags(&track.path, file_tags, !args.no_backup) {
+            Ok(()) => num_written += 1,
+            Err(error) => utils::print_error(&format!("{track}: {error:#}")),
+        }
+    }
+    println!("{}", format!("Stripped Serato data from {num_written} files").green());
+}
+
+/// Rewrite the Serato manual gain for all tracks under the given path.
+fn rewrite_gain(root: &Path, options: GainOptions, args: &GainArgs) {
+    let tracks = utils::collect_tracks(root);
+    let mut changes = Vec::new();
+    for track in &tracks {
+        let Some(mut file_tags) = utils::read_tags(track, args.verbose) else {
+            continue;
+        };
+        match serato::rewrite_gain(&mut file_tags, options) {
+            Ok(Some((original, updated))) => {
+                println!(
+                    "{track}: {:.3} dB -> {} (auto gain {:.3} dB)",
+                    original.gain,
+                    format!("{:.3} dB", updated.gain).yellow(),
+                    updated.auto_gain
+                );
+                changes.push((track, file_tags));
+            }
+            Ok(None) => {
+                if args.verbose {
+                    println!("{}: {}", track, "unchanged".green());
+                }
+            }
+            Err(error) => utils::print_error(&format!("{track}: {error:#}")),
+        }
+    }
+
+    if changes.is_empty() {
+        println!("{}", "No gain changes".green());
+        return;
+    }
+    if args.print || !(args.force || prompt::confirm()) {
+        return;
+    }
+
+    let mut num_written = 0;
+    for (track, file_tags) in &changes {
+        match write_tags(&track.path, file_tags, !args.no_backup) {
+            Ok(()) => num_written += 1,
+            Err(error) => utils::print_error(&format!("{track}: {error:#}")),
+        }
+    }
+    println!("{}", format!("Updated gain for {num_written} files").green());
+}
+
+/// Shift Serato markers for all tracks under the given path.
+fn shift_grid(root: &Path, args: &ShiftGridArgs) {
+    let tracks = utils::collect_tracks(root);
+    let mut changes = Vec::new();
+    for track in &tracks {
+        let Some(mut file_tags) = utils::read_tags(track, args.verbose) else {
+            continue;
+        };
+        match serato::shift_markers(&mut file_tags, args.ms) {
+            Ok(result) if result.is_empty() => {
+                if args.verbose {
+                    println!("{}: {}", track, "no Serato markers".green());
+                }
+            }
+            Ok(result) => {
+                println!("{track}: {}", result.to_string().yellow());
+                changes.push((track, file_tags));
+            }
+            Err(error) => utils::print_error(&format!("{track}: {error:#}")),
+        }
+    }
+
+    if changes.is_empty() {
+        println!("{}", "No markers to shift".green());
+        return;
+    }
+    if args.print || !(args.force || prompt::confirm()) {
+        return;
+    }
+
+    let mut num_written = 0;
+    for (track, file_tags) in &changes {
+        match write_tags(&track.path, file_tags, !args.no_backup) {
+            Ok(()) => num_written += 1,
+            Err(error) => utils::print_error(&format!("{track}: {error:#}")),
+        }
+    }
+    println!(
+        "{}",
+        format!("Shifted markers by {} ms for {num_written} files", args.ms).green()
+    );
+}
+
+/// Add template cue points to tracks without cues.
+fn apply_cue_templates(root: &Path, cue_templates: &[CueTemplate], args: &CueTemplateArgs) {
+    let tracks = utils::collect_tracks(root);
+    let mut changes = Vec::new();
+    for track in &tracks {
+        let Some(mut file_tags) = utils::read_tags(track, args.verbose) else {
+            continue;
+        };
+        let genre = file_tags.genre().unwrap_or_default().to_string();
+        let Some(template) = templates::find_template(cue_templates, &genre, &track.path) else {
+            continue;
+        };
+        match serato::apply_cue_template(&mut file_tags, template) {
+            Ok(CueTemplateResult::Added(0)) => {}
+            Ok(CueTemplateResult::Added(count)) => {
+                println!("{track}: {}", format!("{count} cues").yellow());
+                changes.push((track, file_tags));
+            }
+            Ok(CueTemplateResult::HasCues) => {
+                if args.verbose {
+                    println!("{}: {}", track, "has cues".green());
+                }
+            }
+            Ok(CueTemplateResult::NoBeatGrid) => {
+                if args.verbose {
+                    println!("{}: {}", track, "no beatgrid".yellow());
+                }
+            }
+            Err(error) => utils::print_error(&format!("{track}: {error:#}")),
+        }
+    }
+
+    if changes.is_empty() {
+        println!("{}", "No cues to add".green());
+        return;
+    }
+    if args.print || !(args.force || prompt::confirm()) {
+        return;
+    }
+
+    let mut num_written = 0;
+    for (track, file_tags) in &changes {
+        match write_tags(&track.path, file_tags, !args.no_backup) {
+            Ok(()) => num_written += 1,
+            Err(error) => utils::print_error(&format!("{track}: {error:#}")),
+        }
+    }
+    println!("{}", format!("Added cues for {num_written} files").green());
+}
+
+/// Set tags and add tracks to crates based on the Serato track colour.
+fn apply_color_rules(root: &Path, serato_dir: &Path, rules: &[ColorRule], args: &ColorsArgs) -> Result<()> {
+    if args.verbose {
+        println!("{}", "Colour rules:".bold());
+        for rule in rules {
+            println!("  {rule}");
+        }
+    }
+
+    let tracks = utils::collect_tracks(root);
+    let mut matched = 0;
+    // Only the new values are kept so the full tags with artwork are not held in memory for every file
+    let mut tag_changes: Vec<(&Track, Option<&str>, Option<&str>)> = Vec::new();
+    let mut crate_tracks: BTreeMap<&str, Vec<&Path>> = BTreeMap::new();
+    for track in &tracks {
+        let Some(file_tags) = utils::read_tags(track, args.verbose) else {
+            continue;
+        };
+        let Some(color) = SeratoData::parse(&file_tags).and_then(|data| data.track_color()) else {
+            continue;
+        };
+        let Some(rule) = colors::find_rule(rules, &color) else {
+            continue;
+        };
+        matched += 1;
+
+        let mut changes = Vec::new();
+        let genre = rule.genre.as_deref().filter(|genre| file_tags.genre() != Some(*genre));
+        if let Some(genre) = genre {
+            changes.push(format!("genre: {genre}"));
+        }
+        let grouping = rule
+            .grouping
+            .as_deref()
+            .filter(|grouping| file_tags.get("TIT1").and_then(|frame| frame.content().text()) != Some(*grouping));
+        if let Some(grouping) = grouping {
+            changes.push(format!("grouping: {grouping}"));
+        }
+        if let Some(crate_name) = &rule.crate_name {
+            crate_tracks.entry(crate_name).or_default().push(&track.path);
+        }
+        if !changes.is_empty() {
+            println!("{track} {}: {}", color.dimmed(), changes.join(", ").yellow());
+            tag_changes.push((track, genre, grouping));
+        } else if args.verbose {
+            println!("{track} {}: {}", color.dimmed(), "tags up to date".green());
+        }
+    }
+
+    let mut crate_changes = Vec::new();
+    for (name, paths) in crate_tracks {
+        let crate_path = crates::crate_file_path(serato_dir, name);
+        let mut serato_crate = if crate_path.is_file() {
+            SeratoCrate::read(&crate_path)?
+        } else {
+            SeratoCrate::new()
+        };
+        let added = paths
+            .iter()
+            .filter(|path| serato_crate.add_track(path, &crate_path))
+            .count();
+        if added > 0 {
+            println!("Crate {}: {added} added", crates::crate_name(&crate_path).cyan());
+            crate_changes.push((crate_path, serato_crate));
+        }
+    }
+
+    println!("{matched} tracks matched colour rules");
+    if tag_changes.is_empty() && crate_changes.is_empty() {
+        println!("{}", "Nothing to change".green());
+        return Ok(());
+    }
+    if args.print || !(args.force || prompt::confirm()) {
+        return Ok(());
+    }
+
+    let mut num_written = 0;
+    for (track, genre, grouping) in &tag_changes {
+        let Some(mut file_tags) = utils::read_tags(track, args.verbose) else {
+            continue;
+        };
+        if let Some(genre) = genre {
+            file_tags.set_genre(*genre);
+        }
+        if let Some(grouping) = grouping {
+            file_tags.set_text("TIT1", *grouping);
+        }
+        match write_tags(&track.path, &file_tags, !args.no_backup) {
+            Ok(()) => num_written += 1,
+            Err(error) => utils::print_error(&format!("{track}: {error:#}")),
+        }
+    }
+    for (crate_path, serato_crate) in &crate_changes {
+        serato_crate.write(crate_path)?;
+    }
+    println!(
+        "{}",
+        format!(
+            "Updated tags for {num_written} files and {} crates",
+            crate_changes.len()
+        )
+        .green()
+    );
+    Ok(())
+}
+
+/// Write tags after backing up the original file.
+///
+/// The written tag is read back,
+/// and the file is restored to its contents before this write if it can not be parsed.
+fn write_tags(path: &Path, file_tags: &Tag, backup: bool) -> Result<()> {
+    let original = fs::read(path).context("Failed to read file")?;
+    let backup_path = backup_path(path);
+    // Keep the oldest backup since it is the one with the original data
+    if backup && !backup_path.exists() {
+        fs::write(&backup_path, &original).context("Failed to back up file")?;
+    }
+    file_tags
+        .write_to_path(path, file_tags.version())
+        .context("Failed to write tags")?;
+    if let Err(error) = Tag::read_from_path(path) {
+        fs::write(path, &original).context("Failed to restore file")?;
+        anyhow::bail!("Written tags could not be read, restored previous file contents: {error}");
+    }
+    Ok(())
+}
+
+/// Backup path next to the original file, for example `Song.mp3.backup`.
+fn backup_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".backup");
+    path.with_file_name(name)
+}
@@ -0,0 +1,365 @@
+use std::f64::consts::PI;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+
+use crate::audio::{AiffInfo, WavInfo};
+use crate::error::{Result, TrackRenameError};
+use crate::file_format::FileFormat;
+use crate::riff;
+
+/// Number of samples in each analysed window.
+pub const WINDOW_SIZE: usize = 4096;
+
+/// Frequency in Hz below which a lossless file is reported as a likely MP3 transcode.
+pub const DEFAULT_MIN_CUTOFF: u32 = 17000;
+
+/// Number of windows spread over the track.
+const WINDOW_COUNT: usize = 32;
+
+/// How far below the midrange level the spectrum needs to drop to count as the cutoff.
+const CUTOFF_DROP_DB: f64 = 50.0;
+
+/// Number of bins averaged when searching for the cutoff to ignore single peaks.
+const SMOOTHING_BINS: usize = 8;
+
+/// Average power spectrum of a track.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Spectrum {
+    pub sample_rate: u32,
+    /// Level in dB for each frequency bin from 0 Hz to the Nyquist frequency
+    pub levels: Vec<f64>,
+}
+
+/// Uncompressed PCM audio data location in an AIFF or WAV file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct PcmData {
+    offset: u64,
+    frames: u64,
+    channels: u16,
+    bytes_per_sample: u16,
+    sample_rate: u32,
+    big_endian: bool,
+}
+
+impl Spectrum {
+    /// Calculate the average spectrum from windows spread over an AIFF or WAV file.
+    ///
+    /// Returns `None` for lossy formats and files that are too short to analyse.
+    pub fn from_path(path: &Path, format: &FileFormat) -> Result<Option<Self>> {
+        let read_error = |e| TrackRenameError::io(format!("Failed to read file: {}", path.display()), e);
+        let mut file = File::open(path).map_err(read_error)?;
+        let pcm = match format {
+            FileFormat::Mp3 => return Ok(None),
+            FileFormat::Aif => PcmData::from_aiff(&mut file)?,
+            FileFormat::Wav => PcmData::from_wav(&mut file)?,
+        };
+        let window_count = WINDOW_COUNT.min(usize::try_from(pcm.frames).unwrap_or(usize::MAX) / WINDOW_SIZE);
+        if window_count == 0 {
+            return Ok(None);
+        }
+        // Skip the start and end of the track which are often silent
+        let start = pcm.frames / 10;
+        let span = pcm.frames.saturating_sub(start * 2 + WINDOW_SIZE as u64);
+        let mut windows = Vec::with_capacity(window_count);
+        for index in 0..window_count {
+            let frame = start + span * index as u64 / window_count as u64;
+            windows.push(pcm.read_window(&mut file, frame).map_err(read_error)?);
+        }
+        Ok(Some(Self::from_windows(&windows, pcm.sample_rate)))
+    }
+
+    /// Calculate the average spectrum from windows of mono samples in the range -1.0 to 1.0.
+    ///
+    /// Each window should have `WINDOW_SIZE` samples.
+    #[must_use]
+    pub fn from_windows(windows: &[Vec<f64>], sample_rate: u32) -> Self {
+        let mut power = vec![0.0; WINDOW_SIZE / 2];
+        for window in windows {
+            let mut bins: Vec<(f64, f64)> = window
+                .iter()
+                .take(WINDOW_SIZE)
+                .enumerate()
+                .map(|(index, sample)| (sample * hann(index), 0.0))
+                .collect();
+            bins.resize(WINDOW_SIZE, (0.0, 0.0));
+            fft(&mut bins);
+            for (total, (re, im)) in power.iter_mut().zip(bins) {
+                *total += re.mul_add(re, im * im);
+            }
+        }
+        let count = windows.len().max(1) as f64;
+        let levels = power
+            .into_iter()
+            .map(|total| 10.0 * (total / count).max(1e-20).log10())
+            .collect();
+        Self { sample_rate, levels }
+    }
+
+    /// Frequency of the bin in Hz.
+    #[must_use]
+    pub fn bin_frequency(&self, bin: usize) -> f64 {
+        bin as f64 * f64::from(self.sample_rate) / WINDOW_SIZE as f64
+    }
+
+    /// Highest frequency with content close to the midrange level.
+    ///
+    /// Returns `None` for silent audio.
+    #[must_use]
+    pub fn cutoff(&self) -> Option<f64> {
+        let reference = self.midrange_level()?;
+        let smoothed: Vec<f64> = self
+            .levels
+            .windows(SMOOTHING_BINS)
+            .map(|bins| bins.iter().sum::<f64>() / SMOOTHING_BINS as f64)
+            .collect();
+        let bin = smoothed
+            .iter()
+            .rposition(|level| *level > reference - CUTOFF_DROP_DB)
+            .map_or(0, |bin| bin + SMOOTHING_BINS / 2);
+        Some(self.bin_frequency(bin))
+    }
+
+    /// Check if the spectrum ends clearly below the Nyquist frequency like MP3 encoded audio.
+    #[must_use]
+    pub fn is_transcoded(&self, min_cutoff: u32) -> bool {
+        let nyquist = f64::from(self.sample_rate) / 2.0;
+        let min_cutoff = f64::from(min_cutoff);
+        nyquist > min_cutoff && self.cutoff().is_some_and(|cutoff| cutoff < min_cutoff)
+    }
+
+    /// Average level between 1 and 5 kHz.
+    fn midrange_level(&self) -> Option<f64> {
+        let levels: Vec<f64> = (0..self.levels.len())
+            .filter(|&bin| (1000.0..=5000.0).contains(&self.bin_frequency(bin)))
+            .map(|bin| self.levels[bin])
+            .collect();
+        let level = levels.iter().sum::<f64>() / levels.len().max(1) as f64;
+        // Power floor is -200 dB
+        (level > -150.0).then_some(level)
+    }
+}
+
+impl PcmData {
+    fn from_aiff(file: &mut File) -> Result<Self> {
+        let mut header = [0_u8; 12];
+        file.read_exact(&mut header).map_err(invalid_audio)?;
+        if &header[0..4] != b"FORM" || !matches!(&header[8..12], b"AIFF" | b"AIFC") {
+            return Err(TrackRenameError::InvalidAudio("Not an AIFF file".to_string()));
+        }
+        let mut comm = None;
+        let mut ssnd = None;
+        let mut chunk_header = [0_u8; 8];
+        while file.read_exact(&mut chunk_header).is_ok() {
+            let size = u32::from_be_bytes([chunk_header[4], chunk_header[5], chunk_header[6], chunk_header[7]]);
+            let position = file.stream_position().map_err(invalid_audio)?;
+            match &chunk_header[0..4] {
+                b"COMM" => {
+                    let mut data = vec![0_u8; size as usize];
+                    file.read_exact(&mut data).map_err(invalid_audio)?;
+                    comm = Some(AiffInfo::parse_comm(&data)?);
+                }
+                // Sound data starts with an offset and block size
+                b"SSND" => ssnd = Some((position + 8, u64::from(size.saturating_sub(8)))),
+                _ => {}
+            }
+            file.seek(SeekFrom::Start(position + u64::from(size) + u64::from(size % 2)))
+                .map_err(invalid_audio)?;
+        }
+        let (Some(info), Some((offset, size))) = (comm, ssnd) else {
+            return Err(TrackRenameError::InvalidAudio(
+                "AIFF file is missing COMM or SSND chunk".to_string(),
+            ));
+        };
+        let big_endian = match info
+            .compression
+            .as_ref()
+            .map(|(compression_type, _)| compression_type.as_str())
+        {
+            None | Some("NONE" | "twos") => true,
+            Some("sowt") => false,
+            Some(compression_type) => {
+                return Err(TrackRenameError::UnsupportedFormat(format!(
+                    "Compressed AIFC audio: {compression_type}"
+                )))
+            }
+        };
+        Self::new(
+            offset,
+            size,
+            info.channels,
+            info.bit_depth,
+            info.sample_rate.round() as u32,
+            big_endian,
+        )
+    }
+
+    fn from_wav(file: &mut File) -> Result<Self> {
+        let chunks = riff::read_chunks(file).map_err(invalid_audio)?;
+        let fmt = chunks
+            .iter()
+            .find(|chunk| &chunk.id == b"fmt ")
+            .ok_or_else(|| TrackRenameError::InvalidAudio("WAV file does not contain a fmt chunk".to_string()))?;
+        let fmt_data = riff::read_chunk_data(file, fmt).map_err(invalid_audio)?;
+        let info = WavInfo::parse_fmt(&fmt_data)?;
+        // PCM or extensible format
+        let format_tag = u16::from_le_bytes([fmt_data[0], fmt_data[1]]);
+        if !matches!(format_tag, 1 | 0xFFFE) {
+            return Err(TrackRenameError::UnsupportedFormat(format!(
+                "WAV audio format: {format_tag:#06x}"
+            )));
+        }
+        let data = chunks
+            .iter()
+            .find(|chunk| &chunk.id == b"data")
+            .ok_or_else(|| TrackRenameError::InvalidAudio("WAV file does not contain a data chunk".to_string()))?;
+        Self::new(
+            data.data_offset(),
+            u64::from(data.size),
+            info.channels,
+            info.bit_depth,
+            info.sample_rate,
+            false,
+        )
+    }
+
+    fn new(offset: u64, size: u64, channels: u16, bit_depth: u16, sample_rate: u32, big_endian: bool) -> Result<Self> {
+        let bytes_per_sample = bit_depth.div_ceil(8);
+        if channels == 0 || !(1..=4).contains(&bytes_per_sample) {
+            return Err(TrackRenameError::UnsupportedFormat(format!(
+                "{channels} channels with {bit_depth} bits per sample"
+            )));
+        }
+        Ok(Self {
+            offset,
+            frames: size / u64::from(channels * bytes_per_sample),
+            channels,
+            bytes_per_sample,
+            sample_rate,
+            big_endian,
+        })
+    }
+
+    /// Read a window of samples starting from the frame and mix them to mono.
+    fn read_window(&self, file: &mut File, frame: u64) -> std::io::Result<Vec<f64>> {
+        let frame_size = usize::from(self.channels * self.bytes_per_sample);
+        let mut data = vec![0_u8; WINDOW_SIZE * frame_size];
+        file.seek(SeekFrom::Start(self.offset + frame * frame_size as u64))?;
+        file.read_exact(&mut data)?;
+        let sample_size = usize::from(self.bytes_per_sample);
+        let scale = f64::from(1_u32 << (sample_size * 8 - 1)) * f64::from(self.channels);
+        Ok(data
+            .chunks_exact(frame_size)
+            .map(|frame| {
+                frame
+                    .chunks_exact(sample_size)
+                    .map(|sample| f64::from(self.decode_sample(sample)))
+                    .sum::<f64>()
+                    / scale
+            })
+            .collect())
+    }
+
+    fn decode_sample(&self, bytes: &[u8]) -> i32 {
+        let mut value = [0_u8; 4];
+        // Place the sample in the high bytes so the sign is kept
+        if self.big_endian {
+            value[..bytes.len()].copy_from_slice(bytes);
+            i32::from_be_bytes(value) >> ((4 - bytes.len()) * 8)
+        } else {
+            value[4 - bytes.len()..].copy_from_slice(bytes);
+            i32::from_le_bytes(value) >> ((4 - bytes.len()) * 8)
+        }
+    }
+}
+
+fn invalid_audio(error: std::io::Error) -> TrackRenameError {
+    TrackRenameError::io("Failed to read audio data", error)
+}
+
+fn hann(index: usize) -> f64 {
+    0.5f64.mul_add(-(2.0 * PI * index as f64 / (WINDOW_SIZE - 1) as f64).cos(), 0.5)
+}
+
+/// In-place radix-2 FFT for complex values given as (real, imaginary).
+///
+/// The length needs to be a power of two.
+fn fft(values: &mut [(f64, f64)]) {
+    let n = values.len();
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            values.swap(i, j);
+        }
+    }
+    let mut len = 2;
+    while len <= n {
+        let angle = -2.0 * PI / len as f64;
+        for start in (0..n).step_by(len) {
+            for k in 0..len / 2 {
+                let (sin, cos) = (angle * k as f64).sin_cos();
+                let (re, im) = values[start + k + len / 2];
+                let twiddled = (re.mul_add(cos, -(im * sin)), re.mul_add(sin, im * cos));
+                let even = values[start + k];
+                values[start + k] = (even.0 + twiddled.0, even.1 + twiddled.1);
+                values[start + k + len / 2] = (even.0 - twiddled.0, even.1 - twiddled.1);
+            }
+        }
+        len <<= 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Windows with sine waves every 250 Hz up to the maximum frequency.
+    fn sine_windows(sample_rate: u32, max_frequency: u32) -> Vec<Vec<f64>> {
+        let frequencies: Vec<f64> = (1..=max_frequency / 250).map(|step| f64::from(step * 250)).collect();
+        (0..4)
+            .map(|window| {
+                (0..WINDOW_SIZE)
+                    .map(|index| {
+                        let time = (window * WINDOW_SIZE + index) as f64 / f64::from(sample_rate);
+                        frequencies
+                            .iter()
+                            .map(|frequency| (2.0 * PI * frequency * time).sin())
+                            .sum::<f64>()
+                            / frequencies.len() as f64
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_cutoff() {
+        let spectrum = Spectrum::from_windows(&sine_windows(44100, 16000), 44100);
+        let cutoff = spectrum.cutoff().expect("Missing cutoff");
+        assert!((15500.0..16500.0).contains(&cutoff), "cutoff {cutoff}");
+        assert!(spectrum.is_transcoded(DEFAULT_MIN_CUTOFF));
+
+        let spectrum = Spectrum::from_windows(&sine_windows(44100, 21000), 44100);
+        let cutoff = spectrum.cutoff().expect("Missing cutoff");
+        assert!(cutoff > 20500.0, "cutoff {cutoff}");
+        assert!(!spectrum.is_transcoded(DEFAULT_MIN_CUTOFF));
+
+        let silence = Spectrum::from_windows(&[vec![0.0; WINDOW_SIZE]], 44100);
+        assert_eq!(silence.cutoff(), None);
+        assert!(!silence.is_transcoded(DEFAULT_MIN_CUTOFF));
+    }
+
+    #[test]
+    fn test_decode_sample() {
+        let pcm = |big_endian| PcmData::new(0, 0, 1, 24, 44100, big_endian).expect("Valid format");
+        assert_eq!(pcm(true).decode_sample(&[0xFF, 0xFF, 0xFE]), -2);
+        assert_eq!(pcm(false).decode_sample(&[0x00, 0x00, 0x80]), -(1 << 23));
+        assert_eq!(pcm(false).decode_sample(&[0x01, 0x00, 0x00]), 1);
+    }
+}
@@ -1,61 +1,210 @@
+use std::collections::{BTreeMap, HashMap};
+use std::env;
+use std::fmt;
 use std::fs;
 use std::path::{Path, PathBuf};
-use std::sync::LazyLock;
+use std::sync::{LazyLock, Mutex, MutexGuard, OnceLock, PoisonError};
+use std::time::{SystemTime, UNIX_EPOCH};
 
-use dashmap::DashMap;
+use dashmap::{DashMap, DashSet};
+use rand::seq::IteratorRandom;
 use rayon::prelude::*;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
 
+use crate::error::{Result, TrackRenameError};
+use crate::formatting::RULES_VERSION;
+use crate::state_store::{Revision, StateLocation};
+use crate::tags::TrackTags;
 use crate::track::TrackMetadata;
-use crate::track::VERSION;
+use crate::utils;
 
-const STATE_FILE_DIR: &str = "track-rename";
 #[cfg(not(test))]
 const STATE_FILE_NAME: &str = "state.json";
 #[cfg(test)]
 const STATE_FILE_NAME: &str = "test_state.json";
+#[cfg(not(test))]
+const HISTORY_FILE_NAME: &str = "renames.json";
+#[cfg(test)]
+const HISTORY_FILE_NAME: &str = "test_renames.json";
+#[cfg(not(test))]
+const TAG_HISTORY_FILE_NAME: &str = "tag_history.json";
+#[cfg(test)]
+const TAG_HISTORY_FILE_NAME: &str = "test_tag_history.json";
 
-static STATE_PATH: LazyLock<PathBuf> = LazyLock::new(|| {
-    dirs::data_dir()
-        .expect("Failed to get data directory path")
-        .join(STATE_FILE_DIR)
-        .join(STATE_FILE_NAME)
+/// Number of times saving is retried when another machine writes the same file concurrently.
+const MAX_SAVE_ATTEMPTS: usize = 5;
+
+static LOCATION: OnceLock<StateLocation> = OnceLock::new();
+
+static USER: LazyLock<String> = LazyLock::new(|| {
+    if let Ok(user) = env::var("TRACK_RENAME_USER") {
+        if !user.trim().is_empty() {
+            return user.trim().to_string();
+        }
+    }
+    let user = env::var("USER")
+        .or_else(|_| env::var("USERNAME"))
+        .unwrap_or_else(|_| "unknown".to_string());
+    let host = env::var("HOSTNAME")
+        .or_else(|_| env::var("COMPUTERNAME"))
+        .or_else(|_| fs::read_to_string("/etc/hostname"))
+        .map(|host| host.trim().to_string())
+        .unwrap_or_default();
+    if host.is_empty() {
+        user
+    } else {
+        format!("{user}@{host}")
+    }
 });
 
+/// User and machine recorded for state entries and renames, like `user@host`.
+///
+/// Set `TRACK_RENAME_USER` to override it,
+/// for example when a shared studio library is processed from a common account.
+#[must_use]
+pub fn user() -> &'static str {
+    USER.as_str()
+}
+
+/// Use a shared location for the state and rename history instead of the user data directory.
+///
+/// Must be called before the state is loaded.
+/// Returns false if the location was already set.
+#[allow(clippy::must_use_candidate)]
+pub fn set_location(location: StateLocation) -> bool {
+    LOCATION.set(location).is_ok()
+}
+
+/// Location where the state and rename history files are stored.
+#[must_use]
+pub fn location() -> &'static StateLocation {
+    LOCATION.get_or_init(StateLocation::default)
+}
+
 /// Maintain a map of processed tracks between program runs.
 ///
-/// Enables skipping tracks that have already been processed with the same program version,
+/// Enables skipping tracks that have already been processed with the same formatting rules version,
 /// in case they have not been modified since then.
 #[derive(Debug, Default)]
 pub struct State {
     inner: DashMap<PathBuf, TrackMetadata>,
+    /// Paths inserted in this run, which take precedence when merging with a concurrently saved state
+    updated: DashSet<PathBuf>,
+    /// Entries with a track ID whose file no longer exists, by ID
+    orphans: DashMap<String, (PathBuf, TrackMetadata)>,
+    /// Revision of the state file when it was loaded or last saved
+    revision: Mutex<Revision>,
+}
+
+/// State entry that does not match the file on disk.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StateAnomaly {
+    /// No file at the recorded path
+    Missing(PathBuf),
+    /// File size or modified time differs from the recorded values
+    Changed {
+        path: PathBuf,
+        recorded: Box<TrackMetadata>,
+        actual: Box<TrackMetadata>,
+    },
+}
+
+/// Result of checking a sample of state entries against the files.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StateCheck {
+    /// Number of entries checked
+    pub checked: usize,
+    pub anomalies: Vec<StateAnomaly>,
 }
 
 impl State {
     /// Load the state from the saved file, filtering out non-existent paths.
+    ///
+    /// Entries for missing paths are kept for a shared location,
+    /// since they can belong to files that only exist on another machine.
+    /// Missing paths with a track ID are kept aside for finding files renamed outside track-rename.
     #[must_use]
     pub fn load() -> Self {
-        let inner: DashMap<PathBuf, TrackMetadata> = Self::read_state()
+        let (inner, revision) = Self::read_state();
+        let shared = *location() != StateLocation::default();
+        let orphans = DashMap::new();
+        let inner: DashMap<PathBuf, TrackMetadata> = inner
             .into_par_iter()
-            .filter(|(path, _)| path.exists())
+            .filter(|(path, metadata)| {
+                if path.exists() {
+                    return true;
+                }
+                if !metadata.id.is_empty() {
+                    orphans.insert(metadata.id.clone(), (path.clone(), metadata.clone()));
+                }
+                shared
+            })
             .collect();
 
-        Self { inner }
+        Self {
+            inner,
+            orphans,
+            revision: Mutex::new(revision),
+            ..Self::default()
+        }
     }
 
     /// Save the current state to a file.
-    pub fn save(&self) -> anyhow::Result<()> {
-        let parent_dir = Self::state_path().parent().expect("Failed to get state parent path");
-        fs::create_dir_all(parent_dir)?;
-        let data = serde_json::to_string(&self.inner)?;
-        fs::write(Self::state_path(), data)?;
-        Ok(())
+    ///
+    /// If another machine saved the state after it was loaded,
+    /// the entries updated in this run are merged into the newer state instead of overwriting it.
+    pub fn save(&self) -> Result<()> {
+        let mut revision = self.revision().clone();
+        let mut data = serde_json::to_string(&self.inner)
+            .map_err(|error| TrackRenameError::json("Failed to serialize state", error))?;
+        let mut merged_state = None;
+        for _ in 0..MAX_SAVE_ATTEMPTS {
+            if let Some(saved) = location().write(STATE_FILE_NAME, &data, &revision)? {
+                *self.revision() = saved;
+                // Later saves continue from the merged state so the other entries are not dropped
+                if let Some(merged) = merged_state {
+                    self.inner.clear();
+                    for (path, metadata) in merged {
+                        self.inner.insert(path, metadata);
+                    }
+                }
+                return Ok(());
+            }
+            let (contents, current) = location()
+                .read(STATE_FILE_NAME)?
+                .unwrap_or_else(|| (String::new(), Revision::Missing));
+            let mut merged: HashMap<PathBuf, TrackMetadata> = if contents.is_empty() {
+                HashMap::new()
+            } else {
+                serde_json::from_str(&contents)
+                    .map_err(|error| TrackRenameError::json("Failed to parse saved state", error))?
+            };
+            for path in self.updated.iter() {
+                if let Some(metadata) = self.inner.get(path.key()) {
+                    merged.insert(path.key().clone(), metadata.clone());
+                }
+            }
+            data = serde_json::to_string(&merged)
+                .map_err(|error| TrackRenameError::json("Failed to serialize state", error))?;
+            merged_state = Some(merged);
+            revision = current;
+        }
+        Err(TrackRenameError::Conflict(format!(
+            "State was changed by another process {MAX_SAVE_ATTEMPTS} times while saving"
+        )))
     }
 
     /// Insert a new entry into the state.
     ///
+    /// Entries without a user are attributed to the current user.
     /// Returns the old value associated with the same key if there was one.
     #[allow(clippy::must_use_candidate)]
-    pub fn insert(&self, path: PathBuf, metadata: TrackMetadata) -> Option<TrackMetadata> {
+    pub fn insert(&self, path: PathBuf, mut metadata: TrackMetadata) -> Option<TrackMetadata> {
+        if metadata.user.is_empty() {
+            user().clone_into(&mut metadata.user);
+        }
+        self.updated.insert(path.clone());
         self.inner.insert(path, metadata)
     }
 
@@ -64,11 +213,57 @@ impl State {
         self.inner.get(path).map(|entry| entry.clone())
     }
 
+    /// Check if the track has already been processed and has not been modified since.
+    ///
+    /// Compares the size and modified time fingerprint from a single stat call,
+    /// so unchanged files can be skipped without opening them.
+    /// Entries saved before file sizes were stored only compare the modified time.
+    /// The program version is ignored so releases without rule changes do not reprocess everything.
+    /// Files processed with other formatting options, like another filename separator, are not unchanged.
+    #[must_use]
+    pub fn is_unchanged(&self, path: &Path, metadata: &TrackMetadata) -> bool {
+        self.inner.get(path).is_some_and(|entry| {
+            entry.rules_version == metadata.rules_version
+                && entry.format_hash == metadata.format_hash
+                && entry.modified >= metadata.modified
+                && (entry.size == 0 || entry.size == metadata.size)
+        })
+    }
+
+    /// Move the entry of a file renamed outside track-rename to its new path.
+    ///
+    /// Returns the old path if the track ID matches an entry whose file no longer exists.
+    #[allow(clippy::must_use_candidate)]
+    pub fn relink(&self, path: &Path, id: &str) -> Option<PathBuf> {
+        let (_, (old_path, metadata)) = self.orphans.remove(id)?;
+        self.inner.remove(&old_path);
+        self.insert(path.to_path_buf(), metadata);
+        Some(old_path)
+    }
+
+    /// Returns true if some entries with a track ID point to files that no longer exist.
+    #[must_use]
+    pub fn has_orphans(&self) -> bool {
+        !self.orphans.is_empty()
+    }
+
     #[must_use]
     pub fn len(&self) -> usize {
         self.inner.len()
     }
 
+    /// Number of state entries under the directory for each user that processed them.
+    ///
+    /// Entries saved before users were recorded are counted under an empty name.
+    #[must_use]
+    pub fn user_counts(&self, root: &Path) -> BTreeMap<String, usize> {
+        let mut counts = BTreeMap::new();
+        for entry in self.inner.iter().filter(|entry| entry.key().starts_with(root)) {
+            *counts.entry(entry.value().user.clone()).or_insert(0) += 1;
+        }
+        counts
+    }
+
     #[must_use]
     pub fn is_empty(&self) -> bool {
         self.inner.is_empty()
@@ -76,48 +271,399 @@ impl State {
 
     /// Remove outdated entries from state.
     ///
-    /// Removes entries that do not exist on disk anymore or the version does not match current version.
+    /// Removes entries that do not exist on disk anymore or were processed with other formatting rules.
     /// Returns the number of elements removed.
     #[allow(clippy::must_use_candidate)]
     pub fn clean(&self) -> usize {
         let start_count = self.inner.len();
 
-        self.inner.retain(|key, value| key.exists() && value.version == VERSION);
+        self.inner
+            .retain(|key, value| key.exists() && value.rules_version == RULES_VERSION);
 
         let end_count = self.inner.len();
 
         start_count.saturating_sub(end_count)
     }
 
-    fn read_state() -> DashMap<PathBuf, TrackMetadata> {
-        Self::get_state_path().map_or_else(DashMap::new, |file_path| match fs::read_to_string(file_path) {
-            Ok(contents) => serde_json::from_str(&contents).unwrap_or_else(|err| {
-                eprintln!("Failed to parse state file: {err}");
-                DashMap::new()
-            }),
+    /// Check a random sample of the entries inserted in this run against the files on disk.
+    ///
+    /// An entry for a file that is missing or has a different size or modified time
+    /// points to a rename or write that was recorded but did not actually happen.
+    #[must_use]
+    pub fn verify_sample(&self, sample_size: usize) -> StateCheck {
+        let paths: Vec<PathBuf> = self
+            .updated
+            .iter()
+            .map(|path| path.key().clone())
+            .choose_multiple(&mut rand::rng(), sample_size);
+        let anomalies = paths
+            .par_iter()
+            .filter_map(|path| {
+                let recorded = self.get(path)?;
+                let Ok(file) = fs::metadata(path) else {
+                    return Some(StateAnomaly::Missing(path.clone()));
+                };
+                let actual = TrackMetadata {
+                    modified: utils::modified_time(&file).unwrap_or_default(),
+                    size: file.len(),
+                    ..recorded.clone()
+                };
+                let changed =
+                    actual.modified != recorded.modified || (recorded.size != 0 && actual.size != recorded.size);
+                changed.then(|| StateAnomaly::Changed {
+                    path: path.clone(),
+                    recorded: Box::new(recorded),
+                    actual: Box::new(actual),
+                })
+            })
+            .collect();
+        StateCheck {
+            checked: paths.len(),
+            anomalies,
+        }
+    }
+
+    fn revision(&self) -> MutexGuard<'_, Revision> {
+        self.revision.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn read_state() -> (DashMap<PathBuf, TrackMetadata>, Revision) {
+        match location().read(STATE_FILE_NAME) {
+            Ok(Some((contents, revision))) => {
+                let inner = serde_json::from_str(&contents).unwrap_or_else(|err| {
+                    eprintln!("Failed to parse state file: {err}");
+                    DashMap::new()
+                });
+                (inner, revision)
+            }
+            Ok(None) => (DashMap::new(), Revision::Missing),
             Err(err) => {
-                eprintln!("Failed to read state file: {err}");
-                DashMap::new()
+                eprintln!("Failed to read state file: {err:#}");
+                (DashMap::new(), Revision::Missing)
             }
-        })
+        }
+    }
+}
+
+impl fmt::Display for StateAnomaly {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Missing(path) => write!(f, "Missing file: {}", path.display()),
+            Self::Changed { path, recorded, actual } => {
+                write!(f, "Changed file: {}", path.display())?;
+                if actual.size != recorded.size {
+                    write!(f, " (size {} -> {})", recorded.size, actual.size)?;
+                }
+                if actual.modified != recorded.modified {
+                    write!(
+                        f,
+                        " (modified {} -> {})",
+                        utils::format_timestamp(recorded.modified),
+                        utils::format_timestamp(actual.modified)
+                    )?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// History of renamed and converted files over all program runs.
+///
+/// Used for fixing file references in other programs after files have been renamed.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct RenameHistory {
+    entries: Vec<RenameEntry>,
+    /// Number of entries read from the saved file
+    #[serde(skip)]
+    loaded: usize,
+    #[serde(skip)]
+    revision: Revision,
+}
+
+/// One rename from the old path to the new path.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RenameEntry {
+    pub old: PathBuf,
+    pub new: PathBuf,
+    /// Seconds since unix epoch
+    pub timestamp: u64,
+    /// User and machine that renamed the file, see [`user`].
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub user: String,
+    /// Track ID of the file, see [`crate::track_id`].
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub id: String,
+}
+
+impl RenameHistory {
+    /// Load rename history from the saved file.
+    #[must_use]
+    pub fn load() -> Self {
+        let (entries, revision) = load_entries(HISTORY_FILE_NAME, "rename history");
+        Self {
+            loaded: entries.len(),
+            entries,
+            revision,
+        }
     }
 
-    fn get_state_path() -> Option<&'static Path> {
-        Self::state_path().exists().then(Self::state_path)
+    /// Save rename history to a file.
+    ///
+    /// If another machine saved the history after it was loaded,
+    /// the entries added since loading are appended to the newer history instead.
+    pub fn save(&self) -> Result<()> {
+        save_entries(
+            HISTORY_FILE_NAME,
+            "rename history",
+            &self.entries,
+            self.loaded,
+            &self.revision,
+        )
+    }
+
+    /// Add a new rename to the history.
+    pub fn add(&mut self, old: PathBuf, new: PathBuf, id: &str) {
+        self.entries.push(RenameEntry {
+            old,
+            new,
+            timestamp: current_timestamp(),
+            user: user().to_string(),
+            id: id.to_string(),
+        });
+    }
+
+    /// Append all entries from another history.
+    pub fn extend(&mut self, other: &Self) {
+        self.entries.extend_from_slice(&other.entries);
+    }
+
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// All renames in the order they were made.
+    #[must_use]
+    pub fn entries(&self) -> &[RenameEntry] {
+        &self.entries
+    }
+
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Map each old path to its latest path,
+    /// following consecutive renames of the same file.
+    #[must_use]
+    pub fn resolved(&self) -> HashMap<PathBuf, PathBuf> {
+        let mut latest: HashMap<PathBuf, PathBuf> = HashMap::new();
+        // Current path to all previous paths of the same file
+        let mut origins: HashMap<PathBuf, Vec<PathBuf>> = HashMap::new();
+        for entry in &self.entries {
+            let mut previous = origins.remove(&entry.old).unwrap_or_default();
+            previous.push(entry.old.clone());
+            for old in &previous {
+                latest.insert(old.clone(), entry.new.clone());
+            }
+            origins.entry(entry.new.clone()).or_default().extend(previous);
+        }
+        latest.retain(|old, new| old != new);
+        latest
     }
 
-    fn state_path() -> &'static Path {
-        STATE_PATH.as_path()
+    /// All earlier paths of a file, following renames backwards from its current path.
+    #[must_use]
+    pub fn previous_paths(&self, path: &Path) -> Vec<PathBuf> {
+        let mut paths = vec![path.to_path_buf()];
+        for entry in self.entries.iter().rev() {
+            if paths.contains(&entry.new) && !paths.contains(&entry.old) {
+                paths.push(entry.old.clone());
+            }
+        }
+        paths.remove(0);
+        paths
     }
 }
 
+/// Append-only history of tag values written over all program runs.
+///
+/// Used for looking up what a track was called before it was formatted,
+/// together with the rename history for finding the earlier paths of the file.
+#[derive(Debug, Default)]
+pub struct TagHistory {
+    entries: Vec<TagEntry>,
+    /// Number of entries read from the saved file
+    loaded: usize,
+    revision: Revision,
+}
+
+/// Artist, title, album, and genre tag values.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TagValues {
+    pub artist: String,
+    pub title: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub album: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub genre: String,
+}
+
+/// Tag values before and after one write to a file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TagEntry {
+    /// Path of the file when the tags were written
+    pub path: PathBuf,
+    pub old: TagValues,
+    pub new: TagValues,
+    /// Seconds since unix epoch
+    pub timestamp: u64,
+    /// User and machine that wrote the tags, see [`user`].
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub user: String,
+    /// Track ID of the file, see [`crate::track_id`].
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub id: String,
+}
+
+impl TagHistory {
+    /// Load tag history from the saved file.
+    #[must_use]
+    pub fn load() -> Self {
+        let (entries, revision) = load_entries(TAG_HISTORY_FILE_NAME, "tag history");
+        Self {
+            loaded: entries.len(),
+            entries,
+            revision,
+        }
+    }
+
+    /// Append the entries added since loading to the saved file.
+    pub fn save(&self) -> Result<()> {
+        save_entries(
+            TAG_HISTORY_FILE_NAME,
+            "tag history",
+            &self.entries,
+            self.loaded,
+            &self.revision,
+        )
+    }
+
+    /// Add the current and formatted tag values of a track.
+    pub fn add(&mut self, path: PathBuf, id: &str, tags: &TrackTags) {
+        self.entries.push(TagEntry {
+            path,
+            old: TagValues {
+                artist: tags.current_artist.clone(),
+                title: tags.current_title.clone(),
+                album: tags.current_album.clone(),
+                genre: tags.current_genre.clone(),
+            },
+            new: TagValues {
+                artist: tags.formatted_artist.clone(),
+                title: tags.formatted_title.clone(),
+                album: tags.formatted_album.clone(),
+                genre: tags.formatted_genre.clone(),
+            },
+            timestamp: current_timestamp(),
+            user: user().to_string(),
+            id: id.to_string(),
+        });
+    }
+
+    /// Append all entries from another history.
+    pub fn extend(&mut self, other: &Self) {
+        self.entries.extend_from_slice(&other.entries);
+    }
+
+    /// All tag writes in the order they were made.
+    #[must_use]
+    pub fn entries(&self) -> &[TagEntry] {
+        &self.entries
+    }
+
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+/// History file contents, shared by the rename and tag histories.
+#[derive(Serialize, Deserialize)]
+struct HistoryFile<T> {
+    entries: T,
+}
+
+/// Seconds since unix epoch.
+pub(crate) fn current_timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default()
+}
+
+/// Read history entries, printing an error and returning no entries if the file can't be read.
+fn load_entries<T: DeserializeOwned>(name: &str, description: &str) -> (Vec<T>, Revision) {
+    match location().read(name) {
+        Ok(Some((contents, revision))) => match serde_json::from_str::<HistoryFile<Vec<T>>>(&contents) {
+            Ok(history) => (history.entries, revision),
+            Err(err) => {
+                eprintln!("Failed to parse {description} file: {err}");
+                (Vec::new(), revision)
+            }
+        },
+        Ok(None) => (Vec::new(), Revision::Missing),
+        Err(err) => {
+            eprintln!("Failed to read {description} file: {err:#}");
+            (Vec::new(), Revision::Missing)
+        }
+    }
+}
+
+/// Save history entries, appending the entries after `loaded` to a concurrently saved file.
+fn save_entries<T: Serialize + DeserializeOwned + Clone>(
+    name: &str,
+    description: &str,
+    entries: &[T],
+    loaded: usize,
+    revision: &Revision,
+) -> Result<()> {
+    let mut data = serde_json::to_string(&HistoryFile { entries })
+        .map_err(|error| TrackRenameError::json(format!("Failed to serialize {description}"), error))?;
+    let mut revision = revision.clone();
+    for _ in 0..MAX_SAVE_ATTEMPTS {
+        if location().write(name, &data, &revision)?.is_some() {
+            return Ok(());
+        }
+        let (contents, current) = location()
+            .read(name)?
+            .unwrap_or_else(|| (String::new(), Revision::Missing));
+        let mut merged: HistoryFile<Vec<T>> = if contents.is_empty() {
+            HistoryFile { entries: Vec::new() }
+        } else {
+            serde_json::from_str(&contents)
+                .map_err(|error| TrackRenameError::json(format!("Failed to parse saved {description}"), error))?
+        };
+        merged.entries.extend_from_slice(&entries[loaded..]);
+        data = serde_json::to_string(&merged)
+            .map_err(|error| TrackRenameError::json(format!("Failed to serialize {description}"), error))?;
+        revision = current;
+    }
+    Err(TrackRenameError::Conflict(format!(
+        "The {description} was changed by another process {MAX_SAVE_ATTEMPTS} times while saving"
+    )))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     fn setup_test_env() -> PathBuf {
-        let data_dir = dirs::data_dir().expect("Failed to get data directory path");
-        let state_path = data_dir.join(STATE_FILE_DIR).join(STATE_FILE_NAME);
+        let StateLocation::Directory(state_dir) = location() else {
+            panic!("Tests use the default state directory");
+        };
+        let state_path = state_dir.join(STATE_FILE_NAME);
 
         fs::create_dir_all(state_path.parent().unwrap()).unwrap();
 
@@ -128,6 +674,40 @@ mod tests {
         state_path
     }
 
+    #[test]
+    fn test_verify_sample() {
+        let test_path: PathBuf = ["tests", "files", "basic_tags", "Basic Tags - Song - 16-44.aif"]
+            .iter()
+            .collect();
+        let file = fs::metadata(&test_path).expect("Failed to read test file metadata");
+        let current = TrackMetadata {
+            modified: utils::modified_time(&file).expect("Failed to read modified time"),
+            size: file.len(),
+            ..TrackMetadata::default()
+        };
+        let missing = PathBuf::from("tests/files/basic_tags/Renamed - Song.aif");
+
+        let state = State::default();
+        state.insert(test_path.clone(), current.clone());
+        assert_eq!(state.verify_sample(10).anomalies, Vec::new());
+
+        state.insert(missing.clone(), current.clone());
+        let check = state.verify_sample(10);
+        assert_eq!(check.checked, 2);
+        assert_eq!(check.anomalies, vec![StateAnomaly::Missing(missing)]);
+
+        state.insert(
+            test_path,
+            TrackMetadata {
+                size: current.size + 1,
+                ..current
+            },
+        );
+        let check = state.verify_sample(1);
+        assert_eq!(check.checked, 1);
+        assert_eq!(check.anomalies.len(), 1);
+    }
+
     #[test]
     fn test_state() {
         // Everything is tested in a single test case since otherwise tests can fail as they all touch the same file.
@@ -142,7 +722,12 @@ mod tests {
             test_path.clone(),
             TrackMetadata {
                 modified: 123_456_789,
+                size: 1000,
                 version: "test_version".to_string(),
+                rules_version: 0,
+                format_hash: 0,
+                user: String::new(),
+                id: String::new(),
             },
         );
 
@@ -166,7 +751,12 @@ mod tests {
 
         let test_data = TrackMetadata {
             modified: 1_716_068_288,
+            size: 0,
             version: "1.0.0".to_string(),
+            rules_version: 0,
+            format_hash: 0,
+            user: String::new(),
+            id: String::new(),
         };
 
         let state = State::default();
@@ -182,5 +772,149 @@ mod tests {
             state.get(&test_path).unwrap().modified,
             loaded_state.get(&test_path).unwrap().modified
         );
+        let current = TrackMetadata {
+            modified: 1_716_068_288,
+            size: 2000,
+            version: "1.0.0".to_string(),
+            rules_version: RULES_VERSION,
+            format_hash: 0,
+            user: String::new(),
+            id: String::new(),
+        };
+        let state = State::default();
+        assert!(!state.is_unchanged(&test_path, &current));
+        state.insert(test_path.clone(), current.clone());
+        assert!(state.is_unchanged(&test_path, &current));
+        assert_eq!(state.get(&test_path).unwrap().user, user());
+        assert_eq!(state.user_counts(Path::new("tests")).get(user()), Some(&1));
+        let resized = TrackMetadata {
+            size: 2001,
+            ..current.clone()
+        };
+        assert!(!state.is_unchanged(&test_path, &resized));
+        let new_release = TrackMetadata {
+            version: "2.0.0".to_string(),
+            ..current.clone()
+        };
+        assert!(state.is_unchanged(&test_path, &new_release));
+        let new_rules = TrackMetadata {
+            rules_version: RULES_VERSION + 1,
+            ..current.clone()
+        };
+        assert!(!state.is_unchanged(&test_path, &new_rules));
+        let new_options = TrackMetadata {
+            format_hash: 1,
+            ..current.clone()
+        };
+        assert!(!state.is_unchanged(&test_path, &new_options));
+        let modified = TrackMetadata {
+            modified: current.modified + 1,
+            ..current.clone()
+        };
+        assert!(!state.is_unchanged(&test_path, &modified));
+
+        // Entries saved by another process after loading are kept when saving
+        let state_path = setup_test_env();
+        let other_path: PathBuf = ["tests", "files", "basic_tags", "Basic Tags - Song - 16-44.mp3"]
+            .iter()
+            .collect();
+        let loaded_state = State::load();
+        let other_state = State::load();
+        other_state.insert(other_path.clone(), current.clone());
+        other_state.save().expect("Failed to save state");
+        loaded_state.insert(test_path.clone(), current);
+        loaded_state.save().expect("Failed to save state");
+        let merged_state = State::load();
+        assert!(merged_state.get(&test_path).is_some());
+        assert!(merged_state.get(&other_path).is_some());
+        fs::remove_file(state_path).expect("Failed to remove state");
+    }
+
+    #[test]
+    fn test_relink_renamed_file() {
+        let state = State::default();
+        let metadata = TrackMetadata {
+            id: "abc".to_string(),
+            ..TrackMetadata::default()
+        };
+        state
+            .orphans
+            .insert("abc".to_string(), (PathBuf::from("/music/old.aif"), metadata));
+        assert!(state.has_orphans());
+        assert_eq!(state.relink(Path::new("/music/new.aif"), "other"), None);
+        assert_eq!(
+            state.relink(Path::new("/music/new.aif"), "abc"),
+            Some(PathBuf::from("/music/old.aif"))
+        );
+        assert_eq!(state.get(&PathBuf::from("/music/new.aif")).unwrap().id, "abc");
+        assert!(!state.has_orphans());
+    }
+
+    #[test]
+    fn test_rename_history_resolved() {
+        let mut history = RenameHistory::default();
+        history.add(PathBuf::from("/music/a.mp3"), PathBuf::from("/music/b.mp3"), "");
+        history.add(PathBuf::from("/music/b.mp3"), PathBuf::from("/music/c.aif"), "");
+        history.add(PathBuf::from("/music/x.mp3"), PathBuf::from("/music/y.mp3"), "");
+        history.add(PathBuf::from("/music/y.mp3"), PathBuf::from("/music/x.mp3"), "");
+        assert!(history.entries().iter().all(|entry| entry.user == user()));
+
+        let resolved = history.resolved();
+        assert_eq!(
+            resolved.get(Path::new("/music/a.mp3")),
+            Some(&PathBuf::from("/music/c.aif"))
+        );
+        assert_eq!(
+            resolved.get(Path::new("/music/b.mp3")),
+            Some(&PathBuf::from("/music/c.aif"))
+        );
+        assert_eq!(
+            resolved.get(Path::new("/music/y.mp3")),
+            Some(&PathBuf::from("/music/x.mp3"))
+        );
+        assert_eq!(resolved.get(Path::new("/music/x.mp3")), None);
+
+        assert_eq!(
+            history.previous_paths(Path::new("/music/c.aif")),
+            vec![PathBuf::from("/music/b.mp3"), PathBuf::from("/music/a.mp3")]
+        );
+        assert_eq!(
+            history.previous_paths(Path::new("/music/x.mp3")),
+            vec![PathBuf::from("/music/y.mp3")]
+        );
+        assert!(history.previous_paths(Path::new("/music/z.mp3")).is_empty());
+    }
+
+    #[test]
+    fn test_tag_history_merge() {
+        let StateLocation::Directory(state_dir) = location() else {
+            panic!("Tests use the default state directory");
+        };
+        let history_path = state_dir.join(TAG_HISTORY_FILE_NAME);
+        let _ = fs::remove_file(&history_path);
+
+        let mut tags = TrackTags::new(
+            "artist - song".to_string(),
+            "artist".to_string(),
+            "song".to_string(),
+            String::new(),
+            String::new(),
+        );
+        tags.formatted_artist = "Artist".to_string();
+        tags.formatted_title = "Song".to_string();
+
+        let mut history = TagHistory::load();
+        let mut other = TagHistory::load();
+        other.add(PathBuf::from("/music/b.aif"), "", &tags);
+        other.save().expect("Failed to save tag history");
+        history.add(PathBuf::from("/music/a.aif"), "", &tags);
+        history.save().expect("Failed to save tag history");
+
+        let loaded = TagHistory::load();
+        let paths: Vec<&Path> = loaded.entries().iter().map(|entry| entry.path.as_path()).collect();
+        assert_eq!(paths, [Path::new("/music/b.aif"), Path::new("/music/a.aif")]);
+        assert_eq!(loaded.entries()[0].old.artist, "artist");
+        assert_eq!(loaded.entries()[0].new.title, "Song");
+        fs::remove_file(&history_path).expect("Failed to remove tag history");
     }
 }
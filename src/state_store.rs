@@ -0,0 +1,350 @@
+use std::fmt;
+use std::fs::{self, OpenOptions};
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::{self, Command, Stdio};
+use std::str::FromStr;
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use crate::error::{Result, TrackRenameError};
+use crate::utils;
+
+/// How long to wait for another process to release the lock on a directory location.
+const LOCK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Lock files older than this were left behind by a process that did not finish.
+const STALE_LOCK_AGE: Duration = Duration::from_mins(2);
+
+/// Location of the state and rename history files.
+///
+/// A shared location lets several machines working on the same synced library
+/// skip files that another machine has already processed.
+/// Writes use optimistic locking: a file is only replaced if it has not changed since it was read,
+/// and otherwise the caller merges its changes with the newer contents and tries again.
+/// A directory is locked with an exclusively created lock file while checking and replacing a file,
+/// and a server checks the revision itself with a conditional request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StateLocation {
+    /// Local directory, network share, or a folder synced with a cloud storage client
+    Directory(PathBuf),
+    /// `WebDAV` or other HTTP server that supports `PUT` with `If-Match` preconditions, accessed with curl
+    Remote(String),
+}
+
+/// Version of a stored file used for detecting concurrent writes.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum Revision {
+    /// File does not exist yet
+    #[default]
+    Missing,
+    /// Hash of the file contents
+    Hash(u64),
+    /// `ETag` header from the server
+    ETag(String),
+}
+
+impl StateLocation {
+    /// Read a file, returning `None` if it does not exist.
+    pub fn read(&self, name: &str) -> Result<Option<(String, Revision)>> {
+        match self {
+            Self::Directory(dir) => Self::read_file(&dir.join(name)),
+            Self::Remote(url) => Self::get(&format!("{url}/{name}")),
+        }
+    }
+
+    /// Replace a file if it still has the expected revision.
+    ///
+    /// Returns the new revision, or `None` if the file was changed by someone else since it was read.
+    pub fn write(&self, name: &str, contents: &str, expected: &Revision) -> Result<Option<Revision>> {
+        match self {
+            Self::Directory(dir) => Self::write_file(dir, name, contents, expected),
+            Self::Remote(url) => Self::put(&format!("{url}/{name}"), contents, expected),
+        }
+    }
+
+    /// Check that the location can be read.
+    pub fn check(&self) -> Result<()> {
+        match self {
+            Self::Directory(dir) => fs::read_dir(dir)
+                .map(|_| ())
+                .map_err(|error| TrackRenameError::io(format!("Failed to read directory: {}", dir.display()), error)),
+            Self::Remote(url) => Self::get(&format!("{url}/")).map(|_| ()),
+        }
+    }
+
+    fn read_file(path: &Path) -> Result<Option<(String, Revision)>> {
+        match fs::read_to_string(path) {
+            Ok(contents) => {
+                let revision = content_hash(&contents);
+                Ok(Some((contents, revision)))
+            }
+            Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(error) => Err(TrackRenameError::io(
+                format!("Failed to read {}", path.display()),
+                error,
+            )),
+        }
+    }
+
+    fn write_file(dir: &Path, name: &str, contents: &str, expected: &Revision) -> Result<Option<Revision>> {
+        fs::create_dir_all(dir)
+            .map_err(|error| TrackRenameError::io(format!("Failed to create directory: {}", dir.display()), error))?;
+        let path = dir.join(name);
+        let temp_path = dir.join(format!(".{name}.{}.tmp", process::id()));
+        fs::write(&temp_path, contents)
+            .map_err(|error| TrackRenameError::io(format!("Failed to write {}", temp_path.display()), error))?;
+        let result = LockFile::acquire(&dir.join(format!("{name}.lock"))).and_then(|_lock| {
+            let current = Self::read_file(&path)?.map_or(Revision::Missing, |(_, revision)| revision);
+            if current != *expected {
+                return Ok(None);
+            }
+            fs::rename(&temp_path, &path)
+                .map_err(|error| TrackRenameError::io(format!("Failed to replace {}", path.display()), error))?;
+            Ok(Some(content_hash(contents)))
+        });
+        let _ = fs::remove_file(&temp_path);
+        result
+    }
+
+    fn get(url: &str) -> Result<Option<(String, Revision)>> {
+        let output = curl()
+            .args(["--dump-header", "-", url])
+            .output()
+            .map_err(|error| TrackRenameError::io("Failed to run curl", error))?;
+        if !output.status.success() {
+            return Err(request_failed(&String::from_utf8_lossy(&output.stderr)));
+        }
+        let response = String::from_utf8_lossy(&output.stdout);
+        let (headers, body) = response
+            .split_once("\r\n\r\n")
+            .ok_or_else(|| TrackRenameError::Request(format!("Invalid response from {url}")))?;
+        match status_code(headers) {
+            Some(200..=299) => {
+                let revision = header(headers, "etag").map_or_else(|| content_hash(body), Revision::ETag);
+                Ok(Some((body.to_string(), revision)))
+            }
+            Some(404) => Ok(None),
+            _ => Err(request_failed(headers.lines().next().unwrap_or_default())),
+        }
+    }
+
+    fn put(url: &str, contents: &str, expected: &Revision) -> Result<Option<Revision>> {
+        let precondition = match expected {
+            Revision::Missing => "If-None-Match: *".to_string(),
+            Revision::ETag(etag) => format!("If-Match: {etag}"),
+            Revision::Hash(_) => {
+                return Err(TrackRenameError::Request(format!(
+                    "Server did not return an ETag for {url}"
+                )));
+            }
+        };
+        let mut child = curl()
+            .args([
+                "--request",
+                "PUT",
+                "--data-binary",
+                "@-",
+                "--header",
+                &precondition,
+                "--dump-header",
+                "-",
+                url,
+            ])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|error| TrackRenameError::io("Failed to run curl", error))?;
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin
+                .write_all(contents.as_bytes())
+                .map_err(|error| TrackRenameError::io("Failed to send request", error))?;
+        }
+        let output = child
+            .wait_with_output()
+            .map_err(|error| TrackRenameError::io("Failed to run curl", error))?;
+        if !output.status.success() {
+            return Err(request_failed(&String::from_utf8_lossy(&output.stderr)));
+        }
+        let response = String::from_utf8_lossy(&output.stdout);
+        // Skip the interim "100 Continue" response before the final headers
+        let headers = response
+            .split("\r\n\r\n")
+            .find(|headers| status_code(headers).is_some_and(|code| code >= 200))
+            .unwrap_or_default();
+        match status_code(headers) {
+            Some(200..=299) => Ok(Some(
+                header(headers, "etag").map_or_else(|| content_hash(contents), Revision::ETag),
+            )),
+            Some(412) => Ok(None),
+            _ => Err(request_failed(headers.lines().next().unwrap_or_default())),
+        }
+    }
+}
+
+impl Default for StateLocation {
+    fn default() -> Self {
+        Self::Directory(
+            dirs::data_dir()
+                .expect("Failed to get data directory path")
+                .join("track-rename"),
+        )
+    }
+}
+
+impl FromStr for StateLocation {
+    type Err = TrackRenameError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+        if s.is_empty() {
+            return Err(TrackRenameError::InvalidArgument("Empty state location".to_string()));
+        }
+        if s.starts_with("http://") || s.starts_with("https://") {
+            Ok(Self::Remote(s.trim_end_matches('/').to_string()))
+        } else {
+            Ok(Self::Directory(utils::expand_home_dir(s)))
+        }
+    }
+}
+
+impl fmt::Display for StateLocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Directory(dir) => write!(f, "{}", dir.display()),
+            Self::Remote(url) => write!(f, "{url}"),
+        }
+    }
+}
+
+/// Lock file that is removed when dropped.
+struct LockFile(PathBuf);
+
+impl LockFile {
+    /// Create the lock file, waiting for another process to remove it.
+    ///
+    /// A lock file left behind by a crashed process is removed once it is old enough.
+    fn acquire(path: &Path) -> Result<Self> {
+        let start = SystemTime::now();
+        loop {
+            match OpenOptions::new().write(true).create_new(true).open(path) {
+                Ok(_) => return Ok(Self(path.to_path_buf())),
+                Err(error) if error.kind() == io::ErrorKind::AlreadyExists => {
+                    let stale = fs::metadata(path)
+                        .and_then(|metadata| metadata.modified())
+                        .ok()
+                        .and_then(|modified| modified.elapsed().ok())
+                        .is_some_and(|age| age > STALE_LOCK_AGE);
+                    if stale {
+                        let _ = fs::remove_file(path);
+                    } else if start.elapsed().unwrap_or_default() > LOCK_TIMEOUT {
+                        return Err(TrackRenameError::io(
+                            format!("Timed out waiting for lock file: {}", path.display()),
+                            error,
+                        ));
+                    } else {
+                        thread::sleep(Duration::from_millis(100));
+                    }
+                }
+                Err(error) => {
+                    return Err(TrackRenameError::io(
+                        format!("Failed to create lock file: {}", path.display()),
+                        error,
+                    ));
+                }
+            }
+        }
+    }
+}
+
+impl Drop for LockFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+/// Credentials for remote locations are read from `~/.netrc` if it exists.
+fn curl() -> Command {
+    let mut command = Command::new("curl");
+    command.args(["--silent", "--show-error", "--max-time", "60", "--netrc-optional"]);
+    command
+}
+
+fn request_failed(message: &str) -> TrackRenameError {
+    TrackRenameError::Request(format!("Request failed: {}", message.trim()))
+}
+
+fn content_hash(contents: &str) -> Revision {
+    let mut hasher = DefaultHasher::new();
+    contents.hash(&mut hasher);
+    Revision::Hash(hasher.finish())
+}
+
+fn status_code(headers: &str) -> Option<u16> {
+    headers.lines().next()?.split_whitespace().nth(1)?.parse().ok()
+}
+
+fn header(headers: &str, name: &str) -> Option<String> {
+    headers.lines().find_map(|line| {
+        let (key, value) = line.split_once(':')?;
+        key.trim().eq_ignore_ascii_case(name).then(|| value.trim().to_string())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_state_location() {
+        assert_eq!(
+            StateLocation::from_str("https://dav.example.com/track-rename/").unwrap(),
+            StateLocation::Remote("https://dav.example.com/track-rename".to_string())
+        );
+        assert_eq!(
+            StateLocation::from_str("/Volumes/Studio/track-rename").unwrap(),
+            StateLocation::Directory(PathBuf::from("/Volumes/Studio/track-rename"))
+        );
+        assert!(StateLocation::from_str(" ").is_err());
+    }
+
+    #[test]
+    fn test_directory_write_conflict() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let location = StateLocation::Directory(dir.path().to_path_buf());
+        assert_eq!(location.read("state.json").unwrap(), None);
+
+        let first = location
+            .write("state.json", "first", &Revision::Missing)
+            .unwrap()
+            .expect("Write should succeed");
+        // Written by another machine after this one read the file
+        assert!(location.write("state.json", "second", &first).unwrap().is_some());
+        assert_eq!(location.write("state.json", "third", &first).unwrap(), None);
+        assert_eq!(location.read("state.json").unwrap().unwrap().0, "second");
+        assert!(!dir.path().join("state.json.lock").exists());
+
+        // Another process holding the lock blocks the write until it is released
+        let lock = LockFile::acquire(&dir.path().join("state.json.lock")).unwrap();
+        let writer = thread::spawn({
+            let location = location.clone();
+            let revision = location.read("state.json").unwrap().unwrap().1;
+            move || location.write("state.json", "fourth", &revision).unwrap()
+        });
+        thread::sleep(Duration::from_millis(200));
+        assert_eq!(location.read("state.json").unwrap().unwrap().0, "second");
+        drop(lock);
+        assert!(writer.join().unwrap().is_some());
+        assert_eq!(location.read("state.json").unwrap().unwrap().0, "fourth");
+    }
+
+    #[test]
+    fn test_response_headers() {
+        let headers = "HTTP/1.1 201 Created\r\nETag: \"abc\"\r\nContent-Length: 0";
+        assert_eq!(status_code(headers), Some(201));
+        assert_eq!(header(headers, "etag"), Some("\"abc\"".to_string()));
+        assert_eq!(header(headers, "last-modified"), None);
+    }
+}
@@ -1,6 +1,92 @@
-use std::fmt;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::{self, Write};
+use std::fs;
+use std::path::{Path, PathBuf};
 
+use anyhow::{Context, Result};
 use colored::Colorize;
+use serde::Serialize;
+
+use track_rename::i18n::Message;
+use track_rename::utils;
+
+/// Genre name used in the statistics report for tracks without a genre.
+const NO_GENRE: &str = "(none)";
+
+/// Summary line labels used for aligning the values.
+const SUMMARY_LABELS: [Message; 32] = [
+    Message::SummaryTags,
+    Message::SummaryRenamed,
+    Message::SummaryConverted,
+    Message::SummaryRepaired,
+    Message::SummaryVbrHeader,
+    Message::SummaryAifc,
+    Message::SummaryResampled,
+    Message::SummaryWrongFormat,
+    Message::SummaryDeleted,
+    Message::SummaryDuplicate,
+    Message::SummaryLinked,
+    Message::SummaryUpgraded,
+    Message::SummaryId3v1Removed,
+    Message::SummaryApeRemoved,
+    Message::SummaryChaptersRemoved,
+    Message::SummaryIdsAdded,
+    Message::SummaryRelinked,
+    Message::SummaryArtistConflicts,
+    Message::SummaryArtwork,
+    Message::SummaryMinorSkipped,
+    Message::SummaryMinorApproved,
+    Message::SummaryFramesSkipped,
+    Message::SummaryDuplicateFrames,
+    Message::SummaryPlaceholders,
+    Message::SummaryRetried,
+    Message::SummaryFailed,
+    Message::SummaryBadWrites,
+    Message::SummaryRenameFailed,
+    Message::SummaryDeclined,
+    Message::SummaryUnchanged,
+    Message::SummarySpaceSaved,
+    Message::SummarySpaceAdded,
+];
+
+/// Counts for the tracks processed in one directory.
+#[derive(Debug, Clone, Default)]
+pub struct DirectoryStatistics {
+    pub directory: PathBuf,
+    pub processed: usize,
+    pub tags_fixed: usize,
+    pub renamed: usize,
+    pub errors: usize,
+}
+
+/// Counts for the tracks in one top-level folder or with one formatted genre.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct GroupStatistics {
+    pub processed: usize,
+    pub tags_fixed: usize,
+    pub renamed: usize,
+    pub errors: usize,
+    pub bytes_saved: u64,
+    pub bytes_added: u64,
+}
+
+/// Per-folder and per-genre counts written with `--stats-report`.
+#[derive(Debug, Serialize)]
+struct GroupReport<'a> {
+    folders: &'a BTreeMap<String, GroupStatistics>,
+    genres: &'a BTreeMap<String, GroupStatistics>,
+}
+
+/// Totals before processing a track, used for counting the changes per directory.
+#[derive(Debug, Clone, Copy)]
+pub struct TrackSnapshot {
+    tags_fixed: usize,
+    renamed: usize,
+    errors: usize,
+    proposed: usize,
+    bytes_saved: u64,
+    bytes_added: u64,
+}
 
 /// Store renaming statistics.
 #[derive(Debug, Default)]
@@ -9,46 +95,479 @@ pub struct Statistics {
     pub tags_fixed: usize,
     pub to_rename: usize,
     pub renamed: usize,
+    /// Renames that only change capitalization
+    pub to_rename_case_only: usize,
+    pub renamed_case_only: usize,
     pub to_remove: usize,
     pub removed: usize,
     pub duplicates: usize,
+    pub to_link: usize,
+    pub linked: usize,
+    pub to_upgrade: usize,
+    pub upgraded: usize,
+    pub id3v1_removed: usize,
+    pub ape_tags: usize,
+    pub ape_removed: usize,
+    pub chapter_tags: usize,
+    pub chapters_removed: usize,
+    pub ids_added: usize,
+    /// Files renamed outside track-rename that were found by their track ID
+    pub relinked: usize,
+    /// Tracks where the artist in the filename differs from the artist tag
+    pub artist_conflicts: usize,
+    pub artist_from_filename: usize,
+    pub art_oversized: usize,
+    pub art_resized: usize,
+    pub art_bytes_saved: u64,
+    pub minor_skipped: usize,
+    pub minor_approved: usize,
+    /// Malformed frames skipped by the tolerant reader
+    pub frames_skipped: usize,
+    /// Redundant TXXX and GEOB frames removed when rewriting tags
+    pub duplicate_frames: usize,
+    /// Online-only cloud storage files that were not downloaded
+    pub placeholders_skipped: usize,
+    /// Tracks that failed with a transient error and were tried again at the end
+    pub retried: usize,
+    pub retry_recovered: usize,
     pub failed: usize,
+    pub bad_writes: usize,
+    /// Renames that were accepted but could not be done
+    pub rename_failed: usize,
+    /// Changes declined at the prompt
+    pub declined: usize,
+    /// Tracks that did not need any changes, including tracks skipped as unchanged since the last run
+    pub unchanged: usize,
     pub converted: usize,
+    pub repaired: usize,
+    pub vbr_mismatch: usize,
+    pub vbr_fixed: usize,
+    /// AIFC and compressed AIFF files
+    pub aifc_files: usize,
+    pub aifc_converted: usize,
+    pub to_resample: usize,
+    pub resampled: usize,
+    /// Tracks not in the required format for their directory
+    pub wrong_format: usize,
+    pub format_converted: usize,
+    /// File size decrease from tag writes, conversions, and linked duplicates
+    pub bytes_saved: u64,
+    /// File size increase from tag writes and conversions
+    pub bytes_added: u64,
+    /// Counts for each directory in processing order
+    pub directories: Vec<DirectoryStatistics>,
+    directory_index: HashMap<PathBuf, usize>,
+    /// Counts for each top-level folder under the input directory
+    pub folders: BTreeMap<String, GroupStatistics>,
+    /// Counts for each formatted genre
+    pub genres: BTreeMap<String, GroupStatistics>,
+}
+
+impl DirectoryStatistics {
+    /// Return true if any track was changed or failed.
+    pub const fn has_changes(&self) -> bool {
+        self.tags_fixed > 0 || self.renamed > 0 || self.errors > 0
+    }
+}
+
+impl fmt::Display for DirectoryStatistics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let path = utils::path_to_string_relative(&self.directory);
+        write!(
+            f,
+            "{}: {}",
+            if path.is_empty() { "." } else { &path },
+            Message::DirectorySummary.format(&[&self.processed, &self.tags_fixed, &self.renamed, &self.errors])
+        )
+    }
+}
+
+impl GroupStatistics {
+    const fn add(&mut self, before: TrackSnapshot, after: TrackSnapshot) {
+        self.processed += 1;
+        self.tags_fixed += after.tags_fixed - before.tags_fixed;
+        self.renamed += after.renamed - before.renamed;
+        self.errors += after.errors - before.errors;
+        self.bytes_saved += after.bytes_saved - before.bytes_saved;
+        self.bytes_added += after.bytes_added - before.bytes_added;
+    }
 }
 
 impl Statistics {
+    /// Current totals for counting the changes of a single track.
+    pub const fn track_snapshot(&self) -> TrackSnapshot {
+        TrackSnapshot {
+            tags_fixed: self.tags_fixed,
+            renamed: self.renamed,
+            errors: self.failed + self.bad_writes + self.rename_failed,
+            proposed: self.proposed(),
+            bytes_saved: self.bytes_saved,
+            bytes_added: self.bytes_added,
+        }
+    }
+
+    /// Return true if no change was proposed and nothing failed since the snapshot.
+    pub const fn unchanged_since(&self, before: TrackSnapshot) -> bool {
+        self.proposed() == before.proposed && !self.failed_since(before)
+    }
+
+    /// Return true if changes were proposed but all of them were declined.
+    pub const fn all_declined(&self) -> bool {
+        self.declined > 0
+            && self.tags_fixed == 0
+            && self.renamed == 0
+            && self.removed == 0
+            && self.linked == 0
+            && self.upgraded == 0
+            && self.converted == 0
+    }
+
+    /// Number of changes shown for confirmation or skipped as minor.
+    const fn proposed(&self) -> usize {
+        self.tags
+            + self.to_rename
+            + self.to_remove
+            + self.duplicates
+            + self.to_link
+            + self.to_upgrade
+            + self.minor_skipped
+            + self.wrong_format
+            + self.to_resample
+    }
+
+    /// Return true if tags were fixed or the file was renamed since the snapshot, without errors.
+    pub const fn modified_since(&self, before: TrackSnapshot) -> bool {
+        (self.tags_fixed > before.tags_fixed || self.renamed > before.renamed) && !self.failed_since(before)
+    }
+
+    /// Return true if processing failed since the snapshot.
+    pub const fn failed_since(&self, before: TrackSnapshot) -> bool {
+        self.failed + self.bad_writes + self.rename_failed > before.errors
+    }
+
+    /// Add the changes since the snapshot to the counts for the given directory.
+    pub fn add_directory_track(&mut self, directory: &Path, before: TrackSnapshot) {
+        let after = self.track_snapshot();
+        let index = *self.directory_index.entry(directory.to_path_buf()).or_insert_with(|| {
+            self.directories.push(DirectoryStatistics {
+                directory: directory.to_path_buf(),
+                ..Default::default()
+            });
+            self.directories.len() - 1
+        });
+        let counts = &mut self.directories[index];
+        counts.processed += 1;
+        counts.tags_fixed += after.tags_fixed - before.tags_fixed;
+        counts.renamed += after.renamed - before.renamed;
+        counts.errors += after.errors - before.errors;
+    }
+
+    /// Add the changes since the snapshot to the counts for the given top-level folder and genre.
+    pub fn add_group_track(&mut self, folder: &str, genre: &str, before: TrackSnapshot) {
+        let after = self.track_snapshot();
+        let genre = if genre.is_empty() { NO_GENRE } else { genre };
+        self.folders.entry(folder.to_string()).or_default().add(before, after);
+        self.genres.entry(genre.to_string()).or_default().add(before, after);
+    }
+
+    /// Write the per-folder and per-genre counts to a file as CSV if the file extension is `csv`, otherwise as JSON.
+    pub fn write_report(&self, path: &Path) -> Result<()> {
+        let is_csv = path
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("csv"));
+        let contents = if is_csv {
+            self.report_csv()
+        } else {
+            serde_json::to_string_pretty(&GroupReport {
+                folders: &self.folders,
+                genres: &self.genres,
+            })
+            .context("Failed to serialize statistics report")?
+        };
+        fs::write(path, contents).with_context(|| format!("Failed to write statistics report: {}", path.display()))?;
+        println!("Wrote statistics report to: {}", path.display());
+        Ok(())
+    }
+
+    fn report_csv(&self) -> String {
+        let mut csv = String::from("group,name,processed,tags_fixed,renamed,errors,bytes_saved,bytes_added\n");
+        for (group, counts) in [("folder", &self.folders), ("genre", &self.genres)] {
+            for (name, count) in counts {
+                let _ = writeln!(
+                    csv,
+                    "{group},{},{},{},{},{},{},{}",
+                    utils::csv_field(name),
+                    count.processed,
+                    count.tags_fixed,
+                    count.renamed,
+                    count.errors,
+                    count.bytes_saved,
+                    count.bytes_added
+                );
+            }
+        }
+        csv
+    }
+
+    /// Add a file size change to the saved or added totals.
+    pub const fn record_size_change(&mut self, size_before: u64, size_after: u64) {
+        if size_after < size_before {
+            self.bytes_saved += size_before - size_after;
+        } else {
+            self.bytes_added += size_after - size_before;
+        }
+    }
+
     /// Return true if nothing needs to change
     pub const fn no_changes(&self) -> bool {
         self.tags == 0
             && self.to_rename == 0
             && self.to_remove == 0
             && self.duplicates == 0
+            && self.to_link == 0
+            && self.to_upgrade == 0
+            && self.ape_tags == 0
+            && self.chapter_tags == 0
+            && self.art_oversized == 0
+            && self.minor_skipped == 0
+            && self.frames_skipped == 0
+            && self.placeholders_skipped == 0
+            && self.retried == 0
             && self.failed == 0
+            && self.bad_writes == 0
+            && self.rename_failed == 0
             && self.converted == 0
+            && self.repaired == 0
+            && self.vbr_mismatch == 0
+            && self.aifc_files == 0
+            && self.to_resample == 0
+            && self.wrong_format == 0
     }
 }
 
 impl fmt::Display for Statistics {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         if self.no_changes() {
-            write!(f, "{}", "All Good".green())?;
+            write!(f, "{}", Message::AllGood.text().green())?;
         } else {
-            writeln!(f, "{}", "Updated:".bold())?;
-            writeln!(f, "Fix tags:   {} / {}", self.tags_fixed, self.tags)?;
-            writeln!(f, "Renamed:    {} / {}", self.renamed, self.to_rename)?;
+            writeln!(f, "{}", Message::Updated.text().bold())?;
+            // Align values using the longest label in the current language
+            let width = SUMMARY_LABELS
+                .iter()
+                .map(|label| label.text().chars().count())
+                .max()
+                .unwrap_or_default();
+            let mut line = |label: Message, value: String| writeln!(f, "{:<width$} {value}", label.text());
+
+            line(Message::SummaryTags, format!("{} / {}", self.tags_fixed, self.tags))?;
+            let mut renamed = format!("{} / {}", self.renamed, self.to_rename);
+            if self.to_rename_case_only > 0 {
+                let _ = write!(
+                    renamed,
+                    " ({}: {} / {})",
+                    Message::CaseOnly.text(),
+                    self.renamed_case_only,
+                    self.to_rename_case_only
+                );
+            }
+            line(Message::SummaryRenamed, renamed)?;
             if self.converted > 0 {
-                writeln!(f, "Converted:  {}", self.converted)?;
+                line(Message::SummaryConverted, self.converted.to_string())?;
+            }
+            if self.repaired > 0 {
+                line(Message::SummaryRepaired, self.repaired.to_string())?;
+            }
+            if self.vbr_mismatch > 0 {
+                line(
+                    Message::SummaryVbrHeader,
+                    format!("{} / {}", self.vbr_fixed, self.vbr_mismatch),
+                )?;
+            }
+            if self.aifc_files > 0 {
+                line(
+                    Message::SummaryAifc,
+                    format!("{} / {}", self.aifc_converted, self.aifc_files),
+                )?;
+            }
+            if self.to_resample > 0 {
+                line(
+                    Message::SummaryResampled,
+                    format!("{} / {}", self.resampled, self.to_resample),
+                )?;
+            }
+            if self.wrong_format > 0 {
+                line(
+                    Message::SummaryWrongFormat,
+                    format!("{} / {}", self.format_converted, self.wrong_format),
+                )?;
             }
             if self.to_remove > 0 {
-                writeln!(f, "Deleted:    {} / {}", self.removed, self.to_remove)?;
+                line(
+                    Message::SummaryDeleted,
+                    format!("{} / {}", self.removed, self.to_remove),
+                )?;
             }
             if self.duplicates > 0 {
-                writeln!(f, "Duplicate:  {}", self.duplicates)?;
+                line(Message::SummaryDuplicate, self.duplicates.to_string())?;
+            }
+            if self.to_link > 0 {
+                line(Message::SummaryLinked, format!("{} / {}", self.linked, self.to_link))?;
+            }
+            if self.to_upgrade > 0 {
+                line(
+                    Message::SummaryUpgraded,
+                    format!("{} / {}", self.upgraded, self.to_upgrade),
+                )?;
+            }
+            if self.id3v1_removed > 0 {
+                line(Message::SummaryId3v1Removed, self.id3v1_removed.to_string())?;
+            }
+            if self.ape_tags > 0 {
+                line(
+                    Message::SummaryApeRemoved,
+                    format!("{} / {}", self.ape_removed, self.ape_tags),
+                )?;
+            }
+            if self.chapter_tags > 0 {
+                line(
+                    Message::SummaryChaptersRemoved,
+                    format!("{} / {}", self.chapters_removed, self.chapter_tags),
+                )?;
+            }
+            if self.ids_added > 0 {
+                line(Message::SummaryIdsAdded, self.ids_added.to_string())?;
+            }
+            if self.relinked > 0 {
+                line(Message::SummaryRelinked, self.relinked.to_string())?;
+            }
+            if self.artist_conflicts > 0 {
+                line(
+                    Message::SummaryArtistConflicts,
+                    format!("{} / {}", self.artist_from_filename, self.artist_conflicts),
+                )?;
+            }
+            if self.art_oversized > 0 {
+                line(
+                    Message::SummaryArtwork,
+                    format!(
+                        "{} / {} ({})",
+                        self.art_resized,
+                        self.art_oversized,
+                        utils::format_bytes(self.art_bytes_saved)
+                    ),
+                )?;
+            }
+            if self.minor_skipped > 0 {
+                line(Message::SummaryMinorSkipped, self.minor_skipped.to_string())?;
+            }
+            if self.minor_approved > 0 {
+                line(Message::SummaryMinorApproved, self.minor_approved.to_string())?;
+            }
+            if self.frames_skipped > 0 {
+                line(Message::SummaryFramesSkipped, self.frames_skipped.to_string())?;
+            }
+            if self.duplicate_frames > 0 {
+                line(Message::SummaryDuplicateFrames, self.duplicate_frames.to_string())?;
+            }
+            if self.placeholders_skipped > 0 {
+                line(Message::SummaryPlaceholders, self.placeholders_skipped.to_string())?;
+            }
+            if self.retried > 0 {
+                line(
+                    Message::SummaryRetried,
+                    format!("{} / {}", self.retry_recovered, self.retried),
+                )?;
             }
             if self.failed > 0 {
-                writeln!(f, "Failed:     {}", self.failed)?;
+                line(Message::SummaryFailed, self.failed.to_string())?;
+            }
+            if self.bad_writes > 0 {
+                line(Message::SummaryBadWrites, self.bad_writes.to_string())?;
+            }
+            if self.rename_failed > 0 {
+                line(Message::SummaryRenameFailed, self.rename_failed.to_string())?;
+            }
+            if self.declined > 0 {
+                line(Message::SummaryDeclined, self.declined.to_string())?;
+            }
+            if self.unchanged > 0 {
+                line(Message::SummaryUnchanged, self.unchanged.to_string())?;
+            }
+            if self.bytes_saved > 0 {
+                line(Message::SummarySpaceSaved, utils::format_bytes(self.bytes_saved))?;
+            }
+            if self.bytes_added > 0 {
+                line(Message::SummarySpaceAdded, utils::format_bytes(self.bytes_added))?;
+            }
+            let changed: Vec<&DirectoryStatistics> =
+                self.directories.iter().filter(|counts| counts.has_changes()).collect();
+            if self.directories.len() > 1 && !changed.is_empty() {
+                writeln!(f, "\n{}", Message::Directories.text().bold())?;
+                for counts in changed {
+                    writeln!(f, "  {counts}")?;
+                }
+            }
+            if self.all_declined() {
+                writeln!(f, "\n{}", Message::AllDeclined.text().yellow())?;
             }
         }
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_group_report() {
+        let mut stats = Statistics::default();
+        let before = stats.track_snapshot();
+        stats.tags_fixed += 1;
+        stats.renamed += 1;
+        stats.record_size_change(5000, 3000);
+        stats.add_group_track("House", "House", before);
+        let before = stats.track_snapshot();
+        stats.failed += 1;
+        stats.add_group_track("House", "", before);
+        let before = stats.track_snapshot();
+        stats.add_group_track("Techno, Hard", "Techno", before);
+
+        assert_eq!(
+            stats.folders["House"],
+            GroupStatistics {
+                processed: 2,
+                tags_fixed: 1,
+                renamed: 1,
+                errors: 1,
+                bytes_saved: 2000,
+                bytes_added: 0,
+            }
+        );
+        assert_eq!(
+            stats.report_csv(),
+            "group,name,processed,tags_fixed,renamed,errors,bytes_saved,bytes_added\n\
+             folder,House,2,1,1,1,2000,0\n\
+             folder,\"Techno, Hard\",1,0,0,0,0,0\n\
+             genre,(none),1,0,0,1,0,0\n\
+             genre,House,1,1,1,0,2000,0\n\
+             genre,Techno,1,0,0,0,0,0\n"
+        );
+    }
+
+    #[test]
+    fn test_declined_and_unchanged() {
+        let mut stats = Statistics::default();
+        let before = stats.track_snapshot();
+        assert!(stats.unchanged_since(before));
+        stats.to_rename += 1;
+        assert!(!stats.unchanged_since(before));
+
+        stats.declined += 1;
+        assert!(!stats.no_changes());
+        assert!(stats.all_declined());
+        stats.tags += 1;
+        stats.tags_fixed += 1;
+        assert!(!stats.all_declined());
+    }
+}
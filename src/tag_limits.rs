@@ -0,0 +1,212 @@
+use std::fmt;
+use std::sync::LazyLock;
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::tags::TrackTags;
+
+/// Parenthesized or bracketed group at the end of a value, like " (Extended Mix)".
+static RE_TRAILING_GROUP: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\s*(\([^()]*\)|\[[^\[\]]*\])\s*$").expect("Failed to compile trailing group regex"));
+
+/// Maximum number of characters for each tag field.
+///
+/// Fields without a limit are not checked.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TagLimits {
+    pub artist: Option<usize>,
+    pub title: Option<usize>,
+    pub album: Option<usize>,
+    pub genre: Option<usize>,
+}
+
+/// Tag value that is longer than the limit for its field.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LengthViolation {
+    pub field: &'static str,
+    pub length: usize,
+    pub limit: usize,
+}
+
+impl TagLimits {
+    /// True if no field has a limit.
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.artist.is_none() && self.title.is_none() && self.album.is_none() && self.genre.is_none()
+    }
+
+    /// Check the given artist, title, album, and genre values against the limits.
+    #[must_use]
+    pub fn check(&self, artist: &str, title: &str, album: &str, genre: &str) -> Vec<LengthViolation> {
+        [
+            ("artist", artist, self.artist),
+            ("title", title, self.title),
+            ("album", album, self.album),
+            ("genre", genre, self.genre),
+        ]
+        .into_iter()
+        .filter_map(|(field, value, limit)| {
+            let limit = limit?;
+            let length = value.chars().count();
+            (length > limit).then_some(LengthViolation { field, length, limit })
+        })
+        .collect()
+    }
+
+    /// Check the formatted tag values against the limits.
+    #[must_use]
+    pub fn check_tags(&self, tags: &TrackTags) -> Vec<LengthViolation> {
+        self.check(
+            &tags.formatted_artist,
+            &tags.formatted_title,
+            &tags.formatted_album,
+            &tags.formatted_genre,
+        )
+    }
+
+    /// Shorten formatted tag values that are over the limit with [`truncate`].
+    ///
+    /// The full artist and title are kept for the filename,
+    /// so versions that only differ in a dropped descriptor like "(Extended Mix)" keep separate names.
+    /// Returns the values that were shortened.
+    pub fn truncate_tags(&self, tags: &mut TrackTags) -> Vec<LengthViolation> {
+        let violations = self.check_tags(tags);
+        if !violations.is_empty() && tags.untruncated.is_none() {
+            tags.untruncated = Some((tags.formatted_artist.clone(), tags.formatted_title.clone()));
+        }
+        for violation in &violations {
+            let value = match violation.field {
+                "artist" => &mut tags.formatted_artist,
+                "title" => &mut tags.formatted_title,
+                "album" => &mut tags.formatted_album,
+                _ => &mut tags.formatted_genre,
+            };
+            *value = truncate(value, violation.limit);
+        }
+        if !violations.is_empty() {
+            tags.formatted_name = format!("{} - {}", tags.formatted_artist, tags.formatted_title);
+            if tags.formatted_canonical_name.is_some() {
+                tags.set_canonical_name();
+            }
+        }
+        violations
+    }
+}
+
+/// Shorten a tag value to at most `limit` characters.
+///
+/// Trailing parenthesized groups like "(Extended Mix)" are dropped first, since the start of the value matters most.
+/// If that is not enough, the value is cut at the last word boundary before the limit.
+#[must_use]
+pub fn truncate(value: &str, limit: usize) -> String {
+    let mut truncated = value.trim().to_string();
+    while truncated.chars().count() > limit {
+        let Some(group) = RE_TRAILING_GROUP.find(&truncated) else {
+            break;
+        };
+        if group.start() == 0 {
+            break;
+        }
+        truncated.truncate(group.start());
+    }
+    if truncated.chars().count() <= limit {
+        return truncated;
+    }
+
+    let cut: String = truncated.chars().take(limit).collect();
+    let next_is_boundary = truncated.chars().nth(limit).is_some_and(char::is_whitespace);
+    let cut = if next_is_boundary {
+        cut.as_str()
+    } else {
+        cut.rfind(char::is_whitespace)
+            .map_or(cut.as_str(), |index| &cut[..index])
+    };
+    cut.trim_end_matches(|c: char| c.is_whitespace() || matches!(c, '-' | ',' | '(' | '[' | '&'))
+        .to_string()
+}
+
+impl fmt::Display for TagLimits {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let limits: Vec<String> = [
+            ("artist", self.artist),
+            ("title", self.title),
+            ("album", self.album),
+            ("genre", self.genre),
+        ]
+        .into_iter()
+        .filter_map(|(field, limit)| limit.map(|limit| format!("{field}={limit}")))
+        .collect();
+        if limits.is_empty() {
+            write!(f, "None")
+        } else {
+            write!(f, "{}", limits.join(", "))
+        }
+    }
+}
+
+impl fmt::Display for LengthViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {} > {}", self.field, self.length, self.limit)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_truncate() {
+        assert_eq!(truncate("Song", 10), "Song");
+        assert_eq!(truncate("Song (Extended Mix) (Clean)", 20), "Song (Extended Mix)");
+        assert_eq!(truncate("Song (Extended Mix) [Remastered]", 10), "Song");
+        assert_eq!(
+            truncate("A Very Long Song Title Without Groups", 20),
+            "A Very Long Song"
+        );
+        assert_eq!(truncate("Supercalifragilistic", 10), "Supercalif");
+        assert_eq!(truncate("(Intro) Song Name Here", 12), "(Intro) Song");
+    }
+
+    #[test]
+    fn test_check_limits() {
+        let limits = TagLimits {
+            title: Some(10),
+            genre: Some(5),
+            ..TagLimits::default()
+        };
+        assert!(!limits.is_empty());
+        assert!(TagLimits::default().is_empty());
+        let violations = limits.check("A Long Artist Name", "Song (Extended Mix)", "", "House");
+        assert_eq!(
+            violations,
+            vec![LengthViolation {
+                field: "title",
+                length: 19,
+                limit: 10
+            }]
+        );
+        assert_eq!(limits.to_string(), "title=10, genre=5");
+    }
+
+    #[test]
+    fn test_truncate_tags_keeps_full_title() {
+        let limits = TagLimits {
+            title: Some(10),
+            ..TagLimits::default()
+        };
+        let mut tags = TrackTags {
+            formatted_artist: "Artist".to_string(),
+            formatted_title: "Song (Extended Mix)".to_string(),
+            ..TrackTags::default()
+        };
+        assert_eq!(limits.truncate_tags(&mut tags).len(), 1);
+        assert_eq!(tags.formatted_title, "Song");
+        assert_eq!(tags.formatted_name, "Artist - Song");
+        assert_eq!(
+            tags.untruncated,
+            Some(("Artist".to_string(), "Song (Extended Mix)".to_string()))
+        );
+    }
+}
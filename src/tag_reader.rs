@@ -0,0 +1,359 @@
+use std::fs::File;
+use std::io::{BufReader, Cursor, Read, Seek, SeekFrom};
+use std::path::Path;
+
+use id3::{Frame, Tag, TagLike, Version};
+
+use crate::error::{Result, TrackRenameError};
+
+/// Frames needed for formatting:
+/// artist (`TPE1`), album artist (`TPE2`), title (`TIT2`), subtitle (`TIT3`), album (`TALB`),
+/// compilation (`TCMP`), genre (`TCON`), user text like the track ID (`TXXX`), and year (`TYER`, `TDRC`).
+const TEXT_FRAMES: [&[u8; 4]; 10] = [
+    b"TPE1", b"TPE2", b"TIT2", b"TIT3", b"TALB", b"TCMP", b"TCON", b"TXXX", b"TYER", b"TDRC",
+];
+
+/// `ID3v2` header flags that change the frame layout: unsynchronisation and extended header.
+const UNSUPPORTED_HEADER_FLAGS: u8 = 0b1100_0000;
+
+/// Read only the artist, title, album, genre, and user-defined text frames from an audio file.
+///
+/// Other frames like embedded artwork are skipped without reading them into memory.
+/// Returns `None` when the file does not start with an ID3v2.3 or ID3v2.4 tag
+/// or uses a tag layout the lightweight reader does not handle,
+/// in which case the full tag should be read instead.
+pub fn read_text_frames(path: &Path) -> Result<Option<Tag>> {
+    read_from_path(path, read_text_frames_from)
+}
+
+/// Read the text frames needed for formatting from an MP3 or AIFF stream.
+pub fn read_text_frames_from(mut reader: impl Read + Seek) -> id3::Result<Option<Tag>> {
+    if !seek_to_id3_header(&mut reader)? {
+        return Ok(None);
+    }
+    let mut header = [0_u8; 10];
+    reader.read_exact(&mut header)?;
+    let version = header[3];
+    if &header[..3] != b"ID3" || !matches!(version, 3 | 4) || header[5] & UNSUPPORTED_HEADER_FLAGS != 0 {
+        return Ok(None);
+    }
+
+    let tag_size = u64::from(synchsafe(&header[6..10]));
+    let mut frames = Vec::new();
+    let mut offset: u64 = 0;
+    while offset + 10 <= tag_size {
+        let mut frame_header = [0_u8; 10];
+        reader.read_exact(&mut frame_header)?;
+        if frame_header[0] == 0 {
+            // Padding
+            break;
+        }
+        let size_bytes = &frame_header[4..8];
+        let size = if version == 4 {
+            synchsafe(size_bytes)
+        } else {
+            u32::from_be_bytes([size_bytes[0], size_bytes[1], size_bytes[2], size_bytes[3]])
+        };
+        offset += 10 + u64::from(size);
+        if offset > tag_size {
+            return Ok(None);
+        }
+        if TEXT_FRAMES.iter().any(|id| id[..] == frame_header[..4]) {
+            frames.extend_from_slice(&frame_header);
+            let start = frames.len();
+            frames.resize(start + size as usize, 0);
+            reader.read_exact(&mut frames[start..])?;
+        } else {
+            reader.seek(SeekFrom::Current(i64::from(size)))?;
+        }
+    }
+
+    // Rebuild a tag containing only the wanted frames so the id3 crate handles text decoding
+    let mut tag_data = Vec::with_capacity(10 + frames.len());
+    tag_data.extend_from_slice(&header[..6]);
+    tag_data.extend_from_slice(&to_synchsafe(frames.len() as u32));
+    tag_data.extend_from_slice(&frames);
+    Ok(Some(Tag::read_from2(Cursor::new(tag_data))?))
+}
+
+/// Tag read by skipping frames that could not be parsed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TolerantTag {
+    pub tag: Tag,
+    /// IDs of the frames that were skipped
+    pub skipped_frames: Vec<String>,
+}
+
+/// Read all frames that can be parsed from an audio file and skip the malformed ones.
+///
+/// Used when the full tag can not be read because of a single broken frame.
+/// Returns `None` when the file does not have an ID3v2.3 or ID3v2.4 tag
+/// or uses a tag layout the reader does not handle.
+pub fn read_tolerant(path: &Path) -> Result<Option<TolerantTag>> {
+    read_from_path(path, read_tolerant_from)
+}
+
+/// Read all frames that can be parsed from an MP3, AIFF, or WAV stream.
+pub fn read_tolerant_from(reader: impl Read + Seek) -> id3::Result<Option<TolerantTag>> {
+    Ok(read_frame_list(reader)?.map(|list| {
+        let mut tag = Tag::with_version(list.version);
+        for frame in list.frames {
+            tag.add_frame(frame);
+        }
+        TolerantTag {
+            tag,
+            skipped_frames: list.skipped_frames,
+        }
+    }))
+}
+
+/// Read all frames in the order they are stored in an audio file.
+///
+/// Unlike a [`Tag`], frames that share an ID and description are all kept,
+/// so duplicates written by other tools can be found.
+/// Returns `None` when the file does not have an ID3v2.3 or ID3v2.4 tag
+/// or uses a tag layout the reader does not handle.
+pub fn read_all_frames(path: &Path) -> Result<Option<Vec<Frame>>> {
+    Ok(read_from_path(path, read_frame_list)?.map(|list| list.frames))
+}
+
+/// Open the file and run the reader on it.
+fn read_from_path<T>(path: &Path, read: impl FnOnce(BufReader<File>) -> id3::Result<T>) -> Result<T> {
+    let file = File::open(path)
+        .map_err(|error| TrackRenameError::io(format!("Failed to open file: {}", path.display()), error))?;
+    read(BufReader::new(file)).map_err(|source| TrackRenameError::TagRead {
+        path: path.to_path_buf(),
+        source,
+    })
+}
+
+/// Frames parsed one at a time from a tag.
+struct FrameList {
+    version: Version,
+    frames: Vec<Frame>,
+    skipped_frames: Vec<String>,
+}
+
+fn read_frame_list(mut reader: impl Read + Seek) -> id3::Result<Option<FrameList>> {
+    if !seek_to_id3_header(&mut reader)? {
+        return Ok(None);
+    }
+    let mut header = [0_u8; 10];
+    reader.read_exact(&mut header)?;
+    let version = header[3];
+    if &header[..3] != b"ID3" || !matches!(version, 3 | 4) || header[5] & UNSUPPORTED_HEADER_FLAGS != 0 {
+        return Ok(None);
+    }
+
+    // A truncated file still has frames worth reading
+    let mut data = Vec::new();
+    reader
+        .take(u64::from(synchsafe(&header[6..10])))
+        .read_to_end(&mut data)?;
+
+    let mut frames = Vec::new();
+    let mut skipped_frames = Vec::new();
+    let mut offset = 0;
+    while offset + 10 <= data.len() && data[offset] != 0 {
+        let frame_header = &data[offset..offset + 10];
+        let id = String::from_utf8_lossy(&frame_header[..4]).to_string();
+        let size_bytes = &frame_header[4..8];
+        let size = if version == 4 {
+            synchsafe(size_bytes)
+        } else {
+            u32::from_be_bytes([size_bytes[0], size_bytes[1], size_bytes[2], size_bytes[3]])
+        } as usize;
+        let end = offset + 10 + size;
+        // The next frame can not be found after an invalid ID or size
+        if end > data.len()
+            || !frame_header[..4]
+                .iter()
+                .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit())
+        {
+            skipped_frames.push(id);
+            break;
+        }
+        match parse_frame(&header, &data[offset..end]) {
+            Some(frame_tag) => frames.extend(frame_tag.frames().cloned()),
+            None => skipped_frames.push(id),
+        }
+        offset = end;
+    }
+    Ok(Some(FrameList {
+        version: if version == 4 { Version::Id3v24 } else { Version::Id3v23 },
+        frames,
+        skipped_frames,
+    }))
+}
+
+/// Parse a single frame by wrapping it in a tag of its own.
+fn parse_frame(header: &[u8; 10], frame: &[u8]) -> Option<Tag> {
+    let mut tag_data = Vec::with_capacity(10 + frame.len());
+    tag_data.extend_from_slice(&header[..6]);
+    tag_data.extend_from_slice(&to_synchsafe(u32::try_from(frame.len()).ok()?));
+    tag_data.extend_from_slice(frame);
+    Tag::read_from2(Cursor::new(tag_data)).ok()
+}
+
+/// Move reader to the start of the ID3 tag.
+///
+/// MP3 files have the tag at the start, AIFF and WAV files store it in an `ID3 ` chunk.
+fn seek_to_id3_header(reader: &mut (impl Read + Seek)) -> id3::Result<bool> {
+    let mut magic = [0_u8; 12];
+    if reader.read_exact(&mut magic).is_err() {
+        return Ok(false);
+    }
+    if &magic[..3] == b"ID3" {
+        reader.seek(SeekFrom::Start(0))?;
+        return Ok(true);
+    }
+    let is_aiff = &magic[..4] == b"FORM" && matches!(&magic[8..12], b"AIFF" | b"AIFC");
+    let is_wav = &magic[..4] == b"RIFF" && &magic[8..12] == b"WAVE";
+    if !is_aiff && !is_wav {
+        return Ok(false);
+    }
+    let mut chunk_header = [0_u8; 8];
+    while reader.read_exact(&mut chunk_header).is_ok() {
+        if matches!(&chunk_header[..4], b"ID3 " | b"id3 ") {
+            return Ok(true);
+        }
+        let size_bytes = [chunk_header[4], chunk_header[5], chunk_header[6], chunk_header[7]];
+        // RIFF uses little-endian chunk sizes
+        let size = if is_wav {
+            u32::from_le_bytes(size_bytes)
+        } else {
+            u32::from_be_bytes(size_bytes)
+        };
+        // Chunks are padded to an even size
+        reader.seek(SeekFrom::Current(i64::from(size) + i64::from(size % 2)))?;
+    }
+    Ok(false)
+}
+
+fn synchsafe(bytes: &[u8]) -> u32 {
+    bytes
+        .iter()
+        .fold(0, |value, byte| (value << 7) | u32::from(byte & 0x7F))
+}
+
+const fn to_synchsafe(value: u32) -> [u8; 4] {
+    [
+        ((value >> 21) & 0x7F) as u8,
+        ((value >> 14) & 0x7F) as u8,
+        ((value >> 7) & 0x7F) as u8,
+        (value & 0x7F) as u8,
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use id3::frame::{Picture, PictureType};
+
+    fn tag_with_artwork(version: Version) -> Vec<u8> {
+        let mut tag = Tag::new();
+        tag.set_artist("Artist");
+        tag.set_title("Song (Extended Mix)");
+        tag.add_frame(Picture {
+            mime_type: "image/jpeg".to_string(),
+            picture_type: PictureType::CoverFront,
+            description: String::new(),
+            data: vec![0xFF; 300_000],
+        });
+        tag.set_album("Album");
+        tag.set_genre("House");
+        let mut data = Vec::new();
+        tag.write_to(&mut data, version).expect("Failed to write tag");
+        data.extend_from_slice(&[0xFF, 0xFB, 0x90, 0x64]);
+        data
+    }
+
+    #[test]
+    fn test_read_text_frames() {
+        for version in [Version::Id3v23, Version::Id3v24] {
+            let tag = read_text_frames_from(Cursor::new(tag_with_artwork(version)))
+                .expect("Failed to read tag")
+                .expect("Tag should be supported");
+            assert_eq!(tag.version(), version);
+            assert_eq!(tag.artist(), Some("Artist"));
+            assert_eq!(tag.title(), Some("Song (Extended Mix)"));
+            assert_eq!(tag.album(), Some("Album"));
+            assert_eq!(tag.genre(), Some("House"));
+            assert_eq!(tag.pictures().count(), 0);
+        }
+    }
+
+    #[test]
+    fn test_read_text_frames_from_aiff() {
+        let id3 = tag_with_artwork(Version::Id3v24);
+        let comm = [0_u8; 18];
+        let mut data = Vec::new();
+        data.extend_from_slice(b"FORM");
+        data.extend_from_slice(&0_u32.to_be_bytes());
+        data.extend_from_slice(b"AIFF");
+        data.extend_from_slice(b"COMM");
+        data.extend_from_slice(&(comm.len() as u32).to_be_bytes());
+        data.extend_from_slice(&comm);
+        data.extend_from_slice(b"ID3 ");
+        data.extend_from_slice(&(id3.len() as u32).to_be_bytes());
+        data.extend_from_slice(&id3);
+
+        let tag = read_text_frames_from(Cursor::new(data))
+            .expect("Failed to read tag")
+            .expect("Tag should be supported");
+        assert_eq!(tag.artist(), Some("Artist"));
+        assert_eq!(tag.genre(), Some("House"));
+    }
+
+    #[test]
+    fn test_read_text_frames_from_wav() {
+        let id3 = tag_with_artwork(Version::Id3v23);
+        let fmt = [0_u8; 18];
+        let mut data = Vec::new();
+        data.extend_from_slice(b"RIFF");
+        data.extend_from_slice(&0_u32.to_le_bytes());
+        data.extend_from_slice(b"WAVE");
+        data.extend_from_slice(b"fmt ");
+        data.extend_from_slice(&(fmt.len() as u32).to_le_bytes());
+        data.extend_from_slice(&fmt);
+        data.extend_from_slice(b"id3 ");
+        data.extend_from_slice(&(id3.len() as u32).to_le_bytes());
+        data.extend_from_slice(&id3);
+
+        let tag = read_text_frames_from(Cursor::new(data))
+            .expect("Failed to read tag")
+            .expect("Tag should be supported");
+        assert_eq!(tag.title(), Some("Song (Extended Mix)"));
+    }
+
+    #[test]
+    fn test_read_tolerant() {
+        let mut data = tag_with_artwork(Version::Id3v24);
+        // Break the title frame text encoding
+        let title = data
+            .windows(4)
+            .position(|window| window == b"TIT2")
+            .expect("Missing title frame");
+        data[title + 10] = 9;
+        assert!(Tag::read_from2(Cursor::new(data.clone())).is_err());
+
+        let result = read_tolerant_from(Cursor::new(data))
+            .expect("Failed to read tag")
+            .expect("Tag should be supported");
+        assert_eq!(result.skipped_frames, vec!["TIT2".to_string()]);
+        assert_eq!(result.tag.artist(), Some("Artist"));
+        assert_eq!(result.tag.title(), None);
+        assert_eq!(result.tag.album(), Some("Album"));
+        assert_eq!(result.tag.genre(), Some("House"));
+        assert_eq!(result.tag.pictures().count(), 1);
+    }
+
+    #[test]
+    fn test_read_text_frames_without_tag() {
+        let data = vec![0xFF, 0xFB, 0x90, 0x64, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+        assert!(read_text_frames_from(Cursor::new(data))
+            .expect("Failed to read")
+            .is_none());
+    }
+}
@@ -1,6 +1,9 @@
 use colored::Colorize;
 use id3::{Tag, TagLike};
 
+use crate::canonical_name;
+use crate::formatting::FilenameSeparator;
+use crate::prompt::{self, FieldChange};
 use crate::track::Track;
 use crate::utils;
 
@@ -16,6 +19,19 @@ pub struct TrackTags {
     pub formatted_title: String,
     pub formatted_album: String,
     pub formatted_genre: String,
+    pub current_album_artist: String,
+    pub formatted_album_artist: String,
+    /// Track is marked as part of a compilation with the TCMP frame
+    pub compilation: bool,
+    /// Mix and version descriptors from the subtitle frame (TIT3)
+    pub current_subtitle: String,
+    pub formatted_subtitle: String,
+    pub current_year: Option<i32>,
+    pub formatted_year: Option<i32>,
+    pub current_canonical_name: Option<String>,
+    pub formatted_canonical_name: Option<String>,
+    /// Formatted artist and title before shortening to the tag length limits, used for the filename
+    pub untruncated: Option<(String, String)>,
     pub update_needed: bool,
 }
 
@@ -36,7 +52,7 @@ impl TrackTags {
     ///
     /// Fallback to parsing them from filename if tags are empty.
     #[must_use]
-    pub fn parse_tag_data(track: &Track, tag: &Tag) -> Self {
+    pub fn parse_tag_data(track: &Track, tag: &Tag, separator: FilenameSeparator) -> Self {
         let mut artist = String::new();
         let mut title = String::new();
 
@@ -53,7 +69,7 @@ impl TrackTags {
             (None, None) => {
                 eprintln!("\n{}", format!("Missing tags: {}", track.path.display()).yellow());
                 current_name = format!("{artist} - {title}");
-                if let Some((a, t)) = utils::get_tags_from_filename(&track.name) {
+                if let Some((a, t)) = utils::get_tags_from_filename(&track.name, separator) {
                     artist = a;
                     title = t;
                 }
@@ -62,7 +78,7 @@ impl TrackTags {
                 eprintln!("\n{}", format!("Missing artist tag: {}", track.path.display()).yellow());
                 title = utils::normalize_str(t);
                 current_name = format!("{artist} - {title}");
-                if let Some((a, _)) = utils::get_tags_from_filename(&track.name) {
+                if let Some((a, _)) = utils::get_tags_from_filename(&track.name, separator) {
                     artist = a;
                 }
             }
@@ -70,14 +86,29 @@ impl TrackTags {
                 eprintln!("\n{}", format!("Missing title tag: {}", track.path.display()).yellow());
                 artist = utils::normalize_str(a);
                 current_name = format!("{artist} - {title}");
-                if let Some((_, t)) = utils::get_tags_from_filename(&track.name) {
+                if let Some((_, t)) = utils::get_tags_from_filename(&track.name, separator) {
                     title = t;
                 }
             }
         }
         let album = utils::normalize_str(tag.album().unwrap_or_default());
         let genre = utils::normalize_str(tag.genre_parsed().unwrap_or_default().as_ref());
-        Self::new(current_name, artist, title, album, genre)
+        let year = tag_year(tag);
+        let canonical_name = canonical_name::read(tag);
+        let subtitle = utils::normalize_str(tag_subtitle(tag).unwrap_or_default());
+        let album_artist = utils::normalize_str(tag.album_artist().unwrap_or_default());
+        Self {
+            formatted_album_artist: album_artist.clone(),
+            current_album_artist: album_artist,
+            compilation: tag_compilation(tag),
+            formatted_subtitle: subtitle.clone(),
+            current_subtitle: subtitle,
+            current_year: year,
+            formatted_year: year,
+            current_canonical_name: canonical_name.clone(),
+            formatted_canonical_name: canonical_name,
+            ..Self::new(current_name, artist, title, album, genre)
+        }
     }
 
     /// Returns true if any of the formatted tag fields differ from their current value,
@@ -88,7 +119,113 @@ impl TrackTags {
             || self.current_artist != self.formatted_artist
             || self.current_title != self.formatted_title
             || self.current_album != self.formatted_album
+            || self.current_album_artist != self.formatted_album_artist
             || self.current_genre != self.formatted_genre
+            || self.current_subtitle != self.formatted_subtitle
+            || self.current_year != self.formatted_year
+            || self.current_canonical_name != self.formatted_canonical_name
+    }
+
+    /// Use the formatted artist and title as the canonical name stored in the tag.
+    pub fn set_canonical_name(&mut self) {
+        self.formatted_canonical_name = Some(canonical_name::from_artist_and_title(
+            &self.formatted_artist,
+            &self.formatted_title,
+        ));
+    }
+
+    /// Ask user for new formatted tag values.
+    ///
+    /// Empty input keeps the formatted value.
+    pub fn edit(&mut self) {
+        self.formatted_artist = prompt::edit_value("Artist", &self.formatted_artist);
+        self.formatted_title = prompt::edit_value("Title", &self.formatted_title);
+        self.formatted_album = prompt::edit_value("Album", &self.formatted_album);
+        self.formatted_genre = prompt::edit_value("Genre", &self.formatted_genre);
+        self.formatted_name = format!("{} - {}", self.formatted_artist, self.formatted_title);
+        if self.formatted_canonical_name.is_some() {
+            self.set_canonical_name();
+        }
+    }
+
+    /// Get changed fields with current and formatted values.
+    #[must_use]
+    pub fn changes(&self) -> Vec<FieldChange> {
+        [
+            ("name", &self.current_name, &self.formatted_name),
+            ("album", &self.current_album, &self.formatted_album),
+            ("album_artist", &self.current_album_artist, &self.formatted_album_artist),
+            ("genre", &self.current_genre, &self.formatted_genre),
+            ("subtitle", &self.current_subtitle, &self.formatted_subtitle),
+        ]
+        .into_iter()
+        .filter(|(_, current, formatted)| current != formatted)
+        .map(|(field, current, formatted)| FieldChange {
+            field: field.to_string(),
+            current: current.clone(),
+            proposed: formatted.clone(),
+        })
+        .chain(self.year_changed().then(|| FieldChange {
+            field: "year".to_string(),
+            current: self.current_year.map(|year| year.to_string()).unwrap_or_default(),
+            proposed: self.formatted_year.map(|year| year.to_string()).unwrap_or_default(),
+        }))
+        .chain(
+            (self.current_canonical_name != self.formatted_canonical_name).then(|| FieldChange {
+                field: "canonical_name".to_string(),
+                current: self.current_canonical_name.clone().unwrap_or_default(),
+                proposed: self.formatted_canonical_name.clone().unwrap_or_default(),
+            }),
+        )
+        .collect()
+    }
+
+    /// Returns true if the year was moved from the title to the year tag.
+    #[must_use]
+    pub fn year_changed(&self) -> bool {
+        self.current_year != self.formatted_year
+    }
+
+    /// Compare tags read back from the file against the formatted values.
+    ///
+    /// Returns the fields that do not match, with the value in the file as the current value.
+    #[must_use]
+    pub fn write_mismatches(&self, tag: &Tag) -> Vec<FieldChange> {
+        [
+            ("artist", tag.artist(), &self.formatted_artist),
+            ("title", tag.title(), &self.formatted_title),
+            ("album", tag.album(), &self.formatted_album),
+            ("album_artist", tag.album_artist(), &self.formatted_album_artist),
+            ("genre", tag.genre(), &self.formatted_genre),
+            ("subtitle", tag_subtitle(tag), &self.formatted_subtitle),
+        ]
+        .into_iter()
+        .filter(|(_, written, formatted)| written.unwrap_or_default() != formatted.as_str())
+        .map(|(field, written, formatted)| FieldChange {
+            field: field.to_string(),
+            current: written.unwrap_or_default().to_string(),
+            proposed: formatted.clone(),
+        })
+        .chain(
+            self.formatted_year
+                .filter(|year| tag_year(tag) != Some(*year))
+                .map(|year| FieldChange {
+                    field: "year".to_string(),
+                    current: tag_year(tag).map(|year| year.to_string()).unwrap_or_default(),
+                    proposed: year.to_string(),
+                }),
+        )
+        .chain(
+            self.formatted_canonical_name
+                .as_ref()
+                .filter(|name| canonical_name::read(tag).as_ref() != Some(*name))
+                .map(|name| FieldChange {
+                    field: "canonical_name".to_string(),
+                    current: canonical_name::read(tag).unwrap_or_default(),
+                    proposed: name.clone(),
+                }),
+        )
+        .collect()
     }
 
     /// Print coloured diff for changes in tags.
@@ -102,9 +239,83 @@ impl TrackTags {
             print!("{}: ", "Album".bold());
             utils::print_diff(&self.current_album, &self.formatted_album);
         }
+        if self.current_album_artist != self.formatted_album_artist {
+            print!("{}: ", "Album artist".bold());
+            utils::print_diff(&self.current_album_artist, &self.formatted_album_artist);
+        }
         if self.current_genre != self.formatted_genre {
             print!("{}: ", "Genre".bold());
             utils::print_diff(&self.current_genre, &self.formatted_genre);
         }
+        if self.current_subtitle != self.formatted_subtitle {
+            print!("{}: ", "Subtitle".bold());
+            utils::print_diff(&self.current_subtitle, &self.formatted_subtitle);
+        }
+        if let (true, Some(year)) = (self.year_changed(), self.formatted_year) {
+            println!("{}: {}", "Year".bold(), year.to_string().green());
+        }
+        match (&self.current_canonical_name, &self.formatted_canonical_name) {
+            (Some(current), Some(formatted)) if current != formatted => {
+                print!("{}: ", "Canonical name".bold());
+                utils::print_diff(current, formatted);
+            }
+            (None, Some(formatted)) => println!("{}: {}", "Canonical name".bold(), formatted.green()),
+            _ => {}
+        }
+    }
+}
+
+/// True if the TCMP frame marks the track as part of a compilation.
+fn tag_compilation(tag: &Tag) -> bool {
+    tag.get("TCMP")
+        .and_then(|frame| frame.content().text())
+        .is_some_and(|value| value.trim() == "1")
+}
+
+/// Subtitle from the TIT3 frame.
+fn tag_subtitle(tag: &Tag) -> Option<&str> {
+    tag.get("TIT3").and_then(|frame| frame.content().text())
+}
+
+/// Year from the ID3v2.3 year frame or the ID3v2.4 recording time.
+fn tag_year(tag: &Tag) -> Option<i32> {
+    tag.year().or_else(|| tag.date_recorded().map(|date| date.year))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_mismatches() {
+        let tags = TrackTags {
+            formatted_artist: "Artist".to_string(),
+            formatted_title: "Song (Extended Mix)".to_string(),
+            formatted_album: String::new(),
+            formatted_genre: "House".to_string(),
+            ..TrackTags::default()
+        };
+        let mut tag = Tag::new();
+        tag.set_artist("Artist");
+        tag.set_title("Song (Extended Mix)");
+        tag.set_genre("House");
+        assert!(tags.write_mismatches(&tag).is_empty());
+
+        tag.remove_genre();
+        let mismatches = tags.write_mismatches(&tag);
+        assert_eq!(mismatches.len(), 1);
+        assert_eq!(mismatches[0].field, "genre");
+        assert_eq!(mismatches[0].current, "");
+        assert_eq!(mismatches[0].proposed, "House");
+
+        tag.set_genre("House");
+        let tags = TrackTags {
+            formatted_year: Some(1998),
+            ..tags
+        };
+        tag.set_year(1998);
+        assert!(tags.write_mismatches(&tag).is_empty());
+        tag.remove_year();
+        assert_eq!(tags.write_mismatches(&tag)[0].field, "year");
     }
 }
@@ -0,0 +1,145 @@
+use std::fmt;
+use std::fmt::Display;
+use std::sync::LazyLock;
+
+use id3::{Tag, TagLike};
+use regex::Regex;
+
+use crate::serato::SeratoData;
+
+/// Match BPM values in text like `(128 BPM)` or `- 94bpm`
+static RE_TITLE_BPM: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?i)\b(\d{2,3}(?:\.\d+)?)\s*bpm\b").unwrap());
+
+/// BPM values for a track from the different sources.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct TempoSources {
+    /// BPM tag (`TBPM`)
+    pub tag: Option<f32>,
+    /// Serato beatgrid
+    pub beatgrid: Option<f32>,
+    /// BPM mentioned in the title
+    pub title: Option<f32>,
+}
+
+impl TempoSources {
+    /// Read BPM values from the tag data.
+    #[must_use]
+    pub fn from_tags(file_tags: &Tag) -> Self {
+        let tag = file_tags
+            .get("TBPM")
+            .and_then(|frame| frame.content().text())
+            .and_then(|text| text.trim().parse::<f32>().ok())
+            .filter(|bpm| *bpm > 0.0);
+        let beatgrid = SeratoData::parse(file_tags)
+            .and_then(|data| data.beatgrid)
+            .and_then(|beatgrid| beatgrid.bpm());
+        let title = file_tags.title().and_then(bpm_from_text);
+        Self { tag, beatgrid, title }
+    }
+
+    /// Check that all available BPM values agree within the given tolerance.
+    ///
+    /// Returns suggested fixes for each disagreeing source,
+    /// or an empty list if the values are consistent.
+    /// The beatgrid is used as the reference when available,
+    /// since it is the value that has been verified by listening in Serato.
+    #[must_use]
+    pub fn check(&self, tolerance: f32) -> Vec<String> {
+        let Some((reference_name, reference)) = self
+            .beatgrid
+            .map(|bpm| ("beatgrid", bpm))
+            .or_else(|| self.tag.map(|bpm| ("BPM tag", bpm)))
+        else {
+            return Vec::new();
+        };
+
+        let mut suggestions = Vec::new();
+        for (name, value) in [("BPM tag", self.tag), ("title", self.title)] {
+            let Some(value) = value else {
+                continue;
+            };
+            if name == reference_name || (value - reference).abs() <= tolerance {
+                continue;
+            }
+            let half_or_double = [value * 2.0, value / 2.0]
+                .iter()
+                .any(|scaled| (scaled - reference).abs() <= tolerance);
+            let suggestion = if half_or_double {
+                format!(
+                    "{name} {value:.2} is half or double the {reference_name} {reference:.2}, check the tempo range"
+                )
+            } else if name == "BPM tag" {
+                format!("set BPM tag to {reference:.2} from the beatgrid")
+            } else {
+                format!("title BPM {value:.2} differs from the {reference_name} {reference:.2}, fix the title")
+            };
+            suggestions.push(suggestion);
+        }
+        suggestions
+    }
+}
+
+impl Display for TempoSources {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let format_bpm = |bpm: Option<f32>| bpm.map_or_else(|| "-".to_string(), |bpm| format!("{bpm:.2}"));
+        write!(
+            f,
+            "tag {}, beatgrid {}, title {}",
+            format_bpm(self.tag),
+            format_bpm(self.beatgrid),
+            format_bpm(self.title)
+        )
+    }
+}
+
+/// Parse BPM value mentioned in text, for example a title like `Intro (128 BPM)`.
+#[must_use]
+pub fn bpm_from_text(text: &str) -> Option<f32> {
+    RE_TITLE_BPM
+        .captures(text)
+        .and_then(|captures| captures[1].parse::<f32>().ok())
+        .filter(|bpm| (40.0..=250.0).contains(bpm))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bpm_from_text() {
+        assert_eq!(bpm_from_text("Track (Intro 128 BPM)"), Some(128.0));
+        assert_eq!(bpm_from_text("Track - 94bpm Edit"), Some(94.0));
+        assert_eq!(bpm_from_text("Track (Extended Mix)"), None);
+        assert_eq!(bpm_from_text("Track 1000 BPM"), None);
+    }
+
+    #[test]
+    fn test_check_tempo() {
+        let consistent = TempoSources {
+            tag: Some(128.0),
+            beatgrid: Some(127.9),
+            title: Some(128.0),
+        };
+        assert!(consistent.check(0.5).is_empty());
+
+        let wrong_tag = TempoSources {
+            tag: Some(124.0),
+            beatgrid: Some(128.0),
+            title: None,
+        };
+        assert_eq!(wrong_tag.check(0.5), vec!["set BPM tag to 128.00 from the beatgrid"]);
+
+        let double = TempoSources {
+            tag: Some(170.0),
+            beatgrid: Some(85.0),
+            title: None,
+        };
+        assert!(double.check(0.5)[0].contains("half or double"));
+
+        let title_only = TempoSources {
+            title: Some(100.0),
+            ..TempoSources::default()
+        };
+        assert!(title_only.check(0.5).is_empty());
+    }
+}
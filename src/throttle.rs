@@ -0,0 +1,103 @@
+use std::fmt;
+use std::str::FromStr;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::TrackRenameError;
+
+/// Maximum number of operations per second, given as `N/s`, `N/min`, or just `N` for per second.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Rate {
+    per_second: f64,
+}
+
+/// Waits between operations so they are not done faster than the given rate.
+#[derive(Debug)]
+pub struct Throttle {
+    interval: Duration,
+    last: Option<Instant>,
+}
+
+impl Rate {
+    /// Minimum time between two operations.
+    #[must_use]
+    pub fn interval(self) -> Duration {
+        Duration::from_secs_f64(1.0 / self.per_second)
+    }
+}
+
+impl FromStr for Rate {
+    type Err = TrackRenameError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (count, seconds) = match s.trim().split_once('/') {
+            None => (s.trim(), 1.0),
+            Some((count, "s" | "sec")) => (count, 1.0),
+            Some((count, "m" | "min")) => (count, 60.0),
+            Some((_, unit)) => {
+                return Err(TrackRenameError::InvalidArgument(format!(
+                    "Unsupported rate unit \"{unit}\", use N/s or N/min"
+                )));
+            }
+        };
+        let count: f64 = count
+            .trim()
+            .parse()
+            .map_err(|e| TrackRenameError::InvalidArgument(format!("Invalid rate: {e}")))?;
+        if !count.is_finite() || count <= 0.0 {
+            return Err(TrackRenameError::InvalidArgument(format!(
+                "Rate should be greater than zero: {s}"
+            )));
+        }
+        Ok(Self {
+            per_second: count / seconds,
+        })
+    }
+}
+
+impl fmt::Display for Rate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.per_second < 1.0 {
+            write!(f, "{}/min", self.per_second * 60.0)
+        } else {
+            write!(f, "{}/s", self.per_second)
+        }
+    }
+}
+
+impl Throttle {
+    #[must_use]
+    pub fn new(rate: Rate) -> Self {
+        Self {
+            interval: rate.interval(),
+            last: None,
+        }
+    }
+
+    /// Sleep until the interval since the previous call has passed.
+    pub fn wait(&mut self) {
+        if let Some(remaining) = self.last.and_then(|last| self.interval.checked_sub(last.elapsed())) {
+            thread::sleep(remaining);
+        }
+        self.last = Some(Instant::now());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_rate() {
+        assert_eq!(Rate::from_str("5/s").unwrap().interval(), Duration::from_millis(200));
+        assert_eq!(Rate::from_str("2").unwrap().interval(), Duration::from_millis(500));
+        assert_eq!(Rate::from_str("30/min").unwrap().interval(), Duration::from_secs(2));
+        assert_eq!(Rate::from_str("30/min").unwrap().to_string(), "30/min");
+        assert_eq!(Rate::from_str("10/s").unwrap().to_string(), "10/s");
+        assert!(Rate::from_str("0/s").is_err());
+        assert!(Rate::from_str("5/h").is_err());
+        assert!(Rate::from_str("fast").is_err());
+    }
+}
@@ -1,25 +1,31 @@
 use std::cmp::Ordering;
 use std::fmt;
+use std::fs;
 use std::path::{Path, PathBuf};
 use std::process::Command;
 use std::str::FromStr;
 use std::sync::LazyLock;
 
-use anyhow::Context;
 use colored::Colorize;
-use id3::Tag;
+use id3::{Tag, TagLike, Version};
 use serde::{Deserialize, Serialize};
 use unicode_normalization::UnicodeNormalization;
 
+use crate::audio::{self, AiffInfo, AudioProperties, ResampleTarget, WavInfo};
+use crate::error::{Result, TrackRenameError};
 use crate::file_format::FileFormat;
+use crate::formatting::{AlbumArtist, FormatOptions, RuleCategory, Subtitle, YearInTitle, VARIOUS_ARTISTS};
 use crate::genre::GENRE_MAPPINGS;
+use crate::mpeg::Mp3Info;
+use crate::prompt::FieldChange;
 use crate::tags::TrackTags;
 use crate::utils;
-use crate::utils::{get_file_modified_time, path_to_string, path_to_string_relative};
-use crate::{formatting, genre};
+use crate::utils::{modified_time, path_to_string, path_to_string_relative};
+use crate::{artists, formatting, genre};
 
 // Other audio file extensions that should trigger a warning message,
-const OTHER_FILE_EXTENSIONS: [&str; 3] = ["wav", "flac", "m4a"];
+/// Audio file extensions that are not supported and should be converted.
+pub const OTHER_FILE_EXTENSIONS: [&str; 2] = ["flac", "m4a"];
 
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
 
@@ -59,18 +65,38 @@ pub struct Track {
 pub struct TrackMetadata {
     /// Last modified timestamp provided by the OS.
     pub modified: u64,
+    /// File size in bytes.
+    #[serde(default)]
+    pub size: u64,
     /// The track-rename library version this file was last processed with.
     pub version: String,
+    /// The formatting rules version this file was last processed with.
+    #[serde(default)]
+    pub rules_version: u32,
+    /// Hash of the formatting options this file was last processed with, see [`FormatOptions::state_hash`].
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub format_hash: u64,
+    /// User and machine that last processed this file, see [`crate::state::user`].
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub user: String,
+    /// Track ID stored in the file tags, see [`crate::track_id`].
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub id: String,
+}
+
+#[allow(clippy::trivially_copy_pass_by_ref)]
+const fn is_zero(value: &u64) -> bool {
+    *value == 0
 }
 
 impl Track {
     /// New Track from the given path.
-    pub fn new(path: &Path) -> anyhow::Result<Self> {
+    pub fn new(path: &Path) -> Result<Self> {
         let extension = path
             .extension()
-            .context("Failed to get file extension")?
+            .ok_or_else(|| TrackRenameError::invalid_path(path, "Failed to get file extension"))?
             .to_str()
-            .context("File extension contains invalid Unicode")?
+            .ok_or_else(|| TrackRenameError::invalid_path(path, "File extension contains invalid Unicode"))?
             .to_string();
 
         let format = FileFormat::from_str(&extension)?;
@@ -81,10 +107,13 @@ impl Track {
     /// Note that extension string is necessary in addition to format
     /// since the file name extension might differ from the one used by `FileFormat`,
     /// in which case it would not point to the original filename.
-    pub fn new_with_extension(path: &Path, extension: String, format: FileFormat) -> anyhow::Result<Self> {
+    pub fn new_with_extension(path: &Path, extension: String, format: FileFormat) -> Result<Self> {
         let name = Self::get_nfc_filename_from_path(path)?;
-        let root = path.parent().context("Failed to get file root")?.to_owned();
-        let directory = utils::get_filename_from_path(&root).context("Failed to get parent directory name")?;
+        let root = path
+            .parent()
+            .ok_or_else(|| TrackRenameError::invalid_path(path, "Failed to get file root"))?
+            .to_owned();
+        let directory = utils::get_filename_from_path(&root)?;
 
         // Rebuild the full path with desired Unicode handling
         let path = dunce::simplified(root.join(format!("{name}.{extension}")).as_path()).to_path_buf();
@@ -101,6 +130,31 @@ impl Track {
         })
     }
 
+    /// New Track for formatting tag values without accessing the file.
+    ///
+    /// The path is only used for the directory and genre specific rules, so it does not need to exist.
+    #[must_use]
+    pub fn without_file(path: &Path) -> Self {
+        let root = path.parent().map(Path::to_path_buf).unwrap_or_default();
+        Self {
+            name: path
+                .file_stem()
+                .map(|stem| utils::normalize_str(&stem.to_string_lossy()))
+                .unwrap_or_default(),
+            extension: path
+                .extension()
+                .map(|extension| extension.to_string_lossy().to_string())
+                .unwrap_or_default(),
+            directory: root
+                .file_name()
+                .map(|directory| directory.to_string_lossy().to_string())
+                .unwrap_or_default(),
+            root,
+            path: path.to_path_buf(),
+            ..Default::default()
+        }
+    }
+
     #[must_use]
     pub fn try_from_path(path: &Path) -> Option<Self> {
         let extension = path.extension().and_then(|e| e.to_str()).unwrap_or_default().trim();
@@ -129,7 +183,7 @@ impl Track {
                         )
                         .bright_yellow()
                     );
-                };
+                }
             }
         }
         None
@@ -141,54 +195,245 @@ impl Track {
         format!("{}.{}", self.name, self.extension)
     }
 
-    pub fn format_tags(&mut self, file_tags: &Tag) {
-        let mut tags = TrackTags::parse_tag_data(self, file_tags);
-        let (formatted_artist, formatted_title) =
-            formatting::format_tags_for_artist_and_title(&tags.current_artist, &tags.current_title);
+    pub fn format_tags(&mut self, file_tags: &Tag, options: &FormatOptions) {
+        let tags = TrackTags::parse_tag_data(self, file_tags, options.filename_separator);
+        self.format_tag_values(tags, options);
+    }
+
+    /// Format the current values in the given tags and use them as the tags of this track.
+    ///
+    /// Shared by the renamer and the plugin API, so both give the same formatted values.
+    pub fn format_tag_values(&mut self, mut tags: TrackTags, options: &FormatOptions) {
+        let rules = options.rules;
+        let formatted_album = if rules.contains(RuleCategory::Album) {
+            self.format_album(&tags.current_album)
+        } else {
+            tags.current_album.clone()
+        };
+        let formatted_genre = if rules.contains(RuleCategory::Genre) {
+            self.format_genre(&tags.current_genre)
+        } else {
+            tags.current_genre.clone()
+        };
+        let (formatted_artist, formatted_title) = formatting::format_tags_in_bpm_range_cached(
+            &tags.current_artist,
+            &Self::title_with_subtitle(&tags.current_title, &tags.current_subtitle, options),
+            rules,
+            options.bpm_ranges.range_for_track(&self.path, &formatted_genre),
+        );
 
-        let mut formatted_album = formatting::format_album(&tags.current_album);
-        let mut formatted_genre = genre::format_genre(&tags.current_genre);
+        tags.formatted_name = format!("{formatted_artist} - {formatted_title}");
+        tags.formatted_artist = formatted_artist;
+        tags.formatted_title = formatted_title;
+        tags.formatted_album = formatted_album;
+        tags.formatted_genre = formatted_genre;
 
-        if formatted_album.is_empty() && self.directory.to_lowercase().starts_with("djcity") {
-            formatted_album = "DJCity.com".to_string();
+        self.tags = tags;
+        self.move_year_to_tag(options.year_in_title);
+        self.move_subtitle(options.subtitle);
+        self.format_album_artist(options);
+    }
+
+    /// Format the given artist and title instead of the values from the tags.
+    pub fn format_artist_and_title(&mut self, artist: &str, title: &str, options: &FormatOptions) {
+        let (formatted_artist, formatted_title) = formatting::format_tags_in_bpm_range_cached(
+            artist,
+            &Self::title_with_subtitle(title, &self.tags.current_subtitle, options),
+            options.rules,
+            options
+                .bpm_ranges
+                .range_for_track(&self.path, &self.tags.formatted_genre),
+        );
+        self.tags.formatted_name = format!("{formatted_artist} - {formatted_title}");
+        self.tags.formatted_artist = formatted_artist;
+        self.tags.formatted_title = formatted_title;
+        self.move_year_to_tag(options.year_in_title);
+        self.move_subtitle(options.subtitle);
+        self.format_album_artist(options);
+    }
+
+    /// Format the album artist and fill in a missing one if configured.
+    ///
+    /// A filled album artist leaves out featured artists.
+    fn format_album_artist(&mut self, options: &FormatOptions) {
+        let current = &self.tags.current_album_artist;
+        self.tags.formatted_album_artist = if current.is_empty() {
+            match options.album_artist {
+                AlbumArtist::Format => String::new(),
+                AlbumArtist::Fill if !self.tags.compilation => {
+                    formatting::split_featured_artists(&self.tags.formatted_artist)
+                        .0
+                        .to_string()
+                }
+                AlbumArtist::Fill | AlbumArtist::Compilation => VARIOUS_ARTISTS.to_string(),
+            }
+        } else {
+            formatting::format_album_artist(current, options)
+        };
+    }
+
+    /// Title to format, with the subtitle descriptors added when moving them back to the title.
+    fn title_with_subtitle(title: &str, subtitle: &str, options: &FormatOptions) -> String {
+        if options.subtitle == Subtitle::Title {
+            formatting::join_subtitle_to_title(title, subtitle)
+        } else {
+            title.to_string()
+        }
+    }
+
+    /// Move mix and version descriptors between the formatted title and the subtitle tag if configured.
+    ///
+    /// The descriptors stay in the title if the subtitle tag already has a different value.
+    fn move_subtitle(&mut self, subtitle: Subtitle) {
+        self.tags.formatted_subtitle = match subtitle {
+            Subtitle::Keep => self.tags.current_subtitle.clone(),
+            // Already added to the title before formatting
+            Subtitle::Title => String::new(),
+            Subtitle::Tag => {
+                let current = &self.tags.current_subtitle;
+                match formatting::split_subtitle_from_title(&self.tags.formatted_title) {
+                    Some((title, subtitle)) if current.is_empty() || *current == subtitle => {
+                        self.tags.formatted_title = title.to_string();
+                        self.tags.formatted_name =
+                            format!("{} - {}", self.tags.formatted_artist, self.tags.formatted_title);
+                        subtitle
+                    }
+                    _ => current.clone(),
+                }
+            }
+        };
+    }
+
+    /// Move a year at the end of the formatted title to the year tag if configured.
+    ///
+    /// The year stays in the title if the year tag already has a different year.
+    fn move_year_to_tag(&mut self, year_in_title: YearInTitle) {
+        self.tags.formatted_year = self.tags.current_year;
+        if year_in_title != YearInTitle::Tag {
+            return;
         }
-        if formatted_album.is_empty() && self.directory.to_lowercase().starts_with("trayze") {
-            formatted_album = "djtrayze.com".to_string();
+        let Some((title, year)) = formatting::split_year_from_title(&self.tags.formatted_title) else {
+            return;
+        };
+        if self.tags.current_year.is_none_or(|current| current == year) {
+            self.tags.formatted_title = title.to_string();
+            self.tags.formatted_name = format!("{} - {}", self.tags.formatted_artist, self.tags.formatted_title);
+            self.tags.formatted_year = Some(year);
         }
+    }
 
+    /// Artist from the filename if it differs from the formatted artist tag.
+    ///
+    /// Returns `None` if the file has no artist tag or the filename has no artist.
+    #[must_use]
+    pub fn filename_artist_conflict(&self, file_tags: &Tag, options: &FormatOptions) -> Option<String> {
+        let tag_artist = file_tags.artist()?;
+        if tag_artist.trim().is_empty() || tag_artist.trim().eq_ignore_ascii_case("Various Artists") {
+            return None;
+        }
+        options.filename_separator.split(&self.name)?;
+        let (artist, title) = utils::get_tags_from_filename(&self.name, options.filename_separator)?;
+        let (formatted_artist, _) = formatting::format_tags_cached(&artist, &title, options);
+        (!formatted_artist.is_empty()
+            && artists::normalized_key(&formatted_artist) != artists::normalized_key(&self.tags.formatted_artist))
+        .then_some(artist)
+    }
+
+    /// Format album and fill in the album for known record pool directories.
+    fn format_album(&self, album: &str) -> String {
+        let formatted_album = formatting::format_album(album);
+        if formatted_album.is_empty() && self.directory.to_lowercase().starts_with("djcity") {
+            "DJCity.com".to_string()
+        } else if formatted_album.is_empty() && self.directory.to_lowercase().starts_with("trayze") {
+            "djtrayze.com".to_string()
+        } else {
+            formatted_album
+        }
+    }
+
+    /// Format genre and fill in the genre mapped from the directory name.
+    fn format_genre(&self, genre: &str) -> String {
+        let formatted_genre = genre::format_genre(genre);
         if formatted_genre.is_empty()
             && (self.root.ends_with(DJ_MUSIC_PATH.as_path()) || GENRE_MAPPINGS.contains_key(self.directory.as_str()))
         {
-            formatted_genre = (*GENRE_MAPPINGS.get(self.directory.as_str()).unwrap_or(&"")).to_string();
+            (*GENRE_MAPPINGS.get(self.directory.as_str()).unwrap_or(&"")).to_string()
+        } else {
+            formatted_genre
         }
+    }
 
-        tags.formatted_name = format!("{formatted_artist} - {formatted_title}");
-        tags.formatted_artist = formatted_artist;
-        tags.formatted_title = formatted_title;
-        tags.formatted_album = formatted_album;
-        tags.formatted_genre = formatted_genre;
+    /// Return formatted file name without the file extension.
+    #[must_use]
+    pub fn formatted_filename(&self, options: &FormatOptions) -> String {
+        // Filenames keep the full values when the tags were shortened to the length limits
+        let (artist, title) = self.tags.untruncated.as_ref().map_or(
+            (&self.tags.formatted_artist, &self.tags.formatted_title),
+            |(artist, title)| (artist, title),
+        );
+        // Filenames keep the descriptors moved to the subtitle tag so different versions stay apart
+        let title = if options.subtitle == Subtitle::Tag {
+            formatting::join_subtitle_to_title(title, &self.tags.formatted_subtitle)
+        } else {
+            title.clone()
+        };
+        let (file_artist, file_title) = formatting::format_filename(artist, &title);
+        options.filename_separator.join(&file_artist, &file_title)
+    }
 
-        self.tags = tags;
+    /// Return the formatted file name using only the artist and title parsed from the current file name.
+    ///
+    /// Returns `None` if the file name does not have a separator between the artist and title.
+    #[must_use]
+    pub fn formatted_filename_from_name(&self, options: &FormatOptions) -> Option<String> {
+        let separator = options.filename_separator;
+        let (artist, title) = separator.split(&self.name)?;
+        let (artist, title) = formatting::format_tags_with_options(
+            &utils::normalize_str(artist.trim()),
+            &utils::normalize_str(title.trim()),
+            options.rules,
+            options.bpm_ranges.default,
+        );
+        let (file_artist, file_title) = formatting::format_filename(&artist, &title);
+        Some(separator.join(&file_artist, &file_title))
     }
 
-    /// Return formatted file name without the file extension.
+    /// Return formatted file name with the file extension.
     #[must_use]
-    pub fn formatted_filename(&self) -> String {
-        let (file_artist, file_title) =
-            formatting::format_filename(&self.tags.formatted_artist, &self.tags.formatted_title);
-
-        match (file_artist.is_empty(), file_title.is_empty()) {
-            (true, true) => String::new(),
-            (true, false) => file_title,
-            (false, true) => file_artist,
-            (false, false) => format!("{file_artist} - {file_title}"),
+    pub fn formatted_filename_with_extension(&self, options: &FormatOptions) -> String {
+        format!("{}.{}", self.formatted_filename(options), self.format)
+    }
+
+    /// Identifier that stays the same across runs, for referring to the track in reports.
+    ///
+    /// Uses the embedded track ID when it is known,
+    /// otherwise the path relative to the given root directory with `/` separators.
+    #[must_use]
+    pub fn report_id(&self, root: &Path) -> String {
+        if !self.metadata.id.is_empty() {
+            return self.metadata.id.clone();
         }
+        let Ok(relative) = self.path.strip_prefix(root) else {
+            return path_to_string(&self.path);
+        };
+        if relative.as_os_str().is_empty() {
+            return self.filename();
+        }
+        relative
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/")
     }
 
-    /// Return formatted file name with the file extension.
+    /// Return the filename change from the current to the formatted file name.
     #[must_use]
-    pub fn formatted_filename_with_extension(&self) -> String {
-        format!("{}.{}", self.formatted_filename(), self.format)
+    pub fn filename_change(&self, options: &FormatOptions) -> FieldChange {
+        FieldChange {
+            field: "filename".to_string(),
+            current: self.filename(),
+            proposed: self.formatted_filename_with_extension(options),
+        }
     }
 
     /// Return the full path with new filename.
@@ -198,12 +443,12 @@ impl Track {
     }
 
     /// Create new Track from existing Track that has been renamed.
-    pub fn renamed_track(&self, path: PathBuf, name: String) -> anyhow::Result<Self> {
-        let metadata = Self::read_metadata(&path)?;
+    pub fn renamed_track(&self, path: PathBuf, name: String) -> Result<Self> {
+        let metadata = self.read_metadata_keeping_id(&path)?;
         Ok(Self {
             name,
             extension: self.format.to_string(),
-            directory: self.directory.to_string(),
+            directory: self.directory.clone(),
             format: self.format.clone(),
             root: self.root.clone(),
             path,
@@ -216,6 +461,12 @@ impl Track {
         })
     }
 
+    /// Re-read file metadata after the file has been modified.
+    pub fn refresh_metadata(&mut self) -> Result<()> {
+        self.metadata = self.read_metadata_keeping_id(&self.path)?;
+        Ok(())
+    }
+
     /// Print track if it has not been already.
     pub fn show(&mut self, total_tracks: usize, max_width: usize) {
         if !self.printed {
@@ -229,14 +480,26 @@ impl Track {
         }
     }
 
+    /// Show the track header again on the next call to `show`.
+    pub const fn reset_printed(&mut self) {
+        self.printed = false;
+    }
+
     /// Convert mp3 file to aif using ffmpeg.
     /// Returns an updated Track if conversion was successful.
-    pub fn convert_mp3_to_aif(&self) -> anyhow::Result<Self> {
+    pub fn convert_mp3_to_aif(&self) -> Result<Self> {
         let output_path = self.path.with_extension("aif");
         let output_path_string = path_to_string_relative(&output_path);
         output_path
             .try_exists()
-            .context(format!("File already exists: {output_path_string}").red())?;
+            .map_err(|e| TrackRenameError::io(format!("File already exists: {output_path_string}"), e))?;
+
+        // ffmpeg trims the encoder delay and padding when decoding,
+        // so the AIFF file is already gapless and must not get an iTunSMPB comment that would trim it again
+        let gapless = Mp3Info::from_path(&self.path)
+            .ok()
+            .flatten()
+            .and_then(|info| info.gapless());
 
         let output = Command::new("ffmpeg")
             .args([
@@ -253,49 +516,277 @@ impl Track {
                 "4",
                 path_to_string(&output_path).as_str(),
             ])
-            .output()?;
+            .output()
+            .map_err(|e| TrackRenameError::io("Failed to run ffmpeg", e))?;
 
         if !output.status.success() {
-            anyhow::bail!(
-                "{}",
-                format!("FFmpeg error: {}", String::from_utf8_lossy(&output.stderr)).red()
-            );
+            return Err(TrackRenameError::Convert(
+                String::from_utf8_lossy(&output.stderr).trim().to_string(),
+            ));
         }
 
         output_path
             .try_exists()
-            .context(format!("Converted file does not exist: {output_path_string}").red())?;
+            .map_err(|e| TrackRenameError::io(format!("Converted file does not exist: {output_path_string}"), e))?;
 
         println!("Conversion successful: {}", output_path_string.cyan());
 
-        trash::delete(&self.path).context("Failed to move mp3 file to trash".red())?;
+        if let Some(gapless) = gapless {
+            println!("Gapless: {gapless}");
+        }
+
+        trash::delete(&self.path)?;
+        self.converted_track(output_path, FileFormat::Aif)
+    }
+
+    /// Convert the track to the given format and move the original file to the trash.
+    /// Returns an updated Track if conversion was successful.
+    ///
+    /// Tags are copied from the original file.
+    pub fn convert_to_format(&self, format: &FileFormat) -> Result<Self> {
+        if self.format == FileFormat::Mp3 && *format == FileFormat::Aif {
+            return self.convert_mp3_to_aif();
+        }
+        let output_path = self.path.with_extension(format.to_string());
+        self.convert_to(&output_path, format, false)?;
+        if let Ok(tag) = Tag::read_from_path(&self.path) {
+            tag.write_to_path(&output_path, Version::Id3v24)
+                .map_err(|source| TrackRenameError::TagWrite {
+                    path: output_path.clone(),
+                    source,
+                })?;
+        }
+        println!(
+            "Conversion successful: {}",
+            path_to_string_relative(&output_path).cyan()
+        );
+        trash::delete(&self.path)?;
+        self.converted_track(output_path, format.clone())
+    }
 
-        let metadata = Self::read_metadata(&output_path)?;
-        let new_track = Self {
+    /// Create a new Track for the converted output file.
+    fn converted_track(&self, path: PathBuf, format: FileFormat) -> Result<Self> {
+        let metadata = self.read_metadata_keeping_id(&path)?;
+        Ok(Self {
             name: self.name.clone(),
-            extension: "aif".to_string(),
-            directory: self.directory.to_string(),
-            format: FileFormat::Aif,
+            extension: format.to_string(),
+            directory: self.directory.clone(),
+            format,
             root: self.root.clone(),
-            path: output_path,
+            path,
             metadata,
             number: self.number,
             tags: TrackTags::default(),
             tags_updated: self.tags_updated,
             not_processed: self.not_processed,
             printed: self.printed,
+        })
+    }
+
+    /// Read sample rate, bit depth, and channel count from the audio file header.
+    /// Falls back to ffprobe if the header could not be parsed.
+    pub fn audio_properties(&self) -> Result<AudioProperties> {
+        let properties = match self.format {
+            FileFormat::Mp3 => AudioProperties::from_mp3(&self.path),
+            FileFormat::Aif => AudioProperties::from_aiff(&self.path),
+            FileFormat::Wav => AudioProperties::from_wav(&self.path),
+        };
+        properties.or_else(|_| AudioProperties::from_ffprobe(&self.path))
+    }
+
+    /// Audio duration in seconds calculated from the audio data.
+    pub fn duration(&self) -> Result<f64> {
+        match self.format {
+            FileFormat::Mp3 => Mp3Info::from_path(&self.path)?
+                .map(|info| info.duration())
+                .ok_or_else(|| {
+                    TrackRenameError::InvalidAudio(format!("No MPEG audio frames found: {}", self.path.display()))
+                }),
+            FileFormat::Aif => AiffInfo::from_path(&self.path).map(|info| info.duration()),
+            FileFormat::Wav => WavInfo::from_path(&self.path).map(|info| info.duration()),
+        }
+    }
+
+    /// Convert AIFC file to standard big-endian PCM AIFF using ffmpeg.
+    ///
+    /// Keeps the sample rate and bit depth, compressed audio is decoded to 16-bit.
+    pub fn convert_to_pcm_aif(&self) -> Result<()> {
+        let info = AiffInfo::from_path(&self.path)?;
+        self.resample_aif(&ResampleTarget {
+            sample_rate: info.sample_rate.round() as u32,
+            bit_depth: audio::pcm_bit_depth(info.bit_depth),
+        })
+    }
+
+    /// Resample AIFF file to the target sample rate and bit depth using ffmpeg.
+    ///
+    /// The original tag is written back afterwards so custom frames like Serato data are preserved.
+    pub fn resample_aif(&self, target: &ResampleTarget) -> Result<()> {
+        if self.format != FileFormat::Aif {
+            return Err(TrackRenameError::UnsupportedFormat(format!(
+                "Resampling is only supported for AIFF files: {}",
+                self.path.display()
+            )));
+        }
+        let temp_path = self.root.join(format!(".{}.resample.tmp", self.filename()));
+        let sample_rate = target.sample_rate.to_string();
+        let output = Command::new("ffmpeg")
+            .args([
+                "-v",
+                "error",
+                "-y",
+                "-i",
+                path_to_string(&self.path).as_str(),
+                "-map",
+                "0:a",
+                "-ar",
+                sample_rate.as_str(),
+                "-c:a",
+                target.aiff_codec(),
+                "-map_metadata",
+                "-1",
+                "-f",
+                "aiff",
+                path_to_string(&temp_path).as_str(),
+            ])
+            .output()
+            .map_err(|e| TrackRenameError::io("Failed to run ffmpeg", e))?;
+
+        if !output.status.success() {
+            let _ = std::fs::remove_file(&temp_path);
+            return Err(TrackRenameError::Convert(
+                String::from_utf8_lossy(&output.stderr).trim().to_string(),
+            ));
+        }
+
+        if let Ok(tag) = Tag::read_from_path(&self.path) {
+            tag.write_to_path(&temp_path, id3::Version::Id3v24)
+                .map_err(|source| TrackRenameError::TagWrite {
+                    path: temp_path.clone(),
+                    source,
+                })?;
+        }
+
+        std::fs::rename(&temp_path, &self.path).map_err(|source| TrackRenameError::Rename {
+            from: temp_path,
+            to: self.path.clone(),
+            source,
+        })?;
+        Ok(())
+    }
+
+    /// Convert audio to the given format at the output path using ffmpeg.
+    ///
+    /// The original file is left untouched and no tag data is copied,
+    /// so tags need to be written to the output file separately.
+    /// PCM output keeps the bit depth of the source, lossy sources are decoded to 16-bit.
+    pub fn convert_to(&self, output_path: &Path, format: &FileFormat, overwrite: bool) -> Result<()> {
+        let bit_depth = || {
+            self.audio_properties()
+                .map_or(16, |properties| properties.pcm_bit_depth())
         };
+        let codec_args = match format {
+            FileFormat::Mp3 => vec!["-c:a", "libmp3lame", "-b:a", "320k", "-f", "mp3"],
+            FileFormat::Aif => vec!["-c:a", audio::aiff_codec(bit_depth()), "-f", "aiff"],
+            FileFormat::Wav => vec!["-c:a", audio::wav_codec(bit_depth()), "-f", "wav"],
+        };
+        let output = Command::new("ffmpeg")
+            .args([
+                "-v",
+                "error",
+                if overwrite { "-y" } else { "-n" },
+                "-i",
+                path_to_string(&self.path).as_str(),
+                "-map",
+                "0:a",
+                "-map_metadata",
+                "-1",
+            ])
+            .args(codec_args)
+            .arg(path_to_string(output_path))
+            .output()
+            .map_err(|e| TrackRenameError::io("Failed to run ffmpeg", e))?;
 
-        Ok(new_track)
+        if !output.status.success() {
+            return Err(TrackRenameError::Convert(
+                String::from_utf8_lossy(&output.stderr).trim().to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Rebuild the MP3 VBR header by remuxing the audio stream with ffmpeg.
+    ///
+    /// The audio data is copied as is, and the original tag is written back afterwards in the same version
+    /// so custom frames like Serato data are preserved.
+    pub fn rebuild_vbr_header(&self) -> Result<()> {
+        let temp_path = self.root.join(format!(".{}.remux.tmp", self.filename()));
+        let output = Command::new("ffmpeg")
+            .args([
+                "-v",
+                "error",
+                "-y",
+                "-i",
+                path_to_string(&self.path).as_str(),
+                "-map",
+                "0:a",
+                "-c",
+                "copy",
+                "-map_metadata",
+                "-1",
+                "-write_xing",
+                "1",
+                "-f",
+                "mp3",
+                path_to_string(&temp_path).as_str(),
+            ])
+            .output()
+            .map_err(|e| TrackRenameError::io("Failed to run ffmpeg", e))?;
+
+        if !output.status.success() {
+            let _ = std::fs::remove_file(&temp_path);
+            return Err(TrackRenameError::Convert(
+                String::from_utf8_lossy(&output.stderr).trim().to_string(),
+            ));
+        }
+
+        // Keep the original file if its tag could not be copied to the remuxed file
+        let tag = match Tag::read_from_path(&self.path) {
+            Ok(tag) => Some(tag),
+            Err(error) if matches!(error.kind, id3::ErrorKind::NoTag) => None,
+            Err(source) => {
+                let _ = std::fs::remove_file(&temp_path);
+                return Err(TrackRenameError::TagRead {
+                    path: self.path.clone(),
+                    source,
+                });
+            }
+        };
+        if let Some(tag) = tag {
+            if let Err(source) = tag.write_to_path(&temp_path, tag.version()) {
+                let _ = std::fs::remove_file(&temp_path);
+                return Err(TrackRenameError::TagWrite {
+                    path: temp_path,
+                    source,
+                });
+            }
+        }
+
+        std::fs::rename(&temp_path, &self.path).map_err(|source| TrackRenameError::Rename {
+            from: temp_path,
+            to: self.path.clone(),
+            source,
+        })?;
+        Ok(())
     }
 
     /// Get filename from Path with special characters retained instead of decomposed.
-    fn get_nfc_filename_from_path(path: &Path) -> anyhow::Result<String> {
+    fn get_nfc_filename_from_path(path: &Path) -> Result<String> {
         Ok(path
             .file_stem()
-            .context("Failed to get file stem")?
+            .ok_or_else(|| TrackRenameError::invalid_path(path, "Failed to get file stem"))?
             .to_str()
-            .context("Filename contains invalid Unicode")?
+            .ok_or_else(|| TrackRenameError::invalid_path(path, "Filename contains invalid Unicode"))?
             // Rust uses unicode NFD (Normalization Form Decomposed) by default,
             // which converts special chars like "å" to "a\u{30a}",
             // which then get printed as a regular "a".
@@ -306,18 +797,34 @@ impl Track {
             .collect::<String>())
     }
 
-    /// Read file metadata for track.
-    fn read_metadata(path: &Path) -> anyhow::Result<TrackMetadata> {
-        if !path.exists() {
+    /// Read file metadata for track with a single stat call.
+    fn read_metadata(path: &Path) -> Result<TrackMetadata> {
+        let Ok(metadata) = fs::metadata(path) else {
             #[cfg(test)]
             return Ok(TrackMetadata::default());
             #[cfg(not(test))]
-            anyhow::bail!("File does not exist: {}", path.display());
-        }
-        let modified = get_file_modified_time(path)?;
+            return Err(TrackRenameError::NotFound(path.to_path_buf()));
+        };
         Ok(TrackMetadata {
-            modified,
+            modified: modified_time(&metadata)?,
+            size: metadata.len(),
             version: VERSION.to_string(),
+            rules_version: formatting::RULES_VERSION,
+            format_hash: 0,
+            user: String::new(),
+            id: String::new(),
+        })
+    }
+
+    /// Read file metadata for a modified, renamed, or converted file of this track.
+    ///
+    /// The track ID is stored in the tags, so it is kept from the current metadata
+    /// together with the formatting options hash.
+    fn read_metadata_keeping_id(&self, path: &Path) -> Result<TrackMetadata> {
+        Ok(TrackMetadata {
+            id: self.metadata.id.clone(),
+            format_hash: self.metadata.format_hash,
+            ..Self::read_metadata(path)?
         })
     }
 }
@@ -398,6 +905,59 @@ mod tests {
         assert_eq!(track.filename(), "test_song.mp3");
     }
 
+    #[test]
+    fn test_report_id() {
+        let root = Path::new("/music");
+        let mut track = Track::new(Path::new("/music/House/Artist - Song.mp3")).expect("Failed to create track");
+        assert_eq!(track.report_id(root), "House/Artist - Song.mp3");
+        assert_eq!(track.report_id(&track.path.clone()), "Artist - Song.mp3");
+        assert_eq!(track.report_id(Path::new("/other")), "/music/House/Artist - Song.mp3");
+
+        track.metadata.id = "3f2b9c1e-0000-4000-8000-000000000000".to_string();
+        assert_eq!(track.report_id(root), "3f2b9c1e-0000-4000-8000-000000000000");
+    }
+
+    #[test]
+    fn test_formatted_filename_from_name() {
+        let options = FormatOptions::default();
+        let track = Track::new(Path::new("/music/Artist - Song (Original Mix).mp3")).expect("Failed to create track");
+        assert_eq!(
+            track.formatted_filename_from_name(&options).as_deref(),
+            Some("Artist - Song")
+        );
+
+        let track = Track::new(Path::new("/music/Artist - Song.mp3")).expect("Failed to create track");
+        assert_eq!(
+            track.formatted_filename_from_name(&options).as_deref(),
+            Some("Artist - Song")
+        );
+
+        let track = Track::new(Path::new("/music/Song.mp3")).expect("Failed to create track");
+        assert_eq!(track.formatted_filename_from_name(&options), None);
+    }
+
+    #[test]
+    fn test_filename_artist_conflict() {
+        let options = FormatOptions::default();
+        let mut tag = Tag::new();
+        tag.set_artist("Other Artist");
+        tag.set_title("Song");
+        let mut track = Track::new(Path::new("/music/Artist ft Someone - Song.mp3")).expect("Failed to create track");
+        track.format_tags(&tag, &options);
+        assert_eq!(
+            track.filename_artist_conflict(&tag, &options).as_deref(),
+            Some("Artist ft Someone")
+        );
+        track.format_artist_and_title("Artist ft Someone", "Song", &options);
+        assert_eq!(track.tags.formatted_artist, "Artist feat. Someone");
+        assert_eq!(track.filename_artist_conflict(&tag, &options), None);
+
+        tag.set_artist("artist feat. someone");
+        track.format_tags(&tag, &options);
+        assert_eq!(track.filename_artist_conflict(&tag, &options), None);
+        assert_eq!(track.filename_artist_conflict(&Tag::new(), &options), None);
+    }
+
     #[test]
     fn test_track_with_special_characters() {
         let path = Path::new("/Users/esgrove/Räntä & Benjamin Mùll - Sippa På En Tequila (Ö Remix).mp3");
@@ -0,0 +1,74 @@
+use std::fmt::Write;
+use std::path::Path;
+
+use id3::frame::ExtendedText;
+use id3::{Tag, TagLike};
+
+use crate::utils;
+
+/// Description of the `TXXX` frame that stores the track ID.
+pub const FRAME_DESCRIPTION: &str = "TRACK_RENAME_ID";
+
+/// Read the track ID from the tag.
+#[must_use]
+pub fn read(tag: &Tag) -> Option<String> {
+    tag.extended_texts()
+        .find(|text| text.description == FRAME_DESCRIPTION)
+        .map(|text| text.value.trim().to_string())
+        .filter(|id| !id.is_empty())
+}
+
+/// Read the track ID from a file without reading the full tag.
+#[must_use]
+pub fn read_from_path(path: &Path) -> Option<String> {
+    utils::read_text_tags_from_path(path).ok().as_ref().and_then(read)
+}
+
+/// Store the track ID in the tag, replacing an existing one.
+pub fn set(tag: &mut Tag, id: &str) {
+    tag.remove_extended_text(Some(FRAME_DESCRIPTION), None);
+    tag.add_frame(ExtendedText {
+        description: FRAME_DESCRIPTION.to_string(),
+        value: id.to_string(),
+    });
+}
+
+/// Generate a new random version 4 UUID.
+#[must_use]
+pub fn generate() -> String {
+    let mut bytes: [u8; 16] = rand::random();
+    bytes[6] = (bytes[6] & 0x0F) | 0x40;
+    bytes[8] = (bytes[8] & 0x3F) | 0x80;
+    let mut id = String::with_capacity(36);
+    for (index, byte) in bytes.iter().enumerate() {
+        if matches!(index, 4 | 6 | 8 | 10) {
+            id.push('-');
+        }
+        let _ = write!(id, "{byte:02x}");
+    }
+    id
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generate_uuid() {
+        let id = generate();
+        assert_eq!(id.len(), 36);
+        assert_eq!(id.chars().nth(14), Some('4'));
+        assert!(matches!(id.chars().nth(19), Some('8' | '9' | 'a' | 'b')));
+        assert_ne!(id, generate());
+    }
+
+    #[test]
+    fn test_set_and_read_id() {
+        let mut tag = Tag::new();
+        assert_eq!(read(&tag), None);
+        set(&mut tag, "first");
+        set(&mut tag, "second");
+        assert_eq!(read(&tag), Some("second".to_string()));
+        assert_eq!(tag.extended_texts().count(), 1);
+    }
+}
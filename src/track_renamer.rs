@@ -1,54 +1,166 @@
-use std::collections::{HashMap, HashSet};
+use std::any::Any;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::error::Error;
 use std::fs;
 use std::fs::File;
 use std::io::{self, Write};
+use std::panic::{self, AssertUnwindSafe};
 use std::path::{Path, PathBuf};
 use std::string::String;
-use std::time::Instant;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
 
 use anyhow::{Context, Result};
-use colored::Colorize;
-use id3::{Tag, TagLike};
+use colored::{ColoredString, Colorize};
+use id3::{Tag, TagLike, Timestamp};
 use itertools::Itertools;
+use rand::seq::SliceRandom;
 use rayon::prelude::*;
+use rayon::ThreadPool;
 
-use crate::config::Config;
+use crate::config::{ArtistConflict, Config, MinorChanges, Placeholders, Reveal, TrackOrder};
+use crate::decisions::{Action, Decision, DecisionLog, EditedTags};
+use crate::feat_index::FeatIndex;
+use crate::prefetch::TagPrefetcher;
 use crate::statistics::Statistics;
 use crate::RenamerArgs;
 
-use track_rename::file_format::FileFormat;
+use track_rename::ape;
+use track_rename::artists::{self, KnownArtists, SplitCandidate};
+use track_rename::artwork::{self, ArtworkResult};
+use track_rename::audio::{AiffInfo, AudioProperties, ResampleTarget};
+use track_rename::canonical_name;
+use track_rename::corpus::{self, CorpusEntry};
+use track_rename::duplicate_frames::{self, DuplicateFrames};
+use track_rename::error::TrackRenameError;
+use track_rename::events::{self, Event};
+use track_rename::failure::{FailedFile, FailureCategory};
+use track_rename::file_format::{self, FileFormat};
+use track_rename::formatting::{self, FormatOptions};
 use track_rename::genre::GENRE_MAPPINGS;
+use track_rename::i18n::Message;
+use track_rename::mpeg::Mp3Info;
+use track_rename::output::{OrderedOutput, TrackOutput};
+use track_rename::playlist::{self, Playlist};
+use track_rename::prompt::{self, Answer, ChangePattern, FieldChange, PromptContext};
+use track_rename::rename_journal::RenameJournal;
+use track_rename::repair;
+use track_rename::riff;
 use track_rename::serato;
-use track_rename::state::State;
-use track_rename::track::{Track, DJ_MUSIC_PATH};
-use track_rename::utils;
+use track_rename::serato::crates::{self, SeratoCrate};
+use track_rename::state::{RenameHistory, State, TagHistory};
+use track_rename::tag_reader;
+use track_rename::throttle::Throttle;
+use track_rename::track::{Track, DJ_MUSIC_PATH, OTHER_FILE_EXTENSIONS};
+use track_rename::track_id;
+use track_rename::utils::{self, CaseSensitivity, GatheredFiles, SkippedPath};
 
 /// Audio track tag and filename formatting.
 #[derive(Debug, Default)]
 pub struct TrackRenamer {
     root: PathBuf,
     config: Config,
+    /// Formatting options from the config
+    format: FormatOptions,
     state: State,
     stats: Statistics,
     tracks: Vec<Track>,
     tracks_count: usize,
-    failed_files: Vec<String>,
+    failed_files: Vec<FailedFile>,
     processed_files: HashMap<String, Vec<Track>>,
     genres: HashMap<String, usize>,
+    descriptors: HashMap<String, usize>,
+    feat_index: FeatIndex,
+    /// Artist names from the library for splitting "Various Artists" titles
+    known_artists: KnownArtists,
+    /// Original and formatted artist and title for `--record-corpus`
+    corpus: BTreeSet<CorpusEntry>,
     tag_versions: HashMap<String, usize>,
+    audio_formats: HashMap<String, usize>,
     checked_genre_mappings: HashSet<String>,
-    current_path: PathBuf,
+    /// Missing and empty genre mapping warnings printed at the end of the run
+    genre_mapping_warnings: Vec<String>,
+    /// Tag values over the `max_length` limits or shortened to them, printed at the end of the run
+    tag_length_warnings: Vec<String>,
+    output: OrderedOutput<io::Stdout>,
+    playlist: Option<Playlist>,
+    /// Renamed and converted files in this run
+    renames: RenameHistory,
+    /// Journal of renames in the order they were applied for `--rename-journal`
+    rename_journal: Option<RenameJournal>,
+    /// Rate limiter for `--throttle-renames`
+    rename_throttle: Option<Throttle>,
+    /// Tracks added to the state or modified in this run for `--new-playlist`
+    new_tracks: Vec<PathBuf>,
+    /// Playlist for tracks with accepted changes for `--reveal`
+    review_playlist: Option<Playlist>,
+    /// Tag values written in this run
+    tag_history: TagHistory,
+    /// User chose to stop processing
+    quit: bool,
+    /// Interactive answers in this run
+    decisions: DecisionLog,
+    /// Thread pool for concurrent file reads
+    io_pool: Option<Arc<ThreadPool>>,
+    /// Probed filesystem case sensitivity for renames
+    case_sensitivity: CaseSensitivity,
+    /// Directories and files that could not be read while gathering tracks
+    skipped_paths: Vec<SkippedPath>,
+    /// MP3 files that still have an `APEv2` tag
+    ape_files: Vec<PathBuf>,
+    /// Files with chapter frames that are not removed
+    chapter_files: Vec<PathBuf>,
+    /// Online-only cloud storage files that were skipped
+    placeholder_files: Vec<PathBuf>,
+    /// Files that failed with a transient error and are tried again at the end of the run
+    retry_paths: Vec<PathBuf>,
+    retries_left: usize,
 }
 
 impl TrackRenamer {
     /// Create Renamer from command line arguments.
-    pub fn new(path: PathBuf, args: &RenamerArgs) -> Self {
-        Self {
+    pub fn new(path: PathBuf, args: &RenamerArgs) -> Result<Self> {
+        let mut config = Config::from_args(args)?;
+        if let Some(dir) = &config.copy_to {
+            let dir = if dir.exists() {
+                dunce::canonicalize(dir)?
+            } else {
+                std::path::absolute(dir).context("Failed to resolve copy destination path")?
+            };
+            if dir.starts_with(&path) {
+                anyhow::bail!(
+                    "Copy destination can't be inside the input directory: {}",
+                    path.display()
+                );
+            }
+            config.copy_to = Some(dir);
+        }
+        if let Some(path) = &config.new_playlist {
+            // Check the file format before processing any tracks
+            Playlist::new(path)?;
+        }
+        let review_playlist = match &config.reveal {
+            Some(Reveal::Playlist(path)) if path.is_file() => Some(Playlist::read(path)?),
+            Some(Reveal::Playlist(path)) => Some(Playlist::new(path)?),
+            _ => None,
+        };
+        let rename_journal = config
+            .rename_journal
+            .as_deref()
+            .filter(|_| !config.read_only)
+            .map(RenameJournal::open)
+            .transpose()?;
+        Ok(Self {
             root: path,
-            config: Config::from_args(args),
+            rename_throttle: config.throttle_renames.map(Throttle::new),
+            rename_journal,
+            review_playlist,
+            format: config.format_options(),
+            config,
             state: State::load(),
             ..Default::default()
-        }
+        })
     }
 
     #[cfg(test)]
@@ -56,6 +168,7 @@ impl TrackRenamer {
     pub fn new_with_config(path: PathBuf, config: Config) -> Self {
         Self {
             root: path,
+            format: config.format_options(),
             config,
             ..Default::default()
         }
@@ -71,10 +184,71 @@ impl TrackRenamer {
         if self.config.convert_failed && !utils::ffmpeg_available() {
             anyhow::bail!("Convert failed specified but ffmpeg command was not found!")
         }
+        if self.config.fix_vbr && !utils::ffmpeg_available() {
+            anyhow::bail!("Fix VBR specified but ffmpeg command was not found!")
+        }
+        if self.config.fix_aifc && !utils::ffmpeg_available() {
+            anyhow::bail!("Fix AIFC specified but ffmpeg command was not found!")
+        }
+        if self.config.resample.is_some() && !self.config.print_only && !utils::ffmpeg_available() {
+            anyhow::bail!("Resample specified but ffmpeg command was not found!")
+        }
+        if self.config.artwork.is_some() && !self.config.print_only && !utils::ffmpeg_available() {
+            anyhow::bail!("Max art size specified but ffmpeg command was not found!")
+        }
+
+        if !self.config.rules.is_all() {
+            println!(
+                "{}",
+                Message::PartialRules.format(&[&self.config.rules]).yellow().bold()
+            );
+        }
+
+        if let Some(path) = &self.config.replay {
+            self.decisions = DecisionLog::replay(path)?;
+            println!(
+                "Replaying {} decisions from {}",
+                self.decisions.replay_count(),
+                path.display()
+            );
+            if self.decisions.replaying_log() {
+                println!(
+                    "{}",
+                    "Answers are not logged since the decision log is being replayed".yellow()
+                );
+            }
+        }
+
+        if let Some(threads) = self.config.io_threads {
+            let pool = rayon::ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .thread_name(|index| format!("io-{index}"))
+                .build()
+                .context("Failed to create IO thread pool")?;
+            self.io_pool = Some(Arc::new(pool));
+        }
 
         self.gather_files()?;
+        if let Some(limit) = self.config.max_changes {
+            self.check_max_changes(limit)?;
+        }
+        if let Some(count) = self.config.preview {
+            if !self.preview_changes(count) {
+                return Ok(());
+            }
+        }
+        if let Some(threshold) = self.config.batch_threshold {
+            if !self.config.print_only && !self.config.force {
+                self.confirm_similar_changes(threshold);
+            }
+        }
         self.process_tracks()?;
+        self.update_playlist()?;
+        self.update_crates()?;
+        self.write_new_playlist()?;
+        self.save_history()?;
         self.update_state()?;
+        self.verify_state();
 
         Ok(())
     }
@@ -82,7 +256,11 @@ impl TrackRenamer {
     /// Gather audio files recursively from the root path.
     pub fn gather_files(&mut self) -> Result<()> {
         let start_instant = Instant::now();
-        let mut track_list: Vec<Track> = if self.root.is_file() {
+        let mut track_list: Vec<Track> = if let Some(list) = self.config.files_from.clone() {
+            self.get_tracks_from_list(&list)?
+        } else if playlist::is_playlist(&self.root) {
+            self.get_tracks_from_playlist()?
+        } else if self.root.is_file() {
             if let Some(mut track) = Track::try_from_path(&self.root) {
                 track.number = 1;
                 vec![track]
@@ -97,6 +275,39 @@ impl TrackRenamer {
             anyhow::bail!("no supported audio files found");
         }
 
+        let format_hash = self.format.state_hash();
+        for track in &mut track_list {
+            track.metadata.format_hash = format_hash;
+        }
+
+        let aliases = Self::remove_aliases(&mut track_list);
+        if !aliases.is_empty() {
+            println!(
+                "{}",
+                format!("Skipping aliases for files already included ({}):", aliases.len()).yellow()
+            );
+            for (alias, physical) in &aliases {
+                println!("  {} -> {}", alias.display(), physical.display());
+            }
+        }
+
+        if !self.config.no_state
+            && !self.config.force_rules_version
+            && !self.config.upgrade_tags
+            && self.config.rules.is_all()
+            && !self.state.is_empty()
+        {
+            self.skip_unchanged_tracks(&mut track_list);
+        }
+
+        if self.config.quick && !self.config.upgrade_tags {
+            self.skip_clean_filenames(&mut track_list);
+        }
+
+        if let Some(order) = self.config.order {
+            Self::sort_tracks(&mut track_list, order);
+        }
+
         // Assign track numbers for nice print output
         track_list.par_iter_mut().enumerate().for_each(|(number, track)| {
             track.number = number + 1;
@@ -105,6 +316,16 @@ impl TrackRenamer {
         self.tracks_count = track_list.len();
         self.tracks = track_list;
 
+        // Filenames with only one separator reliably give the artist names already in the library
+        let separator = self.format.filename_separator;
+        for track in &self.tracks {
+            if let Some((artist, title)) = separator.split(&track.name) {
+                if separator.split(title).is_none() {
+                    self.known_artists.insert(artist);
+                }
+            }
+        }
+
         if self.config.verbose {
             if self.tracks_count < 100 {
                 let index_width: usize = self.tracks_count.to_string().chars().count();
@@ -121,8 +342,200 @@ impl TrackRenamer {
         Ok(())
     }
 
+    /// Check if the track has changed since it was last processed.
+    fn needs_processing(config: &Config, state: &State, track: &Track) -> bool {
+        config.no_state
+            || config.force_rules_version
+            || config.upgrade_tags
+            || !config.rules.is_all()
+            || !state.is_unchanged(&track.path, &track.metadata)
+    }
+
+    /// Remove tracks that have not changed since they were last processed.
+    ///
+    /// The decision only uses the size and modified time from gathering files,
+    /// so unchanged files are never opened.
+    /// Tracks in the wrong format for their directory are kept so they are reported on every run.
+    /// Skipped tracks are still included in the duplicate check.
+    fn skip_unchanged_tracks(&mut self, track_list: &mut Vec<Track>) {
+        if self.state.has_orphans() {
+            self.relink_moved_tracks(track_list);
+        }
+        let (unchanged, changed): (Vec<Track>, Vec<Track>) =
+            std::mem::take(track_list).into_par_iter().partition(|track| {
+                self.state.is_unchanged(&track.path, &track.metadata)
+                    && file_format::required_format(&self.config.format_rules, &track.path)
+                        .is_none_or(|format| *format == track.format)
+            });
+        *track_list = changed;
+        if self.config.verbose || self.config.debug {
+            println!("{}", Message::SkippingUnchanged.format(&[&unchanged.len()]));
+        }
+        self.stats.unchanged += unchanged.len();
+        for track in unchanged {
+            self.processed_files.entry(track.name.clone()).or_default().push(track);
+        }
+    }
+
+    /// Find files renamed outside track-rename by their track ID and move their state entries to the new path.
+    ///
+    /// Only files without a state entry are opened, and only when some state entries point to missing files.
+    /// The renames are added to the rename history so playlists and crates can be updated.
+    fn relink_moved_tracks(&mut self, track_list: &[Track]) {
+        let moved: Vec<(PathBuf, &Track, String)> = track_list
+            .par_iter()
+            .filter(|track| self.state.get(&track.path).is_none())
+            .filter_map(|track| {
+                let id = track_id::read_from_path(&track.path)?;
+                let old_path = self.state.relink(&track.path, &id)?;
+                Some((old_path, track, id))
+            })
+            .collect();
+        for (old_path, track, id) in moved {
+            if self.config.verbose || self.config.debug {
+                println!(
+                    "{}",
+                    format!(
+                        "Renamed outside track-rename: {} -> {}",
+                        utils::path_to_string_relative(&old_path),
+                        utils::path_to_string_relative(&track.path)
+                    )
+                    .yellow()
+                );
+            }
+            self.renames.add(old_path, track.path.clone(), &id);
+            self.stats.relinked += 1;
+        }
+    }
+
+    /// Remove tracks whose filename is already formatted, without reading any tags.
+    ///
+    /// Tracks whose filename can not be parsed as `artist - title` are kept.
+    /// Skipped tracks are still included in the duplicate check but not saved to the state,
+    /// since their tags were not checked.
+    fn skip_clean_filenames(&mut self, track_list: &mut Vec<Track>) {
+        let total = track_list.len();
+        let (clean, affected): (Vec<_>, Vec<_>) = std::mem::take(track_list)
+            .into_par_iter()
+            .map(|track| {
+                let formatted = track.formatted_filename_from_name(&self.format);
+                (track, formatted)
+            })
+            .partition(|(track, formatted)| formatted.as_ref().is_some_and(|name| *name == track.name));
+
+        println!("{}", Message::QuickCheck.format(&[&affected.len(), &total]).bold());
+        for (track, formatted) in &affected {
+            match formatted {
+                Some(name) => {
+                    let (old, new) = utils::color_diff(&track.name, name, false);
+                    println!("  {old} -> {new}");
+                }
+                None => println!("  {}", utils::path_to_string_relative(&track.path).yellow()),
+            }
+        }
+        println!();
+
+        *track_list = affected.into_iter().map(|(track, _)| track).collect();
+        for (track, _) in clean {
+            self.processed_files.entry(track.name.clone()).or_default().push(track);
+        }
+    }
+
+    /// Read playlist and return the audio tracks it contains in playlist order.
+    fn get_tracks_from_playlist(&mut self) -> Result<Vec<Track>> {
+        let playlist = Playlist::read(&self.root)?;
+        if self.config.verbose || self.config.debug {
+            println!(
+                "Getting audio files from {} playlist: {}",
+                playlist.format.to_string().to_uppercase(),
+                format!("{}", self.root.display()).cyan()
+            );
+        }
+
+        let mut seen = HashSet::new();
+        let track_list = playlist
+            .tracks
+            .iter()
+            .filter(|path| seen.insert(path.as_path()))
+            .filter_map(|path| {
+                if path.is_file() {
+                    Track::try_from_path(path)
+                } else {
+                    eprintln!("{}", format!("Playlist track not found: {}", path.display()).yellow());
+                    None
+                }
+            })
+            .collect();
+
+        self.playlist = Some(playlist);
+        Ok(track_list)
+    }
+
+    /// Get tracks from a list of file paths in a file or stdin.
+    fn get_tracks_from_list(&self, list: &Path) -> Result<Vec<Track>> {
+        let contents = if list == Path::new("-") {
+            io::read_to_string(io::stdin()).context("Failed to read file list from stdin")?
+        } else {
+            fs::read_to_string(list).with_context(|| format!("Failed to read file list: {}", list.display()))?
+        };
+        let current_dir = std::env::current_dir().context("Failed to get current working directory")?;
+        let paths = utils::parse_file_list(&contents, &current_dir);
+        if self.config.verbose || self.config.debug {
+            println!("Getting {} audio files from list", paths.len());
+        }
+
+        let mut seen = HashSet::new();
+        let track_list = paths
+            .iter()
+            .filter(|path| seen.insert(path.as_path()))
+            .filter_map(|path| {
+                if path.is_file() {
+                    Track::try_from_path(path)
+                } else {
+                    eprintln!("{}", format!("Listed file not found: {}", path.display()).yellow());
+                    None
+                }
+            })
+            .collect();
+        Ok(track_list)
+    }
+
+    /// Remove tracks that point to the same physical file as an earlier track,
+    /// for example through a symlink, so the same file is not processed and renamed twice.
+    ///
+    /// The track with the resolved path is kept when there is one.
+    /// Returns the removed alias paths together with the resolved path.
+    fn remove_aliases(track_list: &mut Vec<Track>) -> Vec<(PathBuf, PathBuf)> {
+        let physical_paths: Vec<PathBuf> = track_list
+            .par_iter()
+            .map(|track| dunce::canonicalize(&track.path).unwrap_or_else(|_| track.path.clone()))
+            .collect();
+        let mut kept: HashMap<&Path, usize> = HashMap::with_capacity(physical_paths.len());
+        for (index, physical) in physical_paths.iter().enumerate() {
+            let kept_index = kept.entry(physical.as_path()).or_insert(index);
+            if track_list[index].path == *physical {
+                *kept_index = index;
+            }
+        }
+        if kept.len() == track_list.len() {
+            return Vec::new();
+        }
+
+        let keep: HashSet<usize> = kept.into_values().collect();
+        let aliases = (0..track_list.len())
+            .filter(|index| !keep.contains(index))
+            .map(|index| (track_list[index].path.clone(), physical_paths[index].clone()))
+            .collect();
+        let mut index = 0;
+        track_list.retain(|_| {
+            index += 1;
+            keep.contains(&(index - 1))
+        });
+        aliases
+    }
+
     /// Find and return a list of audio tracks from the root directory.
-    fn get_tracks_from_root_directory(&self) -> Vec<Track> {
+    fn get_tracks_from_root_directory(&mut self) -> Vec<Track> {
         if self.config.verbose || self.config.debug {
             println!(
                 "Getting audio files from: {}",
@@ -130,32 +543,315 @@ impl TrackRenamer {
             );
         }
 
-        let mut track_list = utils::collect_tracks(&self.root);
+        let GatheredFiles {
+            tracks: mut track_list,
+            mut skipped,
+            ignored,
+            os_metadata,
+        } = self.io_pool.as_ref().map_or_else(
+            || utils::gather_files(&self.root),
+            |pool| pool.install(|| utils::gather_files(&self.root)),
+        );
+        skipped.sort_unstable_by(|a, b| a.path.cmp(&b.path));
+        self.skipped_paths = skipped;
+        Self::print_ignored_files(&ignored, os_metadata);
+
+        // Sort by full path so directories are in sorted order
+        track_list.par_sort_unstable_by(|a, b| a.path.cmp(&b.path));
+
+        track_list
+    }
+
+    /// Print the number of files that are not supported audio files for each extension.
+    fn print_ignored_files(ignored: &BTreeMap<String, usize>, os_metadata: usize) {
+        let total = ignored.values().sum::<usize>() + os_metadata;
+        if total == 0 {
+            return;
+        }
+        println!("{}", format!("Ignored {total} files:").bold());
+        let counts: Vec<(String, usize)> = ignored
+            .iter()
+            .map(|(extension, count)| {
+                let label = if extension.is_empty() {
+                    "no extension".to_string()
+                } else if OTHER_FILE_EXTENSIONS.contains(&extension.as_str()) {
+                    format!(".{extension} (needs conversion)").yellow().to_string()
+                } else {
+                    format!(".{extension}")
+                };
+                (label, *count)
+            })
+            .chain((os_metadata > 0).then(|| ("OS metadata".to_string(), os_metadata)))
+            .sorted_unstable_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)))
+            .collect();
+        let width = counts.first().map_or(0, |(_, count)| count.to_string().len());
+        for (label, count) in counts {
+            println!("  {count:>width$} {label}");
+        }
+    }
 
-        if self.config.sort_files {
+    /// Sort tracks in the processing order.
+    fn sort_tracks(track_list: &mut [Track], order: TrackOrder) {
+        match order {
+            TrackOrder::Newest => {
+                track_list.par_sort_by(|a, b| b.metadata.modified.cmp(&a.metadata.modified));
+            }
+            TrackOrder::Oldest => track_list.par_sort_by_key(|track| track.metadata.modified),
             // Sort by filename, ignoring parent dir
-            track_list.par_sort_unstable();
+            TrackOrder::Name => track_list.par_sort_unstable(),
+            TrackOrder::Path => track_list.par_sort_unstable_by(|a, b| a.path.cmp(&b.path)),
+            TrackOrder::Random => track_list.shuffle(&mut rand::rng()),
+        }
+    }
+
+    /// Print the most common word changes the formatting would make, without modifying files.
+    ///
+    /// Returns false if the user chose not to continue.
+    fn preview_changes(&self, count: usize) -> bool {
+        let track_changes: Vec<Vec<(String, String)>> = self
+            .formatted_pending_tracks()
+            .into_iter()
+            .filter_map(|track| {
+                let tags = &track.tags;
+                let mut changes: Vec<(String, String)> = [
+                    (&tags.current_artist, &tags.formatted_artist),
+                    (&tags.current_title, &tags.formatted_title),
+                    (&tags.current_album, &tags.formatted_album),
+                    (&tags.current_genre, &tags.formatted_genre),
+                ]
+                .into_iter()
+                .flat_map(|(current, formatted)| utils::word_changes(current, formatted))
+                .collect();
+                // Count each change once per track
+                changes.sort_unstable();
+                changes.dedup();
+                (!changes.is_empty()).then_some(changes)
+            })
+            .collect();
+
+        if track_changes.is_empty() {
+            println!("{}", "No pending tag changes".green());
+            return true;
+        }
+        let mut counts: HashMap<(String, String), usize> = HashMap::new();
+        for change in track_changes.iter().flatten() {
+            *counts.entry(change.clone()).or_insert(0) += 1;
+        }
+        let counts: Vec<_> = counts
+            .into_iter()
+            .sorted_unstable_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)))
+            .collect();
+        println!(
+            "{}",
+            format!(
+                "Most common changes in {} tracks ({} of {} shown):",
+                track_changes.len(),
+                count.min(counts.len()),
+                counts.len()
+            )
+            .bold()
+        );
+        let width = counts.first().map_or(0, |(_, tracks)| tracks.to_string().len());
+        for ((removed, added), tracks) in counts.iter().take(count) {
+            println!(
+                "  {tracks:>width$}  {} -> {}",
+                format!("\"{removed}\"").red(),
+                format!("\"{added}\"").green()
+            );
+        }
+        println!();
+        self.config.print_only || self.config.force || prompt::confirm()
+    }
+
+    /// Read and format the tags of the tracks that will be processed, without modifying files.
+    fn formatted_pending_tracks(&self) -> Vec<Track> {
+        self.tracks
+            .par_iter()
+            .filter(|track| self.is_pending(track))
+            .filter_map(|track| {
+                let file_tags = self.read_pending_tags(track)?;
+                let mut track = track.clone();
+                self.format_pending_track(&mut track, &file_tags);
+                Some(track)
+            })
+            .collect()
+    }
+
+    /// Tracks that will be read when processing.
+    fn is_pending(&self, track: &Track) -> bool {
+        Self::needs_processing(&self.config, &self.state, track)
+            && (self.config.placeholders == Placeholders::Hydrate || !utils::is_cloud_placeholder(&track.path))
+    }
+
+    /// Read the tags with the same reader that processing uses.
+    fn read_pending_tags(&self, track: &Track) -> Option<Tag> {
+        if self.config.text_tags_only() {
+            utils::read_text_tags(track, false)
         } else {
-            // Sort by full path so directories are in sorted order
-            track_list.par_sort_unstable_by(|a, b| a.path.cmp(&b.path));
+            utils::read_tags(track, false)
         }
+    }
 
-        track_list
+    /// Format the tags like processing does, using the first choice where processing would ask.
+    fn format_pending_track(&self, track: &mut Track, file_tags: &Tag) {
+        track.format_tags(file_tags, &self.format);
+        if let Some(chosen) = self.various_artists_candidates(track).first() {
+            track.format_artist_and_title(&chosen.artist, &chosen.title, &self.format);
+        }
+        if self.config.artist_conflict == ArtistConflict::Filename {
+            if let Some(filename_artist) = track.filename_artist_conflict(file_tags, &self.format) {
+                let title = track.tags.current_title.clone();
+                track.format_artist_and_title(&filename_artist, &title, &self.format);
+            }
+        }
+        if self.config.canonical_name {
+            track.tags.set_canonical_name();
+        }
+    }
+
+    /// Check if processing the track would modify the file.
+    ///
+    /// Uses the same decisions as `process_track`, without asking or modifying anything.
+    fn has_pending_changes(&self, track: &Track) -> bool {
+        let config = &self.config;
+        if config.convert_failed
+            && file_format::required_format(&config.format_rules, &track.path)
+                .is_some_and(|format| *format != track.format)
+        {
+            return true;
+        }
+        if !self.is_pending(track) {
+            return false;
+        }
+        let Some(file_tags) = self.read_pending_tags(track) else {
+            return (config.repair_failed || config.convert_failed) && track.format == FileFormat::Mp3;
+        };
+        if config.fix_vbr
+            && config.check_vbr
+            && track.format == FileFormat::Mp3
+            && Mp3Info::from_path(&track.path)
+                .ok()
+                .flatten()
+                .is_some_and(|info| info.duration_mismatch().is_some())
+        {
+            return true;
+        }
+        if config.fix_aifc
+            && config.check_aifc
+            && track.format == FileFormat::Aif
+            && AiffInfo::from_path(&track.path).is_ok_and(|info| info.compression.is_some())
+        {
+            return true;
+        }
+        if config.upgrade_tags {
+            return file_tags.version() != id3::Version::Id3v24
+                || (track.format == FileFormat::Mp3 && utils::read_id3v1_only(&track.path).is_some());
+        }
+        if config.write_all_tags
+            || (config.write_id && track_id::read(&file_tags).unwrap_or_default().is_empty())
+            || (config.strip_ape && track.format == FileFormat::Mp3 && ape::has_tag(&track.path).unwrap_or(false))
+            || file_tags
+                .frames()
+                .any(|frame| config.strip_frames.iter().any(|strip| strip == frame.id()))
+            || config
+                .artwork
+                .is_some_and(|options| artwork::has_oversized_pictures(&file_tags, options.max_size))
+            || config.resample.is_some_and(|target| {
+                track
+                    .audio_properties()
+                    .is_ok_and(|properties| properties.exceeds(&target))
+            })
+        {
+            return true;
+        }
+        let mut track = track.clone();
+        self.format_pending_track(&mut track, &file_tags);
+        // The file is modified by writing the tags whether or not it is renamed
+        let tags_written =
+            track.tags.changed() && self.minor_change_action(&track.tags.changes()) != Some(MinorChanges::Skip);
+        tags_written
+            || (!config.tags_only
+                && track.filename() != track.formatted_filename_with_extension(&self.format)
+                && self.minor_change_action(&[track.filename_change(&self.format)]) != Some(MinorChanges::Skip))
+    }
+
+    /// Files are renamed when renaming was requested or the tags do not change.
+    fn pending_rename(&self, track: &Track) -> bool {
+        !self.config.tags_only
+            && (self.config.rename_files || !track.tags.changed())
+            && track.filename() != track.formatted_filename_with_extension(&self.format)
+    }
+
+    /// Abort before modifying any files if more than `limit` tracks would be modified.
+    fn check_max_changes(&self, limit: usize) -> Result<()> {
+        let changes = self
+            .tracks
+            .par_iter()
+            .filter(|track| self.has_pending_changes(track))
+            .count();
+        if self.config.verbose {
+            println!("Pending changes: {changes} (max {limit})");
+        }
+        if changes > limit {
+            anyhow::bail!("Pending changes ({changes}) exceed --max-changes {limit}, no files were modified");
+        }
+        Ok(())
+    }
+
+    /// Ask once for tag and rename changes that are the same for at least `threshold` tracks.
+    ///
+    /// Accepted patterns are applied without asking when the tracks are processed,
+    /// and declined patterns are asked separately for each track as usual.
+    fn confirm_similar_changes(&mut self, threshold: usize) {
+        let mut counts: HashMap<(Action, ChangePattern), usize> = HashMap::new();
+        for track in self.formatted_pending_tracks() {
+            let tag_pattern = ChangePattern::from_changes(&track.tags.changes());
+            let rename_pattern = if self.pending_rename(&track) {
+                ChangePattern::from_changes(&[track.filename_change(&self.format)])
+            } else {
+                ChangePattern::default()
+            };
+            for (action, pattern) in [(Action::Tags, tag_pattern), (Action::Rename, rename_pattern)] {
+                if !pattern.is_empty() {
+                    *counts.entry((action, pattern)).or_insert(0) += 1;
+                }
+            }
+        }
+        let groups = counts
+            .into_iter()
+            .filter(|(_, count)| *count >= threshold)
+            .sorted_unstable_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0 .1.cmp(&b.0 .1)));
+        for ((action, pattern), count) in groups {
+            println!("{}", Message::ApplySimilar.format(&[&count]).bold());
+            println!("{pattern}");
+            if prompt::confirm() {
+                self.decisions.approve_pattern(action, pattern);
+            }
+            println!();
+        }
     }
 
     // Format tags and rename files if needed.
     pub fn process_tracks(&mut self) -> Result<()> {
+        events::emit(&Event::RunStarted {
+            total: self.tracks_count,
+        });
         if self.tracks_count == 0 {
-            println!("{}", "No tracks to process".green());
+            println!("{}", Message::NoTracks.text().green());
             return Ok(());
         } else if self.tracks_count == 1 {
-            println!("{}", "Processing 1 track...".bold());
+            println!("{}", Message::ProcessingTrack.text().bold());
         } else {
-            println!("{}", format!("Processing {} tracks...", self.tracks_count).bold());
+            println!("{}", Message::ProcessingTracks.format(&[&self.tracks_count]).bold());
         }
 
         let dryrun_header = if self.config.print_only {
-            println!("{}", "Running in print-only mode".yellow().bold());
+            let mode = if self.config.read_only {
+                Message::ReadOnlyMode
+            } else {
+                Message::PrintOnlyMode
+            };
+            println!("{}", mode.text().yellow().bold());
             " (dryrun)"
         } else {
             ""
@@ -164,295 +860,1345 @@ impl TrackRenamer {
         let rename_file_header = format!("Rename file{dryrun_header}:").cyan().bold();
         let max_index_width: usize = self.tracks_count.to_string().chars().count();
 
-        self.current_path = self.root.clone();
+        // Directory headers are only useful when iterating in directory order
+        self.output = OrderedOutput::stdout(self.config.directory_order()).starting_in(&self.root);
+
+        // Tags are read ahead concurrently but all changes are still applied one track at a time
+        let prefetcher = self.io_pool.as_ref().map(|pool| {
+            let paths = self
+                .tracks
+                .iter()
+                .filter(|track| Self::needs_processing(&self.config, &self.state, track))
+                .filter(|track| {
+                    self.config.placeholders == Placeholders::Hydrate || !utils::is_cloud_placeholder(&track.path)
+                })
+                .map(|track| track.path.clone())
+                .collect();
+            TagPrefetcher::start(paths, Arc::clone(pool), self.config.text_tags_only())
+        });
 
         let start_instant = Instant::now();
-        for track in &mut self.tracks {
-            if !self.config.sort_files {
-                // Print current directory when iterating in directory order
-                if self.current_path != track.root {
-                    self.current_path.clone_from(&track.root);
-                    let path = utils::path_to_string_relative(&self.current_path);
-                    if !path.is_empty() {
-                        println!("\n{}", path.magenta());
-                    }
-                }
+        self.retries_left = self.config.retries;
+        let mut tracks = std::mem::take(&mut self.tracks);
+        let mut result = Ok(());
+        for track in &mut tracks {
+            if self.quit {
+                // Remaining tracks were not handled so they should not be saved to state
+                track.not_processed = true;
+                continue;
             }
-
-            // If this is a DJ MUSIC subdirectory, check genre mappings
-            if !self.checked_genre_mappings.contains(track.directory.as_str())
-                && utils::contains_subpath(&track.root, DJ_MUSIC_PATH.as_path())
-            {
-                if !GENRE_MAPPINGS.contains_key(track.directory.as_str()) {
-                    eprintln!(
-                        "\n{}",
-                        format!("WARNING: DJ music folder missing genre mapping: {}", track.directory).yellow()
-                    );
-                } else if GENRE_MAPPINGS.get(track.directory.as_str()).unwrap_or(&"").is_empty() {
-                    eprintln!(
-                        "\n{}",
-                        format!("WARNING: Empty genre mapping for: {}", track.directory).yellow()
-                    );
-                }
-                self.checked_genre_mappings.insert(track.directory.clone());
+            if self.config.directory_order() {
+                self.print_finished_directory(Some(&track.root));
             }
-
-            Self::print_running_index(self.tracks_count, track.number, max_index_width);
-
-            // Skip filenames in user configs exclude list
-            if self
-                .config
-                .excluded_tracks
-                .iter()
-                .any(|excluded_file| excluded_file == track)
-            {
-                if self.config.verbose {
-                    track.show(self.tracks_count, max_index_width);
-                    let message = format!("Skipping track in exclude list: {track}");
-                    println!("{}", message.yellow());
-                    utils::print_divider(&message);
-                }
-                continue;
+            let before = self.stats.track_snapshot();
+            result = self.process_track_guarded(
+                track,
+                prefetcher.as_ref(),
+                &fix_tags_header,
+                &rename_file_header,
+                max_index_width,
+            );
+            if result.is_err() {
+                break;
             }
-
-            // File might have been deleted between gathering files and now,
-            // for example when handling duplicates.
-            if !track.path.exists() {
-                track.show(self.tracks_count, max_index_width);
-                let message = format!("Track no longer exists: {track}");
-                utils::print_error(&message);
-                utils::print_divider(&message);
-                continue;
+            self.stats.add_directory_track(&track.root, before);
+            self.stats
+                .add_group_track(&self.top_level_folder(track), &track.tags.formatted_genre, before);
+        }
+        if result.is_ok() {
+            if self.config.directory_order() {
+                self.print_finished_directory(None);
             }
+            result = self.retry_failed_tracks(&mut tracks, &fix_tags_header, &rename_file_header, max_index_width);
+        }
+        self.tracks = tracks;
+        result?;
 
-            let needs_processing = self.config.no_state
-                || match self.state.get(&track.path) {
-                    Some(state) => state.modified < track.metadata.modified || state.version != track.metadata.version,
-                    None => true,
-                };
+        if self.config.link_duplicates && !self.config.print_only && !self.quit {
+            self.link_duplicates();
+        }
 
-            if needs_processing {
-                let mut tag_result = utils::read_tags(track, self.config.verbose || self.config.debug);
-                if tag_result.is_none() && self.config.convert_failed && track.format == FileFormat::Mp3 {
-                    println!("Converting MP3 to AIF...");
-                    match track.convert_mp3_to_aif() {
-                        Ok(aif_track) => {
-                            self.stats.converted += 1;
-                            *track = aif_track;
-                            tag_result = utils::read_tags(track, self.config.verbose || self.config.debug);
-                        }
-                        Err(e) => {
-                            eprintln!("{e}");
-                        }
-                    }
-                }
-                let Some(mut file_tags) = tag_result else {
-                    self.stats.failed += 1;
-                    if self.config.log_failures {
-                        self.failed_files.push(utils::path_to_string(&track.path));
-                    }
-                    continue;
-                };
+        println!("\n{}", Message::Finished.text().green());
+        events::emit(&Event::RunFinished {
+            tags_fixed: self.stats.tags_fixed,
+            renamed: self.stats.renamed,
+            failed: self.stats.failed,
+            bytes_saved: self.stats.bytes_saved,
+            bytes_added: self.stats.bytes_added,
+        });
+        if self.config.debug || self.config.profile {
+            let duration = start_instant.elapsed();
+            println!("Time taken: {:.3}s", duration.as_secs_f64());
+        }
+        if self.config.profile {
+            println!("{}", formatting::cache_stats());
+        }
+        Self::print_unconverged_inputs();
+        self.stats.declined = self.decisions.declined();
+        println!("{}", self.stats);
+        if self.decisions.len() > 0 {
+            println!("Logged decisions to: {}", DecisionLog::path()?.display());
+        }
+        if self.config.log_failures && !self.failed_files.is_empty() {
+            utils::write_log_for_failed_files(&self.failed_files)?;
+        }
+        if self.config.verbose {
+            self.print_tag_version_counts();
+            self.print_audio_format_counts();
+        }
+        if self.config.genre_statistics {
+            println!("{}", format!("Genres ({}):", self.genres.len()).cyan().bold());
+            let mut genre_list: Vec<(&String, &usize)> =
+                self.genres.iter().sorted_unstable_by(|a, b| b.1.cmp(a.1)).collect();
 
-                // Store id3 tag version count
-                *self.tag_versions.entry(file_tags.version().to_string()).or_insert(0) += 1;
+            Self::print_top_counts(&genre_list);
+            genre_list.sort_unstable();
+            if !self.config.read_only {
+                Self::write_genre_log(&genre_list)?;
+            }
+        }
+        if self.config.descriptor_statistics {
+            println!("{}", format!("Descriptors ({}):", self.descriptors.len()).cyan().bold());
+            let descriptor_list: Vec<(&String, &usize)> = self
+                .descriptors
+                .iter()
+                .sorted_unstable_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0)))
+                .collect();
 
-                if self.config.debug && self.config.verbose {
-                    utils::print_tag_data(&file_tags);
-                    serato::print_serato_tags(&file_tags);
-                }
+            Self::print_top_counts(&descriptor_list);
+            if !self.config.read_only {
+                Self::write_descriptor_log(&descriptor_list)?;
+            }
+        }
+        if let Some(path) = &self.config.feat_index {
+            println!(
+                "{}",
+                format!("Artists with featured artists: {}", self.feat_index.len())
+                    .cyan()
+                    .bold()
+            );
+            self.feat_index.write(path)?;
+        }
+        if let Some(path) = &self.config.stats_report {
+            self.stats.write_report(path)?;
+        }
+        if let Some(path) = &self.config.record_corpus {
+            let added = corpus::append(path, &self.corpus)?;
+            println!("Added {added} new corpus entries to: {}", path.display());
+        }
+        self.print_all_duplicates();
+        self.print_skipped_paths();
+        self.print_genre_mapping_warnings();
+        self.print_tag_length_warnings();
+        self.print_ape_files();
+        self.print_chapter_files();
+        self.print_placeholder_files();
 
-                track.format_tags(&file_tags);
-                let formatted_name = track.formatted_filename();
-                if formatted_name.is_empty() {
-                    eprintln!(
-                        "\n{}",
-                        format!("Formatted name should never be empty: {}", track.path.display()).red()
-                    );
-                }
-                let tags_changed = track.tags.changed();
-                if tags_changed || self.config.write_all_tags {
-                    if tags_changed {
-                        track.show(self.tracks_count, max_index_width);
-                        self.stats.tags += 1;
-                        println!("{fix_tags_header}");
-                        track.tags.show_diff();
+        Ok(())
+    }
+
+    #[inline]
+    /// Print running index
+    fn print_running_index(total_tracks: usize, number: usize, max_index_width: usize) {
+        print!("\r{number:>max_index_width$}/{total_tracks}");
+        io::stdout().flush().expect("Failed to flush stdout");
+    }
+
+    /// Print the counts for the previous directory when moving on to the next directory,
+    /// or after the last track when there is no next directory.
+    fn print_finished_directory(&self, next: Option<&Path>) {
+        if let Some(counts) = self.stats.directories.last() {
+            if next.is_none_or(|directory| counts.directory != directory) {
+                println!("\n{}", counts.to_string().dimmed());
+            }
+        }
+    }
+
+    /// Count and print the total number of each file extension in the file list.
+    fn print_extension_counts(&self) {
+        println!("{}", "File format counts:".bold());
+        self.tracks
+            .iter()
+            .map(|track| track.format.to_string())
+            .counts()
+            .into_iter()
+            .sorted_unstable_by(|a, b| b.1.cmp(&a.1))
+            .for_each(|(format, count)| println!("{format}: {count}"));
+    }
+
+    /// Write renamed paths back to the input playlist.
+    fn update_playlist(&mut self) -> Result<()> {
+        let Some(playlist) = self.playlist.as_mut() else {
+            return Ok(());
+        };
+        if !self.config.update_playlist || self.renames.is_empty() {
+            return Ok(());
+        }
+        let updated = playlist.update_paths(&self.renames.resolved());
+        if updated == 0 {
+            return Ok(());
+        }
+        println!(
+            "{}",
+            format!(
+                "Update playlist: {updated} renamed tracks in {}",
+                playlist.path.display()
+            )
+            .cyan()
+        );
+        if !self.config.print_only {
+            playlist.save()?;
+        }
+        Ok(())
+    }
+
+    /// Write the tracks added or modified in this run to a new playlist or crate.
+    fn write_new_playlist(&self) -> Result<()> {
+        let Some(path) = &self.config.new_playlist else {
+            return Ok(());
+        };
+        if self.new_tracks.is_empty() {
+            println!("{}", "No new or modified tracks for the playlist".green());
+            return Ok(());
+        }
+        let mut playlist = Playlist::new(path)?;
+        for track_path in &self.new_tracks {
+            playlist.add_track(track_path);
+        }
+        println!(
+            "{}",
+            format!(
+                "Write playlist: {} new or modified tracks to {}",
+                playlist.tracks.len(),
+                path.display()
+            )
+            .cyan()
+        );
+        if !self.config.print_only {
+            playlist.save()?;
+        }
+        Ok(())
+    }
+
+    /// Update renamed track paths in all Serato crates.
+    fn update_crates(&self) -> Result<()> {
+        if !self.config.update_crates || self.renames.is_empty() {
+            return Ok(());
+        }
+        let Some(serato_dir) = &self.config.serato_dir else {
+            anyhow::bail!("Update crates specified but Serato directory was not found!");
+        };
+        let renamed = self.renames.resolved();
+        for crate_path in crates::crate_files(serato_dir)? {
+            let mut serato_crate = match SeratoCrate::read(&crate_path) {
+                Ok(serato_crate) => serato_crate,
+                Err(error) => {
+                    utils::print_error(&format!("{error:#}"));
+                    continue;
+                }
+            };
+            let updated = serato_crate.rename_tracks(&renamed, &crate_path);
+            if updated == 0 {
+                continue;
+            }
+            println!(
+                "{}",
+                format!(
+                    "Update crate: {updated} renamed tracks in {}",
+                    crates::crate_name(&crate_path)
+                )
+                .cyan()
+            );
+            if !self.config.print_only {
+                serato_crate.write(&crate_path)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Append a rename to the journal if one is used.
+    ///
+    /// Renames that are only printed are marked as dry runs.
+    fn journal_rename(&mut self, old: &Path, new: &Path) {
+        if let Some(journal) = &mut self.rename_journal {
+            if let Err(error) = journal.record(old, new, self.config.print_only) {
+                utils::print_error(&error.to_string());
+            }
+        }
+    }
+
+    /// Store renamed paths so file references can be fixed later with the relocate command,
+    /// and the written tag values for the history command.
+    fn save_history(&self) -> Result<()> {
+        if self.config.print_only || self.config.test_mode || self.config.copy_to.is_some() {
+            return Ok(());
+        }
+        if !self.renames.is_empty() {
+            let mut history = RenameHistory::load();
+            history.extend(&self.renames);
+            history.save()?;
+        }
+        if !self.tag_history.is_empty() {
+            let mut history = TagHistory::load();
+            history.extend(&self.tag_history);
+            history.save()?;
+        }
+        Ok(())
+    }
+
+    /// Insert processed tracks and save state.
+    fn update_state(&self) -> Result<()> {
+        // Files formatted with only some of the rules still need a normal run
+        if self.config.read_only || !self.config.rules.is_all() {
+            return Ok(());
+        }
+        let (added_count, updated_count) = self
+            .tracks
+            .par_iter()
+            .filter(|track| !track.not_processed)
+            .map(|track| {
+                if self.state.insert(track.path.clone(), track.metadata.clone()).is_some() {
+                    (0, 1)
+                } else {
+                    (1, 0)
+                }
+            })
+            .reduce(|| (0, 0), |acc, item| (acc.0 + item.0, acc.1 + item.1));
+
+        if self.config.debug || self.config.verbose {
+            println!(
+                "State updated: {} new tracks added, {} existing tracks updated. Total: {}",
+                added_count,
+                updated_count,
+                self.state.len()
+            );
+        }
+
+        Ok(self.state.save()?)
+    }
+
+    /// Check a sample of the state entries from this run against the files and report mismatches.
+    fn verify_state(&self) {
+        let Some(count) = self.config.verify_state else {
+            return;
+        };
+        let check = self.state.verify_sample(count);
+        if check.checked == 0 {
+            return;
+        }
+        if check.anomalies.is_empty() {
+            println!(
+                "{}",
+                format!("State check: {} entries match the files", check.checked).green()
+            );
+            return;
+        }
+        println!(
+            "{}",
+            format!(
+                "State check: {} of {} entries do not match the files:",
+                check.anomalies.len(),
+                check.checked
+            )
+            .yellow()
+            .bold()
+        );
+        for anomaly in &check.anomalies {
+            println!("  {anomaly}");
+        }
+    }
+
+    /// Process a single track and count a panic as a failed track.
+    ///
+    /// Contains panics from a single broken file so the rest of the library is still processed.
+    fn process_track_guarded(
+        &mut self,
+        track: &mut Track,
+        prefetcher: Option<&TagPrefetcher>,
+        fix_tags_header: &ColoredString,
+        rename_file_header: &ColoredString,
+        max_index_width: usize,
+    ) -> Result<()> {
+        // Processing switches to the mirrored copy, but the state and retries use the source file
+        let source = self.config.copy_to.is_some().then(|| track.clone());
+        let before = self.stats.track_snapshot();
+        let is_new = self.config.new_playlist.is_some() && self.state.get(&track.path).is_none();
+        let accepted = self.decisions.accepted();
+        let outcome = panic::catch_unwind(AssertUnwindSafe(|| {
+            self.process_track(track, prefetcher, fix_tags_header, rename_file_header, max_index_width)
+        }));
+        if self.config.new_playlist.is_some()
+            && !track.not_processed
+            && matches!(outcome, Ok(Ok(())))
+            && (self.stats.modified_since(before) || (is_new && !self.stats.failed_since(before)))
+        {
+            self.new_tracks.push(track.path.clone());
+        }
+        if matches!(outcome, Ok(Ok(()))) && !track.not_processed && self.stats.unchanged_since(before) {
+            self.stats.unchanged += 1;
+        }
+        if self.config.reveal.is_some()
+            && !self.config.test_mode
+            && matches!(outcome, Ok(Ok(())))
+            && self.decisions.accepted() > accepted
+            && self.stats.modified_since(before)
+        {
+            self.reveal(&track.path);
+        }
+        if let Some(source) = source {
+            self.restore_source(track, source);
+        }
+        match outcome {
+            Ok(result) => result,
+            Err(payload) => {
+                if self.config.test_mode {
+                    panic::resume_unwind(payload);
+                }
+                self.record_panic(track, payload.as_ref());
+                Ok(())
+            }
+        }
+    }
+
+    /// Open the folder of a track with accepted changes or add it to the review playlist.
+    fn reveal(&mut self, path: &Path) {
+        let result = match self.review_playlist.as_mut() {
+            Some(playlist) if playlist.tracks.iter().any(|track_path| track_path == path) => Ok(()),
+            Some(playlist) => {
+                playlist.add_track(path);
+                // Saved right away so the track can be auditioned while the run continues
+                playlist.save()
+            }
+            None => utils::reveal_in_file_manager(path),
+        };
+        if let Err(error) = result {
+            utils::print_error(&error.to_string());
+        }
+    }
+
+    /// First directory of the track under the input directory, or `.` for tracks directly in it.
+    ///
+    /// Tracks outside the input directory, for example from a playlist, use their own directory name.
+    fn top_level_folder(&self, track: &Track) -> String {
+        track.root.strip_prefix(&self.root).map_or_else(
+            |_| track.directory.clone(),
+            |relative| {
+                relative.components().next().map_or_else(
+                    || ".".to_string(),
+                    |folder| folder.as_os_str().to_string_lossy().to_string(),
+                )
+            },
+        )
+    }
+
+    /// Copy the source file to the same relative path in the mirror directory and process the copy instead.
+    fn copy_to_mirror(&self, track: &mut Track, dir: &Path) -> Result<()> {
+        let relative = track
+            .path
+            .strip_prefix(&self.root)
+            .ok()
+            .filter(|relative| !relative.as_os_str().is_empty())
+            .map_or_else(|| PathBuf::from(track.filename()), Path::to_path_buf);
+        let copy_path = dir.join(relative);
+        let copy_root = copy_path.parent().unwrap_or(dir).to_path_buf();
+        fs::create_dir_all(&copy_root)
+            .with_context(|| format!("Failed to create directory: {}", copy_root.display()))?;
+        // Write a new file instead of `fs::copy` so a read-only source does not give a read-only copy
+        let mut source = File::open(&track.path).with_context(|| format!("Failed to open: {track}"))?;
+        let mut copy =
+            File::create(&copy_path).with_context(|| format!("Failed to create: {}", copy_path.display()))?;
+        io::copy(&mut source, &mut copy).with_context(|| format!("Failed to copy to: {}", copy_path.display()))?;
+        track.path = copy_path;
+        track.root = copy_root;
+        Ok(())
+    }
+
+    /// Point a track processed in copy mode back to the source file.
+    fn restore_source(&mut self, track: &mut Track, source: Track) {
+        let copy_path = std::mem::replace(&mut track.path, source.path);
+        track.root = source.root;
+        track.name = source.name;
+        track.extension = source.extension;
+        track.format = source.format;
+        track.metadata = source.metadata;
+        for path in &mut self.retry_paths {
+            if *path == copy_path {
+                path.clone_from(&track.path);
+            }
+        }
+    }
+
+    /// Process tracks that failed with a transient error again after a delay.
+    fn retry_failed_tracks(
+        &mut self,
+        tracks: &mut [Track],
+        fix_tags_header: &ColoredString,
+        rename_file_header: &ColoredString,
+        max_index_width: usize,
+    ) -> Result<()> {
+        while !self.retry_paths.is_empty() && !self.quit {
+            let paths: HashSet<PathBuf> = std::mem::take(&mut self.retry_paths).into_iter().collect();
+            let attempt = self.config.retries - self.retries_left + 1;
+            self.retries_left -= 1;
+            if attempt == 1 {
+                self.stats.retried = paths.len();
+            }
+            println!(
+                "\n{}",
+                Message::RetryingFailed
+                    .format(&[&paths.len(), &attempt, &self.config.retries])
+                    .yellow()
+                    .bold()
+            );
+            thread::sleep(Duration::from_secs_f64(self.config.retry_delay));
+            for track in tracks.iter_mut().filter(|track| paths.contains(&track.path)) {
+                if self.quit {
+                    break;
+                }
+                track.not_processed = false;
+                track.reset_printed();
+                let failed = self.stats.failed;
+                let deferred = self.retry_paths.len();
+                self.process_track_guarded(track, None, fix_tags_header, rename_file_header, max_index_width)?;
+                if self.stats.failed == failed && self.retry_paths.len() == deferred {
+                    self.stats.retry_recovered += 1;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Format tags and rename a single track.
+    fn process_track(
+        &mut self,
+        track: &mut Track,
+        prefetcher: Option<&TagPrefetcher>,
+        fix_tags_header: &ColoredString,
+        rename_file_header: &ColoredString,
+        max_index_width: usize,
+    ) -> Result<()> {
+        // Tracks are processed in order so this writes the directory header right away.
+        // The output is not needed for processing, so a write error does not stop the run.
+        if let Err(error) = self.output.complete(track.number, TrackOutput::new(&track.root, "")) {
+            utils::print_error(&format!("Failed to write output: {error}"));
+        }
+
+        // If this is a DJ MUSIC subdirectory, check genre mappings
+        if !self.config.no_genre_warnings
+            && !self.checked_genre_mappings.contains(track.directory.as_str())
+            && utils::contains_subpath(&track.root, DJ_MUSIC_PATH.as_path())
+        {
+            if !GENRE_MAPPINGS.contains_key(track.directory.as_str()) {
+                self.genre_mapping_warnings
+                    .push(Message::MissingGenreMapping.format(&[&track.directory]));
+            } else if GENRE_MAPPINGS.get(track.directory.as_str()).unwrap_or(&"").is_empty() {
+                self.genre_mapping_warnings
+                    .push(Message::EmptyGenreMapping.format(&[&track.directory]));
+            }
+            self.checked_genre_mappings.insert(track.directory.clone());
+        }
+
+        Self::print_running_index(self.tracks_count, track.number, max_index_width);
+        if track.metadata.id.is_empty() {
+            if let Some(metadata) = self.state.get(&track.path) {
+                track.metadata.id = metadata.id;
+            }
+        }
+        events::emit(&Event::TrackStarted {
+            index: track.number,
+            total: self.tracks_count,
+            path: &track.path,
+            id: &track.report_id(&self.root),
+        });
+
+        // Skip filenames in user configs exclude list
+        if self
+            .config
+            .excluded_tracks
+            .iter()
+            .any(|excluded_file| excluded_file == track)
+        {
+            if self.config.verbose {
+                track.show(self.tracks_count, max_index_width);
+                let message = format!("Skipping track in exclude list: {track}");
+                println!("{}", message.yellow());
+                utils::print_divider(&message);
+            }
+            return Ok(());
+        }
+
+        // File might have been deleted between gathering files and now,
+        // for example when handling duplicates.
+        if !track.path.exists() {
+            track.show(self.tracks_count, max_index_width);
+            let message = format!("Track no longer exists: {track}");
+            utils::print_error(&message);
+            utils::print_divider(&message);
+            return Ok(());
+        }
+
+        // Reading an online-only file downloads it first, which is slow for a whole cloud library
+        if utils::is_cloud_placeholder(&track.path) {
+            if self.config.placeholders == Placeholders::Skip {
+                self.stats.placeholders_skipped += 1;
+                self.placeholder_files.push(track.path.clone());
+                track.not_processed = true;
+                if self.config.verbose {
+                    track.show(self.tracks_count, max_index_width);
+                    let message = format!("Skipping online-only file: {track}");
+                    println!("{}", message.yellow());
+                    utils::print_divider(&message);
+                }
+                return Ok(());
+            }
+            if self.config.verbose {
+                println!("\n{}", format!("Downloading online-only file: {track}").dimmed());
+            }
+        }
+
+        if !self.config.format_rules.is_empty() {
+            self.check_format_rule(track, max_index_width);
+        }
+
+        let needs_processing = Self::needs_processing(&self.config, &self.state, track);
+
+        if needs_processing {
+            let verbose = self.config.verbose || self.config.debug;
+            let source_path = track.path.clone();
+            if let Some(dir) = self.config.copy_to.clone().filter(|_| !self.config.print_only) {
+                if let Err(error) = self.copy_to_mirror(track, &dir) {
+                    track.show(self.tracks_count, max_index_width);
+                    utils::print_error(&format!("{error:#}"));
+                    self.stats.failed += 1;
+                    track.not_processed = true;
+                    return Ok(());
+                }
+            }
+            let result = if let Some(result) = prefetcher.and_then(|tags| tags.take(&source_path)) {
+                result
+            } else if self.config.text_tags_only() {
+                utils::read_text_tags_from_path(&track.path)
+            } else {
+                Tag::read_from_path(&track.path)
+            };
+            let read_failure = result
+                .as_ref()
+                .err()
+                .map(|error| FailedFile::from_id3_error(utils::path_to_string(&track.path), error));
+            let mut tag_result = match result {
+                Err(error) if self.config.tolerant && !matches!(error.kind, id3::ErrorKind::NoTag) => {
+                    Self::read_tolerant(track, error, &mut self.stats, verbose)
+                }
+                result => utils::tags_from_result(track, result, verbose),
+            };
+            if tag_result.is_none() && self.config.repair_failed && track.format == FileFormat::Mp3 {
+                tag_result = Self::repair_track(track, &self.config, &mut self.stats);
+            }
+            if tag_result.is_none() && self.config.convert_failed && track.format == FileFormat::Mp3 {
+                println!("Converting MP3 to AIF...");
+                match track.convert_mp3_to_aif() {
+                    Ok(aif_track) => {
+                        self.stats.converted += 1;
+                        self.stats
+                            .record_size_change(track.metadata.size, aif_track.metadata.size);
+                        self.journal_rename(&track.path, &aif_track.path);
+                        self.renames
+                            .add(track.path.clone(), aif_track.path.clone(), &track.metadata.id);
+                        *track = aif_track;
+                        tag_result = utils::read_tags(track, self.config.verbose || self.config.debug);
                     }
-                    if !self.config.print_only
-                        && (self.config.force || utils::confirm())
-                        && Self::write_tags(track, &mut file_tags)
-                    {
-                        if tags_changed {
-                            track.tags_updated = true;
-                            self.stats.tags_fixed += 1;
-                        }
-                    } else {
-                        track.not_processed = true;
+                    Err(e) => {
+                        eprintln!("{}", e.to_string().red());
+                    }
+                }
+            }
+            let Some(mut file_tags) = tag_result else {
+                let mut failure = read_failure.unwrap_or_else(|| {
+                    FailedFile::new(
+                        utils::path_to_string(&track.path),
+                        FailureCategory::Other,
+                        "Failed to read tags",
+                    )
+                });
+                if utils::is_cloud_placeholder(&track.path) {
+                    failure.category = FailureCategory::Placeholder;
+                }
+                if self.retries_left > 0 && failure.category.is_transient() {
+                    // Leave out of the state until the retry pass at the end of the run
+                    track.not_processed = true;
+                    self.retry_paths.push(track.path.clone());
+                    println!("{}", format!("Retrying later ({})", failure.category).yellow());
+                    return Ok(());
+                }
+                self.stats.failed += 1;
+                if self.config.log_failures {
+                    failure.id = track.report_id(&self.root);
+                    self.failed_files.push(failure);
+                }
+                return Ok(());
+            };
+
+            if self.config.check_vbr && track.format == FileFormat::Mp3 {
+                Self::check_vbr_header(track, &self.config, &mut self.stats, max_index_width, self.tracks_count);
+            }
+
+            if self.config.check_aifc && track.format == FileFormat::Aif {
+                Self::check_aifc(track, &self.config, &mut self.stats, max_index_width, self.tracks_count);
+            }
+
+            if self.config.upgrade_tags {
+                self.upgrade_tag_version(track, file_tags, max_index_width);
+                return Ok(());
+            }
+
+            // Store id3 tag version count
+            *self.tag_versions.entry(file_tags.version().to_string()).or_insert(0) += 1;
+
+            track.metadata.id = track_id::read(&file_tags).unwrap_or_default();
+            let add_id = self.config.write_id && !self.config.print_only && track.metadata.id.is_empty();
+            if add_id {
+                track.metadata.id = track_id::generate();
+            }
+
+            // APEv2 tags left over from old rips can conflict with the ID3 tag in DJ software
+            let has_ape = track.format == FileFormat::Mp3 && ape::has_tag(&track.path).unwrap_or(false);
+            if has_ape {
+                self.stats.ape_tags += 1;
+            }
+            let strip_ape = has_ape && self.config.strip_ape;
+
+            // Some tools export DJ edits with bogus chapter frames
+            let has_chapters = utils::has_chapter_frames(&file_tags);
+            if has_chapters {
+                self.stats.chapter_tags += 1;
+            }
+            let strip_frames: Vec<&str> = file_tags
+                .frames()
+                .map(id3::Frame::id)
+                .filter(|id| self.config.strip_frames.iter().any(|strip| strip == id))
+                .unique()
+                .collect();
+            // Oversized embedded artwork is re-encoded during the tag write
+            let resize_art = self
+                .config
+                .artwork
+                .is_some_and(|options| artwork::has_oversized_pictures(&file_tags, options.max_size));
+            if resize_art {
+                self.stats.art_oversized += 1;
+            }
+            let strip = strip_ape || !strip_frames.is_empty() || resize_art;
+
+            let audio_properties = if self.config.verbose || self.config.resample.is_some() {
+                match track.audio_properties() {
+                    Ok(properties) => {
+                        *self.audio_formats.entry(properties.to_string()).or_insert(0) += 1;
+                        Some(properties)
                     }
-                    if tags_changed {
-                        utils::print_divider(&track.tags.formatted_name);
+                    Err(error) => {
+                        if self.config.debug {
+                            utils::print_error(&error.to_string());
+                        }
+                        None
                     }
                 }
+            } else {
+                None
+            };
+
+            if self.config.debug && self.config.verbose {
+                utils::print_tag_data(&file_tags);
+                serato::print_serato_tags(&file_tags);
+                if let Some(properties) = audio_properties {
+                    println!("Audio: {properties}");
+                }
+            }
 
-                // Store unique genre count
-                if !track.tags.formatted_genre.is_empty() {
-                    *self.genres.entry(track.tags.formatted_genre.clone()).or_insert(0) += 1;
+            if let (Some(target), Some(properties)) = (self.config.resample, audio_properties) {
+                if properties.exceeds(&target) {
+                    Self::resample_track(
+                        track,
+                        &self.config,
+                        &mut self.stats,
+                        target,
+                        &properties,
+                        max_index_width,
+                        self.tracks_count,
+                    );
                 }
+            }
 
-                if self.config.tags_only {
-                    self.processed_files
-                        .entry(formatted_name.to_lowercase())
-                        .or_default()
-                        .push(track.clone());
+            track.format_tags(&file_tags, &self.format);
+            self.resolve_various_artists(track, max_index_width);
+            self.resolve_artist_conflict(track, &file_tags, max_index_width);
+            if self.config.canonical_name {
+                track.tags.set_canonical_name();
+            }
+            self.check_tag_lengths(track);
+            self.known_artists.insert(&track.tags.formatted_artist);
+            if self.config.record_corpus.is_some() {
+                self.corpus.insert(CorpusEntry {
+                    artist: track.tags.current_artist.clone(),
+                    title: track.tags.current_title.clone(),
+                    formatted_artist: track.tags.formatted_artist.clone(),
+                    formatted_title: track.tags.formatted_title.clone(),
+                });
+            }
+            let formatted_name = track.formatted_filename(&self.format);
+            if formatted_name.is_empty() {
+                eprintln!(
+                    "\n{}",
+                    format!("Formatted name should never be empty: {}", track.path.display()).red()
+                );
+            }
+            let tags_changed = track.tags.changed();
+            let minor_action = if tags_changed {
+                self.minor_change_action(&track.tags.changes())
+            } else {
+                None
+            };
+            if minor_action == Some(MinorChanges::Skip) {
+                self.stats.minor_skipped += 1;
+                track.not_processed = true;
+                events::emit(&Event::ActionSkipped {
+                    path: &track.path,
+                    action: "tags",
+                });
+            } else if tags_changed || self.config.write_all_tags || strip || add_id {
+                // Adding only the track ID does not change any visible values
+                let auto_approve = minor_action == Some(MinorChanges::Approve)
+                    || !(tags_changed || self.config.write_all_tags || strip);
+                if tags_changed {
+                    track.show(self.tracks_count, max_index_width);
+                    self.stats.tags += 1;
+                    println!("{fix_tags_header}");
+                    track.tags.show_diff();
+                    events::emit(&Event::DiffComputed {
+                        path: &track.path,
+                        action: "tags",
+                        changes: &track.tags.changes(),
+                    });
+                } else if strip {
+                    track.show(self.tracks_count, max_index_width);
+                    println!("{fix_tags_header}");
+                    if strip_ape {
+                        println!("{}", "Remove APEv2 tag".yellow());
+                    }
+                    if !strip_frames.is_empty() {
+                        println!("{}", format!("Remove frames: {}", strip_frames.join(", ")).yellow());
+                    }
+                    if resize_art {
+                        println!("{}", "Resize artwork".yellow());
+                    }
+                    if add_id {
+                        println!("{}", "Add track ID".yellow());
+                    }
+                }
+                if !self.config.print_only
+                    && (self.config.force
+                        || auto_approve
+                        || Self::confirm_change(
+                            track,
+                            Action::Tags,
+                            |track| track.tags.changes(),
+                            &mut self.quit,
+                            &mut self.decisions,
+                        ))
+                    && Self::write_tags(track, &mut file_tags, &self.config, &mut self.stats)
+                {
+                    // Store the new size and modified time so the next run can skip this file
+                    let size_before = track.metadata.size;
+                    if let Err(error) = track.refresh_metadata() {
+                        utils::print_error(&error.to_string());
+                    }
+                    self.stats.record_size_change(size_before, track.metadata.size);
+                    if add_id {
+                        self.stats.ids_added += 1;
+                    }
+                    if track.tags.changed() {
+                        track.tags_updated = true;
+                        self.tag_history
+                            .add(track.path.clone(), &track.metadata.id, &track.tags);
+                        self.stats.tags_fixed += 1;
+                        if auto_approve {
+                            self.stats.minor_approved += 1;
+                        }
+                    }
+                    events::emit(&Event::ActionApplied {
+                        path: &track.path,
+                        action: "tags",
+                    });
+                } else {
+                    track.not_processed = true;
+                    events::emit(&Event::ActionSkipped {
+                        path: &track.path,
+                        action: "tags",
+                    });
+                }
+                if tags_changed || strip {
+                    utils::print_divider(&track.tags.formatted_name);
+                }
+            }
+            if has_ape && ape::has_tag(&track.path).unwrap_or(true) {
+                self.ape_files.push(track.path.clone());
+            }
+            if has_chapters && !self.config.strips_chapters() {
+                self.chapter_files.push(track.path.clone());
+            }
+            if self.quit {
+                return Ok(());
+            }
+            // Tags might have been edited by the user
+            let formatted_name = track.formatted_filename(&self.format);
 
-                    continue;
+            // Store unique genre count
+            if !track.tags.formatted_genre.is_empty() {
+                *self.genres.entry(track.tags.formatted_genre.clone()).or_insert(0) += 1;
+            }
+            // Count descriptors in the original title to find spellings the formatting rules miss
+            if self.config.descriptor_statistics {
+                for descriptor in formatting::title_descriptors(&track.tags.current_title) {
+                    *self.descriptors.entry(descriptor.to_string()).or_insert(0) += 1;
                 }
+            }
+            if self.config.feat_index.is_some() {
+                self.feat_index.add(&track.tags.formatted_artist);
+            }
 
-                let formatted_file_name = track.formatted_filename_with_extension();
-                let formatted_path = track.path_with_new_name(&formatted_file_name);
+            if self.config.tags_only {
+                self.processed_files
+                    .entry(formatted_name.to_lowercase())
+                    .or_default()
+                    .push(track.clone());
 
-                // Convert paths to strings for additional comparisons.
-                // macOS and Windows paths are case-insensitive by default,
-                // so `is_file()` will ignore differences in capitalization.
-                let formatted_path_string = utils::path_to_string_relative(&formatted_path);
-                let original_path_string = utils::path_to_string_relative(&track.path);
+                return Ok(());
+            }
 
-                if formatted_path_string != original_path_string {
-                    let capitalization_change_only =
-                        if formatted_path_string.to_lowercase() == original_path_string.to_lowercase() {
-                            // File path contains only capitalization changes:
-                            // Need to use a temp file to workaround case-insensitive file systems.
-                            true
-                        } else {
-                            false
-                        };
-                    if !formatted_path.is_file() || self.config.overwrite_existing || capitalization_change_only {
+            let formatted_file_name = track.formatted_filename_with_extension(&self.format);
+            let formatted_path = track.path_with_new_name(&formatted_file_name);
+
+            // Convert paths to strings for additional comparisons.
+            // macOS and Windows paths are case-insensitive by default,
+            // so `is_file()` will ignore differences in capitalization.
+            let formatted_path_string = utils::path_to_string_relative(&formatted_path);
+            let original_path_string = utils::path_to_string_relative(&track.path);
+
+            if formatted_path_string != original_path_string {
+                let case_only = formatted_path_string.to_lowercase() == original_path_string.to_lowercase();
+                // On a case-insensitive filesystem the new name with only capitalization changes is the same file
+                let same_file = case_only && utils::is_same_file(&track.path, &formatted_path);
+                if !formatted_path.is_file() || self.config.overwrite_existing || same_file {
+                    let minor_action = self.minor_change_action(&[track.filename_change(&self.format)]);
+                    if minor_action == Some(MinorChanges::Skip) {
+                        self.stats.minor_skipped += 1;
+                        track.not_processed = true;
+                        events::emit(&Event::ActionSkipped {
+                            path: &track.path,
+                            action: "rename",
+                        });
+                    } else if self.config.rename_files || !track.tags_updated {
                         // Rename files if the flag was given or if tags were not changed
-                        if self.config.rename_files || !track.tags_updated {
-                            track.show(self.tracks_count, max_index_width);
+                        let auto_approve = minor_action == Some(MinorChanges::Approve);
+                        track.show(self.tracks_count, max_index_width);
+                        if case_only {
+                            println!("{rename_file_header} {}", Message::CaseOnly.text().yellow());
+                            self.stats.to_rename_case_only += 1;
+                        } else {
                             println!("{rename_file_header}");
-                            utils::print_stacked_diff(&track.filename(), &formatted_file_name);
-                            self.stats.to_rename += 1;
-                            if !self.config.print_only && (self.config.force || utils::confirm()) {
-                                if formatted_path.is_file() && self.config.overwrite_existing {
-                                    println!(
-                                        "{}",
-                                        format!("Overwriting existing file: {formatted_path_string}").yellow()
-                                    );
-                                }
-                                if capitalization_change_only {
-                                    let temp_file =
-                                        formatted_path.with_extension(format!("{}.{}", track.format, "tmp"));
-                                    utils::rename_track(&track.path, &temp_file, self.config.test_mode)?;
-                                    utils::rename_track(&temp_file, &formatted_path, self.config.test_mode)?;
-                                } else {
-                                    utils::rename_track(&track.path, &formatted_path, self.config.test_mode)?;
-                                }
-                                if self.config.test_mode && formatted_path.exists() {
-                                    fs::remove_file(formatted_path).context("Failed to remove renamed file")?;
-                                } else {
-                                    // Update track data with the renamed path
-                                    let renamed_track = track.renamed_track(formatted_path, formatted_name.clone())?;
-                                    *track = renamed_track;
+                        }
+                        utils::print_stacked_diff(&track.filename(), &formatted_file_name);
+                        events::emit(&Event::DiffComputed {
+                            path: &track.path,
+                            action: "rename",
+                            changes: &[track.filename_change(&self.format)],
+                        });
+                        self.stats.to_rename += 1;
+                        if !self.config.print_only
+                            && (self.config.force
+                                || auto_approve
+                                || Self::confirm_change(
+                                    track,
+                                    Action::Rename,
+                                    |track| vec![track.filename_change(&self.format)],
+                                    &mut self.quit,
+                                    &mut self.decisions,
+                                ))
+                        {
+                            let original_path = track.path.clone();
+                            if formatted_path.is_file() && self.config.overwrite_existing {
+                                println!(
+                                    "{}",
+                                    Message::OverwritingFile.format(&[&formatted_path_string]).yellow()
+                                );
+                            }
+                            if let Some(throttle) = &mut self.rename_throttle {
+                                throttle.wait();
+                            }
+                            // Capitalization changes need a temp file on a case-insensitive filesystem.
+                            // Probed only when renaming so nothing is written in print and read-only modes.
+                            let temp_file = (case_only && !self.case_sensitivity.is_case_sensitive(&track.root))
+                                .then(|| formatted_path.with_extension(format!("{}.{}", track.format, "tmp")));
+                            if let Err(error) = utils::rename_track(&track.path, &formatted_path, temp_file.as_deref())
+                            {
+                                if self.config.test_mode {
+                                    return Err(error.into());
                                 }
-                                self.stats.renamed += 1;
+                                self.record_rename_failure(track, &error);
+                                utils::print_divider(&formatted_file_name);
+                                return Ok(());
+                            }
+                            if self.config.test_mode && formatted_path.exists() {
+                                fs::remove_file(formatted_path).context("Failed to remove renamed file")?;
                             } else {
-                                track.not_processed = true;
+                                self.journal_rename(&track.path, &formatted_path);
+                                self.renames
+                                    .add(track.path.clone(), formatted_path.clone(), &track.metadata.id);
+                                // Update track data with the renamed path
+                                let renamed_track = track.renamed_track(formatted_path, formatted_name.clone())?;
+                                *track = renamed_track;
+                            }
+                            self.stats.renamed += 1;
+                            if case_only {
+                                self.stats.renamed_case_only += 1;
+                            }
+                            if auto_approve {
+                                self.stats.minor_approved += 1;
                             }
-                            utils::print_divider(&formatted_file_name);
+                            events::emit(&Event::ActionApplied {
+                                path: &original_path,
+                                action: "rename",
+                            });
+                        } else {
+                            if self.config.print_only {
+                                self.journal_rename(&track.path, &formatted_path);
+                            }
+                            track.not_processed = true;
+                            events::emit(&Event::ActionSkipped {
+                                path: &track.path,
+                                action: "rename",
+                            });
                         }
-                    } else if formatted_path != track.path {
-                        // A file with the formatted name already exists
-                        track.show(self.tracks_count, max_index_width);
-                        println!("{}", "Duplicate:".bright_red().bold());
-                        println!("Rename:   {original_path_string}");
-                        println!("Existing: {formatted_path_string}");
                         utils::print_divider(&formatted_file_name);
-                        self.stats.duplicates += 1;
                     }
+                } else if formatted_path != track.path {
+                    // A file with the formatted name already exists
+                    track.show(self.tracks_count, max_index_width);
+                    println!("{}", Message::SummaryDuplicate.text().bright_red().bold());
+                    println!("Rename:   {original_path_string}");
+                    println!("Existing: {formatted_path_string}");
+                    utils::print_divider(&formatted_file_name);
+                    self.stats.duplicates += 1;
                 }
-                self.processed_files
-                    .entry(formatted_name.to_lowercase())
-                    .or_default()
-                    .push(track.clone());
-            } else {
-                self.processed_files
-                    .entry(track.name.to_string())
-                    .or_default()
-                    .push(track.clone());
             }
+            self.processed_files
+                .entry(formatted_name.to_lowercase())
+                .or_default()
+                .push(track.clone());
+        } else {
+            self.processed_files
+                .entry(track.name.clone())
+                .or_default()
+                .push(track.clone());
         }
+        Ok(())
+    }
 
-        println!("{}", "\nFinished".green());
-        if self.config.debug {
-            let duration = start_instant.elapsed();
-            println!("Time taken: {:.3}s", duration.as_secs_f64());
-        }
-        println!("{}", self.stats);
-        if self.config.log_failures && !self.failed_files.is_empty() {
-            utils::write_log_for_failed_files(&self.failed_files)?;
-        }
-        if self.config.verbose {
-            self.print_tag_version_counts();
+    /// Rewrite an outdated tag as ID3v2.4 without changing the tag contents.
+    fn upgrade_tag_version(&mut self, track: &mut Track, file_tags: Tag, max_index_width: usize) {
+        // Formatting is not applied so the track still needs processing on a normal run
+        track.not_processed = true;
+
+        // Files with only an ID3v1 tag are read as having an ID3v2.4 tag with the ID3v1 values
+        let v1_tags = if track.format == FileFormat::Mp3 && file_tags.version() == id3::Version::Id3v24 {
+            utils::read_id3v1_only(&track.path)
+        } else {
+            None
+        };
+        let from_v1 = v1_tags.is_some();
+        let current_version = if from_v1 {
+            "ID3v1".to_string()
+        } else {
+            file_tags.version().to_string()
+        };
+        *self.tag_versions.entry(current_version.clone()).or_insert(0) += 1;
+        if !from_v1 && file_tags.version() == id3::Version::Id3v24 {
+            return;
         }
-        if self.config.genre_statistics {
-            println!("{}", format!("Genres ({}):", self.genres.len()).cyan().bold());
-            let mut genre_list: Vec<(&String, &usize)> =
-                self.genres.iter().sorted_unstable_by(|a, b| b.1.cmp(a.1)).collect();
 
-            Self::print_top_genres(&genre_list);
-            genre_list.sort_unstable();
-            Self::write_genre_log(&genre_list)?;
+        let file_tags = match v1_tags {
+            Some(tags) => tags,
+            // Tags read with the lightweight reader only contain the formatting frames
+            None if self.config.text_tags_only() => match utils::read_tags(track, self.config.verbose) {
+                Some(tags) => tags,
+                None => return,
+            },
+            None => file_tags,
+        };
+
+        let dryrun_header = if self.config.print_only { " (dryrun)" } else { "" };
+        let change = FieldChange {
+            field: "version".to_string(),
+            current: current_version,
+            proposed: id3::Version::Id3v24.to_string(),
+        };
+        track.show(self.tracks_count, max_index_width);
+        println!("{}", format!("Upgrade tags{dryrun_header}:").blue().bold());
+        println!("{} -> {}", change.current.yellow(), change.proposed.green());
+        self.stats.to_upgrade += 1;
+
+        if !self.config.print_only
+            && (self.config.force
+                || Self::confirm_change(
+                    track,
+                    Action::Upgrade,
+                    |_| vec![change.clone()],
+                    &mut self.quit,
+                    &mut self.decisions,
+                ))
+        {
+            let write = |path: &Path| -> Result<()> {
+                if from_v1 {
+                    // Also removes the ID3v1 tag which is now included in the ID3v2.4 tag
+                    id3::v1v2::write_to_path(path, &file_tags, id3::Version::Id3v24)?;
+                } else {
+                    file_tags.write_to_path(path, id3::Version::Id3v24)?;
+                }
+                Ok(())
+            };
+            let result = if self.config.safe_writes {
+                utils::replace_atomically(&track.path, write)
+            } else {
+                write(&track.path)
+            };
+            match result {
+                Ok(()) => {
+                    self.stats.upgraded += 1;
+                    let size_before = track.metadata.size;
+                    if let Err(error) = track.refresh_metadata() {
+                        utils::print_error(&error.to_string());
+                    }
+                    self.stats.record_size_change(size_before, track.metadata.size);
+                }
+                Err(error) => utils::print_error(&format!("Failed to upgrade tags for: {track}\n{error}")),
+            }
         }
-        self.print_all_duplicates();
+        utils::print_divider(&track.filename());
+    }
 
-        Ok(())
+    /// Report a track that is not in the required format for its directory,
+    /// and convert it if conversion is enabled.
+    fn check_format_rule(&mut self, track: &mut Track, max_index_width: usize) {
+        let Some(format) = file_format::required_format(&self.config.format_rules, &track.path) else {
+            return;
+        };
+        if *format == track.format {
+            return;
+        }
+        let format = format.clone();
+        track.show(self.tracks_count, max_index_width);
+        self.stats.wrong_format += 1;
+        let message = format!(
+            "Wrong format {}, directory requires {}",
+            track.format.to_string().to_uppercase(),
+            format.to_string().to_uppercase()
+        );
+        println!("{}", message.yellow());
+        if self.config.convert_failed && !self.config.print_only && (self.config.force || prompt::confirm()) {
+            let size_before = track.metadata.size;
+            match track.convert_to_format(&format) {
+                Ok(converted) => {
+                    self.stats.format_converted += 1;
+                    self.stats.record_size_change(size_before, converted.metadata.size);
+                    self.journal_rename(&track.path, &converted.path);
+                    self.renames
+                        .add(track.path.clone(), converted.path.clone(), &track.metadata.id);
+                    *track = converted;
+                }
+                Err(error) => utils::print_error(&error.to_string()),
+            }
+        }
+        utils::print_divider(&message);
     }
 
-    #[inline]
-    /// Print running index
-    fn print_running_index(total_tracks: usize, number: usize, max_index_width: usize) {
-        print!("\r{number:>max_index_width$}/{total_tracks}");
-        io::stdout().flush().expect("Failed to flush stdout");
+    /// Choose where to split a "Various Artists" title that contains more than one ` - `.
+    ///
+    /// The best scoring split is used, and the user is asked when the best splits are equally likely.
+    fn resolve_various_artists(&self, track: &mut Track, max_index_width: usize) {
+        let candidates = self.various_artists_candidates(track);
+        if candidates.is_empty() {
+            return;
+        }
+        let index = if artists::is_ambiguous(&candidates) && !self.config.force && !self.config.print_only {
+            track.show(self.tracks_count, max_index_width);
+            let options: Vec<String> = candidates
+                .iter()
+                .map(|candidate| format!("Artist: {} | Title: {}", candidate.artist, candidate.title))
+                .collect();
+            prompt::select("Choose artist and title:", &options)
+        } else {
+            0
+        };
+        let chosen = &candidates[index];
+        track.format_artist_and_title(&chosen.artist, &chosen.title, &self.format);
     }
 
-    /// Count and print the total number of each file extension in the file list.
-    fn print_extension_counts(&self) {
-        println!("{}", "File format counts:".bold());
-        self.tracks
-            .iter()
-            .map(|track| track.format.to_string())
-            .counts()
-            .into_iter()
-            .sorted_unstable_by(|a, b| b.1.cmp(&a.1))
-            .for_each(|(format, count)| println!("{format}: {count}"));
+    /// Possible artist and title splits for a "Various Artists" title, best first.
+    ///
+    /// Empty if the title can only be split one way.
+    fn various_artists_candidates(&self, track: &Track) -> Vec<SplitCandidate> {
+        if !track.tags.current_artist.trim().eq_ignore_ascii_case("Various Artists") {
+            return Vec::new();
+        }
+        let candidates = artists::split_candidates(&track.tags.current_title, &self.known_artists);
+        if candidates.len() < 2 {
+            return Vec::new();
+        }
+        candidates
     }
 
-    /// Insert processed tracks and save state.
-    fn update_state(&self) -> Result<()> {
-        let (added_count, updated_count) = self
-            .tracks
-            .par_iter()
-            .filter(|track| !track.not_processed)
-            .map(|track| {
-                if self.state.insert(track.path.clone(), track.metadata.clone()).is_some() {
-                    (0, 1)
-                } else {
-                    (1, 0)
+    /// Choose between the artist tag and the artist in the filename when they disagree.
+    fn resolve_artist_conflict(&mut self, track: &mut Track, file_tags: &Tag, max_index_width: usize) {
+        let Some(filename_artist) = track.filename_artist_conflict(file_tags, &self.format) else {
+            return;
+        };
+        self.stats.artist_conflicts += 1;
+        track.show(self.tracks_count, max_index_width);
+        let tag_artist = track.tags.current_artist.clone();
+        let use_filename = match self.config.artist_conflict {
+            ArtistConflict::Ask if !self.config.force && !self.config.print_only => {
+                let options = [
+                    format!("Tag:      {tag_artist}"),
+                    format!("Filename: {filename_artist}"),
+                ];
+                prompt::select("Artist tag and filename disagree:", &options) == 1
+            }
+            policy => {
+                println!("{}", "Artist tag and filename disagree:".yellow());
+                println!("  Tag:      {tag_artist}");
+                println!("  Filename: {filename_artist}");
+                policy == ArtistConflict::Filename
+            }
+        };
+        if use_filename {
+            self.stats.artist_from_filename += 1;
+            let title = track.tags.current_title.clone();
+            track.format_artist_and_title(&filename_artist, &title, &self.format);
+        }
+    }
+
+    /// Configured action if all changes are at most the minor change threshold.
+    fn minor_change_action(&self, changes: &[FieldChange]) -> Option<MinorChanges> {
+        self.config
+            .minor_changes
+            .filter(|_| prompt::is_minor_change(changes, self.config.minor_threshold))
+    }
+
+    /// Count a rename that could not be done and leave the track out of the state.
+    fn record_rename_failure(&mut self, track: &mut Track, error: &TrackRenameError) {
+        let message = error
+            .source()
+            .map_or_else(|| error.to_string(), |source| format!("{error}: {source}"));
+        utils::print_error(&message);
+        track.not_processed = true;
+        self.stats.rename_failed += 1;
+        if self.config.log_failures {
+            let category = match error {
+                TrackRenameError::Rename { source, .. } | TrackRenameError::Io { source, .. } => {
+                    FailureCategory::from_io_error(source)
                 }
-            })
-            .reduce(|| (0, 0), |acc, item| (acc.0 + item.0, acc.1 + item.1));
+                _ => FailureCategory::Other,
+            };
+            let mut failure = FailedFile::new(utils::path_to_string(&track.path), category, &message);
+            failure.id = track.report_id(&self.root);
+            self.failed_files.push(failure);
+        }
+    }
 
-        if self.config.debug || self.config.verbose {
-            println!(
-                "State updated: {} new tracks added, {} existing tracks updated. Total: {}",
-                added_count,
-                updated_count,
-                self.state.len()
-            );
+    /// Count a track where processing panicked as failed and leave it out of the state.
+    fn record_panic(&mut self, track: &mut Track, payload: &(dyn Any + Send)) {
+        let message = payload
+            .downcast_ref::<&str>()
+            .map(ToString::to_string)
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "unknown error".to_string());
+        utils::print_error(&format!("Processing failed for {}: {message}", track.path.display()));
+        track.not_processed = true;
+        self.stats.failed += 1;
+        if self.config.log_failures {
+            let mut failure = FailedFile::new(utils::path_to_string(&track.path), FailureCategory::Panic, &message);
+            failure.id = track.report_id(&self.root);
+            self.failed_files.push(failure);
         }
+    }
+
+    /// Replace duplicate tracks that have identical file contents with links to one copy.
+    ///
+    /// Keeps all paths valid for crates and playlists while only storing the audio data once.
+    fn link_duplicates(&mut self) {
+        let mut groups: Vec<Vec<PathBuf>> = self
+            .processed_files
+            .values()
+            .filter(|tracks| tracks.len() > 1)
+            .map(|tracks| {
+                tracks
+                    .iter()
+                    .map(|track| track.path.clone())
+                    .sorted_unstable()
+                    .collect()
+            })
+            .collect();
+        groups.sort_unstable();
 
-        self.state.save()
+        for paths in groups {
+            let original = &paths[0];
+            for duplicate in &paths[1..] {
+                if self.quit {
+                    return;
+                }
+                if utils::is_same_file(original, duplicate) {
+                    continue;
+                }
+                match utils::same_contents(original, duplicate) {
+                    Ok(true) => {}
+                    Ok(false) => continue,
+                    Err(error) => {
+                        utils::print_error(&format!("Failed to compare {}: {error}", duplicate.display()));
+                        continue;
+                    }
+                }
+                let Some(mut track) = Track::try_from_path(duplicate) else {
+                    continue;
+                };
+                self.stats.to_link += 1;
+                println!("{}", "Link identical duplicate:".magenta().bold());
+                println!("Original:  {}", utils::path_to_string_relative(original));
+                println!("Duplicate: {}", utils::path_to_string_relative(duplicate));
+                let change = FieldChange {
+                    field: "file".to_string(),
+                    current: utils::path_to_string(duplicate),
+                    proposed: utils::path_to_string(original),
+                };
+                if self.config.force
+                    || Self::confirm_change(
+                        &mut track,
+                        Action::Link,
+                        |_| vec![change.clone()],
+                        &mut self.quit,
+                        &mut self.decisions,
+                    )
+                {
+                    match utils::replace_with_link(original, duplicate) {
+                        Ok(kind) => {
+                            println!("{}", format!("Replaced with {kind}").green());
+                            self.stats.linked += 1;
+                            // The duplicate now shares the data with the original
+                            self.stats.record_size_change(track.metadata.size, 0);
+                        }
+                        Err(error) => utils::print_error(&error.to_string()),
+                    }
+                }
+                utils::print_divider(&track.filename());
+            }
+        }
     }
 
     /// Print all paths for duplicate tracks with the same name.
@@ -489,6 +2235,116 @@ impl TrackRenamer {
         }
     }
 
+    /// Print directories and files that could not be read,
+    /// so it is clear which parts of the library were never scanned.
+    fn print_skipped_paths(&self) {
+        let (permission_denied, other): (Vec<&SkippedPath>, Vec<&SkippedPath>) =
+            self.skipped_paths.iter().partition(|skipped| skipped.permission_denied);
+        if !permission_denied.is_empty() {
+            println!(
+                "{}",
+                format!("Skipped (permission denied) ({}):", permission_denied.len())
+                    .red()
+                    .bold()
+            );
+            for skipped in permission_denied {
+                println!("  {}", skipped.path.display());
+            }
+        }
+        if !other.is_empty() {
+            println!("{}", format!("Skipped ({}):", other.len()).red().bold());
+            for skipped in other {
+                println!("  {}: {}", skipped.path.display(), skipped.reason);
+            }
+        }
+    }
+
+    /// Shorten formatted tag values over the length limits, or collect a warning for them.
+    ///
+    /// The shortened values are shown in the tag diff and listed again at the end of the run.
+    fn check_tag_lengths(&mut self, track: &mut Track) {
+        let limits = self.config.tag_limits;
+        if limits.is_empty() {
+            return;
+        }
+        let (violations, message) = if self.config.truncate_tags {
+            (limits.truncate_tags(&mut track.tags), Message::TagShortened)
+        } else {
+            (limits.check_tags(&track.tags), Message::TagTooLong)
+        };
+        let path = utils::path_to_string_relative(&track.path);
+        for violation in violations {
+            self.tag_length_warnings.push(message.format(&[
+                &violation.field,
+                &violation.length,
+                &violation.limit,
+                &path,
+            ]));
+        }
+    }
+
+    /// Print the tag values that are over the length limits or were shortened.
+    fn print_tag_length_warnings(&self) {
+        for warning in &self.tag_length_warnings {
+            eprintln!("{}", warning.yellow());
+        }
+        if !self.tag_length_warnings.is_empty() && !self.config.truncate_tags {
+            eprintln!("Use --truncate-tags to shorten them");
+        }
+    }
+
+    /// Print the genre mapping warnings collected for DJ music folders.
+    fn print_genre_mapping_warnings(&self) {
+        for warning in &self.genre_mapping_warnings {
+            eprintln!("{}", warning.yellow());
+        }
+    }
+
+    fn print_ape_files(&self) {
+        if self.ape_files.is_empty() {
+            return;
+        }
+        println!("{}", format!("APEv2 tags ({}):", self.ape_files.len()).yellow().bold());
+        for path in &self.ape_files {
+            println!("  {}", utils::path_to_string_relative(path));
+        }
+        if !self.config.strip_ape {
+            println!("Use --strip-ape to remove them");
+        }
+    }
+
+    fn print_chapter_files(&self) {
+        if self.chapter_files.is_empty() {
+            return;
+        }
+        println!(
+            "{}",
+            format!("Chapter frames ({}):", self.chapter_files.len())
+                .yellow()
+                .bold()
+        );
+        for path in &self.chapter_files {
+            println!("  {}", utils::path_to_string_relative(path));
+        }
+        println!("Use --strip-chapters to remove them");
+    }
+
+    fn print_placeholder_files(&self) {
+        if self.placeholder_files.is_empty() {
+            return;
+        }
+        println!(
+            "{}",
+            format!("Skipped online-only files ({}):", self.placeholder_files.len())
+                .yellow()
+                .bold()
+        );
+        for path in &self.placeholder_files {
+            println!("  {}", utils::path_to_string_relative(path));
+        }
+        println!("Use --placeholders hydrate to download and process them");
+    }
+
     fn print_tag_version_counts(&self) {
         println!("{}", "Tag versions:".cyan().bold());
         let total: usize = self.tag_versions.values().sum();
@@ -505,16 +2361,28 @@ impl TrackRenamer {
             .for_each(|string| println!("{string}"));
     }
 
-    fn print_top_genres(genre_list: &[(&String, &usize)]) {
-        let max_length = genre_list
-            .iter()
-            .take(20)
-            .map(|g| g.0.chars().count())
+    fn print_audio_format_counts(&self) {
+        if self.audio_formats.is_empty() {
+            return;
+        }
+        println!("{}", "Audio formats:".cyan().bold());
+        let max_length = self
+            .audio_formats
+            .keys()
+            .map(|format| format.chars().count())
             .max()
-            .unwrap_or(60);
+            .unwrap_or(0);
+        self.audio_formats
+            .iter()
+            .sorted_unstable_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0)))
+            .for_each(|(format, count)| println!("{format:<max_length$}   {count}"));
+    }
+
+    fn print_top_counts(list: &[(&String, &usize)]) {
+        let max_length = list.iter().take(20).map(|g| g.0.chars().count()).max().unwrap_or(60);
 
-        for (genre, count) in genre_list.iter().take(20) {
-            println!("{genre:<max_length$}   {count}");
+        for (name, count) in list.iter().take(20) {
+            println!("{name:<max_length$}   {count}");
         }
     }
 
@@ -530,7 +2398,480 @@ impl TrackRenamer {
         Ok(())
     }
 
-    fn write_tags(track: &Track, file_tags: &mut Tag) -> bool {
+    /// Write title descriptors with their counts to a txt file in the current working directory.
+    fn write_descriptor_log(descriptors: &[(&String, &usize)]) -> Result<()> {
+        let filepath = Path::new("descriptors.txt");
+        let mut file = File::create(filepath).context("Failed to create output file")?;
+        for (descriptor, count) in descriptors {
+            writeln!(file, "{count}\t{descriptor}")?;
+        }
+
+        println!("Logged descriptors to: {}", dunce::canonicalize(filepath)?.display());
+        Ok(())
+    }
+
+    /// Check MP3 VBR header matches the actual audio frame count and optionally rebuild it.
+    fn check_vbr_header(
+        track: &mut Track,
+        config: &Config,
+        stats: &mut Statistics,
+        max_index_width: usize,
+        total_tracks: usize,
+    ) {
+        let info = match Mp3Info::from_path(&track.path) {
+            Ok(Some(info)) => info,
+            Ok(None) => {
+                track.show(total_tracks, max_index_width);
+                utils::print_error("No MPEG audio frames found");
+                return;
+            }
+            Err(error) => {
+                track.show(total_tracks, max_index_width);
+                utils::print_error(&error.to_string());
+                return;
+            }
+        };
+        let Some(mismatch) = info.duration_mismatch() else {
+            return;
+        };
+
+        track.show(total_tracks, max_index_width);
+        stats.vbr_mismatch += 1;
+        println!("{}", "VBR header:".yellow().bold());
+        println!("{mismatch}");
+        match info.header_duration() {
+            Some(header_duration) => {
+                println!("Duration: {:.3}s -> {:.3}s", header_duration, info.duration());
+            }
+            None => println!("Duration: {:.3}s", info.duration()),
+        }
+
+        if config.fix_vbr && !config.print_only && (config.force || prompt::confirm()) {
+            match track.rebuild_vbr_header() {
+                Ok(()) => {
+                    stats.vbr_fixed += 1;
+                    let size_before = track.metadata.size;
+                    if let Err(error) = track.refresh_metadata() {
+                        utils::print_error(&error.to_string());
+                    }
+                    stats.record_size_change(size_before, track.metadata.size);
+                }
+                Err(error) => utils::print_error(&error.to_string()),
+            }
+        }
+        utils::print_divider(&mismatch.to_string());
+    }
+
+    /// Report AIFC files and optionally convert them to standard PCM AIFF.
+    fn check_aifc(
+        track: &mut Track,
+        config: &Config,
+        stats: &mut Statistics,
+        max_index_width: usize,
+        total_tracks: usize,
+    ) {
+        let info = match AiffInfo::from_path(&track.path) {
+            Ok(info) => info,
+            Err(error) => {
+                track.show(total_tracks, max_index_width);
+                utils::print_error(&error.to_string());
+                return;
+            }
+        };
+        let Some((compression_type, compression_name)) = &info.compression else {
+            return;
+        };
+
+        track.show(total_tracks, max_index_width);
+        stats.aifc_files += 1;
+        let description = if compression_name.is_empty() {
+            compression_type.clone()
+        } else {
+            format!("{compression_type} ({compression_name})")
+        };
+        let message = if info.is_compressed() {
+            format!("Compressed AIFC: {description}")
+        } else {
+            format!("AIFC: {description}")
+        };
+        println!("{}", message.yellow().bold());
+
+        if config.fix_aifc && !config.print_only && (config.force || prompt::confirm()) {
+            match track.convert_to_pcm_aif() {
+                Ok(()) => {
+                    stats.aifc_converted += 1;
+                    let size_before = track.metadata.size;
+                    if let Err(error) = track.refresh_metadata() {
+                        utils::print_error(&error.to_string());
+                    }
+                    stats.record_size_change(size_before, track.metadata.size);
+                }
+                Err(error) => utils::print_error(&error.to_string()),
+            }
+        }
+        utils::print_divider(&message);
+    }
+
+    /// Resample track that has a higher sample rate or bit depth than the target.
+    fn resample_track(
+        track: &mut Track,
+        config: &Config,
+        stats: &mut Statistics,
+        target: ResampleTarget,
+        properties: &AudioProperties,
+        max_index_width: usize,
+        total_tracks: usize,
+    ) {
+        track.show(total_tracks, max_index_width);
+        // Only lower the sample rate or bit depth, upconverting would just make the file bigger
+        let target = properties.limit_target(&target);
+        let target_properties = AudioProperties {
+            sample_rate: target.sample_rate,
+            bit_depth: properties.bit_depth.map(|bits| bits.min(target.bit_depth)),
+            channels: properties.channels,
+        };
+        let message = format!("{properties} -> {target_properties}");
+        if track.format != FileFormat::Aif {
+            println!(
+                "{}",
+                format!(
+                    "Resampling {} files is not supported: {properties}",
+                    track.format.to_string().to_uppercase()
+                )
+                .yellow()
+            );
+            return;
+        }
+        println!("{}", "Resample:".magenta().bold());
+        println!("{message}");
+        stats.to_resample += 1;
+        if !config.print_only && (config.force || prompt::confirm()) {
+            match track.resample_aif(&target) {
+                Ok(()) => {
+                    stats.resampled += 1;
+                    let size_before = track.metadata.size;
+                    if let Err(error) = track.refresh_metadata() {
+                        utils::print_error(&error.to_string());
+                    }
+                    stats.record_size_change(size_before, track.metadata.size);
+                }
+                Err(error) => utils::print_error(&error.to_string()),
+            }
+        }
+        utils::print_divider(&message);
+    }
+
+    /// Read the tag again skipping malformed frames after the full tag could not be read.
+    ///
+    /// Falls back to the partially read tag if the tolerant reader does not support the file.
+    fn read_tolerant(track: &Track, error: id3::Error, stats: &mut Statistics, verbose: bool) -> Option<Tag> {
+        let result = match tag_reader::read_tolerant(&track.path) {
+            Ok(Some(result)) => result,
+            Ok(None) => return utils::tags_from_result(track, Err(error), verbose),
+            Err(tolerant_error) => {
+                utils::print_error(&format!("Tolerant read failed for: {track}\n{tolerant_error}"));
+                return utils::tags_from_result(track, Err(error), verbose);
+            }
+        };
+        if !result.skipped_frames.is_empty() {
+            println!(
+                "\n{}",
+                format!(
+                    "Skipped malformed frames in {track}: {}",
+                    result.skipped_frames.join(", ")
+                )
+                .yellow()
+            );
+            if verbose {
+                println!("{error}");
+            }
+            stats.frames_skipped += result.skipped_frames.len();
+        }
+        Some(result.tag)
+    }
+
+    /// Try to repair a file with unreadable tags.
+    /// Returns the tag data if the repaired file was taken into use.
+    fn repair_track(track: &mut Track, config: &Config, stats: &mut Statistics) -> Option<Tag> {
+        println!("Trying to repair tags...");
+        let repaired = match repair::repair_mp3(&track.path) {
+            Ok(Some(repaired)) => repaired,
+            Ok(None) => {
+                eprintln!("{}", "Repair failed: no readable tag or audio data found".red());
+                return None;
+            }
+            Err(error) => {
+                utils::print_error(&format!("Repair failed: {error}"));
+                return None;
+            }
+        };
+
+        println!(
+            "{}",
+            format!("Repaired copy readable, removed {} bytes", repaired.removed_bytes).green()
+        );
+        if repaired.tag_removed {
+            println!("{}", "All existing tag data will be removed".yellow());
+        }
+        if config.verbose || config.debug {
+            utils::print_tag_data(&repaired.tag);
+        }
+
+        if config.print_only || !(config.force || prompt::confirm()) {
+            if let Err(error) = repaired.discard() {
+                utils::print_error(&error.to_string());
+            }
+            return None;
+        }
+
+        if let Err(error) = repaired.apply() {
+            utils::print_error(&error.to_string());
+            return None;
+        }
+
+        let size_before = track.metadata.size;
+        if let Err(error) = track.refresh_metadata() {
+            utils::print_error(&error.to_string());
+        }
+        stats.record_size_change(size_before, track.metadata.size);
+        stats.repaired += 1;
+        Some(repaired.tag)
+    }
+
+    /// Ask user to confirm a tag, rename, or link change.
+    ///
+    /// Uses the replayed answer instead if there is one for this track and action.
+    /// Accepting all skips only these prompts for the rest of the run,
+    /// and quitting skips this change and stops processing after the current track.
+    fn confirm_change(
+        track: &mut Track,
+        action: Action,
+        changes: impl Fn(&Track) -> Vec<FieldChange>,
+        quit: &mut bool,
+        decisions: &mut DecisionLog,
+    ) -> bool {
+        let mut edited = None;
+        let mut prompted = false;
+        let all_accepted = decisions.all_accepted();
+        let answer = if all_accepted {
+            Answer::Yes
+        } else if let Some(decision) = decisions.replayed(&track.path, action) {
+            if let Some(tags) = &decision.edited {
+                tags.apply(&mut track.tags);
+                track.tags.show_diff();
+                edited = Some(tags.clone());
+            }
+            println!(
+                "Replayed answer: {}",
+                format!("{:?}", decision.answer).to_lowercase().cyan()
+            );
+            decision.answer
+        } else if decisions.pattern_approved(action, &ChangePattern::from_changes(&changes(track))) {
+            println!("{}", Message::ApprovedSimilar.text().cyan());
+            Answer::Yes
+        } else {
+            let extra: &[Answer] = match action {
+                Action::Tags => &[Answer::Edit, Answer::All, Answer::Quit],
+                Action::Rename | Action::Link | Action::Upgrade => &[Answer::All, Answer::Quit],
+            };
+            prompted = true;
+            loop {
+                let context = PromptContext {
+                    path: track.path.clone(),
+                    action: action.to_string(),
+                    changes: changes(track),
+                };
+                match prompt::ask(extra, Some(&context)) {
+                    Answer::Edit => {
+                        track.tags.edit();
+                        track.tags.show_diff();
+                        edited = Some(EditedTags::from_tags(&track.tags));
+                    }
+                    answer => break answer,
+                }
+            }
+        };
+
+        let decision = Decision {
+            path: track.path.clone(),
+            action,
+            answer,
+            edited,
+        };
+        if let Err(error) = decisions.add(&decision) {
+            utils::print_error(&error.to_string());
+        }
+        if (prompted || all_accepted) && matches!(answer, Answer::Yes | Answer::All) {
+            decisions.record_accepted();
+        }
+        if matches!(answer, Answer::No | Answer::Quit) {
+            decisions.record_declined();
+        }
+
+        match answer {
+            Answer::Yes => true,
+            Answer::No | Answer::Edit => false,
+            Answer::All => {
+                println!("{}", Message::ApplyingAll.text().yellow());
+                decisions.accept_all();
+                true
+            }
+            Answer::Quit => {
+                println!("{}", Message::Stopping.text().yellow());
+                *quit = true;
+                false
+            }
+        }
+    }
+
+    fn write_tags(track: &Track, file_tags: &mut Tag, config: &Config, stats: &mut Statistics) -> bool {
+        // Tags read with the lightweight reader only contain the formatting frames
+        if config.text_tags_only() {
+            let tags = match Tag::read_from_path(&track.path) {
+                Err(error) if config.tolerant && !matches!(error.kind, id3::ErrorKind::NoTag) => {
+                    Self::read_tolerant(track, error, stats, false)
+                }
+                result => utils::tags_from_result(track, result, false),
+            };
+            let Some(tags) = tags else {
+                stats.failed += 1;
+                return false;
+            };
+            *file_tags = tags;
+        }
+        // The parsed tag already hides duplicates with the same encoding, so count them from the file
+        let duplicates = match tag_reader::read_all_frames(&track.path) {
+            Ok(Some(frames)) => duplicate_frames::find(&frames),
+            _ => DuplicateFrames::default(),
+        };
+        duplicate_frames::merge(file_tags);
+        if !track.metadata.id.is_empty() && track_id::read(file_tags).is_none() {
+            track_id::set(file_tags, &track.metadata.id);
+        }
+        let had_chapters = utils::has_chapter_frames(file_tags);
+        utils::remove_frames(file_tags, &config.strip_frames);
+        let art = config.artwork.map_or_else(ArtworkResult::default, |options| {
+            artwork::resize_pictures(file_tags, options).unwrap_or_else(|error| {
+                utils::print_error(&format!("Failed to resize artwork: {error}"));
+                ArtworkResult::default()
+            })
+        });
+        let mut id3v1_removed = false;
+        let mut ape_removed = false;
+        let mut write = |path: &Path| -> Result<()> {
+            Self::write_formatted_tags(track, file_tags, path)?;
+            if track.format == FileFormat::Mp3 {
+                if config.strip_ape {
+                    ape_removed = ape::remove_tag(path).context("Failed to remove APEv2 tag")?;
+                }
+                id3v1_removed = utils::remove_id3v1_tag(path).context("Failed to remove ID3v1 tag")?;
+            }
+            if track.format == FileFormat::Wav {
+                // Keep an existing INFO list in sync for software that does not read the ID3 chunk
+                riff::write_info(path, file_tags).context("Failed to update RIFF INFO list")?;
+            }
+            Ok(())
+        };
+        let result = if config.safe_writes {
+            utils::replace_atomically(&track.path, write)
+        } else {
+            write(&track.path)
+        };
+        if id3v1_removed {
+            stats.id3v1_removed += 1;
+        }
+        if ape_removed {
+            stats.ape_removed += 1;
+        }
+        if result.is_ok() && had_chapters && !utils::has_chapter_frames(file_tags) {
+            stats.chapters_removed += 1;
+        }
+        if result.is_ok() && !duplicates.is_empty() {
+            println!("Merged duplicate frames: {}", duplicates.frames.join(", "));
+            if duplicates.conflicting > 0 {
+                println!(
+                    "{}",
+                    format!(
+                        "{} duplicate frames had a different value, kept the last one",
+                        duplicates.conflicting
+                    )
+                    .yellow()
+                );
+            }
+            stats.duplicate_frames += duplicates.frames.len();
+        }
+        if result.is_ok() && art.resized > 0 {
+            println!(
+                "Resized artwork: {} -> {}",
+                utils::format_bytes(art.original_bytes as u64),
+                utils::format_bytes(art.new_bytes as u64)
+            );
+            stats.art_resized += 1;
+            stats.art_bytes_saved += art.saved_bytes() as u64;
+        }
+        if let Err(error) = result {
+            eprintln!(
+                "\n{}",
+                format!("Failed to write tags for: {}\n{}", track.path.display(), error).red()
+            );
+            return false;
+        }
+        if config.verify_writes && !Self::verify_written_tags(track) {
+            stats.bad_writes += 1;
+            return false;
+        }
+        true
+    }
+
+    /// Read tags back from the file and check they match the formatted values.
+    ///
+    /// Some files accept the write without an error but silently drop frames.
+    fn verify_written_tags(track: &Track) -> bool {
+        let mismatches = match Tag::read_from_path(&track.path) {
+            Ok(tag) => track.tags.write_mismatches(&tag),
+            Err(error) => {
+                utils::print_error(&format!(
+                    "Failed to read back written tags for: {}\n{error}",
+                    track.path.display()
+                ));
+                return false;
+            }
+        };
+        if mismatches.is_empty() {
+            return true;
+        }
+        utils::print_error(&format!("Written tags do not match for: {}", track.path.display()));
+        for mismatch in mismatches {
+            eprintln!(
+                "  {}: expected \"{}\", file has \"{}\"",
+                mismatch.field, mismatch.proposed, mismatch.current
+            );
+        }
+        false
+    }
+
+    /// Warn about inputs where formatting did not reach a stable result.
+    fn print_unconverged_inputs() {
+        let unconverged = formatting::unconverged_inputs();
+        if unconverged.is_empty() {
+            return;
+        }
+        eprintln!(
+            "{}",
+            format!(
+                "Formatting did not converge after {} passes for {} inputs:",
+                formatting::MAX_FORMAT_PASSES,
+                unconverged.len()
+            )
+            .yellow()
+        );
+        for (artist, title) in unconverged {
+            eprintln!("  {artist} - {title}");
+        }
+    }
+
+    /// Write formatted tag values to the given file.
+    fn write_formatted_tags(track: &Track, file_tags: &mut Tag, path: &Path) -> Result<()> {
         // Remove genre first to try to get rid of old ID3v1 genre IDs
         file_tags.remove_genre();
         file_tags.remove_disc();
@@ -539,7 +2880,7 @@ impl TrackRenamer {
         file_tags.remove_total_tracks();
         file_tags.remove_all_lyrics();
         file_tags.remove_all_synchronised_lyrics();
-        if let Err(error) = file_tags.write_to_path(&track.path, id3::Version::Id3v24) {
+        if let Err(error) = file_tags.write_to_path(path, id3::Version::Id3v24) {
             eprintln!(
                 "\n{}",
                 format!("Failed to remove tags for: {}\n{}", track.path.display(), error).red()
@@ -549,15 +2890,30 @@ impl TrackRenamer {
         file_tags.set_title(track.tags.formatted_title.clone());
         file_tags.set_album(track.tags.formatted_album.clone());
         file_tags.set_genre(track.tags.formatted_genre.clone());
-        if let Err(error) = file_tags.write_to_path(&track.path, id3::Version::Id3v24) {
-            eprintln!(
-                "\n{}",
-                format!("Failed to write tags for: {}\n{}", track.path.display(), error).red()
-            );
-            false
+        if !track.tags.formatted_album_artist.is_empty() {
+            file_tags.set_album_artist(track.tags.formatted_album_artist.clone());
+        }
+        if track.tags.formatted_subtitle.is_empty() {
+            file_tags.remove("TIT3");
         } else {
-            true
+            file_tags.set_text("TIT3", track.tags.formatted_subtitle.clone());
+        }
+        if let (true, Some(year)) = (track.tags.year_changed(), track.tags.formatted_year) {
+            file_tags.remove_year();
+            file_tags.set_date_recorded(Timestamp {
+                year,
+                month: None,
+                day: None,
+                hour: None,
+                minute: None,
+                second: None,
+            });
         }
+        if let Some(name) = &track.tags.formatted_canonical_name {
+            canonical_name::set(file_tags, name);
+        }
+        file_tags.write_to_path(path, id3::Version::Id3v24)?;
+        Ok(())
     }
 }
 
@@ -565,7 +2921,6 @@ impl TrackRenamer {
 mod tests {
     use super::*;
 
-    use std::env;
     use std::fs::copy;
     use std::path::Path;
     use std::path::PathBuf;
@@ -574,6 +2929,13 @@ mod tests {
     use rand::distr::Alphanumeric;
     use rand::Rng;
 
+    #[cfg(feature = "fixture")]
+    use track_rename::fixture::{self, FixtureTags};
+    #[cfg(feature = "fixture")]
+    use track_rename::formatting::YearInTitle;
+    #[cfg(feature = "fixture")]
+    use track_rename::serato::SeratoData;
+
     static NO_TAGS_DIR: LazyLock<PathBuf> = LazyLock::new(|| ["tests", "files", "no_tags"].iter().collect());
     static BASIC_TAGS_DIR: LazyLock<PathBuf> = LazyLock::new(|| ["tests", "files", "basic_tags"].iter().collect());
     static EXTENDED_TAGS_DIR: LazyLock<PathBuf> =
@@ -636,6 +2998,116 @@ mod tests {
         });
     }
 
+    #[cfg(feature = "fixture")]
+    #[test]
+    fn test_format_tags_keeps_serato_data() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let paths: Vec<PathBuf> = [FileFormat::Aif, FileFormat::Wav, FileFormat::Mp3]
+            .iter()
+            .map(|format| {
+                let path =
+                    fixture::generate(dir.path(), format, FixtureTags::Serato).expect("Failed to generate fixture");
+                let mut tag = Tag::read_from_path(&path).expect("Failed to read fixture tags");
+                tag.set_title("Song  (extended mix)");
+                tag.write_to_path(&path, id3::Version::Id3v24)
+                    .expect("Failed to write fixture tags");
+                path
+            })
+            .collect();
+        let config = Config {
+            rename_files: false,
+            ..Config::new_for_tests()
+        };
+        let mut renamer = TrackRenamer::new_with_config(dir.path().to_path_buf(), config);
+        renamer.run().expect("Rename failed");
+
+        for path in paths {
+            let tag = Tag::read_from_path(&path).expect("Failed to read tags");
+            assert_eq!(tag.title(), Some("Song (extended mix)"));
+            let serato_data = SeratoData::parse(&tag).expect("Missing Serato data");
+            assert!(serato_data.autotags.is_some());
+            assert!(serato_data.beatgrid.is_some());
+        }
+    }
+
+    #[cfg(feature = "fixture")]
+    #[test]
+    fn test_tags_only_keeps_different_year_tag() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let paths: Vec<PathBuf> = [
+            (FileFormat::Mp3, id3::Version::Id3v24),
+            (FileFormat::Aif, id3::Version::Id3v23),
+        ]
+        .iter()
+        .map(|(format, version)| {
+            let path = fixture::generate(dir.path(), format, FixtureTags::Basic).expect("Failed to generate fixture");
+            let mut tag = Tag::read_from_path(&path).expect("Failed to read fixture tags");
+            tag.set_title("Song (2019)");
+            tag.set_year(2020);
+            tag.write_to_path(&path, *version)
+                .expect("Failed to write fixture tags");
+            path
+        })
+        .collect();
+        let config = Config {
+            rename_files: false,
+            tags_only: true,
+            year_in_title: YearInTitle::Tag,
+            ..Config::new_for_tests()
+        };
+        assert!(config.text_tags_only());
+        let mut renamer = TrackRenamer::new_with_config(dir.path().to_path_buf(), config);
+        renamer.run().expect("Rename failed");
+
+        for path in paths {
+            let tag = Tag::read_from_path(&path).expect("Failed to read tags");
+            assert_eq!(tag.title(), Some("Song (2019)"));
+            let year = tag.year().or_else(|| tag.date_recorded().map(|date| date.year));
+            assert_eq!(year, Some(2020));
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_remove_symlink_aliases() {
+        let source = BASIC_TAGS_DIR.join("Basic Tags - Song - 16-44.mp3");
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let temp_file = temp_test_file(&source, dir.path()).expect("Failed to create temp file path");
+        copy(&source, &temp_file).expect("Failed to copy test file");
+        let temp_file = dunce::canonicalize(&temp_file).expect("Failed to resolve temp file");
+        let link = temp_file.with_file_name("Link.mp3");
+        std::os::unix::fs::symlink(&temp_file, &link).expect("Failed to create symlink");
+
+        let mut track_list: Vec<Track> = [&link, &temp_file]
+            .iter()
+            .filter_map(|path| Track::try_from_path(path))
+            .collect();
+        let aliases = TrackRenamer::remove_aliases(&mut track_list);
+        assert_eq!(track_list.len(), 1);
+        assert_eq!(track_list[0].path, temp_file);
+        assert_eq!(aliases, vec![(link, temp_file)]);
+    }
+
+    #[test]
+    fn test_sort_tracks() {
+        let track = |name: &str, modified: u64| {
+            let mut track = Track::default();
+            track.name = name.to_string();
+            track.path = PathBuf::from(format!("/music/{name}.mp3"));
+            track.metadata.modified = modified;
+            track
+        };
+        let names = |tracks: &[Track]| tracks.iter().map(|track| track.name.clone()).collect::<Vec<_>>();
+        let mut tracks = vec![track("B", 3), track("A", 1), track("C", 2)];
+
+        TrackRenamer::sort_tracks(&mut tracks, TrackOrder::Newest);
+        assert_eq!(names(&tracks), ["B", "C", "A"]);
+        TrackRenamer::sort_tracks(&mut tracks, TrackOrder::Oldest);
+        assert_eq!(names(&tracks), ["A", "C", "B"]);
+        TrackRenamer::sort_tracks(&mut tracks, TrackOrder::Name);
+        assert_eq!(names(&tracks), ["A", "B", "C"]);
+    }
+
     /// Generic test function that takes a function or closure with one `PathBuf` as input argument.
     /// It will create temporary test files and run the test function with them.
     fn run_test_on_files<F: Fn(PathBuf)>(test_dir: &Path, test_func: F) {
@@ -643,7 +3115,8 @@ mod tests {
             let entry = entry.expect("Failed to read entry");
             let path = entry.path();
             if path.is_file() && not_hidden_file(&path) {
-                let temp_file = temp_test_file(&path).expect("Failed to create temp file path");
+                let dir = tempfile::tempdir().expect("Failed to create temp dir");
+                let temp_file = temp_test_file(&path, dir.path()).expect("Failed to create temp file path");
                 copy(&path, &temp_file).expect("Failed to copy test file");
                 assert!(temp_file.exists());
                 test_func(temp_file.clone());
@@ -655,11 +3128,11 @@ mod tests {
     fn not_hidden_file(path: &Path) -> bool {
         path.file_name()
             .and_then(|name| name.to_str())
-            .map_or(true, |s| !s.starts_with('.'))
+            .is_none_or(|s| !s.starts_with('.'))
     }
 
-    /// Create a new temporary file with an added random string in the name
-    fn temp_test_file(path: &Path) -> Option<PathBuf> {
+    /// Create a new temporary file path in the given directory with an added random string in the name
+    fn temp_test_file(path: &Path, dir: &Path) -> Option<PathBuf> {
         let file_stem = path.file_stem()?.to_owned();
         let extension = path.extension()?.to_owned();
         let random_string: String = rand::rng()
@@ -668,10 +3141,6 @@ mod tests {
             .map(char::from)
             .collect();
 
-        let temp_dir = format!("track-rename-{random_string}");
-        let temp_dir_path = env::temp_dir().join(temp_dir);
-        fs::create_dir_all(&temp_dir_path).expect("Failed to create temp subdir");
-
         let test_file_name = format!(
             "{} ({}).{}",
             file_stem.to_string_lossy(),
@@ -679,7 +3148,6 @@ mod tests {
             extension.to_string_lossy()
         );
 
-        let temp_file_path = temp_dir_path.join(test_file_name);
-        Some(temp_file_path)
+        Some(dir.join(test_file_name))
     }
 }
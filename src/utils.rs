@@ -1,29 +1,132 @@
 use std::cmp::Ordering;
-use std::io::Write;
+use std::collections::{BTreeMap, HashMap};
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom, Write};
 use std::path::{Path, PathBuf};
 use std::process::Command;
 use std::time::UNIX_EPOCH;
 
-use anyhow::Context;
 use colored::{ColoredString, Colorize};
 use difference::{Changeset, Difference};
-use id3::{Error, ErrorKind, Tag};
+use id3::v1v2::FormatVersion;
+use id3::{Error, ErrorKind, Tag, TagLike};
 use itertools::Itertools;
 use rayon::prelude::*;
 use unicode_normalization::UnicodeNormalization;
 use walkdir::WalkDir;
 
+use crate::error::{Result, TrackRenameError};
+use crate::failure::FailedFile;
+use crate::file_format::FileFormat;
+use crate::formatting::FilenameSeparator;
+use crate::layout;
 use crate::track::Track;
+use crate::{riff, tag_reader};
 
-/// Recursively collect all supported audio tracks from given root path.
+/// Directory or file that could not be read while gathering tracks.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SkippedPath {
+    pub path: PathBuf,
+    pub reason: String,
+    pub permission_denied: bool,
+}
+
+/// Files found while gathering tracks from a directory.
+#[derive(Debug, Default)]
+pub struct GatheredFiles {
+    pub tracks: Vec<Track>,
+    /// Directories and files that could not be read
+    pub skipped: Vec<SkippedPath>,
+    /// Counts of files that are not supported audio files by lowercase extension
+    pub ignored: BTreeMap<String, usize>,
+    /// Operating system metadata files like `.DS_Store` and `._` `AppleDouble` files
+    pub os_metadata: usize,
+}
+
+/// One entry found while walking a directory.
+enum GatheredEntry {
+    Track(Box<Track>),
+    Skipped(SkippedPath),
+    Ignored(String),
+    OsMetadata,
+}
+
+/// File names created by operating systems that are never audio files.
+const OS_METADATA_FILES: [&str; 4] = [".DS_Store", "Thumbs.db", "desktop.ini", "Icon\r"];
+
+/// Collect all supported audio tracks from a single file or recursively from a directory, sorted by path.
+#[must_use]
 pub fn collect_tracks(root: &Path) -> Vec<Track> {
-    WalkDir::new(root)
+    let mut tracks = gather_files(root).tracks;
+    tracks.sort_unstable_by(|a, b| a.path.cmp(&b.path));
+    tracks
+}
+
+/// Recursively collect all supported audio tracks from given root path,
+/// together with the directories and files that could not be read and counts of ignored files.
+#[must_use]
+pub fn gather_files(root: &Path) -> GatheredFiles {
+    let entries: Vec<GatheredEntry> = WalkDir::new(root)
         .into_iter()
         .par_bridge()
-        .filter_map(std::result::Result::ok)
-        .filter(|e| e.path().is_file())
-        .filter_map(|entry| Track::try_from_path(entry.path()))
-        .collect()
+        .filter_map(|entry| match entry {
+            // Directory entries already know the file type, so only symlinks need an extra stat call
+            Ok(entry) => {
+                (entry.file_type().is_file() || (entry.path_is_symlink() && entry.path().is_file())).then(|| {
+                    if is_os_metadata_file(entry.path()) {
+                        GatheredEntry::OsMetadata
+                    } else {
+                        Track::try_from_path(entry.path()).map_or_else(
+                            || GatheredEntry::Ignored(lowercase_extension(entry.path())),
+                            |track| GatheredEntry::Track(Box::new(track)),
+                        )
+                    }
+                })
+            }
+            Err(error) => Some(GatheredEntry::Skipped(SkippedPath::from_walk_error(root, &error))),
+        })
+        .collect();
+
+    let mut gathered = GatheredFiles::default();
+    for entry in entries {
+        match entry {
+            GatheredEntry::Track(track) => gathered.tracks.push(*track),
+            GatheredEntry::Skipped(skipped) => gathered.skipped.push(skipped),
+            GatheredEntry::Ignored(extension) => *gathered.ignored.entry(extension).or_insert(0) += 1,
+            GatheredEntry::OsMetadata => gathered.os_metadata += 1,
+        }
+    }
+    gathered
+}
+
+/// Return true for files created by the operating system, like `.DS_Store` or `._Song.mp3` `AppleDouble` files.
+#[must_use]
+pub fn is_os_metadata_file(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| name.starts_with("._") || OS_METADATA_FILES.contains(&name))
+}
+
+/// Lowercase file extension, or an empty string if there is none.
+fn lowercase_extension(path: &Path) -> String {
+    path.extension()
+        .map(|extension| extension.to_string_lossy().to_lowercase())
+        .unwrap_or_default()
+}
+
+impl SkippedPath {
+    fn from_walk_error(root: &Path, error: &walkdir::Error) -> Self {
+        let permission_denied = error
+            .io_error()
+            .is_some_and(|e| e.kind() == std::io::ErrorKind::PermissionDenied);
+        let reason = error
+            .io_error()
+            .map_or_else(|| "filesystem loop".to_string(), |e| e.kind().to_string());
+        Self {
+            path: error.path().unwrap_or(root).to_path_buf(),
+            reason,
+            permission_denied,
+        }
+    }
 }
 
 /// Format bool value as a coloured string.
@@ -102,18 +205,6 @@ pub fn color_diff(old: &str, new: &str, stacked: bool) -> (String, String) {
     (old_diff, new_diff)
 }
 
-/// Ask user to confirm action.
-///
-/// Note: everything except `n` or `N` is a yes.
-#[must_use]
-pub fn confirm() -> bool {
-    print!("Proceed (y/n)? ");
-    std::io::stdout().flush().expect("Failed to flush stdout");
-    let mut ans = String::new();
-    std::io::stdin().read_line(&mut ans).expect("Failed to read line");
-    ans.trim().to_lowercase() != "n"
-}
-
 /// Check if the given path contains the subpath.
 ///
 /// Checks if `subpath` is a part of `path`,
@@ -142,6 +233,10 @@ pub fn confirm() -> bool {
 /// // `b/c/x` is not a subpath of `/a/b/c/d`
 /// assert!(!contains_subpath(main_path, subpath));
 ///
+/// let subpath = Path::new("d/e");
+/// // `d/e` is not a subpath of `/a/b/c/d` even though it starts with the last component
+/// assert!(!contains_subpath(main_path, subpath));
+///
 /// let subpath = Path::new("/a/b/c/d/e");
 /// // `/a/b/c/d/e` is longer than `/a/b/c/d`
 /// assert!(!contains_subpath(main_path, subpath));
@@ -151,45 +246,266 @@ pub fn contains_subpath(path: &Path, subpath: &Path) -> bool {
     let main_components: Vec<_> = path.components().collect();
     let sub_components: Vec<_> = subpath.components().collect();
 
-    // Sanity check
-    if sub_components.len() > main_components.len() {
+    if sub_components.is_empty() {
         return false;
     }
+    main_components
+        .windows(sub_components.len())
+        .any(|window| window == sub_components.as_slice())
+}
 
-    // Find the start index of the first subpath component in the main path
-    if let Some(first_sub_component) = sub_components.first() {
-        for (index, main_component) in main_components.iter().enumerate() {
-            if main_component == first_sub_component {
-                // Check all the subcomponents match starting from this index
-                if main_components[index..]
-                    .iter()
-                    .zip(sub_components.iter())
-                    .all(|(main, sub)| main == sub)
-                {
-                    return true;
-                }
+/// Check ffmpeg is found in PATH.
+#[must_use]
+pub fn ffmpeg_available() -> bool {
+    Command::new("ffmpeg").arg("-version").output().is_ok()
+}
+
+/// Get the version number reported by an ffmpeg program like `ffmpeg` or `ffprobe`.
+///
+/// Returns `None` if the program is not found in PATH.
+#[must_use]
+pub fn ffmpeg_version(program: &str) -> Option<String> {
+    let output = Command::new(program).arg("-version").output().ok()?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    // First line looks like "ffmpeg version 7.1 Copyright (c) 2000-2024 the FFmpeg developers"
+    let version = stdout
+        .lines()
+        .next()
+        .and_then(|line| line.split_whitespace().nth(2))
+        .unwrap_or("unknown");
+    Some(version.to_string())
+}
+
+/// Check if the filesystem containing the given directory treats file names case-sensitively.
+///
+/// Creates a temporary lowercase probe file and checks if it can be found with an uppercase name.
+pub fn is_case_sensitive(dir: &Path) -> Result<bool> {
+    let probe = dir.join(format!(".track-rename-case-probe-{}", std::process::id()));
+    std::fs::File::create(&probe)
+        .map_err(|e| TrackRenameError::io(format!("Failed to create probe file in: {}", dir.display()), e))?;
+    let uppercase = dir.join(
+        probe
+            .file_name()
+            .map(|name| name.to_string_lossy().to_uppercase())
+            .unwrap_or_default(),
+    );
+    let case_sensitive = std::fs::symlink_metadata(uppercase).is_err();
+    let _ = std::fs::remove_file(&probe);
+    Ok(case_sensitive)
+}
+
+/// Files at least this large show progress when copied to another device.
+const COPY_PROGRESS_MIN_SIZE: u64 = 64 * 1024 * 1024;
+const COPY_BUFFER_SIZE: usize = 1024 * 1024;
+/// `ID3v1` tag size at the end of the file.
+const ID3V1_SIZE: u64 = 128;
+/// Extended `ID3v1` block size before the `ID3v1` tag.
+const ID3V1_EXTENDED_SIZE: u64 = 227;
+
+/// Filesystem case sensitivity, probed once for each device.
+///
+/// Renames that only change capitalization need a temporary intermediate name
+/// on case-insensitive filesystems, since the original and new names refer to the same file.
+#[derive(Debug, Default)]
+pub struct CaseSensitivity {
+    devices: HashMap<u64, bool>,
+}
+
+impl CaseSensitivity {
+    /// Check if the filesystem containing the given directory is case-sensitive.
+    ///
+    /// Directories that can not be probed are treated as case-insensitive,
+    /// since the two-step rename works on both kinds of filesystems.
+    pub fn is_case_sensitive(&mut self, dir: &Path) -> bool {
+        let Some(device) = device_id(dir) else {
+            return is_case_sensitive(dir).unwrap_or(false);
+        };
+        if let Some(&case_sensitive) = self.devices.get(&device) {
+            return case_sensitive;
+        }
+        match is_case_sensitive(dir) {
+            Ok(case_sensitive) => {
+                self.devices.insert(device, case_sensitive);
+                case_sensitive
             }
+            Err(_) => false,
         }
     }
+}
+
+#[cfg(unix)]
+fn device_id(path: &Path) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+
+    std::fs::metadata(path).ok().map(|metadata| metadata.dev())
+}
+
+#[cfg(not(unix))]
+const fn device_id(_path: &Path) -> Option<u64> {
+    None
+}
+
+/// Check if the file is an online-only placeholder from a cloud storage client like Dropbox or `OneDrive`.
+///
+/// Reading a placeholder downloads the file first, or fails if the sync client is not running.
+#[must_use]
+pub fn is_cloud_placeholder(path: &Path) -> bool {
+    std::fs::metadata(path).is_ok_and(|metadata| is_placeholder_metadata(path, &metadata))
+}
+
+#[cfg(target_os = "windows")]
+fn is_placeholder_metadata(_path: &Path, metadata: &std::fs::Metadata) -> bool {
+    use std::os::windows::fs::MetadataExt;
+
+    const FILE_ATTRIBUTE_OFFLINE: u32 = 0x0000_1000;
+    const FILE_ATTRIBUTE_RECALL_ON_OPEN: u32 = 0x0004_0000;
+    const FILE_ATTRIBUTE_RECALL_ON_DATA_ACCESS: u32 = 0x0040_0000;
+    metadata.file_attributes()
+        & (FILE_ATTRIBUTE_OFFLINE | FILE_ATTRIBUTE_RECALL_ON_OPEN | FILE_ATTRIBUTE_RECALL_ON_DATA_ACCESS)
+        != 0
+}
+
+#[cfg(target_os = "macos")]
+fn is_placeholder_metadata(_path: &Path, metadata: &std::fs::Metadata) -> bool {
+    use std::os::macos::fs::MetadataExt;
+
+    // File Provider files that have not been downloaded
+    const SF_DATALESS: u32 = 0x4000_0000;
+    metadata.st_flags() & SF_DATALESS != 0
+}
+
+/// Directory names of cloud storage clients that can keep files online-only on Linux.
+///
+/// Matched at the start of a path component, so `Dropbox (Personal)` and `OneDrive - Company` also match.
+#[cfg(all(unix, not(target_os = "macos")))]
+const CLOUD_SYNC_DIRECTORIES: [&str; 5] = ["Dropbox", "OneDrive", "pCloudDrive", "Google Drive", "Insync"];
+
+/// Linux has no placeholder attribute, so a file without allocated blocks is only treated as a placeholder
+/// inside a cloud storage directory, since network mounts like SMB and sshfs also report zero blocks.
+#[cfg(all(unix, not(target_os = "macos")))]
+fn is_placeholder_metadata(path: &Path, metadata: &std::fs::Metadata) -> bool {
+    use std::os::unix::fs::MetadataExt;
+
+    // Small files can be stored inline without allocated blocks
+    metadata.is_file()
+        && metadata.len() > 64 * 1024
+        && metadata.blocks() == 0
+        && path.components().any(|component| {
+            let name = component.as_os_str().to_string_lossy();
+            CLOUD_SYNC_DIRECTORIES
+                .iter()
+                .any(|directory| name.starts_with(directory))
+        })
+}
+
+#[cfg(not(any(unix, target_os = "windows")))]
+const fn is_placeholder_metadata(_path: &Path, _metadata: &std::fs::Metadata) -> bool {
     false
 }
 
-/// Check ffmpeg is found in PATH.
+/// Open the folder containing the file in the system file manager.
+///
+/// Selects the file where the file manager supports it.
+pub fn reveal_in_file_manager(path: &Path) -> Result<()> {
+    let mut command = reveal_command(path);
+    command
+        .spawn()
+        .map_err(|error| TrackRenameError::io(format!("Failed to open folder for {}", path.display()), error))?;
+    Ok(())
+}
+
+#[cfg(target_os = "macos")]
+fn reveal_command(path: &Path) -> Command {
+    let mut command = Command::new("open");
+    command.arg("-R").arg(path);
+    command
+}
+
+#[cfg(target_os = "windows")]
+fn reveal_command(path: &Path) -> Command {
+    let mut command = Command::new("explorer");
+    command.arg(format!("/select,{}", path.display()));
+    command
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+fn reveal_command(path: &Path) -> Command {
+    let mut command = Command::new("xdg-open");
+    command.arg(path.parent().unwrap_or(path));
+    command
+}
+
+/// Replace a leading `~` with the user home directory.
 #[must_use]
-pub fn ffmpeg_available() -> bool {
-    Command::new("ffmpeg").arg("-version").output().is_ok()
+pub fn expand_home_dir(path: &str) -> PathBuf {
+    match (path.strip_prefix('~'), dirs::home_dir()) {
+        (Some(rest), Some(home)) => home.join(rest.trim_start_matches(['/', '\\'])),
+        _ => PathBuf::from(path),
+    }
+}
+
+/// Format a byte count with a binary unit, for example `1.5 MiB`.
+#[must_use]
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if bytes < 1024 {
+        return format!("{bytes} B");
+    }
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = UNITS[0];
+    for next in &UNITS[1..] {
+        if value < 1024.0 {
+            break;
+        }
+        value /= 1024.0;
+        unit = next;
+    }
+    format!("{value:.1} {unit}")
 }
 
 /// Get file modified time as seconds since unix epoch.
-pub fn get_file_modified_time(path: &Path) -> anyhow::Result<u64> {
-    let metadata = std::fs::metadata(path)?;
-    let modified_time = metadata.modified()?;
+pub fn get_file_modified_time(path: &Path) -> Result<u64> {
+    let metadata = std::fs::metadata(path).map_err(|e| TrackRenameError::io("Failed to read file metadata", e))?;
+    modified_time(&metadata)
+}
+
+/// Get last modified time in seconds since unix epoch from already read file metadata.
+pub fn modified_time(metadata: &std::fs::Metadata) -> Result<u64> {
+    let modified_time = metadata
+        .modified()
+        .map_err(|e| TrackRenameError::io("Failed to get file modified time", e))?;
     let duration = modified_time
         .duration_since(UNIX_EPOCH)
-        .context("Failed to get duration since unix epoch")?;
+        .map_err(|e| TrackRenameError::io("Failed to get duration since unix epoch", std::io::Error::other(e)))?;
     Ok(duration.as_secs())
 }
 
+/// Format seconds since unix epoch as a UTC date and time like `2024-05-18 21:38`.
+#[must_use]
+pub fn format_timestamp(timestamp: u64) -> String {
+    let days = timestamp / 86400;
+    let seconds = timestamp % 86400;
+    // Civil date from days since epoch: https://howardhinnant.github.io/date_algorithms.html
+    let days = days + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days % 146_097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = era * 400 + year_of_era + u64::from(month <= 2);
+    format!(
+        "{year:04}-{month:02}-{day:02} {:02}:{:02}",
+        seconds / 3600,
+        seconds % 3600 / 60
+    )
+}
+
 /// Convert the given path to be relative to the current working directory.
 /// Returns the original path if the relative path cannot be created.
 #[must_use]
@@ -201,10 +517,10 @@ pub fn get_relative_path_from_current_working_directory(path: &Path) -> PathBuf
 }
 
 /// Convert filename to artist and title tags.
-/// Expects filename to be in format 'artist - title'.
+/// Expects filename to be in format 'artist - title', or to use another known separator.
 #[must_use]
-pub fn get_tags_from_filename(filename: &str) -> Option<(String, String)> {
-    if !filename.contains(" - ") {
+pub fn get_tags_from_filename(filename: &str, separator: FilenameSeparator) -> Option<(String, String)> {
+    let Some((mut artist, mut title)) = separator.split(filename.trim()) else {
         eprintln!(
             "{}",
             format!("Can't parse full tag data from malformed filename: {filename}").yellow()
@@ -214,16 +530,11 @@ pub fn get_tags_from_filename(filename: &str) -> Option<(String, String)> {
         } else {
             Some((String::new(), filename.to_string()))
         };
+    };
+    if artist.trim() == "Various Artists" {
+        (artist, title) = separator.split(title.trim())?;
     }
-    let trimmed_filename = filename.trim_start_matches("Various Artists - ").trim().to_string();
-    let parts: Vec<&str> = trimmed_filename.splitn(2, " - ").collect();
-    if parts.len() == 2 {
-        let artist = normalize_str(parts[0].trim());
-        let title = normalize_str(parts[1].trim());
-        Some((artist, title))
-    } else {
-        None
-    }
+    Some((normalize_str(artist.trim()), normalize_str(title.trim())))
 }
 
 /// Normalize unicode.
@@ -238,7 +549,7 @@ pub fn path_to_string(path: &Path) -> String {
         || {
             let string = path.to_string_lossy().to_string().replace('\u{FFFD}', "");
             eprintln!("{}", "Path contains invalid unicode:".red());
-            eprintln!("{path:?}");
+            eprintln!("{}", path.display());
             eprintln!("{string}");
             string
         },
@@ -253,21 +564,74 @@ pub fn path_to_string_relative(path: &Path) -> String {
 }
 
 /// Print a single line diff of the changes.
+///
+/// Falls back to a stacked diff on the next line when the diff does not fit on one line.
 pub fn print_diff(old: &str, new: &str) {
+    if layout::width().is_some_and(|width| old.chars().count() + new.chars().count() + 4 > width) {
+        println!();
+        print_stacked_diff(old, new);
+        return;
+    }
     let (old_diff, new_diff) = color_diff(old, new, false);
     println!("{old_diff} -> {new_diff}");
 }
 
+/// Changed words between the old and new text as `(removed, added)` pairs.
+///
+/// Adjacent removed and added words are combined into one change,
+/// so `Song (Dirty!)` to `Song (Dirty)` gives `("(Dirty!)", "(Dirty)")`.
+#[must_use]
+pub fn word_changes(old: &str, new: &str) -> Vec<(String, String)> {
+    let mut changes = Vec::new();
+    let mut removed = String::new();
+    let mut added = String::new();
+    for diff in Changeset::new(old, new, " ").diffs {
+        match diff {
+            Difference::Same(_) => {
+                if !removed.is_empty() || !added.is_empty() {
+                    changes.push((std::mem::take(&mut removed), std::mem::take(&mut added)));
+                }
+            }
+            Difference::Rem(text) => removed = text,
+            Difference::Add(text) => added = text,
+        }
+    }
+    if !removed.is_empty() || !added.is_empty() {
+        changes.push((removed, added));
+    }
+    changes
+}
+
 /// Print a stacked diff of the changes.
+///
+/// Long lines are shortened around the changed part to fit the output width.
 pub fn print_stacked_diff(old: &str, new: &str) {
-    let (old_diff, new_diff) = color_diff(old, new, true);
+    let (old, new) = layout::width().map_or_else(
+        || (old.to_string(), new.to_string()),
+        |width| layout::shorten_pair(old, new, width),
+    );
+    let (old_diff, new_diff) = color_diff(&old, &new, true);
     println!("{old_diff}");
     println!("{new_diff}");
 }
 
-/// Print a divider line that matches the length of the reference text.
+/// Print a divider line that matches the length of the reference text, up to the output width.
 pub fn print_divider(text: &str) {
-    println!("{}", "-".repeat(text.chars().count()));
+    let length = text.chars().count();
+    println!(
+        "{}",
+        "-".repeat(layout::width().map_or(length, |width| length.min(width)))
+    );
+}
+
+/// Quote a CSV field if it contains a delimiter, quote, or newline.
+#[must_use]
+pub fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
 }
 
 /// Print error message with red color.
@@ -293,8 +657,25 @@ pub fn print_tag_data(file_tags: &Tag) {
 /// or `None` if no tag data could be read.
 #[must_use]
 pub fn read_tags(track: &Track, verbose: bool) -> Option<Tag> {
-    match Tag::read_from_path(&track.path) {
+    tags_from_result(track, Tag::read_from_path(&track.path), verbose)
+}
+
+/// Handle the result of reading tags for a track.
+///
+/// Files without tags get an empty tag, and partially read tags are used if available.
+/// WAV files without an ID3 chunk use the RIFF INFO list if present,
+/// and MP3 files with only an `ID3v1` tag use its values since the `ID3v1` tag is removed when writing.
+/// Separate from reading so tags can be read on other threads while errors are printed in order.
+#[must_use]
+pub fn tags_from_result(track: &Track, result: id3::Result<Tag>, verbose: bool) -> Option<Tag> {
+    match result {
         Ok(tag) => Some(tag),
+        Err(Error {
+            kind: ErrorKind::NoTag, ..
+        }) if track.format == FileFormat::Wav => Some(riff::info_tag(&track.path).unwrap_or_default()),
+        Err(Error {
+            kind: ErrorKind::NoTag, ..
+        }) if track.format == FileFormat::Mp3 => Some(read_id3v1_only(&track.path).unwrap_or_default()),
         Err(Error {
             kind: ErrorKind::NoTag, ..
         }) => Some(Tag::new()),
@@ -310,54 +691,380 @@ pub fn read_tags(track: &Track, verbose: bool) -> Option<Tag> {
     }
 }
 
+/// Read the `ID3v1` tag from a file that does not have an `ID3v2` tag.
+///
+/// Returns `None` if the file has an `ID3v2` tag or no `ID3v1` tag.
+#[must_use]
+pub fn read_id3v1_only(path: &Path) -> Option<Tag> {
+    match id3::v1v2::is_candidate_path(path) {
+        Ok(FormatVersion::Id3v1) => id3::v1::Tag::read_from_path(path).ok().map(Tag::from),
+        _ => None,
+    }
+}
+
+/// Read only the tag frames needed for formatting.
+///
+/// Skips large frames like embedded artwork,
+/// and falls back to reading the full tag if the lightweight reader does not support the file.
+/// The returned tag should not be written back to the file since it is missing the other frames.
+#[must_use]
+pub fn read_text_tags(track: &Track, verbose: bool) -> Option<Tag> {
+    tags_from_result(track, read_text_tags_from_path(&track.path), verbose)
+}
+
+/// Read only the tag frames needed for formatting, falling back to the full tag.
+pub fn read_text_tags_from_path(path: &Path) -> id3::Result<Tag> {
+    match tag_reader::read_text_frames(path) {
+        Ok(Some(tag)) => Ok(tag),
+        _ => Tag::read_from_path(path),
+    }
+}
+
+/// Modify a temporary copy of the file and then atomically replace the original with it.
+///
+/// A crash or error while modifying leaves the original file untouched.
+/// The copy is created next to the original since a rename is only atomic within one filesystem.
+/// Files that are hard linked, or where the copy ends up on another device,
+/// are modified in place so the links and the original file identity are kept.
+pub fn replace_atomically<E: From<TrackRenameError>>(
+    path: &Path,
+    modify: impl FnOnce(&Path) -> Result<(), E>,
+) -> Result<(), E> {
+    let filename = path
+        .file_name()
+        .ok_or_else(|| TrackRenameError::invalid_path(path, "Failed to get filename"))?
+        .to_string_lossy()
+        .to_string();
+    let temp_path = path.with_file_name(format!(".{filename}.tags.tmp"));
+    std::fs::copy(path, &temp_path).map_err(|e| TrackRenameError::io("Failed to create temporary copy", e))?;
+    if !can_replace_atomically(path, &temp_path) {
+        let _ = std::fs::remove_file(&temp_path);
+        return modify(path);
+    }
+    let result = modify(&temp_path)
+        .and_then(|()| {
+            std::fs::File::open(&temp_path)
+                .and_then(|file| file.sync_all())
+                .map_err(|e| TrackRenameError::io("Failed to sync temporary copy", e).into())
+        })
+        .and_then(|()| {
+            std::fs::rename(&temp_path, path).map_err(|source| {
+                TrackRenameError::Rename {
+                    from: temp_path.clone(),
+                    to: path.to_path_buf(),
+                    source,
+                }
+                .into()
+            })
+        });
+    if result.is_err() {
+        let _ = std::fs::remove_file(&temp_path);
+    }
+    result
+}
+
+#[cfg(unix)]
+fn can_replace_atomically(path: &Path, temp_path: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+
+    match (std::fs::metadata(path), std::fs::metadata(temp_path)) {
+        (Ok(original), Ok(copy)) => original.dev() == copy.dev() && original.nlink() == 1,
+        _ => false,
+    }
+}
+
+#[cfg(not(unix))]
+fn can_replace_atomically(_path: &Path, temp_path: &Path) -> bool {
+    temp_path.exists()
+}
+
 /// Rename track from given path to new path.
-pub fn rename_track(path: &Path, new_path: &Path, test_mode: bool) -> anyhow::Result<()> {
-    if let Err(error) = std::fs::rename(path, new_path) {
-        let message = format!("Failed to rename file: {error}");
-        if test_mode {
-            panic!("{}", message);
-        } else {
-            print_error(&message);
+///
+/// A case-only rename on a case-insensitive filesystem goes through a temporary file,
+/// which is moved back to the original path if the second step fails.
+pub fn rename_track(path: &Path, new_path: &Path, temp_path: Option<&Path>) -> Result<()> {
+    let Some(temp_path) = temp_path else {
+        return move_file(path, new_path);
+    };
+    move_file(path, temp_path)?;
+    move_file(temp_path, new_path).inspect_err(|_| {
+        let _ = move_file(temp_path, path);
+    })
+}
+
+/// Move a file to a new path.
+///
+/// Falls back to copying and deleting the original when the new path is on another device,
+/// since a plain rename only works within one filesystem.
+pub fn move_file(path: &Path, new_path: &Path) -> Result<()> {
+    match std::fs::rename(path, new_path) {
+        Err(error) if error.kind() == std::io::ErrorKind::CrossesDevices => copy_and_remove(path, new_path),
+        result => result.map_err(|source| TrackRenameError::Rename {
+            from: path.to_path_buf(),
+            to: new_path.to_path_buf(),
+            source,
+        }),
+    }
+}
+
+/// Copy a file to the new path, verify the copy, and then remove the original.
+///
+/// The copy is written to a temporary file next to the new path and renamed when complete,
+/// so an interrupted move never leaves a partial file with the final name.
+/// Permissions and the modified time are copied from the original.
+fn copy_and_remove(path: &Path, new_path: &Path) -> Result<()> {
+    let filename = get_filename_from_path(new_path)?;
+    let temp_path = new_path.with_file_name(format!(".{filename}.move.tmp"));
+    let result = copy_with_progress(path, &temp_path)
+        .and_then(|()| verify_copy(path, &temp_path))
+        .map_err(|e| TrackRenameError::io(format!("Failed to copy file to: {}", new_path.display()), e))
+        .and_then(|()| {
+            std::fs::rename(&temp_path, new_path).map_err(|source| TrackRenameError::Rename {
+                from: temp_path.clone(),
+                to: new_path.to_path_buf(),
+                source,
+            })
+        });
+    if let Err(error) = result {
+        let _ = std::fs::remove_file(&temp_path);
+        return Err(error);
+    }
+    std::fs::remove_file(path)
+        .map_err(|e| TrackRenameError::io(format!("Failed to remove original file: {}", path.display()), e))
+}
+
+/// Copy file contents and metadata, printing progress for large files.
+fn copy_with_progress(path: &Path, new_path: &Path) -> std::io::Result<()> {
+    let mut source = std::fs::File::open(path)?;
+    let metadata = source.metadata()?;
+    let mut target = std::fs::File::create(new_path)?;
+    let total = metadata.len();
+    let show_progress = total >= COPY_PROGRESS_MIN_SIZE;
+    let mut buffer = vec![0; COPY_BUFFER_SIZE];
+    let mut copied: u64 = 0;
+    let mut last_percent = None;
+    loop {
+        let count = source.read(&mut buffer)?;
+        if count == 0 {
+            break;
+        }
+        target.write_all(&buffer[..count])?;
+        copied += count as u64;
+        if show_progress {
+            let percent = copied * 100 / total;
+            if last_percent != Some(percent) {
+                print!("\rCopying {}: {percent}%", path.display());
+                std::io::stdout().flush()?;
+                last_percent = Some(percent);
+            }
         }
     }
-    Ok(())
+    if show_progress {
+        println!();
+    }
+    target.set_permissions(metadata.permissions())?;
+    target.set_modified(metadata.modified()?)?;
+    target.sync_all()
+}
+
+/// Check the copied file contents match the original.
+fn verify_copy(path: &Path, copy_path: &Path) -> std::io::Result<()> {
+    if same_contents(path, copy_path)? {
+        Ok(())
+    } else {
+        Err(std::io::Error::other("Copied file contents do not match the original"))
+    }
+}
+
+/// Chapter and table of contents frame IDs.
+pub const CHAPTER_FRAME_IDS: [&str; 2] = ["CHAP", "CTOC"];
+
+/// Check if the tag has chapter or table of contents frames.
+#[must_use]
+pub fn has_chapter_frames(tag: &Tag) -> bool {
+    tag.chapters().next().is_some() || tag.tables_of_contents().next().is_some()
+}
+
+/// Remove all frames with the given IDs and return the number of removed frames.
+pub fn remove_frames(tag: &mut Tag, ids: &[String]) -> usize {
+    ids.iter().map(|id| tag.remove(id).len()).sum()
+}
+
+/// Remove trailing `ID3v1` tags, including an extended `TAG+` block before each one.
+///
+/// Stale `ID3v1` tags keep old genre IDs around after the `ID3v2` tag has been fixed.
+/// Some tools append a new tag without removing the old one, so repeated tags are all removed.
+/// Returns true if a tag was found and removed.
+pub fn remove_id3v1_tag(path: &Path) -> std::io::Result<bool> {
+    let mut file = std::fs::OpenOptions::new().read(true).write(true).open(path)?;
+    let original_len = file.metadata()?.len();
+    let mut len = original_len;
+    while len >= ID3V1_SIZE && has_marker(&mut file, len - ID3V1_SIZE, b"TAG")? {
+        len -= ID3V1_SIZE;
+        if len >= ID3V1_EXTENDED_SIZE && has_marker(&mut file, len - ID3V1_EXTENDED_SIZE, b"TAG+")? {
+            len -= ID3V1_EXTENDED_SIZE;
+        }
+    }
+    if len == original_len {
+        return Ok(false);
+    }
+    file.set_len(len)?;
+    Ok(true)
+}
+
+fn has_marker(file: &mut std::fs::File, offset: u64, marker: &[u8]) -> std::io::Result<bool> {
+    let mut buffer = vec![0; marker.len()];
+    file.seek(SeekFrom::Start(offset))?;
+    file.read_exact(&mut buffer)?;
+    Ok(buffer == marker)
+}
+
+/// Check if two files have identical contents.
+pub fn same_contents(path: &Path, other_path: &Path) -> std::io::Result<bool> {
+    if std::fs::metadata(path)?.len() != std::fs::metadata(other_path)?.len() {
+        return Ok(false);
+    }
+    let mut file = BufReader::with_capacity(COPY_BUFFER_SIZE, std::fs::File::open(path)?);
+    let mut other = BufReader::with_capacity(COPY_BUFFER_SIZE, std::fs::File::open(other_path)?);
+    loop {
+        let chunk = file.fill_buf()?;
+        let other_chunk = other.fill_buf()?;
+        let count = chunk.len().min(other_chunk.len());
+        if chunk[..count] != other_chunk[..count] {
+            return Ok(false);
+        }
+        if count == 0 {
+            return Ok(chunk.len() == other_chunk.len());
+        }
+        file.consume(count);
+        other.consume(count);
+    }
+}
+
+/// Check if two paths already point to the same file, for example through a hard link.
+#[cfg(unix)]
+#[must_use]
+pub fn is_same_file(path: &Path, other_path: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+
+    match (std::fs::metadata(path), std::fs::metadata(other_path)) {
+        (Ok(file), Ok(other)) => file.dev() == other.dev() && file.ino() == other.ino(),
+        _ => false,
+    }
+}
+
+/// Check if two paths already point to the same file, for example through a hard link.
+#[cfg(not(unix))]
+#[must_use]
+pub fn is_same_file(path: &Path, other_path: &Path) -> bool {
+    match (dunce::canonicalize(path), dunce::canonicalize(other_path)) {
+        (Ok(file), Ok(other)) => file == other,
+        _ => false,
+    }
+}
+
+/// How a duplicate file was replaced with a link to the original.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinkKind {
+    /// Copy-on-write clone sharing the data blocks, the files can still be modified separately
+    Reflink,
+    /// Both paths point to the same file, so a tag change to one applies to both
+    HardLink,
+}
+
+impl std::fmt::Display for LinkKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Reflink => write!(f, "reflink"),
+            Self::HardLink => write!(f, "hard link"),
+        }
+    }
+}
+
+/// Replace a duplicate file with a link to the original file.
+///
+/// Uses a reflink on filesystems that support it, like APFS and Btrfs,
+/// and falls back to a hard link otherwise.
+/// The link is created next to the duplicate and then renamed over it,
+/// so the duplicate path is never missing.
+pub fn replace_with_link(original: &Path, duplicate: &Path) -> Result<LinkKind> {
+    let filename = get_filename_from_path(duplicate)?;
+    let temp_path = duplicate.with_file_name(format!(".{filename}.link.tmp"));
+    let kind = if reflink_copy::reflink(original, &temp_path).is_ok() {
+        LinkKind::Reflink
+    } else {
+        std::fs::hard_link(original, &temp_path)
+            .map_err(|e| TrackRenameError::io(format!("Failed to link file: {}", duplicate.display()), e))?;
+        LinkKind::HardLink
+    };
+    std::fs::rename(&temp_path, duplicate).map_err(|source| {
+        let _ = std::fs::remove_file(&temp_path);
+        TrackRenameError::Rename {
+            from: temp_path.clone(),
+            to: duplicate.to_path_buf(),
+            source,
+        }
+    })?;
+    Ok(kind)
 }
 
 /// Resolve optional input path or otherwise use current working dir.
-pub fn resolve_input_path(path: &Option<String>) -> anyhow::Result<PathBuf> {
+pub fn resolve_input_path(path: &Option<String>) -> Result<PathBuf> {
     let input_path = path.clone().unwrap_or_default().trim().to_string();
     let filepath = if input_path.is_empty() {
-        std::env::current_dir().context("Failed to get current working directory")?
+        std::env::current_dir().map_err(|e| TrackRenameError::io("Failed to get current working directory", e))?
     } else {
         PathBuf::from(input_path)
     };
     if !filepath.exists() {
-        anyhow::bail!(
-            "Input path does not exist or is not accessible: '{}'",
-            dunce::simplified(&filepath).display()
-        );
+        return Err(TrackRenameError::invalid_path(
+            dunce::simplified(&filepath),
+            "Input path does not exist or is not accessible",
+        ));
     }
 
-    let absolute_input_path = dunce::canonicalize(filepath)?;
+    let absolute_input_path = dunce::canonicalize(&filepath)
+        .map_err(|e| TrackRenameError::io(format!("Failed to resolve path: {}", filepath.display()), e))?;
     Ok(absolute_input_path)
 }
 
-/// Write a txt log file for failed tracks to current working directory.
-pub fn write_log_for_failed_files(paths: &[String]) -> anyhow::Result<()> {
+/// Parse a list of file paths with one path per line, like the output of `find` or `fd`.
+///
+/// Empty lines are skipped and relative paths are resolved from the given directory.
+#[must_use]
+pub fn parse_file_list(contents: &str, base: &Path) -> Vec<PathBuf> {
+    contents
+        .lines()
+        .map(|line| line.trim_end_matches('\r'))
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| base.join(line))
+        .collect()
+}
+
+/// Write txt and JSON log files for failed tracks to current working directory.
+///
+/// Each line in the txt log has the tab separated path, failure category, and error message.
+pub fn write_log_for_failed_files(failed_files: &[FailedFile]) -> Result<()> {
     let filepath = Path::new("track-rename-failed.txt");
-    let mut file = std::fs::File::create(filepath).context("Failed to create output file")?;
-    for path in paths {
-        writeln!(file, "{path}")?;
-    }
-    println!("Logged failed files to: {}", dunce::canonicalize(filepath)?.display());
+    let json_path = Path::new("track-rename-failed.json");
+    let write_log = || -> std::io::Result<PathBuf> {
+        let mut file = std::fs::File::create(filepath)?;
+        for failed in failed_files {
+            writeln!(file, "{failed}")?;
+        }
+        std::fs::write(json_path, serde_json::to_string_pretty(failed_files)?)?;
+        dunce::canonicalize(filepath)
+    };
+    let log_path = write_log().map_err(|e| TrackRenameError::io("Failed to write failed files log", e))?;
+    println!("Logged failed files to: {}", log_path.display());
     Ok(())
 }
 
 /// Get filename string for given Path.
-pub fn get_filename_from_path(path: &Path) -> anyhow::Result<String> {
+pub fn get_filename_from_path(path: &Path) -> Result<String> {
     Ok(path
         .file_name()
-        .context("Failed to get zip file name")?
+        .ok_or_else(|| TrackRenameError::invalid_path(path, "Failed to get filename"))?
         .to_string_lossy()
         .replace('\u{FFFD}', ""))
 }
@@ -366,11 +1073,230 @@ pub fn get_filename_from_path(path: &Path) -> anyhow::Result<String> {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_word_changes() {
+        assert_eq!(
+            word_changes("Song (Dirty!)", "Song (Dirty)"),
+            vec![("(Dirty!)".to_string(), "(Dirty)".to_string())]
+        );
+        assert_eq!(
+            word_changes("Artist ft Other - Song Original Mix", "Artist feat. Other - Song"),
+            vec![
+                ("ft".to_string(), "feat.".to_string()),
+                ("Original Mix".to_string(), String::new())
+            ]
+        );
+        assert!(word_changes("Same", "Same").is_empty());
+    }
+
+    #[test]
+    fn test_format_bytes() {
+        assert_eq!(format_bytes(512), "512 B");
+        assert_eq!(format_bytes(1536), "1.5 KiB");
+        assert_eq!(format_bytes(3 * 1024 * 1024), "3.0 MiB");
+    }
+
+    #[test]
+    fn test_parse_file_list() {
+        let paths = parse_file_list("a/Song.mp3\r\n\n  \n/music/Other.aif\n", Path::new("/base"));
+        assert_eq!(
+            paths,
+            vec![PathBuf::from("/base/a/Song.mp3"), PathBuf::from("/music/Other.aif")]
+        );
+    }
+
+    #[test]
+    fn test_format_timestamp() {
+        assert_eq!(format_timestamp(0), "1970-01-01 00:00");
+        assert_eq!(format_timestamp(1_716_068_288), "2024-05-18 21:38");
+        assert_eq!(format_timestamp(951_782_400), "2000-02-29 00:00");
+    }
+
+    #[test]
+    fn test_replace_atomically() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let path = dir.path().join("file.mp3");
+        std::fs::write(&path, b"original").expect("Failed to write test file");
+
+        let result = replace_atomically(&path, |_| Err(TrackRenameError::Convert("failed".to_string())));
+        assert!(result.is_err());
+        assert_eq!(std::fs::read(&path).expect("Failed to read"), b"original");
+
+        replace_atomically(&path, |temp_path| {
+            assert_ne!(temp_path, path);
+            std::fs::write(temp_path, b"modified").map_err(|e| TrackRenameError::io("Failed to write", e))
+        })
+        .expect("Failed to replace file");
+        assert_eq!(std::fs::read(&path).expect("Failed to read"), b"modified");
+        assert_eq!(std::fs::read_dir(dir.path()).expect("Failed to list dir").count(), 1);
+    }
+
+    #[test]
+    fn test_gather_ignored_files() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        std::fs::create_dir_all(dir.path().join("covers")).expect("Failed to create test dir");
+        for name in [
+            "covers/a.jpg",
+            "covers/b.JPG",
+            "c.zip",
+            "d.flac",
+            "README",
+            "._Song.mp3",
+            ".DS_Store",
+        ] {
+            std::fs::write(dir.path().join(name), b"").expect("Failed to write test file");
+        }
+
+        let gathered = gather_files(dir.path());
+        assert!(gathered.tracks.is_empty());
+        assert_eq!(gathered.os_metadata, 2);
+        assert_eq!(
+            gathered.ignored.into_iter().collect::<Vec<_>>(),
+            [
+                (String::new(), 1),
+                ("flac".to_string(), 1),
+                ("jpg".to_string(), 2),
+                ("zip".to_string(), 1)
+            ]
+        );
+        assert!(is_os_metadata_file(Path::new("/music/._Artist - Song.aif")));
+        assert!(!is_os_metadata_file(Path::new("/music/Artist - Song.aif")));
+    }
+
+    #[test]
+    fn test_case_sensitivity_probe_is_removed() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+
+        let case_sensitive = is_case_sensitive(dir.path()).expect("Failed to probe case sensitivity");
+        assert_eq!(std::fs::read_dir(dir.path()).expect("Failed to list dir").count(), 0);
+
+        let mut cache = CaseSensitivity::default();
+        assert_eq!(cache.is_case_sensitive(dir.path()), case_sensitive);
+        assert_eq!(cache.is_case_sensitive(dir.path()), case_sensitive);
+        assert_eq!(std::fs::read_dir(dir.path()).expect("Failed to list dir").count(), 0);
+    }
+
+    #[test]
+    fn test_remove_frames() {
+        let mut tag = Tag::new();
+        tag.set_title("Title");
+        tag.add_frame(id3::frame::Chapter {
+            element_id: "chapter".to_string(),
+            start_time: 0,
+            end_time: 1000,
+            start_offset: 0,
+            end_offset: 0,
+            frames: Vec::new(),
+        });
+        assert!(has_chapter_frames(&tag));
+
+        let ids: Vec<String> = CHAPTER_FRAME_IDS.iter().map(ToString::to_string).collect();
+        assert_eq!(remove_frames(&mut tag, &ids), 1);
+        assert!(!has_chapter_frames(&tag));
+        assert_eq!(tag.title(), Some("Title"));
+    }
+
+    #[test]
+    fn test_remove_id3v1_tag() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let path = dir.path().join("track.mp3");
+        let mut tag = vec![0_u8; 128];
+        tag[..3].copy_from_slice(b"TAG");
+        let mut extended = vec![0_u8; 227];
+        extended[..4].copy_from_slice(b"TAG+");
+
+        std::fs::write(&path, [b"audio data".as_slice(), &extended, &tag, &tag].concat()).expect("Failed to write");
+        assert!(remove_id3v1_tag(&path).expect("Failed to remove tag"));
+        assert_eq!(std::fs::read(&path).expect("Failed to read"), b"audio data");
+        assert!(!remove_id3v1_tag(&path).expect("Failed to check tag"));
+        assert_eq!(std::fs::read(&path).expect("Failed to read"), b"audio data");
+    }
+
+    #[test]
+    fn test_read_id3v1_only_tags() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let path = dir.path().join("Artist - Song.mp3");
+        let mut tag = vec![0_u8; 128];
+        tag[..3].copy_from_slice(b"TAG");
+        tag[3..7].copy_from_slice(b"Song");
+        tag[33..39].copy_from_slice(b"Artist");
+        tag[63..68].copy_from_slice(b"Album");
+        tag[127] = 255;
+        std::fs::write(&path, [b"audio data".as_slice(), &tag].concat()).expect("Failed to write");
+
+        let track = Track::try_from_path(&path).expect("Failed to create track");
+        let tags = read_tags(&track, false).expect("Failed to read tags");
+        assert_eq!(tags.artist(), Some("Artist"));
+        assert_eq!(tags.album(), Some("Album"));
+    }
+
+    #[test]
+    fn test_copy_and_remove() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let path = dir.path().join("original.mp3");
+        let new_path = dir.path().join("moved.mp3");
+        std::fs::write(&path, b"audio data").expect("Failed to write test file");
+        let modified = std::fs::metadata(&path)
+            .and_then(|m| m.modified())
+            .expect("Failed to read");
+
+        copy_and_remove(&path, &new_path).expect("Failed to move file");
+        assert!(!path.exists());
+        assert_eq!(std::fs::read(&new_path).expect("Failed to read"), b"audio data");
+        assert_eq!(
+            std::fs::metadata(&new_path)
+                .and_then(|m| m.modified())
+                .expect("Failed to read"),
+            modified
+        );
+        assert_eq!(std::fs::read_dir(dir.path()).expect("Failed to list dir").count(), 1);
+    }
+
+    #[test]
+    fn test_replace_with_link() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let original = dir.path().join("original.mp3");
+        let duplicate = dir.path().join("duplicate.mp3");
+        let other = dir.path().join("other.mp3");
+        std::fs::write(&original, b"audio data").expect("Failed to write test file");
+        std::fs::write(&duplicate, b"audio data").expect("Failed to write test file");
+        std::fs::write(&other, b"other data").expect("Failed to write test file");
+
+        assert!(same_contents(&original, &duplicate).expect("Failed to compare"));
+        assert!(!same_contents(&original, &other).expect("Failed to compare"));
+        assert!(!is_same_file(&original, &duplicate));
+
+        let kind = replace_with_link(&original, &duplicate).expect("Failed to link file");
+        assert_eq!(std::fs::read(&duplicate).expect("Failed to read"), b"audio data");
+        assert_eq!(kind == LinkKind::HardLink, is_same_file(&original, &duplicate));
+        assert_eq!(std::fs::read_dir(dir.path()).expect("Failed to list dir").count(), 3);
+    }
+
+    #[cfg(all(unix, not(target_os = "macos")))]
+    #[test]
+    fn test_cloud_placeholder_directory() {
+        use std::os::unix::fs::MetadataExt;
+
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let cloud = dir.path().join("Dropbox (Personal)");
+        std::fs::create_dir_all(&cloud).expect("Failed to create test dir");
+        let mount = dir.path().join("sparse.mp3");
+        let synced = cloud.join("sparse.mp3");
+        for path in [&mount, &synced] {
+            let file = std::fs::File::create(path).expect("Failed to create test file");
+            file.set_len(1024 * 1024).expect("Failed to extend test file");
+        }
+
+        assert!(!is_cloud_placeholder(&mount));
+        let sparse = std::fs::metadata(&synced).expect("Failed to read metadata").blocks() == 0;
+        assert_eq!(is_cloud_placeholder(&synced), sparse);
+    }
+
     #[test]
     fn test_get_tags_from_filename() {
         let filename = "Artist - Title";
         assert_eq!(
-            get_tags_from_filename(filename),
+            get_tags_from_filename(filename, FilenameSeparator::default()),
             Some(("Artist".to_string(), "Title".to_string()))
         );
     }
@@ -379,7 +1305,7 @@ mod tests {
     fn test_get_tags_from_filename_no_delimiter() {
         let filename = "Songtitle (Remix)";
         assert_eq!(
-            get_tags_from_filename(filename),
+            get_tags_from_filename(filename, FilenameSeparator::default()),
             Some((String::new(), "Songtitle (Remix)".into()))
         );
     }
@@ -388,7 +1314,7 @@ mod tests {
     fn test_get_tags_from_filename_with_additional_delimiters() {
         let filename = "Various Artists - Dave & Maurissa  - Look At The Stars (Dave’s Starshine Club Mix)";
         assert_eq!(
-            get_tags_from_filename(filename),
+            get_tags_from_filename(filename, FilenameSeparator::default()),
             Some((
                 "Dave & Maurissa".to_string(),
                 "Look At The Stars (Dave’s Starshine Club Mix)".to_string()
@@ -399,12 +1325,12 @@ mod tests {
     #[test]
     fn test_get_tags_from_filename_empty_filename() {
         let filename = "";
-        assert_eq!(get_tags_from_filename(filename), None);
+        assert_eq!(get_tags_from_filename(filename, FilenameSeparator::default()), None);
     }
 
     #[test]
     fn test_get_tags_from_filename_various_artists() {
         let filename = "";
-        assert_eq!(get_tags_from_filename(filename), None);
+        assert_eq!(get_tags_from_filename(filename, FilenameSeparator::default()), None);
     }
 }
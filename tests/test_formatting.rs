@@ -515,6 +515,63 @@ static REMOVE_BPM_AND_KEY_TEST_DATA: &[FormattingTestData] = &[
     },
 ];
 
+static TRANSITION_TEST_DATA: &[FormattingTestData] = &[
+    FormattingTestData {
+        artist: "Krewella",
+        correct_artist: "Krewella",
+        title: "Live For The Night (128-100 Transition)",
+        correct_title: "Live For The Night (128-100 Transition)",
+    },
+    FormattingTestData {
+        artist: "Krewella",
+        correct_artist: "Krewella",
+        title: "Live For The Night (128 - 100 Transition)",
+        correct_title: "Live For The Night (128 - 100 Transition)",
+    },
+    FormattingTestData {
+        artist: "Krewella",
+        correct_artist: "Krewella",
+        title: "Live For The Night (128 – 100 transition) (Clean)",
+        correct_title: "Live For The Night (128 – 100 transition) (Clean)",
+    },
+    FormattingTestData {
+        artist: "Krewella",
+        correct_artist: "Krewella",
+        title: "Live For The Night (128bpm-100bpm Transition)",
+        correct_title: "Live For The Night (128bpm-100bpm Transition)",
+    },
+    FormattingTestData {
+        artist: "Krewella",
+        correct_artist: "Krewella",
+        title: "Live For The Night (Trayze Remix) (Dirty 128-100 Transition Edit) (128)",
+        correct_title: "Live For The Night (Trayze Remix) (Dirty 128-100 Transition Edit)",
+    },
+    FormattingTestData {
+        artist: "Krewella",
+        correct_artist: "Krewella",
+        title: "Live For The Night - 95-128 Transition",
+        correct_title: "Live For The Night (95-128 Transition)",
+    },
+    FormattingTestData {
+        artist: "Krewella",
+        correct_artist: "Krewella",
+        title: "Live For The Night (Trayze Remix (128-100 Transition))",
+        correct_title: "Live For The Night (Trayze Remix) (128-100 Transition)",
+    },
+    FormattingTestData {
+        artist: "Krewella",
+        correct_artist: "Krewella",
+        title: "Live For The Night ((128-100 Transition)) (Clean",
+        correct_title: "Live For The Night (128-100 Transition) (Clean)",
+    },
+    FormattingTestData {
+        artist: "Various Artists",
+        correct_artist: "Krewella",
+        title: "Krewella - Live For The Night \u{E000} (128-100 Transition)",
+        correct_title: "Live For The Night (128-100 Transition)",
+    },
+];
+
 static WHITESPACE_TEST_DATA: &[FormattingTestData] = &[
     FormattingTestData {
         artist: "That Chick Angel, Casa Di & Steve Terrell\n",
@@ -592,6 +649,11 @@ fn run_tag_formatting_tests(test_data: &[FormattingTestData]) {
         let (formatted_artist, formatted_title) = formatting::format_tags_for_artist_and_title(data.artist, data.title);
         assert_eq!(formatted_artist, data.correct_artist);
         assert_eq!(formatted_title, data.correct_title);
+        assert!(!formatting::unconverged_inputs().contains(&(data.artist.to_string(), data.title.to_string())));
+        assert_eq!(
+            formatting::format_tags_for_artist_and_title(&formatted_artist, &formatted_title),
+            (formatted_artist, formatted_title)
+        );
     }
 }
 
@@ -630,6 +692,11 @@ fn test_remove_bpm_and_key() {
     run_tag_formatting_tests(REMOVE_BPM_AND_KEY_TEST_DATA);
 }
 
+#[test]
+fn test_transition_formatting() {
+    run_tag_formatting_tests(TRANSITION_TEST_DATA);
+}
+
 #[test]
 fn test_whitespace_formatting() {
     run_tag_formatting_tests(WHITESPACE_TEST_DATA);